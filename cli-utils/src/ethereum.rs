@@ -6,6 +6,18 @@ pub struct EthereumConnectionArgs {
     #[arg(long = "ethereum-endpoint", env = "ETH_RPC")]
     pub ethereum_endpoint: String,
 
+    /// Ethereum endpoints to fall over to when the main one fails
+    #[arg(
+        long = "ethereum-fallback-endpoint",
+        env = "ETH_RPC_FALLBACKS",
+        value_delimiter = ','
+    )]
+    pub fallback_endpoints: Vec<String>,
+
+    /// Number of endpoints that must agree on merkle roots read from Ethereum
+    #[arg(long = "eth-read-quorum", env = "ETH_RPC_READ_QUORUM")]
+    pub read_quorum: Option<usize>,
+
     /// Number of retries for the ethereum endpoint
     #[arg(long = "eth-max-retries", env = "ETH_RPC_MAX_RETRIES")]
     pub max_retries: Option<u32>,
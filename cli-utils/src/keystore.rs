@@ -0,0 +1,355 @@
+//! Encrypted JSON keystores in the [Web3 Secret Storage] v3 format.
+//!
+//! Ethereum keystores hold a 32-byte private key and are compatible with geth, foundry
+//! and other wallets. Gear keystores use the same envelope with `"scheme": "sr25519"`
+//! and hold a Substrate URI (mnemonic with an optional derivation path).
+//!
+//! [Web3 Secret Storage]: https://ethereum.org/en/developers/docs/data-structures-and-encoding/web3-secret-storage/
+
+use crate::signer::KeyKind;
+use aes::Aes128;
+use anyhow::{anyhow, Context, Result};
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::Hmac;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use tiny_keccak::{Hasher, Keccak};
+use zeroize::Zeroizing;
+
+const VERSION: u32 = 3;
+const CIPHER: &str = "aes-128-ctr";
+const GEAR_SCHEME: &str = "sr25519";
+const DERIVED_KEY_LEN: usize = 32;
+/// Limits of the key derivation cost accepted from keystores, so that a crafted keystore can't
+/// exhaust memory or hang startup. Keystores made by geth with default parameters are within
+/// them.
+const MAX_SCRYPT_LOG_N: u32 = 18;
+const MAX_SCRYPT_R: u32 = 8;
+const MAX_SCRYPT_P: u32 = 16;
+const MAX_PBKDF2_ROUNDS: u32 = 1 << 22;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+/// Cost of the scrypt key derivation used for new keystores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScryptCost {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl ScryptCost {
+    /// Parameters geth uses by default.
+    pub const STANDARD: Self = Self {
+        log_n: 18,
+        r: 8,
+        p: 1,
+    };
+    /// Parameters geth uses with `--lightkdf`.
+    pub const LIGHT: Self = Self {
+        log_n: 12,
+        r: 8,
+        p: 6,
+    };
+}
+
+#[derive(Serialize, Deserialize)]
+struct Keystore {
+    version: u32,
+    #[serde(default)]
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    /// Set for Gear keystores only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scheme: Option<String>,
+    #[serde(alias = "Crypto")]
+    crypto: Crypto,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Crypto {
+    cipher: String,
+    cipherparams: CipherParams,
+    ciphertext: String,
+    kdf: String,
+    kdfparams: KdfParams,
+    mac: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CipherParams {
+    iv: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum KdfParams {
+    Scrypt {
+        dklen: usize,
+        n: u64,
+        r: u32,
+        p: u32,
+        salt: String,
+    },
+    Pbkdf2 {
+        dklen: usize,
+        c: u32,
+        prf: String,
+        salt: String,
+    },
+}
+
+impl KdfParams {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Scrypt { .. } => "scrypt",
+            Self::Pbkdf2 { .. } => "pbkdf2",
+        }
+    }
+
+    fn derive(&self, password: &[u8]) -> Result<Zeroizing<[u8; DERIVED_KEY_LEN]>> {
+        let mut key = Zeroizing::new([0u8; DERIVED_KEY_LEN]);
+        match self {
+            Self::Scrypt {
+                dklen,
+                n,
+                r,
+                p,
+                salt,
+            } => {
+                if *dklen != DERIVED_KEY_LEN || !n.is_power_of_two() {
+                    return Err(anyhow!("unsupported scrypt parameters"));
+                }
+                if n.trailing_zeros() > MAX_SCRYPT_LOG_N || *r > MAX_SCRYPT_R || *p > MAX_SCRYPT_P {
+                    return Err(anyhow!(
+                        "scrypt parameters n={n}, r={r}, p={p} exceed the limits n={}, r={}, p={}",
+                        1u64 << MAX_SCRYPT_LOG_N,
+                        MAX_SCRYPT_R,
+                        MAX_SCRYPT_P
+                    ));
+                }
+                let params = scrypt::Params::new(n.trailing_zeros() as u8, *r, *p, *dklen)
+                    .map_err(|e| anyhow!("invalid scrypt parameters: {e}"))?;
+                scrypt::scrypt(password, &decode(salt, "salt")?, &params, key.as_mut())
+                    .map_err(|e| anyhow!("scrypt failed: {e}"))?;
+            }
+            Self::Pbkdf2 {
+                dklen,
+                c,
+                prf,
+                salt,
+            } => {
+                if *dklen != DERIVED_KEY_LEN || prf != "hmac-sha256" {
+                    return Err(anyhow!("unsupported pbkdf2 parameters"));
+                }
+                if *c > MAX_PBKDF2_ROUNDS {
+                    return Err(anyhow!(
+                        "pbkdf2 iteration count {c} exceeds the limit {MAX_PBKDF2_ROUNDS}"
+                    ));
+                }
+                pbkdf2::pbkdf2::<Hmac<Sha256>>(password, &decode(salt, "salt")?, *c, key.as_mut())
+                    .map_err(|e| anyhow!("pbkdf2 failed: {e}"))?;
+            }
+        }
+
+        Ok(key)
+    }
+}
+
+/// Encrypts `secret` with `password` and returns the keystore JSON.
+///
+/// `secret` is a hex private key for [`KeyKind::Ethereum`] and a Substrate URI for
+/// [`KeyKind::Gear`].
+pub fn encrypt(kind: KeyKind, secret: &str, password: &str, cost: ScryptCost) -> Result<String> {
+    let mut plaintext = Zeroizing::new(match kind {
+        KeyKind::Ethereum => decode_private_key(secret)?.to_vec(),
+        KeyKind::Gear => {
+            let suri = secret.trim();
+            if suri.is_empty() {
+                return Err(anyhow!("Gear SURI is empty"));
+            }
+            suri.as_bytes().to_vec()
+        }
+    });
+
+    let mut salt = [0u8; 32];
+    let mut iv = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut iv);
+
+    let kdfparams = KdfParams::Scrypt {
+        dklen: DERIVED_KEY_LEN,
+        n: 1 << cost.log_n,
+        r: cost.r,
+        p: cost.p,
+        salt: hex::encode(salt),
+    };
+    let key = kdfparams.derive(password.as_bytes())?;
+
+    // Encrypted in place: the buffer holds the ciphertext from here on.
+    cipher(&key, &iv)?.apply_keystream(&mut plaintext);
+    let ciphertext = plaintext;
+
+    let keystore = Keystore {
+        version: VERSION,
+        id: uuid::Uuid::new_v4().to_string(),
+        address: None,
+        scheme: match kind {
+            KeyKind::Ethereum => None,
+            KeyKind::Gear => Some(GEAR_SCHEME.to_string()),
+        },
+        crypto: Crypto {
+            cipher: CIPHER.to_string(),
+            cipherparams: CipherParams {
+                iv: hex::encode(iv),
+            },
+            mac: hex::encode(mac(&key, &ciphertext)),
+            ciphertext: hex::encode(&*ciphertext),
+            kdf: kdfparams.name().to_string(),
+            kdfparams,
+        },
+    };
+
+    Ok(serde_json::to_string_pretty(&keystore)?)
+}
+
+/// Decrypts the keystore JSON. Ethereum keys are returned as `0x`-prefixed hex.
+pub fn decrypt(kind: KeyKind, contents: &str, password: &str) -> Result<Zeroizing<String>> {
+    let keystore: Keystore = serde_json::from_str(contents).context("invalid keystore")?;
+    let crypto = keystore.crypto;
+    if keystore.version != VERSION {
+        return Err(anyhow!("unsupported keystore version {}", keystore.version));
+    }
+    if crypto.cipher != CIPHER {
+        return Err(anyhow!("unsupported keystore cipher {}", crypto.cipher));
+    }
+    if crypto.kdf != crypto.kdfparams.name() {
+        return Err(anyhow!(
+            "keystore kdf {} doesn't match its parameters",
+            crypto.kdf
+        ));
+    }
+    let iv = decode(&crypto.cipherparams.iv, "iv")?;
+    match (kind, keystore.scheme.as_deref()) {
+        (KeyKind::Ethereum, None) | (KeyKind::Gear, Some(GEAR_SCHEME)) => {}
+        _ => return Err(anyhow!("keystore doesn't hold a {kind} key")),
+    }
+
+    let key = crypto.kdfparams.derive(password.as_bytes())?;
+    let ciphertext = decode(&crypto.ciphertext, "ciphertext")?;
+    let expected_mac = decode(&crypto.mac, "mac")?;
+    if !bool::from(mac(&key, &ciphertext).as_slice().ct_eq(&expected_mac)) {
+        return Err(anyhow!("wrong keystore password"));
+    }
+
+    let mut plaintext = Zeroizing::new(ciphertext);
+    cipher(&key, &iv)?.apply_keystream(&mut plaintext);
+
+    match kind {
+        KeyKind::Ethereum => {
+            if plaintext.len() != 32 {
+                return Err(anyhow!("keystore private key must be 32 bytes"));
+            }
+            Ok(Zeroizing::new(format!("0x{}", hex::encode(&*plaintext))))
+        }
+        KeyKind::Gear => String::from_utf8(plaintext.to_vec())
+            .map(Zeroizing::new)
+            .map_err(|_| anyhow!("keystore SURI isn't valid UTF-8")),
+    }
+}
+
+fn cipher(key: &[u8; DERIVED_KEY_LEN], iv: &[u8]) -> Result<Aes128Ctr> {
+    Aes128Ctr::new_from_slices(&key[..16], iv).map_err(|_| anyhow!("keystore iv must be 16 bytes"))
+}
+
+fn decode(value: &str, field: &str) -> Result<Vec<u8>> {
+    hex::decode(value).with_context(|| format!("keystore {field} is invalid hex"))
+}
+
+fn decode_private_key(value: &str) -> Result<Zeroizing<[u8; 32]>> {
+    let value = value.trim();
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    let mut key = Zeroizing::new([0u8; 32]);
+    hex::decode_to_slice(value, key.as_mut())
+        .map_err(|_| anyhow!("Ethereum private key must be 32 hex-encoded bytes"))?;
+
+    Ok(key)
+}
+
+fn mac(key: &[u8; DERIVED_KEY_LEN], ciphertext: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(&key[16..]);
+    hasher.update(ciphertext);
+    let mut mac = [0u8; 32];
+    hasher.finalize(&mut mac);
+
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETH_KEY: &str = "0x7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+
+    #[test]
+    fn decrypts_spec_test_vector() {
+        let keystore = r#"{
+            "crypto": {
+                "cipher": "aes-128-ctr",
+                "cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+                "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+                "kdf": "pbkdf2",
+                "kdfparams": {
+                    "c": 262144,
+                    "dklen": 32,
+                    "prf": "hmac-sha256",
+                    "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+                },
+                "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+            },
+            "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+            "version": 3
+        }"#;
+
+        let key = decrypt(KeyKind::Ethereum, keystore, "testpassword").unwrap();
+        assert_eq!(key.as_str(), ETH_KEY);
+        assert!(decrypt(KeyKind::Gear, keystore, "testpassword").is_err());
+    }
+
+    #[test]
+    fn round_trips_keys_and_rejects_wrong_password() {
+        let keystore = encrypt(KeyKind::Ethereum, ETH_KEY, "secret", ScryptCost::LIGHT).unwrap();
+        let key = decrypt(KeyKind::Ethereum, &keystore, "secret").unwrap();
+        assert_eq!(key.as_str(), ETH_KEY);
+
+        let err = decrypt(KeyKind::Ethereum, &keystore, "wrong").unwrap_err();
+        assert!(err.to_string().contains("wrong keystore password"));
+
+        let keystore = encrypt(KeyKind::Gear, "//Alice", "secret", ScryptCost::LIGHT).unwrap();
+        assert!(keystore.contains(r#""scheme": "sr25519""#));
+        let suri = decrypt(KeyKind::Gear, &keystore, "secret").unwrap();
+        assert_eq!(suri.as_str(), "//Alice");
+    }
+
+    #[test]
+    fn rejects_excessive_kdf_cost() {
+        let keystore = encrypt(KeyKind::Ethereum, ETH_KEY, "secret", ScryptCost::LIGHT).unwrap();
+        let expensive = keystore.replace(r#""n": 4096"#, r#""n": 1073741824"#);
+        assert_ne!(expensive, keystore);
+        let err = decrypt(KeyKind::Ethereum, &expensive, "secret").unwrap_err();
+        assert!(err.to_string().contains("exceed the limits"), "{err}");
+
+        let pbkdf2 = KdfParams::Pbkdf2 {
+            dklen: DERIVED_KEY_LEN,
+            c: u32::MAX,
+            prf: "hmac-sha256".to_string(),
+            salt: String::new(),
+        };
+        let err = pbkdf2.derive(b"secret").unwrap_err();
+        assert!(err.to_string().contains("exceeds the limit"), "{err}");
+    }
+}
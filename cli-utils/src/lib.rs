@@ -1,12 +1,15 @@
 pub mod common;
 pub mod ethereum;
 pub mod gear;
+pub mod keystore;
 pub mod prometheus;
+pub mod signer;
 
 pub use common::*;
 pub use ethereum::*;
 pub use gear::*;
 pub use prometheus::*;
+pub use signer::*;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,382 @@
+//! Signer keys which don't have to be passed in process arguments.
+//!
+//! Every argument or config field holding a key accepts a reference instead of the key:
+//! - `keystore:<path>` - encrypted JSON keystore, see [`crate::keystore`]. Its password
+//!   is read from `--keystore-password-file`.
+//! - `provider:<name>` - key named `name` is requested from the `--key-provider`.
+//!
+//! Any other value is used as the key itself. Such keys are visible in process listings
+//! when passed as arguments, so a warning is logged for them.
+//!
+//! References keep keys out of arguments, config files and process listings only. Keys are
+//! resolved on startup and payloads are signed in the relayer, so the keys are held in its
+//! memory.
+
+use crate::keystore;
+use anyhow::{anyhow, Context, Result};
+use clap::{Args, ValueEnum};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+};
+use zeroize::Zeroizing;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum KeyKind {
+    /// Ethereum private key, hex-encoded.
+    Ethereum,
+    /// Gear Substrate URI.
+    Gear,
+}
+
+impl fmt::Display for KeyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ethereum => write!(f, "ethereum"),
+            Self::Gear => write!(f, "gear"),
+        }
+    }
+}
+
+/// Where the key referenced by an argument comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySource<'a> {
+    Plain(&'a str),
+    Keystore(&'a Path),
+    Provider(&'a str),
+}
+
+impl<'a> KeySource<'a> {
+    pub fn parse(value: &'a str) -> Self {
+        if let Some(path) = value.strip_prefix("keystore:") {
+            Self::Keystore(Path::new(path))
+        } else if let Some(name) = value.strip_prefix("provider:") {
+            Self::Provider(name)
+        } else {
+            Self::Plain(value)
+        }
+    }
+
+    /// Whether the value refers to a key stored elsewhere.
+    pub fn is_reference(value: &str) -> bool {
+        !matches!(KeySource::parse(value), KeySource::Plain(_))
+    }
+}
+
+/// Source of keys kept outside of the relayer, e.g. a secret manager. It hands out the keys
+/// themselves rather than signing with them, see the [module docs](self).
+pub trait KeyProvider: Send + Sync {
+    fn key(&self, kind: KeyKind, name: &str) -> Result<Zeroizing<String>>;
+}
+
+/// Runs `<program> <kind> <name>` and reads the key from its stdout.
+pub struct CommandKeyProvider {
+    program: PathBuf,
+}
+
+impl CommandKeyProvider {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+        }
+    }
+}
+
+impl KeyProvider for CommandKeyProvider {
+    fn key(&self, kind: KeyKind, name: &str) -> Result<Zeroizing<String>> {
+        let output = Command::new(&self.program)
+            .arg(kind.to_string())
+            .arg(name)
+            .output()
+            .with_context(|| format!("failed to run key provider {}", self.program.display()))?;
+        let stdout = Zeroizing::new(output.stdout);
+        if !output.status.success() {
+            return Err(anyhow!(
+                "key provider {} exited with {}: {}",
+                self.program.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let key = std::str::from_utf8(&stdout)
+            .map_err(|_| {
+                anyhow!(
+                    "key provider {} returned invalid UTF-8",
+                    self.program.display()
+                )
+            })?
+            .trim();
+        if key.is_empty() {
+            return Err(anyhow!(
+                "key provider {} returned no key for {name}",
+                self.program.display()
+            ));
+        }
+
+        Ok(Zeroizing::new(key.to_string()))
+    }
+}
+
+/// Reads keys from a plain TOML file with `[ethereum]` and `[gear]` tables mapping names
+/// to keys. Meant for tests and local networks only.
+pub struct MockKeyProvider {
+    keys: MockKeys,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MockKeys {
+    #[serde(default)]
+    ethereum: HashMap<String, String>,
+    #[serde(default)]
+    gear: HashMap<String, String>,
+}
+
+impl MockKeyProvider {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents =
+            Zeroizing::new(fs::read_to_string(path).with_context(|| {
+                format!("failed to read mock provider keys {}", path.display())
+            })?);
+        let keys = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse mock provider keys {}", path.display()))?;
+
+        Ok(Self { keys })
+    }
+}
+
+impl KeyProvider for MockKeyProvider {
+    fn key(&self, kind: KeyKind, name: &str) -> Result<Zeroizing<String>> {
+        let keys = match kind {
+            KeyKind::Ethereum => &self.keys.ethereum,
+            KeyKind::Gear => &self.keys.gear,
+        };
+
+        keys.get(name)
+            .map(|key| Zeroizing::new(key.clone()))
+            .ok_or_else(|| anyhow!("mock key provider has no {kind} key {name}"))
+    }
+}
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct SignerArgs {
+    /// File with the password of keystores referenced as `keystore:<path>`
+    #[arg(
+        long = "keystore-password-file",
+        env = "KEYSTORE_PASSWORD_FILE",
+        global = true
+    )]
+    pub keystore_password_file: Option<PathBuf>,
+
+    /// Provider of keys referenced as `provider:<name>`: `command:<program>` runs the
+    /// program as `<program> <ethereum|gear> <name>`, `mock:<file>` reads keys from a
+    /// TOML file
+    #[arg(long = "key-provider", env = "KEY_PROVIDER", global = true)]
+    pub key_provider: Option<String>,
+}
+
+/// Turns key arguments into keys, see the [module docs](self).
+#[derive(Clone, Default)]
+pub struct KeyResolver {
+    password_file: Option<PathBuf>,
+    provider: Option<Arc<dyn KeyProvider>>,
+}
+
+impl KeyResolver {
+    pub fn from_args(args: &SignerArgs) -> Result<Self> {
+        let provider = args
+            .key_provider
+            .as_deref()
+            .map(parse_key_provider)
+            .transpose()?;
+
+        Ok(Self {
+            password_file: args.keystore_password_file.clone(),
+            provider,
+        })
+    }
+
+    pub fn with_provider(mut self, provider: Arc<dyn KeyProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Password of the keystores. Trailing newline of the file is ignored.
+    pub fn password(&self) -> Result<Zeroizing<String>> {
+        let path = self
+            .password_file
+            .as_ref()
+            .ok_or_else(|| anyhow!("--keystore-password-file is required to use keystores"))?;
+        let mut password = Zeroizing::new(
+            fs::read_to_string(path)
+                .with_context(|| format!("failed to read password file {}", path.display()))?,
+        );
+        let len = password.trim_end_matches(['\r', '\n']).len();
+        password.truncate(len);
+
+        Ok(password)
+    }
+
+    pub fn resolve(&self, kind: KeyKind, value: &str) -> Result<Zeroizing<String>> {
+        match KeySource::parse(value) {
+            KeySource::Plain(key) => Ok(Zeroizing::new(key.to_string())),
+            KeySource::Keystore(path) => {
+                let contents = fs::read_to_string(path)
+                    .with_context(|| format!("failed to read keystore {}", path.display()))?;
+                keystore::decrypt(kind, &contents, &self.password()?)
+                    .with_context(|| format!("failed to decrypt keystore {}", path.display()))
+            }
+            KeySource::Provider(name) => self
+                .provider
+                .as_ref()
+                .ok_or_else(|| anyhow!("--key-provider is required to use provider:{name}"))?
+                .key(kind, name)
+                .with_context(|| format!("failed to get {kind} key {name} from key provider")),
+        }
+    }
+
+    /// Replaces the reference in `value` with the key it points to.
+    pub fn resolve_in_place(&self, kind: KeyKind, value: &mut String) -> Result<()> {
+        if KeySource::is_reference(value) {
+            *value = self.resolve(kind, value)?.to_string();
+        } else if is_in_arguments(value, std::env::args()) {
+            log::warn!(
+                "Plain {kind} key is passed in process arguments and is visible to other \
+                 users of the host. Use keystore:<path> or provider:<name> instead"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `value` is passed as an argument itself or as `--name=<value>`.
+fn is_in_arguments(value: &str, mut args: impl Iterator<Item = String>) -> bool {
+    !value.is_empty()
+        && args.any(|arg| {
+            arg == value
+                || arg
+                    .split_once('=')
+                    .is_some_and(|(name, arg)| name.starts_with('-') && arg == value)
+        })
+}
+
+fn parse_key_provider(spec: &str) -> Result<Arc<dyn KeyProvider>> {
+    if let Some(program) = spec.strip_prefix("command:") {
+        Ok(Arc::new(CommandKeyProvider::new(program)))
+    } else if let Some(path) = spec.strip_prefix("mock:") {
+        Ok(Arc::new(MockKeyProvider::from_path(path)?))
+    } else {
+        Err(anyhow!(
+            "invalid key provider {spec}. Expected command:<program> or mock:<file>"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keystore::ScryptCost;
+
+    const ETH_KEY: &str = "0x2222222222222222222222222222222222222222222222222222222222222222";
+
+    #[test]
+    fn resolves_keys_from_all_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let keys_path = dir.path().join("keys.toml");
+        fs::write(
+            &keys_path,
+            format!("[ethereum]\nfee-payer = \"{ETH_KEY}\"\n\n[gear]\nalice = \"//Alice\"\n"),
+        )
+        .unwrap();
+        let password_path = dir.path().join("password");
+        fs::write(&password_path, "secret\n").unwrap();
+        let keystore_path = dir.path().join("keystore.json");
+        fs::write(
+            &keystore_path,
+            keystore::encrypt(KeyKind::Gear, "//Bob", "secret", ScryptCost::LIGHT).unwrap(),
+        )
+        .unwrap();
+
+        let resolver = KeyResolver::from_args(&SignerArgs {
+            keystore_password_file: Some(password_path.clone()),
+            key_provider: Some(format!("mock:{}", keys_path.display())),
+        })
+        .unwrap();
+
+        let resolve = |kind, value: &str| resolver.resolve(kind, value).map(|key| key.to_string());
+        assert_eq!(resolve(KeyKind::Ethereum, ETH_KEY).unwrap(), ETH_KEY);
+        assert_eq!(
+            resolve(KeyKind::Ethereum, "provider:fee-payer").unwrap(),
+            ETH_KEY
+        );
+        assert_eq!(resolve(KeyKind::Gear, "provider:alice").unwrap(), "//Alice");
+        assert_eq!(
+            resolve(
+                KeyKind::Gear,
+                &format!("keystore:{}", keystore_path.display())
+            )
+            .unwrap(),
+            "//Bob"
+        );
+        assert!(resolve(KeyKind::Gear, "provider:fee-payer").is_err());
+        assert!(resolve(
+            KeyKind::Ethereum,
+            &format!("keystore:{}", keystore_path.display())
+        )
+        .is_err());
+
+        let mut suri = "provider:alice".to_string();
+        resolver.resolve_in_place(KeyKind::Gear, &mut suri).unwrap();
+        assert_eq!(suri, "//Alice");
+    }
+
+    #[test]
+    fn requires_configured_sources() {
+        let resolver = KeyResolver::default();
+
+        let err = resolver
+            .resolve(KeyKind::Gear, "provider:alice")
+            .unwrap_err();
+        assert!(err.to_string().contains("--key-provider is required"));
+        let err = resolver
+            .resolve(KeyKind::Gear, "keystore:/nonexistent")
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("failed to read keystore"));
+        assert!(KeyResolver::from_args(&SignerArgs {
+            keystore_password_file: None,
+            key_provider: Some("vault:secret".into()),
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn detects_keys_in_arguments() {
+        let args = |args: &[&str]| {
+            args.iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<_>>()
+                .into_iter()
+        };
+
+        assert!(is_in_arguments(
+            ETH_KEY,
+            args(&["relayer", "--eth-fee-payer", ETH_KEY])
+        ));
+        assert!(is_in_arguments(
+            "//Alice",
+            args(&["relayer", "--gear-fee-payer=//Alice"])
+        ));
+        assert!(!is_in_arguments(
+            "//Alice",
+            args(&["relayer", "--gear-fee-payer", "provider:alice"])
+        ));
+        assert!(!is_in_arguments("", args(&["relayer", "--suri="])));
+    }
+}
@@ -35,3 +35,32 @@ impl IMessageQueue::VaraMessage {
         ret
     }
 }
+
+sol! {
+    /// Subset of [Multicall3](https://github.com/mds1/multicall) used to deliver several
+    /// messages in one transaction.
+    #[sol(rpc)]
+    interface IMulticall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        // `Result` in the original, renamed not to shadow the prelude one.
+        struct CallResult {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls) external payable returns (CallResult[] memory returnData);
+    }
+}
+
+sol! {
+    /// Verifier of the final proofs `MessageQueue` checks merkle roots against.
+    #[sol(rpc)]
+    interface IVerifier {
+        function safeVerifyProof(bytes calldata proof, uint256[] calldata publicInputs) external view returns (bool success);
+    }
+}
@@ -0,0 +1,204 @@
+//! Health scoring of Ethereum RPC endpoints and agreement checks for quorum reads.
+
+use crate::Error;
+use std::{sync::Mutex, time::Duration};
+
+/// Latencies within the same step are considered equal when ranking endpoints, so that
+/// small fluctuations don't move requests between them.
+const LATENCY_STEP: Duration = Duration::from_millis(100);
+
+/// Health of a single endpoint as seen by this client.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointHealth {
+    /// Number of requests the endpoint failed to serve in a row.
+    pub consecutive_failures: u32,
+    /// Moving average of the latency of served requests.
+    pub latency: Option<Duration>,
+}
+
+/// Health of all configured endpoints, shared between clones and reconnects of `EthApi`.
+pub(crate) struct HealthTracker {
+    endpoints: Mutex<Vec<EndpointHealth>>,
+}
+
+impl HealthTracker {
+    pub fn new(count: usize) -> Self {
+        Self {
+            endpoints: Mutex::new(vec![EndpointHealth::default(); count]),
+        }
+    }
+
+    pub fn record_success(&self, index: usize, latency: Duration) {
+        let mut endpoints = self.endpoints.lock().expect("Health tracker lock poisoned");
+        let health = &mut endpoints[index];
+        health.consecutive_failures = 0;
+        health.latency = Some(match health.latency {
+            Some(average) => (average * 3 + latency) / 4,
+            None => latency,
+        });
+    }
+
+    pub fn record_failure(&self, index: usize) {
+        let mut endpoints = self.endpoints.lock().expect("Health tracker lock poisoned");
+        endpoints[index].consecutive_failures += 1;
+    }
+
+    /// Orders endpoint indices from the healthiest one: endpoints with fewer failures in
+    /// a row go first, then faster ones. Endpoints which haven't served anything yet go
+    /// after measured ones, and configuration order breaks ties, so the first endpoint
+    /// stays preferred while everything is healthy.
+    pub fn rank(&self, indices: &mut [usize]) {
+        let endpoints = self.endpoints.lock().expect("Health tracker lock poisoned");
+        indices.sort_by_key(|&index| {
+            let health = &endpoints[index];
+            let latency_step = health
+                .latency
+                .map(|latency| latency.as_millis() / LATENCY_STEP.as_millis())
+                .unwrap_or(u128::MAX);
+
+            (health.consecutive_failures, latency_step, index)
+        });
+    }
+
+    pub fn snapshot(&self) -> Vec<EndpointHealth> {
+        self.endpoints
+            .lock()
+            .expect("Health tracker lock poisoned")
+            .clone()
+    }
+}
+
+/// Returns the value at least `required` endpoints agree on. If more than one value
+/// reaches `required`, endpoints disagree and no value is trusted.
+pub(crate) fn agree<T: PartialEq>(
+    read: &'static str,
+    responses: Vec<Result<T, Error>>,
+    required: usize,
+) -> Result<T, Error> {
+    let mut groups: Vec<(T, usize)> = Vec::new();
+    let mut responded = 0;
+    for response in responses {
+        let Ok(value) = response else {
+            continue;
+        };
+
+        responded += 1;
+        match groups.iter_mut().find(|(known, _)| *known == value) {
+            Some((_, count)) => *count += 1,
+            None => groups.push((value, 1)),
+        }
+    }
+
+    let distinct = groups.len();
+    let reaching = groups
+        .iter()
+        .filter(|(_, count)| *count >= required)
+        .count();
+    let Some((value, agreeing)) = groups.into_iter().max_by_key(|(_, count)| *count) else {
+        return Err(Error::QuorumUnavailable {
+            read,
+            required,
+            responded,
+        });
+    };
+
+    if reaching == 1 {
+        return Ok(value);
+    }
+
+    if distinct > 1 {
+        return Err(Error::ProvidersDisagree {
+            read,
+            required,
+            responded,
+            agreeing,
+        });
+    }
+
+    Err(Error::QuorumUnavailable {
+        read,
+        required,
+        responded,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_by_failures_then_latency() {
+        let tracker = HealthTracker::new(3);
+        let mut indices = [0, 1, 2];
+        tracker.rank(&mut indices);
+        assert_eq!(indices, [0, 1, 2]);
+
+        tracker.record_failure(0);
+        tracker.record_success(1, Duration::from_millis(300));
+        tracker.record_success(2, Duration::from_millis(100));
+        tracker.rank(&mut indices);
+        assert_eq!(indices, [2, 1, 0]);
+
+        tracker.record_success(0, Duration::from_millis(150));
+        tracker.rank(&mut indices);
+        assert_eq!(indices, [0, 2, 1]);
+        assert_eq!(tracker.snapshot()[0].consecutive_failures, 0);
+    }
+
+    #[test]
+    fn quorum_agreement() {
+        let root = Some([1u8; 32]);
+        let other = Some([2u8; 32]);
+
+        let result = agree("root", vec![Ok(root), Ok(root), Ok(other)], 2);
+        assert_eq!(result.unwrap(), root);
+
+        let result = agree(
+            "root",
+            vec![Ok(root), Err(Error::ErrorFetchingBlock), Ok(root)],
+            2,
+        );
+        assert_eq!(result.unwrap(), root);
+
+        let result = agree("root", vec![Ok(root), Ok(other), Ok(None)], 2);
+        assert!(matches!(
+            result,
+            Err(Error::ProvidersDisagree {
+                responded: 3,
+                agreeing: 1,
+                ..
+            })
+        ));
+
+        let result = agree(
+            "root",
+            vec![
+                Ok(root),
+                Err(Error::ErrorFetchingBlock),
+                Err(Error::ErrorFetchingBlock),
+            ],
+            2,
+        );
+        assert!(matches!(
+            result,
+            Err(Error::QuorumUnavailable { responded: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn quorum_is_not_reached_by_two_groups() {
+        let root = Some([1u8; 32]);
+        let other = Some([2u8; 32]);
+
+        let result = agree("root", vec![Ok(root), Ok(root), Ok(other), Ok(other)], 2);
+        assert!(matches!(
+            result,
+            Err(Error::ProvidersDisagree {
+                required: 2,
+                responded: 4,
+                agreeing: 2,
+                ..
+            })
+        ));
+    }
+}
@@ -1,4 +1,7 @@
-use alloy::transports::{RpcError, TransportErrorKind};
+use alloy::{
+    primitives::TxHash,
+    transports::{RpcError, TransportErrorKind},
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -11,10 +14,21 @@ pub enum Error {
     WrongNodeUrl,
     #[error("Wrong private key")]
     WrongPrivateKey,
+    #[error("Remote signer error: {0}")]
+    RemoteSigner(String),
+    #[error("Nonce manager error: {0}")]
+    NonceManager(String),
     #[error("Error during contract execution: {0}")]
     ErrorDuringContractExecution(alloy::contract::Error),
     #[error("Error sending transaction: {0}")]
     ErrorSendingTransaction(alloy::contract::Error),
+    #[error("Failed to sign transaction: {0}")]
+    SigningTransaction(String),
+    #[error("Transaction {tx_hash} might have been sent: {error}")]
+    TransactionMaybeSent {
+        tx_hash: TxHash,
+        error: RpcError<TransportErrorKind>,
+    },
     #[error("Error querying event: {0}")]
     ErrorQueryingEvent(alloy::contract::Error),
     #[error("Error waiting transaction receipt")]
@@ -29,6 +43,66 @@ pub enum Error {
     FailedToBuildClient(alloy::transports::http::reqwest::Error),
     #[error("MessageQueue error: {0:x?}")]
     MessageQueue(super::abi::IMessageQueue::IMessageQueueErrors),
+    #[error("Read quorum {quorum} is invalid for {endpoints} endpoint(s): it must be a majority of endpoints")]
+    InvalidQuorum { quorum: usize, endpoints: usize },
+    #[error("Ethereum endpoints disagree on {read}: {agreeing} of {responded} responses match, {required} required")]
+    ProvidersDisagree {
+        read: &'static str,
+        required: usize,
+        responded: usize,
+        agreeing: usize,
+    },
+    #[error(
+        "Not enough Ethereum endpoints answered {read}: {responded} responded, {required} required"
+    )]
+    QuorumUnavailable {
+        read: &'static str,
+        required: usize,
+        responded: usize,
+    },
+}
+
+impl Error {
+    /// Whether the endpoint failed to serve the request, as opposed to the node answering
+    /// with an error. Only such failures make the client fall over to another endpoint.
+    pub fn is_endpoint_failure(&self) -> bool {
+        match self {
+            Self::ErrorInHTTPTransport(err) => is_transport_failure(err),
+            Self::ErrorDuringContractExecution(err)
+            | Self::ErrorSendingTransaction(err)
+            | Self::ErrorQueryingEvent(err) => matches!(
+                err,
+                alloy::contract::Error::TransportError(err) if is_transport_failure(err)
+            ),
+            _ => false,
+        }
+    }
+
+    /// Hash of the transaction which might have reached a node despite the error. Its
+    /// nonce can't be reused until the transaction is known to be dropped.
+    pub fn maybe_sent_transaction(&self) -> Option<TxHash> {
+        match self {
+            Self::TransactionMaybeSent { tx_hash, .. } => Some(*tx_hash),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn is_transport_failure(err: &RpcError<TransportErrorKind>) -> bool {
+    matches!(
+        err,
+        RpcError::Transport(_) | RpcError::NullResp | RpcError::DeserError { .. }
+    )
+}
+
+/// Whether the node rejected a transaction because it already has it.
+pub(crate) fn is_already_known(err: &RpcError<TransportErrorKind>) -> bool {
+    err.as_error_resp().is_some_and(|resp| {
+        let message = resp.message.to_lowercase();
+        message.contains("already known")
+            || message.contains("known transaction")
+            || message.contains("already imported")
+    })
 }
 
 impl From<RpcError<TransportErrorKind>> for Error {
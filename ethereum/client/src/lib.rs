@@ -1,24 +1,34 @@
 use alloy::{
     contract::Event,
     network::{Ethereum, EthereumWallet, TransactionBuilder},
-    primitives::{Address, Bytes, B256, U256},
+    primitives::{address, Address, Bytes, B256, U256},
     providers::{
         fillers::{
             BlobGasFiller, ChainIdFiller, FillProvider, GasFiller, JoinFill, NonceFiller,
             WalletFiller,
         },
-        Identity, PendingTransactionBuilder, Provider, ProviderBuilder, RootProvider,
+        Identity, Provider, ProviderBuilder, RootProvider,
     },
     pubsub::Subscription,
-    rpc::types::{Block, BlockId, BlockNumberOrTag, Filter, Header, Log as RpcLog},
-    signers::local::PrivateKeySigner,
-    sol_types::SolEvent,
+    rpc::types::{
+        Block, BlockId, BlockNumberOrTag, Filter, Header, Log as RpcLog, TransactionReceipt,
+        TransactionRequest,
+    },
+    sol_types::{SolEvent, SolInterface},
     transports::{ws::WsConnect, RpcError, TransportErrorKind},
 };
+use alloy_consensus::TxEnvelope;
+use alloy_eips::eip2718::Encodable2718;
 use anyhow::{Context, Result as AnyResult};
 use primitive_types::{H160, H256};
 use reqwest::Url;
-use std::{ops::Deref, str::FromStr, time::Duration};
+use std::{
+    future::Future,
+    ops::Deref,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
 
 pub use alloy::primitives::TxHash;
 
@@ -26,16 +36,30 @@ pub mod abi;
 use abi::{
     BridgingPayment, IERC20Manager, IMessageQueue,
     IMessageQueue::{IMessageQueueInstance, MerkleRoot, VaraMessage},
+    IMulticall3, IVerifier,
 };
 
 pub mod error;
 pub use error::Error;
 
+mod endpoints;
+pub use endpoints::EndpointHealth;
+use endpoints::HealthTracker;
+
+pub mod signer;
+pub use signer::{RemoteSigner, Signer};
+
+pub mod nonce;
+pub use nonce::NonceManager;
+
 // 2 Gwei
 const MAX_FEE_PER_GAS: u128 = 2_000_000_000;
 // 0.5 Gwei
 const MAX_PRIORITY_FEE_PER_GAS: u128 = 500_000_000;
 
+/// Address of Multicall3, the same on every chain it's deployed to.
+pub const MULTICALL3_ADDRESS: Address = address!("0xcA11bde05977b3631167028862bE2a173976CA11");
+
 type ProviderType = FillProvider<
     JoinFill<
         JoinFill<
@@ -51,11 +75,10 @@ type ProviderType = FillProvider<
 pub struct Contracts {
     provider: ProviderType,
     message_queue_instance: IMessageQueueInstance<ProviderType, Ethereum>,
-    max_fee_per_gas: u128,
-    max_priority_fee_per_gas: u128,
+    fee_caps: SharedFeeCaps,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MerkleRootEntry {
     pub block_number: u64,
     pub merkle_root: H256,
@@ -71,6 +94,95 @@ pub struct DepositEventEntry {
     pub tx_hash: TxHash,
 }
 
+/// EIP-1559 fee caps of a transaction, in wei.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeCaps {
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl FeeCaps {
+    /// Caps set by `max_fee_per_gas` and `max_priority_fee_per_gas`, the defaults for the
+    /// unset ones.
+    pub fn or_defaults(
+        max_fee_per_gas: Option<u128>,
+        max_priority_fee_per_gas: Option<u128>,
+    ) -> Self {
+        Self {
+            max_fee_per_gas: max_fee_per_gas.unwrap_or(MAX_FEE_PER_GAS),
+            max_priority_fee_per_gas: max_priority_fee_per_gas.unwrap_or(MAX_PRIORITY_FEE_PER_GAS),
+        }
+    }
+}
+
+/// Minimal fee caps of message deliveries, shared by an [`EthApi`] and its clones. Changes
+/// apply to the transactions built afterwards.
+#[derive(Debug, Clone)]
+pub struct SharedFeeCaps(Arc<RwLock<FeeCaps>>);
+
+impl SharedFeeCaps {
+    pub fn new(caps: FeeCaps) -> Self {
+        Self(Arc::new(RwLock::new(caps)))
+    }
+
+    pub fn get(&self) -> FeeCaps {
+        *self.0.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub fn set(&self, caps: FeeCaps) {
+        *self.0.write().unwrap_or_else(|err| err.into_inner()) = caps;
+    }
+}
+
+/// Transaction signed by [`EthApi`] but not broadcast yet. Its hash is already final, so
+/// it can be persisted before the transaction reaches any node.
+#[derive(Debug, Clone)]
+pub struct SignedTransaction(TxEnvelope);
+
+impl SignedTransaction {
+    pub fn hash(&self) -> TxHash {
+        *self.0.tx_hash()
+    }
+}
+
+impl From<TxEnvelope> for SignedTransaction {
+    fn from(tx: TxEnvelope) -> Self {
+        Self(tx)
+    }
+}
+
+/// Message of a merkle tree to deliver with [`EthApi::provide_content_messages`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentMessage {
+    pub total_leaves: u32,
+    pub leaf_index: u32,
+    pub nonce: [u8; 32],
+    pub sender: [u8; 32],
+    pub receiver: [u8; 20],
+    pub payload: Vec<u8>,
+    pub proof: Vec<[u8; 32]>,
+}
+
+/// Result of simulating delivery of a message of a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOutcome {
+    /// Message is included into the batch transaction.
+    Included,
+    /// Message is already processed, so it's left out.
+    AlreadyProcessed,
+    /// Message processing reverts with the given reason, so it's left out.
+    Rejected(String),
+}
+
+/// Batch transaction sent by [`EthApi::provide_content_messages`].
+#[derive(Debug, Clone)]
+pub struct BatchDelivery {
+    /// Hash of the transaction. `None` if no message of the batch is included.
+    pub tx_hash: Option<TxHash>,
+    /// Outcome for every message, in order of the batch.
+    pub outcomes: Vec<BatchOutcome>,
+}
+
 #[derive(Debug)]
 pub enum TxStatus {
     Finalized,
@@ -248,11 +360,30 @@ pub async fn get_block(provider: impl Provider, block: u64) -> AnyResult<Block>
 }
 
 #[derive(Clone)]
-pub struct EthApi {
+struct Endpoint {
+    /// Position of the endpoint in the configured list.
+    index: usize,
     contracts: Contracts,
+}
+
+/// Client of the MessageQueue contract. Talks to a set of interchangeable endpoints:
+/// requests go to the healthiest one and fall over to the others when it fails to
+/// answer. Reads of merkle roots can additionally require agreement of several endpoints.
+#[derive(Clone)]
+pub struct EthApi {
+    /// Endpoints connected to, a non-empty subset of `urls`.
+    endpoints: Vec<Endpoint>,
+    urls: Vec<Url>,
+    health: Arc<HealthTracker>,
+    read_quorum: Option<usize>,
+    message_queue_address: [u8; 20],
+    /// Multicall3 contract used to deliver message batches.
+    multicall_address: Address,
+    fee_caps: SharedFeeCaps,
     public_key: Address,
     wallet: EthereumWallet,
-    url: Url,
+    /// Allocates nonces of transactions when set, see [`Self::with_nonce_manager`].
+    nonces: Option<Arc<NonceManager>>,
     ws_max_retry: Option<u32>,
     ws_retry_interval: Option<Duration>,
 }
@@ -285,126 +416,346 @@ impl EthApi {
         ws_retry_interval: Option<Duration>,
         max_fee_per_gas: Option<u128>,
         max_priority_fee_per_gas: Option<u128>,
+    ) -> Result<EthApi, Error> {
+        Self::new_with_endpoints(
+            &[url.to_string()],
+            message_queue_address,
+            private_key,
+            ws_max_retry,
+            ws_retry_interval,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        )
+        .await
+    }
+
+    /// Connects to every endpoint in `urls`, in order of preference. Endpoints which
+    /// can't be connected to are skipped until the next `reconnect`; it's an error only
+    /// if none of them is available.
+    ///
+    /// `private_key` is parsed with [`Signer::from_spec`], so it may also select a
+    /// [`RemoteSigner`]. Without it transactions are signed with a random key.
+    pub async fn new_with_endpoints(
+        urls: &[String],
+        message_queue_address: &str,
+        private_key: Option<&str>,
+        ws_max_retry: Option<u32>,
+        ws_retry_interval: Option<Duration>,
+        max_fee_per_gas: Option<u128>,
+        max_priority_fee_per_gas: Option<u128>,
     ) -> Result<EthApi, Error> {
         let signer = match private_key {
-            Some(private_key) => {
-                let pk: B256 =
-                    B256::from(U256::from_str(private_key).map_err(|_| Error::WrongPrivateKey)?);
-                PrivateKeySigner::from_bytes(&pk).map_err(|_| Error::WrongPrivateKey)?
-            }
-            None => PrivateKeySigner::random(),
+            Some(private_key) => Signer::from_spec(private_key).await?,
+            None => Signer::random(),
         };
 
-        let public_key = signer.address();
+        Self::new_with_signer(
+            urls,
+            message_queue_address,
+            signer,
+            ws_max_retry,
+            ws_retry_interval,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        )
+        .await
+    }
 
-        let wallet = EthereumWallet::from(signer);
+    pub async fn new_with_signer(
+        urls: &[String],
+        message_queue_address: &str,
+        signer: Signer,
+        ws_max_retry: Option<u32>,
+        ws_retry_interval: Option<Duration>,
+        max_fee_per_gas: Option<u128>,
+        max_priority_fee_per_gas: Option<u128>,
+    ) -> Result<EthApi, Error> {
+        let public_key = signer.address();
+        let wallet = signer.wallet().clone();
 
         let message_queue_address: Address = message_queue_address
             .parse()
             .map_err(|_| Error::WrongAddress)?;
 
-        let url = Url::parse(url).map_err(|_| Error::WrongNodeUrl)?;
+        if urls.is_empty() {
+            return Err(Error::WrongNodeUrl);
+        }
+        let urls = urls
+            .iter()
+            .map(|url| Url::parse(url).map_err(|_| Error::WrongNodeUrl))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut api = EthApi {
+            endpoints: Vec::new(),
+            health: Arc::new(HealthTracker::new(urls.len())),
+            urls,
+            read_quorum: None,
+            message_queue_address: message_queue_address.into_array(),
+            multicall_address: MULTICALL3_ADDRESS,
+            fee_caps: SharedFeeCaps::new(FeeCaps::or_defaults(
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            )),
+            public_key,
+            wallet,
+            nonces: None,
+            ws_max_retry,
+            ws_retry_interval,
+        };
+        api.endpoints = api.connect_endpoints().await?;
 
-        let mut ws = WsConnect::new(url.clone());
+        Ok(api)
+    }
 
-        if let Some(ws_max_retry) = ws_max_retry {
-            ws = ws.with_max_retries(ws_max_retry)
+    /// Requires `quorum` endpoints to return the same result for security-critical reads:
+    /// `read_finalized_merkle_root` and `fetch_merkle_roots_in_range`. The quorum must be a
+    /// majority of endpoints, so that two different results can't both reach it.
+    pub fn with_read_quorum(mut self, quorum: usize) -> Result<Self, Error> {
+        if quorum * 2 <= self.urls.len() || quorum > self.urls.len() {
+            return Err(Error::InvalidQuorum {
+                quorum,
+                endpoints: self.urls.len(),
+            });
         }
 
-        if let Some(ws_retry_interval) = ws_retry_interval {
-            ws = ws.with_retry_interval(ws_retry_interval)
-        }
+        self.read_quorum = Some(quorum);
+        Ok(self)
+    }
 
-        let provider: ProviderType = ProviderBuilder::new()
-            .wallet(wallet.clone())
-            .connect_ws(ws)
-            .await?;
+    /// Allocates nonces locally with the [`NonceManager`] shared by every `EthApi` of the
+    /// process which signs with the same key, saving them to `store` when set. Gaps in
+    /// nonces are filled in the background.
+    pub fn with_nonce_manager(mut self, store: Option<&Path>) -> Result<Self, Error> {
+        let nonces = NonceManager::shared(self.public_key, store)?;
+        nonces.spawn_gap_filler(self.clone());
+        self.nonces = Some(nonces);
 
-        let contracts = Contracts::new(
-            provider,
-            message_queue_address.into_array(),
-            max_fee_per_gas,
-            max_priority_fee_per_gas,
-        )?;
+        Ok(self)
+    }
+
+    /// Fee caps message deliveries don't go below. Changing them through the handle applies
+    /// to every clone of this client.
+    pub fn fee_caps(&self) -> SharedFeeCaps {
+        self.fee_caps.clone()
+    }
+
+    /// Delivers message batches through the Multicall3 deployment at `address` instead
+    /// of [`MULTICALL3_ADDRESS`].
+    pub fn with_multicall_address(mut self, address: &str) -> Result<Self, Error> {
+        self.multicall_address = address.parse().map_err(|_| Error::WrongAddress)?;
+
+        Ok(self)
+    }
+
+    pub async fn reconnect(&self) -> Result<EthApi, Error> {
+        let endpoints = self.connect_endpoints().await?;
 
         Ok(EthApi {
-            contracts,
-            public_key,
-            url,
-            wallet,
-            ws_max_retry,
-            ws_retry_interval,
+            endpoints,
+            ..self.clone()
         })
     }
 
-    pub async fn reconnect(&self) -> Result<EthApi, Error> {
-        let mut ws = WsConnect::new(self.url.clone());
-        if let Some(ws_max_retry) = self.ws_max_retry {
-            ws = ws.with_max_retries(ws_max_retry);
+    async fn connect_endpoints(&self) -> Result<Vec<Endpoint>, Error> {
+        let mut endpoints = Vec::with_capacity(self.urls.len());
+        let mut last_error = None;
+        for (index, url) in self.urls.iter().enumerate() {
+            let mut ws = WsConnect::new(url.clone());
+            if let Some(ws_max_retry) = self.ws_max_retry {
+                ws = ws.with_max_retries(ws_max_retry);
+            }
+            if let Some(ws_retry_interval) = self.ws_retry_interval {
+                ws = ws.with_retry_interval(ws_retry_interval);
+            }
+
+            let provider: ProviderType = match ProviderBuilder::new()
+                .wallet(self.wallet.clone())
+                .connect_ws(ws)
+                .await
+            {
+                Ok(provider) => provider,
+                Err(err) => {
+                    log::warn!(
+                        "Failed to connect to Ethereum endpoint #{index} ({}): {err}",
+                        url.host_str().unwrap_or_default()
+                    );
+                    self.health.record_failure(index);
+                    last_error = Some(err);
+                    continue;
+                }
+            };
+
+            endpoints.push(Endpoint {
+                index,
+                contracts: Contracts::new(
+                    provider,
+                    self.message_queue_address,
+                    self.fee_caps.clone(),
+                )?,
+            });
         }
-        if let Some(ws_retry_interval) = self.ws_retry_interval {
-            ws = ws.with_retry_interval(ws_retry_interval);
+
+        match last_error {
+            Some(err) if endpoints.is_empty() => Err(err.into()),
+            _ => Ok(endpoints),
         }
-        let provider: ProviderType = ProviderBuilder::new()
-            .wallet(self.wallet.clone())
-            .connect_ws(ws)
-            .await?;
+    }
 
-        let contracts = Contracts::new(
-            provider,
-            self.contracts.message_queue_instance.address().0 .0,
-            Some(self.contracts.max_fee_per_gas),
-            Some(self.contracts.max_priority_fee_per_gas),
-        )?;
+    /// Health of every configured endpoint, in configuration order.
+    pub fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        self.health.snapshot()
+    }
 
-        Ok(EthApi {
-            contracts,
-            public_key: self.public_key,
-            url: self.url.clone(),
-            wallet: self.wallet.clone(),
-            ws_max_retry: self.ws_max_retry,
-            ws_retry_interval: self.ws_retry_interval,
-        })
+    /// Connected endpoints, the healthiest first.
+    fn ranked_endpoints(&self) -> Vec<&Endpoint> {
+        let mut indices: Vec<usize> = self.endpoints.iter().map(|e| e.index).collect();
+        self.health.rank(&mut indices);
+
+        indices
+            .into_iter()
+            .filter_map(|index| self.endpoints.iter().find(|e| e.index == index))
+            .collect()
+    }
+
+    fn primary(&self) -> &Contracts {
+        let endpoint = self.ranked_endpoints()[0];
+        &endpoint.contracts
+    }
+
+    /// Performs the call on the healthiest endpoint, falling over to the next ones while
+    /// endpoints fail to answer.
+    async fn with_failover<T, F, Fut>(&self, call: F) -> Result<T, Error>
+    where
+        F: Fn(Contracts) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut last_error = None;
+        for endpoint in self.ranked_endpoints() {
+            let started = Instant::now();
+            match call(endpoint.contracts.clone()).await {
+                Err(err) if err.is_endpoint_failure() => {
+                    log::warn!(
+                        "Ethereum endpoint #{} failed to serve request: {err}",
+                        endpoint.index
+                    );
+                    self.health.record_failure(endpoint.index);
+                    last_error = Some(err);
+                }
+
+                result => {
+                    self.health
+                        .record_success(endpoint.index, started.elapsed());
+                    return result;
+                }
+            }
+        }
+
+        Err(last_error.expect("EthApi always has at least one endpoint"))
+    }
+
+    /// Performs the call on every endpoint and returns the result at least `read_quorum`
+    /// of them agree on. Without a quorum configured it's a regular call with failover.
+    async fn quorum_read<T, F, Fut>(&self, read: &'static str, call: F) -> Result<T, Error>
+    where
+        T: PartialEq,
+        F: Fn(Contracts) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let Some(required) = self.read_quorum else {
+            return self.with_failover(call).await;
+        };
+
+        let responses = futures::future::join_all(self.endpoints.iter().map(|endpoint| {
+            let call = &call;
+            async move {
+                let started = Instant::now();
+                let result = call(endpoint.contracts.clone()).await;
+                match &result {
+                    Err(err) if err.is_endpoint_failure() => {
+                        self.health.record_failure(endpoint.index)
+                    }
+                    _ => self
+                        .health
+                        .record_success(endpoint.index, started.elapsed()),
+                }
+
+                result
+            }
+        }))
+        .await;
+
+        endpoints::agree(read, responses, required)
     }
 
     // TODO: Don't expose provider here.
     pub fn raw_provider(&self) -> &ProviderType {
-        &self.contracts.provider
+        &self.primary().provider
     }
 
     /// Returns the maximum block number that can be submitted as part of a
     /// merkle-root submission into MessageQueue contract.
     pub async fn max_block_number(&self) -> Result<u32, Error> {
-        self.contracts.max_block_number().await
+        self.with_failover(|contracts| async move { contracts.max_block_number().await })
+            .await
     }
 
     /// Returns the maximum block distance allowed between `max_block_number`
     /// and the block number being submitted as part of a merkle-root submission
     /// into MessageQueue contract.
     pub async fn max_block_distance(&self) -> Result<u32, Error> {
-        self.contracts.max_block_distance().await
+        self.with_failover(|contracts| async move { contracts.max_block_distance().await })
+            .await
     }
 
     /// Returns the delay (in seconds) requires before merkle-root submitted
     /// by an admin can be used.
     pub async fn process_admin_message_delay(&self) -> Result<u64, Error> {
-        self.contracts.process_admin_message_delay().await
+        self.with_failover(|contracts| async move { contracts.process_admin_message_delay().await })
+            .await
     }
 
     /// Returns the delay (in seconds) requires before merkle-root submitted
     /// by a pauser can be used.
     pub async fn process_pauser_message_delay(&self) -> Result<u64, Error> {
-        self.contracts.process_pauser_message_delay().await
+        self.with_failover(
+            |contracts| async move { contracts.process_pauser_message_delay().await },
+        )
+        .await
     }
 
     /// Returns the delay (in seconds) requires before merkle-root submitted
     /// by an arbitrary user can be used.
     pub async fn process_user_message_delay(&self) -> Result<u64, Error> {
-        self.contracts.process_user_message_delay().await
+        self.with_failover(|contracts| async move { contracts.process_user_message_delay().await })
+            .await
+    }
+
+    /// Checks whether the verifier `MessageQueue` uses accepts `proof` of `merkle_root` at
+    /// `block_number`. Public inputs are built the same way `submitMerkleRoot` does.
+    pub async fn verifier_accepts_proof(
+        &self,
+        proof: &[u8],
+        block_number: u32,
+        merkle_root: [u8; 32],
+    ) -> Result<bool, Error> {
+        let proof = Bytes::copy_from_slice(proof);
+        self.with_failover(|contracts| {
+            let proof = proof.clone();
+            async move {
+                contracts
+                    .verifier_accepts_proof(proof, block_number, merkle_root)
+                    .await
+            }
+        })
+        .await
     }
 
     pub async fn get_approx_balance(&self) -> Result<f64, Error> {
-        self.contracts.get_approx_balance(self.public_key).await
+        let public_key = self.public_key;
+        self.with_failover(
+            |contracts| async move { contracts.get_approx_balance(public_key).await },
+        )
+        .await
     }
 
     pub async fn provide_merkle_root(
@@ -412,40 +763,281 @@ impl EthApi {
         block_number: u32,
         merkle_root: [u8; 32],
         proof: Vec<u8>,
-    ) -> Result<PendingTransactionBuilder<Ethereum>, Error> {
-        self.contracts
-            .provide_merkle_root(
-                U256::from(block_number),
-                B256::from(merkle_root),
-                Bytes::from(proof),
-            )
+    ) -> Result<TxHash, Error> {
+        let proof = Bytes::from(proof);
+        let tx = self
+            .with_failover(|contracts| {
+                let proof = proof.clone();
+                async move {
+                    contracts
+                        .provide_merkle_root(
+                            U256::from(block_number),
+                            B256::from(merkle_root),
+                            proof,
+                        )
+                        .await
+                }
+            })
+            .await?;
+
+        self.send_transaction(tx).await
+    }
+
+    /// Signs `submitMerkleRoot` with explicit nonce and fee caps, so the transaction can
+    /// later be replaced by another one with the same nonce and higher fees. The
+    /// transaction is sent with [`Self::send_signed`].
+    pub async fn sign_merkle_root_with_fees(
+        &self,
+        block_number: u32,
+        merkle_root: [u8; 32],
+        proof: Vec<u8>,
+        nonce: u64,
+        fees: FeeCaps,
+    ) -> Result<SignedTransaction, Error> {
+        let proof = Bytes::from(proof);
+        let tx = self
+            .with_failover(|contracts| {
+                let proof = proof.clone();
+                async move {
+                    contracts
+                        .provide_merkle_root_with_fees(
+                            U256::from(block_number),
+                            B256::from(merkle_root),
+                            proof,
+                            nonce,
+                            fees,
+                        )
+                        .await
+                }
+            })
+            .await?;
+
+        Ok(SignedTransaction(self.sign_transaction(tx).await?))
+    }
+
+    /// Broadcasts a transaction signed beforehand, see [`Self::broadcast`].
+    pub async fn send_signed(&self, tx: &SignedTransaction) -> Result<TxHash, Error> {
+        self.broadcast(&tx.0).await
+    }
+
+    /// Signs `tx` once and broadcasts it with [`Self::broadcast`].
+    async fn send_transaction(&self, tx: TransactionRequest) -> Result<TxHash, Error> {
+        let tx = self.sign_transaction(tx).await?;
+
+        self.broadcast(&tx).await
+    }
+
+    /// Fills in the chain id, sender, nonce and fee caps of `tx` unless set and signs it.
+    async fn sign_transaction(&self, mut tx: TransactionRequest) -> Result<TxEnvelope, Error> {
+        tx.set_from(self.public_key);
+        if tx.chain_id().is_none() {
+            let chain_id = self
+                .with_failover(|contracts| async move { contracts.chain_id().await })
+                .await?;
+            tx.set_chain_id(chain_id);
+        }
+        if tx.nonce().is_none() {
+            tx.set_nonce(self.pending_nonce().await?);
+        }
+        if tx.max_fee_per_gas().is_none() {
+            let fees = self.estimate_fee_caps().await?;
+            tx.set_max_fee_per_gas(fees.max_fee_per_gas);
+            tx.set_max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
+        }
+
+        tx.build(&self.wallet)
             .await
+            .map_err(|e| Error::SigningTransaction(e.to_string()))
+    }
+
+    /// Broadcasts the signed `tx` through the healthiest endpoint. A node which already
+    /// knows the transaction counts as success.
+    ///
+    /// After an endpoint fails to answer, the transaction might have been broadcast
+    /// anyway. The next endpoint is asked for it first and only gets the very same signed
+    /// transaction, so falling over never sends a duplicate. If it can't be settled
+    /// whether the transaction reached a node, the error is
+    /// [`Error::TransactionMaybeSent`].
+    async fn broadcast(&self, tx: &TxEnvelope) -> Result<TxHash, Error> {
+        let tx_hash = *tx.tx_hash();
+        let raw = tx.encoded_2718();
+
+        let mut last_error = None;
+        for endpoint in self.ranked_endpoints() {
+            let provider = &endpoint.contracts.provider;
+            let started = Instant::now();
+            if last_error.is_some() {
+                if let Ok(Some(_)) = provider.get_transaction_by_hash(tx_hash).await {
+                    self.health
+                        .record_success(endpoint.index, started.elapsed());
+                    return Ok(tx_hash);
+                }
+            }
+
+            match provider.send_raw_transaction(&raw).await {
+                Err(err) if error::is_transport_failure(&err) => {
+                    log::warn!(
+                        "Ethereum endpoint #{} failed to broadcast transaction {tx_hash}: {err}",
+                        endpoint.index
+                    );
+                    self.health.record_failure(endpoint.index);
+                    last_error = Some(err);
+                }
+
+                result => {
+                    self.health
+                        .record_success(endpoint.index, started.elapsed());
+                    return match result {
+                        Ok(_) => Ok(tx_hash),
+                        Err(err) if error::is_already_known(&err) => Ok(tx_hash),
+                        Err(err) if last_error.is_some() => Err(Error::TransactionMaybeSent {
+                            tx_hash,
+                            error: err,
+                        }),
+                        Err(err) => {
+                            log::error!("Sending error: {err:?}");
+                            Err(Error::ErrorSendingTransaction(
+                                alloy::contract::Error::TransportError(err),
+                            ))
+                        }
+                    };
+                }
+            }
+        }
+
+        Err(Error::TransactionMaybeSent {
+            tx_hash,
+            error: last_error.expect("EthApi always has at least one endpoint"),
+        })
+    }
+
+    /// Fee caps suggested by the node for a transaction included in the next blocks.
+    pub async fn estimate_fee_caps(&self) -> Result<FeeCaps, Error> {
+        let estimation = self.raw_provider().estimate_eip1559_fees().await?;
+
+        Ok(FeeCaps {
+            max_fee_per_gas: estimation.max_fee_per_gas,
+            max_priority_fee_per_gas: estimation.max_priority_fee_per_gas,
+        })
+    }
+
+    /// Nonce of the next transaction of the fee payer, including pending ones.
+    pub async fn pending_nonce(&self) -> Result<u64, Error> {
+        Ok(self
+            .raw_provider()
+            .get_transaction_count(self.public_key)
+            .pending()
+            .await?)
+    }
+
+    /// Nonce for a new transaction of the fee payer. With a [`NonceManager`] it has to be
+    /// reported with [`Self::nonce_sent`], [`Self::nonce_maybe_sent`] or
+    /// [`Self::release_nonce`] afterwards.
+    pub async fn allocate_nonce(&self) -> Result<u64, Error> {
+        match &self.nonces {
+            Some(nonces) => nonces.allocate(self).await,
+            None => self.pending_nonce().await,
+        }
+    }
+
+    pub async fn nonce_sent(&self, nonce: u64) {
+        if let Some(nonces) = &self.nonces {
+            nonces.sent(nonce).await;
+        }
+    }
+
+    /// Frees `nonce` for reuse. Only for transactions which are definitely rejected
+    /// before reaching any node.
+    pub async fn release_nonce(&self, nonce: u64) {
+        if let Some(nonces) = &self.nonces {
+            nonces.release(nonce).await;
+        }
+    }
+
+    /// Keeps `nonce` reserved after [`Error::TransactionMaybeSent`], see
+    /// [`NonceManager::maybe_sent`].
+    pub async fn nonce_maybe_sent(&self, nonce: u64) {
+        if let Some(nonces) = &self.nonces {
+            nonces.maybe_sent(nonce, self).await;
+        }
+    }
+
+    /// Reports `nonce` according to the result of sending its transaction.
+    async fn report_nonce(&self, nonce: u64, result: &Result<TxHash, Error>) {
+        match result {
+            Ok(_) => self.nonce_sent(nonce).await,
+            Err(err) if err.maybe_sent_transaction().is_some() => {
+                self.nonce_maybe_sent(nonce).await
+            }
+            Err(_) => self.release_nonce(nonce).await,
+        }
+    }
+
+    /// Sends a zero-value transfer to the fee payer itself with `nonce`, which either
+    /// fills a gap in nonces or replaces a pending transaction given high enough fees.
+    pub async fn cancel_nonce(&self, nonce: u64, fees: FeeCaps) -> Result<TxHash, Error> {
+        let tx = TransactionRequest::default()
+            .with_to(self.public_key)
+            .with_value(U256::ZERO)
+            .with_nonce(nonce)
+            .with_gas_limit(nonce::TRANSFER_GAS)
+            .with_max_fee_per_gas(fees.max_fee_per_gas)
+            .with_max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
+
+        self.send_transaction(tx).await
+    }
+
+    /// Number of fee payer transactions included in the latest block.
+    pub async fn latest_nonce(&self) -> Result<u64, Error> {
+        Ok(self
+            .raw_provider()
+            .get_transaction_count(self.public_key)
+            .latest()
+            .await?)
+    }
+
+    pub async fn get_transaction_receipt(
+        &self,
+        tx_hash: TxHash,
+    ) -> Result<Option<TransactionReceipt>, Error> {
+        Ok(self.raw_provider().get_transaction_receipt(tx_hash).await?)
     }
 
     pub async fn send_challenge_root(&self) -> Result<TxHash, Error> {
-        self.contracts.challenge_root().await
+        let tx = self
+            .with_failover(|contracts| async move { contracts.challenge_root().await })
+            .await?;
+
+        self.send_transaction(tx).await
     }
 
     pub async fn get_tx_status(&self, tx_hash: TxHash) -> Result<TxStatus, Error> {
-        self.contracts.get_tx_status(tx_hash).await
+        self.with_failover(|contracts| async move { contracts.get_tx_status(tx_hash).await })
+            .await
     }
 
     pub async fn read_finalized_merkle_root(
         &self,
         gear_block: u32,
     ) -> Result<Option<[u8; 32]>, Error> {
-        self.contracts
-            .read_merkle_root(U256::from(gear_block), BlockNumberOrTag::Finalized)
-            .await
+        self.quorum_read("finalized merkle root", |contracts| async move {
+            contracts
+                .read_merkle_root(U256::from(gear_block), BlockNumberOrTag::Finalized)
+                .await
+        })
+        .await
     }
 
     pub async fn read_chainhead_merkle_root(
         &self,
         gear_block: u32,
     ) -> Result<Option<[u8; 32]>, Error> {
-        self.contracts
-            .read_merkle_root(U256::from(gear_block), BlockNumberOrTag::Latest)
-            .await
+        self.with_failover(|contracts| async move {
+            contracts
+                .read_merkle_root(U256::from(gear_block), BlockNumberOrTag::Latest)
+                .await
+        })
+        .await
     }
 
     pub async fn fetch_merkle_roots_in_range(
@@ -453,7 +1045,10 @@ impl EthApi {
         from: u64,
         to: u64,
     ) -> Result<Vec<(MerkleRootEntry, Option<u64>)>, Error> {
-        self.contracts.fetch_merkle_roots_in_range(from, to).await
+        self.quorum_read("merkle roots in range", |contracts| async move {
+            contracts.fetch_merkle_roots_in_range(from, to).await
+        })
+        .await
     }
 
     pub async fn get_block_timestamp(&self, block: u64) -> Result<u64, Error> {
@@ -467,8 +1062,22 @@ impl EthApi {
             .timestamp)
     }
 
+    /// Base fee per gas of the latest block, in wei.
+    pub async fn base_fee_per_gas(&self) -> Result<u128, Error> {
+        self.raw_provider()
+            .get_block_by_number(BlockNumberOrTag::Latest)
+            .await
+            .map_err(Error::ErrorInHTTPTransport)?
+            .ok_or(Error::ErrorFetchingBlock)?
+            .header
+            .base_fee_per_gas
+            .map(u128::from)
+            .ok_or(Error::ErrorFetchingBlock)
+    }
+
     pub async fn block_number(&self) -> Result<u64, Error> {
-        self.contracts.block_number().await
+        self.with_failover(|contracts| async move { contracts.block_number().await })
+            .await
     }
 
     pub async fn finalized_block_number(&self) -> AnyResult<u64> {
@@ -498,31 +1107,147 @@ impl EthApi {
         payload: Vec<u8>,
         proof: Vec<[u8; 32]>,
     ) -> Result<TxHash, Error> {
-        self.contracts
-            .provide_content_message(
-                U256::from(block_number),
-                U256::from(total_leaves),
-                U256::from(leaf_index),
-                U256::from_be_bytes(nonce),
-                B256::from(sender),
-                Address::from(receiver),
-                Bytes::from(payload),
-                proof.into_iter().map(B256::from).collect(),
-            )
-            .await
+        let payload = Bytes::from(payload);
+        let proof: Vec<B256> = proof.into_iter().map(B256::from).collect();
+        let tx_nonce = match &self.nonces {
+            Some(nonces) => Some(nonces.allocate(self).await?),
+            None => None,
+        };
+
+        let result = async {
+            let tx = self
+                .with_failover(|contracts| {
+                    let payload = payload.clone();
+                    let proof = proof.clone();
+                    async move {
+                        contracts
+                            .provide_content_message(
+                                U256::from(block_number),
+                                U256::from(total_leaves),
+                                U256::from(leaf_index),
+                                U256::from_be_bytes(nonce),
+                                B256::from(sender),
+                                Address::from(receiver),
+                                payload,
+                                proof,
+                                tx_nonce,
+                            )
+                            .await
+                    }
+                })
+                .await?;
+
+            self.send_transaction(tx).await
+        }
+        .await;
+
+        if let Some(tx_nonce) = tx_nonce {
+            self.report_nonce(tx_nonce, &result).await;
+        }
+
+        result
+    }
+
+    /// Delivers messages of the merkle root at `block_number` in one transaction. Delivery
+    /// is simulated first and only the messages which would be processed are included.
+    ///
+    /// Failures aren't allowed in the sent batch: with them allowed, a gas limit estimated
+    /// too low would let every message run out of gas while the transaction succeeds.
+    /// Instead a message which reverts when the transaction executes reverts the whole
+    /// batch, so messages of a reverted batch are to be delivered again. Use
+    /// [`Self::processed_messages`] on the receipt to find out which messages are
+    /// processed.
+    pub async fn provide_content_messages(
+        &self,
+        block_number: u32,
+        messages: Vec<ContentMessage>,
+    ) -> Result<BatchDelivery, Error> {
+        let block_number = U256::from(block_number);
+        let multicall = self.multicall_address;
+
+        let outcomes = self
+            .with_failover(|contracts| {
+                let messages = messages.clone();
+                async move {
+                    contracts
+                        .simulate_content_messages(multicall, block_number, &messages)
+                        .await
+                }
+            })
+            .await?;
+
+        let included: Vec<_> = messages
+            .into_iter()
+            .zip(&outcomes)
+            .filter(|(_, outcome)| **outcome == BatchOutcome::Included)
+            .map(|(message, _)| message)
+            .collect();
+        if included.is_empty() {
+            return Ok(BatchDelivery {
+                tx_hash: None,
+                outcomes,
+            });
+        }
+
+        let tx_nonce = match &self.nonces {
+            Some(nonces) => Some(nonces.allocate(self).await?),
+            None => None,
+        };
+
+        let result = async {
+            let tx = self
+                .with_failover(|contracts| {
+                    let calls = contracts.content_message_calls(block_number, &included, false);
+                    async move {
+                        contracts
+                            .multicall_transaction(multicall, calls, tx_nonce)
+                            .await
+                    }
+                })
+                .await?;
+
+            self.send_transaction(tx).await
+        }
+        .await;
+
+        if let Some(tx_nonce) = tx_nonce {
+            self.report_nonce(tx_nonce, &result).await;
+        }
+
+        Ok(BatchDelivery {
+            tx_hash: Some(result?),
+            outcomes,
+        })
+    }
+
+    /// Nonces of the messages processed by the transaction with `receipt`.
+    pub fn processed_messages(&self, receipt: &TransactionReceipt) -> Vec<[u8; 32]> {
+        let message_queue = Address::from(self.message_queue_address);
+
+        receipt
+            .inner
+            .logs()
+            .iter()
+            .filter(|log| log.address() == message_queue)
+            .filter_map(|log| log.log_decode::<IMessageQueue::MessageProcessed>().ok())
+            .map(|log| log.inner.data.messageNonce.to_be_bytes())
+            .collect()
     }
 
     pub async fn is_message_processed(&self, nonce: [u8; 32]) -> Result<bool, Error> {
-        self.contracts
-            .is_message_processed(U256::from_be_bytes(nonce))
-            .await
+        self.with_failover(|contracts| async move {
+            contracts
+                .is_message_processed(U256::from_be_bytes(nonce))
+                .await
+        })
+        .await
     }
 
     pub async fn subscribe_logs(
         &self,
     ) -> Result<Subscription<RpcLog>, RpcError<TransportErrorKind>> {
         let filter = Filter::new()
-            .address(*self.contracts.message_queue_instance.address())
+            .address(Address::from(self.message_queue_address))
             .event_signature(IMessageQueue::MerkleRoot::SIGNATURE_HASH);
 
         self.raw_provider().clone().subscribe_logs(&filter).await
@@ -539,8 +1264,7 @@ impl Contracts {
     pub fn new(
         provider: ProviderType,
         message_queue_address: [u8; 20],
-        max_fee_per_gas: Option<u128>,
-        max_priority_fee_per_gas: Option<u128>,
+        fee_caps: SharedFeeCaps,
     ) -> Result<Self, Error> {
         let message_queue_address = Address::from(message_queue_address);
         let message_queue_instance = IMessageQueue::new(message_queue_address, provider.clone());
@@ -548,8 +1272,7 @@ impl Contracts {
         Ok(Contracts {
             provider,
             message_queue_instance,
-            max_fee_per_gas: max_fee_per_gas.unwrap_or(MAX_FEE_PER_GAS),
-            max_priority_fee_per_gas: max_priority_fee_per_gas.unwrap_or(MAX_PRIORITY_FEE_PER_GAS),
+            fee_caps,
         })
     }
 
@@ -598,92 +1321,83 @@ impl Contracts {
             .map_err(Error::ErrorDuringContractExecution)
     }
 
+    pub async fn verifier_accepts_proof(
+        &self,
+        proof: Bytes,
+        block_number: u32,
+        merkle_root: [u8; 32],
+    ) -> Result<bool, Error> {
+        let verifier = self
+            .message_queue_instance
+            .verifier()
+            .call()
+            .await
+            .map_err(Error::ErrorDuringContractExecution)?;
+
+        let merkle_root = U256::from_be_bytes(merkle_root);
+        let public_inputs = vec![
+            merkle_root >> 64,
+            ((merkle_root & U256::from(u64::MAX)) << 128) | (U256::from(block_number) << 96),
+        ];
+
+        IVerifier::new(verifier, self.provider.clone())
+            .safeVerifyProof(proof, public_inputs)
+            .call()
+            .await
+            .map_err(Error::ErrorDuringContractExecution)
+    }
+
     pub async fn get_approx_balance(&self, address: Address) -> Result<f64, Error> {
         let balance = self.provider.get_balance(address).latest().await?;
         let balance: f64 = balance.into();
         Ok(balance / 1_000_000_000_000_000_000.0)
     }
 
+    /// `submitMerkleRoot` transaction with the gas limit estimated.
     pub async fn provide_merkle_root(
         &self,
         block_number: U256,
         merkle_root: B256,
         proof: Bytes,
-    ) -> Result<PendingTransactionBuilder<Ethereum>, Error> {
-        match self
+    ) -> Result<TransactionRequest, Error> {
+        let call = self
             .message_queue_instance
-            .submitMerkleRoot(block_number, merkle_root, proof.clone())
-            .estimate_gas()
-            .await
-        {
-            Ok(gas_used) => {
-                log::info!("Gas used: {gas_used}");
-                match self
-                    .message_queue_instance
-                    .submitMerkleRoot(block_number, merkle_root, proof.clone())
-                    .send()
-                    .await
-                {
-                    Ok(pending_tx) => Ok(pending_tx),
-                    Err(e) => {
-                        log::error!("Sending error: {e:?}");
-                        if let Some(e) =
-                            e.as_decoded_interface_error::<IMessageQueue::IMessageQueueErrors>()
-                        {
-                            return Err(Error::MessageQueue(e));
-                        }
+            .submitMerkleRoot(block_number, merkle_root, proof);
+        let gas_used = call.estimate_gas().await.map_err(execution_error)?;
+        log::info!("Gas used: {gas_used}");
 
-                        Err(Error::ErrorSendingTransaction(e))
-                    }
-                }
-            }
-
-            Err(e) => {
-                if let Some(e) =
-                    e.as_decoded_interface_error::<IMessageQueue::IMessageQueueErrors>()
-                {
-                    return Err(Error::MessageQueue(e));
-                }
-
-                Err(Error::ErrorDuringContractExecution(e))
-            }
-        }
+        Ok(call.into_transaction_request().with_gas_limit(gas_used))
     }
 
-    pub async fn challenge_root(&self) -> Result<TxHash, Error> {
-        match self
+    pub async fn provide_merkle_root_with_fees(
+        &self,
+        block_number: U256,
+        merkle_root: B256,
+        proof: Bytes,
+        nonce: u64,
+        fees: FeeCaps,
+    ) -> Result<TransactionRequest, Error> {
+        let call = self
             .message_queue_instance
-            .challengeRoot()
-            .estimate_gas()
-            .await
-        {
-            Ok(gas_used) => {
-                log::info!("Gas used: {gas_used}");
-                match self.message_queue_instance.challengeRoot().send().await {
-                    Ok(pending_tx) => Ok(*pending_tx.tx_hash()),
-                    Err(e) => {
-                        log::error!("Sending error: {e:?}");
-                        if let Some(e) =
-                            e.as_decoded_interface_error::<IMessageQueue::IMessageQueueErrors>()
-                        {
-                            return Err(Error::MessageQueue(e));
-                        }
+            .submitMerkleRoot(block_number, merkle_root, proof)
+            .nonce(nonce)
+            .max_fee_per_gas(fees.max_fee_per_gas)
+            .max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
+        let gas_used = call.estimate_gas().await.map_err(execution_error)?;
 
-                        Err(Error::ErrorSendingTransaction(e))
-                    }
-                }
-            }
+        Ok(call.into_transaction_request().with_gas_limit(gas_used))
+    }
 
-            Err(e) => {
-                if let Some(e) =
-                    e.as_decoded_interface_error::<IMessageQueue::IMessageQueueErrors>()
-                {
-                    return Err(Error::MessageQueue(e));
-                }
+    pub async fn challenge_root(&self) -> Result<TransactionRequest, Error> {
+        let call = self.message_queue_instance.challengeRoot();
+        let gas_used = call.estimate_gas().await.map_err(execution_error)?;
+        log::info!("Gas used: {gas_used}");
 
-                Err(Error::ErrorDuringContractExecution(e))
-            }
-        }
+        Ok(call.into_transaction_request().with_gas_limit(gas_used))
+    }
+
+    pub async fn chain_id(&self) -> Result<u64, Error> {
+        self.provider.get_chain_id().await.map_err(|e| e.into())
     }
 
     pub async fn block_number(&self) -> Result<u64, Error> {
@@ -733,6 +1447,136 @@ impl Contracts {
             .collect())
     }
 
+    /// Fee caps of message deliveries: node suggestions, but not less than the configured
+    /// caps.
+    async fn message_fee_caps(&self) -> FeeCaps {
+        let max_priority_fee_per_gas = self.provider.get_max_priority_fee_per_gas().await;
+        let gas_price = self.provider.get_gas_price().await;
+        log::trace!("max_priority_fee_per_gas_chain = {max_priority_fee_per_gas:?}, gas_price_chain = {gas_price:?}");
+
+        let min = self.fee_caps.get();
+        let max_fee_per_gas = gas_price
+            .map(|gas_price| {
+                if gas_price < min.max_fee_per_gas {
+                    min.max_fee_per_gas
+                } else {
+                    gas_price
+                }
+            })
+            .unwrap_or(min.max_fee_per_gas);
+
+        let max_priority_fee_per_gas = max_priority_fee_per_gas
+            .map(|max_priority_fee_per_gas| {
+                if max_priority_fee_per_gas < min.max_priority_fee_per_gas {
+                    min.max_priority_fee_per_gas
+                } else {
+                    max_priority_fee_per_gas
+                }
+            })
+            .unwrap_or(min.max_priority_fee_per_gas);
+
+        FeeCaps {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        }
+    }
+
+    /// `aggregate3` calls which process `messages`. With `allow_failure` unset a failure of
+    /// any call reverts the whole batch.
+    pub fn content_message_calls(
+        &self,
+        block_number: U256,
+        messages: &[ContentMessage],
+        allow_failure: bool,
+    ) -> Vec<IMulticall3::Call3> {
+        messages
+            .iter()
+            .map(|message| {
+                let call = self.message_queue_instance.processMessage(
+                    block_number,
+                    U256::from(message.total_leaves),
+                    U256::from(message.leaf_index),
+                    VaraMessage {
+                        nonce: U256::from_be_bytes(message.nonce),
+                        source: B256::from(message.sender),
+                        destination: Address::from(message.receiver),
+                        payload: Bytes::from(message.payload.clone()),
+                    },
+                    message.proof.iter().copied().map(B256::from).collect(),
+                );
+
+                IMulticall3::Call3 {
+                    target: *self.message_queue_instance.address(),
+                    allowFailure: allow_failure,
+                    callData: call.calldata().clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Simulates processing of `messages` in one `aggregate3` call.
+    pub async fn simulate_content_messages(
+        &self,
+        multicall: Address,
+        block_number: U256,
+        messages: &[ContentMessage],
+    ) -> Result<Vec<BatchOutcome>, Error> {
+        let calls = self.content_message_calls(block_number, messages, true);
+        let results = IMulticall3::new(multicall, self.provider.clone())
+            .aggregate3(calls)
+            .call()
+            .await
+            .map_err(Error::ErrorDuringContractExecution)?;
+
+        Ok(results
+            .into_iter()
+            .map(|result| {
+                if result.success {
+                    return BatchOutcome::Included;
+                }
+
+                match IMessageQueue::IMessageQueueErrors::abi_decode(&result.returnData) {
+                    Ok(IMessageQueue::IMessageQueueErrors::MessageAlreadyProcessed(_)) => {
+                        BatchOutcome::AlreadyProcessed
+                    }
+                    Ok(e) => BatchOutcome::Rejected(format!("{e:?}")),
+                    Err(_) => BatchOutcome::Rejected(format!("revert data {}", result.returnData)),
+                }
+            })
+            .collect())
+    }
+
+    /// `aggregate3` transaction of `calls` with the gas limit estimated.
+    pub async fn multicall_transaction(
+        &self,
+        multicall: Address,
+        calls: Vec<IMulticall3::Call3>,
+        tx_nonce: Option<u64>,
+    ) -> Result<TransactionRequest, Error> {
+        let FeeCaps {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        } = self.message_fee_caps().await;
+
+        let multicall = IMulticall3::new(multicall, self.provider.clone());
+        let mut call = multicall
+            .aggregate3(calls)
+            .max_fee_per_gas(max_fee_per_gas)
+            .max_priority_fee_per_gas(max_priority_fee_per_gas);
+        if let Some(tx_nonce) = tx_nonce {
+            call = call.nonce(tx_nonce);
+        }
+
+        let gas_estimated = call
+            .estimate_gas()
+            .await
+            .map_err(Error::ErrorDuringContractExecution)?;
+
+        Ok(call
+            .into_transaction_request()
+            .with_gas_limit(gas_estimated))
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn provide_content_message(
         &self,
@@ -744,12 +1588,13 @@ impl Contracts {
         destination: Address,
         payload: Bytes,
         proof: Vec<B256>,
-    ) -> Result<TxHash, Error> {
+        tx_nonce: Option<u64>,
+    ) -> Result<TransactionRequest, Error> {
         log::trace!(
             "provide_content_message: block_number = {block_number}, total_leaves = {total_leaves}, leaf_index = {leaf_index}, nonce = {nonce}, source = {source}, destination = {destination}, payload = {payload}, proof = {proof:?}",
         );
 
-        let call = self.message_queue_instance.processMessage(
+        let mut call = self.message_queue_instance.processMessage(
             block_number,
             total_leaves,
             leaf_index,
@@ -761,43 +1606,16 @@ impl Contracts {
             },
             proof,
         );
+        if let Some(tx_nonce) = tx_nonce {
+            call = call.nonce(tx_nonce);
+        }
 
-        let gas_estimated = match call.estimate_gas().await {
-            Ok(gas_estimated) => gas_estimated,
-            Err(e) => {
-                if let Some(e) =
-                    e.as_decoded_interface_error::<IMessageQueue::IMessageQueueErrors>()
-                {
-                    return Err(Error::MessageQueue(e));
-                }
-
-                return Err(Error::ErrorDuringContractExecution(e));
-            }
-        };
-
-        let max_priority_fee_per_gas = self.provider.get_max_priority_fee_per_gas().await;
-        let gas_price = self.provider.get_gas_price().await;
-        log::trace!("max_priority_fee_per_gas_chain = {max_priority_fee_per_gas:?}, gas_price_chain = {gas_price:?}");
-
-        let max_fee_per_gas = gas_price
-            .map(|gas_price| {
-                if gas_price < MAX_FEE_PER_GAS {
-                    MAX_FEE_PER_GAS
-                } else {
-                    gas_price
-                }
-            })
-            .unwrap_or(MAX_FEE_PER_GAS);
+        let gas_estimated = call.estimate_gas().await.map_err(execution_error)?;
 
-        let max_priority_fee_per_gas = max_priority_fee_per_gas
-            .map(|max_priority_fee_per_gas| {
-                if max_priority_fee_per_gas < MAX_PRIORITY_FEE_PER_GAS {
-                    MAX_PRIORITY_FEE_PER_GAS
-                } else {
-                    max_priority_fee_per_gas
-                }
-            })
-            .unwrap_or(MAX_PRIORITY_FEE_PER_GAS);
+        let FeeCaps {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        } = self.message_fee_caps().await;
 
         let call = call
             .max_fee_per_gas(max_fee_per_gas)
@@ -812,19 +1630,9 @@ impl Contracts {
             request.gas_limit(),
         );
 
-        match call.send().await {
-            Ok(pending_tx) => Ok(*pending_tx.tx_hash()),
-            Err(e) => {
-                log::error!("Sending error: {e:?}");
-                if let Some(e) =
-                    e.as_decoded_interface_error::<IMessageQueue::IMessageQueueErrors>()
-                {
-                    return Err(Error::MessageQueue(e));
-                }
-
-                Err(Error::ErrorSendingTransaction(e))
-            }
-        }
+        Ok(call
+            .into_transaction_request()
+            .with_gas_limit(gas_estimated))
     }
 
     pub async fn read_merkle_root(
@@ -910,3 +1718,11 @@ impl Contracts {
         Ok(status)
     }
 }
+
+/// Error of estimating a call, with MessageQueue reverts decoded.
+fn execution_error(e: alloy::contract::Error) -> Error {
+    match e.as_decoded_interface_error::<IMessageQueue::IMessageQueueErrors>() {
+        Some(e) => Error::MessageQueue(e),
+        None => Error::ErrorDuringContractExecution(e),
+    }
+}
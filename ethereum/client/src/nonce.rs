@@ -0,0 +1,458 @@
+//! Local allocation of fee payer nonces.
+//!
+//! Several senders of a process may sign with the same key, e.g. merkle root submitter and
+//! message sender. Asking the node for the pending nonce on every transaction makes them
+//! collide, so nonces are handed out by a single [`NonceManager`] per fee payer instead.
+//!
+//! A nonce which is allocated but never reaches the node stalls every later transaction of
+//! the fee payer. Such gaps are detected by comparing the pending nonce of the node with
+//! the allocated ones and filled with zero-value transfers to the fee payer itself.
+
+use crate::{Error, EthApi, TxHash};
+use alloy::primitives::Address;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex as StdMutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// How often the node is checked for nonce gaps.
+pub const GAP_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Allocated nonce which is neither sent nor released for this long is considered lost.
+const UNSENT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Gas of a plain transfer.
+pub(crate) const TRANSFER_GAS: u64 = 21_000;
+
+static MANAGERS: OnceLock<StdMutex<HashMap<Address, Arc<NonceManager>>>> = OnceLock::new();
+
+/// Hands out nonces of a fee payer and fills the gaps left by transactions that never
+/// reached the node.
+///
+/// When `store` is set, the next nonce to allocate is saved there before a nonce is
+/// handed out, so after a restart nonces of transactions lost on the way to the node are
+/// neither reused nor skipped: they are filled as gaps.
+pub struct NonceManager {
+    address: Address,
+    store: OnceLock<PathBuf>,
+    /// Next nonce loaded from a `store` attached after creation, not yet merged into
+    /// `state`.
+    restored: AtomicU64,
+    state: Mutex<NonceState>,
+    gap_filler: AtomicBool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredNonces {
+    address: Address,
+    next_nonce: u64,
+}
+
+#[derive(Debug, Default)]
+struct NonceState {
+    /// Lowest nonce never handed out.
+    next: u64,
+    /// Allocated nonces of transactions not sent yet, with the allocation time.
+    unsent: BTreeMap<u64, Instant>,
+    /// Nonces whose transactions failed to be sent. They're handed out again first.
+    released: BTreeSet<u64>,
+    /// Gap found on the previous check. A gap has to be seen twice in a row to be filled,
+    /// as endpoints might lag behind each other.
+    suspected_gap: Option<u64>,
+}
+
+impl NonceState {
+    fn allocate(&mut self, pending: u64, now: Instant) -> u64 {
+        // Nonces below the pending one are taken by transactions the node already has.
+        self.released = self.released.split_off(&pending);
+
+        let nonce = match self.released.pop_first() {
+            Some(nonce) => nonce,
+            None => {
+                let nonce = self.next.max(pending);
+                self.next = nonce + 1;
+                nonce
+            }
+        };
+        self.unsent.insert(nonce, now);
+
+        nonce
+    }
+
+    fn sent(&mut self, nonce: u64) {
+        self.unsent.remove(&nonce);
+    }
+
+    fn release(&mut self, nonce: u64) {
+        if self.unsent.remove(&nonce).is_some() {
+            self.released.insert(nonce);
+        }
+    }
+
+    /// The transaction with `nonce` might have reached a node. It's known to be sent only
+    /// once the node counts it as pending, otherwise the nonce stays reserved and gets
+    /// filled as a gap if the transaction never shows up.
+    fn maybe_sent(&mut self, nonce: u64, pending: u64) {
+        if nonce < pending {
+            self.sent(nonce);
+        }
+    }
+
+    /// Returns the gap confirmed by this check. The node reports the first nonce it has no
+    /// transaction for as pending, so only that one is known to be missing.
+    fn gap(&mut self, pending: u64, now: Instant) -> Option<u64> {
+        let in_flight = self
+            .unsent
+            .get(&pending)
+            .is_some_and(|allocated_at| now.duration_since(*allocated_at) < UNSENT_TIMEOUT);
+        if pending >= self.next || in_flight {
+            self.suspected_gap = None;
+            return None;
+        }
+
+        if self.suspected_gap == Some(pending) {
+            return Some(pending);
+        }
+
+        self.suspected_gap = Some(pending);
+        None
+    }
+
+    fn filled(&mut self, nonce: u64) {
+        self.unsent.remove(&nonce);
+        self.released.remove(&nonce);
+        self.suspected_gap = None;
+    }
+}
+
+impl NonceManager {
+    /// Manager of `address` shared by the whole process. The first `store` given is
+    /// used by every caller, it's an error to pass a different one afterwards.
+    pub fn shared(address: Address, store: Option<&Path>) -> Result<Arc<Self>, Error> {
+        let mut managers = MANAGERS
+            .get_or_init(Default::default)
+            .lock()
+            .expect("nonce managers lock is poisoned");
+
+        if let Some(manager) = managers.get(&address) {
+            if let Some(store) = store {
+                manager.attach_store(store)?;
+            }
+
+            return Ok(manager.clone());
+        }
+
+        let manager = Arc::new(Self::new(address, store.map(Path::to_path_buf))?);
+        managers.insert(address, manager.clone());
+
+        Ok(manager)
+    }
+
+    fn new(address: Address, store: Option<PathBuf>) -> Result<Self, Error> {
+        let next = match &store {
+            Some(path) => load(address, path)?,
+            None => 0,
+        };
+
+        Ok(Self {
+            address,
+            store: store.map(OnceLock::from).unwrap_or_default(),
+            restored: AtomicU64::new(0),
+            state: Mutex::new(NonceState {
+                next,
+                ..Default::default()
+            }),
+            gap_filler: AtomicBool::new(false),
+        })
+    }
+
+    /// Starts saving nonces to `store` if they aren't saved anywhere yet. Nonces saved
+    /// there by a previous run are taken into account on the next allocation.
+    fn attach_store(&self, store: &Path) -> Result<(), Error> {
+        match self.store.get() {
+            Some(current) if current == store => Ok(()),
+            Some(_) => Err(Error::NonceManager(format!(
+                "nonces of {} are already managed with another nonce file",
+                self.address
+            ))),
+            None => {
+                let next = load(self.address, store)?;
+                self.restored.fetch_max(next, Ordering::SeqCst);
+                let _ = self.store.set(store.to_path_buf());
+
+                Ok(())
+            }
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Nonce for a new transaction. Has to be reported with [`Self::sent`] or
+    /// [`Self::release`] afterwards.
+    pub async fn allocate(&self, eth_api: &EthApi) -> Result<u64, Error> {
+        let mut state = self.state.lock().await;
+        let pending = eth_api.pending_nonce().await?;
+
+        state.next = state.next.max(self.restored.swap(0, Ordering::SeqCst));
+        let next = state.next;
+        let nonce = state.allocate(pending, Instant::now());
+        if state.next != next {
+            if let Err(err) = self.save(state.next) {
+                state.next = next;
+                state.unsent.remove(&nonce);
+                return Err(err);
+            }
+        }
+
+        Ok(nonce)
+    }
+
+    /// The transaction with `nonce` is accepted by the node.
+    pub async fn sent(&self, nonce: u64) {
+        self.state.lock().await.sent(nonce);
+    }
+
+    /// The transaction with `nonce` was definitely rejected before reaching any node, so
+    /// the nonce is free to reuse.
+    pub async fn release(&self, nonce: u64) {
+        self.state.lock().await.release(nonce);
+    }
+
+    /// It's unknown whether the transaction with `nonce` reached a node, see
+    /// [`Error::TransactionMaybeSent`]. The nonce is resynced with the pending nonce of the
+    /// node and stays reserved until the transaction shows up or the gap is filled.
+    pub async fn maybe_sent(&self, nonce: u64, eth_api: &EthApi) {
+        let mut state = self.state.lock().await;
+        match eth_api.pending_nonce().await {
+            Ok(pending) => state.maybe_sent(nonce, pending),
+            Err(err) => log::warn!(
+                "Failed to resync nonce {nonce} of {} with the node: {err}",
+                self.address
+            ),
+        }
+    }
+
+    /// Cancels the first gap in nonces of the fee payer if it's confirmed by this check.
+    pub async fn fill_gap(&self, eth_api: &EthApi) -> Result<Option<(u64, TxHash)>, Error> {
+        let mut state = self.state.lock().await;
+        let pending = eth_api.pending_nonce().await?;
+        let Some(nonce) = state.gap(pending, Instant::now()) else {
+            return Ok(None);
+        };
+
+        let fees = eth_api.estimate_fee_caps().await?;
+        let tx_hash = eth_api.cancel_nonce(nonce, fees).await?;
+        state.filled(nonce);
+
+        Ok(Some((nonce, tx_hash)))
+    }
+
+    /// Starts filling gaps in the background. Only the first call per manager has effect.
+    pub fn spawn_gap_filler(self: &Arc<Self>, eth_api: EthApi) {
+        if self.gap_filler.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        tokio::spawn(gap_filler(self.clone(), eth_api));
+    }
+
+    fn save(&self, next_nonce: u64) -> Result<(), Error> {
+        let Some(path) = self.store.get() else {
+            return Ok(());
+        };
+
+        let contents = serde_json::to_vec(&StoredNonces {
+            address: self.address,
+            next_nonce,
+        })
+        .map_err(|e| Error::NonceManager(e.to_string()))?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, contents)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|e| {
+                Error::NonceManager(format!("failed to save nonces to {}: {e}", path.display()))
+            })
+    }
+}
+
+fn load(address: Address, path: &Path) -> Result<u64, Error> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => {
+            return Err(Error::NonceManager(format!(
+                "failed to read nonces from {}: {err}",
+                path.display()
+            )))
+        }
+    };
+
+    let stored: StoredNonces = serde_json::from_slice(&contents)
+        .map_err(|e| Error::NonceManager(format!("invalid nonce file {}: {e}", path.display())))?;
+    if stored.address != address {
+        return Err(Error::NonceManager(format!(
+            "nonce file {} belongs to {}, not {address}",
+            path.display(),
+            stored.address
+        )));
+    }
+
+    Ok(stored.next_nonce)
+}
+
+async fn gap_filler(manager: Arc<NonceManager>, mut eth_api: EthApi) {
+    let address = manager.address;
+    let mut interval = tokio::time::interval(GAP_CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match manager.fill_gap(&eth_api).await {
+            Ok(Some((nonce, tx_hash))) => {
+                log::warn!("Filled gap at nonce {nonce} of {address} with transaction {tx_hash}")
+            }
+
+            Ok(None) => {}
+
+            Err(err) => {
+                log::warn!("Failed to check nonces of {address} for gaps: {err}");
+                if err.is_endpoint_failure() {
+                    match eth_api.reconnect().await {
+                        Ok(reconnected) => eth_api = reconnected,
+                        Err(err) => log::warn!("Failed to reconnect to Ethereum: {err}"),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_unique_nonces_and_reuses_released_ones() {
+        let now = Instant::now();
+        let mut state = NonceState::default();
+
+        assert_eq!(state.allocate(5, now), 5);
+        // The node doesn't count unsent transactions as pending.
+        assert_eq!(state.allocate(5, now), 6);
+        assert_eq!(state.allocate(5, now), 7);
+
+        state.sent(5);
+        state.release(6);
+        assert_eq!(state.allocate(6, now), 6);
+
+        // Released nonce got taken meanwhile, e.g. the send error was spurious.
+        state.release(7);
+        assert_eq!(state.allocate(8, now), 8);
+        assert!(state.released.is_empty());
+
+        // Another process used the key.
+        assert_eq!(state.allocate(20, now), 20);
+        assert_eq!(state.next, 21);
+    }
+
+    #[test]
+    fn keeps_maybe_sent_nonces_reserved() {
+        let now = Instant::now();
+        let mut state = NonceState::default();
+        assert_eq!(state.allocate(0, now), 0);
+        assert_eq!(state.allocate(0, now), 1);
+
+        // The node hasn't got the transaction, so the nonce is neither reused nor sent.
+        state.maybe_sent(0, 0);
+        state.release(1);
+        assert_eq!(state.allocate(0, now), 1);
+        assert!(state.unsent.contains_key(&0));
+        assert_eq!(state.gap(0, now + UNSENT_TIMEOUT), None);
+        assert_eq!(state.gap(0, now + UNSENT_TIMEOUT), Some(0));
+
+        state.maybe_sent(1, 2);
+        assert!(!state.unsent.contains_key(&1));
+    }
+
+    #[test]
+    fn confirms_gaps_on_second_check() {
+        let now = Instant::now();
+        let mut state = NonceState::default();
+        for _ in 0..3 {
+            state.allocate(0, now);
+        }
+
+        // Nonce 0 is being sent.
+        assert_eq!(state.gap(0, now), None);
+        assert_eq!(state.gap(0, now), None);
+
+        state.sent(0);
+        state.release(1);
+        state.sent(2);
+        assert_eq!(state.gap(1, now), None);
+        assert_eq!(state.gap(1, now), Some(1));
+        state.filled(1);
+        assert!(state.released.is_empty());
+
+        assert_eq!(state.gap(3, now), None);
+        assert_eq!(state.gap(3, now), None);
+
+        // Allocated nonce which is never reported is filled after a while.
+        let nonce = state.allocate(3, now);
+        let later = now + UNSENT_TIMEOUT;
+        assert_eq!(state.gap(nonce, later), None);
+        assert_eq!(state.gap(nonce, later), Some(nonce));
+    }
+
+    #[test]
+    fn persists_next_nonce() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nonces.json");
+        let address = Address::repeat_byte(1);
+
+        let manager = NonceManager::new(address, Some(path.clone())).unwrap();
+        manager.save(42).unwrap();
+        assert_eq!(load(address, &path).unwrap(), 42);
+
+        let manager = NonceManager::new(address, Some(path.clone())).unwrap();
+        assert_eq!(manager.state.try_lock().unwrap().next, 42);
+        assert!(matches!(
+            NonceManager::new(Address::repeat_byte(2), Some(path.clone())),
+            Err(Error::NonceManager(_))
+        ));
+    }
+
+    #[test]
+    fn attaches_store_to_shared_manager() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nonces.json");
+        let address = Address::repeat_byte(3);
+        NonceManager::new(address, Some(path.clone()))
+            .unwrap()
+            .save(42)
+            .unwrap();
+
+        let manager = NonceManager::shared(address, None).unwrap();
+        let attached = NonceManager::shared(address, Some(&path)).unwrap();
+        assert!(Arc::ptr_eq(&manager, &attached));
+        assert_eq!(manager.store.get(), Some(&path));
+        assert_eq!(manager.restored.load(Ordering::SeqCst), 42);
+
+        assert!(NonceManager::shared(address, None).is_ok());
+        assert!(NonceManager::shared(address, Some(&path)).is_ok());
+        assert!(matches!(
+            NonceManager::shared(address, Some(&dir.path().join("other.json"))),
+            Err(Error::NonceManager(_))
+        ));
+    }
+}
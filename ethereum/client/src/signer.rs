@@ -0,0 +1,392 @@
+use crate::Error;
+use alloy::{
+    network::{EthereumWallet, TransactionBuilder, TxSigner},
+    primitives::{Address, Bytes, Signature, B256, U256},
+    rpc::types::TransactionRequest,
+    signers::{self, local::PrivateKeySigner},
+};
+use alloy_consensus::{
+    transaction::SignerRecoverable, SignableTransaction, Transaction, TxEnvelope,
+};
+use alloy_eips::{eip2718::Decodable2718, Typed2718};
+use async_trait::async_trait;
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Prefix of the fee payer value selecting a [`RemoteSigner`]: `remote:<url>`, or
+/// `remote:<url>#<address>` when the signer holds several keys.
+pub const REMOTE_SIGNER_PREFIX: &str = "remote:";
+
+const REMOTE_SIGNER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Signs every transaction sent by [`EthApi`](crate::EthApi): merkle roots, messages and
+/// root challenges alike.
+#[derive(Clone)]
+pub struct Signer {
+    address: Address,
+    wallet: EthereumWallet,
+}
+
+impl Signer {
+    pub fn local(private_key: &str) -> Result<Self, Error> {
+        let pk = B256::from(U256::from_str(private_key).map_err(|_| Error::WrongPrivateKey)?);
+        let signer = PrivateKeySigner::from_bytes(&pk).map_err(|_| Error::WrongPrivateKey)?;
+
+        Ok(Self::new(signer))
+    }
+
+    pub fn random() -> Self {
+        Self::new(PrivateKeySigner::random())
+    }
+
+    pub fn remote(signer: RemoteSigner) -> Self {
+        Self::new(signer)
+    }
+
+    /// Connects to a [`RemoteSigner`] for values with [`REMOTE_SIGNER_PREFIX`], otherwise
+    /// treats the value as a hex private key.
+    pub async fn from_spec(spec: &str) -> Result<Self, Error> {
+        let Some(remote) = spec.strip_prefix(REMOTE_SIGNER_PREFIX) else {
+            return Self::local(spec);
+        };
+
+        let (url, address) = match remote.split_once('#') {
+            Some((url, address)) => (
+                url,
+                Some(Address::from_str(address).map_err(|_| Error::WrongAddress)?),
+            ),
+            None => (remote, None),
+        };
+
+        RemoteSigner::connect(url, address).await.map(Self::remote)
+    }
+
+    pub fn is_remote(spec: &str) -> bool {
+        spec.starts_with(REMOTE_SIGNER_PREFIX)
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub(crate) fn wallet(&self) -> &EthereumWallet {
+        &self.wallet
+    }
+
+    fn new<S: TxSigner<Signature> + Send + Sync + 'static>(signer: S) -> Self {
+        Self {
+            address: signer.address(),
+            wallet: EthereumWallet::from(signer),
+        }
+    }
+}
+
+/// Signer keeping the key outside of the process, e.g. in a HSM, and reached over the
+/// JSON-RPC API of web3signer: `eth_accounts` and `eth_signTransaction`.
+///
+/// The signed transaction is checked to be the one requested and signed by the key of
+/// `address` before its signature is used.
+#[derive(Clone)]
+pub struct RemoteSigner {
+    client: reqwest::Client,
+    url: Url,
+    address: Address,
+    next_id: Arc<AtomicU64>,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RemoteSigner {
+    /// Connects to the signer and checks that it holds the key of `address`. Without
+    /// `address` the signer has to hold exactly one key.
+    pub async fn connect(url: &str, address: Option<Address>) -> Result<Self, Error> {
+        let mut signer = Self {
+            client: reqwest::Client::builder()
+                .timeout(REMOTE_SIGNER_TIMEOUT)
+                .build()
+                .map_err(|e| Error::RemoteSigner(e.to_string()))?,
+            url: Url::parse(url).map_err(|_| Error::WrongNodeUrl)?,
+            address: Address::ZERO,
+            next_id: Arc::new(AtomicU64::new(1)),
+        };
+
+        let accounts: Vec<Address> = signer
+            .call("eth_accounts", json!([]))
+            .await
+            .map_err(Error::RemoteSigner)?;
+        signer.address = match address {
+            Some(address) if accounts.contains(&address) => address,
+            Some(address) => {
+                return Err(Error::RemoteSigner(format!(
+                    "signer doesn't hold the key of {address}"
+                )))
+            }
+            None => match accounts[..] {
+                [address] => address,
+                _ => {
+                    return Err(Error::RemoteSigner(format!(
+                    "signer holds {} keys, select one with {REMOTE_SIGNER_PREFIX}<url>#<address>",
+                    accounts.len()
+                )))
+                }
+            },
+        };
+
+        Ok(signer)
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let response: RpcResponse<T> = self
+            .client
+            .post(self.url.clone())
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("{method} failed: {e}"))?
+            .json()
+            .await
+            .map_err(|e| format!("{method} returned invalid response: {e}"))?;
+
+        match response {
+            RpcResponse {
+                error: Some(RpcError { code, message }),
+                ..
+            } => Err(format!("{method} failed with code {code}: {message}")),
+            RpcResponse {
+                result: Some(result),
+                ..
+            } => Ok(result),
+            _ => Err(format!("{method} returned no result")),
+        }
+    }
+}
+
+#[async_trait]
+impl TxSigner<Signature> for RemoteSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> signers::Result<Signature> {
+        let request = transaction_request(self.address, tx).map_err(signers::Error::other)?;
+        let raw: Bytes = self
+            .call("eth_signTransaction", json!([request]))
+            .await
+            .map_err(signers::Error::other)?;
+
+        let signed = TxEnvelope::decode_2718(&mut raw.as_ref()).map_err(|e| {
+            signers::Error::other(format!("signer returned invalid transaction: {e}"))
+        })?;
+        if signed.signature_hash() != tx.signature_hash() {
+            return Err(signers::Error::other(
+                "signer returned a different transaction than requested",
+            ));
+        }
+        let signer = signed.recover_signer().map_err(|e| {
+            signers::Error::other(format!("signer returned invalid signature: {e}"))
+        })?;
+        if signer != self.address {
+            return Err(signers::Error::other(format!(
+                "signer returned a transaction signed by {signer} instead of {}",
+                self.address
+            )));
+        }
+
+        Ok(*signed.signature())
+    }
+}
+
+fn transaction_request(
+    from: Address,
+    tx: &dyn SignableTransaction<Signature>,
+) -> Result<TransactionRequest, String> {
+    if tx.is_eip4844() || tx.is_eip7702() {
+        return Err(format!("transaction type {} isn't supported", tx.ty()));
+    }
+
+    let mut request = TransactionRequest::default()
+        .with_from(from)
+        .with_kind(tx.kind())
+        .with_nonce(tx.nonce())
+        .with_gas_limit(tx.gas_limit())
+        .with_value(tx.value())
+        .with_input(tx.input().clone());
+    request.transaction_type = Some(tx.ty());
+    if let Some(chain_id) = tx.chain_id() {
+        request.set_chain_id(chain_id);
+    }
+    if let Some(access_list) = tx.access_list() {
+        request.set_access_list(access_list.clone());
+    }
+    match (tx.is_dynamic_fee(), tx.max_priority_fee_per_gas()) {
+        (true, Some(max_priority_fee_per_gas)) => {
+            request.set_max_fee_per_gas(tx.max_fee_per_gas());
+            request.set_max_priority_fee_per_gas(max_priority_fee_per_gas);
+        }
+        _ => request.set_gas_price(tx.gas_price().unwrap_or_else(|| tx.max_fee_per_gas())),
+    }
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        network::TxSignerSync,
+        primitives::{address, TxKind},
+    };
+    use alloy_consensus::TxEip1559;
+    use alloy_eips::eip2718::Encodable2718;
+    use axum::{extract::State, routing::post, Json, Router};
+    use serde_json::Value;
+    use tokio::net::TcpListener;
+
+    const KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+    #[derive(Clone)]
+    struct MockSigner {
+        wallet: EthereumWallet,
+        accounts: Vec<Address>,
+        /// Signs a transaction with a different nonce than requested.
+        tamper: bool,
+    }
+
+    const OTHER_KEY: &str = "0x8da4ef21b864d2cc526dbdb2a120bd2874c36c9d0a1fb7f8c63d7f7a8b41de8f";
+
+    async fn handle(State(mock): State<MockSigner>, Json(request): Json<Value>) -> Json<Value> {
+        let result = match request["method"].as_str() {
+            Some("eth_accounts") => json!(mock.accounts),
+            Some("eth_signTransaction") => {
+                let mut tx: TransactionRequest =
+                    serde_json::from_value(request["params"][0].clone()).unwrap();
+                if mock.tamper {
+                    tx.nonce = tx.nonce.map(|nonce| nonce + 1);
+                }
+                let signed = tx.build(&mock.wallet).await.unwrap();
+                json!(Bytes::from(signed.encoded_2718()))
+            }
+            _ => {
+                return Json(json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": { "code": -32601, "message": "method not found" },
+                }))
+            }
+        };
+
+        Json(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }))
+    }
+
+    async fn mock_signer(key: &str, accounts: Vec<Address>, tamper: bool) -> String {
+        let mock = MockSigner {
+            wallet: EthereumWallet::from(key.parse::<PrivateKeySigner>().unwrap()),
+            accounts,
+            tamper,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let router = Router::new().route("/", post(handle)).with_state(mock);
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        url
+    }
+
+    fn transaction() -> TxEip1559 {
+        TxEip1559 {
+            chain_id: 1,
+            nonce: 7,
+            gas_limit: 500_000,
+            max_fee_per_gas: 2_000_000_000,
+            max_priority_fee_per_gas: 500_000_000,
+            to: TxKind::Call(address!("0x1111111111111111111111111111111111111111")),
+            value: U256::ZERO,
+            access_list: Default::default(),
+            input: Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]),
+        }
+    }
+
+    #[tokio::test]
+    async fn signs_transactions_remotely() {
+        let local = KEY.parse::<PrivateKeySigner>().unwrap();
+        let url = mock_signer(KEY, vec![local.address()], false).await;
+
+        let signer = Signer::from_spec(&format!("{REMOTE_SIGNER_PREFIX}{url}"))
+            .await
+            .unwrap();
+        assert_eq!(signer.address(), local.address());
+
+        let remote = RemoteSigner::connect(&url, Some(local.address()))
+            .await
+            .unwrap();
+        let mut tx = transaction();
+        let signature = remote.sign_transaction(&mut tx).await.unwrap();
+        assert_eq!(signature, local.sign_transaction_sync(&mut tx).unwrap());
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_keys_and_tampered_transactions() {
+        let local = KEY.parse::<PrivateKeySigner>().unwrap();
+        let other = address!("0x2222222222222222222222222222222222222222");
+
+        let url = mock_signer(KEY, vec![local.address(), other], false).await;
+        assert!(matches!(
+            RemoteSigner::connect(&url, None).await,
+            Err(Error::RemoteSigner(_))
+        ));
+        let unknown = address!("0x3333333333333333333333333333333333333333");
+        assert!(matches!(
+            RemoteSigner::connect(&url, Some(unknown)).await,
+            Err(Error::RemoteSigner(_))
+        ));
+
+        let url = mock_signer(KEY, vec![local.address()], true).await;
+        let remote = RemoteSigner::connect(&url, None).await.unwrap();
+        let err = remote
+            .sign_transaction(&mut transaction())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("different transaction"));
+
+        // Signature by another key over the requested transaction.
+        let url = mock_signer(OTHER_KEY, vec![local.address()], false).await;
+        let remote = RemoteSigner::connect(&url, None).await.unwrap();
+        let err = remote
+            .sign_transaction(&mut transaction())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("signed by"));
+    }
+}
@@ -105,11 +105,13 @@ pub struct BlockFinalityProof {
     pub message: Vec<u8>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BranchNodeData {
     pub data: Vec<u8>,
     pub target_child: u8,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StorageInclusionProof {
     pub address: Vec<u8>,
 
@@ -0,0 +1,72 @@
+use keccak_hash::keccak;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Sources of the prover the circuits are built from.
+const SOURCE_DIR: &str = "src";
+/// Sources of the workspace the prover is built in: the gadget crates it depends on and the
+/// manifest pinning plonky2. They're missing when the crate is built outside of the workspace.
+const WORKSPACE_SOURCES: &[&str] = &["../circuits", "../Cargo.toml"];
+
+fn main() {
+    source_hash();
+}
+
+/// Sets `PROVER_SOURCE_HASH` to the hash of the circuit sources, so the circuit cache ignores
+/// entries built from other sources even when the prover version is the same.
+fn source_hash() {
+    if !Path::new(SOURCE_DIR).is_dir() {
+        panic!("Prover sources aren't found at {SOURCE_DIR} to hash them for the circuit cache");
+    }
+
+    let mut files = vec![];
+    let workspace_sources = WORKSPACE_SOURCES
+        .iter()
+        .filter(|source| Path::new(source).exists());
+    for source in std::iter::once(&SOURCE_DIR).chain(workspace_sources) {
+        println!("cargo:rerun-if-changed={source}");
+        collect_files(Path::new(source), &mut files);
+    }
+    files.sort();
+
+    let mut data = vec![];
+    for file in files {
+        let contents =
+            fs::read(&file).unwrap_or_else(|e| panic!("Failed to read {}: {e}", file.display()));
+        let path = file.to_string_lossy();
+        data.extend_from_slice(&(path.len() as u64).to_le_bytes());
+        data.extend_from_slice(path.as_bytes());
+        data.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+        data.extend_from_slice(&contents);
+    }
+
+    println!("cargo:rustc-env=PROVER_SOURCE_HASH={:x}", keccak(data));
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return;
+    }
+
+    let entries =
+        fs::read_dir(path).unwrap_or_else(|e| panic!("Failed to read {}: {e}", path.display()));
+    for entry in entries {
+        let path = entry
+            .unwrap_or_else(|e| panic!("Failed to read {}: {e}", path.display()))
+            .path();
+        if path.is_dir() {
+            if path.file_name().is_some_and(|name| name == "target") {
+                continue;
+            }
+            collect_files(&path, files);
+        } else if matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("rs" | "toml")
+        ) {
+            files.push(path);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! ### Statistics of the circuits, used to size prover machines.
+
+use crate::{
+    common::{circuit_build_time, targets::TargetSet, ProofWithCircuitData},
+    final_proof::{message_sent::MessageSent, FinalProof},
+    latest_validator_set::{next_validator_set::NextValidatorSet, LatestValidatorSet},
+    prelude::*,
+    proving::{BlockFinality, ExportedProofWithCircuitData, GenesisConfig, StorageInclusion},
+};
+use plonky2::plonk::circuit_data::CommonCircuitData;
+use serde::Serialize;
+use std::{fs, time::Instant};
+
+/// Data required to prove every circuit once: the first authority set change after
+/// genesis and a message queue root in a block finalized by the next authority set.
+#[derive(Clone)]
+pub struct Fixture {
+    pub genesis_config: GenesisConfig,
+    /// Finality of a block in the last epoch of the genesis authority set.
+    pub current_epoch_block_finality: BlockFinality,
+    /// Inclusion of the next authority set hash into storage of the block above.
+    pub next_validator_set_inclusion_proof: StorageInclusion,
+    pub next_validator_set_data: Vec<u8>,
+    /// Finality of the block containing the message queue root.
+    pub block_finality: BlockFinality,
+    /// Headers from the block containing the message queue root to the finalized one.
+    pub headers: Vec<GearHeader>,
+    pub message_inclusion_proof: StorageInclusion,
+    pub message_contents: Vec<u8>,
+}
+
+/// Shape of a plonky2 circuit.
+#[derive(Clone, Debug, Serialize)]
+pub struct CircuitShape {
+    /// Count of gates before padding, if known.
+    pub gates: Option<usize>,
+    /// Log2 of the count of rows after padding.
+    pub degree_bits: usize,
+    /// Maximal degree of the constraints.
+    pub constraint_degree: usize,
+    pub gate_types: Vec<String>,
+    pub public_inputs: usize,
+}
+
+impl CircuitShape {
+    pub fn new(common: &CommonCircuitData<F, D>, gates: Option<usize>) -> Self {
+        Self {
+            gates,
+            degree_bits: common.degree_bits(),
+            constraint_degree: common.constraint_degree(),
+            gate_types: common.gates.iter().map(|gate| gate.0.id()).collect(),
+            public_inputs: common.num_public_inputs,
+        }
+    }
+}
+
+/// Resources spent on proving.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Usage {
+    /// Time spent building circuits, summed over threads. Circuits loaded from the circuit
+    /// cache aren't built.
+    pub build_time_ms: u128,
+    /// Rest of the elapsed time, spent on generating proofs.
+    pub proving_time_ms: u128,
+    /// Peak resident set size of the process while proving. Not reported if the peak of
+    /// the process can't be reset beforehand, which is supported on Linux only.
+    pub peak_memory_bytes: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CircuitStats {
+    pub circuit: &'static str,
+    /// Not known for circuits proven outside of plonky2.
+    #[serde(flatten)]
+    pub shape: Option<CircuitShape>,
+    /// Includes proving of the circuits verified recursively by this one.
+    #[serde(flatten)]
+    pub usage: Usage,
+}
+
+/// Proves every circuit from the innermost outwards. Returns their statistics and the final
+/// proof exported to `gnark-wrapper`, which is proven by the caller.
+pub fn run(fixture: Fixture) -> (Vec<CircuitStats>, ExportedProofWithCircuitData) {
+    let mut stats = Vec::with_capacity(5);
+
+    let block_finality = fixture.current_epoch_block_finality.clone();
+    prove_circuit(&mut stats, "block_finality", || block_finality.prove());
+
+    let inclusion_proof = fixture.next_validator_set_inclusion_proof.clone();
+    prove_circuit(&mut stats, "storage_inclusion", || inclusion_proof.prove());
+
+    let latest_validator_set = LatestValidatorSet {
+        change_proof: NextValidatorSet {
+            current_epoch_block_finality: fixture.current_epoch_block_finality,
+            next_validator_set_inclusion_proof: fixture.next_validator_set_inclusion_proof,
+            next_validator_set_storage_data: fixture.next_validator_set_data,
+        },
+    };
+    let latest_validator_set = prove_circuit(&mut stats, "latest_validator_set", || {
+        latest_validator_set.prove_genesis(fixture.genesis_config)
+    });
+
+    let final_proof = FinalProof {
+        current_validator_set_verifier_data: latest_validator_set.circuit_data().clone(),
+        current_validator_set_proof: latest_validator_set.proof(),
+        message_sent: MessageSent {
+            block_finality: fixture.block_finality,
+            headers: fixture.headers,
+            inclusion_proof: fixture.message_inclusion_proof,
+            message_storage_data: fixture.message_contents,
+        },
+    };
+    let final_proof = prove_circuit(&mut stats, "final_proof", || {
+        final_proof.prove(fixture.genesis_config)
+    });
+
+    let (wrapped, usage) = measure(|| final_proof.wrap_bn128());
+    stats.push(CircuitStats {
+        circuit: "bn128_wrap",
+        shape: Some(CircuitShape::new(
+            &wrapped.circuit_data.common,
+            Some(wrapped.num_gates),
+        )),
+        usage,
+    });
+
+    (stats, wrapped.export())
+}
+
+fn prove_circuit<TS: TargetSet>(
+    stats: &mut Vec<CircuitStats>,
+    circuit: &'static str,
+    prove: impl FnOnce() -> ProofWithCircuitData<TS>,
+) -> ProofWithCircuitData<TS> {
+    log::info!("Proving {circuit}...");
+
+    let (proof, usage) = measure(prove);
+    stats.push(CircuitStats {
+        circuit,
+        shape: Some(CircuitShape::new(
+            &proof.circuit_data().common,
+            proof.num_gates(),
+        )),
+        usage,
+    });
+
+    proof
+}
+
+/// Runs `prove` and reports its time and peak memory.
+pub fn measure<T>(prove: impl FnOnce() -> T) -> (T, Usage) {
+    // Peak is tracked by the kernel for the whole process, so it's reset to the current
+    // resident set size first. Writing "5" to `clear_refs` is supported since Linux 4.0.
+    // Without the reset the peak may come from anything proven before.
+    let peak_reset = fs::write("/proc/self/clear_refs", "5").is_ok();
+
+    let build_time = circuit_build_time();
+    let now = Instant::now();
+    let result = prove();
+    let elapsed = now.elapsed();
+    let build_time = circuit_build_time() - build_time;
+
+    (
+        result,
+        Usage {
+            build_time_ms: build_time.as_millis(),
+            proving_time_ms: elapsed.saturating_sub(build_time).as_millis(),
+            peak_memory_bytes: peak_reset.then(peak_memory).flatten(),
+        },
+    )
+}
+
+fn peak_memory() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let kib = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+
+    Some(kib * 1024)
+}
@@ -21,7 +21,7 @@ mod single_validator_sign;
 
 use crate::{
     common::{
-        array_to_bits, common_data_for_recursion,
+        array_to_bits, build_circuit, common_data_for_recursion,
         targets::{
             impl_parsable_target_set, impl_target_set, Blake2Target, ParsableTargetSet, TargetSet,
             VerifierDataTarget,
@@ -438,7 +438,7 @@ impl SignComposition {
             )
             .expect("Failed to build circuit");
 
-        let cyclic_circuit_data = builder.build::<C>();
+        let cyclic_circuit_data = build_circuit::<C>(builder);
 
         pw.set_verifier_data_target(&verifier_data_target, &cyclic_circuit_data.verifier_only);
 
@@ -0,0 +1,573 @@
+//! ### On-disk cache of the circuits that are built the same way for every proof.
+//!
+//! Building recursive circuits takes minutes, so `LatestValidatorSet` and `FinalProof` circuit
+//! data is stored in the directory passed to [`init`] and loaded from it on the next start.
+//! An entry is keyed by everything the circuit is built from: the circuits it verifies and,
+//! for the circuits embedding it, `GenesisConfig`. Entries written by another prover version
+//! or by a prover built from other circuit sources are ignored.
+
+use crate::{
+    common::build_circuit,
+    prelude::*,
+    proving::GenesisConfig,
+    serialization::{GateSerializer, GeneratorSerializer, ReadAdapter},
+};
+use anyhow::{anyhow, Context};
+use keccak_hash::keccak;
+use plonky2::{
+    field::types::{Field, PrimeField64},
+    hash::hash_types::HashOut,
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitData, VerifierCircuitData},
+    },
+    util::serialization::{Buffer, IoResult, Read as _},
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::Instant,
+};
+
+/// Version of the entry layout.
+const CACHE_VERSION: u32 = 2;
+const PROVER_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Hash of the sources circuits are built from, set by the build script.
+const SOURCE_HASH: &str = env!("PROVER_SOURCE_HASH");
+const EXTENSION: &str = "circuit";
+
+static CACHE: OnceLock<CircuitCache> = OnceLock::new();
+
+/// Targets of a cached circuit which witness is set to.
+pub(crate) trait CachedTargets: Sized {
+    fn write(&self, dst: &mut Vec<u8>) -> IoResult<()>;
+    fn read(src: &mut Buffer) -> IoResult<Self>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey([u8; 32]);
+
+impl CacheKey {
+    /// Key of `circuit` built to verify proofs of `inner` circuits. `genesis_config` is set
+    /// for the circuits that embed it as a constant.
+    pub fn new(
+        circuit: &str,
+        inner: &[&VerifierCircuitData<F, C, D>],
+        genesis_config: Option<GenesisConfig>,
+    ) -> Self {
+        let mut data = vec![];
+        write_string(&mut data, circuit).expect("Write to Vec doesn't fail");
+        write_string(&mut data, PROVER_VERSION).expect("Write to Vec doesn't fail");
+        write_string(&mut data, SOURCE_HASH).expect("Write to Vec doesn't fail");
+        data.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+
+        for circuit_data in inner {
+            data.extend(digest_to_bytes(&circuit_data.verifier_only.circuit_digest));
+            data.extend(
+                circuit_data
+                    .common
+                    .to_bytes(&GateSerializer)
+                    .expect("Failed to serialize common circuit data"),
+            );
+        }
+
+        if let Some(genesis_config) = genesis_config {
+            data.extend_from_slice(&genesis_config.authority_set_id.to_le_bytes());
+            data.extend_from_slice(&genesis_config.authority_set_hash);
+        }
+
+        Self(keccak(data).0)
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// Circuit data along with the targets to set witness to.
+pub(crate) struct CachedCircuit<T> {
+    pub circuit_data: Arc<CircuitData<F, C, D>>,
+    pub targets: T,
+    /// Count of gates before padding.
+    pub num_gates: usize,
+}
+
+struct CircuitCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<CacheKey, Arc<Entry>>>,
+}
+
+struct Header {
+    circuit: String,
+    key: CacheKey,
+    digest: HashOut<F>,
+    num_gates: usize,
+}
+
+struct Entry {
+    header: Header,
+    targets: Vec<u8>,
+    circuit_data: Arc<CircuitData<F, C, D>>,
+}
+
+/// Loads the circuits cached in `path`, creating the directory if it doesn't exist. Returns
+/// the count of loaded circuits.
+///
+/// Fails if an entry is corrupted or its circuit data doesn't match the digest it was
+/// cached with, so it's never used to build proofs.
+pub fn init(path: &Path) -> anyhow::Result<usize> {
+    fs::create_dir_all(path)
+        .with_context(|| format!("Failed to create circuit cache {}", path.display()))?;
+
+    let mut entries = HashMap::new();
+    for dir_entry in fs::read_dir(path)? {
+        let entry_path = dir_entry?.path();
+        if entry_path.extension().and_then(|ext| ext.to_str()) != Some(EXTENSION) {
+            continue;
+        }
+
+        let now = Instant::now();
+        let Some(entry) = read_entry(&entry_path)? else {
+            log::info!(
+                "Ignoring circuit cache entry {} written by another prover build",
+                entry_path.display()
+            );
+            continue;
+        };
+
+        log::info!(
+            "Loaded cached {} circuit from {} in {}ms",
+            entry.header.circuit,
+            entry_path.display(),
+            now.elapsed().as_millis()
+        );
+        entries.insert(entry.header.key, Arc::new(entry));
+    }
+
+    let loaded = entries.len();
+    CACHE
+        .set(CircuitCache {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+        })
+        .map_err(|_| anyhow!("Circuit cache is already initialized"))?;
+
+    Ok(loaded)
+}
+
+/// Takes the circuit from cache or builds it with `build` and caches the result. Circuits are
+/// always built when cache isn't initialized.
+pub(crate) fn get_or_build<T: CachedTargets>(
+    circuit: &str,
+    key: CacheKey,
+    build: impl FnOnce() -> (CircuitBuilder<F, D>, T),
+) -> CachedCircuit<T> {
+    if let Some(cache) = CACHE.get() {
+        let entry = cache
+            .entries
+            .lock()
+            .expect("Lock isn't poisoned")
+            .get(&key)
+            .cloned();
+        if let Some(entry) = entry {
+            match T::read(&mut Buffer::new(&entry.targets)) {
+                Ok(targets) => {
+                    return CachedCircuit {
+                        circuit_data: entry.circuit_data.clone(),
+                        targets,
+                        num_gates: entry.header.num_gates,
+                    }
+                }
+                Err(_) => {
+                    reject(key, "its targets can't be decoded");
+                }
+            }
+        }
+    }
+
+    let (builder, targets) = build();
+    let num_gates = builder.num_gates();
+    let circuit_data = Arc::new(build_circuit::<C>(builder));
+
+    if let Some(cache) = CACHE.get() {
+        let entry = Entry {
+            header: Header {
+                circuit: circuit.to_string(),
+                key,
+                digest: circuit_data.verifier_only.circuit_digest,
+                num_gates,
+            },
+            targets: vec![],
+            circuit_data: circuit_data.clone(),
+        };
+
+        match cache.insert(entry, &targets) {
+            Ok(path) => log::info!("Cached {circuit} circuit to {}", path.display()),
+            Err(e) => log::warn!("Failed to cache {circuit} circuit: {e:?}"),
+        }
+    }
+
+    CachedCircuit {
+        circuit_data,
+        targets,
+        num_gates,
+    }
+}
+
+/// Removes the circuit from cache so it's built again on the next [`get_or_build`]. Returns
+/// `false` if the circuit isn't cached.
+pub(crate) fn reject(key: CacheKey, reason: &str) -> bool {
+    let Some(cache) = CACHE.get() else {
+        return false;
+    };
+
+    let Some(entry) = cache
+        .entries
+        .lock()
+        .expect("Lock isn't poisoned")
+        .remove(&key)
+    else {
+        return false;
+    };
+
+    let path = entry_path(&cache.path, &entry.header);
+    log::error!(
+        "Cached {} circuit {} is rejected: {reason}. It will be built again",
+        entry.header.circuit,
+        path.display()
+    );
+    if let Err(e) = fs::remove_file(&path) {
+        log::warn!("Failed to remove {}: {e}", path.display());
+    }
+
+    true
+}
+
+impl CircuitCache {
+    fn insert<T: CachedTargets>(&self, mut entry: Entry, targets: &T) -> anyhow::Result<PathBuf> {
+        targets
+            .write(&mut entry.targets)
+            .map_err(|_| anyhow!("Failed to serialize targets"))?;
+
+        let path = entry_path(&self.path, &entry.header);
+        write_entry(&path, &entry)?;
+
+        self.entries
+            .lock()
+            .expect("Lock isn't poisoned")
+            .insert(entry.header.key, Arc::new(entry));
+
+        Ok(path)
+    }
+}
+
+fn entry_path(dir: &Path, header: &Header) -> PathBuf {
+    dir.join(format!("{}-{}.{EXTENSION}", header.circuit, header.key))
+}
+
+fn write_entry(path: &Path, entry: &Entry) -> anyhow::Result<()> {
+    let circuit_data = entry
+        .circuit_data
+        .to_bytes(&GateSerializer, &GeneratorSerializer::<C, D>::default())
+        .map_err(|_| anyhow!("Failed to serialize circuit data"))?;
+
+    let path_tmp = path.with_extension("tmp");
+    let file = File::create(&path_tmp)
+        .with_context(|| format!("Failed to create {}", path_tmp.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    writer.write_all(&CACHE_VERSION.to_le_bytes())?;
+    write_string(&mut writer, PROVER_VERSION)?;
+    write_string(&mut writer, SOURCE_HASH)?;
+    write_string(&mut writer, &entry.header.circuit)?;
+    writer.write_all(&entry.header.key.0)?;
+    writer.write_all(&digest_to_bytes(&entry.header.digest))?;
+    writer.write_all(&(entry.header.num_gates as u64).to_le_bytes())?;
+    write_bytes(&mut writer, &entry.targets)?;
+    writer.write_all(&circuit_data)?;
+
+    writer.into_inner()?.sync_all()?;
+    fs::rename(&path_tmp, path).with_context(|| {
+        format!(
+            "Failed to move {} to {}",
+            path_tmp.display(),
+            path.display()
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Reads the entry from `path`. Returns `None` if it was written by another prover version or
+/// by a prover built from other sources.
+fn read_entry(path: &Path) -> anyhow::Result<Option<Entry>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    if u32::from_le_bytes(version) != CACHE_VERSION
+        || read_string(&mut reader)? != PROVER_VERSION
+        || read_string(&mut reader)? != SOURCE_HASH
+    {
+        return Ok(None);
+    }
+
+    let decode = |reader: &mut BufReader<File>| -> io::Result<_> {
+        let circuit = read_string(reader)?;
+
+        let mut key = [0; 32];
+        reader.read_exact(&mut key)?;
+
+        let mut digest = [0; 32];
+        reader.read_exact(&mut digest)?;
+
+        let mut num_gates = [0; 8];
+        reader.read_exact(&mut num_gates)?;
+
+        let header = Header {
+            circuit,
+            key: CacheKey(key),
+            digest: digest_from_bytes(digest),
+            num_gates: u64::from_le_bytes(num_gates) as usize,
+        };
+
+        Ok((header, read_bytes(reader)?))
+    };
+    let (header, targets) = decode(&mut reader)
+        .with_context(|| format!("Circuit cache entry {} is corrupted", path.display()))?;
+
+    let circuit_data = ReadAdapter::new(reader, None)
+        .read_circuit_data::<F, C, D>(&GateSerializer, &GeneratorSerializer::<C, D>::default())
+        .map_err(|_| {
+            anyhow!(
+                "Circuit cache entry {} is corrupted: failed to decode circuit data",
+                path.display()
+            )
+        })?;
+
+    if circuit_data.verifier_only.circuit_digest != header.digest {
+        return Err(anyhow!(
+            "Circuit cache entry {} is rejected: digest of its circuit data {} doesn't match \
+             the cached digest {}. Remove the entry to build the circuit again",
+            path.display(),
+            hex_digest(&circuit_data.verifier_only.circuit_digest),
+            hex_digest(&header.digest),
+        ));
+    }
+
+    if entry_path(path.parent().unwrap_or(Path::new("")), &header) != path {
+        return Err(anyhow!(
+            "Circuit cache entry {} is rejected: it contains {} circuit with key {}",
+            path.display(),
+            header.circuit,
+            header.key
+        ));
+    }
+
+    Ok(Some(Entry {
+        header,
+        targets,
+        circuit_data: Arc::new(circuit_data),
+    }))
+}
+
+fn digest_to_bytes(digest: &HashOut<F>) -> Vec<u8> {
+    digest
+        .elements
+        .iter()
+        .flat_map(|element| element.to_canonical_u64().to_le_bytes())
+        .collect()
+}
+
+fn digest_from_bytes(bytes: [u8; 32]) -> HashOut<F> {
+    HashOut {
+        elements: bytes
+            .chunks(8)
+            .map(|chunk| {
+                F::from_noncanonical_u64(u64::from_le_bytes(
+                    chunk.try_into().expect("Chunk of 8 bytes"),
+                ))
+            })
+            .collect::<Vec<_>>()
+            .try_into()
+            .expect("4 elements in digest"),
+    }
+}
+
+fn hex_digest(digest: &HashOut<F>) -> String {
+    digest_to_bytes(digest)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn write_string(writer: &mut impl Write, string: &str) -> io::Result<()> {
+    write_bytes(writer, string.as_bytes())
+}
+
+fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0; 8];
+    reader.read_exact(&mut len)?;
+
+    let len = u64::from_le_bytes(len);
+    let mut bytes = vec![];
+    reader.by_ref().take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("expected {len} bytes, found {}", bytes.len()),
+        ));
+    }
+
+    Ok(bytes)
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    String::from_utf8(read_bytes(reader)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plonky2::{
+        iop::{
+            target::Target,
+            witness::{PartialWitness, WitnessWrite},
+        },
+        plonk::circuit_data::CircuitConfig,
+        util::serialization::{Read as _, Write as _},
+    };
+
+    struct Targets(Vec<Target>);
+
+    impl CachedTargets for Targets {
+        fn write(&self, dst: &mut Vec<u8>) -> IoResult<()> {
+            dst.write_target_vec(&self.0)
+        }
+
+        fn read(src: &mut Buffer) -> IoResult<Self> {
+            src.read_target_vec().map(Self)
+        }
+    }
+
+    fn test_entry(squares: usize) -> (Entry, Targets) {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let targets = (0..squares)
+            .map(|_| {
+                let target = builder.add_virtual_target();
+                let square = builder.square(target);
+                builder.register_public_input(square);
+                target
+            })
+            .collect();
+
+        let num_gates = builder.num_gates();
+        let circuit_data = builder.build::<C>();
+
+        let entry = Entry {
+            header: Header {
+                circuit: "test".to_string(),
+                key: CacheKey::new("test", &[], None),
+                digest: circuit_data.verifier_only.circuit_digest,
+                num_gates,
+            },
+            targets: vec![],
+            circuit_data: Arc::new(circuit_data),
+        };
+
+        (entry, Targets(targets))
+    }
+
+    #[test]
+    fn proves_with_loaded_circuit() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut entry, targets) = test_entry(2);
+        targets.write(&mut entry.targets).unwrap();
+        let path = entry_path(dir.path(), &entry.header);
+        write_entry(&path, &entry).unwrap();
+
+        let loaded = read_entry(&path)
+            .unwrap()
+            .expect("Entry of this prover version");
+        assert_eq!(loaded.header.num_gates, entry.header.num_gates);
+        assert!(loaded.circuit_data.verifier_only == entry.circuit_data.verifier_only);
+
+        let Targets(targets) = Targets::read(&mut Buffer::new(&loaded.targets)).unwrap();
+        let mut witness = PartialWitness::new();
+        witness.set_target(targets[0], F::from_canonical_u64(3));
+        witness.set_target(targets[1], F::from_canonical_u64(5));
+        let proof = loaded.circuit_data.prove(witness).unwrap();
+
+        assert_eq!(
+            proof.public_inputs,
+            vec![F::from_canonical_u64(9), F::from_canonical_u64(25)]
+        );
+        entry.circuit_data.verify(proof).unwrap();
+    }
+
+    #[test]
+    fn rejects_circuit_with_another_digest() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut entry, _) = test_entry(2);
+        let (other, _) = test_entry(3);
+        entry.header.digest = other.header.digest;
+        let path = entry_path(dir.path(), &entry.header);
+        write_entry(&path, &entry).unwrap();
+
+        let err = read_entry(&path).err().unwrap().to_string();
+        assert!(err.contains("doesn't match the cached digest"), "{err}");
+    }
+
+    #[test]
+    fn rejects_entry_stored_under_another_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let (entry, _) = test_entry(1);
+        let path = dir
+            .path()
+            .join(format!("test-{}.{EXTENSION}", CacheKey([0; 32])));
+        write_entry(&path, &entry).unwrap();
+
+        let err = read_entry(&path).err().unwrap().to_string();
+        assert!(err.contains("with key"), "{err}");
+    }
+
+    #[test]
+    fn rejects_truncated_bytes() {
+        let mut data = vec![];
+        write_bytes(&mut data, &[1, 2, 3, 4]).unwrap();
+        data.truncate(data.len() - 1);
+
+        let err = read_bytes(&mut data.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn key_depends_on_genesis_config() {
+        let genesis_config = |authority_set_id| GenesisConfig {
+            authority_set_id,
+            authority_set_hash: [1; 32],
+        };
+
+        assert!(
+            CacheKey::new("final_proof", &[], Some(genesis_config(1)))
+                != CacheKey::new("final_proof", &[], Some(genesis_config(2)))
+        );
+        assert!(
+            CacheKey::new("final_proof", &[], None)
+                != CacheKey::new("latest_validator_set", &[], None)
+        );
+    }
+}
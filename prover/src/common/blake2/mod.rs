@@ -4,6 +4,7 @@ pub mod variative;
 
 use crate::{
     common::{
+        build_circuit,
         targets::{ArrayTarget, Blake2Target, ByteTarget, TargetSet},
         ProofWithCircuitData, BUFFER_SIZE,
     },
@@ -178,7 +179,7 @@ impl From<BuilderTargets> for CircuitTargets {
 
         let now = Instant::now();
 
-        let circuit = value.builder.build::<C>();
+        let circuit = build_circuit::<C>(value.builder);
 
         log::trace!(
             "From<BuilderTargets> for CircuitTargets exit. Time: {}ms",
@@ -244,6 +245,7 @@ impl CircuitTargets {
             circuit_data: Arc::from(self.circuit.verifier_data()),
             public_inputs,
             public_inputs_parser: PhantomData,
+            num_gates: None,
         }
     }
 
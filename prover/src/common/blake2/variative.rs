@@ -125,6 +125,7 @@ impl VariativeBlake2 {
             }),
             public_inputs,
             public_inputs_parser: PhantomData,
+            num_gates: None,
         }
     }
 
@@ -6,7 +6,7 @@ pub mod blake2;
 pub mod poseidon_bn128;
 
 use self::poseidon_bn128::config::PoseidonBN128GoldilocksConfig;
-use crate::{prelude::*, proving::ExportedProofWithCircuitData};
+use crate::{circuit_cache::CachedCircuit, prelude::*, proving::ExportedProofWithCircuitData};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use plonky2::{
@@ -18,7 +18,8 @@ use plonky2::{
     plonk::{
         circuit_builder::CircuitBuilder,
         circuit_data::{CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitData},
-        proof::{Proof, ProofWithPublicInputs},
+        config::GenericConfig,
+        proof::{Proof, ProofWithPublicInputs, ProofWithPublicInputsTarget},
     },
 };
 use plonky2_field::goldilocks_field::GoldilocksField;
@@ -27,7 +28,11 @@ use std::{
     fmt::{Debug, Display},
     marker::PhantomData,
     str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 use targets::TargetSet;
 
@@ -36,6 +41,25 @@ lazy_static! {
     static ref BUFFER_SIZE: usize = get_env_variable("BUFFER_SIZE", 4_194_304);
 }
 
+/// Time spent in [`build_circuit`] by all threads, in nanoseconds.
+static CIRCUIT_BUILD_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Builds the circuit, adding the time it takes to [`circuit_build_time`].
+pub fn build_circuit<Cfg: GenericConfig<D, F = F>>(
+    builder: CircuitBuilder<F, D>,
+) -> CircuitData<F, Cfg, D> {
+    let now = Instant::now();
+    let circuit_data = builder.build::<Cfg>();
+    CIRCUIT_BUILD_NANOS.fetch_add(now.elapsed().as_nanos() as u64, Ordering::Relaxed);
+
+    circuit_data
+}
+
+/// Total time spent building circuits since the process start, summed over threads.
+pub fn circuit_build_time() -> Duration {
+    Duration::from_nanos(CIRCUIT_BUILD_NANOS.load(Ordering::Relaxed))
+}
+
 // TODO: introduce `common`/`utils`/etc crate
 pub fn get_env_variable<T>(name: &str, value_default: T) -> T
 where
@@ -69,6 +93,9 @@ where
 
     public_inputs: Vec<F>,
     public_inputs_parser: PhantomData<TS>,
+
+    /// Count of gates before padding, known when the circuit is built from a builder.
+    num_gates: Option<usize>,
 }
 
 impl<TS> ProofWithCircuitData<TS>
@@ -80,7 +107,8 @@ where
         builder: CircuitBuilder<F, D>,
         witness: PartialWitness<F>,
     ) -> ProofWithCircuitData<TS> {
-        let circuit_data = builder.build::<C>();
+        let num_gates = builder.num_gates();
+        let circuit_data = build_circuit::<C>(builder);
         let ProofWithPublicInputs {
             proof,
             public_inputs,
@@ -91,6 +119,7 @@ where
             circuit_data: Arc::from(circuit_data.verifier_data()),
             public_inputs,
             public_inputs_parser: PhantomData,
+            num_gates: Some(num_gates),
         }
     }
 
@@ -109,9 +138,20 @@ where
             circuit_data: Arc::from(circuit_data.verifier_data()),
             public_inputs,
             public_inputs_parser: PhantomData,
+            num_gates: None,
         }
     }
 
+    /// Prove using circuit taken from `circuit_cache` and witness.
+    pub fn prove_from_cached_circuit<T>(
+        circuit: &CachedCircuit<T>,
+        witness: PartialWitness<F>,
+    ) -> ProofWithCircuitData<TS> {
+        let mut proof = Self::prove_from_circuit_data(&circuit.circuit_data, witness);
+        proof.num_gates = Some(circuit.num_gates);
+        proof
+    }
+
     /// Create a new `ProofWithCircuitData` from proof and circuit data.
     pub fn from_proof_and_circuit_data(
         proof: ProofWithPublicInputs<F, C, D>,
@@ -127,6 +167,7 @@ where
             circuit_data: Arc::from(circuit_data),
             public_inputs,
             public_inputs_parser: PhantomData,
+            num_gates: None,
         }
     }
 
@@ -135,6 +176,11 @@ where
         &self.circuit_data
     }
 
+    /// Get count of gates before padding, if known.
+    pub fn num_gates(&self) -> Option<usize> {
+        self.num_gates
+    }
+
     /// Get type-erased public inouts.
     pub fn public_inputs(&self) -> Vec<GoldilocksField> {
         self.public_inputs.clone()
@@ -150,18 +196,43 @@ where
 
     /// Wrap proof in a recursion layer using `PoseidonBN128GoldilocksConfig` and serialize it.
     pub fn export_wrapped(self) -> ExportedProofWithCircuitData {
+        self.wrap_bn128().export()
+    }
+
+    /// Wrap proof in a recursion layer using `PoseidonBN128GoldilocksConfig`.
+    pub fn wrap_bn128(self) -> WrappedProof {
+        let inner_circuit_data = &self.circuit_data;
         let proof_with_public_inputs = ProofWithPublicInputs {
             proof: self.proof,
             public_inputs: self.public_inputs,
         };
 
-        let (proof_with_public_inputs, circuit_data) =
-            wrap_bn128(&self.circuit_data, proof_with_public_inputs);
+        let mut builder: CircuitBuilder<F, D> =
+            CircuitBuilder::new(CircuitConfig::standard_recursion_config());
 
-        ExportedProofWithCircuitData {
-            proof_with_public_inputs: serde_json::to_string(&proof_with_public_inputs).unwrap(),
-            common_circuit_data: serde_json::to_string(&circuit_data.common).unwrap(),
-            verifier_only_circuit_data: serde_json::to_string(&circuit_data.verifier_only).unwrap(),
+        let proof_with_pis_target = builder.add_virtual_proof_with_pis(&inner_circuit_data.common);
+        let verifier_circuit_target =
+            builder.constant_verifier_data(&inner_circuit_data.verifier_only);
+
+        builder.register_public_inputs(&proof_with_pis_target.public_inputs);
+
+        let mut witness = PartialWitness::new();
+        witness.set_proof_with_pis_target(&proof_with_pis_target, &proof_with_public_inputs);
+
+        builder.verify_proof::<C>(
+            &proof_with_pis_target,
+            &verifier_circuit_target,
+            &inner_circuit_data.common,
+        );
+
+        let num_gates = builder.num_gates();
+        let circuit_data = build_circuit::<PoseidonBN128GoldilocksConfig>(builder);
+        let proof = circuit_data.prove(witness).unwrap();
+
+        WrappedProof {
+            proof,
+            circuit_data,
+            num_gates,
         }
     }
 
@@ -210,34 +281,24 @@ impl<BUILDER: CircuitImplBuilder> CircuitDataCache<BUILDER> {
     }
 }
 
-fn wrap_bn128(
-    inner_circuit_data: &VerifierCircuitData<F, C, D>,
-    proof_with_public_inputs: ProofWithPublicInputs<F, C, D>,
-) -> (
-    ProofWithPublicInputs<F, PoseidonBN128GoldilocksConfig, D>,
-    CircuitData<F, PoseidonBN128GoldilocksConfig, D>,
-) {
-    let mut builder: CircuitBuilder<F, D> =
-        CircuitBuilder::new(CircuitConfig::standard_recursion_config());
-
-    let proof_with_pis_target = builder.add_virtual_proof_with_pis(&inner_circuit_data.common);
-    let verifier_circuit_target = builder.constant_verifier_data(&inner_circuit_data.verifier_only);
-
-    builder.register_public_inputs(&proof_with_pis_target.public_inputs);
-
-    let mut witness = PartialWitness::new();
-    witness.set_proof_with_pis_target(&proof_with_pis_target, &proof_with_public_inputs);
-
-    builder.verify_proof::<C>(
-        &proof_with_pis_target,
-        &verifier_circuit_target,
-        &inner_circuit_data.common,
-    );
-
-    let circuit_data = builder.build::<PoseidonBN128GoldilocksConfig>();
-    let proof = circuit_data.prove(witness).unwrap();
+/// Proof wrapped by `ProofWithCircuitData::wrap_bn128`, ready to be exported to `gnark-wrapper`.
+pub struct WrappedProof {
+    pub proof: ProofWithPublicInputs<F, PoseidonBN128GoldilocksConfig, D>,
+    pub circuit_data: CircuitData<F, PoseidonBN128GoldilocksConfig, D>,
+    /// Count of gates before padding.
+    pub num_gates: usize,
+}
 
-    (proof, circuit_data)
+impl WrappedProof {
+    /// Serialize proof and circuit data.
+    pub fn export(&self) -> ExportedProofWithCircuitData {
+        ExportedProofWithCircuitData {
+            proof_with_public_inputs: serde_json::to_string(&self.proof).unwrap(),
+            common_circuit_data: serde_json::to_string(&self.circuit_data.common).unwrap(),
+            verifier_only_circuit_data: serde_json::to_string(&self.circuit_data.verifier_only)
+                .unwrap(),
+        }
+    }
 }
 
 pub trait BuilderExt {
@@ -248,6 +309,13 @@ pub trait BuilderExt {
         witness: &mut PartialWitness<F>,
     ) -> T;
 
+    /// Declare verifier data as a constant and recursively verify a proof of the circuit that
+    /// will be set to the returned target.
+    fn recursively_verify_constant_circuit<T: TargetSet>(
+        &mut self,
+        circuit_data: &VerifierCircuitData<F, C, D>,
+    ) -> (ProofWithPublicInputsTarget<D>, T);
+
     /// Select if `condition` { `a` } else { `b` }
     fn select_target_set<T: TargetSet>(&mut self, condition: BoolTarget, a: &T, b: &T) -> T;
 
@@ -261,18 +329,31 @@ impl BuilderExt for CircuitBuilder<F, D> {
         proof: &ProofWithCircuitData<T>,
         witness: &mut PartialWitness<F>,
     ) -> T {
-        let proof_with_pis_target = self.add_virtual_proof_with_pis(&proof.circuit_data.common);
-        let verifier_data_target = self.constant_verifier_data(&proof.circuit_data.verifier_only);
+        let (proof_with_pis_target, public_inputs) =
+            self.recursively_verify_constant_circuit(&proof.circuit_data);
 
         witness.set_proof_with_pis_target(&proof_with_pis_target, &proof.proof());
 
+        public_inputs
+    }
+
+    fn recursively_verify_constant_circuit<T: TargetSet>(
+        &mut self,
+        circuit_data: &VerifierCircuitData<F, C, D>,
+    ) -> (ProofWithPublicInputsTarget<D>, T) {
+        let proof_with_pis_target = self.add_virtual_proof_with_pis(&circuit_data.common);
+        let verifier_data_target = self.constant_verifier_data(&circuit_data.verifier_only);
+
         self.verify_proof::<C>(
             &proof_with_pis_target,
             &verifier_data_target,
-            &proof.circuit_data.common,
+            &circuit_data.common,
         );
 
-        T::parse_exact(&mut proof_with_pis_target.public_inputs.into_iter())
+        let public_inputs =
+            T::parse_exact(&mut proof_with_pis_target.public_inputs.iter().copied());
+
+        (proof_with_pis_target, public_inputs)
     }
 
     fn select_target_set<T: TargetSet>(&mut self, condition: BoolTarget, a: &T, b: &T) -> T {
@@ -308,13 +389,13 @@ pub fn common_data_for_recursion(
     let config = CircuitConfig::standard_recursion_config();
 
     let builder = CircuitBuilder::<F, D>::new(config.clone());
-    let data = builder.build::<C>();
+    let data = build_circuit::<C>(builder);
 
     let mut builder = CircuitBuilder::<F, D>::new(config.clone());
     let proof = builder.add_virtual_proof_with_pis(&data.common);
     let verifier_data = builder.add_virtual_verifier_data(data.common.config.fri_config.cap_height);
     builder.verify_proof::<C>(&proof, &verifier_data, &data.common);
-    let data = builder.build::<C>();
+    let data = build_circuit::<C>(builder);
 
     let mut builder = CircuitBuilder::<F, D>::new(config);
     let proof = builder.add_virtual_proof_with_pis(&data.common);
@@ -326,7 +407,7 @@ pub fn common_data_for_recursion(
         builder.add_gate(NoopGate, vec![]);
     }
 
-    let mut data = builder.build::<C>().common;
+    let mut data = build_circuit::<C>(builder).common;
     data.num_public_inputs = public_input_count;
 
     data
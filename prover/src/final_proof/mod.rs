@@ -8,12 +8,14 @@ use plonky2::{
     plonk::{
         circuit_builder::CircuitBuilder,
         circuit_data::{CircuitConfig, VerifierCircuitData},
-        proof::ProofWithPublicInputs,
+        proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
     },
+    util::serialization::{Buffer, IoResult, Read, Write},
 };
 use plonky2_field::types::Field;
 
 use crate::{
+    circuit_cache::{self, CacheKey, CachedTargets},
     common::{
         targets::{impl_target_set, Blake2TargetGoldilocks, MessageTargetGoldilocks, TargetSet},
         BuilderExt, ProofWithCircuitData,
@@ -25,7 +27,9 @@ use crate::{
 
 pub mod message_sent;
 
-use message_sent::MessageSent;
+use message_sent::{MessageSent, MessageSentTarget};
+
+const CIRCUIT_NAME: &str = "final_proof";
 
 impl_target_set! {
     /// Public inputs for `FinalProof`.
@@ -46,41 +50,77 @@ pub struct FinalProof {
     pub message_sent: MessageSent,
 }
 
+/// Targets of the circuit that witness is set to.
+struct CircuitTargets {
+    message_sent_proof: ProofWithPublicInputsTarget<D>,
+    latest_validator_set_proof: ProofWithPublicInputsTarget<D>,
+}
+
+impl CachedTargets for CircuitTargets {
+    fn write(&self, dst: &mut Vec<u8>) -> IoResult<()> {
+        dst.write_target_proof_with_public_inputs(&self.message_sent_proof)?;
+        dst.write_target_proof_with_public_inputs(&self.latest_validator_set_proof)
+    }
+
+    fn read(src: &mut Buffer) -> IoResult<Self> {
+        Ok(Self {
+            message_sent_proof: src.read_target_proof_with_public_inputs()?,
+            latest_validator_set_proof: src.read_target_proof_with_public_inputs()?,
+        })
+    }
+}
+
 impl FinalProof {
     pub fn prove(self, genesis_config: GenesisConfig) -> ProofWithCircuitData<FinalProofTarget> {
         let message_sent_proof = self.message_sent.prove();
 
         log::debug!("Composing message sent and latest validator set proofs...");
 
-        let mut config = CircuitConfig::standard_recursion_config();
-        config.fri_config.cap_height = 0;
-        let mut builder = CircuitBuilder::new(config);
-        let mut witness = PartialWitness::new();
+        let key = CacheKey::new(
+            CIRCUIT_NAME,
+            &[
+                message_sent_proof.circuit_data(),
+                &self.current_validator_set_verifier_data,
+            ],
+            Some(genesis_config),
+        );
+        let circuit = circuit_cache::get_or_build(CIRCUIT_NAME, key, || {
+            Self::build_circuit(
+                message_sent_proof.circuit_data(),
+                &self.current_validator_set_verifier_data,
+                genesis_config,
+            )
+        });
 
-        let message_sent_target =
-            builder.recursively_verify_constant_proof(&message_sent_proof, &mut witness);
+        let mut witness = PartialWitness::new();
+        witness.set_proof_with_pis_target(
+            &circuit.targets.message_sent_proof,
+            &message_sent_proof.proof(),
+        );
+        witness.set_proof_with_pis_target(
+            &circuit.targets.latest_validator_set_proof,
+            &self.current_validator_set_proof,
+        );
 
-        let latest_validator_set_target = {
-            let proof_with_pis_target = builder
-                .add_virtual_proof_with_pis(&self.current_validator_set_verifier_data.common);
-            let verifier_data_target = builder
-                .constant_verifier_data(&self.current_validator_set_verifier_data.verifier_only);
+        ProofWithCircuitData::prove_from_cached_circuit(&circuit, witness)
+    }
 
-            witness.set_proof_with_pis_target(
-                &proof_with_pis_target,
-                &self.current_validator_set_proof,
-            );
+    fn build_circuit(
+        message_sent_circuit_data: &VerifierCircuitData<F, C, D>,
+        current_validator_set_verifier_data: &VerifierCircuitData<F, C, D>,
+        genesis_config: GenesisConfig,
+    ) -> (CircuitBuilder<F, D>, CircuitTargets) {
+        let mut config = CircuitConfig::standard_recursion_config();
+        config.fri_config.cap_height = 0;
+        let mut builder = CircuitBuilder::new(config);
 
-            builder.verify_proof::<C>(
-                &proof_with_pis_target,
-                &verifier_data_target,
-                &self.current_validator_set_verifier_data.common,
-            );
+        let (message_sent_proof, message_sent_target) = builder
+            .recursively_verify_constant_circuit::<MessageSentTarget>(message_sent_circuit_data);
 
-            LatestValidatorSetTarget::parse_exact(
-                &mut proof_with_pis_target.public_inputs.into_iter(),
-            )
-        };
+        let (latest_validator_set_proof, latest_validator_set_target) = builder
+            .recursively_verify_constant_circuit::<LatestValidatorSetTarget>(
+            current_validator_set_verifier_data,
+        );
 
         message_sent_target
             .validator_set_hash
@@ -111,6 +151,11 @@ impl FinalProof {
         }
         .register_as_public_inputs(&mut builder);
 
-        ProofWithCircuitData::prove_from_builder(builder, witness)
+        let targets = CircuitTargets {
+            message_sent_proof,
+            latest_validator_set_proof,
+        };
+
+        (builder, targets)
     }
 }
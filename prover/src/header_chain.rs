@@ -1,7 +1,7 @@
 use super::{
     common::{
         blake2::{CircuitTargets as Blake2CircuitTargets, GenericBlake2Target},
-        common_data_for_recursion,
+        build_circuit, common_data_for_recursion,
         targets::{impl_parsable_target_set, Blake2Target, ParsableTargetSet, TargetSet},
         ProofWithCircuitData,
     },
@@ -163,7 +163,7 @@ impl From<BuilderTargets> for CircuitTargets {
         log::trace!("From<BuilderTargets> for CircuitTargets enter");
         let now = Instant::now();
 
-        let circuit = value.builder.build::<C>();
+        let circuit = build_circuit::<C>(value.builder);
 
         log::trace!(
             "From<BuilderTargets> for CircuitTargets exit. Time: {}ms",
@@ -9,13 +9,15 @@ use plonky2::{
     },
     plonk::{
         circuit_builder::CircuitBuilder,
-        circuit_data::{CircuitConfig, CircuitData, CommonCircuitData},
+        circuit_data::{CircuitConfig, VerifierCircuitData, VerifierCircuitTarget},
         proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
     },
     recursion::dummy_circuit::cyclic_base_proof,
+    util::serialization::{Buffer, IoResult, Read, Write},
 };
 
 use crate::{
+    circuit_cache::{self, CacheKey, CachedCircuit, CachedTargets},
     common::{
         common_data_for_recursion,
         targets::{impl_target_set, Blake2TargetGoldilocks, TargetSet, VerifierDataTarget},
@@ -27,7 +29,9 @@ use crate::{
 
 pub mod next_validator_set;
 
-use next_validator_set::NextValidatorSet;
+use next_validator_set::{NextValidatorSet, NextValidatorSetTarget};
+
+const CIRCUIT_NAME: &str = "latest_validator_set";
 
 // Depends on the `CircuitConfig` used to generate this proof.
 // `CircuitConfig::dtandard_recurion_config()` sets 16 merkle cap elements.
@@ -55,14 +59,36 @@ pub struct LatestValidatorSet {
     pub change_proof: NextValidatorSet,
 }
 
-/// Intermediate data that's used in the process of building circuit.
-struct Circuit {
-    cyclic_circuit_data: CircuitData<F, C, D>,
-
-    common_data: CommonCircuitData<F, D>,
+/// Targets of the circuit that witness is set to.
+struct CircuitTargets {
+    next_validator_set_proof: ProofWithPublicInputsTarget<D>,
+    verifier_data: VerifierCircuitTarget,
 
     condition: BoolTarget,
     inner_cyclic_proof_with_pis: ProofWithPublicInputsTarget<D>,
+}
+
+impl CachedTargets for CircuitTargets {
+    fn write(&self, dst: &mut Vec<u8>) -> IoResult<()> {
+        dst.write_target_proof_with_public_inputs(&self.next_validator_set_proof)?;
+        dst.write_target_verifier_circuit(&self.verifier_data)?;
+        dst.write_target_bool(self.condition)?;
+        dst.write_target_proof_with_public_inputs(&self.inner_cyclic_proof_with_pis)
+    }
+
+    fn read(src: &mut Buffer) -> IoResult<Self> {
+        Ok(Self {
+            next_validator_set_proof: src.read_target_proof_with_public_inputs()?,
+            verifier_data: src.read_target_verifier_circuit()?,
+            condition: src.read_target_bool()?,
+            inner_cyclic_proof_with_pis: src.read_target_proof_with_public_inputs()?,
+        })
+    }
+}
+
+/// Intermediate data that's used in the process of building circuit.
+struct Circuit {
+    cyclic_circuit: CachedCircuit<CircuitTargets>,
 
     witness: PartialWitness<F>,
 }
@@ -79,28 +105,31 @@ impl Circuit {
             .enumerate()
             .collect();
 
-        self.witness.set_bool_target(self.condition, false);
+        let cyclic_circuit_data = &self.cyclic_circuit.circuit_data;
+        let targets = &self.cyclic_circuit.targets;
+        self.witness.set_bool_target(targets.condition, false);
         self.witness.set_proof_with_pis_target::<C, D>(
-            &self.inner_cyclic_proof_with_pis,
+            &targets.inner_cyclic_proof_with_pis,
             &cyclic_base_proof(
-                &self.common_data,
-                &self.cyclic_circuit_data.verifier_only,
+                &cyclic_circuit_data.common,
+                &cyclic_circuit_data.verifier_only,
                 genesis_data_pis,
             ),
         );
 
-        ProofWithCircuitData::prove_from_circuit_data(&self.cyclic_circuit_data, self.witness)
+        ProofWithCircuitData::prove_from_cached_circuit(&self.cyclic_circuit, self.witness)
     }
 
     fn prove_recursive(
         mut self,
         composed_proof: ProofWithPublicInputs<F, C, D>,
     ) -> ProofWithCircuitData<LatestValidatorSetTarget> {
-        self.witness.set_bool_target(self.condition, true);
+        let targets = &self.cyclic_circuit.targets;
+        self.witness.set_bool_target(targets.condition, true);
         self.witness
-            .set_proof_with_pis_target(&self.inner_cyclic_proof_with_pis, &composed_proof);
+            .set_proof_with_pis_target(&targets.inner_cyclic_proof_with_pis, &composed_proof);
 
-        ProofWithCircuitData::prove_from_circuit_data(&self.cyclic_circuit_data, self.witness)
+        ProofWithCircuitData::prove_from_cached_circuit(&self.cyclic_circuit, self.witness)
     }
 }
 
@@ -115,26 +144,81 @@ impl LatestValidatorSet {
             config.authority_set_id
         );
 
-        let circuit = self.build_circuit();
+        let next_validator_set_proof = self.change_proof.prove();
+        let circuit = Self::circuit(&next_validator_set_proof);
         circuit.prove_genesis(config)
     }
 
     /// Add one more layer to laready existing proof.
     pub fn prove_recursive(
         self,
-        composed_proof: ProofWithPublicInputs<F, C, D>,
+        composed_proof: ProofWithCircuitData<LatestValidatorSetTarget>,
     ) -> ProofWithCircuitData<LatestValidatorSetTarget> {
-        let circuit = self.build_circuit();
-        circuit.prove_recursive(composed_proof)
-    }
+        let next_validator_set_proof = self.change_proof.prove();
+        let mut circuit = Self::circuit(&next_validator_set_proof);
+
+        // Cyclic proof can be composed only with the proof of the same circuit. Cached circuit
+        // built by another prover would fail to prove, so it's built again.
+        let composed_verifier_data = &composed_proof.circuit_data().verifier_only;
+        if circuit.cyclic_circuit.circuit_data.verifier_only != *composed_verifier_data {
+            let key = Self::cache_key(&next_validator_set_proof);
+            let reason = format!(
+                "its digest {:?} doesn't match digest {:?} of the previous proof",
+                circuit
+                    .cyclic_circuit
+                    .circuit_data
+                    .verifier_only
+                    .circuit_digest,
+                composed_verifier_data.circuit_digest
+            );
+            if circuit_cache::reject(key, &reason) {
+                circuit = Self::circuit(&next_validator_set_proof);
+            }
+        }
 
-    fn build_circuit(self) -> Circuit {
-        log::debug!("LatestValidatorSet; build circuit");
+        circuit.prove_recursive(composed_proof.proof())
+    }
 
-        let next_validator_set_proof = self.change_proof.prove();
+    fn cache_key(
+        next_validator_set_proof: &ProofWithCircuitData<NextValidatorSetTarget>,
+    ) -> CacheKey {
+        CacheKey::new(
+            CIRCUIT_NAME,
+            &[next_validator_set_proof.circuit_data()],
+            None,
+        )
+    }
 
+    fn circuit(next_validator_set_proof: &ProofWithCircuitData<NextValidatorSetTarget>) -> Circuit {
         log::debug!("LatestValidatorSet; next_validator_set_proof proven");
 
+        let cyclic_circuit = circuit_cache::get_or_build(
+            CIRCUIT_NAME,
+            Self::cache_key(next_validator_set_proof),
+            || Self::build_circuit(next_validator_set_proof.circuit_data()),
+        );
+
+        let mut witness = PartialWitness::new();
+        witness.set_proof_with_pis_target(
+            &cyclic_circuit.targets.next_validator_set_proof,
+            &next_validator_set_proof.proof(),
+        );
+        witness.set_verifier_data_target(
+            &cyclic_circuit.targets.verifier_data,
+            &cyclic_circuit.circuit_data.verifier_only,
+        );
+
+        Circuit {
+            cyclic_circuit,
+            witness,
+        }
+    }
+
+    fn build_circuit(
+        next_validator_set_circuit_data: &VerifierCircuitData<F, C, D>,
+    ) -> (CircuitBuilder<F, D>, CircuitTargets) {
+        log::debug!("LatestValidatorSet; build circuit");
+
         let mut builder = CircuitBuilder::new(CircuitConfig::standard_recursion_config());
 
         let genesis_authority_set_id = builder.add_virtual_public_input();
@@ -144,10 +228,10 @@ impl LatestValidatorSet {
         genesis_authority_set_hash.register_as_public_inputs(&mut builder);
 
         // Verify validator set change
-        let mut witness = PartialWitness::new();
-
-        let next_authority_set_public_inputs =
-            builder.recursively_verify_constant_proof(&next_validator_set_proof, &mut witness);
+        let (next_validator_set_proof, next_authority_set_public_inputs) = builder
+            .recursively_verify_constant_circuit::<NextValidatorSetTarget>(
+            next_validator_set_circuit_data,
+        );
 
         let current_set_hash = next_authority_set_public_inputs.current_validator_set_hash;
         let current_set_id = next_authority_set_public_inputs.current_authority_set_id;
@@ -203,19 +287,14 @@ impl LatestValidatorSet {
             )
             .unwrap();
 
-        let cyclic_circuit_data = builder.build::<C>();
-
-        witness.set_verifier_data_target(&verifier_data_target, &cyclic_circuit_data.verifier_only);
-
-        Circuit {
-            cyclic_circuit_data,
-
-            common_data,
+        let targets = CircuitTargets {
+            next_validator_set_proof,
+            verifier_data: verifier_data_target,
 
             condition,
             inner_cyclic_proof_with_pis,
+        };
 
-            witness,
-        }
+        (builder, targets)
     }
 }
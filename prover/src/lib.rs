@@ -5,7 +5,9 @@ use jemallocator::Jemalloc;
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
+pub mod bench;
 mod block_finality;
+pub mod circuit_cache;
 pub(crate) mod common;
 mod final_proof;
 pub mod header_chain;
@@ -68,12 +70,13 @@ pub mod proving {
         final_proof::{message_sent::MessageSent, FinalProof},
         latest_validator_set::{
             next_validator_set::{NextValidatorSet, NextValidatorSetTarget},
-            LatestValidatorSet,
+            LatestValidatorSet, LatestValidatorSetTarget,
         },
         prelude::*,
     };
     use consts::BLAKE2_DIGEST_SIZE;
     use plonky2::{
+        field::types::PrimeField64,
         plonk::{
             circuit_data::{CommonCircuitData, VerifierCircuitData},
             proof::ProofWithPublicInputs,
@@ -168,6 +171,68 @@ pub mod proving {
                 circuit_data,
             )
         }
+
+        /// Checks that `proof` verifies against `circuit_data`. Malformed data is
+        /// reported as an invalid proof.
+        pub fn verify(&self) -> bool {
+            let Ok(circuit_data) = VerifierCircuitData::<F, C, D>::from_bytes(
+                self.circuit_data.0.clone(),
+                &DefaultGateSerializer,
+            ) else {
+                return false;
+            };
+            let Ok(proof) =
+                ProofWithPublicInputs::from_bytes(self.proof.0.clone(), &circuit_data.common)
+            else {
+                return false;
+            };
+
+            circuit_data.verify(proof).is_ok()
+        }
+
+        /// Reads public inputs of a proof composed by `prove_genesis` and
+        /// `prove_validator_set_change`. Returns `None` if the data is malformed.
+        pub fn validator_set_change_public_inputs(&self) -> Option<ValidatorSetChangePublicInputs> {
+            let circuit_data = VerifierCircuitData::<F, C, D>::from_bytes(
+                self.circuit_data.0.clone(),
+                &DefaultGateSerializer,
+            )
+            .ok()?;
+            let proof =
+                ProofWithPublicInputs::from_bytes(self.proof.0.clone(), &circuit_data.common)
+                    .ok()?;
+
+            // Public inputs go in order of `LatestValidatorSetTarget` fields.
+            let mut public_inputs = proof
+                .public_inputs
+                .iter()
+                .map(|input| input.to_canonical_u64());
+            let genesis_authority_set_id = public_inputs.next()?;
+            let genesis_authority_set_hash = public_inputs
+                .by_ref()
+                .take(BLAKE2_DIGEST_SIZE_IN_GOLDILOCKS_FIELD_ELEMENTS)
+                .flat_map(|limb| (limb as u32).to_be_bytes())
+                .collect::<Vec<_>>()
+                .try_into()
+                .ok()?;
+            let authority_set_id = public_inputs.next()?;
+
+            Some(ValidatorSetChangePublicInputs {
+                genesis_config: GenesisConfig {
+                    authority_set_id: genesis_authority_set_id,
+                    authority_set_hash: genesis_authority_set_hash,
+                },
+                authority_set_id,
+            })
+        }
+    }
+
+    /// Public inputs of a proof of authority set changes from genesis.
+    #[derive(Clone, Copy)]
+    pub struct ValidatorSetChangePublicInputs {
+        pub genesis_config: GenesisConfig,
+        /// Authority set the changes are proven up to.
+        pub authority_set_id: u64,
     }
 
     /// All the data that's exported to `gnark-wrapper` as `JSON` strings.
@@ -256,13 +321,13 @@ pub mod proving {
             next_validator_set_storage_data: next_validator_set_data,
         };
 
-        let previous_proof: common::ProofWithCircuitData<NextValidatorSetTarget> =
+        let previous_proof: common::ProofWithCircuitData<LatestValidatorSetTarget> =
             previous_proof.into_plonky2_repr();
 
         let proof = LatestValidatorSet {
             change_proof: next_change,
         }
-        .prove_recursive(previous_proof.proof());
+        .prove_recursive(previous_proof);
 
         ProofWithCircuitData::from_plonky2_repr(&proof)
     }
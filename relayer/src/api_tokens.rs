@@ -0,0 +1,536 @@
+//! Access control for the relayer web server.
+//!
+//! Besides the shared `web_server_token`, which is allowed to call every route, operators may
+//! issue scoped tokens in a TOML file:
+//!
+//! ```toml
+//! [[tokens]]
+//! name = "partner-a"
+//! token_sha256 = "<hex-encoded sha256 of the token>"
+//! scopes = ["merkle_root_proof"]
+//! requests = 60
+//! period = "1m"
+//! ```
+//!
+//! The file is re-read when it changes, so tokens are issued and revoked (`revoked = true` or
+//! removing the entry) without restarting the relayer.
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Name the shared `web_server_token` is reported under in the audit log.
+pub const SHARED_TOKEN_NAME: &str = "web_server_token";
+
+const DEFAULT_QUOTA_PERIOD: &str = "1m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// `POST /relay_messages`
+    RelayMessages,
+    /// `POST /relay_transactions`
+    RelayTransactions,
+    /// `POST /get_merkle_root_proof`
+    MerkleRootProof,
+    /// `GET /status/...`
+    Status,
+    /// `GET /events`
+    Events,
+    /// `/failed_transactions` routes
+    FailedTransactions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub requests: u32,
+    pub period: Duration,
+}
+
+/// Token holder a request is authorized for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denied {
+    /// Token is missing, unknown or revoked.
+    Unauthorized,
+    /// Token isn't allowed to call the route.
+    Forbidden { name: String },
+    /// Token used up its quota for the current period.
+    QuotaExceeded { name: String, retry_after: Duration },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTokensFile {
+    #[serde(default)]
+    tokens: Vec<RawToken>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawToken {
+    name: String,
+    token_sha256: String,
+    scopes: Vec<Scope>,
+    #[serde(default)]
+    requests: Option<u32>,
+    #[serde(default)]
+    period: Option<String>,
+    #[serde(default)]
+    revoked: bool,
+}
+
+struct Token {
+    name: String,
+    scopes: HashSet<Scope>,
+    quota: Option<Quota>,
+}
+
+#[derive(Default)]
+struct LoadedTokens {
+    /// Tokens keyed by sha256 of their value.
+    tokens: HashMap<[u8; 32], Token>,
+    version: Option<FileVersion>,
+}
+
+/// Version of the tokens file the tokens are loaded from. The modification time alone
+/// misses a rewrite within the timestamp granularity of the file system, so the length
+/// and hash of the contents are compared as well.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct FileVersion {
+    modified: SystemTime,
+    len: usize,
+    sha256: [u8; 32],
+}
+
+struct Window {
+    started: Instant,
+    requests: u32,
+}
+
+pub struct ApiTokens {
+    shared_token: RwLock<[u8; 32]>,
+    file: Option<PathBuf>,
+    loaded: RwLock<LoadedTokens>,
+    windows: Mutex<HashMap<String, Window>>,
+    audit_log: Option<Mutex<File>>,
+}
+
+impl ApiTokens {
+    /// Only the shared token is accepted, with access to every route.
+    pub fn new(shared_token: String) -> Self {
+        Self {
+            shared_token: RwLock::new(sha256(&shared_token)),
+            file: None,
+            loaded: RwLock::new(LoadedTokens::default()),
+            windows: Mutex::new(HashMap::new()),
+            audit_log: None,
+        }
+    }
+
+    /// Accepts the shared token along with scoped tokens from `tokens_file`, and appends an
+    /// entry per request to `audit_log` when it's set.
+    pub fn load(
+        shared_token: String,
+        tokens_file: Option<PathBuf>,
+        audit_log: Option<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let audit_log = audit_log
+            .map(|path| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .with_context(|| format!("Failed to open audit log {}", path.display()))
+            })
+            .transpose()?
+            .map(Mutex::new);
+
+        let tokens = Self {
+            file: tokens_file,
+            audit_log,
+            ..Self::new(shared_token)
+        };
+        tokens.reload()?;
+
+        Ok(tokens)
+    }
+
+    /// Re-reads the tokens file if it was changed since the last load. Returns whether
+    /// the tokens were reloaded.
+    pub fn reload(&self) -> anyhow::Result<bool> {
+        let Some(path) = &self.file else {
+            return Ok(false);
+        };
+
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .with_context(|| format!("Failed to read metadata of {}", path.display()))?;
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read tokens file {}", path.display()))?;
+        let version = FileVersion {
+            modified,
+            len: contents.len(),
+            sha256: sha256(&contents),
+        };
+        if self.loaded.read().expect("Tokens lock poisoned").version == Some(version) {
+            return Ok(false);
+        }
+
+        let tokens = parse_tokens_file(path, &contents)?;
+        *self.loaded.write().expect("Tokens lock poisoned") = LoadedTokens {
+            tokens,
+            version: Some(version),
+        };
+
+        Ok(true)
+    }
+
+    /// Replaces the shared token. Requests made with the previous one are rejected from now on.
+    pub fn set_shared_token(&self, shared_token: &str) {
+        *self.shared_token.write().expect("Tokens lock poisoned") = sha256(shared_token);
+    }
+
+    /// Periodically reloads the tokens file. A file which fails to parse is reported and the
+    /// previously loaded tokens stay in effect. Stops once the tokens are dropped.
+    pub fn watch(self: Arc<Self>, interval: Duration) {
+        if self.file.is_none() {
+            return;
+        }
+
+        let tokens = Arc::downgrade(&self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let Some(tokens) = tokens.upgrade() else {
+                    return;
+                };
+
+                match tokens.reload() {
+                    Ok(true) => log::info!("Reloaded web server tokens"),
+                    Ok(false) => {}
+                    Err(err) => log::error!("Failed to reload web server tokens: {err:?}"),
+                }
+            }
+        });
+    }
+
+    pub fn authorize(&self, token: Option<&str>, scope: Scope) -> Result<Caller, Denied> {
+        let Some(token) = token else {
+            return Err(Denied::Unauthorized);
+        };
+
+        let hash = sha256(token);
+        if hash == *self.shared_token.read().expect("Tokens lock poisoned") {
+            return Ok(Caller {
+                name: SHARED_TOKEN_NAME.to_string(),
+            });
+        }
+
+        let (name, quota) = {
+            let loaded = self.loaded.read().expect("Tokens lock poisoned");
+            let token = loaded.tokens.get(&hash).ok_or(Denied::Unauthorized)?;
+            if !token.scopes.contains(&scope) {
+                return Err(Denied::Forbidden {
+                    name: token.name.clone(),
+                });
+            }
+
+            (token.name.clone(), token.quota)
+        };
+
+        if let Some(quota) = quota {
+            self.consume(&name, quota)?;
+        }
+
+        Ok(Caller { name })
+    }
+
+    fn consume(&self, name: &str, quota: Quota) -> Result<(), Denied> {
+        let mut windows = self.windows.lock().expect("Quota lock poisoned");
+        let now = Instant::now();
+        let window = windows.entry(name.to_string()).or_insert(Window {
+            started: now,
+            requests: 0,
+        });
+
+        let elapsed = now.duration_since(window.started);
+        if elapsed >= quota.period {
+            window.started = now;
+            window.requests = 0;
+        }
+
+        if window.requests >= quota.requests {
+            return Err(Denied::QuotaExceeded {
+                name: name.to_string(),
+                retry_after: quota.period.saturating_sub(elapsed),
+            });
+        }
+
+        window.requests += 1;
+
+        Ok(())
+    }
+
+    /// Records a request in the audit log. `blocks` are the merkle root proofs requested.
+    pub fn audit(&self, caller: Option<&str>, route: &str, status: u16, blocks: Option<&[u32]>) {
+        let Some(audit_log) = &self.audit_log else {
+            return;
+        };
+
+        let record = AuditRecord {
+            time: chrono::Utc::now().to_rfc3339(),
+            token: caller,
+            route,
+            status,
+            blocks,
+        };
+        let line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(err) => {
+                log::error!("Failed to serialize audit record: {err:?}");
+                return;
+            }
+        };
+
+        let mut file = audit_log.lock().expect("Audit log lock poisoned");
+        if let Err(err) = writeln!(file, "{line}") {
+            log::error!("Failed to write audit record: {err:?}");
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    time: String,
+    token: Option<&'a str>,
+    route: &'a str,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocks: Option<&'a [u32]>,
+}
+
+fn sha256(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+fn parse_tokens_file(path: &Path, contents: &str) -> anyhow::Result<HashMap<[u8; 32], Token>> {
+    let raw: RawTokensFile = toml::from_str(contents)
+        .with_context(|| format!("Failed to parse tokens file {}", path.display()))?;
+
+    let mut names = HashSet::new();
+    let mut tokens = HashMap::new();
+    for raw in raw.tokens {
+        let name = raw.name.trim().to_string();
+        if name.is_empty() {
+            return Err(anyhow!("tokens: name must not be empty"));
+        }
+        if !names.insert(name.clone()) {
+            return Err(anyhow!("tokens: name {name} is used more than once"));
+        }
+
+        let hash = hex::decode(raw.token_sha256.trim_start_matches("0x"))
+            .ok()
+            .and_then(|hash| <[u8; 32]>::try_from(hash).ok())
+            .ok_or_else(|| anyhow!("token {name}: token_sha256 must be 32 hex-encoded bytes"))?;
+
+        let quota = match (raw.requests, raw.period) {
+            (None, None) => None,
+            (None, Some(_)) => return Err(anyhow!("token {name}: period requires requests")),
+            (Some(0), _) => return Err(anyhow!("token {name}: requests must be positive")),
+            (Some(requests), period) => {
+                let period =
+                    humantime::parse_duration(period.as_deref().unwrap_or(DEFAULT_QUOTA_PERIOD))
+                        .with_context(|| format!("token {name}: period is invalid"))?;
+                if period.is_zero() {
+                    return Err(anyhow!("token {name}: period must be positive"));
+                }
+
+                Some(Quota { requests, period })
+            }
+        };
+
+        if raw.revoked {
+            continue;
+        }
+
+        let token = Token {
+            name: name.clone(),
+            scopes: raw.scopes.into_iter().collect(),
+            quota,
+        };
+        if tokens.insert(hash, token).is_some() {
+            return Err(anyhow!("token {name}: token_sha256 is used more than once"));
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens_file(contents: &str) -> tempfile::TempPath {
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn entry(name: &str, token: &str, extra: &str) -> String {
+        format!(
+            "[[tokens]]\nname = \"{name}\"\ntoken_sha256 = \"{}\"\nscopes = [\"merkle_root_proof\"]\n{extra}\n",
+            hex::encode(sha256(token))
+        )
+    }
+
+    #[test]
+    fn shared_token_has_every_scope() {
+        let tokens = ApiTokens::new("secret".to_string());
+
+        for scope in [Scope::RelayMessages, Scope::MerkleRootProof, Scope::Events] {
+            assert_eq!(
+                tokens.authorize(Some("secret"), scope).unwrap().name,
+                SHARED_TOKEN_NAME
+            );
+        }
+        assert_eq!(
+            tokens.authorize(Some("other"), Scope::Status),
+            Err(Denied::Unauthorized)
+        );
+        assert_eq!(
+            tokens.authorize(None, Scope::Status),
+            Err(Denied::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn shared_token_is_replaced() {
+        let tokens = ApiTokens::new("secret".to_string());
+        tokens.set_shared_token("rotated");
+
+        assert_eq!(
+            tokens.authorize(Some("secret"), Scope::Status),
+            Err(Denied::Unauthorized)
+        );
+        assert!(tokens.authorize(Some("rotated"), Scope::Status).is_ok());
+    }
+
+    #[test]
+    fn scoped_token_is_limited_to_its_scopes() {
+        let path = tokens_file(&entry("partner", "partner-token", ""));
+        let tokens = ApiTokens::load("secret".to_string(), Some(path.to_path_buf()), None).unwrap();
+
+        assert!(tokens
+            .authorize(Some("partner-token"), Scope::MerkleRootProof)
+            .is_ok());
+        assert_eq!(
+            tokens.authorize(Some("partner-token"), Scope::RelayMessages),
+            Err(Denied::Forbidden {
+                name: "partner".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn quota_limits_requests_per_period() {
+        let path = tokens_file(&entry(
+            "partner",
+            "partner-token",
+            "requests = 2\nperiod = \"1h\"",
+        ));
+        let tokens = ApiTokens::load("secret".to_string(), Some(path.to_path_buf()), None).unwrap();
+
+        for _ in 0..2 {
+            assert!(tokens
+                .authorize(Some("partner-token"), Scope::MerkleRootProof)
+                .is_ok());
+        }
+        assert!(matches!(
+            tokens.authorize(Some("partner-token"), Scope::MerkleRootProof),
+            Err(Denied::QuotaExceeded { retry_after, .. }) if retry_after > Duration::from_secs(3500)
+        ));
+        // The shared token isn't metered.
+        assert!(tokens
+            .authorize(Some("secret"), Scope::MerkleRootProof)
+            .is_ok());
+    }
+
+    #[test]
+    fn revocation_applies_on_reload() {
+        let path = tokens_file(&entry("partner", "partner-token", ""));
+        let tokens = ApiTokens::load("secret".to_string(), Some(path.to_path_buf()), None).unwrap();
+        assert!(tokens
+            .authorize(Some("partner-token"), Scope::MerkleRootProof)
+            .is_ok());
+        assert!(!tokens.reload().unwrap());
+
+        std::fs::write(&path, entry("partner", "partner-token", "revoked = true")).unwrap();
+
+        assert!(tokens.reload().unwrap());
+        assert_eq!(
+            tokens.authorize(Some("partner-token"), Scope::MerkleRootProof),
+            Err(Denied::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_tokens_file() {
+        let cases = [
+            (entry("a", "x", "") + &entry("a", "y", ""), "more than once"),
+            (entry("a", "x", "requests = 0"), "must be positive"),
+            (entry("a", "x", "period = \"1m\""), "requires requests"),
+            (
+                "[[tokens]]\nname = \"a\"\ntoken_sha256 = \"00\"\nscopes = []\n".to_string(),
+                "32 hex-encoded bytes",
+            ),
+        ];
+
+        for (contents, expected) in cases {
+            let path = tokens_file(&contents);
+            let err = ApiTokens::load("secret".to_string(), Some(path.to_path_buf()), None)
+                .err()
+                .unwrap();
+            assert!(format!("{err:?}").contains(expected), "{err:?}");
+        }
+    }
+
+    #[test]
+    fn audit_log_records_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let tokens = ApiTokens::load("secret".to_string(), None, Some(path.clone())).unwrap();
+
+        tokens.audit(
+            Some("partner"),
+            "/get_merkle_root_proof",
+            200,
+            Some(&[1, 2]),
+        );
+        tokens.audit(None, "/relay_messages", 401, None);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let records: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["token"], "partner");
+        assert_eq!(records[0]["blocks"], serde_json::json!([1, 2]));
+        assert_eq!(records[1]["token"], serde_json::Value::Null);
+        assert_eq!(records[1]["status"], 401);
+    }
+}
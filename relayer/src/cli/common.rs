@@ -1,3 +1,6 @@
+use crate::message_relayer::common::fee_market::{
+    FeeMarketConfig, DEFAULT_GAS_PER_MESSAGE, DEFAULT_MARGIN_PERCENT,
+};
 use clap::Args;
 pub use cli_utils::{
     BeaconConnectionArgs, BlockStorageArgs, EthereumConnectionArgs, EthereumTxArgs,
@@ -20,6 +23,11 @@ pub struct EthereumSignerArgs {
     /// Private key for fee payer
     #[arg(long = "eth-fee-payer", env = "ETH_FEE_PAYER")]
     pub eth_fee_payer: String,
+
+    /// File to persist allocated fee payer nonces to, so that none is reused or skipped
+    /// after a restart
+    #[arg(long = "eth-nonce-file", env = "ETH_NONCE_FILE")]
+    pub nonce_file: Option<PathBuf>,
 }
 
 #[derive(Args)]
@@ -68,6 +76,68 @@ pub struct RelayerHttpArgs {
     pub timeout_secs: u64,
 }
 
+#[derive(Args)]
+pub struct WebServerAccessArgs {
+    /// TOML file with scoped web-server tokens. It's re-read when changed, so tokens
+    /// can be issued and revoked without a restart
+    #[arg(long = "web-server-tokens", env = "WEB_SERVER_TOKENS")]
+    pub tokens_file: Option<PathBuf>,
+
+    /// File to append an audit record of every web-server request to
+    #[arg(long = "web-server-audit-log", env = "WEB_SERVER_AUDIT_LOG")]
+    pub audit_log: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct FeeMarketArgs {
+    /// Price of one ETH in VARA. When set, messages whose fee doesn't cover the estimated
+    /// delivery cost are held back until it does
+    #[arg(
+        long = "fee-eth-price-in-vara",
+        env = "FEE_ETH_PRICE_IN_VARA",
+        value_parser = parse_eth_price_in_vara
+    )]
+    pub eth_price_in_vara: Option<f64>,
+
+    /// Gas a message delivery is estimated to take
+    #[arg(
+        long = "fee-gas-per-message",
+        env = "FEE_GAS_PER_MESSAGE",
+        default_value_t = DEFAULT_GAS_PER_MESSAGE
+    )]
+    pub gas_per_message: u64,
+
+    /// Margin over the delivery cost the fee has to cover, in percent
+    #[arg(
+        long = "fee-margin-percent",
+        env = "FEE_MARGIN_PERCENT",
+        default_value_t = DEFAULT_MARGIN_PERCENT
+    )]
+    pub margin_percent: u32,
+}
+
+impl FeeMarketArgs {
+    pub fn config(&self) -> Option<FeeMarketConfig> {
+        self.eth_price_in_vara
+            .map(|eth_price_in_vara| FeeMarketConfig {
+                eth_price_in_vara,
+                gas_per_message: self.gas_per_message,
+                margin_percent: self.margin_percent,
+            })
+    }
+}
+
+pub fn parse_eth_price_in_vara(s: &str) -> anyhow::Result<f64> {
+    let price: f64 = s.trim().parse()?;
+    if !price.is_finite() || price <= 0.0 {
+        return Err(anyhow::anyhow!(
+            "Invalid ETH price in VARA: {s}. Expected a positive number"
+        ));
+    }
+
+    Ok(price)
+}
+
 // Type aliases for backward compatibility or clarity if needed, though we use the structs directly above.
 pub type GearArgs = GearConnectionArgs;
 // BeaconRpcArgs was used in relayer, mapping to BeaconConnectionArgs
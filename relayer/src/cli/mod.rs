@@ -1,14 +1,16 @@
 use clap::{Args, Parser, Subcommand};
+use cli_utils::{KeyKind, KeyResolver, SignerArgs};
 use std::{path::PathBuf, time::Duration};
 
 mod common;
 
 pub use common::{
-    BeaconRpcArgs, EthereumArgs, EthereumKillSwitchArgs, EthereumSignerArgs, GearArgs,
-    GearSignerArgs, GenesisConfigArgs, PrometheusArgs, ProofStorageArgs,
+    BeaconRpcArgs, EthereumArgs, EthereumConnectionArgs, EthereumKillSwitchArgs,
+    EthereumSignerArgs, EthereumTxArgs, GearArgs, GearSignerArgs, GenesisConfigArgs,
+    PrometheusArgs, ProofStorageArgs, RelayerHttpArgs,
 };
 
-use crate::cli::common::{BlockStorageArgs, RelayerHttpArgs};
+use crate::cli::common::{BlockStorageArgs, FeeMarketArgs, WebServerAccessArgs};
 
 pub const DEFAULT_COUNT_CONFIRMATIONS: u64 = 8;
 pub const DEFAULT_COUNT_THREADS: usize = 24;
@@ -19,13 +21,21 @@ pub const DEFAULT_COUNT_THREADS: usize = 24;
 pub struct Cli {
     #[command(subcommand)]
     pub command: CliCommands,
+
+    #[clap(flatten)]
+    pub signer: SignerArgs,
 }
 
 #[allow(clippy::enum_variant_names)]
 #[derive(Subcommand)]
 pub enum CliCommands {
+    /// Start services declared in a config file in one process
+    Run(RunArgs),
+
     /// Start core protocol gear to ethereum relayer
     GearEthCore(GearEthCoreArgs),
+    /// Start remote worker generating final proofs for gear-eth-core prover pool
+    ProverWorker(ProverWorkerArgs),
     /// Start core protocol ethereum to gear relayer
     EthGearCore(EthGearCoreArgs),
 
@@ -50,6 +60,204 @@ pub enum CliCommands {
 
     /// Regenerate PlonkVerifier.sol
     UpdateVerifierSol(UpdateVerifierSolArgs),
+
+    /// Export, import or verify gear-eth-core proof storage
+    ProofStorage(ProofStorageToolArgs),
+
+    /// Replay stored gear-eth-core merkle roots through submission policies
+    ReplaySubmissionPolicy(ReplaySubmissionPolicyArgs),
+
+    /// Record gear-eth-core prover inputs or benchmark the prover on them
+    ProverBench(ProverBenchArgs),
+
+    /// Inspect, retry or discard failed transactions of a running token relayer
+    FailedTransactions(FailedTransactionsArgs),
+
+    /// Encrypt a key read from stdin into a keystore
+    CreateKeystore(CreateKeystoreArgs),
+}
+
+impl CliCommands {
+    /// Replaces key references passed in arguments with the keys, see [`cli_utils::signer`].
+    /// Keys referenced in config files are resolved when the config is used.
+    pub fn resolve_keys(&mut self, keys: &KeyResolver) -> anyhow::Result<()> {
+        match self {
+            Self::GearEthCore(args) => {
+                if let Some(fee_payer) = &mut args.ethereum_args.eth_fee_payer {
+                    keys.resolve_in_place(KeyKind::Ethereum, fee_payer)?;
+                }
+                if let Some(fee_payer) = &mut args.proof_storage_args.gear_fee_payer {
+                    keys.resolve_in_place(KeyKind::Gear, fee_payer)?;
+                }
+            }
+            Self::EthGearCore(EthGearCoreArgs { gear_args, .. })
+            | Self::EthGearTokens(EthGearTokensArgs { gear_args, .. })
+            | Self::EthGearManual(EthGearManualArgs { gear_args, .. }) => {
+                keys.resolve_in_place(KeyKind::Gear, &mut gear_args.suri)?;
+            }
+            Self::GearEthTokens(GearEthTokensArgs { ethereum_args, .. })
+            | Self::GearEthManual(GearEthManualArgs { ethereum_args, .. }) => {
+                keys.resolve_in_place(KeyKind::Ethereum, &mut ethereum_args.eth_fee_payer)?;
+            }
+            Self::QueueCleaner(args) => keys.resolve_in_place(KeyKind::Gear, &mut args.suri)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct CreateKeystoreArgs {
+    /// Kind of the key: hex-encoded private key for ethereum, SURI for gear
+    #[arg(long, value_enum)]
+    pub kind: KeyKind,
+
+    /// Path to the keystore to create. It is encrypted with the password from
+    /// `--keystore-password-file`
+    #[arg(long)]
+    pub output: PathBuf,
+
+    /// Use fast key derivation. Only for tests and local networks
+    #[arg(long)]
+    pub light_kdf: bool,
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    /// Path to TOML config declaring the services to run
+    #[arg(long = "config", env = "RELAYER_DAEMON_CONFIG")]
+    pub config: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ReplaySubmissionPolicyArgs {
+    /// Path to gear-eth-core TOML config. Storage and policies are read from it
+    #[arg(long = "config", env = "RELAYER_CONFIG")]
+    pub config: PathBuf,
+
+    /// Relayer id from the config. Can be omitted if the config contains a single relayer
+    #[arg(long = "relayer")]
+    pub relayer: Option<String>,
+
+    /// Base fee in gwei assumed during the whole history when `--base-fees` isn't set
+    #[arg(long = "base-fee-gwei", default_value_t = 10)]
+    pub base_fee_gwei: u64,
+
+    /// JSON file with base fee samples: `[{"block_number": <gear block>, "base_fee": <wei>}]`
+    #[arg(long = "base-fees")]
+    pub base_fees: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct FailedTransactionsArgs {
+    #[clap(flatten)]
+    pub relayer_http_args: RelayerHttpArgs,
+
+    #[command(subcommand)]
+    pub command: FailedTransactionsCommands,
+}
+
+#[derive(Subcommand)]
+pub enum FailedTransactionsCommands {
+    /// List transactions in the dead-letter queue
+    List,
+    /// Put a failed transaction back into the relay queue
+    Retry {
+        /// UUID of the transaction
+        uuid: String,
+
+        /// Stage to restart the transaction at, e.g. `send_message`. Defaults to the stage
+        /// it failed at
+        #[arg(long = "stage")]
+        stage: Option<String>,
+    },
+    /// Drop a failed transaction for good
+    Discard {
+        /// UUID of the transaction
+        uuid: String,
+    },
+}
+
+#[derive(Args)]
+pub struct ProofStorageToolArgs {
+    /// Path to gear-eth-core TOML config. Proof storage and genesis config are read from it
+    #[arg(long = "config", env = "RELAYER_CONFIG")]
+    pub config: PathBuf,
+
+    /// Relayer id from the config. Can be omitted if the config contains a single relayer
+    #[arg(long = "relayer")]
+    pub relayer: Option<String>,
+
+    #[command(subcommand)]
+    pub command: ProofStorageCommands,
+}
+
+#[derive(Subcommand)]
+pub enum ProofStorageCommands {
+    /// Dump the proof chain into an archive
+    Export {
+        /// Path to the archive to create
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Write the proof chain from an archive into the proof storage
+    Import {
+        /// Path to the archive to read
+        #[arg(long)]
+        input: PathBuf,
+    },
+    /// Check that every proof in the chain verifies against the stored circuit data
+    Verify {
+        /// Verify an archive instead of the configured proof storage
+        #[arg(long)]
+        archive: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+pub struct ProverBenchArgs {
+    #[command(subcommand)]
+    pub command: ProverBenchCommands,
+}
+
+#[derive(Subcommand)]
+pub enum ProverBenchCommands {
+    /// Fetch inputs of every circuit from a Gear node into a fixture
+    Record {
+        #[clap(flatten)]
+        gear_args: GearArgs,
+
+        /// Block with the merkle root to prove. Defaults to the latest finalized block.
+        /// Genesis is the authority set preceding the one of the block
+        #[arg(long)]
+        block_number: Option<u32>,
+
+        /// Path to the fixture to create
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Prove every circuit with the fixture and print statistics as JSON
+    Run {
+        /// Path to the fixture to read
+        #[arg(long)]
+        fixture: PathBuf,
+
+        #[arg(
+            long = "thread-counts",
+            value_delimiter = ',',
+            help = format!("Comma-separated counts of worker threads to prove with, one run per count.\n\nDefault is: {DEFAULT_COUNT_THREADS}."),
+        )]
+        thread_counts: Vec<usize>,
+
+        /// Path to gnark data directory. The gnark wrap is skipped when not set
+        #[arg(long = "gnark-data-path", env = "GNARK_DATA_PATH")]
+        gnark_data_path: Option<PathBuf>,
+
+        /// Path to write the report to instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
 }
 
 #[derive(Args)]
@@ -140,6 +348,9 @@ pub struct GearEthCoreArgs {
     #[arg(long, env, default_value = "127.0.0.1:8443")]
     pub web_server_address: String,
 
+    #[clap(flatten)]
+    pub web_server_access: WebServerAccessArgs,
+
     #[arg(
         long,
         help = format!("Count of worker threads for generating signing proofs.\n\nNote that each thread allocates memory, which can lead to an out-of-memory error with a large number of threads.\n\nDefault is: {DEFAULT_COUNT_THREADS}. The value is safe to run the relayer on a machine with 96 CPU cores and 256GiB of RAM."),
@@ -171,12 +382,57 @@ pub struct GearEthCoreArgs {
     pub startup_sync_blocks: Vec<u32>,
 }
 
+#[derive(Args)]
+pub struct ProverWorkerArgs {
+    /// URL of the gear-eth-core prover pool
+    #[arg(long = "pool-url", env = "PROVER_POOL_URL")]
+    pub pool_url: String,
+
+    /// Authorization token for the prover pool
+    #[arg(long = "pool-token", env = "PROVER_POOL_TOKEN")]
+    pub pool_token: String,
+
+    /// Identifier of this worker. If not specified a random one is generated
+    #[arg(long = "worker-id", env = "PROVER_WORKER_ID")]
+    pub worker_id: Option<String>,
+
+    #[clap(flatten)]
+    pub gear_args: GearArgs,
+
+    #[arg(
+        long,
+        help = format!("Count of worker threads for generating signing proofs.\n\nNote that each thread allocates memory, which can lead to an out-of-memory error with a large number of threads.\n\nDefault is: {DEFAULT_COUNT_THREADS}. The value is safe to run the relayer on a machine with 96 CPU cores and 256GiB of RAM."),
+        value_parser = parse_thread_count,
+    )]
+    pub thread_count: Option<ThreadCount>,
+
+    /// Path to gnark data directory
+    #[arg(
+        long = "gnark-data-path",
+        env = "GNARK_DATA_PATH",
+        default_value = "data"
+    )]
+    pub gnark_data_path: PathBuf,
+}
+
 #[derive(Args)]
 pub struct GearEthCoreEthereumSignerArgs {
     /// Address of the ethereum endpoint
     #[arg(long = "ethereum-endpoint", env = "ETH_RPC")]
     pub ethereum_endpoint: Option<String>,
 
+    /// Ethereum endpoints to fall over to when the main one fails
+    #[arg(
+        long = "ethereum-fallback-endpoint",
+        env = "ETH_RPC_FALLBACKS",
+        value_delimiter = ','
+    )]
+    pub fallback_endpoints: Vec<String>,
+
+    /// Number of endpoints that must agree on merkle roots read from Ethereum, a majority of them
+    #[arg(long = "eth-read-quorum", env = "ETH_RPC_READ_QUORUM")]
+    pub read_quorum: Option<usize>,
+
     /// Number of retries for the ethereum endpoint
     #[arg(long = "eth-max-retries", env = "ETH_RPC_MAX_RETRIES")]
     pub max_retries: Option<u32>,
@@ -203,6 +459,11 @@ pub struct GearEthCoreEthereumSignerArgs {
     /// Private key for fee payer
     #[arg(long = "eth-fee-payer", env = "ETH_FEE_PAYER")]
     pub eth_fee_payer: Option<String>,
+
+    /// File to persist allocated fee payer nonces to, so that none is reused or skipped
+    /// after a restart
+    #[arg(long = "eth-nonce-file", env = "ETH_NONCE_FILE")]
+    pub nonce_file: Option<PathBuf>,
 }
 
 #[derive(Args)]
@@ -309,6 +570,19 @@ pub struct GearEthTokensArgs {
     /// Storage path for Ethereum blocks.
     #[arg(long = "ethereum-blocks", env = "ETHEREUM_BLOCKS")]
     pub ethereum_blocks: Option<String>,
+
+    /// How many messages of the same merkle root to deliver in one Ethereum transaction.
+    /// 1 disables batching
+    #[arg(
+        long = "max-batch-size",
+        env = "GEAR_ETH_MAX_BATCH_SIZE",
+        default_value = "1"
+    )]
+    pub max_batch_size: usize,
+
+    /// Address of the Multicall3 contract delivering batches. Default: the canonical deployment
+    #[arg(long = "multicall-address", env = "ETH_MULTICALL_ADDRESS")]
+    pub multicall_address: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -328,6 +602,12 @@ pub enum GearEthTokensCommands {
         /// Socket address for web-server
         #[arg(long, env, default_value = "127.0.0.1:8443")]
         web_server_address: String,
+
+        #[clap(flatten)]
+        web_server_access: WebServerAccessArgs,
+
+        #[clap(flatten)]
+        fee_market: FeeMarketArgs,
     },
 }
 
@@ -356,6 +636,11 @@ pub struct EthGearTokensArgs {
     #[arg(long = "storage-path", env = "ETH_GEAR_TX_STORAGE_PATH")]
     pub storage_path: String,
 
+    /// Path to the embedded database for transactions and blocks. When set, it's used
+    /// instead of the JSON files under the storage path, which are migrated on the first start.
+    #[arg(long = "storage-database", env = "ETH_GEAR_TX_STORAGE_DATABASE")]
+    pub storage_database: Option<PathBuf>,
+
     /// Storage path for Ethereum blocks.
     #[arg(long = "ethereum-blocks", env = "ETHEREUM_BLOCKS")]
     pub ethereum_blocks: Option<String>,
@@ -382,6 +667,9 @@ pub enum EthGearTokensCommands {
         /// Socket address for web-server
         #[arg(long, env, default_value = "127.0.0.1:8443")]
         web_server_address: String,
+
+        #[clap(flatten)]
+        web_server_access: WebServerAccessArgs,
     },
 }
 
@@ -460,10 +748,37 @@ pub struct KillSwitchArgs {
     #[clap(flatten)]
     pub ethereum_args: EthereumKillSwitchArgs,
 
+    #[clap(flatten)]
+    pub verification_args: KillSwitchVerificationArgs,
+
     #[clap(flatten)]
     pub prometheus_args: PrometheusArgs,
 }
 
+#[derive(Args)]
+pub struct KillSwitchVerificationArgs {
+    /// Additional Gear endpoints merkle roots are cross-checked against
+    #[arg(
+        long = "gear-verification-endpoint",
+        env = "GEAR_VERIFICATION_ENDPOINTS",
+        value_delimiter = ','
+    )]
+    pub endpoints: Vec<String>,
+
+    /// Number of Gear endpoints which must agree before a merkle root is challenged or
+    /// accepted. Defaults to all of them
+    #[arg(long = "gear-verification-quorum", env = "GEAR_VERIFICATION_QUORUM")]
+    pub quorum: Option<usize>,
+
+    /// URL mismatches and challenges are POSTed to as JSON
+    #[arg(long = "alert-webhook-url", env = "KILL_SWITCH_ALERT_WEBHOOK_URL")]
+    pub alert_webhook_url: Option<String>,
+
+    /// File mismatches and challenges are appended to as JSON lines
+    #[arg(long = "alert-file", env = "KILL_SWITCH_ALERT_FILE")]
+    pub alert_file: Option<PathBuf>,
+}
+
 #[derive(Args)]
 pub struct QueueCleanerArgs {
     #[clap(flatten)]
@@ -586,4 +901,15 @@ mod tests {
         };
         assert!(err.contains("--ethereum-endpoint/ETH_RPC"));
     }
+
+    #[test]
+    fn eth_price_in_vara_must_be_positive() {
+        assert_eq!(
+            common::parse_eth_price_in_vara("250000").unwrap(),
+            250_000.0
+        );
+        for price in ["0", "-1", "NaN", "inf", "price"] {
+            assert!(common::parse_eth_price_in_vara(price).is_err());
+        }
+    }
 }
@@ -1,10 +1,6 @@
 use std::{sync::Arc, time::Duration};
 
-use alloy::{
-    network::Ethereum,
-    providers::PendingTransactionBuilder,
-    transports::{RpcError, TransportErrorKind},
-};
+use alloy::transports::{RpcError, TransportErrorKind};
 use gsdk::ext::subxt_rpcs;
 use prover::proving::GenesisConfig;
 use tokio::sync::mpsc::UnboundedSender;
@@ -107,18 +103,18 @@ pub(crate) async fn sync_authority_set_id(
 pub(crate) async fn submit_merkle_root_to_ethereum(
     eth_api: &EthApi,
     proof: FinalProof,
-) -> Result<PendingTransactionBuilder<Ethereum>, ethereum_client::Error> {
+) -> Result<TxHash, ethereum_client::Error> {
     log::info!(
         "Submitting merkle root {} at block #{} to ethereum",
         hex::encode(proof.merkle_root),
         proof.block_number
     );
 
-    let pending_tx = eth_api
+    let tx_hash = eth_api
         .provide_merkle_root(proof.block_number, proof.merkle_root, proof.proof)
         .await?;
 
-    Ok(pending_tx)
+    Ok(tx_hash)
 }
 
 pub(crate) async fn send_challege_root_to_ethereum(
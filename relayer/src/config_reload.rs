@@ -0,0 +1,649 @@
+//! Live reload of the `gear-eth-core` TOML config.
+//!
+//! The config is loaded again on `SIGHUP` or when the file is modified, validated the same
+//! way as on startup and compared with the config in effect. Spike settings,
+//! `critical_threshold`, fee caps, fee bumping and the HTTP token are applied to the running
+//! relayers.
+//! Changing any other field requires a restart, so such a reload is rejected as a whole and
+//! the previous config stays in effect.
+
+use crate::{
+    api_tokens::ApiTokens,
+    config::{
+        EffectiveConfig, EffectiveLockConfig, EffectiveProofStorageConfig, EffectiveProverConfig,
+        EffectiveRelayerConfig,
+    },
+    merkle_roots::LiveOptions,
+    proof_storage::S3Config,
+};
+use anyhow::{anyhow, Context};
+use ethereum_client::{FeeCaps, SharedFeeCaps};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    signal::unix::{self, SignalKind},
+    sync::watch,
+};
+
+/// Fields of a relayer which are applied without a restart.
+const LIVE_FIELDS: &[&str] = &[
+    "http.token",
+    "ethereum.max_fee_per_gas",
+    "ethereum.max_priority_fee_per_gas",
+    "options.critical_threshold",
+    "options.spike_window",
+    "options.spike_timeout",
+    "options.priority_spike_timeout",
+    "options.spike_threshold",
+    "fee_bumping.stuck_timeout",
+    "fee_bumping.bump_percent",
+    "fee_bumping.max_fee_per_gas_ceiling",
+    "fee_bumping.max_priority_fee_per_gas_ceiling",
+];
+
+const REDACTED: &str = "<redacted>";
+const UNSET: &str = "<unset>";
+
+/// Field which differs between two configs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Path of the field, e.g. `relayers.mainnet.options.spike_timeout`.
+    pub field: String,
+    pub old: String,
+    pub new: String,
+    /// Whether the change is applied without a restart.
+    pub live: bool,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.old, self.new)
+    }
+}
+
+/// Changes from `old` to `new`. Values of secrets are redacted.
+pub fn diff(old: &EffectiveConfig, new: &EffectiveConfig) -> Vec<Change> {
+    let mut changes = Vec::new();
+    if old.prometheus_endpoint != new.prometheus_endpoint {
+        changes.push(Change {
+            field: "prometheus.endpoint".to_string(),
+            old: old.prometheus_endpoint.clone(),
+            new: new.prometheus_endpoint.clone(),
+            live: false,
+        });
+    }
+
+    let old_relayers: BTreeMap<_, _> = old.relayers.iter().map(|r| (r.id.as_str(), r)).collect();
+    let new_relayers: BTreeMap<_, _> = new.relayers.iter().map(|r| (r.id.as_str(), r)).collect();
+
+    for (id, old_relayer) in &old_relayers {
+        let Some(new_relayer) = new_relayers.get(id) else {
+            changes.push(Change {
+                field: format!("relayers.{id}"),
+                old: "<configured>".to_string(),
+                new: UNSET.to_string(),
+                live: false,
+            });
+            continue;
+        };
+
+        let old_fields = relayer_fields(old_relayer);
+        let mut new_fields = relayer_fields(new_relayer);
+        for (name, old_field) in old_fields {
+            let new_field = new_fields.remove(&name);
+            if new_field.as_ref() != Some(&old_field) {
+                changes.push(field_change(id, &name, Some(old_field), new_field));
+            }
+        }
+        for (name, new_field) in new_fields {
+            changes.push(field_change(id, &name, None, Some(new_field)));
+        }
+    }
+
+    for id in new_relayers.keys() {
+        if !old_relayers.contains_key(id) {
+            changes.push(Change {
+                field: format!("relayers.{id}"),
+                old: UNSET.to_string(),
+                new: "<configured>".to_string(),
+                live: false,
+            });
+        }
+    }
+
+    changes
+}
+
+fn field_change(id: &str, name: &str, old: Option<Field>, new: Option<Field>) -> Change {
+    let display = |field: Option<Field>| match field {
+        Some(field) if field.secret => REDACTED.to_string(),
+        Some(field) => field.value,
+        None => UNSET.to_string(),
+    };
+
+    Change {
+        field: format!("relayers.{id}.{name}"),
+        old: display(old),
+        new: display(new),
+        live: LIVE_FIELDS.contains(&name),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Field {
+    value: String,
+    secret: bool,
+}
+
+#[derive(Default)]
+struct Fields(BTreeMap<String, Field>);
+
+impl Fields {
+    fn add(&mut self, name: &str, value: impl fmt::Debug) {
+        self.0.insert(
+            name.to_string(),
+            Field {
+                value: format!("{value:?}"),
+                secret: false,
+            },
+        );
+    }
+
+    fn secret(&mut self, name: &str, value: &str) {
+        self.0.insert(
+            name.to_string(),
+            Field {
+                value: value.to_string(),
+                secret: true,
+            },
+        );
+    }
+
+    fn s3(&mut self, prefix: &str, s3: &S3Config) {
+        self.add(&format!("{prefix}.endpoint"), s3.endpoint.as_str());
+        self.add(&format!("{prefix}.bucket"), &s3.bucket);
+        self.add(&format!("{prefix}.region"), &s3.region);
+        self.add(&format!("{prefix}.access_key_id"), &s3.access_key_id);
+        self.secret(
+            &format!("{prefix}.secret_access_key"),
+            s3.secret_access_key.as_str(),
+        );
+    }
+}
+
+/// Flattens a relayer config into fields named after their TOML keys.
+fn relayer_fields(relayer: &EffectiveRelayerConfig) -> BTreeMap<String, Field> {
+    let mut fields = Fields::default();
+
+    fields.add("priority", relayer.priority);
+
+    fields.add("gear.endpoint", &relayer.gear.endpoint);
+    fields.add(
+        "gear.max_reconnect_attempts",
+        relayer.gear.max_reconnect_attempts,
+    );
+
+    let ethereum = &relayer.ethereum;
+    fields.add("ethereum.endpoint", &ethereum.endpoint);
+    fields.add("ethereum.fallback_endpoints", &ethereum.fallback_endpoints);
+    fields.add("ethereum.read_quorum", ethereum.read_quorum);
+    fields.add(
+        "ethereum.message_queue_address",
+        &ethereum.message_queue_address,
+    );
+    fields.secret("ethereum.fee_payer", &ethereum.fee_payer);
+    fields.add("ethereum.max_retries", ethereum.max_retries);
+    fields.add("ethereum.retry_interval_ms", ethereum.retry_interval_ms);
+    fields.add("ethereum.max_fee_per_gas", ethereum.max_fee_per_gas);
+    fields.add(
+        "ethereum.max_priority_fee_per_gas",
+        ethereum.max_priority_fee_per_gas,
+    );
+    fields.add("ethereum.nonce_file", &ethereum.nonce_file);
+
+    fields.add("http.address", &relayer.http.address);
+    fields.secret("http.token", &relayer.http.token);
+    fields.add("http.tokens_file", &relayer.http.tokens_file);
+    fields.add("http.audit_log", &relayer.http.audit_log);
+
+    fields.add("storage.block_storage", &relayer.storage.block_storage);
+    fields.add("storage.database", &relayer.storage.database);
+
+    match &relayer.proof_storage {
+        EffectiveProofStorageConfig::FileSystem { path } => {
+            fields.add("proof_storage.kind", "filesystem");
+            fields.add("proof_storage.filesystem_path", path);
+        }
+        EffectiveProofStorageConfig::Gear {
+            fee_payer,
+            config_dir,
+        } => {
+            fields.add("proof_storage.kind", "gear");
+            fields.secret("proof_storage.gear_fee_payer", fee_payer);
+            fields.add("proof_storage.config_dir", config_dir);
+        }
+        EffectiveProofStorageConfig::ObjectStore {
+            s3,
+            prefix,
+            cache_dir,
+        } => {
+            fields.add("proof_storage.kind", "s3");
+            fields.s3("proof_storage", s3);
+            fields.add("proof_storage.prefix", prefix);
+            fields.add("proof_storage.cache_dir", cache_dir);
+        }
+    }
+
+    match &relayer.prover {
+        EffectiveProverConfig::Local => fields.add("prover.kind", "local"),
+        EffectiveProverConfig::Pool {
+            address,
+            token,
+            config,
+            loopback_workers,
+        } => {
+            fields.add("prover.kind", "pool");
+            fields.add("prover.address", address);
+            fields.secret("prover.token", token);
+            fields.add("prover.lease_timeout", config.lease_timeout);
+            fields.add("prover.max_attempts", config.max_attempts);
+            fields.add("prover.loopback_workers", loopback_workers);
+        }
+        EffectiveProverConfig::Mock { latency } => {
+            fields.add("prover.kind", "mock");
+            fields.add("prover.latency", latency);
+        }
+    }
+
+    match &relayer.leader_election {
+        None => fields.add("leader_election", UNSET),
+        Some(leader_election) => {
+            fields.add("leader_election.holder_id", &leader_election.holder_id);
+            fields.add(
+                "leader_election.failover_window",
+                leader_election.failover_window,
+            );
+            match &leader_election.lock {
+                EffectiveLockConfig::File { path } => {
+                    fields.add("leader_election.kind", "file");
+                    fields.add("leader_election.path", path);
+                }
+                EffectiveLockConfig::ObjectStore { s3, key } => {
+                    fields.add("leader_election.kind", "s3");
+                    fields.s3("leader_election", s3);
+                    fields.add("leader_election.key", key);
+                }
+                EffectiveLockConfig::Database { url, name } => {
+                    fields.add("leader_election.kind", "postgres");
+                    fields.secret("leader_election.url", url);
+                    fields.add("leader_election.name", name);
+                }
+            }
+        }
+    }
+
+    let options = &relayer.options;
+    fields.add(
+        "genesis.authority_set_id",
+        options.genesis_config.authority_set_id,
+    );
+    fields.add(
+        "genesis.authority_set_hash",
+        hex::encode(options.genesis_config.authority_set_hash),
+    );
+    fields.add("gnark.data_path", &options.gnark_data_path);
+    fields.add("submission_policy", options.submission_policy);
+    fields.add(
+        "fee_bumping.stuck_timeout",
+        options.fee_bumping.stuck_timeout,
+    );
+    fields.add("fee_bumping.bump_percent", options.fee_bumping.bump_percent);
+    fields.add(
+        "fee_bumping.max_fee_per_gas_ceiling",
+        options.fee_bumping.max_fee_per_gas,
+    );
+    fields.add(
+        "fee_bumping.max_priority_fee_per_gas_ceiling",
+        options.fee_bumping.max_priority_fee_per_gas,
+    );
+    fields.add("options.confirmations_merkle_root", options.confirmations);
+    fields.add("options.start_authority_set_id", options.last_sealed);
+    fields.add(
+        "options.bridging_payment_address",
+        options.bridging_payment_address,
+    );
+    fields.add("options.thread_count", options.count_thread);
+    fields.add("options.critical_threshold", options.critical_threshold);
+    fields.add(
+        "options.startup_sync_strategy",
+        &options.startup_sync_strategy,
+    );
+    fields.add("options.spike_window", options.spike_config.window);
+    fields.add("options.spike_timeout", options.spike_config.timeout);
+    fields.add(
+        "options.priority_spike_timeout",
+        options.spike_config.priority_timeout,
+    );
+    fields.add("options.spike_threshold", options.spike_config.threshold);
+    fields.add("options.save_interval", options.save_interval);
+    fields.add("options.check_interval", options.check_interval);
+
+    fields.0
+}
+
+/// Handles through which reloaded options reach a running relayer.
+pub struct LiveRelayer {
+    pub options: watch::Sender<LiveOptions>,
+    pub tokens: Arc<ApiTokens>,
+    pub fee_caps: SharedFeeCaps,
+}
+
+pub struct ConfigReloader {
+    path: PathBuf,
+    config: EffectiveConfig,
+    modified: Option<SystemTime>,
+    relayers: HashMap<String, LiveRelayer>,
+}
+
+impl ConfigReloader {
+    /// `config` is the config the `relayers` were started with, loaded from `path`.
+    pub fn new(
+        path: PathBuf,
+        config: EffectiveConfig,
+        relayers: HashMap<String, LiveRelayer>,
+    ) -> Self {
+        let modified = modified(&path).ok();
+
+        Self {
+            path,
+            config,
+            modified,
+            relayers,
+        }
+    }
+
+    /// Loads the config file and applies it, see [`ConfigReloader::apply`].
+    pub fn reload(&mut self) -> anyhow::Result<Vec<Change>> {
+        let config = EffectiveConfig::from_path(&self.path)?;
+        self.apply(config)
+    }
+
+    /// Logs the changes from the config in effect and applies them to the running relayers.
+    /// If any of the changes requires a restart, nothing is applied.
+    pub fn apply(&mut self, config: EffectiveConfig) -> anyhow::Result<Vec<Change>> {
+        let changes = diff(&self.config, &config);
+        for change in &changes {
+            log::info!(
+                "Config reload: {change}{}",
+                if change.live {
+                    ""
+                } else {
+                    " (requires restart)"
+                }
+            );
+        }
+
+        let restart: Vec<&str> = changes
+            .iter()
+            .filter(|change| !change.live)
+            .map(|change| change.field.as_str())
+            .collect();
+        if !restart.is_empty() {
+            return Err(anyhow!(
+                "changes of {} require a restart",
+                restart.join(", ")
+            ));
+        }
+
+        for relayer in &config.relayers {
+            let Some(live) = self.relayers.get(&relayer.id) else {
+                continue;
+            };
+
+            let options = relayer.options.live();
+            live.options.send_if_modified(|current| {
+                let modified = *current != options;
+                *current = options;
+                modified
+            });
+            live.tokens.set_shared_token(&relayer.http.token);
+            live.fee_caps.set(FeeCaps::or_defaults(
+                relayer.ethereum.max_fee_per_gas,
+                relayer.ethereum.max_priority_fee_per_gas,
+            ));
+        }
+
+        self.config = config;
+        Ok(changes)
+    }
+
+    /// Whether the config file was modified since it was last checked.
+    fn file_modified(&mut self) -> bool {
+        match modified(&self.path) {
+            Ok(modified) if self.modified != Some(modified) => {
+                self.modified = Some(modified);
+                true
+            }
+            Ok(_) => false,
+            Err(err) => {
+                log::warn!(
+                    "Failed to check config file {}: {err:?}",
+                    self.path.display()
+                );
+                false
+            }
+        }
+    }
+
+    /// Reloads the config on `SIGHUP` and when the file modification time changes, checked
+    /// every `interval`.
+    pub fn spawn(mut self, interval: Duration) -> anyhow::Result<()> {
+        let mut hangup =
+            unix::signal(SignalKind::hangup()).context("Failed to set SIGHUP handler")?;
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                let trigger = tokio::select! {
+                    _ = hangup.recv() => "SIGHUP",
+                    _ = interval.tick() => {
+                        if !self.file_modified() {
+                            continue;
+                        }
+                        "file change"
+                    }
+                };
+
+                log::info!("Reloading config {} on {trigger}", self.path.display());
+                match self.reload() {
+                    Ok(changes) if changes.is_empty() => log::info!("Config reload: no changes"),
+                    Ok(changes) => log::info!("Config reload: applied {} change(s)", changes.len()),
+                    Err(err) => log::error!(
+                        "Config reload rejected, previous config stays in effect: {err:#}"
+                    ),
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> std::io::Result<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_roots::CriticalThreshold;
+    use std::fs::File;
+
+    fn config(gear_endpoint: &str, token: &str, options: &str) -> String {
+        format!(
+            r#"
+[relayers.mainnet.gear]
+endpoint = "{gear_endpoint}"
+
+[relayers.mainnet.ethereum]
+endpoint = "https://eth.example"
+message_queue_address = "0x1111111111111111111111111111111111111111"
+fee_payer = "0x{}"
+
+[relayers.mainnet.genesis]
+authority_set_hash = "0x{}"
+authority_set_id = 42
+
+[relayers.mainnet.http]
+address = "127.0.0.1:8443"
+token = "{token}"
+
+[relayers.mainnet.storage]
+block_storage = "/tmp/mainnet-blocks.json"
+
+[relayers.mainnet.proof_storage]
+kind = "filesystem"
+filesystem_path = "/tmp/mainnet-proofs"
+
+{options}
+"#,
+            "22".repeat(32),
+            "33".repeat(32),
+        )
+    }
+
+    const LIVE_OPTIONS: &str = r#"
+[relayers.mainnet.fee_bumping]
+max_fee_per_gas_ceiling_gwei = 300
+
+[relayers.mainnet.options]
+critical_threshold = "authority_set_change"
+spike_timeout = "10m"
+"#;
+
+    fn reloader(path: PathBuf, contents: &str) -> (ConfigReloader, watch::Receiver<LiveOptions>) {
+        let config = EffectiveConfig::from_toml_str(contents).unwrap();
+        let relayer = &config.relayers[0];
+        let (options, updates) = watch::channel(relayer.options.live());
+        let tokens = Arc::new(ApiTokens::new(relayer.http.token.clone()));
+        let fee_caps = SharedFeeCaps::new(FeeCaps::or_defaults(
+            relayer.ethereum.max_fee_per_gas,
+            relayer.ethereum.max_priority_fee_per_gas,
+        ));
+        let relayers = HashMap::from([(
+            relayer.id.clone(),
+            LiveRelayer {
+                options,
+                tokens,
+                fee_caps,
+            },
+        )]);
+
+        (ConfigReloader::new(path, config, relayers), updates)
+    }
+
+    #[test]
+    fn applies_live_changes_from_modified_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("relayer.toml");
+        let initial = config("wss://gear.example", "secret", "");
+        std::fs::write(&path, &initial).unwrap();
+        let (mut reloader, updates) = reloader(path.clone(), &initial);
+        assert!(!reloader.file_modified());
+
+        let modified = config("wss://gear.example", "rotated", LIVE_OPTIONS)
+            .replace("fee_payer =", "max_fee_per_gas = 3000000000\nfee_payer =");
+        std::fs::write(&path, modified).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+        assert!(reloader.file_modified());
+
+        let changes = reloader.reload().unwrap();
+        let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "relayers.mainnet.ethereum.max_fee_per_gas",
+                "relayers.mainnet.fee_bumping.max_fee_per_gas_ceiling",
+                "relayers.mainnet.http.token",
+                "relayers.mainnet.options.critical_threshold",
+                "relayers.mainnet.options.spike_timeout",
+            ]
+        );
+        assert!(changes.iter().all(|change| change.live));
+
+        assert!(updates.has_changed().unwrap());
+        let live = *updates.borrow();
+        assert_eq!(
+            live.critical_threshold,
+            CriticalThreshold::AuthoritySetChange
+        );
+        assert_eq!(live.spike_config.timeout, Duration::from_secs(10 * 60));
+        assert_eq!(live.fee_bumping.max_fee_per_gas, 300_000_000_000);
+
+        let tokens = &reloader.relayers["mainnet"].tokens;
+        assert!(tokens
+            .authorize(Some("rotated"), crate::api_tokens::Scope::Status)
+            .is_ok());
+        assert_eq!(
+            reloader.relayers["mainnet"].fee_caps.get(),
+            FeeCaps::or_defaults(Some(3_000_000_000), None)
+        );
+    }
+
+    #[test]
+    fn rejects_changes_requiring_restart() {
+        let initial = config("wss://gear.example", "secret", "");
+        let (mut reloader, updates) = reloader(PathBuf::from("relayer.toml"), &initial);
+
+        let err = reloader
+            .apply(
+                EffectiveConfig::from_toml_str(&config(
+                    "wss://other.example",
+                    "secret",
+                    LIVE_OPTIONS,
+                ))
+                .unwrap(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "changes of relayers.mainnet.gear.endpoint require a restart"
+        );
+        assert!(!updates.has_changed().unwrap());
+
+        // The rejected config isn't taken as the one in effect.
+        let changes = reloader
+            .apply(EffectiveConfig::from_toml_str(&initial).unwrap())
+            .unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        let old =
+            EffectiveConfig::from_toml_str(&config("wss://gear.example", "secret", "")).unwrap();
+        let new = EffectiveConfig::from_toml_str(
+            &config("wss://gear.example", "rotated", "").replace("22", "44"),
+        )
+        .unwrap();
+
+        let changes = diff(&old, &new);
+        assert_eq!(changes.len(), 2);
+        for change in changes {
+            assert_eq!(
+                (change.old.as_str(), change.new.as_str()),
+                (REDACTED, REDACTED)
+            );
+        }
+    }
+}
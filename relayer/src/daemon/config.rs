@@ -0,0 +1,1111 @@
+use super::supervisor::{RestartConfig, RestartPolicy};
+use crate::{
+    cli::{FeePayers, DEFAULT_COUNT_CONFIRMATIONS},
+    config::{EffectiveConfig, EffectiveHttpConfig, EffectiveProverConfig, EffectiveRelayerConfig},
+    message_relayer::common::{
+        ethereum::message_sender::DEFAULT_MAX_BATCH_SIZE,
+        fee_market::{FeeMarketConfig, DEFAULT_GAS_PER_MESSAGE, DEFAULT_MARGIN_PERCENT},
+    },
+};
+use anyhow::{anyhow, Context};
+use cli_utils::{KeyKind, KeyResolver, KeySource};
+use ethereum_client::Signer;
+use primitive_types::{H160, H256};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use url::Url;
+
+const DEFAULT_PROMETHEUS_ENDPOINT: &str = "0.0.0.0:9090";
+const DEFAULT_SIZE_BATCH_MULTIPLIER: u64 = 30;
+const DEFAULT_QUEUE_CLEANER_DELAY: u64 = 30;
+const DEFAULT_RELAYER_HTTP_TIMEOUT: &str = "30m";
+
+/// Config of the `run` command: connections shared by all services and the services
+/// themselves.
+#[derive(Clone)]
+pub struct DaemonConfig {
+    pub prometheus_endpoint: String,
+    pub gear: DaemonGearConfig,
+    pub ethereum: Option<DaemonEthereumConfig>,
+    pub beacon: Option<DaemonBeaconConfig>,
+    pub services: Vec<ServiceConfig>,
+}
+
+#[derive(Clone)]
+pub struct DaemonGearConfig {
+    pub endpoint: String,
+    pub max_reconnect_attempts: u8,
+    /// Account the services sending extrinsics sign with.
+    pub suri: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct DaemonEthereumConfig {
+    pub endpoint: String,
+    /// Endpoints to fall over to, in order of preference.
+    pub fallback_endpoints: Vec<String>,
+    pub read_quorum: Option<usize>,
+    pub message_queue_address: Option<String>,
+    /// Private key the token relayers send transactions with.
+    pub fee_payer: Option<String>,
+    pub max_retries: Option<u32>,
+    pub retry_interval_ms: Option<u64>,
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+    /// File nonces of the fee payer are persisted to.
+    pub nonce_file: Option<PathBuf>,
+}
+
+#[derive(Clone)]
+pub struct DaemonBeaconConfig {
+    pub endpoint: String,
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Clone)]
+pub struct ServiceConfig {
+    pub name: String,
+    pub restart: RestartConfig,
+    pub kind: ServiceKind,
+}
+
+#[derive(Clone)]
+pub enum ServiceKind {
+    /// Merkle root relayers from a `gear-eth-core` config file. They keep their own
+    /// connections, only the Prometheus endpoint is shared.
+    GearEthCore {
+        relayers: Vec<EffectiveRelayerConfig>,
+    },
+    EthGearCore {
+        checkpoint_light_client: H256,
+        size_batch_multiplier: u64,
+    },
+    QueueCleaner {
+        delay: u64,
+    },
+    KillSwitch(KillSwitchService),
+    GearEthTokens(GearEthTokensService),
+    EthGearTokens(EthGearTokensService),
+}
+
+impl ServiceKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::GearEthCore { .. } => "gear-eth-core",
+            Self::EthGearCore { .. } => "eth-gear-core",
+            Self::QueueCleaner { .. } => "queue-cleaner",
+            Self::KillSwitch(_) => "kill-switch",
+            Self::GearEthTokens(_) => "gear-eth-tokens",
+            Self::EthGearTokens(_) => "eth-gear-tokens",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct KillSwitchService {
+    pub from_eth_block: Option<u64>,
+    pub observer_pk_path: PathBuf,
+    pub admin_pk_path: Option<PathBuf>,
+    pub relayer_http_url: String,
+    pub relayer_http_token: String,
+    pub relayer_http_timeout: Duration,
+    /// Additional Gear endpoints merkle roots are cross-checked against.
+    pub verification_endpoints: Vec<String>,
+    pub verification_quorum: Option<usize>,
+    pub alert_webhook_url: Option<String>,
+    pub alert_file: Option<PathBuf>,
+}
+
+#[derive(Clone)]
+pub struct GearEthTokensService {
+    pub transfers: GearEthTransfers,
+    pub storage_path: String,
+    pub governance_admin: String,
+    pub governance_pauser: String,
+    pub confirmations_merkle_root: u64,
+    pub confirmations_status: u64,
+    /// Accounts which don't pay fees. bridgeAdmin and bridgePauser if not set.
+    pub no_fee: Option<FeePayers>,
+    /// Messages of the same merkle root delivered in one transaction.
+    pub max_batch_size: usize,
+    /// Multicall3 deployment delivering batches. The canonical one if not set.
+    pub multicall_address: Option<String>,
+}
+
+#[derive(Clone)]
+pub enum GearEthTransfers {
+    All,
+    Paid {
+        bridging_payment_address: H256,
+        http: EffectiveHttpConfig,
+        /// Underpaid messages are held back when set.
+        fee_market: Option<FeeMarketConfig>,
+    },
+}
+
+#[derive(Clone)]
+pub struct EthGearTokensService {
+    pub transfers: EthGearTransfers,
+    pub vft_manager_address: H256,
+    pub storage_path: String,
+    /// Embedded database transactions and blocks are kept in instead of `storage_path`.
+    pub storage_database: Option<PathBuf>,
+    pub ethereum_blocks: Option<String>,
+}
+
+#[derive(Clone)]
+pub enum EthGearTransfers {
+    All {
+        erc20_manager_address: H160,
+    },
+    Paid {
+        bridging_payment_address: H160,
+        http: EffectiveHttpConfig,
+    },
+}
+
+impl DaemonConfig {
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config {}", path.display()))?;
+        Self::from_toml_str(&contents)
+    }
+
+    pub fn from_toml_str(contents: &str) -> anyhow::Result<Self> {
+        let raw: RawDaemonConfig = toml::from_str(contents)?;
+        raw.into_effective()
+    }
+
+    /// Replaces key references in the config with the keys, see [`cli_utils::signer`].
+    pub fn resolve_keys(&mut self, keys: &KeyResolver) -> anyhow::Result<()> {
+        if let Some(suri) = &mut self.gear.suri {
+            keys.resolve_in_place(KeyKind::Gear, suri)
+                .context("gear.suri")?;
+        }
+        if let Some(fee_payer) = self
+            .ethereum
+            .as_mut()
+            .and_then(|ethereum| ethereum.fee_payer.as_mut())
+        {
+            keys.resolve_in_place(KeyKind::Ethereum, fee_payer)
+                .context("ethereum.fee_payer")?;
+        }
+        for service in &mut self.services {
+            if let ServiceKind::GearEthCore { relayers } = &mut service.kind {
+                for relayer in relayers {
+                    relayer
+                        .resolve_keys(keys)
+                        .with_context(|| format!("service {}", service.name))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct RawDaemonConfig {
+    #[serde(default)]
+    prometheus: RawPrometheusConfig,
+    gear: RawGearConfig,
+    #[serde(default)]
+    ethereum: Option<RawEthereumConfig>,
+    #[serde(default)]
+    beacon: Option<RawBeaconConfig>,
+    #[serde(default)]
+    restart: RawRestartConfig,
+    services: BTreeMap<String, RawService>,
+}
+
+#[derive(Default, Deserialize)]
+struct RawPrometheusConfig {
+    endpoint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawGearConfig {
+    endpoint: String,
+    #[serde(default = "default_max_reconnect_attempts")]
+    max_reconnect_attempts: u8,
+    suri: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawEthereumConfig {
+    endpoint: String,
+    #[serde(default)]
+    fallback_endpoints: Vec<String>,
+    read_quorum: Option<usize>,
+    message_queue_address: Option<String>,
+    fee_payer: Option<String>,
+    max_retries: Option<u32>,
+    retry_interval_ms: Option<u64>,
+    max_fee_per_gas: Option<u128>,
+    max_priority_fee_per_gas: Option<u128>,
+    nonce_file: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+struct RawBeaconConfig {
+    endpoint: String,
+    timeout: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct RawRestartConfig {
+    policy: Option<RestartPolicy>,
+    initial_backoff: Option<String>,
+    max_backoff: Option<String>,
+    max_restarts: Option<u32>,
+    reset_after: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawService {
+    #[serde(default)]
+    restart: RawRestartConfig,
+    #[serde(flatten)]
+    kind: RawServiceKind,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum RawServiceKind {
+    GearEthCore {
+        config: PathBuf,
+    },
+    EthGearCore {
+        checkpoint_light_client: String,
+        size_batch_multiplier: Option<u64>,
+    },
+    QueueCleaner {
+        delay: Option<u64>,
+    },
+    KillSwitch {
+        from_eth_block: Option<u64>,
+        observer_pk_path: PathBuf,
+        admin_pk_path: Option<PathBuf>,
+        relayer_http_url: String,
+        relayer_http_token: String,
+        relayer_http_timeout: Option<String>,
+        #[serde(default)]
+        verification_endpoints: Vec<String>,
+        verification_quorum: Option<usize>,
+        alert_webhook_url: Option<String>,
+        alert_file: Option<PathBuf>,
+    },
+    GearEthTokens {
+        bridging_payment_address: Option<String>,
+        storage_path: String,
+        governance_admin: String,
+        governance_pauser: String,
+        confirmations_merkle_root: Option<u64>,
+        confirmations_status: Option<u64>,
+        /// Accounts which don't pay fees. An empty list makes everyone pay.
+        no_fee: Option<Vec<String>>,
+        max_batch_size: Option<usize>,
+        multicall_address: Option<String>,
+        http: Option<RawHttpConfig>,
+        fee_market: Option<RawFeeMarketConfig>,
+    },
+    EthGearTokens {
+        vft_manager_address: String,
+        erc20_manager_address: Option<String>,
+        bridging_payment_address: Option<String>,
+        storage_path: String,
+        storage_database: Option<PathBuf>,
+        ethereum_blocks: Option<String>,
+        http: Option<RawHttpConfig>,
+    },
+}
+
+#[derive(Deserialize)]
+struct RawFeeMarketConfig {
+    eth_price_in_vara: f64,
+    gas_per_message: Option<u64>,
+    margin_percent: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct RawHttpConfig {
+    address: String,
+    token: String,
+    #[serde(default)]
+    tokens_file: Option<PathBuf>,
+    #[serde(default)]
+    audit_log: Option<PathBuf>,
+}
+
+impl RawDaemonConfig {
+    fn into_effective(self) -> anyhow::Result<DaemonConfig> {
+        if self.services.is_empty() {
+            return Err(anyhow!("config must define at least one service"));
+        }
+
+        validate_url(&self.gear.endpoint, "gear.endpoint")?;
+        let gear = DaemonGearConfig {
+            endpoint: self.gear.endpoint,
+            max_reconnect_attempts: self.gear.max_reconnect_attempts,
+            suri: self.gear.suri.filter(|suri| !suri.trim().is_empty()),
+        };
+
+        let ethereum = self.ethereum.map(parse_ethereum).transpose()?;
+        let beacon = self
+            .beacon
+            .map(|beacon| {
+                validate_url(&beacon.endpoint, "beacon.endpoint")?;
+                let timeout = beacon
+                    .timeout
+                    .as_deref()
+                    .map(|timeout| parse_duration(timeout, "beacon.timeout"))
+                    .transpose()?;
+
+                anyhow::Ok(DaemonBeaconConfig {
+                    endpoint: beacon.endpoint,
+                    timeout,
+                })
+            })
+            .transpose()?;
+
+        let restart = self
+            .restart
+            .merge_into(RestartConfig::default(), "restart")?;
+
+        let mut services = Vec::with_capacity(self.services.len());
+        for (name, service) in self.services {
+            validate_service_name(&name)?;
+            let restart = service
+                .restart
+                .merge_into(restart, &format!("service {name}: restart"))?;
+            let kind = service.kind.into_effective(&name)?;
+
+            let requires = |missing: bool, field: &str| {
+                if missing {
+                    Err(anyhow!(
+                        "service {name}: {field} is required by {} services",
+                        kind.name()
+                    ))
+                } else {
+                    Ok(())
+                }
+            };
+            let ethereum_lacks =
+                |missing: fn(&DaemonEthereumConfig) -> bool| ethereum.as_ref().is_some_and(missing);
+            match &kind {
+                ServiceKind::GearEthCore { .. } => {}
+                ServiceKind::EthGearCore { .. } => {
+                    requires(gear.suri.is_none(), "gear.suri")?;
+                    requires(beacon.is_none(), "beacon")?;
+                }
+                ServiceKind::QueueCleaner { .. } => {
+                    requires(gear.suri.is_none(), "gear.suri")?;
+                }
+                ServiceKind::KillSwitch(_) => {
+                    requires(ethereum.is_none(), "ethereum")?;
+                    requires(
+                        ethereum_lacks(|ethereum| ethereum.message_queue_address.is_none()),
+                        "ethereum.message_queue_address",
+                    )?;
+                }
+                ServiceKind::GearEthTokens(_) => {
+                    requires(ethereum.is_none(), "ethereum")?;
+                    requires(
+                        ethereum_lacks(|ethereum| ethereum.message_queue_address.is_none()),
+                        "ethereum.message_queue_address",
+                    )?;
+                    requires(
+                        ethereum_lacks(|ethereum| ethereum.fee_payer.is_none()),
+                        "ethereum.fee_payer",
+                    )?;
+                }
+                ServiceKind::EthGearTokens(_) => {
+                    requires(gear.suri.is_none(), "gear.suri")?;
+                    requires(ethereum.is_none(), "ethereum")?;
+                    requires(beacon.is_none(), "beacon")?;
+                }
+            }
+
+            services.push(ServiceConfig {
+                name,
+                restart,
+                kind,
+            });
+        }
+
+        let config = DaemonConfig {
+            prometheus_endpoint: self
+                .prometheus
+                .endpoint
+                .unwrap_or_else(|| DEFAULT_PROMETHEUS_ENDPOINT.to_string()),
+            gear,
+            ethereum,
+            beacon,
+            services,
+        };
+        validate_listeners(&config)?;
+
+        Ok(config)
+    }
+}
+
+impl RawRestartConfig {
+    fn merge_into(self, base: RestartConfig, section: &str) -> anyhow::Result<RestartConfig> {
+        let duration = |value: Option<String>, default: Duration, field: &str| match value {
+            Some(value) => parse_duration(&value, &format!("{section}.{field}")),
+            None => Ok(default),
+        };
+
+        let config = RestartConfig {
+            policy: self.policy.unwrap_or(base.policy),
+            initial_backoff: duration(
+                self.initial_backoff,
+                base.initial_backoff,
+                "initial_backoff",
+            )?,
+            max_backoff: duration(self.max_backoff, base.max_backoff, "max_backoff")?,
+            max_restarts: self.max_restarts.or(base.max_restarts),
+            reset_after: duration(self.reset_after, base.reset_after, "reset_after")?,
+        };
+        if config.initial_backoff > config.max_backoff {
+            return Err(anyhow!(
+                "{section}.initial_backoff must not be greater than {section}.max_backoff"
+            ));
+        }
+
+        Ok(config)
+    }
+}
+
+impl RawServiceKind {
+    fn into_effective(self, name: &str) -> anyhow::Result<ServiceKind> {
+        Ok(match self {
+            Self::GearEthCore { config } => {
+                let EffectiveConfig { relayers, .. } = EffectiveConfig::from_path(&config)
+                    .with_context(|| {
+                        format!("service {name}: invalid config {}", config.display())
+                    })?;
+                ServiceKind::GearEthCore { relayers }
+            }
+
+            Self::EthGearCore {
+                checkpoint_light_client,
+                size_batch_multiplier,
+            } => ServiceKind::EthGearCore {
+                checkpoint_light_client: H256(decode_fixed_hex(
+                    &checkpoint_light_client,
+                    name,
+                    "checkpoint_light_client",
+                )?),
+                size_batch_multiplier: size_batch_multiplier
+                    .unwrap_or(DEFAULT_SIZE_BATCH_MULTIPLIER),
+            },
+
+            Self::QueueCleaner { delay } => ServiceKind::QueueCleaner {
+                delay: delay.unwrap_or(DEFAULT_QUEUE_CLEANER_DELAY),
+            },
+
+            Self::KillSwitch {
+                from_eth_block,
+                observer_pk_path,
+                admin_pk_path,
+                relayer_http_url,
+                relayer_http_token,
+                relayer_http_timeout,
+                verification_endpoints,
+                verification_quorum,
+                alert_webhook_url,
+                alert_file,
+            } => {
+                validate_non_empty_path(&observer_pk_path, name, "observer_pk_path")?;
+                validate_url(
+                    &relayer_http_url,
+                    &format!("service {name}: relayer_http_url"),
+                )?;
+                validate_non_empty(&relayer_http_token, name, "relayer_http_token")?;
+                for endpoint in &verification_endpoints {
+                    validate_url(endpoint, &format!("service {name}: verification_endpoints"))?;
+                }
+                if verification_quorum
+                    .is_some_and(|quorum| quorum == 0 || quorum > 1 + verification_endpoints.len())
+                {
+                    return Err(anyhow!(
+                        "service {name}: verification_quorum must be between 1 and the number of Gear endpoints ({})",
+                        1 + verification_endpoints.len()
+                    ));
+                }
+                if let Some(url) = &alert_webhook_url {
+                    validate_url(url, &format!("service {name}: alert_webhook_url"))?;
+                }
+
+                ServiceKind::KillSwitch(KillSwitchService {
+                    from_eth_block,
+                    observer_pk_path,
+                    admin_pk_path,
+                    relayer_http_url,
+                    relayer_http_token,
+                    relayer_http_timeout: parse_duration(
+                        relayer_http_timeout
+                            .as_deref()
+                            .unwrap_or(DEFAULT_RELAYER_HTTP_TIMEOUT),
+                        &format!("service {name}: relayer_http_timeout"),
+                    )?,
+                    verification_endpoints,
+                    verification_quorum,
+                    alert_webhook_url,
+                    alert_file,
+                })
+            }
+
+            Self::GearEthTokens {
+                bridging_payment_address,
+                storage_path,
+                governance_admin,
+                governance_pauser,
+                confirmations_merkle_root,
+                confirmations_status,
+                no_fee,
+                max_batch_size,
+                multicall_address,
+                http,
+                fee_market,
+            } => {
+                validate_non_empty(&storage_path, name, "storage_path")?;
+                validate_non_empty(&governance_admin, name, "governance_admin")?;
+                validate_non_empty(&governance_pauser, name, "governance_pauser")?;
+                if max_batch_size == Some(0) {
+                    return Err(anyhow!("service {name}: max_batch_size must be positive"));
+                }
+                if let Some(address) = &multicall_address {
+                    decode_fixed_hex::<20>(address, name, "multicall_address")?;
+                }
+
+                if fee_market.is_some() && bridging_payment_address.is_none() {
+                    return Err(anyhow!(
+                        "service {name}: fee_market is only used with bridging_payment_address"
+                    ));
+                }
+
+                let transfers = match (bridging_payment_address, http) {
+                    (None, None) => GearEthTransfers::All,
+                    (Some(address), Some(http)) => GearEthTransfers::Paid {
+                        bridging_payment_address: H256(decode_fixed_hex(
+                            &address,
+                            name,
+                            "bridging_payment_address",
+                        )?),
+                        http: parse_http(http, name)?,
+                        fee_market: fee_market
+                            .map(|fee_market| parse_fee_market(fee_market, name))
+                            .transpose()?,
+                    },
+                    (Some(_), None) => {
+                        return Err(anyhow!(
+                            "service {name}: http is required with bridging_payment_address"
+                        ))
+                    }
+                    (None, Some(_)) => {
+                        return Err(anyhow!(
+                            "service {name}: http is only used with bridging_payment_address"
+                        ))
+                    }
+                };
+
+                ServiceKind::GearEthTokens(GearEthTokensService {
+                    transfers,
+                    storage_path,
+                    governance_admin,
+                    governance_pauser,
+                    confirmations_merkle_root: confirmations_merkle_root
+                        .unwrap_or(DEFAULT_COUNT_CONFIRMATIONS),
+                    confirmations_status: confirmations_status
+                        .unwrap_or(DEFAULT_COUNT_CONFIRMATIONS),
+                    no_fee: no_fee.map(|ids| {
+                        if ids.is_empty() {
+                            FeePayers::All
+                        } else {
+                            FeePayers::ExcludedIds(ids)
+                        }
+                    }),
+                    max_batch_size: max_batch_size.unwrap_or(DEFAULT_MAX_BATCH_SIZE),
+                    multicall_address,
+                })
+            }
+
+            Self::EthGearTokens {
+                vft_manager_address,
+                erc20_manager_address,
+                bridging_payment_address,
+                storage_path,
+                storage_database,
+                ethereum_blocks,
+                http,
+            } => {
+                validate_non_empty(&storage_path, name, "storage_path")?;
+                if let Some(database) = &storage_database {
+                    validate_non_empty_path(database, name, "storage_database")?;
+                    if database == Path::new(&storage_path) {
+                        return Err(anyhow!(
+                            "service {name}: storage_database must differ from storage_path"
+                        ));
+                    }
+                }
+
+                let transfers = match (erc20_manager_address, bridging_payment_address, http) {
+                    (Some(address), None, None) => EthGearTransfers::All {
+                        erc20_manager_address: H160(decode_fixed_hex(
+                            &address,
+                            name,
+                            "erc20_manager_address",
+                        )?),
+                    },
+                    (None, Some(address), Some(http)) => EthGearTransfers::Paid {
+                        bridging_payment_address: H160(decode_fixed_hex(
+                            &address,
+                            name,
+                            "bridging_payment_address",
+                        )?),
+                        http: parse_http(http, name)?,
+                    },
+                    (None, Some(_), None) => {
+                        return Err(anyhow!(
+                            "service {name}: http is required with bridging_payment_address"
+                        ))
+                    }
+                    (Some(_), None, Some(_)) => {
+                        return Err(anyhow!(
+                            "service {name}: http is only used with bridging_payment_address"
+                        ))
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "service {name}: exactly one of erc20_manager_address and bridging_payment_address must be set"
+                        ))
+                    }
+                };
+
+                ServiceKind::EthGearTokens(EthGearTokensService {
+                    transfers,
+                    vft_manager_address: H256(decode_fixed_hex(
+                        &vft_manager_address,
+                        name,
+                        "vft_manager_address",
+                    )?),
+                    storage_path,
+                    storage_database,
+                    ethereum_blocks,
+                })
+            }
+        })
+    }
+}
+
+fn parse_ethereum(raw: RawEthereumConfig) -> anyhow::Result<DaemonEthereumConfig> {
+    validate_url(&raw.endpoint, "ethereum.endpoint")?;
+    for endpoint in &raw.fallback_endpoints {
+        validate_url(endpoint, "ethereum.fallback_endpoints")?;
+    }
+    let endpoints = 1 + raw.fallback_endpoints.len();
+    if raw
+        .read_quorum
+        .is_some_and(|quorum| quorum * 2 <= endpoints || quorum > endpoints)
+    {
+        return Err(anyhow!(
+            "ethereum.read_quorum must be a majority of the {endpoints} endpoint(s)"
+        ));
+    }
+    if let Some(address) = &raw.message_queue_address {
+        let _ = decode_hex::<20>(address, "ethereum.message_queue_address")?;
+    }
+    if let Some(fee_payer) = raw
+        .fee_payer
+        .as_ref()
+        .filter(|fee_payer| !KeySource::is_reference(fee_payer) && !Signer::is_remote(fee_payer))
+    {
+        let _ = decode_hex::<32>(fee_payer, "ethereum.fee_payer")?;
+    }
+
+    Ok(DaemonEthereumConfig {
+        endpoint: raw.endpoint,
+        fallback_endpoints: raw.fallback_endpoints,
+        read_quorum: raw.read_quorum,
+        message_queue_address: raw.message_queue_address,
+        fee_payer: raw.fee_payer,
+        max_retries: raw.max_retries,
+        retry_interval_ms: raw.retry_interval_ms,
+        max_fee_per_gas: raw.max_fee_per_gas,
+        max_priority_fee_per_gas: raw.max_priority_fee_per_gas,
+        nonce_file: raw.nonce_file,
+    })
+}
+
+fn parse_fee_market(raw: RawFeeMarketConfig, name: &str) -> anyhow::Result<FeeMarketConfig> {
+    if !raw.eth_price_in_vara.is_finite() || raw.eth_price_in_vara <= 0.0 {
+        return Err(anyhow!(
+            "service {name}: fee_market.eth_price_in_vara must be positive"
+        ));
+    }
+
+    Ok(FeeMarketConfig {
+        eth_price_in_vara: raw.eth_price_in_vara,
+        gas_per_message: raw.gas_per_message.unwrap_or(DEFAULT_GAS_PER_MESSAGE),
+        margin_percent: raw.margin_percent.unwrap_or(DEFAULT_MARGIN_PERCENT),
+    })
+}
+
+fn parse_http(raw: RawHttpConfig, name: &str) -> anyhow::Result<EffectiveHttpConfig> {
+    raw.address
+        .parse::<SocketAddr>()
+        .with_context(|| format!("service {name}: http.address is invalid"))?;
+    validate_non_empty(&raw.token, name, "http.token")?;
+    if let Some(tokens_file) = &raw.tokens_file {
+        validate_non_empty_path(tokens_file, name, "http.tokens_file")?;
+    }
+    if let Some(audit_log) = &raw.audit_log {
+        validate_non_empty_path(audit_log, name, "http.audit_log")?;
+    }
+
+    Ok(EffectiveHttpConfig {
+        address: raw.address,
+        token: raw.token,
+        tokens_file: raw.tokens_file,
+        audit_log: raw.audit_log,
+    })
+}
+
+/// Every HTTP server of the daemon, including the ones of merkle root relayers, needs an
+/// address of its own.
+fn validate_listeners(config: &DaemonConfig) -> anyhow::Result<()> {
+    let mut addresses = HashSet::from([config.prometheus_endpoint.clone()]);
+
+    for service in &config.services {
+        let name = &service.name;
+        let listeners = match &service.kind {
+            ServiceKind::GearEthCore { relayers } => relayers
+                .iter()
+                .flat_map(|relayer| {
+                    let pool = match &relayer.prover {
+                        EffectiveProverConfig::Pool { address, .. } => Some(address),
+                        EffectiveProverConfig::Local | EffectiveProverConfig::Mock { .. } => None,
+                    };
+                    std::iter::once(&relayer.http.address).chain(pool)
+                })
+                .collect(),
+            ServiceKind::GearEthTokens(GearEthTokensService {
+                transfers: GearEthTransfers::Paid { http, .. },
+                ..
+            })
+            | ServiceKind::EthGearTokens(EthGearTokensService {
+                transfers: EthGearTransfers::Paid { http, .. },
+                ..
+            }) => vec![&http.address],
+            _ => vec![],
+        };
+
+        for address in listeners {
+            if !addresses.insert(address.clone()) {
+                return Err(anyhow!(
+                    "service {name}: address {address} is already used by another listener"
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_service_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty()
+        || !name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+    {
+        return Err(anyhow!(
+            "invalid service name {name:?}; expected [A-Za-z0-9_-]+"
+        ));
+    }
+    Ok(())
+}
+
+fn decode_fixed_hex<const LEN: usize>(
+    value: &str,
+    service: &str,
+    field: &str,
+) -> anyhow::Result<[u8; LEN]> {
+    decode_hex(value, &format!("service {service}: {field}"))
+}
+
+fn decode_hex<const LEN: usize>(value: &str, field: &str) -> anyhow::Result<[u8; LEN]> {
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    let bytes = hex::decode(value).with_context(|| format!("{field} is invalid hex"))?;
+    bytes.try_into().map_err(|got: Vec<u8>| {
+        anyhow!(
+            "{field} has wrong length. Expected {}, got {}",
+            LEN,
+            got.len()
+        )
+    })
+}
+
+fn validate_url(value: &str, field: &str) -> anyhow::Result<()> {
+    Url::parse(value).with_context(|| format!("{field} must be a valid URL"))?;
+    Ok(())
+}
+
+fn validate_non_empty(value: &str, service: &str, field: &str) -> anyhow::Result<()> {
+    if value.trim().is_empty() {
+        return Err(anyhow!("service {service}: {field} must not be empty"));
+    }
+    Ok(())
+}
+
+fn validate_non_empty_path(path: &Path, service: &str, field: &str) -> anyhow::Result<()> {
+    if path.as_os_str().is_empty() {
+        return Err(anyhow!("service {service}: {field} must not be empty"));
+    }
+    Ok(())
+}
+
+fn parse_duration(value: &str, field: &str) -> anyhow::Result<Duration> {
+    let duration =
+        humantime::parse_duration(value).with_context(|| format!("{field} is invalid"))?;
+    if duration.is_zero() {
+        return Err(anyhow!("{field} must be positive"));
+    }
+    Ok(duration)
+}
+
+fn default_max_reconnect_attempts() -> u8 {
+    3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_config() -> String {
+        format!(
+            r#"
+[prometheus]
+endpoint = "127.0.0.1:9900"
+
+[gear]
+endpoint = "wss://gear.example"
+suri = "//Alice"
+
+[ethereum]
+endpoint = "https://eth.example"
+fallback_endpoints = ["https://eth-fallback.example"]
+read_quorum = 2
+message_queue_address = "0x1111111111111111111111111111111111111111"
+fee_payer = "0x{}"
+
+[beacon]
+endpoint = "https://beacon.example"
+timeout = "30s"
+
+[restart]
+initial_backoff = "1s"
+max_backoff = "1m"
+
+[services.checkpoints]
+kind = "eth-gear-core"
+checkpoint_light_client = "0x{}"
+
+[services.cleaner]
+kind = "queue-cleaner"
+delay = 60
+
+[services.cleaner.restart]
+policy = "always"
+max_restarts = 5
+
+[services.gear-eth]
+kind = "gear-eth-tokens"
+bridging_payment_address = "0x{}"
+storage_path = "/tmp/gear-eth"
+governance_admin = "kGkLEU3e3XXkJp2WK4eNpVmSab5xUNL9QtmLPh8QfCL2EgotW"
+governance_pauser = "kGkLEU3e3XXkJp2WK4eNpVmSab5xUNL9QtmLPh8QfCL2EgotW"
+no_fee = []
+max_batch_size = 16
+
+[services.gear-eth.http]
+address = "127.0.0.1:8443"
+token = "secret"
+
+[services.gear-eth.fee_market]
+eth_price_in_vara = 250000.0
+
+[services.eth-gear]
+kind = "eth-gear-tokens"
+vft_manager_address = "0x{}"
+erc20_manager_address = "0x2222222222222222222222222222222222222222"
+storage_path = "/tmp/eth-gear"
+"#,
+            "22".repeat(32),
+            "33".repeat(32),
+            "44".repeat(32),
+            "55".repeat(32),
+        )
+    }
+
+    fn config_error(contents: &str) -> String {
+        match DaemonConfig::from_toml_str(contents) {
+            Ok(_) => panic!("config unexpectedly parsed successfully"),
+            Err(err) => format!("{err:#}"),
+        }
+    }
+
+    fn service<'a>(config: &'a DaemonConfig, name: &str) -> &'a ServiceConfig {
+        config
+            .services
+            .iter()
+            .find(|service| service.name == name)
+            .expect("service must be present")
+    }
+
+    #[test]
+    fn parses_valid_config() {
+        let config = DaemonConfig::from_toml_str(&valid_config()).unwrap();
+
+        assert_eq!(config.prometheus_endpoint, "127.0.0.1:9900");
+        assert_eq!(config.gear.suri.as_deref(), Some("//Alice"));
+        assert_eq!(config.ethereum.as_ref().unwrap().read_quorum, Some(2));
+        assert_eq!(
+            config.beacon.as_ref().unwrap().timeout,
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(config.services.len(), 4);
+
+        let checkpoints = service(&config, "checkpoints");
+        assert_eq!(checkpoints.restart.policy, RestartPolicy::OnFailure);
+        assert_eq!(checkpoints.restart.initial_backoff, Duration::from_secs(1));
+        assert!(matches!(
+            checkpoints.kind,
+            ServiceKind::EthGearCore {
+                size_batch_multiplier: DEFAULT_SIZE_BATCH_MULTIPLIER,
+                ..
+            }
+        ));
+
+        let cleaner = service(&config, "cleaner");
+        assert_eq!(cleaner.restart.policy, RestartPolicy::Always);
+        assert_eq!(cleaner.restart.max_restarts, Some(5));
+        assert_eq!(cleaner.restart.max_backoff, Duration::from_secs(60));
+        assert!(matches!(
+            cleaner.kind,
+            ServiceKind::QueueCleaner { delay: 60 }
+        ));
+
+        let ServiceKind::GearEthTokens(gear_eth) = &service(&config, "gear-eth").kind else {
+            panic!("expected gear-eth-tokens service");
+        };
+        assert!(matches!(gear_eth.no_fee, Some(FeePayers::All)));
+        assert_eq!(gear_eth.max_batch_size, 16);
+        assert_eq!(gear_eth.multicall_address, None);
+        assert!(matches!(
+            &gear_eth.transfers,
+            GearEthTransfers::Paid { http, .. } if http.address == "127.0.0.1:8443"
+        ));
+        let GearEthTransfers::Paid { fee_market, .. } = &gear_eth.transfers else {
+            unreachable!();
+        };
+        assert_eq!(
+            fee_market.as_ref(),
+            Some(&FeeMarketConfig {
+                eth_price_in_vara: 250_000.0,
+                gas_per_message: DEFAULT_GAS_PER_MESSAGE,
+                margin_percent: DEFAULT_MARGIN_PERCENT,
+            })
+        );
+
+        let ServiceKind::EthGearTokens(eth_gear) = &service(&config, "eth-gear").kind else {
+            panic!("expected eth-gear-tokens service");
+        };
+        assert!(matches!(
+            eth_gear.transfers,
+            EthGearTransfers::All { erc20_manager_address } if erc20_manager_address == H160::repeat_byte(0x22)
+        ));
+    }
+
+    #[test]
+    fn example_config_parses() {
+        let config = DaemonConfig::from_toml_str(include_str!("../../daemon.toml.example"))
+            .expect("example config must stay in sync with the TOML schema");
+        assert_eq!(config.services.len(), 5);
+        assert_eq!(
+            service(&config, "kill-switch").restart.policy,
+            RestartPolicy::Always
+        );
+    }
+
+    #[test]
+    fn rejects_missing_shared_connections() {
+        let config = valid_config().replace("suri = \"//Alice\"\n", "");
+        assert!(config_error(&config).contains("gear.suri is required by eth-gear-core"));
+
+        let config =
+            valid_config().replace(&format!("fee_payer = \"0x{}\"\n", "22".repeat(32)), "");
+        assert!(config_error(&config).contains("ethereum.fee_payer is required by gear-eth-tokens"));
+    }
+
+    #[test]
+    fn rejects_shared_listener_address() {
+        let config = valid_config().replace("127.0.0.1:8443", "127.0.0.1:9900");
+        assert!(config_error(&config).contains("address 127.0.0.1:9900 is already used"));
+    }
+
+    #[test]
+    fn rejects_paid_transfers_without_http() {
+        let config = valid_config().replace(
+            "[services.gear-eth.http]\naddress = \"127.0.0.1:8443\"\ntoken = \"secret\"\n",
+            "",
+        );
+        assert!(config_error(&config).contains("http is required with bridging_payment_address"));
+    }
+
+    #[test]
+    fn parses_eth_gear_storage_database() {
+        let config = valid_config().replace(
+            "storage_path = \"/tmp/eth-gear\"",
+            "storage_path = \"/tmp/eth-gear\"\nstorage_database = \"/tmp/eth-gear-db\"",
+        );
+        let config = DaemonConfig::from_toml_str(&config).unwrap();
+        let ServiceKind::EthGearTokens(eth_gear) = &service(&config, "eth-gear").kind else {
+            panic!("expected eth-gear-tokens service");
+        };
+        assert_eq!(
+            eth_gear.storage_database.as_deref(),
+            Some(Path::new("/tmp/eth-gear-db"))
+        );
+
+        let config = valid_config().replace(
+            "storage_path = \"/tmp/eth-gear\"",
+            "storage_path = \"/tmp/eth-gear\"\nstorage_database = \"/tmp/eth-gear\"",
+        );
+        assert!(config_error(&config).contains("storage_database must differ from storage_path"));
+    }
+
+    #[test]
+    fn rejects_invalid_restart_config() {
+        let config = valid_config().replace("max_backoff = \"1m\"", "max_backoff = \"100ms\"");
+        assert!(config_error(&config)
+            .contains("restart.initial_backoff must not be greater than restart.max_backoff"));
+
+        let config = valid_config().replace("policy = \"always\"", "policy = \"sometimes\"");
+        assert!(config_error(&config).contains("unknown variant"));
+    }
+
+    #[test]
+    fn rejects_unknown_service_kind() {
+        let config = valid_config().replace("kind = \"queue-cleaner\"", "kind = \"cleaner\"");
+        assert!(config_error(&config).contains("unknown variant"));
+    }
+}
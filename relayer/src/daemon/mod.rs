@@ -0,0 +1,6 @@
+//! `run` command: services declared in one config file run in a single process. They share
+//! the Gear connection, Ethereum and beacon clients and the Prometheus endpoint, and are
+//! restarted according to their own restart policies.
+
+pub mod config;
+pub mod supervisor;
@@ -0,0 +1,321 @@
+use anyhow::{anyhow, Context};
+use futures::FutureExt;
+use prometheus::{IntCounterVec, IntGaugeVec, Opts};
+use serde::Deserialize;
+use std::{future::Future, panic::AssertUnwindSafe, time::Duration};
+use tokio::time::{self, Instant};
+use utils_prometheus::{impl_metered_service, MeteredService};
+
+/// When a service is started again after it stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Restart after errors and after the service exits on its own.
+    Always,
+    /// Restart after errors only.
+    OnFailure,
+    /// Never restart. An error stops the whole daemon.
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartConfig {
+    pub policy: RestartPolicy,
+    /// Delay before the first restart. Doubled on every next one up to `max_backoff`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Restarts allowed before the service is given up on. Unlimited if not set.
+    pub max_restarts: Option<u32>,
+    /// A service running for this long is considered healthy again: backoff and restart
+    /// count start from scratch.
+    pub reset_after: Duration,
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        Self {
+            policy: RestartPolicy::OnFailure,
+            initial_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(5 * 60),
+            max_restarts: None,
+            reset_after: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+/// Exponential backoff between restarts of a service.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    config: RestartConfig,
+    delay: Duration,
+    restarts: u32,
+}
+
+impl Backoff {
+    pub fn new(config: RestartConfig) -> Self {
+        Self {
+            config,
+            delay: config.initial_backoff,
+            restarts: 0,
+        }
+    }
+
+    /// Delay before the next start of a service which ran for `uptime`. `None` when the
+    /// restart limit is reached.
+    pub fn next_delay(&mut self, uptime: Duration) -> Option<Duration> {
+        if uptime >= self.config.reset_after {
+            self.delay = self.config.initial_backoff;
+            self.restarts = 0;
+        }
+
+        if self
+            .config
+            .max_restarts
+            .is_some_and(|max_restarts| self.restarts >= max_restarts)
+        {
+            return None;
+        }
+
+        self.restarts += 1;
+        let delay = self.delay;
+        self.delay = self
+            .delay
+            .saturating_mul(2)
+            .min(self.config.max_backoff)
+            .max(self.config.initial_backoff);
+
+        Some(delay)
+    }
+
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+}
+
+impl_metered_service!(
+    struct Metrics {
+        restarts: IntCounterVec = IntCounterVec::new(
+            Opts::new(
+                "daemon_service_restarts",
+                "Number of times a service was restarted by the daemon"
+            ),
+            &["service"],
+        ),
+        running: IntGaugeVec = IntGaugeVec::new(
+            Opts::new(
+                "daemon_service_running",
+                "Whether a service is running (1) or waiting to be restarted (0)"
+            ),
+            &["service"],
+        ),
+    }
+);
+
+/// Runs services and restarts them according to their [`RestartConfig`].
+#[derive(Clone)]
+pub struct Supervisor {
+    metrics: Metrics,
+}
+
+impl MeteredService for Supervisor {
+    fn get_sources(&self) -> impl IntoIterator<Item = Box<dyn prometheus::core::Collector>> {
+        self.metrics.get_sources()
+    }
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self {
+            metrics: Metrics::new(),
+        }
+    }
+
+    /// Runs the service returned by `start` until the restart policy tells to stop. A panic
+    /// counts as a failure instead of bringing down the other services.
+    ///
+    /// Returns `Ok` when the service exited and isn't restarted, `Err` when it failed and
+    /// isn't restarted.
+    pub async fn supervise<F, Fut>(
+        &self,
+        name: &str,
+        config: RestartConfig,
+        mut start: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<()>>,
+    {
+        let running = self.metrics.running.with_label_values(&[name]);
+        let restarts = self.metrics.restarts.with_label_values(&[name]);
+        let mut backoff = Backoff::new(config);
+
+        loop {
+            log::info!("Service {name}: starting");
+            let started = Instant::now();
+            running.set(1);
+            let result = match AssertUnwindSafe(start()).catch_unwind().await {
+                Ok(result) => result,
+                Err(panic) => Err(anyhow!("panicked: {}", panic_message(&*panic))),
+            };
+            running.set(0);
+
+            let result = match (result, config.policy) {
+                (Ok(()), RestartPolicy::Always) => {
+                    log::warn!("Service {name}: exited");
+                    Ok(())
+                }
+                (Ok(()), _) => {
+                    log::info!("Service {name}: exited, not restarting");
+                    return Ok(());
+                }
+                (Err(err), RestartPolicy::Never) => {
+                    return Err(err).with_context(|| format!("service {name} failed"));
+                }
+                (Err(err), _) => {
+                    log::error!("Service {name}: failed: {err:?}");
+                    Err(err)
+                }
+            };
+
+            let Some(delay) = backoff.next_delay(started.elapsed()) else {
+                let restarts = backoff.restarts();
+                return match result {
+                    Ok(()) => Err(anyhow!(
+                        "service {name} exited after {restarts} restart(s), giving up"
+                    )),
+                    Err(err) => Err(err).with_context(|| {
+                        format!("service {name} failed after {restarts} restart(s), giving up")
+                    }),
+                };
+            };
+
+            log::info!("Service {name}: restarting in {delay:?}");
+            time::sleep(delay).await;
+            restarts.inc();
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown reason")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    fn config(policy: RestartPolicy, max_restarts: Option<u32>) -> RestartConfig {
+        RestartConfig {
+            policy,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            max_restarts,
+            reset_after: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max_and_resets() {
+        let mut backoff = Backoff::new(RestartConfig {
+            max_restarts: Some(4),
+            ..config(RestartPolicy::OnFailure, None)
+        });
+        let short = Duration::from_millis(1);
+
+        let delays = (0..5)
+            .map(|_| backoff.next_delay(short))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            vec![
+                Some(Duration::from_millis(1)),
+                Some(Duration::from_millis(2)),
+                Some(Duration::from_millis(4)),
+                Some(Duration::from_millis(4)),
+                None,
+            ]
+        );
+
+        assert_eq!(
+            backoff.next_delay(Duration::from_secs(60)),
+            Some(Duration::from_millis(1))
+        );
+        assert_eq!(backoff.restarts(), 1);
+    }
+
+    #[tokio::test]
+    async fn restarts_failed_service_until_limit() {
+        let starts = Arc::new(AtomicU32::new(0));
+        let supervisor = Supervisor::new();
+
+        let result = supervisor
+            .supervise("test", config(RestartPolicy::OnFailure, Some(2)), || {
+                let starts = starts.clone();
+                async move {
+                    starts.fetch_add(1, Ordering::SeqCst);
+                    Err(anyhow!("boom"))
+                }
+            })
+            .await;
+
+        let err = result.expect_err("service must be given up on");
+        assert!(format!("{err:#}").contains("failed after 2 restart(s)"));
+        assert!(format!("{err:#}").contains("boom"));
+        assert_eq!(starts.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            supervisor
+                .metrics
+                .restarts
+                .with_label_values(&["test"])
+                .get(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_restart_policy_to_exits_and_panics() {
+        let supervisor = Supervisor::new();
+
+        supervisor
+            .supervise("exits", config(RestartPolicy::OnFailure, None), || async {
+                Ok(())
+            })
+            .await
+            .expect("exit isn't restarted on failure policy");
+
+        let starts = Arc::new(AtomicU32::new(0));
+        supervisor
+            .supervise("always", config(RestartPolicy::Always, Some(1)), || {
+                let starts = starts.clone();
+                async move {
+                    starts.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            })
+            .await
+            .expect_err("exits are restarted until the limit");
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+
+        let err = supervisor
+            .supervise("panics", config(RestartPolicy::Never, None), || async {
+                panic!("service panicked")
+            })
+            .await
+            .expect_err("panic is a failure");
+        assert!(format!("{err:#}").contains("panicked: service panicked"));
+    }
+}
@@ -17,6 +17,20 @@ pub fn decode_byte_array<const LEN: usize>(hex: &str) -> anyhow::Result<[u8; LEN
 }
 
 pub fn decode_byte_vec(hex: &str) -> anyhow::Result<Vec<u8>> {
-    let address = if &hex[..2] == "0x" { &hex[2..] } else { hex };
+    let address = hex.strip_prefix("0x").unwrap_or(hex);
     Ok(hex::decode(address)?)
 }
+
+/// Serializes byte vectors as hex strings, for use with `#[serde(with = "...")]`.
+pub mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        hex::decode(value).map_err(serde::de::Error::custom)
+    }
+}
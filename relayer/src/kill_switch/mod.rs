@@ -3,7 +3,8 @@ use std::{process, time::Duration};
 use reqwest::Client as HttpClient;
 use thiserror::Error;
 
-use ethereum_client::{EthApi, MerkleRootEntry, TxHash, TxStatus};
+use ethereum_client::{EthApi, TxHash, TxStatus};
+use primitive_types::H256;
 use prometheus::{Gauge, IntCounter, IntGauge};
 use utils_prometheus::{impl_metered_service, MeteredService};
 
@@ -15,7 +16,12 @@ use crate::{
     message_relayer::common::web_request::{MerkleRootBlocks, MerkleRootsResponse},
     prover_interface::FinalProof,
 };
-use gear_common::api_provider::ApiProviderConnection;
+
+pub mod notifier;
+pub mod verification;
+
+use notifier::{Alert, Notifiers};
+use verification::{RootVerifier, Verdict};
 
 const SCAN_EVENTS_PERIOD_SEC: Duration = Duration::from_secs(12);
 const ERROR_REPEAT_DELAY: Duration = Duration::from_secs(3);
@@ -38,6 +44,10 @@ impl_metered_service! {
             "kill_switch_merkle_root_mismatch_cnt",
             "Amount of merkle root mismatches found",
         ),
+        merkle_root_inconclusive_cnt: IntCounter = IntCounter::new(
+            "kill_switch_merkle_root_inconclusive_cnt",
+            "Amount of merkle root checks Gear endpoints didn't agree on",
+        ),
         challenge_sent_cnt: IntCounter = IntCounter::new(
             "kill_switch_challenge_sent_cnt",
             "Amount of challenge sends finalized",
@@ -53,8 +63,6 @@ impl_metered_service! {
 enum ScanForEventsError {
     #[error("Ethereum API error: {0}")]
     EthApi(#[from] ethereum_client::Error),
-    #[error("Gear API error: {0}")]
-    GearApi(#[from] anyhow::Error),
     #[error("Other error: {0}")]
     Other(anyhow::Error),
 }
@@ -99,7 +107,8 @@ enum State {
 }
 
 pub struct KillSwitchRelayer {
-    api_provider: ApiProviderConnection,
+    verifier: RootVerifier,
+    notifiers: Notifiers,
     eth_observer_api: EthApi,
     eth_admin_api: Option<EthApi>,
     http_client: HttpClient,
@@ -108,6 +117,8 @@ pub struct KillSwitchRelayer {
     start_from_eth_block: Option<u64>,
     state: State,
     challenged_block: Option<u64>,
+    /// Block of the last inconclusive check, so that it's reported only once.
+    inconclusive_block: Option<u64>,
 
     metrics: Metrics,
 }
@@ -120,7 +131,8 @@ impl MeteredService for KillSwitchRelayer {
 
 impl KillSwitchRelayer {
     pub async fn new(
-        api_provider: ApiProviderConnection,
+        verifier: RootVerifier,
+        notifiers: Notifiers,
         eth_observer_api: EthApi,
         eth_admin_api: Option<EthApi>,
         http_client: HttpClient,
@@ -128,7 +140,8 @@ impl KillSwitchRelayer {
         relayer_http_url: String,
     ) -> Self {
         Self {
-            api_provider,
+            verifier,
+            notifiers,
             eth_observer_api,
             eth_admin_api,
             relayer_http_url,
@@ -136,18 +149,21 @@ impl KillSwitchRelayer {
             start_from_eth_block: from_eth_block,
             state: State::ScanForEvents,
             challenged_block: None,
+            inconclusive_block: None,
             metrics: Metrics::new(),
         }
     }
 
     pub async fn run(&mut self) -> anyhow::Result<()> {
         log::info!(
-            "Starting kill switch relayer, with {}",
+            "Starting kill switch relayer, with {}, verifying merkle roots against {} of {} Gear endpoint(s)",
             if self.eth_admin_api.is_some() {
                 "observer + admin roles"
             } else {
                 "observer role"
-            }
+            },
+            self.verifier.quorum(),
+            self.verifier.source_count(),
         );
 
         loop {
@@ -206,13 +222,6 @@ impl KillSwitchRelayer {
                                 }
                             }
                         }
-                        Error::ScanForEvents(ScanForEventsError::GearApi(e)) => {
-                            // Reconnect on Gear API
-                            log::error!("Gear API error: {e}, reconnecting...");
-                            if let Err(e) = self.api_provider.reconnect().await {
-                                log::error!("Gear API reconnect failed: {e}");
-                            }
-                        }
                         e => {
                             log::error!("Error in kill switch relayer: {e}");
                         }
@@ -270,17 +279,57 @@ impl KillSwitchRelayer {
         }
 
         for (event, _block_number_eth) in events {
-            if !self.compare_merkle_roots(&event).await? {
-                // Okay, we have a mismatch,
-                // that means for some reason the proof with incorrect merkle root was submitted to relayer MQ contract.
-                // We need to challenge it by submitting the correct merkle root.
-                log::debug!("Got event with mismatched merkle root: {:?}", &event);
+            let (verdict, sources) = self.verifier.verify(&event).await;
+            match verdict {
+                Verdict::Confirmed => {}
+
+                Verdict::Mismatch => {
+                    // Okay, we have a mismatch,
+                    // that means for some reason the proof with incorrect merkle root was submitted to relayer MQ contract.
+                    // We need to challenge it by submitting the correct merkle root.
+                    log::info!(
+                        "Merkle root mismatch for block #{}, on-chain: {}, Gear endpoints: {sources:?}",
+                        event.block_number,
+                        event.merkle_root,
+                    );
+                    self.metrics.merkle_root_mismatch_cnt.inc();
+                    self.notifiers
+                        .notify(Alert::MerkleRootMismatch {
+                            block_number: event.block_number,
+                            onchain_merkle_root: event.merkle_root,
+                            sources,
+                        })
+                        .await;
+
+                    // Switch to challenge root state
+                    self.state = State::ChallengeRoot { tx_hash: None };
+                    self.challenged_block = Some(event.block_number);
+
+                    return Ok(());
+                }
 
-                // Switch to challenge root state
-                self.state = State::ChallengeRoot { tx_hash: None };
-                self.challenged_block = Some(event.block_number);
+                // Neither challenge nor skip the merkle root: the scan is repeated from
+                // the same block until Gear endpoints agree.
+                Verdict::Inconclusive => {
+                    log::warn!(
+                        "Gear endpoints don't agree on merkle root {} for block #{}: {sources:?}",
+                        event.merkle_root,
+                        event.block_number,
+                    );
+                    if self.inconclusive_block != Some(event.block_number) {
+                        self.inconclusive_block = Some(event.block_number);
+                        self.metrics.merkle_root_inconclusive_cnt.inc();
+                        self.notifiers
+                            .notify(Alert::VerificationInconclusive {
+                                block_number: event.block_number,
+                                onchain_merkle_root: event.merkle_root,
+                                sources,
+                            })
+                            .await;
+                    }
 
-                return Ok(());
+                    return Ok(());
+                }
             }
         }
 
@@ -301,6 +350,13 @@ impl KillSwitchRelayer {
 
             match tx_status {
                 TxStatus::Finalized => {
+                    self.notifiers
+                        .notify(Alert::ChallengeFinalized {
+                            block_number: self.challenged_block,
+                            tx_hash: H256::from(tx_hash.0),
+                        })
+                        .await;
+
                     // For submit merkle root we need admin role
                     if self.eth_admin_api.is_some() {
                         log::info!("Challenge root TX {tx_hash:#x} finalized, switching to submit merkle root state");
@@ -328,6 +384,12 @@ impl KillSwitchRelayer {
         self.state = State::ChallengeRoot {
             tx_hash: Some(tx_hash),
         };
+        self.notifiers
+            .notify(Alert::ChallengeSent {
+                block_number: self.challenged_block,
+                tx_hash: H256::from(tx_hash.0),
+            })
+            .await;
 
         Ok(())
     }
@@ -366,9 +428,7 @@ impl KillSwitchRelayer {
         let proof = self
             .fetch_merkle_root_proof_from_relayer(self.challenged_block.expect("bad state"))
             .await?;
-        let tx_hash = *submit_merkle_root_to_ethereum(eth_admin_api, proof)
-            .await?
-            .tx_hash();
+        let tx_hash = submit_merkle_root_to_ethereum(eth_admin_api, proof).await?;
         self.state = State::SubmitMerkleRoot {
             tx_hash: Some(tx_hash),
         };
@@ -376,52 +436,6 @@ impl KillSwitchRelayer {
         Ok(())
     }
 
-    async fn compare_merkle_roots(
-        &self,
-        event: &MerkleRootEntry,
-    ) -> Result<bool, ScanForEventsError> {
-        let gear_api = self.api_provider.client();
-        let res = gear_api
-            .block_number_to_hash(event.block_number as u32)
-            .await
-            .convert()?;
-
-        let Some(block_hash) = res else {
-            log::info!(
-                "Block #{} is not present on Gear RPC node, cannot compare merkle roots",
-                event.block_number,
-            );
-            return Ok(false);
-        };
-
-        let Some((_, merkle_root)) = gear_api
-            .fetch_queue_merkle_root(block_hash)
-            .await
-            .convert()?
-        else {
-            log::info!(
-                "Block #{} is not present on Gear RPC node, cannot compare merkle roots",
-                event.block_number,
-            );
-            return Ok(false);
-        };
-
-        let is_matches = merkle_root == event.merkle_root;
-
-        if !is_matches {
-            log::info!(
-                "Merkle root mismatch for block #{}, hash {:X?}, expected: {}, got: {}",
-                event.block_number,
-                block_hash,
-                merkle_root,
-                event.merkle_root,
-            );
-            self.metrics.merkle_root_mismatch_cnt.inc();
-        }
-
-        Ok(is_matches)
-    }
-
     async fn fetch_merkle_root_proof_from_relayer(
         &self,
         block: u64,
@@ -479,24 +493,6 @@ impl KillSwitchRelayer {
     }
 }
 
-fn is_block_no_present_error(err: &anyhow::Error) -> bool {
-    err.to_string().contains("not present on RPC node")
-}
-
-trait ConvertToOptGearApiError<T> {
-    fn convert(self) -> anyhow::Result<Option<T>>;
-}
-
-impl<T> ConvertToOptGearApiError<T> for anyhow::Result<T> {
-    fn convert(self) -> anyhow::Result<Option<T>> {
-        match self {
-            Err(err) if is_block_no_present_error(&err) => Ok(None),
-            Err(err) => Err(err),
-            Ok(val) => Ok(Some(val)),
-        }
-    }
-}
-
 fn downcast_anyhow_to_ethereum_client(err: anyhow::Error) -> ScanForEventsError {
     match err.downcast::<ethereum_client::Error>() {
         Ok(e) => ScanForEventsError::EthApi(e),
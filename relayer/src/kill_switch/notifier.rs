@@ -0,0 +1,164 @@
+//! Delivery of kill switch alerts to external systems.
+
+use std::path::PathBuf;
+
+use primitive_types::H256;
+use reqwest::Client as HttpClient;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+
+use super::verification::SourceReport;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Alert {
+    /// Quorum of Gear endpoints doesn't have the merkle root submitted to Ethereum.
+    MerkleRootMismatch {
+        block_number: u64,
+        onchain_merkle_root: H256,
+        sources: Vec<SourceReport>,
+    },
+    /// Gear endpoints don't agree on the merkle root, so it's neither challenged nor
+    /// considered valid until they do.
+    VerificationInconclusive {
+        block_number: u64,
+        onchain_merkle_root: H256,
+        sources: Vec<SourceReport>,
+    },
+    ChallengeSent {
+        block_number: Option<u64>,
+        tx_hash: H256,
+    },
+    ChallengeFinalized {
+        block_number: Option<u64>,
+        tx_hash: H256,
+    },
+}
+
+#[async_trait::async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, alert: &Alert) -> anyhow::Result<()>;
+}
+
+/// POSTs every alert as JSON to the configured URL.
+pub struct WebhookNotifier {
+    client: HttpClient,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(client: HttpClient, url: String) -> Self {
+        Self { client, url }
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, alert: &Alert) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .json(alert)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Appends every alert as a JSON line to a file.
+pub struct FileNotifier {
+    path: PathBuf,
+}
+
+impl FileNotifier {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for FileNotifier {
+    async fn notify(&self, alert: &Alert) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(alert)?;
+        line.push(b'\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(&line).await?;
+        file.flush().await?;
+
+        Ok(())
+    }
+}
+
+/// Set of notifiers alerts are delivered to. Delivery failures are logged and never
+/// interrupt the kill switch.
+#[derive(Default)]
+pub struct Notifiers {
+    notifiers: Vec<Box<dyn Notifier>>,
+}
+
+impl Notifiers {
+    pub fn push(&mut self, notifier: impl Notifier + 'static) {
+        self.notifiers.push(Box::new(notifier));
+    }
+
+    pub async fn notify(&self, alert: Alert) {
+        for notifier in &self.notifiers {
+            if let Err(err) = notifier.notify(&alert).await {
+                log::error!("Failed to deliver kill switch alert {alert:?}: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kill_switch::verification::Observation;
+
+    #[tokio::test]
+    async fn file_notifier_appends_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alerts.jsonl");
+
+        let mut notifiers = Notifiers::default();
+        notifiers.push(FileNotifier::new(path.clone()));
+
+        notifiers
+            .notify(Alert::MerkleRootMismatch {
+                block_number: 42,
+                onchain_merkle_root: H256::repeat_byte(1),
+                sources: vec![SourceReport {
+                    source: "gear-1".to_string(),
+                    observation: Observation::Differs {
+                        merkle_root: H256::repeat_byte(2),
+                    },
+                }],
+            })
+            .await;
+        notifiers
+            .notify(Alert::ChallengeSent {
+                block_number: Some(42),
+                tx_hash: H256::repeat_byte(3),
+            })
+            .await;
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let alerts: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0]["event"], "merkle_root_mismatch");
+        assert_eq!(alerts[0]["block_number"], 42);
+        assert_eq!(alerts[0]["sources"][0]["source"], "gear-1");
+        assert_eq!(alerts[0]["sources"][0]["status"], "differs");
+        assert_eq!(alerts[1]["event"], "challenge_sent");
+    }
+}
@@ -0,0 +1,221 @@
+//! Cross-checking of on-chain merkle roots against several independent Gear endpoints.
+
+use anyhow::anyhow;
+use ethereum_client::MerkleRootEntry;
+use gear_common::api_provider::ApiProviderConnection;
+use primitive_types::H256;
+use serde::Serialize;
+
+/// Gear endpoint merkle roots are compared against.
+pub struct RootSource {
+    name: String,
+    connection: ApiProviderConnection,
+}
+
+impl RootSource {
+    pub fn new(name: String, connection: ApiProviderConnection) -> Self {
+        Self { name, connection }
+    }
+
+    async fn observe(&self, event: &MerkleRootEntry) -> Observation {
+        match self.merkle_root(event.block_number as u32).await {
+            Ok(Some(merkle_root)) if merkle_root == event.merkle_root => Observation::Matches,
+            Ok(Some(merkle_root)) => Observation::Differs { merkle_root },
+            Ok(None) => Observation::Missing,
+            Err(err) => Observation::Unavailable {
+                error: err.to_string(),
+            },
+        }
+    }
+
+    async fn merkle_root(&self, block_number: u32) -> anyhow::Result<Option<H256>> {
+        let gear_api = self.connection.client();
+        let Some(block_hash) = gear_api
+            .block_number_to_hash(block_number)
+            .await
+            .convert()?
+        else {
+            return Ok(None);
+        };
+
+        Ok(gear_api
+            .fetch_queue_merkle_root(block_hash)
+            .await
+            .convert()?
+            .map(|(_, merkle_root)| merkle_root))
+    }
+}
+
+/// What a single source says about an on-chain merkle root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Observation {
+    /// Source has the same merkle root at the block.
+    Matches,
+    /// Source has a different merkle root at the block.
+    Differs { merkle_root: H256 },
+    /// Block isn't present on the source.
+    Missing,
+    /// Source failed to answer.
+    Unavailable { error: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceReport {
+    pub source: String,
+    #[serde(flatten)]
+    pub observation: Observation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Quorum of sources has the same merkle root.
+    Confirmed,
+    /// Quorum of sources doesn't have the on-chain merkle root.
+    Mismatch,
+    /// Sources don't agree or too few of them answered.
+    Inconclusive,
+}
+
+pub fn tally<'a>(
+    observations: impl IntoIterator<Item = &'a Observation>,
+    quorum: usize,
+) -> Verdict {
+    let (mut matches, mut mismatches) = (0, 0);
+    for observation in observations {
+        match observation {
+            Observation::Matches => matches += 1,
+            Observation::Differs { .. } | Observation::Missing => mismatches += 1,
+            Observation::Unavailable { .. } => {}
+        }
+    }
+
+    match (matches >= quorum, mismatches >= quorum) {
+        (true, false) => Verdict::Confirmed,
+        (false, true) => Verdict::Mismatch,
+        _ => Verdict::Inconclusive,
+    }
+}
+
+/// Requires `quorum` sources to agree on whether an on-chain merkle root is correct.
+pub struct RootVerifier {
+    sources: Vec<RootSource>,
+    quorum: usize,
+}
+
+impl RootVerifier {
+    pub fn new(sources: Vec<RootSource>, quorum: usize) -> anyhow::Result<Self> {
+        if quorum == 0 || quorum > sources.len() {
+            return Err(anyhow!(
+                "Verification quorum must be between 1 and the number of Gear endpoints ({})",
+                sources.len()
+            ));
+        }
+
+        Ok(Self { sources, quorum })
+    }
+
+    pub fn quorum(&self) -> usize {
+        self.quorum
+    }
+
+    pub fn source_count(&self) -> usize {
+        self.sources.len()
+    }
+
+    pub async fn verify(&mut self, event: &MerkleRootEntry) -> (Verdict, Vec<SourceReport>) {
+        let observations =
+            futures::future::join_all(self.sources.iter().map(|source| source.observe(event)))
+                .await;
+
+        for (source, observation) in self.sources.iter_mut().zip(&observations) {
+            if let Observation::Unavailable { error } = observation {
+                log::warn!(
+                    "Gear endpoint {} failed to answer for block #{}: {error}, reconnecting...",
+                    source.name,
+                    event.block_number
+                );
+                if let Err(err) = source.connection.reconnect().await {
+                    log::error!("Gear endpoint {} reconnect failed: {err}", source.name);
+                }
+            }
+        }
+
+        let verdict = tally(&observations, self.quorum);
+        let reports = self
+            .sources
+            .iter()
+            .zip(observations)
+            .map(|(source, observation)| SourceReport {
+                source: source.name.clone(),
+                observation,
+            })
+            .collect();
+
+        (verdict, reports)
+    }
+}
+
+fn is_block_no_present_error(err: &anyhow::Error) -> bool {
+    err.to_string().contains("not present on RPC node")
+}
+
+trait ConvertToOptGearApiError<T> {
+    fn convert(self) -> anyhow::Result<Option<T>>;
+}
+
+impl<T> ConvertToOptGearApiError<T> for anyhow::Result<T> {
+    fn convert(self) -> anyhow::Result<Option<T>> {
+        match self {
+            Err(err) if is_block_no_present_error(&err) => Ok(None),
+            Err(err) => Err(err),
+            Ok(val) => Ok(Some(val)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unavailable() -> Observation {
+        Observation::Unavailable {
+            error: "connection closed".to_string(),
+        }
+    }
+
+    fn differs() -> Observation {
+        Observation::Differs {
+            merkle_root: H256::repeat_byte(2),
+        }
+    }
+
+    #[test]
+    fn single_source_decides_alone() {
+        assert_eq!(tally(&[Observation::Matches], 1), Verdict::Confirmed);
+        assert_eq!(tally(&[differs()], 1), Verdict::Mismatch);
+        assert_eq!(tally(&[Observation::Missing], 1), Verdict::Mismatch);
+        assert_eq!(tally(&[unavailable()], 1), Verdict::Inconclusive);
+    }
+
+    #[test]
+    fn faulty_source_cannot_trigger_challenge() {
+        let observations = [differs(), Observation::Matches, Observation::Matches];
+        assert_eq!(tally(&observations, 2), Verdict::Confirmed);
+
+        let observations = [differs(), Observation::Matches, unavailable()];
+        assert_eq!(tally(&observations, 2), Verdict::Inconclusive);
+    }
+
+    #[test]
+    fn faulty_source_cannot_hide_mismatch() {
+        let observations = [Observation::Matches, differs(), Observation::Missing];
+        assert_eq!(tally(&observations, 2), Verdict::Mismatch);
+    }
+
+    #[test]
+    fn split_votes_are_inconclusive() {
+        let observations = [Observation::Matches, differs()];
+        assert_eq!(tally(&observations, 1), Verdict::Inconclusive);
+    }
+}
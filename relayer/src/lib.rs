@@ -1,12 +1,16 @@
+pub mod api_tokens;
 pub mod cli;
 pub mod common;
 pub mod config;
+pub mod config_reload;
+pub mod daemon;
 pub mod ethereum_checkpoints;
 pub mod hex_utils;
 pub mod kill_switch;
 pub mod merkle_roots;
 pub mod message_relayer;
 pub mod proof_storage;
+pub mod prover_bench;
 pub mod prover_interface;
 pub mod queue_cleaner;
 pub mod rpc;
@@ -1,6 +1,10 @@
 use actix_web::dev::ServerHandle;
 use anyhow::{anyhow, Context, Result as AnyResult};
 use clap::Parser;
+use cli_utils::{
+    keystore::{self, ScryptCost},
+    KeyResolver,
+};
 use eth_events_electra_client::traits::EthereumEventClient;
 use ethereum_beacon_client::BeaconClient;
 use ethereum_client::{EthApi, PollingEthApi};
@@ -8,32 +12,64 @@ use ethereum_common::SLOTS_PER_EPOCH;
 use gclient::ext::sp_runtime::AccountId32;
 use gear_common::api_provider::{ApiProvider, ApiProviderConnection};
 use historical_proxy_client::{traits::HistoricalProxy as _, HistoricalProxy};
-use primitive_types::U256;
+use primitive_types::{H256, U256};
 use prover::consts::SIZE_THREAD_STACK_MIN;
 use relayer::{
+    api_tokens::ApiTokens,
     cli::{
-        BeaconRpcArgs, Cli, CliCommands, EthGearManualArgs, EthGearTokensArgs,
-        EthGearTokensCommands, EthereumArgs, EthereumKillSwitchArgs, EthereumSignerArgs, FeePayers,
-        FetchMerkleRootsArgs, GearEthCoreArgs, GearEthTokensCommands, GearSignerArgs,
+        BeaconRpcArgs, Cli, CliCommands, CreateKeystoreArgs, EthGearManualArgs, EthGearTokensArgs,
+        EthGearTokensCommands, EthereumArgs, EthereumConnectionArgs, EthereumKillSwitchArgs,
+        EthereumSignerArgs, EthereumTxArgs, FailedTransactionsArgs, FailedTransactionsCommands,
+        FeePayers, FetchMerkleRootsArgs, GearEthCoreArgs, GearEthTokensCommands, GearSignerArgs,
+        KillSwitchVerificationArgs, ProofStorageCommands, ProofStorageToolArgs, ProverBenchArgs,
+        ProverBenchCommands, RelayerHttpArgs, ReplaySubmissionPolicyArgs, RunArgs,
         DEFAULT_COUNT_CONFIRMATIONS, DEFAULT_COUNT_THREADS,
     },
     common,
     config::{
-        EffectiveConfig, EffectiveEthereumConfig, EffectiveGearConfig, EffectiveProofStorageConfig,
-        EffectiveRelayerConfig,
+        EffectiveConfig, EffectiveEthereumConfig, EffectiveGearConfig,
+        EffectiveLeaderElectionConfig, EffectiveLockConfig, EffectiveProofStorageConfig,
+        EffectiveProverConfig, EffectiveRelayerConfig,
+    },
+    config_reload::{ConfigReloader, LiveRelayer},
+    daemon::{
+        config::{
+            DaemonConfig, DaemonEthereumConfig, DaemonGearConfig, EthGearTokensService,
+            EthGearTransfers, GearEthTokensService, GearEthTransfers, KillSwitchService,
+            ServiceConfig, ServiceKind,
+        },
+        supervisor::Supervisor,
     },
     ethereum_checkpoints, hex_utils,
-    kill_switch::KillSwitchRelayer,
+    kill_switch::{
+        notifier::{FileNotifier, Notifiers, WebhookNotifier},
+        verification::{RootSource, RootVerifier},
+        KillSwitchRelayer,
+    },
     merkle_roots::{
-        self, authority_set_sync::SharedAuthoritySetSync, prover::SharedFinalityProver,
+        self,
+        authority_set_sync::SharedAuthoritySetSync,
+        leader_election::{
+            DatabaseLockBackend, FileLockBackend, LeaderElection, LeaderElectionConfig,
+            LockBackend, ObjectStoreLockBackend,
+        },
+        policy::{self, BaseFeeSample, ReplayedRoot, SubmissionPolicy},
+        prover::SharedFinalityProver,
+        prover_pool::{self, HttpJobSource, ProverPool, ProverWorker},
+        storage::{DatabaseBackend, JsonSnapshotBackend, MerkleRootStorage, StateBackend},
     },
     message_relayer::{self, eth_to_gear, gear_to_eth},
-    proof_storage::{FileSystemProofStorage, GearProofStorage, ProofStorage},
-    prover_interface, server,
+    proof_storage::{
+        FileSystemProofStorage, GearProofStorage, ObjectStoreProofStorage, ProofArchive,
+        ProofStorage, S3ObjectStore,
+    },
+    prover_bench::{self, ProverFixture},
+    prover_interface,
+    server::{self, TransactionQueue},
 };
 use sails_rs::{calls::Query, gclient::calls::GClientRemoting, ActorId};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     env,
     fs::{self, File},
     future::Future,
@@ -45,11 +81,18 @@ use std::{
     time::Duration,
 };
 
-use tokio::{sync::mpsc, task, time};
-use utils_prometheus::{MeteredService, MetricsBuilder};
+use tokio::{
+    sync::{mpsc, watch},
+    task, time,
+};
+use utils_prometheus::{MeteredService, MetricsBuilder, SharedMetrics};
 use vft_manager_client::traits::VftManager;
 use zeroize::Zeroizing;
 
+/// How often web-server token files are checked for changes.
+const TOKENS_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+const CONFIG_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
 fn main() -> AnyResult<()> {
     rayon::ThreadPoolBuilder::new()
         .num_threads(30)
@@ -78,9 +121,15 @@ async fn run() -> AnyResult<()> {
         .parse_default_env()
         .init();
 
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    let keys = KeyResolver::from_args(&cli.signer)?;
+    cli.command.resolve_keys(&keys)?;
 
     match cli.command {
+        CliCommands::Run(args) => {
+            return run_daemon(args, &keys).await;
+        }
+
         CliCommands::UpdateVerifierSol(args) => {
             let working_directory = env::current_dir()?;
             let path_srs_setup = {
@@ -195,50 +244,59 @@ async fn run() -> AnyResult<()> {
         }
 
         CliCommands::GearEthCore(args) => {
-            return run_gear_eth_core(args).await;
+            return run_gear_eth_core(args, &keys).await;
         }
 
-        CliCommands::KillSwitch(args) => {
-            use reqwest::header;
+        CliCommands::ProverWorker(args) => {
+            check_rust_min_stack()?;
+            init_circuit_cache()?;
 
             let api_provider = ApiProvider::new(
                 args.gear_args.get_endpoint()?,
                 args.gear_args.max_reconnect_attempts,
             )
             .await
-            .expect("Failed to connect to Gear API");
+            .context("Failed to connect to Gear API")?;
+
+            let worker_id = args
+                .worker_id
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            let source = HttpJobSource::new(args.pool_url, args.pool_token)?;
+            let worker = ProverWorker::new(
+                worker_id,
+                source,
+                api_provider.connection(),
+                match args.thread_count {
+                    Some(thread_count) => thread_count.into(),
+                    None => Some(DEFAULT_COUNT_THREADS),
+                },
+                args.gnark_data_path,
+            );
+            api_provider.spawn();
 
-            let (eth_observer_api, eth_admin_api) =
-                create_eth_killswitch_client(&args.ethereum_args)
-                    .await
-                    .expect("Failed to create Ethereum client");
-            let http_client = reqwest::Client::builder()
-                .timeout(Duration::from_secs(args.relayer_http_args.timeout_secs))
-                .default_headers({
-                    let mut headers = header::HeaderMap::new();
-                    headers.insert(
-                        "X-Token",
-                        header::HeaderValue::from_str(&args.relayer_http_args.access_token)
-                            .expect("Invalid token"),
-                    );
-                    headers
-                })
-                .build()
-                .expect("Failed to create HTTP client");
+            worker.spawn().await?;
+        }
 
-            let metrics = MetricsBuilder::new();
+        CliCommands::KillSwitch(args) => {
+            let api_provider = ApiProvider::new(
+                args.gear_args.get_endpoint()?,
+                args.gear_args.max_reconnect_attempts,
+            )
+            .await
+            .expect("Failed to connect to Gear API");
 
-            let mut kill_switch = KillSwitchRelayer::new(
+            let mut kill_switch = create_kill_switch(
+                args.gear_args.get_endpoint()?,
                 api_provider.connection(),
-                eth_observer_api,
-                eth_admin_api,
-                http_client,
+                args.gear_args.max_reconnect_attempts,
+                &args.ethereum_args,
+                &args.relayer_http_args,
+                args.verification_args,
                 args.from_eth_block,
-                args.relayer_http_args.url,
             )
-            .await;
+            .await?;
 
-            metrics
+            MetricsBuilder::new()
                 .register_service(&kill_switch)
                 .build()
                 .run(args.prometheus_args.prometheus_endpoint)
@@ -260,7 +318,10 @@ async fn run() -> AnyResult<()> {
         }
 
         CliCommands::GearEthTokens(args) => {
-            let eth_api = create_eth_signer_client(&args.ethereum_args).await;
+            let mut eth_api = create_eth_signer_client(&args.ethereum_args).await;
+            if let Some(address) = &args.multicall_address {
+                eth_api = eth_api.with_multicall_address(address)?;
+            }
 
             let gsdk_args = message_relayer::common::GSdkArgs {
                 vara_endpoint: args.gear_args.get_endpoint()?,
@@ -273,61 +334,10 @@ async fn run() -> AnyResult<()> {
             .await
             .context("Failed to create API provider")?;
 
-            let api = provider.connection().client();
-            let governance_admin: [u8; 32] = AccountId32::from_str(&args.governance_admin)
-                .expect("Failed to parse governance admin address")
-                .into();
-            let governance_admin: ActorId = ActorId::from(governance_admin);
-            let governance_pauser: [u8; 32] = AccountId32::from_str(&args.governance_pauser)
-                .expect("Failed to parse governance pauser address")
-                .into();
-            let governance_pauser: ActorId = ActorId::from(governance_pauser);
-
-            let mut excluded_from_fees = HashSet::new();
-            match args.no_fee {
-                None => {
-                    log::warn!("No free from charge accounts listed, using default: bridgeAdmin and bridgePauser from chain constants");
-                    match api.bridge_admin().await {
-                        Ok(admin) => {
-                            log::info!("Bridge admin: {admin}");
-                            let admin: &[u8] = admin.as_ref();
-                            excluded_from_fees.insert(AccountId32::try_from(admin).unwrap());
-                        }
-                        Err(e) => {
-                            log::error!("Failed to get bridge admin: {e}");
-                        }
-                    };
-
-                    match api.bridge_pauser().await {
-                        Ok(pauser) => {
-                            log::info!("Bridge pauser: {pauser}");
-                            let pauser: &[u8] = pauser.as_ref();
-                            excluded_from_fees.insert(AccountId32::try_from(pauser).unwrap());
-                        }
-                        Err(e) => {
-                            log::error!("Failed to get bridge pauser: {e}");
-                        }
-                    };
-
-                    if excluded_from_fees.is_empty() {
-                        return Err(anyhow!("Exiting"));
-                    }
-                }
-
-                Some(FeePayers::All) => {
-                    log::info!("All accounts haave to pay fees");
-                }
-
-                Some(FeePayers::ExcludedIds(ids)) => {
-                    for id in ids {
-                        let account_id = AccountId32::from_str(id.as_str())
-                            .map_err(|e| anyhow!(r#"Failed to decode address "{id}": {e:?}"#))?;
-
-                        log::debug!("Account {account_id} is excluded from paying fees");
-                        excluded_from_fees.insert(account_id);
-                    }
-                }
-            }
+            let (governance_admin, governance_pauser) =
+                parse_governance(&args.governance_admin, &args.governance_pauser)?;
+            let excluded_from_fees =
+                excluded_from_fees(&provider.connection().client(), args.no_fee).await?;
 
             match args.command {
                 GearEthTokensCommands::AllTokenTransfers => {
@@ -338,6 +348,7 @@ async fn run() -> AnyResult<()> {
                             .unwrap_or(DEFAULT_COUNT_CONFIRMATIONS),
                         args.confirmations_status
                             .unwrap_or(DEFAULT_COUNT_CONFIRMATIONS),
+                        args.max_batch_size,
                         args.storage_path,
                         governance_admin,
                         governance_pauser,
@@ -359,25 +370,18 @@ async fn run() -> AnyResult<()> {
                     bridging_payment_address,
                     web_server_token,
                     web_server_address,
+                    web_server_access,
+                    fee_market,
                 } => {
                     let bridging_payment_address =
                         hex_utils::decode_h256(&bridging_payment_address)
                             .context("Failed to parse address")?;
+                    let fee_market = fee_market
+                        .config()
+                        .map(|config| config.build(eth_api.clone()));
 
-                    // spawn web-server
                     let tcp_listener = TcpListener::bind(web_server_address)?;
                     let (sender, receiver) = mpsc::unbounded_channel();
-                    let web_server = server::create(
-                        tcp_listener,
-                        web_server_token,
-                        "gear-eth-token-paid-transfers".to_string(),
-                        Some(sender),
-                        None,
-                        None,
-                    )
-                    .context("Failed to create web server")?;
-                    let handle_server = web_server.handle();
-                    task::spawn(web_server);
 
                     let relayer = gear_to_eth::paid_token_transfers::Relayer::new(
                         eth_api,
@@ -387,7 +391,9 @@ async fn run() -> AnyResult<()> {
                             .unwrap_or(DEFAULT_COUNT_CONFIRMATIONS),
                         args.confirmations_status
                             .unwrap_or(DEFAULT_COUNT_CONFIRMATIONS),
+                        args.max_batch_size,
                         excluded_from_fees,
+                        fee_market,
                         receiver,
                         args.storage_path.clone(),
                         governance_admin,
@@ -396,6 +402,24 @@ async fn run() -> AnyResult<()> {
                     .await
                     .unwrap();
 
+                    // spawn web-server
+                    let web_server = server::create(
+                        tcp_listener,
+                        web_server_tokens(
+                            web_server_token,
+                            web_server_access.tokens_file,
+                            web_server_access.audit_log,
+                        )?,
+                        "gear-eth-token-paid-transfers".to_string(),
+                        Some(sender),
+                        None,
+                        None,
+                        Some(TransactionQueue::GearToEth(relayer.tx_manager())),
+                    )
+                    .context("Failed to create web server")?;
+                    let handle_server = web_server.handle();
+                    task::spawn(web_server);
+
                     MetricsBuilder::new()
                         .register_service(&relayer)
                         .build()
@@ -416,16 +440,11 @@ async fn run() -> AnyResult<()> {
 
             let program_id =
                 hex_utils::decode_h256(&args.program_id).expect("Failed to decode program_id");
-            let multiplier = if args.size_batch_multiplier > 0 {
-                args.size_batch_multiplier
-            } else {
-                1
-            };
-            let relayer = ethereum_checkpoints::Relayer::new(
+            let relayer = create_eth_gear_core_relayer(
                 program_id,
                 beacon_client,
                 gear_api,
-                multiplier.saturating_mul(SLOTS_PER_EPOCH),
+                args.size_batch_multiplier,
             );
 
             MetricsBuilder::new()
@@ -444,8 +463,10 @@ async fn run() -> AnyResult<()> {
             beacon_rpc,
             prometheus_args,
             storage_path,
+            storage_database,
             ethereum_blocks,
         }) => {
+            let storage = create_eth_gear_storage(storage_path, storage_database)?;
             let eth_api = PollingEthApi::new(&ethereum_rpc).await?;
             let beacon_client = create_beacon_client(&beacon_rpc).await;
 
@@ -498,7 +519,7 @@ async fn run() -> AnyResult<()> {
                         historical_proxy_address.into_bytes().into(),
                         vft_manager_address,
                         connection,
-                        storage_path,
+                        storage,
                         genesis_time,
                         ethereum_blocks.clone(),
                     )
@@ -517,6 +538,7 @@ async fn run() -> AnyResult<()> {
                     bridging_payment_address,
                     web_server_token,
                     web_server_address,
+                    web_server_access,
                 } => {
                     let bridging_payment_address =
                         hex_utils::decode_h160(&bridging_payment_address)
@@ -524,17 +546,6 @@ async fn run() -> AnyResult<()> {
 
                     let tcp_listener = TcpListener::bind(web_server_address)?;
                     let (sender, receiver) = mpsc::unbounded_channel();
-                    let web_server = server::create(
-                        tcp_listener,
-                        web_server_token,
-                        "eth-gear-token-paid-transfers".to_string(),
-                        None,
-                        None,
-                        Some(sender),
-                    )
-                    .context("Failed to create web server")?;
-                    let handle_server = web_server.handle();
-                    task::spawn(web_server);
 
                     let relayer = eth_to_gear::paid_token_transfers::Relayer::new(
                         gear_args.suri,
@@ -545,7 +556,7 @@ async fn run() -> AnyResult<()> {
                         historical_proxy_address.into_bytes().into(),
                         vft_manager_address,
                         connection,
-                        storage_path,
+                        storage,
                         genesis_time,
                         ethereum_blocks.clone(),
                         Some(receiver),
@@ -553,6 +564,23 @@ async fn run() -> AnyResult<()> {
                     .await
                     .expect("Failed to create relayer");
 
+                    let web_server = server::create(
+                        tcp_listener,
+                        web_server_tokens(
+                            web_server_token,
+                            web_server_access.tokens_file,
+                            web_server_access.audit_log,
+                        )?,
+                        "eth-gear-token-paid-transfers".to_string(),
+                        None,
+                        None,
+                        Some(sender),
+                        Some(TransactionQueue::EthToGear(relayer.tx_manager())),
+                    )
+                    .context("Failed to create web server")?;
+                    let handle_server = web_server.handle();
+                    task::spawn(web_server);
+
                     MetricsBuilder::new()
                         .register_service(&relayer)
                         .build()
@@ -577,14 +605,8 @@ async fn run() -> AnyResult<()> {
             .await
             .expect("Failed to create API provider");
 
-            let governance_admin: [u8; 32] = AccountId32::from_str(&args.governance_admin)
-                .expect("Failed to parse governance admin address")
-                .into();
-            let governance_admin: ActorId = ActorId::from(governance_admin);
-            let governance_pauser: [u8; 32] = AccountId32::from_str(&args.governance_pauser)
-                .expect("Failed to parse governance pauser address")
-                .into();
-            let governance_pauser: ActorId = ActorId::from(governance_pauser);
+            let (governance_admin, governance_pauser) =
+                parse_governance(&args.governance_admin, &args.governance_pauser)?;
 
             let connection = api_provider.connection();
             api_provider.spawn();
@@ -700,217 +722,794 @@ async fn run() -> AnyResult<()> {
         }
 
         CliCommands::FetchMerkleRoots(args) => fetch_merkle_roots(args).await?,
+
+        CliCommands::ProofStorage(args) => run_proof_storage_tool(args, &keys).await?,
+
+        CliCommands::ReplaySubmissionPolicy(args) => replay_submission_policy(args).await?,
+
+        CliCommands::ProverBench(args) => prover_bench(args).await?,
+
+        CliCommands::FailedTransactions(args) => failed_transactions(args).await?,
+
+        CliCommands::CreateKeystore(args) => create_keystore(args, &keys)?,
     };
 
     Ok(())
 }
 
-async fn run_gear_eth_core(args: GearEthCoreArgs) -> AnyResult<()> {
+fn check_rust_min_stack() -> AnyResult<()> {
     let rust_min_stack = env::var("RUST_MIN_STACK").context("RUST_MIN_STACK")?;
     let rust_min_stack = rust_min_stack.parse::<usize>().context("RUST_MIN_STACK")?;
     if rust_min_stack < SIZE_THREAD_STACK_MIN {
         return Err(anyhow!("RUST_MIN_STACK={rust_min_stack} is less than the required minimum ({SIZE_THREAD_STACK_MIN}). Re-run the program with the corresponding environment variable set.\n\nAt the moment we cannot control how the external libraries spawn threads so base on the environment variable from standard library. For details - https://doc.rust-lang.org/std/thread/index.html#stack-size"));
     }
 
-    let EffectiveConfig {
-        prometheus_endpoint,
-        relayers,
-    } = match args.config.as_ref() {
+    Ok(())
+}
+
+/// Loads circuits cached by the previous runs from `CIRCUIT_CACHE_PATH`. When it isn't set,
+/// circuits are built for every proof.
+fn init_circuit_cache() -> AnyResult<()> {
+    let Ok(path) = env::var("CIRCUIT_CACHE_PATH") else {
+        log::info!("CIRCUIT_CACHE_PATH is not set, circuit data isn't cached");
+        return Ok(());
+    };
+
+    let loaded = prover::circuit_cache::init(Path::new(&path))
+        .with_context(|| format!("Failed to load circuit cache from {path}"))?;
+    log::info!("Loaded {loaded} cached circuits from {path}");
+
+    Ok(())
+}
+
+async fn run_gear_eth_core(args: GearEthCoreArgs, keys: &KeyResolver) -> AnyResult<()> {
+    check_rust_min_stack()?;
+    init_circuit_cache()?;
+
+    let config = match args.config.as_ref() {
         Some(path) => EffectiveConfig::from_path(path)?,
         None => EffectiveConfig::from_cli(&args)?,
     };
+    // The reloader compares configs with key references, the relayers get the keys.
+    let EffectiveConfig {
+        prometheus_endpoint,
+        mut relayers,
+    } = config.clone();
+    for relayer in &mut relayers {
+        relayer.resolve_keys(keys)?;
+    }
 
     if relayers.len() == 1 {
         let relayer = relayers
             .into_iter()
             .next()
             .expect("relayers length is checked immediately above");
-        let running =
+        let mut running =
             start_gear_eth_core_relayer(relayer, Some(prometheus_endpoint), None, false).await?;
+        if let Some(path) = args.config {
+            watch_gear_eth_core_config(path, config, std::slice::from_mut(&mut running))?;
+        }
         return wait_single_relayer(running).await;
     }
 
-    run_gear_eth_core_relayers(relayers, prometheus_endpoint).await
-}
-
-struct RunningGearEthCoreRelayer {
-    id: String,
-    task: task::JoinHandle<AnyResult<()>>,
-    server_handle: ServerHandle,
-    metrics: Option<MetricsBuilder>,
-}
-
-async fn run_gear_eth_core_relayers(
-    mut relayers: Vec<EffectiveRelayerConfig>,
-    prometheus_endpoint: String,
-) -> AnyResult<()> {
     log::info!(
         "Starting {} merkle-root relayers in one process. Prometheus endpoint {prometheus_endpoint} uses relayer labels for per-relayer collectors",
         relayers.len()
     );
 
-    // Start higher-priority relayers first; authority-set proving is serialized by
-    // SharedAuthoritySetSync so only one heavy proving job runs at a time.
-    relayers.sort_by(|a, b| b.priority.cmp(&a.priority));
-    let shared_authority_set_sync = SharedAuthoritySetSync::new();
-
-    let shared_prover = SharedFinalityProver::new();
-    let mut metrics = MetricsBuilder::new();
-    metrics.register_service_mut(&shared_prover);
-
-    let mut running = start_required_gear_eth_core_relayers(relayers, |mut relayer| {
-        relayer
-            .options
-            .shared_authority_set_sync
-            .replace(shared_authority_set_sync.clone());
-        start_gear_eth_core_relayer(relayer, None, Some(&shared_prover), true)
-    })
-    .await?;
-
-    for relayer in &mut running {
-        if let Some(relayer_metrics) = relayer.metrics.take() {
-            metrics.append(relayer_metrics);
-        }
-    }
+    let (mut running, metrics) = start_shared_gear_eth_core_relayers(relayers).await?;
     metrics.build().run(prometheus_endpoint).await;
+    if let Some(path) = args.config {
+        watch_gear_eth_core_config(path, config, &mut running)?;
+    }
 
     supervise_running_gear_eth_core_relayers(running).await
 }
 
-async fn start_required_gear_eth_core_relayers<I, F, Fut>(
-    relayers: impl IntoIterator<Item = I>,
-    mut start: F,
-) -> AnyResult<Vec<RunningGearEthCoreRelayer>>
-where
-    F: FnMut(I) -> Fut,
-    Fut: Future<Output = AnyResult<RunningGearEthCoreRelayer>>,
-{
-    let mut running = Vec::new();
-    for relayer in relayers {
-        match start(relayer).await {
-            Ok(relayer) => running.push(relayer),
-            Err(err) => {
-                stop_running_gear_eth_core_relayers(running).await;
-                return Err(err);
-            }
-        }
-    }
-    Ok(running)
-}
+/// Applies changes of the config file to the running relayers, see [`ConfigReloader`].
+fn watch_gear_eth_core_config(
+    path: PathBuf,
+    config: EffectiveConfig,
+    running: &mut [RunningGearEthCoreRelayer],
+) -> AnyResult<()> {
+    let relayers = running
+        .iter_mut()
+        .filter_map(|relayer| Some((relayer.id.clone(), relayer.live.take()?)))
+        .collect::<HashMap<_, _>>();
+    log::info!(
+        "Watching config {} for changes, reload with SIGHUP",
+        path.display()
+    );
 
-async fn stop_running_gear_eth_core_relayers(running: Vec<RunningGearEthCoreRelayer>) {
-    let mut tasks = Vec::with_capacity(running.len());
-    for relayer in running {
-        stop_web_server(relayer.server_handle).await;
-        relayer.task.abort();
-        tasks.push(relayer.task);
-    }
+    ConfigReloader::new(path, config, relayers).spawn(CONFIG_RELOAD_INTERVAL)
+}
 
-    for task in tasks {
-        let _ = task.await;
-    }
+/// Connections shared by the services of the `run` command.
+#[derive(Clone)]
+struct DaemonClients {
+    gear: ApiProviderConnection,
+    gear_config: DaemonGearConfig,
+    ethereum: Option<DaemonEthereumConfig>,
+    eth_signer: Option<EthApi>,
+    eth_polling: Option<PollingEthApi>,
+    beacon: Option<BeaconClient>,
 }
 
-async fn supervise_running_gear_eth_core_relayers(
-    running: Vec<RunningGearEthCoreRelayer>,
-) -> AnyResult<()> {
-    if running.is_empty() {
-        return Err(anyhow!("no merkle-root relayers started"));
+impl DaemonClients {
+    // Presence of the required sections is checked when the config is loaded.
+    fn suri(&self) -> AnyResult<String> {
+        self.gear_config
+            .suri
+            .clone()
+            .ok_or_else(|| anyhow!("gear.suri is not configured"))
     }
 
-    let mut tasks = Vec::with_capacity(running.len());
-    let mut ids = Vec::with_capacity(running.len());
-    let mut server_handles = Vec::with_capacity(running.len());
-
-    for relayer in running {
-        ids.push(relayer.id);
-        server_handles.push(relayer.server_handle);
-        tasks.push(relayer.task);
+    fn ethereum(&self) -> AnyResult<&DaemonEthereumConfig> {
+        self.ethereum
+            .as_ref()
+            .ok_or_else(|| anyhow!("ethereum is not configured"))
     }
 
-    let (result, index, remaining_tasks) = futures::future::select_all(tasks).await;
-    for server_handle in server_handles {
-        stop_web_server(server_handle).await;
-    }
-    for task in remaining_tasks {
-        task.abort();
+    fn eth_signer(&self) -> AnyResult<EthApi> {
+        self.eth_signer
+            .clone()
+            .ok_or_else(|| anyhow!("ethereum.fee_payer is not configured"))
     }
 
-    let id = ids
-        .get(index)
-        .cloned()
-        .unwrap_or_else(|| "<unknown>".to_string());
+    fn eth_polling(&self) -> AnyResult<PollingEthApi> {
+        self.eth_polling
+            .clone()
+            .ok_or_else(|| anyhow!("ethereum is not configured"))
+    }
 
-    match result {
-        Ok(Ok(())) => Err(anyhow!("merkle-root relayer {id} exited unexpectedly")),
-        Ok(Err(err)) => Err(err).with_context(|| format!("merkle-root relayer {id} failed")),
-        Err(err) => Err(anyhow!("merkle-root relayer {id} task failed: {err}")),
+    fn beacon(&self) -> AnyResult<BeaconClient> {
+        self.beacon
+            .clone()
+            .ok_or_else(|| anyhow!("beacon is not configured"))
     }
 }
 
-async fn wait_single_relayer(running: RunningGearEthCoreRelayer) -> AnyResult<()> {
-    let RunningGearEthCoreRelayer {
-        id,
-        task,
-        server_handle,
-        ..
-    } = running;
-    match task.await {
-        Ok(result) => result,
-        Err(err) => {
-            stop_web_server(server_handle).await;
-            Err(anyhow!("merkle-root relayer {id} task failed: {err}"))
-        }
+async fn run_daemon(args: RunArgs, keys: &KeyResolver) -> AnyResult<()> {
+    let mut config = DaemonConfig::from_path(&args.config)?;
+    config.resolve_keys(keys)?;
+    let DaemonConfig {
+        prometheus_endpoint,
+        gear,
+        ethereum,
+        beacon,
+        services,
+    } = config;
+
+    let requires = |kind: fn(&ServiceKind) -> bool| services.iter().any(|s| kind(&s.kind));
+    if requires(|kind| matches!(kind, ServiceKind::GearEthCore { .. })) {
+        check_rust_min_stack()?;
+        init_circuit_cache()?;
     }
-}
 
-async fn start_gear_eth_core_relayer(
-    config: EffectiveRelayerConfig,
-    prometheus_endpoint: Option<String>,
-    shared_prover: Option<&SharedFinalityProver>,
-    label_metrics: bool,
-) -> AnyResult<RunningGearEthCoreRelayer> {
-    let id = config.id.clone();
-    let api_provider = ApiProvider::new(
-        config.gear.endpoint.clone(),
-        config.gear.max_reconnect_attempts,
-    )
-    .await
-    .context("Failed to connect to Gear API")?;
+    let api_provider = ApiProvider::new(gear.endpoint.clone(), gear.max_reconnect_attempts)
+        .await
+        .context("Failed to connect to Gear API")?;
 
-    let eth_api = create_eth_signer_client_from_config(&config.ethereum).await?;
+    let eth_signer = match &ethereum {
+        Some(
+            config @ DaemonEthereumConfig {
+                fee_payer: Some(fee_payer),
+                message_queue_address: Some(_),
+                ..
+            },
+        ) if requires(|kind| matches!(kind, ServiceKind::GearEthTokens(_))) => Some(
+            create_eth_signer_client(&EthereumSignerArgs {
+                ethereum_args: daemon_ethereum_args(config),
+                eth_fee_payer: fee_payer.clone(),
+                nonce_file: config.nonce_file.clone(),
+            })
+            .await,
+        ),
+        _ => None,
+    };
+    let eth_polling = match &ethereum {
+        Some(config) if requires(|kind| matches!(kind, ServiceKind::EthGearTokens(_))) => {
+            Some(PollingEthApi::new(&config.endpoint).await?)
+        }
+        _ => None,
+    };
+    let beacon = match &beacon {
+        Some(config) => Some(
+            BeaconClient::new(config.endpoint.clone(), config.timeout)
+                .await
+                .context("Failed to create beacon client")?,
+        ),
+        None => None,
+    };
 
-    let mut metrics = MetricsBuilder::new();
+    let clients = DaemonClients {
+        gear: api_provider.connection(),
+        gear_config: gear,
+        ethereum,
+        eth_signer,
+        eth_polling,
+        beacon,
+    };
+    api_provider.spawn();
 
-    let metric_relayer_id = label_metrics.then_some(id.as_str());
-    let proof_storage = create_proof_storage_from_config(
-        &config.proof_storage,
-        &config.gear,
-        &mut metrics,
+    let supervisor = Supervisor::new();
+    let metrics = SharedMetrics::new();
+    metrics.set(
+        "daemon",
+        MetricsBuilder::new().register_service(&supervisor),
+    );
+    metrics.run(prometheus_endpoint).await;
+
+    log::info!(
+        "Starting {} service(s): {}",
+        services.len(),
+        services
+            .iter()
+            .map(|service| format!("{} ({})", service.name, service.kind.name()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let supervised = services.into_iter().map(|service| {
+        let ServiceConfig {
+            name,
+            restart,
+            kind,
+        } = service;
+        let supervisor = &supervisor;
+        let clients = &clients;
+        let metrics = &metrics;
+
+        async move {
+            let result = supervisor
+                .supervise(&name, restart, || {
+                    run_daemon_service(name.clone(), kind.clone(), clients.clone(), metrics.clone())
+                })
+                .await;
+            metrics.remove(&name);
+
+            result
+        }
+    });
+
+    // A service which is given up on stops the daemon, the others are dropped with it.
+    futures::future::try_join_all(supervised).await?;
+
+    log::info!("All services exited");
+
+    Ok(())
+}
+
+async fn run_daemon_service(
+    name: String,
+    kind: ServiceKind,
+    clients: DaemonClients,
+    metrics: SharedMetrics,
+) -> AnyResult<()> {
+    match kind {
+        ServiceKind::GearEthCore { relayers } => {
+            let (running, relayer_metrics) = start_shared_gear_eth_core_relayers(relayers).await?;
+            metrics.set(&name, relayer_metrics);
+
+            supervise_running_gear_eth_core_relayers(running).await
+        }
+
+        ServiceKind::EthGearCore {
+            checkpoint_light_client,
+            size_batch_multiplier,
+        } => {
+            let gear_api = clients.gear.clone().gclient_client(&clients.suri()?)?;
+            let relayer = create_eth_gear_core_relayer(
+                checkpoint_light_client,
+                clients.beacon()?,
+                gear_api,
+                size_batch_multiplier,
+            );
+            metrics.set(&name, daemon_service_metrics(&name, &relayer));
+
+            relayer.run().await;
+
+            Ok(())
+        }
+
+        ServiceKind::QueueCleaner { delay } => {
+            relayer::queue_cleaner::queue_cleaner(clients.gear.clone(), clients.suri()?, delay)
+                .await
+        }
+
+        ServiceKind::KillSwitch(KillSwitchService {
+            from_eth_block,
+            observer_pk_path,
+            admin_pk_path,
+            relayer_http_url,
+            relayer_http_token,
+            relayer_http_timeout,
+            verification_endpoints,
+            verification_quorum,
+            alert_webhook_url,
+            alert_file,
+        }) => {
+            let mut kill_switch = create_kill_switch(
+                clients.gear_config.endpoint.clone(),
+                clients.gear.clone(),
+                clients.gear_config.max_reconnect_attempts,
+                &EthereumKillSwitchArgs {
+                    ethereum_args: daemon_ethereum_args(clients.ethereum()?),
+                    eth_observer_pk_path: observer_pk_path,
+                    eth_admin_pk_path: admin_pk_path,
+                },
+                &RelayerHttpArgs {
+                    url: relayer_http_url,
+                    access_token: relayer_http_token,
+                    timeout_secs: relayer_http_timeout.as_secs(),
+                },
+                KillSwitchVerificationArgs {
+                    endpoints: verification_endpoints,
+                    quorum: verification_quorum,
+                    alert_webhook_url,
+                    alert_file,
+                },
+                from_eth_block,
+            )
+            .await?;
+            metrics.set(&name, daemon_service_metrics(&name, &kill_switch));
+
+            kill_switch.run().await
+        }
+
+        ServiceKind::GearEthTokens(GearEthTokensService {
+            transfers,
+            storage_path,
+            governance_admin,
+            governance_pauser,
+            confirmations_merkle_root,
+            confirmations_status,
+            no_fee,
+            max_batch_size,
+            multicall_address,
+        }) => {
+            let mut eth_api = clients.eth_signer()?;
+            if let Some(address) = &multicall_address {
+                eth_api = eth_api.with_multicall_address(address)?;
+            }
+            let (governance_admin, governance_pauser) =
+                parse_governance(&governance_admin, &governance_pauser)?;
+
+            match transfers {
+                GearEthTransfers::All => {
+                    let relayer = gear_to_eth::all_token_transfers::Relayer::new(
+                        eth_api,
+                        clients.gear.clone(),
+                        confirmations_merkle_root,
+                        confirmations_status,
+                        max_batch_size,
+                        storage_path,
+                        governance_admin,
+                        governance_pauser,
+                    )
+                    .await
+                    .context("Failed to create relayer")?;
+                    metrics.set(&name, daemon_service_metrics(&name, &relayer));
+
+                    relayer.run().await
+                }
+
+                GearEthTransfers::Paid {
+                    bridging_payment_address,
+                    http,
+                    fee_market,
+                } => {
+                    let fee_market = fee_market.map(|config| config.build(eth_api.clone()));
+                    let excluded_from_fees =
+                        excluded_from_fees(&clients.gear.client(), no_fee).await?;
+                    let tcp_listener = TcpListener::bind(&http.address)?;
+                    let (sender, receiver) = mpsc::unbounded_channel();
+
+                    let relayer = gear_to_eth::paid_token_transfers::Relayer::new(
+                        eth_api,
+                        bridging_payment_address,
+                        clients.gear.clone(),
+                        confirmations_merkle_root,
+                        confirmations_status,
+                        max_batch_size,
+                        excluded_from_fees,
+                        fee_market,
+                        receiver,
+                        storage_path,
+                        governance_admin,
+                        governance_pauser,
+                    )
+                    .await
+                    .context("Failed to create relayer")?;
+
+                    let web_server = server::create(
+                        tcp_listener,
+                        web_server_tokens(http.token, http.tokens_file, http.audit_log)?,
+                        name.clone(),
+                        Some(sender),
+                        None,
+                        None,
+                        Some(TransactionQueue::GearToEth(relayer.tx_manager())),
+                    )
+                    .context("Failed to create web server")?;
+                    let handle_server = web_server.handle();
+                    task::spawn(web_server);
+                    metrics.set(&name, daemon_service_metrics(&name, &relayer));
+
+                    let result = relayer.run().await;
+                    stop_web_server(handle_server).await;
+                    result
+                }
+            }
+        }
+
+        ServiceKind::EthGearTokens(EthGearTokensService {
+            transfers,
+            vft_manager_address,
+            storage_path,
+            storage_database,
+            ethereum_blocks,
+        }) => {
+            let storage = create_eth_gear_storage(storage_path, storage_database)?;
+            let suri = clients.suri()?;
+            let eth_api = clients.eth_polling()?;
+            let beacon_client = clients.beacon()?;
+
+            let (historical_proxy_address, checkpoint_light_client_address) =
+                fetch_historical_proxy_and_checkpoints(
+                    clients.gear.clone(),
+                    vft_manager_address.0.into(),
+                    &suri,
+                )
+                .await
+                .context("Failed to fetch historical proxy")?;
+            let genesis_time = beacon_client
+                .get_genesis()
+                .await
+                .context("Failed to fetch chain genesis")?
+                .data
+                .genesis_time;
+
+            match transfers {
+                EthGearTransfers::All {
+                    erc20_manager_address,
+                } => {
+                    let relayer = eth_to_gear::all_token_transfers::Relayer::new(
+                        suri,
+                        eth_api,
+                        beacon_client,
+                        erc20_manager_address,
+                        checkpoint_light_client_address.into_bytes().into(),
+                        historical_proxy_address.into_bytes().into(),
+                        vft_manager_address,
+                        clients.gear.clone(),
+                        storage,
+                        genesis_time,
+                        ethereum_blocks,
+                    )
+                    .await
+                    .context("Failed to create relayer")?;
+                    metrics.set(&name, daemon_service_metrics(&name, &relayer));
+
+                    relayer.run().await
+                }
+
+                EthGearTransfers::Paid {
+                    bridging_payment_address,
+                    http,
+                } => {
+                    let tcp_listener = TcpListener::bind(&http.address)?;
+                    let (sender, receiver) = mpsc::unbounded_channel();
+
+                    let relayer = eth_to_gear::paid_token_transfers::Relayer::new(
+                        suri,
+                        eth_api,
+                        beacon_client,
+                        bridging_payment_address,
+                        checkpoint_light_client_address.into_bytes().into(),
+                        historical_proxy_address.into_bytes().into(),
+                        vft_manager_address,
+                        clients.gear.clone(),
+                        storage,
+                        genesis_time,
+                        ethereum_blocks,
+                        Some(receiver),
+                    )
+                    .await
+                    .context("Failed to create relayer")?;
+
+                    let web_server = server::create(
+                        tcp_listener,
+                        web_server_tokens(http.token, http.tokens_file, http.audit_log)?,
+                        name.clone(),
+                        None,
+                        None,
+                        Some(sender),
+                        Some(TransactionQueue::EthToGear(relayer.tx_manager())),
+                    )
+                    .context("Failed to create web server")?;
+                    let handle_server = web_server.handle();
+                    task::spawn(web_server);
+                    metrics.set(&name, daemon_service_metrics(&name, &relayer));
+
+                    let result = relayer.run().await;
+                    stop_web_server(handle_server).await;
+                    result
+                }
+            }
+        }
+    }
+}
+
+fn daemon_service_metrics(name: &str, service: &impl MeteredService) -> MetricsBuilder {
+    MetricsBuilder::new().register_labeled_service(service, [("service", name)])
+}
+
+fn daemon_ethereum_args(config: &DaemonEthereumConfig) -> EthereumArgs {
+    EthereumArgs {
+        connection: EthereumConnectionArgs {
+            ethereum_endpoint: config.endpoint.clone(),
+            fallback_endpoints: config.fallback_endpoints.clone(),
+            read_quorum: config.read_quorum,
+            max_retries: config.max_retries,
+            retry_interval_ms: config.retry_interval_ms,
+        },
+        tx: EthereumTxArgs {
+            max_fee_per_gas: config.max_fee_per_gas,
+            max_priority_fee_per_gas: config.max_priority_fee_per_gas,
+        },
+        mq_address: config.message_queue_address.clone().unwrap_or_default(),
+    }
+}
+
+struct RunningGearEthCoreRelayer {
+    id: String,
+    task: task::JoinHandle<AnyResult<()>>,
+    server_handle: ServerHandle,
+    metrics: Option<MetricsBuilder>,
+    /// Taken by the config reloader when the relayer is started from a config file.
+    live: Option<LiveRelayer>,
+}
+
+/// Starts relayers sharing one finality prover. Their collectors are labeled by relayer id.
+async fn start_shared_gear_eth_core_relayers(
+    mut relayers: Vec<EffectiveRelayerConfig>,
+) -> AnyResult<(Vec<RunningGearEthCoreRelayer>, MetricsBuilder)> {
+    // Start higher-priority relayers first; authority-set proving is serialized by
+    // SharedAuthoritySetSync so only one heavy proving job runs at a time.
+    relayers.sort_by(|a, b| b.priority.cmp(&a.priority));
+    let shared_authority_set_sync = SharedAuthoritySetSync::new();
+
+    let shared_prover = SharedFinalityProver::new();
+    let mut metrics = MetricsBuilder::new();
+    metrics.register_service_mut(&shared_prover);
+
+    let mut running = start_required_gear_eth_core_relayers(relayers, |mut relayer| {
+        relayer
+            .options
+            .shared_authority_set_sync
+            .replace(shared_authority_set_sync.clone());
+        start_gear_eth_core_relayer(relayer, None, Some(&shared_prover), true)
+    })
+    .await?;
+
+    for relayer in &mut running {
+        if let Some(relayer_metrics) = relayer.metrics.take() {
+            metrics.append(relayer_metrics);
+        }
+    }
+
+    Ok((running, metrics))
+}
+
+async fn start_required_gear_eth_core_relayers<I, F, Fut>(
+    relayers: impl IntoIterator<Item = I>,
+    mut start: F,
+) -> AnyResult<Vec<RunningGearEthCoreRelayer>>
+where
+    F: FnMut(I) -> Fut,
+    Fut: Future<Output = AnyResult<RunningGearEthCoreRelayer>>,
+{
+    let mut running = Vec::new();
+    for relayer in relayers {
+        match start(relayer).await {
+            Ok(relayer) => running.push(relayer),
+            Err(err) => {
+                stop_running_gear_eth_core_relayers(running).await;
+                return Err(err);
+            }
+        }
+    }
+    Ok(running)
+}
+
+async fn stop_running_gear_eth_core_relayers(running: Vec<RunningGearEthCoreRelayer>) {
+    let mut tasks = Vec::with_capacity(running.len());
+    for relayer in running {
+        stop_web_server(relayer.server_handle).await;
+        relayer.task.abort();
+        tasks.push(relayer.task);
+    }
+
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+async fn supervise_running_gear_eth_core_relayers(
+    running: Vec<RunningGearEthCoreRelayer>,
+) -> AnyResult<()> {
+    if running.is_empty() {
+        return Err(anyhow!("no merkle-root relayers started"));
+    }
+
+    let mut tasks = Vec::with_capacity(running.len());
+    let mut ids = Vec::with_capacity(running.len());
+    let mut server_handles = Vec::with_capacity(running.len());
+
+    for relayer in running {
+        ids.push(relayer.id);
+        server_handles.push(relayer.server_handle);
+        tasks.push(relayer.task);
+    }
+
+    let (result, index, remaining_tasks) = futures::future::select_all(tasks).await;
+    for server_handle in server_handles {
+        stop_web_server(server_handle).await;
+    }
+    for task in remaining_tasks {
+        task.abort();
+    }
+
+    let id = ids
+        .get(index)
+        .cloned()
+        .unwrap_or_else(|| "<unknown>".to_string());
+
+    match result {
+        Ok(Ok(())) => Err(anyhow!("merkle-root relayer {id} exited unexpectedly")),
+        Ok(Err(err)) => Err(err).with_context(|| format!("merkle-root relayer {id} failed")),
+        Err(err) => Err(anyhow!("merkle-root relayer {id} task failed: {err}")),
+    }
+}
+
+async fn wait_single_relayer(running: RunningGearEthCoreRelayer) -> AnyResult<()> {
+    let RunningGearEthCoreRelayer {
+        id,
+        task,
+        server_handle,
+        ..
+    } = running;
+    match task.await {
+        Ok(result) => result,
+        Err(err) => {
+            stop_web_server(server_handle).await;
+            Err(anyhow!("merkle-root relayer {id} task failed: {err}"))
+        }
+    }
+}
+
+async fn start_gear_eth_core_relayer(
+    mut config: EffectiveRelayerConfig,
+    prometheus_endpoint: Option<String>,
+    shared_prover: Option<&SharedFinalityProver>,
+    label_metrics: bool,
+) -> AnyResult<RunningGearEthCoreRelayer> {
+    let id = config.id.clone();
+    let api_provider = ApiProvider::new(
+        config.gear.endpoint.clone(),
+        config.gear.max_reconnect_attempts,
+    )
+    .await
+    .context("Failed to connect to Gear API")?;
+
+    let eth_api = create_eth_signer_client_from_config(&config.ethereum).await?;
+    let fee_caps = eth_api.fee_caps();
+    if let EffectiveProverConfig::Mock { latency } = config.prover {
+        ensure_mock_verifier(&eth_api)
+            .await
+            .with_context(|| format!("merkle-root relayer {id}: mock prover can't be used"))?;
+        log::warn!("Merkle root relayer {id}: final proofs are mocked with {latency:?} latency");
+        config.options.mock_prover = Some(latency);
+    }
+
+    let mut metrics = MetricsBuilder::new();
+
+    let metric_relayer_id = label_metrics.then_some(id.as_str());
+    let proof_storage = create_proof_storage_from_config(
+        &config.proof_storage,
+        &config.gear,
+        &mut metrics,
         metric_relayer_id,
     )
     .await?;
-    let storage = relayer::merkle_roots::storage::MerkleRootStorage::new(
-        proof_storage,
-        config.storage.block_storage.clone(),
-    );
+    let storage = match &config.storage.database {
+        Some(database) => {
+            let backend = DatabaseBackend::open(database, config.storage.block_storage.clone())
+                .await
+                .context("Failed to open merkle root database")?;
+            MerkleRootStorage::with_backend(
+                proof_storage,
+                config.storage.block_storage.clone(),
+                Box::new(backend),
+            )
+        }
+        None => MerkleRootStorage::new(proof_storage, config.storage.block_storage.clone()),
+    };
 
     let tcp_listener = TcpListener::bind(&config.http.address)?;
+    let prover_pool_listener = match &config.prover {
+        EffectiveProverConfig::Local | EffectiveProverConfig::Mock { .. } => None,
+        EffectiveProverConfig::Pool { address, .. } => Some(TcpListener::bind(address)?),
+    };
+
+    let tokens = web_server_tokens(
+        config.http.token.clone(),
+        config.http.tokens_file.clone(),
+        config.http.audit_log.clone(),
+    )?;
+    let (live_options, live_updates) = watch::channel(config.options.live());
+    config.options.live_updates = Some(live_updates);
 
     let (sender, receiver) = mpsc::unbounded_channel();
     let web_server = server::create(
         tcp_listener,
-        config.http.token.clone(),
+        tokens.clone(),
         format!("merkle-root relayer {}", config.id),
         None,
         Some(sender),
         None,
+        None,
     )
     .context("Failed to create web server")?;
     let handle_server = web_server.handle();
     let task_handle_server = handle_server.clone();
     tokio::spawn(web_server);
 
+    let prover_pool_server = match (&config.prover, prover_pool_listener) {
+        (
+            EffectiveProverConfig::Pool {
+                address,
+                token,
+                config: pool_config,
+                loopback_workers,
+            },
+            Some(listener),
+        ) => {
+            let pool = ProverPool::new(*pool_config);
+            let pool_server = prover_pool::create_server(listener, token.clone(), pool.clone())
+                .context("Failed to create prover pool server")?;
+            let handle_pool_server = pool_server.handle();
+            tokio::spawn(pool_server);
+
+            for index in 0..*loopback_workers {
+                ProverWorker::new(
+                    format!("{id}-loopback-{index}"),
+                    pool.clone(),
+                    api_provider.connection(),
+                    config.options.count_thread,
+                    config.options.gnark_data_path.clone(),
+                )
+                .spawn();
+            }
+
+            log::info!(
+                "Merkle root relayer {id}: prover pool listens on {address} with {loopback_workers} loopback worker(s)"
+            );
+            register_gear_eth_core_metrics(&mut metrics, metric_relayer_id, &pool);
+            config.options.prover_pool = Some(pool);
+
+            Some(handle_pool_server)
+        }
+        _ => None,
+    };
+
+    if let Some(leader_election) = &config.leader_election {
+        let election = create_leader_election(leader_election, &id);
+        register_gear_eth_core_metrics(&mut metrics, metric_relayer_id, &election);
+        config.options.leader = Some(election.run());
+    }
+
     let relayer = if let Some(shared_prover) = shared_prover {
         let prover = shared_prover.register(
             id.clone(),
@@ -919,6 +1518,8 @@ async fn start_gear_eth_core_relayer(
             config.options.genesis_config,
             config.options.count_thread,
             config.options.gnark_data_path.clone(),
+            config.options.prover_pool.clone(),
+            config.options.mock_prover,
         );
         merkle_roots::Relayer::new_with_prover_io(
             api_provider.connection(),
@@ -954,6 +1555,9 @@ async fn start_gear_eth_core_relayer(
     let task = tokio::spawn(async move {
         let result = relayer.run().await;
         stop_web_server(task_handle_server).await;
+        if let Some(handle) = prover_pool_server {
+            stop_web_server(handle).await;
+        }
         result
     });
 
@@ -962,9 +1566,44 @@ async fn start_gear_eth_core_relayer(
         task,
         server_handle: handle_server,
         metrics,
+        live: Some(LiveRelayer {
+            options: live_options,
+            tokens,
+            fee_caps,
+        }),
     })
 }
 
+fn create_leader_election(
+    config: &EffectiveLeaderElectionConfig,
+    relayer_id: &str,
+) -> LeaderElection {
+    let backend: Arc<dyn LockBackend> = match &config.lock {
+        EffectiveLockConfig::File { path } => Arc::new(FileLockBackend::new(path.clone())),
+        EffectiveLockConfig::ObjectStore { s3, key } => Arc::new(ObjectStoreLockBackend::new(
+            Arc::new(S3ObjectStore::new(s3.clone())),
+            key.clone(),
+        )),
+        EffectiveLockConfig::Database { url, name } => {
+            Arc::new(DatabaseLockBackend::new(url.clone(), name.clone()))
+        }
+    };
+    let holder = config
+        .holder_id
+        .clone()
+        .unwrap_or_else(|| format!("{relayer_id}-{}", uuid::Uuid::new_v4()));
+    log::info!("Merkle root relayer {relayer_id}: taking part in leader election as {holder}");
+
+    LeaderElection::new(
+        backend,
+        holder,
+        relayer_id.to_string(),
+        LeaderElectionConfig {
+            failover_window: config.failover_window,
+        },
+    )
+}
+
 fn register_gear_eth_core_metrics(
     metrics: &mut MetricsBuilder,
     relayer_id: Option<&str>,
@@ -987,6 +1626,20 @@ async fn stop_web_server(handle: ServerHandle) {
     }
 }
 
+fn web_server_tokens(
+    token: String,
+    tokens_file: Option<PathBuf>,
+    audit_log: Option<PathBuf>,
+) -> AnyResult<Arc<ApiTokens>> {
+    let tokens = Arc::new(
+        ApiTokens::load(token, tokens_file, audit_log)
+            .context("Failed to load web server tokens")?,
+    );
+    tokens.clone().watch(TOKENS_RELOAD_INTERVAL);
+
+    Ok(tokens)
+}
+
 async fn create_gclient_client(args: &GearSignerArgs) -> gclient::GearApi {
     let endpoint = args.connection.get_endpoint().expect("Invalid gear args");
     gclient::GearApi::builder()
@@ -1004,8 +1657,11 @@ async fn create_eth_signer_client(args: &EthereumSignerArgs) -> EthApi {
         mq_address,
     } = &args.ethereum_args;
 
-    EthApi::new_with_retries(
-        &connection.ethereum_endpoint,
+    let eth_api = EthApi::new_with_endpoints(
+        &ethereum_endpoints(
+            &connection.ethereum_endpoint,
+            &connection.fallback_endpoints,
+        ),
         mq_address,
         Some(&args.eth_fee_payer),
         connection.max_retries,
@@ -1015,11 +1671,45 @@ async fn create_eth_signer_client(args: &EthereumSignerArgs) -> EthApi {
     )
     .await
     .expect("Error while creating ethereum client")
+    .with_nonce_manager(args.nonce_file.as_deref())
+    .expect("Error while loading ethereum nonces");
+
+    with_read_quorum(eth_api, connection.read_quorum).expect("Invalid ethereum read quorum")
+}
+
+fn ethereum_endpoints(endpoint: &str, fallback_endpoints: &[String]) -> Vec<String> {
+    std::iter::once(endpoint.to_string())
+        .chain(fallback_endpoints.iter().cloned())
+        .collect()
+}
+
+fn with_read_quorum(eth_api: EthApi, read_quorum: Option<usize>) -> AnyResult<EthApi> {
+    match read_quorum {
+        Some(quorum) => Ok(eth_api.with_read_quorum(quorum)?),
+        None => Ok(eth_api),
+    }
+}
+
+/// Mock proofs are accepted only by `VerifierMock.sol`, so it's checked that the verifier
+/// `MessageQueue` uses accepts one.
+async fn ensure_mock_verifier(eth_api: &EthApi) -> AnyResult<()> {
+    let proof = prover_interface::FinalProof::mock(0, [0; 32]);
+    let accepted = eth_api
+        .verifier_accepts_proof(&proof.proof, proof.block_number, proof.merkle_root)
+        .await
+        .context("Failed to query verifier")?;
+    if !accepted {
+        return Err(anyhow!(
+            "verifier of the message queue rejects mock proofs, it isn't a VerifierMock"
+        ));
+    }
+
+    Ok(())
 }
 
 async fn create_eth_signer_client_from_config(args: &EffectiveEthereumConfig) -> AnyResult<EthApi> {
-    EthApi::new_with_retries(
-        &args.endpoint,
+    let eth_api = EthApi::new_with_endpoints(
+        &ethereum_endpoints(&args.endpoint, &args.fallback_endpoints),
         &args.message_queue_address,
         Some(&args.fee_payer),
         args.max_retries,
@@ -1028,7 +1718,11 @@ async fn create_eth_signer_client_from_config(args: &EffectiveEthereumConfig) ->
         args.max_priority_fee_per_gas,
     )
     .await
-    .context("Error while creating ethereum client")
+    .context("Error while creating ethereum client")?
+    .with_nonce_manager(args.nonce_file.as_deref())
+    .context("Error while loading ethereum nonces")?;
+
+    with_read_quorum(eth_api, args.read_quorum)
 }
 
 async fn create_eth_killswitch_client(
@@ -1057,6 +1751,7 @@ async fn create_eth_killswitch_client(
     let observer_api = create_eth_signer_client(&EthereumSignerArgs {
         ethereum_args: args.ethereum_args.clone(),
         eth_fee_payer: observer_pk_str,
+        nonce_file: None,
     })
     .await;
 
@@ -1071,6 +1766,7 @@ async fn create_eth_killswitch_client(
             create_eth_signer_client(&EthereumSignerArgs {
                 ethereum_args: args.ethereum_args.clone(),
                 eth_fee_payer: admin_pk_str,
+                nonce_file: None,
             })
             .await,
         )
@@ -1081,16 +1777,187 @@ async fn create_eth_killswitch_client(
     Ok((observer_api, maybe_admin_api))
 }
 
+async fn create_kill_switch(
+    gear_endpoint: String,
+    gear_connection: ApiProviderConnection,
+    max_reconnect_attempts: u8,
+    ethereum_args: &EthereumKillSwitchArgs,
+    relayer_http_args: &RelayerHttpArgs,
+    verification_args: KillSwitchVerificationArgs,
+    from_eth_block: Option<u64>,
+) -> AnyResult<KillSwitchRelayer> {
+    use reqwest::header;
+
+    let (eth_observer_api, eth_admin_api) = create_eth_killswitch_client(ethereum_args)
+        .await
+        .context("Failed to create Ethereum client")?;
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(relayer_http_args.timeout_secs))
+        .default_headers({
+            let mut headers = header::HeaderMap::new();
+            headers.insert(
+                "X-Token",
+                header::HeaderValue::from_str(&relayer_http_args.access_token)
+                    .context("Invalid token")?,
+            );
+            headers
+        })
+        .build()
+        .context("Failed to create HTTP client")?;
+
+    let mut sources = vec![RootSource::new(gear_endpoint, gear_connection)];
+    for endpoint in &verification_args.endpoints {
+        let provider = ApiProvider::new(endpoint.clone(), max_reconnect_attempts)
+            .await
+            .with_context(|| format!("Failed to connect to Gear API at {endpoint}"))?;
+        sources.push(RootSource::new(endpoint.clone(), provider.connection()));
+        provider.spawn();
+    }
+    let quorum = verification_args.quorum.unwrap_or(sources.len());
+    let verifier = RootVerifier::new(sources, quorum)?;
+
+    let mut notifiers = Notifiers::default();
+    if let Some(url) = verification_args.alert_webhook_url {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create HTTP client")?;
+        notifiers.push(WebhookNotifier::new(client, url));
+    }
+    if let Some(path) = verification_args.alert_file {
+        notifiers.push(FileNotifier::new(path));
+    }
+
+    Ok(KillSwitchRelayer::new(
+        verifier,
+        notifiers,
+        eth_observer_api,
+        eth_admin_api,
+        http_client,
+        from_eth_block,
+        relayer_http_args.url.clone(),
+    )
+    .await)
+}
+
+fn create_eth_gear_core_relayer(
+    program_id: H256,
+    beacon_client: BeaconClient,
+    gear_api: gclient::GearApi,
+    size_batch_multiplier: u64,
+) -> ethereum_checkpoints::Relayer {
+    let multiplier = if size_batch_multiplier > 0 {
+        size_batch_multiplier
+    } else {
+        1
+    };
+
+    ethereum_checkpoints::Relayer::new(
+        program_id,
+        beacon_client,
+        gear_api,
+        multiplier.saturating_mul(SLOTS_PER_EPOCH),
+    )
+}
+
+fn create_eth_gear_storage(
+    storage_path: String,
+    storage_database: Option<PathBuf>,
+) -> AnyResult<Arc<dyn eth_to_gear::storage::Storage>> {
+    Ok(match storage_database {
+        Some(database) => Arc::new(
+            eth_to_gear::storage::DatabaseStorage::open(&database, storage_path.into())
+                .context("Failed to open transaction database")?,
+        ),
+        None => Arc::new(eth_to_gear::storage::JSONStorage::new(storage_path)),
+    })
+}
+
+fn parse_governance(admin: &str, pauser: &str) -> AnyResult<(ActorId, ActorId)> {
+    let governance_admin: [u8; 32] = AccountId32::from_str(admin)
+        .map_err(|e| anyhow!("Failed to parse governance admin address: {e}"))?
+        .into();
+    let governance_pauser: [u8; 32] = AccountId32::from_str(pauser)
+        .map_err(|e| anyhow!("Failed to parse governance pauser address: {e}"))?
+        .into();
+
+    Ok((
+        ActorId::from(governance_admin),
+        ActorId::from(governance_pauser),
+    ))
+}
+
+async fn excluded_from_fees(
+    api: &gear_rpc_client::GearApi,
+    no_fee: Option<FeePayers>,
+) -> AnyResult<HashSet<AccountId32>> {
+    let mut excluded_from_fees = HashSet::new();
+    match no_fee {
+        None => {
+            log::warn!("No free from charge accounts listed, using default: bridgeAdmin and bridgePauser from chain constants");
+            match api.bridge_admin().await {
+                Ok(admin) => {
+                    log::info!("Bridge admin: {admin}");
+                    let admin: &[u8] = admin.as_ref();
+                    excluded_from_fees.insert(AccountId32::try_from(admin).unwrap());
+                }
+                Err(e) => {
+                    log::error!("Failed to get bridge admin: {e}");
+                }
+            };
+
+            match api.bridge_pauser().await {
+                Ok(pauser) => {
+                    log::info!("Bridge pauser: {pauser}");
+                    let pauser: &[u8] = pauser.as_ref();
+                    excluded_from_fees.insert(AccountId32::try_from(pauser).unwrap());
+                }
+                Err(e) => {
+                    log::error!("Failed to get bridge pauser: {e}");
+                }
+            };
+
+            if excluded_from_fees.is_empty() {
+                return Err(anyhow!("Exiting"));
+            }
+        }
+
+        Some(FeePayers::All) => {
+            log::info!("All accounts haave to pay fees");
+        }
+
+        Some(FeePayers::ExcludedIds(ids)) => {
+            for id in ids {
+                let account_id = AccountId32::from_str(id.as_str())
+                    .map_err(|e| anyhow!(r#"Failed to decode address "{id}": {e:?}"#))?;
+
+                log::debug!("Account {account_id} is excluded from paying fees");
+                excluded_from_fees.insert(account_id);
+            }
+        }
+    }
+
+    Ok(excluded_from_fees)
+}
+
 async fn create_eth_client(args: &EthereumArgs) -> EthApi {
-    EthApi::new(
-        &args.connection.ethereum_endpoint,
+    let connection = &args.connection;
+    let eth_api = EthApi::new_with_endpoints(
+        &ethereum_endpoints(
+            &connection.ethereum_endpoint,
+            &connection.fallback_endpoints,
+        ),
         &args.mq_address,
         None,
+        None,
+        None,
         args.tx.max_fee_per_gas,
         args.tx.max_priority_fee_per_gas,
     )
     .await
-    .expect("Error while creating ethereum client")
+    .expect("Error while creating ethereum client");
+
+    with_read_quorum(eth_api, connection.read_quorum).expect("Invalid ethereum read quorum")
 }
 
 async fn create_beacon_client(args: &BeaconRpcArgs) -> BeaconClient {
@@ -1129,11 +1996,128 @@ async fn create_proof_storage_from_config(
             log::warn!("Fee payer not present, falling back to FileSystemProofStorage");
             Arc::new(FileSystemProofStorage::new(path.clone()).await)
         }
+        EffectiveProofStorageConfig::ObjectStore {
+            s3,
+            prefix,
+            cache_dir,
+        } => {
+            let store = Arc::new(S3ObjectStore::new(s3.clone()));
+            let proof_storage =
+                ObjectStoreProofStorage::new(store, prefix.clone(), cache_dir.clone())
+                    .await
+                    .context("Failed to initialize object store proof storage")?;
+
+            Arc::new(proof_storage)
+        }
     };
 
     Ok(proof_storage)
 }
 
+fn select_relayer(config: &Path, relayer: Option<String>) -> AnyResult<EffectiveRelayerConfig> {
+    let EffectiveConfig { relayers, .. } = EffectiveConfig::from_path(config)?;
+    match relayer {
+        Some(id) => relayers
+            .into_iter()
+            .find(|relayer| relayer.id == id)
+            .ok_or_else(|| anyhow!("Relayer {id} not found in the config")),
+        None if relayers.len() == 1 => Ok(relayers
+            .into_iter()
+            .next()
+            .expect("relayers length is checked immediately above")),
+        None => Err(anyhow!(
+            "Config contains several relayers, specify one with --relayer"
+        )),
+    }
+}
+
+async fn run_proof_storage_tool(args: ProofStorageToolArgs, keys: &KeyResolver) -> AnyResult<()> {
+    let mut relayer = select_relayer(&args.config, args.relayer)?;
+    relayer.proof_storage.resolve_keys(keys)?;
+    let genesis_config = relayer.options.genesis_config;
+
+    let archive = match &args.command {
+        ProofStorageCommands::Verify {
+            archive: Some(path),
+        }
+        | ProofStorageCommands::Import { input: path } => {
+            let archive = ProofArchive::read(path)?;
+            archive.check_genesis_config(&genesis_config)?;
+            Some(archive)
+        }
+        _ => None,
+    };
+
+    let proof_storage = create_proof_storage_from_config(
+        &relayer.proof_storage,
+        &relayer.gear,
+        &mut MetricsBuilder::new(),
+        None,
+    )
+    .await?;
+
+    let archive = match archive {
+        Some(archive) => archive,
+        None => ProofArchive::export(proof_storage.as_ref(), genesis_config)
+            .await
+            .context("Failed to read proof chain from storage")?,
+    };
+
+    log::info!(
+        "Verifying {} proofs up to authority set id #{}",
+        archive.proofs.len(),
+        archive.latest_authority_set_id().unwrap_or_default()
+    );
+
+    match args.command {
+        ProofStorageCommands::Export { output } => {
+            let archive = task::spawn_blocking(move || archive.verify().map(|_| archive)).await??;
+            archive.write(&output)?;
+            log::info!("Proof chain exported to {}", output.display());
+        }
+        ProofStorageCommands::Import { .. } => {
+            // Archive is verified before anything is written to the storage.
+            let archive = task::spawn_blocking(move || archive.verify().map(|_| archive)).await??;
+            let imported = archive.import_verified(proof_storage.as_ref()).await?;
+            log::info!("Imported {imported} proofs");
+        }
+        ProofStorageCommands::Verify { .. } => {
+            task::spawn_blocking(move || archive.verify()).await??;
+            log::info!("All proofs are valid");
+        }
+    }
+
+    Ok(())
+}
+
+fn create_keystore(args: CreateKeystoreArgs, keys: &KeyResolver) -> AnyResult<()> {
+    if args.output.exists() {
+        return Err(anyhow!("{} already exists", args.output.display()));
+    }
+
+    let password = keys.password()?;
+    let mut secret = Zeroizing::new(String::new());
+    std::io::stdin()
+        .read_to_string(&mut secret)
+        .context("Failed to read the key from stdin")?;
+    let cost = if args.light_kdf {
+        ScryptCost::LIGHT
+    } else {
+        ScryptCost::STANDARD
+    };
+
+    let keystore = keystore::encrypt(args.kind, secret.trim(), &password, cost)?;
+    fs::write(&args.output, keystore)
+        .with_context(|| format!("Failed to write {}", args.output.display()))?;
+    log::info!(
+        "Keystore written to {}, use it as keystore:{}",
+        args.output.display(),
+        args.output.display()
+    );
+
+    Ok(())
+}
+
 async fn fetch_merkle_roots(args: FetchMerkleRootsArgs) -> AnyResult<()> {
     let eth_api = create_eth_client(&args.ethereum_args).await;
     let block_finalized = eth_api.finalized_block_number().await?;
@@ -1204,6 +2188,207 @@ async fn fetch_historical_proxy_and_checkpoints(
     Ok((historical_proxy_address, checkpoints_address))
 }
 
+async fn failed_transactions(args: FailedTransactionsArgs) -> AnyResult<()> {
+    let FailedTransactionsArgs {
+        relayer_http_args,
+        command,
+    } = args;
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(relayer_http_args.timeout_secs))
+        .build()
+        .context("Failed to create HTTP client")?;
+    let url = relayer_http_args.url.trim_end_matches('/');
+    let request = match &command {
+        FailedTransactionsCommands::List => client.get(format!("{url}/failed_transactions")),
+        FailedTransactionsCommands::Retry { uuid, stage } => {
+            let request = client.post(format!("{url}/failed_transactions/{uuid}/retry"));
+            match stage {
+                Some(stage) => request.query(&[("stage", stage)]),
+                None => request,
+            }
+        }
+        FailedTransactionsCommands::Discard { uuid } => {
+            client.post(format!("{url}/failed_transactions/{uuid}/discard"))
+        }
+    };
+
+    let response = request
+        .header("X-Token", &relayer_http_args.access_token)
+        .send()
+        .await
+        .context("Failed to send request to the relayer")?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .context("Failed to read relayer response")?;
+    if !status.is_success() {
+        return Err(anyhow!("Relayer responded with {status}: {body}"));
+    }
+
+    match command {
+        FailedTransactionsCommands::Discard { uuid } => println!("Discarded transaction {uuid}"),
+        _ => {
+            let body: serde_json::Value =
+                serde_json::from_str(&body).context("Failed to decode relayer response")?;
+            println!("{}", serde_json::to_string_pretty(&body)?);
+        }
+    }
+
+    Ok(())
+}
+
+async fn prover_bench(args: ProverBenchArgs) -> AnyResult<()> {
+    match args.command {
+        ProverBenchCommands::Record {
+            gear_args,
+            block_number,
+            output,
+        } => {
+            let gear_api = gear_rpc_client::GearApi::new(
+                &gear_args.get_endpoint()?,
+                gear_args.max_reconnect_attempts,
+            )
+            .await?;
+
+            let block = match block_number {
+                Some(block_number) => gear_api
+                    .block_number_to_hash(block_number)
+                    .await
+                    .context("Unable to determine hash of block with merkle root")?,
+                None => gear_api.latest_finalized_block().await?,
+            };
+
+            let fixture = ProverFixture::record(&gear_api, block).await?;
+            fixture.write(&output)?;
+            log::info!(
+                "Fixture for block #{} with genesis authority set #{} written to {}",
+                fixture.block_number,
+                fixture.genesis_authority_set_id,
+                output.display()
+            );
+        }
+        ProverBenchCommands::Run {
+            fixture,
+            mut thread_counts,
+            gnark_data_path,
+            output,
+        } => {
+            check_rust_min_stack()?;
+
+            let fixture = ProverFixture::read(&fixture)?;
+            if thread_counts.is_empty() {
+                thread_counts.push(DEFAULT_COUNT_THREADS);
+            }
+
+            let report = task::spawn_blocking(move || {
+                prover_bench::run(&fixture, &thread_counts, gnark_data_path.as_deref())
+            })
+            .await??;
+
+            let report = serde_json::to_string_pretty(&report)?;
+            match output {
+                Some(output) => {
+                    fs::write(&output, report)
+                        .with_context(|| format!("Failed to write report {}", output.display()))?;
+                    log::info!("Report written to {}", output.display());
+                }
+                None => println!("{report}"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn replay_submission_policy(args: ReplaySubmissionPolicyArgs) -> AnyResult<()> {
+    let relayer = select_relayer(&args.config, args.relayer)?;
+
+    // A running relayer keeps the database locked. Opening it fails then, so the operator
+    // has to point `storage.database` of the config at a copy of it.
+    let backend: Box<dyn StateBackend> = match &relayer.storage.database {
+        Some(database) => Box::new(
+            DatabaseBackend::open(database, relayer.storage.block_storage.clone())
+                .await
+                .context(
+                    "Failed to open merkle root database. If the relayer is running, point \
+                     storage.database at a copy of the database",
+                )?,
+        ),
+        None => Box::new(JsonSnapshotBackend::new(
+            relayer.storage.block_storage.clone(),
+        )),
+    };
+    let state = backend.load().await?.unwrap_or_default();
+    let mut roots: Vec<_> = state
+        .roots
+        .values()
+        .map(|root| ReplayedRoot {
+            block_number: root.block_number,
+            nonces: root.message_nonces.len(),
+            priority: root.priority,
+        })
+        .collect();
+    roots.sort_by_key(|root| root.block_number);
+
+    let base_fees = match &args.base_fees {
+        Some(path) => {
+            let file = File::open(path)
+                .with_context(|| format!("Failed to open base fees {}", path.display()))?;
+            let mut samples: Vec<BaseFeeSample> = serde_json::from_reader(file)
+                .with_context(|| format!("Failed to decode base fees {}", path.display()))?;
+            samples.sort_by_key(|sample| sample.block_number);
+            samples
+        }
+        None => vec![BaseFeeSample {
+            block_number: 0,
+            base_fee: args.base_fee_gwei as u128 * 1_000_000_000,
+        }],
+    };
+
+    let (Some(first), Some(last)) = (roots.first(), roots.last()) else {
+        println!("No merkle roots in storage of relayer {}", relayer.id);
+        return Ok(());
+    };
+    println!(
+        "Replaying {} merkle roots of relayer {} from block #{} to #{}",
+        roots.len(),
+        relayer.id,
+        first.block_number,
+        last.block_number
+    );
+
+    let spike_config = relayer.options.spike_config;
+    let mut policies = vec![("spike", SubmissionPolicy::new(spike_config, None))];
+    match relayer.options.submission_policy {
+        Some(cost) => policies.push((
+            "cost-aware",
+            SubmissionPolicy::new(spike_config, Some(cost)),
+        )),
+        None => println!("No submission_policy is configured, replaying spike policy only"),
+    }
+
+    println!(
+        "{:<12} {:>12} {:>8} {:>10} {:>14} {:>12}",
+        "policy", "submissions", "forced", "deferrals", "spent (ETH)", "max delay"
+    );
+    for (name, policy) in policies {
+        let report = policy::replay(policy, &roots, &base_fees, relayer.options.check_interval);
+        println!(
+            "{:<12} {:>12} {:>8} {:>10} {:>14.6} {:>12}",
+            name,
+            report.submissions,
+            report.forced_submissions,
+            report.deferrals,
+            report.spent as f64 / 1e18,
+            humantime::format_duration(report.max_delay).to_string(),
+        );
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1327,6 +2512,7 @@ mod tests {
                 task: tokio::spawn(async move { result }),
                 server_handle,
                 metrics: None,
+                live: None,
             },
             addr,
         )
@@ -1351,6 +2537,7 @@ mod tests {
                 task: tokio::spawn(async { futures::future::pending::<AnyResult<()>>().await }),
                 server_handle,
                 metrics: None,
+                live: None,
             },
             addr,
         )
@@ -0,0 +1,135 @@
+//! Replacement of stuck merkle root submissions with higher EIP-1559 fees.
+
+use ethereum_client::FeeCaps;
+use std::time::Duration;
+
+/// Nodes reject replacements which don't raise both fee caps by at least 10%.
+pub const MIN_BUMP_PERCENT: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeBumpConfig {
+    /// Transaction is replaced if it isn't included in a block for this long.
+    pub stuck_timeout: Duration,
+    /// Percentage both fee caps are raised by on every replacement.
+    pub bump_percent: u32,
+    /// Ceiling of `max_fee_per_gas`, in wei.
+    pub max_fee_per_gas: u128,
+    /// Ceiling of `max_priority_fee_per_gas`, in wei.
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl Default for FeeBumpConfig {
+    fn default() -> Self {
+        Self {
+            stuck_timeout: Duration::from_secs(3 * 60),
+            bump_percent: 20,
+            max_fee_per_gas: 200_000_000_000,
+            max_priority_fee_per_gas: 10_000_000_000,
+        }
+    }
+}
+
+impl FeeBumpConfig {
+    /// Fee caps of the original transaction: the network estimate limited by the ceilings.
+    pub fn initial(&self, estimate: FeeCaps) -> FeeCaps {
+        self.limit(estimate)
+    }
+
+    /// Fee caps of the next replacement. Returns `None` if the ceilings don't allow
+    /// raising fees enough for the replacement to be accepted.
+    pub fn bump(&self, current: FeeCaps, estimate: Option<FeeCaps>) -> Option<FeeCaps> {
+        let raise = |value: u128, percent: u32| value + (value * percent as u128).div_ceil(100);
+
+        let mut next = FeeCaps {
+            max_fee_per_gas: raise(current.max_fee_per_gas, self.bump_percent),
+            max_priority_fee_per_gas: raise(current.max_priority_fee_per_gas, self.bump_percent),
+        };
+        if let Some(estimate) = estimate {
+            next.max_fee_per_gas = next.max_fee_per_gas.max(estimate.max_fee_per_gas);
+            next.max_priority_fee_per_gas = next
+                .max_priority_fee_per_gas
+                .max(estimate.max_priority_fee_per_gas);
+        }
+        let next = self.limit(next);
+
+        let accepted = next.max_fee_per_gas >= raise(current.max_fee_per_gas, MIN_BUMP_PERCENT)
+            && next.max_priority_fee_per_gas
+                >= raise(current.max_priority_fee_per_gas, MIN_BUMP_PERCENT);
+
+        accepted.then_some(next)
+    }
+
+    fn limit(&self, fees: FeeCaps) -> FeeCaps {
+        let max_fee_per_gas = fees.max_fee_per_gas.min(self.max_fee_per_gas);
+
+        FeeCaps {
+            max_fee_per_gas,
+            max_priority_fee_per_gas: fees
+                .max_priority_fee_per_gas
+                .min(self.max_priority_fee_per_gas)
+                .min(max_fee_per_gas),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn fees(max_fee_per_gas: u128, max_priority_fee_per_gas: u128) -> FeeCaps {
+        FeeCaps {
+            max_fee_per_gas: max_fee_per_gas * GWEI,
+            max_priority_fee_per_gas: max_priority_fee_per_gas * GWEI,
+        }
+    }
+
+    fn config() -> FeeBumpConfig {
+        FeeBumpConfig {
+            bump_percent: 20,
+            max_fee_per_gas: 100 * GWEI,
+            max_priority_fee_per_gas: 5 * GWEI,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn initial_fees_are_limited_by_ceilings() {
+        assert_eq!(config().initial(fees(30, 2)), fees(30, 2));
+        assert_eq!(config().initial(fees(300, 20)), fees(100, 5));
+    }
+
+    #[test]
+    fn bumps_by_percent_or_to_estimate() {
+        assert_eq!(
+            config().bump(fees(30, 2), None),
+            Some(FeeCaps {
+                max_fee_per_gas: 36 * GWEI,
+                max_priority_fee_per_gas: 2_400_000_000,
+            })
+        );
+        assert_eq!(
+            config().bump(fees(30, 2), Some(fees(50, 1))),
+            Some(FeeCaps {
+                max_fee_per_gas: 50 * GWEI,
+                max_priority_fee_per_gas: 2_400_000_000,
+            })
+        );
+    }
+
+    #[test]
+    fn stops_at_ceiling() {
+        // Capped at the ceiling, still a valid replacement.
+        assert_eq!(
+            config().bump(fees(90, 4), None),
+            Some(FeeCaps {
+                max_fee_per_gas: 100 * GWEI,
+                max_priority_fee_per_gas: 4_800_000_000,
+            })
+        );
+        // Ceiling doesn't allow raising fees by 10%.
+        assert_eq!(config().bump(fees(95, 4), None), None);
+        assert_eq!(config().bump(fees(100, 5), None), None);
+    }
+}
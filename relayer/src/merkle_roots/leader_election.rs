@@ -0,0 +1,638 @@
+//! Active-passive leader election for replicated gear-eth-core relayers.
+//!
+//! Every replica periodically tries to acquire or extend a lease in a shared [`LockBackend`].
+//! The replica holding the lease is the leader and is the only one submitting merkle roots
+//! to Ethereum. Followers keep running the rest of the pipeline so they can take over as soon
+//! as the leader stops renewing its lease, which happens at most `failover_window` after the
+//! last renewal.
+//!
+//! Leases are compared against the wall clock, so clocks of the replicas must be synchronized
+//! with a precision much better than the failover window.
+
+use crate::proof_storage::{ObjectStore, PutCondition, PutOutcome};
+use anyhow::Context;
+use prometheus::IntGauge;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    os::fd::AsRawFd,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{watch, Mutex};
+use tokio_postgres::{types::ToSql, Client, NoTls};
+use utils_prometheus::{impl_metered_service, MeteredService};
+use zeroize::Zeroizing;
+
+/// Lease stored in a [`LockBackend`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Lease {
+    holder: String,
+    /// Unix timestamp in milliseconds.
+    expires_at: u64,
+}
+
+impl Lease {
+    /// Returns the lease `holder` should write, or `None` if the lease is owned by someone else.
+    fn next(current: Option<&Lease>, holder: &str, ttl: Duration, now: u64) -> Option<Lease> {
+        match current {
+            Some(lease) if lease.holder != holder && lease.expires_at > now => None,
+            _ => Some(Lease {
+                holder: holder.to_string(),
+                expires_at: now + ttl.as_millis() as u64,
+            }),
+        }
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[async_trait::async_trait]
+pub trait LockBackend: Send + Sync {
+    /// Acquires the lease for `holder` or extends it if `holder` already owns it.
+    /// Returns `false` if the lease is owned by someone else.
+    async fn try_acquire(&self, holder: &str, ttl: Duration) -> anyhow::Result<bool>;
+
+    /// Releases the lease if it's owned by `holder`.
+    async fn release(&self, holder: &str) -> anyhow::Result<()>;
+}
+
+/// Lease kept in a file guarded by an exclusive `flock`. Suitable for replicas running
+/// on the same host or sharing a filesystem with working advisory locks.
+pub struct FileLockBackend {
+    path: PathBuf,
+}
+
+impl FileLockBackend {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn update(
+        path: PathBuf,
+        f: impl FnOnce(Option<Lease>) -> Option<Lease>,
+    ) -> anyhow::Result<bool> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open lease file {}", path.display()))?;
+        let _lock = FileLock::exclusive(&file)?;
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let current = match contents.trim() {
+            "" => None,
+            contents => Some(
+                serde_json::from_str(contents)
+                    .with_context(|| format!("Failed to decode lease file {}", path.display()))?,
+            ),
+        };
+
+        let Some(lease) = f(current) else {
+            return Ok(false);
+        };
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&serde_json::to_vec(&lease)?)?;
+        file.sync_all()?;
+
+        Ok(true)
+    }
+}
+
+struct FileLock<'a>(&'a File);
+
+impl<'a> FileLock<'a> {
+    fn exclusive(file: &'a File) -> anyhow::Result<Self> {
+        // SAFETY: the descriptor stays valid while `file` is borrowed.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to lock lease file");
+        }
+
+        Ok(Self(file))
+    }
+}
+
+impl Drop for FileLock<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the descriptor stays valid while the file is borrowed.
+        unsafe {
+            libc::flock(self.0.as_raw_fd(), libc::LOCK_UN);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl LockBackend for FileLockBackend {
+    async fn try_acquire(&self, holder: &str, ttl: Duration) -> anyhow::Result<bool> {
+        let path = self.path.clone();
+        let holder = holder.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            Self::update(path, |current| {
+                Lease::next(current.as_ref(), &holder, ttl, unix_millis())
+            })
+        })
+        .await?
+    }
+
+    async fn release(&self, holder: &str) -> anyhow::Result<()> {
+        let path = self.path.clone();
+        let holder = holder.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            Self::update(path, |current| {
+                current
+                    .filter(|lease| lease.holder == holder)
+                    .map(|lease| Lease {
+                        expires_at: 0,
+                        ..lease
+                    })
+            })
+        })
+        .await??;
+
+        Ok(())
+    }
+}
+
+/// Lease kept in a shared object store and updated with conditional writes, so any
+/// number of replicas on different hosts can compete for it.
+pub struct ObjectStoreLockBackend {
+    store: Arc<dyn ObjectStore>,
+    key: String,
+}
+
+impl ObjectStoreLockBackend {
+    pub fn new(store: Arc<dyn ObjectStore>, key: String) -> Self {
+        Self { store, key }
+    }
+
+    async fn update(
+        &self,
+        f: impl FnOnce(Option<&Lease>) -> Option<Lease>,
+    ) -> anyhow::Result<bool> {
+        let (current, condition) = match self.store.get(&self.key).await? {
+            Some(object) => (
+                Some(
+                    serde_json::from_slice::<Lease>(&object.bytes)
+                        .with_context(|| format!("Failed to decode lease {}", self.key))?,
+                ),
+                PutCondition::IfMatch(object.etag),
+            ),
+            None => (None, PutCondition::IfAbsent),
+        };
+
+        let Some(lease) = f(current.as_ref()) else {
+            return Ok(false);
+        };
+
+        match self
+            .store
+            .put(&self.key, serde_json::to_vec(&lease)?, condition)
+            .await?
+        {
+            PutOutcome::Written(_) => Ok(true),
+            // Another replica updated the lease concurrently.
+            PutOutcome::PreconditionFailed => Ok(false),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl LockBackend for ObjectStoreLockBackend {
+    async fn try_acquire(&self, holder: &str, ttl: Duration) -> anyhow::Result<bool> {
+        self.update(|current| Lease::next(current, holder, ttl, unix_millis()))
+            .await
+    }
+
+    async fn release(&self, holder: &str) -> anyhow::Result<()> {
+        self.update(|current| {
+            current
+                .filter(|lease| lease.holder == holder)
+                .map(|lease| Lease {
+                    holder: lease.holder.clone(),
+                    expires_at: 0,
+                })
+        })
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Lease kept in a row of a shared PostgreSQL table and updated with conditional upserts,
+/// so any number of replicas on different hosts can compete for it. The table is created
+/// on the first connection. Connections aren't encrypted.
+pub struct DatabaseLockBackend {
+    url: Zeroizing<String>,
+    name: String,
+    client: Mutex<Option<Client>>,
+}
+
+impl DatabaseLockBackend {
+    pub fn new(url: Zeroizing<String>, name: String) -> Self {
+        Self {
+            url,
+            name,
+            client: Mutex::new(None),
+        }
+    }
+
+    /// Runs `statement` and returns the count of modified rows. Reconnects if the
+    /// connection was lost.
+    async fn execute(
+        &self,
+        statement: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> anyhow::Result<u64> {
+        let mut client = self.client.lock().await;
+        if client.as_ref().is_none_or(Client::is_closed) {
+            *client = Some(self.connect().await?);
+        }
+
+        let client = client.as_ref().expect("Connected above");
+        Ok(client.execute(statement, params).await?)
+    }
+
+    async fn connect(&self) -> anyhow::Result<Client> {
+        let (client, connection) = tokio_postgres::connect(&self.url, NoTls)
+            .await
+            .context("Failed to connect to the lease database")?;
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                log::warn!("Lease database connection is closed: {err}");
+            }
+        });
+
+        client
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS relayer_leader_leases (
+                    name TEXT PRIMARY KEY,
+                    holder TEXT NOT NULL,
+                    expires_at BIGINT NOT NULL
+                )",
+            )
+            .await
+            .context("Failed to create the lease table")?;
+
+        Ok(client)
+    }
+}
+
+#[async_trait::async_trait]
+impl LockBackend for DatabaseLockBackend {
+    async fn try_acquire(&self, holder: &str, ttl: Duration) -> anyhow::Result<bool> {
+        let now = unix_millis();
+        let expires_at = (now + ttl.as_millis() as u64) as i64;
+
+        // Same rule as `Lease::next`: the row is taken over only by its holder or once
+        // the lease has expired.
+        let updated = self
+            .execute(
+                "INSERT INTO relayer_leader_leases (name, holder, expires_at) VALUES ($1, $2, $3)
+                ON CONFLICT (name) DO UPDATE
+                SET holder = EXCLUDED.holder, expires_at = EXCLUDED.expires_at
+                WHERE relayer_leader_leases.holder = EXCLUDED.holder
+                    OR relayer_leader_leases.expires_at <= $4",
+                &[&self.name, &holder, &expires_at, &(now as i64)],
+            )
+            .await?;
+
+        Ok(updated == 1)
+    }
+
+    async fn release(&self, holder: &str) -> anyhow::Result<()> {
+        self.execute(
+            "UPDATE relayer_leader_leases SET expires_at = 0 WHERE name = $1 AND holder = $2",
+            &[&self.name, &holder],
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LeaderElectionConfig {
+    /// Lease duration. A follower takes over at most this long after the leader stops
+    /// renewing the lease.
+    pub failover_window: Duration,
+}
+
+impl LeaderElectionConfig {
+    fn renew_interval(&self) -> Duration {
+        self.failover_window / 3
+    }
+}
+
+/// Leadership state published by [`LeaderElection`].
+#[derive(Clone)]
+pub struct LeaderStatus(watch::Receiver<bool>);
+
+impl LeaderStatus {
+    pub fn is_leader(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Waits until leadership changes and returns the new state. Returns `None` once the
+    /// election has stopped.
+    pub async fn changed(&mut self) -> Option<bool> {
+        self.0.changed().await.ok()?;
+        Some(*self.0.borrow_and_update())
+    }
+}
+
+impl_metered_service!(
+    struct Metrics {
+        is_leader: IntGauge = IntGauge::new(
+            "merkle_root_relayer_is_leader",
+            "Whether this replica is the leader allowed to submit merkle roots",
+        ),
+        lease_renewal_failures: IntGauge = IntGauge::new(
+            "merkle_root_relayer_lease_renewal_failures",
+            "Number of consecutive failures to reach the leader election backend",
+        ),
+    }
+);
+
+pub struct LeaderElection {
+    backend: Arc<dyn LockBackend>,
+    holder: String,
+    relayer_id: String,
+    config: LeaderElectionConfig,
+    metrics: Metrics,
+}
+
+impl MeteredService for LeaderElection {
+    fn get_sources(&self) -> impl IntoIterator<Item = Box<dyn prometheus::core::Collector>> {
+        self.metrics.get_sources()
+    }
+}
+
+impl LeaderElection {
+    pub fn new(
+        backend: Arc<dyn LockBackend>,
+        holder: String,
+        relayer_id: String,
+        config: LeaderElectionConfig,
+    ) -> Self {
+        Self {
+            backend,
+            holder,
+            relayer_id,
+            config,
+            metrics: Metrics::new(),
+        }
+    }
+
+    /// Starts the election. The lease is released once every [`LeaderStatus`] is dropped.
+    pub fn run(self) -> LeaderStatus {
+        let (sender, receiver) = watch::channel(false);
+        tokio::spawn(self.task(sender));
+
+        LeaderStatus(receiver)
+    }
+
+    async fn task(self, sender: watch::Sender<bool>) {
+        let relayer_id = self.relayer_id.as_str();
+        let holder = self.holder.as_str();
+        let renew_interval = self.config.renew_interval();
+
+        let mut interval = tokio::time::interval(renew_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last_renewal: Option<Instant> = None;
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = sender.closed() => break,
+            }
+
+            // A hanging backend call must not keep the stale leadership published while
+            // the lease expires and another replica takes over.
+            let started = Instant::now();
+            let result = tokio::time::timeout(
+                renew_interval,
+                self.backend
+                    .try_acquire(holder, self.config.failover_window),
+            )
+            .await
+            .unwrap_or_else(|_| {
+                Err(anyhow::anyhow!(
+                    "Lease renewal timed out after {renew_interval:?}"
+                ))
+            });
+            let is_leader = match result {
+                Ok(acquired) => {
+                    self.metrics.lease_renewal_failures.set(0);
+                    last_renewal = acquired.then_some(started);
+                    acquired
+                }
+                Err(err) => {
+                    self.metrics.lease_renewal_failures.inc();
+                    log::warn!(
+                        "Merkle root relayer {relayer_id}: failed to renew leader lease as {holder}: {err:?}"
+                    );
+
+                    // Step down before the lease may expire and another replica takes over.
+                    last_renewal = last_renewal.filter(|renewal| {
+                        renewal.elapsed() + renew_interval < self.config.failover_window
+                    });
+                    last_renewal.is_some()
+                }
+            };
+
+            self.metrics.is_leader.set(is_leader as i64);
+            let changed = sender
+                .send_if_modified(|current| std::mem::replace(current, is_leader) != is_leader);
+            match (changed, is_leader) {
+                (true, true) => {
+                    log::info!("Merkle root relayer {relayer_id}: {holder} became the leader")
+                }
+                (true, false) => {
+                    log::warn!("Merkle root relayer {relayer_id}: {holder} is a follower now")
+                }
+                _ => {}
+            }
+        }
+
+        if last_renewal.is_some() {
+            if let Err(err) = self.backend.release(holder).await {
+                log::warn!(
+                    "Merkle root relayer {relayer_id}: failed to release leader lease: {err:?}"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proof_storage::FileSystemObjectStore;
+
+    async fn check_backend(backend: &dyn LockBackend) {
+        let ttl = Duration::from_secs(60);
+
+        assert!(backend.try_acquire("a", ttl).await.unwrap());
+        assert!(!backend.try_acquire("b", ttl).await.unwrap());
+        assert!(backend.try_acquire("a", ttl).await.unwrap());
+
+        // Release by a non-holder has no effect.
+        backend.release("b").await.unwrap();
+        assert!(!backend.try_acquire("b", ttl).await.unwrap());
+
+        backend.release("a").await.unwrap();
+        assert!(backend.try_acquire("b", ttl).await.unwrap());
+        assert!(!backend.try_acquire("a", ttl).await.unwrap());
+
+        // Expired lease is taken over.
+        assert!(backend.try_acquire("b", Duration::ZERO).await.unwrap());
+        assert!(backend.try_acquire("a", ttl).await.unwrap());
+    }
+
+    #[test]
+    fn lease_is_taken_over_once_expired() {
+        let lease = Lease {
+            holder: "a".into(),
+            expires_at: 1_000,
+        };
+        let ttl = Duration::from_secs(1);
+
+        assert_eq!(Lease::next(Some(&lease), "b", ttl, 999), None);
+        assert_eq!(
+            Lease::next(Some(&lease), "a", ttl, 999),
+            Some(Lease {
+                holder: "a".into(),
+                expires_at: 1_999,
+            })
+        );
+        assert_eq!(
+            Lease::next(Some(&lease), "b", ttl, 1_000).map(|lease| lease.holder),
+            Some("b".into())
+        );
+        assert!(Lease::next(None, "b", ttl, 0).is_some());
+    }
+
+    #[tokio::test]
+    async fn file_lock_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        check_backend(&FileLockBackend::new(dir.path().join("leader.json"))).await;
+    }
+
+    #[tokio::test]
+    async fn object_store_lock_backend() {
+        let root = tempfile::tempdir().unwrap();
+        let store = Arc::new(
+            FileSystemObjectStore::new(root.path().to_path_buf())
+                .await
+                .unwrap(),
+        );
+        check_backend(&ObjectStoreLockBackend::new(
+            store,
+            "mainnet/leader.json".into(),
+        ))
+        .await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires PostgreSQL at LEADER_ELECTION_DATABASE_URL"]
+    async fn database_lock_backend() {
+        let url = std::env::var("LEADER_ELECTION_DATABASE_URL")
+            .expect("LEADER_ELECTION_DATABASE_URL is set");
+        check_backend(&DatabaseLockBackend::new(
+            Zeroizing::new(url),
+            format!("mainnet-{}", uuid::Uuid::new_v4()),
+        ))
+        .await;
+    }
+
+    /// Grants the lease once and hangs on every later call.
+    struct HangingBackend {
+        acquired: std::sync::atomic::AtomicBool,
+    }
+
+    #[async_trait::async_trait]
+    impl LockBackend for HangingBackend {
+        async fn try_acquire(&self, _holder: &str, _ttl: Duration) -> anyhow::Result<bool> {
+            if !self
+                .acquired
+                .swap(true, std::sync::atomic::Ordering::SeqCst)
+            {
+                return Ok(true);
+            }
+
+            std::future::pending().await
+        }
+
+        async fn release(&self, _holder: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn leader_steps_down_when_backend_hangs() {
+        let config = LeaderElectionConfig {
+            failover_window: Duration::from_millis(300),
+        };
+        let mut status = LeaderElection::new(
+            Arc::new(HangingBackend {
+                acquired: Default::default(),
+            }),
+            "a".into(),
+            "test".into(),
+            config,
+        )
+        .run();
+
+        assert_eq!(status.changed().await, Some(true));
+
+        // The lease granted above expires `failover_window` after it was acquired.
+        let stepped_down = tokio::time::timeout(config.failover_window, status.changed()).await;
+        assert_eq!(stepped_down.ok(), Some(Some(false)));
+    }
+
+    #[tokio::test]
+    async fn follower_takes_over_after_leader_stops() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("leader.json");
+        let config = LeaderElectionConfig {
+            failover_window: Duration::from_millis(300),
+        };
+        let election = |holder: &str| {
+            LeaderElection::new(
+                Arc::new(FileLockBackend::new(path.clone())),
+                holder.into(),
+                "test".into(),
+                config,
+            )
+            .run()
+        };
+
+        let mut first = election("a");
+        assert_eq!(first.changed().await, Some(true));
+
+        let mut second = election("b");
+        tokio::time::sleep(config.failover_window).await;
+        assert!(!second.is_leader());
+
+        drop(first);
+        let became_leader = tokio::time::timeout(config.failover_window * 3, async {
+            while second.changed().await != Some(true) {}
+        })
+        .await;
+        assert!(became_leader.is_ok());
+    }
+}
@@ -16,7 +16,7 @@ use ethereum_client::EthApi;
 use gear_common::api_provider::ApiProviderConnection;
 use gear_rpc_client::dto::RawBlockInclusionProof;
 use primitive_types::{H256, U256};
-use prometheus::IntGauge;
+use prometheus::{Gauge, IntCounter, IntGauge};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, HashMap, VecDeque},
@@ -30,13 +30,19 @@ use tokio::{
     sync::{
         broadcast::{error::RecvError, Receiver},
         mpsc::UnboundedReceiver,
+        watch,
     },
     time::{Interval, MissedTickBehavior},
 };
 use utils_prometheus::{impl_metered_service, MeteredService};
 
 pub mod authority_set_sync;
+pub mod fee_bumping;
+pub mod leader_election;
+pub mod policy;
 pub mod prover;
+pub mod prover_pool;
+pub mod status;
 pub mod storage;
 pub mod submitter;
 
@@ -103,13 +109,17 @@ impl Relayer {
             options.genesis_config,
             options.count_thread,
             options.gnark_data_path.clone(),
+            options.prover_pool.clone(),
+            options.mock_prover,
         ));
 
         let submitter = submitter::MerkleRootSubmitter::new(
             eth_api.clone(),
             storage.clone(),
             options.confirmations,
+            options.live_updates(),
             options.relayer_id.clone(),
+            options.leader.clone(),
         );
         let merkle_roots = MerkleRootRelayer::new(api_provider, storage, options).await;
 
@@ -155,7 +165,9 @@ impl Relayer {
             eth_api.clone(),
             storage.clone(),
             options.confirmations,
+            options.live_updates(),
             options.relayer_id.clone(),
+            options.leader.clone(),
         );
         let merkle_roots = MerkleRootRelayer::new(api_provider, storage, options).await;
 
@@ -231,6 +243,14 @@ impl_metered_service!(
             "merkle_root_relayer_waiting_for_authority_set_sync",
             "Number of blocks waiting for authority set sync"
         ),
+        deferred_batches: IntCounter = IntCounter::new(
+            "merkle_root_relayer_deferred_batches",
+            "Number of checks at which a ready batch was deferred by the submission policy"
+        ),
+        estimated_daily_spend: Gauge = Gauge::new(
+            "merkle_root_relayer_estimated_daily_spend",
+            "Estimated ETH spent on merkle root submissions today"
+        ),
     }
 );
 
@@ -248,8 +268,10 @@ pub struct MerkleRootRelayer {
     first_pending_timestamp: Option<Instant>,
     queued_root_timestamps: VecDeque<Instant>,
     merkle_root_batch: Vec<PendingMerkleRoot>,
+    policy: policy::SubmissionPolicy,
 
     options: MerkleRootRelayerOptions,
+    live_options: watch::Receiver<LiveOptions>,
 
     save_interval: Interval,
     main_interval: Interval,
@@ -291,7 +313,9 @@ impl MerkleRootRelayer {
             first_pending_timestamp: None,
             queued_root_timestamps: VecDeque::with_capacity(8),
             merkle_root_batch: Vec::with_capacity(8),
+            policy: policy::SubmissionPolicy::new(options.spike_config, options.submission_policy),
 
+            live_options: options.live_updates(),
             options,
             save_interval,
             main_interval,
@@ -301,6 +325,23 @@ impl MerkleRootRelayer {
         }
     }
 
+    /// Picks up options changed by a config reload.
+    fn apply_live_options(&mut self) {
+        if !self.live_options.has_changed().unwrap_or(false) {
+            return;
+        }
+
+        let live = *self.live_options.borrow_and_update();
+        self.options.spike_config = live.spike_config;
+        self.options.critical_threshold = live.critical_threshold;
+        self.options.fee_bumping = live.fee_bumping;
+        self.policy.set_spike_config(live.spike_config);
+        log::info!(
+            "Merkle root relayer {}: applied reloaded options: {live:?}",
+            self.options.relayer_id
+        );
+    }
+
     fn prune_old_timestamps(&mut self) {
         let cutoff_time = Instant::now() - self.options.spike_config.window;
 
@@ -313,6 +354,93 @@ impl MerkleRootRelayer {
         }
     }
 
+    async fn status(&self) -> status::RelayerStatus {
+        let batch: Vec<u32> = self
+            .merkle_root_batch
+            .iter()
+            .map(|pending| pending.block_number)
+            .collect();
+
+        let mut merkle_roots = Vec::with_capacity(self.roots.len());
+        let mut finalized_merkle_roots = 0;
+        for (&(block_number, merkle_root), root) in &self.roots {
+            if let MerkleRootStatus::Finalized = root.status {
+                finalized_merkle_roots += 1;
+                continue;
+            }
+
+            merkle_roots.push(status::MerkleRootState {
+                block_number,
+                block_hash: root.block_hash,
+                merkle_root,
+                queue_id: root.queue_id,
+                status: root.status.clone(),
+                submitted: self
+                    .storage
+                    .is_merkle_root_submitted(block_number, merkle_root)
+                    .await,
+                waiting_requests: root.http_requests.len(),
+            });
+        }
+        merkle_roots.sort_by_key(|root| root.block_number);
+
+        let prover_jobs = match &self.options.prover_pool {
+            Some(pool) => pool
+                .jobs()
+                .into_iter()
+                .map(|job| status::ProverJob {
+                    block_number: job.block_number,
+                    merkle_root: job.merkle_root,
+                    job_id: Some(job.job_id),
+                    attempts: job.attempts,
+                    worker_id: job.worker_id,
+                })
+                .collect(),
+            // Batched roots also wait for proof generation but aren't sent to the prover yet.
+            None => merkle_roots
+                .iter()
+                .filter(|root| {
+                    root.status == MerkleRootStatus::GenerateProof
+                        && !batch.contains(&root.block_number)
+                })
+                .map(|root| status::ProverJob {
+                    block_number: root.block_number,
+                    merkle_root: root.merkle_root,
+                    job_id: None,
+                    attempts: 0,
+                    worker_id: None,
+                })
+                .collect(),
+        };
+
+        status::RelayerStatus {
+            relayer_id: self.options.relayer_id.clone(),
+            last_submitted_block: self.last_submitted_block,
+            merkle_roots,
+            finalized_merkle_roots,
+            batch,
+            authority_set_sync: status::AuthoritySetSyncStatus {
+                latest_proven_authority_set_id: self
+                    .storage
+                    .proofs
+                    .get_latest_authority_set_id()
+                    .await,
+                waiting: self
+                    .waiting_for_authority_set_sync
+                    .iter()
+                    .map(
+                        |(&authority_set_id, blocks)| status::WaitingForAuthoritySet {
+                            authority_set_id,
+                            blocks: blocks.iter().map(GearBlock::number).collect(),
+                        },
+                    )
+                    .collect(),
+            },
+            prover_jobs,
+            submissions: self.storage.tracked_transactions().await,
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn run(
         mut self,
@@ -335,6 +463,7 @@ impl MerkleRootRelayer {
                 Default::default()
             }
         };
+        self.policy.restore_spending(self.storage.spending().await);
         let gear_api = self.api_provider.client();
         let mut last_sealed = match self.options.last_sealed {
             Some(era) => era,
@@ -373,6 +502,7 @@ impl MerkleRootRelayer {
                         block_hash,
                         status,
                         message_nonces: Vec::new(),
+                        priority: merkle_root.priority,
                         proof: merkle_root.proof.clone(),
                         http_requests: Vec::new(),
                         block_inclusion_proof: merkle_root.block_inclusion_proof.clone(),
@@ -633,7 +763,7 @@ impl MerkleRootRelayer {
             );
             self.storage
                 .submitted_merkle_root(block_number, merkle_root)
-                .await;
+                .await?;
             return Ok(());
         }
 
@@ -762,6 +892,8 @@ impl MerkleRootRelayer {
         http: &mut UnboundedReceiver<MerkleRootsRequest>,
         eth_api: &EthApi,
     ) -> anyhow::Result<bool> {
+        self.apply_live_options();
+
         let client = self.api_provider.client();
         tokio::select! {
             _ = self.save_interval.tick() => {
@@ -791,42 +923,67 @@ impl MerkleRootRelayer {
                 }
 
 
-                let has_priority = self.merkle_root_batch.iter().any(|root| root.priority);
-                let timeout = if has_priority {
-                    self.options.spike_config.priority_timeout
+                let batch = policy::BatchState {
+                    roots: self.merkle_root_batch.len(),
+                    nonces: self.merkle_root_batch.iter().map(|root| root.nonces_count).sum(),
+                    priority: self.merkle_root_batch.iter().any(|root| root.priority),
+                    pending_for: self.first_pending_timestamp.map(|t| t.elapsed()).unwrap_or_default(),
+                };
+                let base_fee = if self.policy.is_cost_aware() && batch.roots > 0 {
+                    match eth_api.base_fee_per_gas().await {
+                        Ok(base_fee) => Some(base_fee),
+                        Err(err) => {
+                            log::warn!("Merkle root relayer {}: failed to fetch base fee, falling back to spike policy: {err}", self.options.relayer_id);
+                            None
+                        }
+                    }
                 } else {
-                    self.options.spike_config.timeout
+                    None
                 };
-                let is_spike = self.merkle_root_batch.iter().map(|root| root.nonces_count).sum::<usize>() >= self.options.spike_config.threshold;
-                let is_timeout = self.first_pending_timestamp
-                    .is_some_and(|t| t.elapsed() >= timeout);
-
-                if is_spike || is_timeout {
-                    // consume the timestamp to not trigger timeout again immediately.
-                    self.first_pending_timestamp.take();
-                    let batch_size = self.merkle_root_batch.len();
-                    if batch_size == 0 {
-                        return Ok(true);
+                let now = chrono::Utc::now().timestamp() as u64;
+                self.metrics.estimated_daily_spend.set(self.policy.spent(now) as f64 / 1e18);
+
+                match self.policy.decide(&batch, base_fee, now) {
+                    policy::Decision::Wait => {}
+
+                    policy::Decision::DeferBaseFee(base_fee) => {
+                        self.metrics.deferred_batches.inc();
+                        log::info!("Merkle root relayer {}: deferring batch of {} merkle root(s), base fee {base_fee} wei is above the limit", self.options.relayer_id, batch.roots);
                     }
-                    log::info!("Merkle root relayer {}: triggering proof generation. Batch size: {batch_size}, Reason: Spike={is_spike}, Timeout={is_timeout}", self.options.relayer_id);
-                    // do not group blocks by authority set id, prover will do this for us.
-                    for pending in self.merkle_root_batch.drain(..) {
-                        let merkle_root = &self.roots[&(pending.block_number, pending.merkle_root)];
-                        if !prover.prove(
-                            pending.block_number,
-                            pending.block_hash,
-                            pending.merkle_root,
-                            pending.inner_proof,
-                            pending.queue_id,
-                            /* request is part of the batch: */
-                            true,
-                            merkle_root.block_inclusion_proof.clone(),
-                        ) {
-                            log::warn!(
-                                "Merkle root relayer {}: prover connection closed, exiting",
-                                self.options.relayer_id
-                            );
-                            return Ok(false);
+
+                    policy::Decision::DeferBudget => {
+                        self.metrics.deferred_batches.inc();
+                        log::info!("Merkle root relayer {}: deferring batch of {} merkle root(s), daily budget is exhausted", self.options.relayer_id, batch.roots);
+                    }
+
+                    policy::Decision::Submit(trigger) => {
+                        // consume the timestamp to not trigger timeout again immediately.
+                        self.first_pending_timestamp.take();
+                        let batch_size = self.merkle_root_batch.len();
+                        let estimated = self.policy.record_submission(base_fee, now);
+                        if let Err(err) = self.storage.record_spending(self.policy.spending()).await {
+                            log::error!("Merkle root relayer {}: {err:?}", self.options.relayer_id);
+                        }
+                        log::info!("Merkle root relayer {}: triggering proof generation. Batch size: {batch_size}, Reason: {trigger:?}, estimated cost: {estimated} wei", self.options.relayer_id);
+                        // do not group blocks by authority set id, prover will do this for us.
+                        for pending in self.merkle_root_batch.drain(..) {
+                            let merkle_root = &self.roots[&(pending.block_number, pending.merkle_root)];
+                            if !prover.prove(
+                                pending.block_number,
+                                pending.block_hash,
+                                pending.merkle_root,
+                                pending.inner_proof,
+                                pending.queue_id,
+                                /* request is part of the batch: */
+                                true,
+                                merkle_root.block_inclusion_proof.clone(),
+                            ) {
+                                log::warn!(
+                                    "Merkle root relayer {}: prover connection closed, exiting",
+                                    self.options.relayer_id
+                                );
+                                return Ok(false);
+                            }
                         }
                     }
                 }
@@ -889,6 +1046,12 @@ impl MerkleRootRelayer {
                                     }
                                 }
                             }
+
+                            MerkleRootsRequest::GetStatus { response } => {
+                                if response.send(self.status().await).is_err() {
+                                    log::warn!("Merkle root relayer {}: HTTP status request dropped", self.options.relayer_id);
+                                }
+                            }
                         }
                     }
 
@@ -990,7 +1153,7 @@ impl MerkleRootRelayer {
                                     };
                                 }
                             });
-
+                        self.persist_root(block_number, merkle_root).await?;
 
                         if !submitter.submit_merkle_root(block_number, merkle_root, proof) {
                             log::warn!(
@@ -1029,7 +1192,7 @@ impl MerkleRootRelayer {
                                         log::info!("Merkle root relayer {}: send HTTP response for merkle root {merkle_root} at block #{block_number}", self.options.relayer_id);
                                     }
                             });
-
+                            self.persist_root(block_number, merkle_root).await?;
                         }
 
                         self.roots.entry((block_number, merkle_root))
@@ -1049,6 +1212,7 @@ impl MerkleRootRelayer {
                                     };
                                 }
                             });
+                        self.persist_root(block_number, merkle_root).await?;
 
                         if !submitter.submit_merkle_root(block_number, merkle_root, proof) {
                             log::warn!(
@@ -1074,7 +1238,7 @@ impl MerkleRootRelayer {
 
                 match response {
                     authority_set_sync::Response::AuthoritySetSynced(id, block) => {
-                        self.storage.authority_set_processed(block).await;
+                        self.storage.authority_set_processed(block).await?;
 
                         let Some(mut to_submit) = self.waiting_for_authority_set_sync.remove(&id) else {
                             log::warn!("Merkle root relayer {}: no blocks to sync for authority set #{id}", self.options.relayer_id);
@@ -1135,6 +1299,13 @@ impl MerkleRootRelayer {
                         _ => Some(merkle_root.block_number),
                     };
                     merkle_root.status = MerkleRootStatus::Finalized;
+                    self.storage
+                        .write_root(
+                            response.merkle_root_block,
+                            response.merkle_root,
+                            merkle_root,
+                        )
+                        .await?;
                     log::info!(
                         "Merkle root relayer {}: merkle root {} for block #{} is finalized",
                         self.options.relayer_id,
@@ -1164,6 +1335,13 @@ impl MerkleRootRelayer {
 
                 submitter::ResponseStatus::Failed(err) => {
                     merkle_root.status = MerkleRootStatus::Failed(err.to_string());
+                    self.storage
+                        .write_root(
+                            response.merkle_root_block,
+                            response.merkle_root,
+                            merkle_root,
+                        )
+                        .await?;
                     log::error!(
                         "Merkle root relayer {}: failed to finalize merkle root {} for block #{}: {}",
                         self.options.relayer_id,
@@ -1196,6 +1374,18 @@ impl MerkleRootRelayer {
         Ok(())
     }
 
+    /// Writes the merkle root through to the storage after its status has changed.
+    async fn persist_root(&self, block_number: u32, merkle_root: H256) -> anyhow::Result<()> {
+        match self.roots.get(&(block_number, merkle_root)) {
+            Some(root) => {
+                self.storage
+                    .write_root(block_number, merkle_root, root)
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
     /// Attempt to create proof for merkle root of `block`. If authority set that signed `block`
     /// is not yet proven, proof generation will be delayed until authority set is synced.
     #[allow(clippy::too_many_arguments)]
@@ -1255,7 +1445,13 @@ impl MerkleRootRelayer {
                 merkle_root,
                 block.number()
             );
-            self.storage.merkle_root_processed(block.number()).await;
+            self.storage
+                .merkle_root_processed(
+                    block.number(),
+                    merkle_root,
+                    self.roots.get(&(block.number(), merkle_root)),
+                )
+                .await?;
             if let Err(err) = self.storage.save(&self.roots).await {
                 log::error!(
                     "Merkle root relayer {}: failed to save block storage state: {err:?}",
@@ -1300,10 +1496,12 @@ impl MerkleRootRelayer {
                         block_hash,
                         status: MerkleRootStatus::GenerateProof,
                         message_nonces: nonces,
+                        priority: false,
                         http_requests: Vec::new(),
                         proof: None,
                         block_inclusion_proof: block_inclusion_proof.clone(),
-                    });
+                    })
+                    .priority |= priority == Priority::Yes;
                 if matches!(batch, Batch::Yes) {
                     let now = Instant::now();
 
@@ -1363,10 +1561,12 @@ impl MerkleRootRelayer {
                             block.number(),
                         ),
                         message_nonces: nonces,
+                        priority: false,
                         http_requests: Vec::new(),
                         proof: None,
                         block_inclusion_proof,
-                    });
+                    })
+                    .priority |= priority == Priority::Yes;
 
                 // Enqueue an authority set sync for this id whenever the proof is missing.
                 // `or_insert_with` de-duplicates per id, so this never spams the runner.
@@ -1405,6 +1605,7 @@ impl MerkleRootRelayer {
                         block_hash: block.hash(),
                         status: MerkleRootStatus::Failed(err.to_string()),
                         message_nonces: nonces,
+                        priority: priority == Priority::Yes,
                         http_requests: Vec::new(),
                         proof: None,
                         block_inclusion_proof,
@@ -1415,7 +1616,13 @@ impl MerkleRootRelayer {
                     "Merkle root relayer {}: failed to get proof for authority set id {signed_by_authority_set_id}: {err}",
                     self.options.relayer_id
                 );
-                self.storage.merkle_root_processed(block_number).await;
+                self.storage
+                    .merkle_root_processed(
+                        block_number,
+                        merkle_root,
+                        self.roots.get(&(block_number, merkle_root)),
+                    )
+                    .await?;
                 if let Err(save_err) = self.storage.save(&self.roots).await {
                     log::error!(
                         "Merkle root relayer {}: failed to save block storage state: {save_err:?}",
@@ -1427,7 +1634,13 @@ impl MerkleRootRelayer {
         }
 
         // Mark root processed only after durable root state exists or the work is queued.
-        self.storage.merkle_root_processed(block_number).await;
+        self.storage
+            .merkle_root_processed(
+                block_number,
+                merkle_root,
+                self.roots.get(&(block_number, merkle_root)),
+            )
+            .await?;
         if let Err(err) = self.storage.save(&self.roots).await {
             log::error!(
                 "Merkle root relayer {}: failed to save block storage state: {err:?}",
@@ -1445,6 +1658,9 @@ pub struct MerkleRoot {
     pub block_hash: H256,
     pub queue_id: u64,
     pub message_nonces: Vec<U256>,
+    /// Whether priority bridging of a message in the block was paid for.
+    #[serde(default)]
+    pub priority: bool,
     #[serde(skip)]
     pub http_requests: Vec<tokio::sync::oneshot::Sender<MerkleRootsResponse>>,
     #[serde(default)]
@@ -1460,6 +1676,7 @@ impl Clone for MerkleRoot {
             block_hash: self.block_hash,
             queue_id: self.queue_id,
             message_nonces: self.message_nonces.clone(),
+            priority: self.priority,
             http_requests: Vec::new(),
             proof: self.proof.clone(),
             status: self.status.clone(),
@@ -1468,7 +1685,7 @@ impl Clone for MerkleRoot {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MerkleRootStatus {
     WaitForAuthoritySetSync(u64, u32),
     GenerateProof,
@@ -1498,6 +1715,22 @@ pub struct MerkleRootRelayerOptions {
     /// When multiple relayers share a process, authority-set proving is serialized through
     /// this shared worker so only one heavy proving job runs at a time.
     pub shared_authority_set_sync: Option<Arc<authority_set_sync::SharedAuthoritySetSync>>,
+    /// Pool of prover workers. When not set, final proofs are generated in-process.
+    pub prover_pool: Option<prover_pool::ProverPool>,
+    /// Latency of the mock prover. When set, final proofs are faked instead of generated,
+    /// which only a mock verifier accepts.
+    pub mock_prover: Option<Duration>,
+    /// Defers batches while submitting them is too expensive. When not set, batches are
+    /// submitted as soon as `spike_config` allows.
+    pub submission_policy: Option<policy::CostAwareConfig>,
+    /// Replacement of stuck merkle root transactions with higher fees.
+    pub fee_bumping: fee_bumping::FeeBumpConfig,
+    /// Leadership among replicas of this relayer. When set, only the leader submits
+    /// merkle roots while followers keep the rest of the pipeline warm.
+    pub leader: Option<leader_election::LeaderStatus>,
+    /// Updates of [`LiveOptions`] sent on config reload. When not set, the options stay as
+    /// configured at startup.
+    pub live_updates: Option<watch::Receiver<LiveOptions>>,
 }
 
 impl MerkleRootRelayerOptions {
@@ -1509,9 +1742,33 @@ impl MerkleRootRelayerOptions {
             .map(|relayer| relayer.options)
             .ok_or_else(|| anyhow::anyhow!("No relayer config found"))
     }
+
+    /// Options which may be changed while the relayer is running.
+    pub fn live(&self) -> LiveOptions {
+        LiveOptions {
+            spike_config: self.spike_config,
+            critical_threshold: self.critical_threshold,
+            fee_bumping: self.fee_bumping,
+        }
+    }
+
+    fn live_updates(&self) -> watch::Receiver<LiveOptions> {
+        self.live_updates
+            .clone()
+            .unwrap_or_else(|| watch::channel(self.live()).1)
+    }
+}
+
+/// Subset of [`MerkleRootRelayerOptions`] applied to a running relayer when its config is
+/// reloaded, see [`crate::config_reload`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiveOptions {
+    pub spike_config: SpikeConfig,
+    pub critical_threshold: CriticalThreshold,
+    pub fee_bumping: fee_bumping::FeeBumpConfig,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct SpikeConfig {
     /// Timeout after which we start generating proof
     /// for batch of requests without priority requests.
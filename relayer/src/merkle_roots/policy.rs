@@ -0,0 +1,530 @@
+//! Decides when a batch of merkle roots gets proved and submitted to Ethereum.
+//!
+//! Without a [`CostAwareConfig`] the decision is made by [`SpikeConfig`] alone. With it,
+//! batches which are ready by spike or timeout are additionally deferred while the base fee
+//! is too high or the daily budget is exhausted, unless the oldest message in the batch has
+//! already waited for `max_message_age`.
+
+use super::SpikeConfig;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Gear produces a block every 3 seconds.
+pub const GEAR_BLOCK_TIME: Duration = Duration::from_secs(3);
+
+/// Gas used by a single merkle root submission when it isn't configured explicitly.
+pub const DEFAULT_SUBMISSION_GAS: u64 = 400_000;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostAwareConfig {
+    /// Batches are deferred while the base fee is above this value, in wei.
+    pub max_base_fee: u128,
+    /// Base fee limit for batches with paid priority bridging, in wei.
+    pub priority_max_base_fee: u128,
+    /// Amount of wei which may be spent on submissions during a UTC day.
+    pub daily_budget: u128,
+    /// Gas used by a single submission, used to estimate its cost.
+    pub submission_gas: u64,
+    /// Batch is submitted regardless of the base fee and budget once its oldest
+    /// message has waited this long.
+    pub max_message_age: Duration,
+}
+
+/// Batch of merkle roots waiting for proof generation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BatchState {
+    pub roots: usize,
+    pub nonces: usize,
+    /// Whether priority bridging is paid for any of the roots.
+    pub priority: bool,
+    /// Time since the oldest root of the batch was queued.
+    pub pending_for: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Spike,
+    Timeout,
+    MessageAge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    /// Batch isn't ready yet.
+    Wait,
+    /// Batch is ready but submitting it now is too expensive.
+    DeferBaseFee(u128),
+    DeferBudget,
+    Submit(Trigger),
+}
+
+/// Estimated amount of wei spent on submissions during a UTC day. Persisted by the merkle
+/// root storage so that a restart doesn't reset the daily budget.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DailySpending {
+    /// Number of the day since the unix epoch.
+    pub day: u64,
+    pub spent: u128,
+}
+
+#[derive(Clone)]
+pub struct SubmissionPolicy {
+    spike: SpikeConfig,
+    cost: Option<CostAwareConfig>,
+    spending: DailySpending,
+    /// Base fee of the latest check it was known at, in wei.
+    last_base_fee: Option<u128>,
+}
+
+impl SubmissionPolicy {
+    pub fn new(spike: SpikeConfig, cost: Option<CostAwareConfig>) -> Self {
+        Self {
+            spike,
+            cost,
+            spending: DailySpending::default(),
+            last_base_fee: None,
+        }
+    }
+
+    /// Continues accounting of spending restored after a restart.
+    pub fn restore_spending(&mut self, spending: DailySpending) {
+        self.spending = spending;
+    }
+
+    pub fn spending(&self) -> DailySpending {
+        self.spending
+    }
+
+    /// Replaces spike settings while keeping the spending of the current day.
+    pub fn set_spike_config(&mut self, spike: SpikeConfig) {
+        self.spike = spike;
+    }
+
+    pub fn is_cost_aware(&self) -> bool {
+        self.cost.is_some()
+    }
+
+    /// Estimated amount of wei spent on submissions during the day `now` belongs to.
+    pub fn spent(&self, now: u64) -> u128 {
+        if now / SECONDS_PER_DAY == self.spending.day {
+            self.spending.spent
+        } else {
+            0
+        }
+    }
+
+    /// Estimated cost of a submission in wei. When the base fee is unknown, `max_base_fee`
+    /// is assumed, or the last known base fee if it's higher, so that submissions made
+    /// without knowing the base fee still count against the budget.
+    fn estimate(&self, base_fee: Option<u128>) -> u128 {
+        let submission_gas = self
+            .cost
+            .map_or(DEFAULT_SUBMISSION_GAS, |cost| cost.submission_gas);
+        let base_fee = base_fee.unwrap_or_else(|| {
+            let max_base_fee = self.cost.map_or(0, |cost| cost.max_base_fee);
+            self.last_base_fee.unwrap_or_default().max(max_base_fee)
+        });
+
+        base_fee.saturating_mul(submission_gas as u128)
+    }
+
+    /// `now` is a unix timestamp in seconds, used to reset the daily budget.
+    pub fn decide(&self, batch: &BatchState, base_fee: Option<u128>, now: u64) -> Decision {
+        if batch.roots == 0 {
+            return Decision::Wait;
+        }
+
+        let timeout = if batch.priority {
+            self.spike.priority_timeout
+        } else {
+            self.spike.timeout
+        };
+        let trigger = if batch.nonces >= self.spike.threshold {
+            Some(Trigger::Spike)
+        } else if batch.pending_for >= timeout {
+            Some(Trigger::Timeout)
+        } else {
+            None
+        };
+
+        let Some(cost) = self.cost else {
+            return trigger.map_or(Decision::Wait, Decision::Submit);
+        };
+
+        if batch.pending_for >= cost.max_message_age {
+            return Decision::Submit(Trigger::MessageAge);
+        }
+
+        let Some(trigger) = trigger else {
+            return Decision::Wait;
+        };
+
+        if self.spent(now).saturating_add(self.estimate(base_fee)) > cost.daily_budget {
+            return Decision::DeferBudget;
+        }
+
+        // The base fee limit can't be checked when the base fee is unknown.
+        let Some(base_fee) = base_fee else {
+            return Decision::Submit(trigger);
+        };
+
+        let max_base_fee = if batch.priority {
+            cost.priority_max_base_fee
+        } else {
+            cost.max_base_fee
+        };
+        if base_fee > max_base_fee {
+            return Decision::DeferBaseFee(base_fee);
+        }
+
+        Decision::Submit(trigger)
+    }
+
+    /// Accounts a submission made at `now` and returns its estimated cost in wei.
+    pub fn record_submission(&mut self, base_fee: Option<u128>, now: u64) -> u128 {
+        let estimated = self.estimate(base_fee);
+        if base_fee.is_some() {
+            self.last_base_fee = base_fee;
+        }
+
+        let day = now / SECONDS_PER_DAY;
+        if day != self.spending.day {
+            self.spending = DailySpending { day, spent: 0 };
+        }
+        self.spending.spent = self.spending.spent.saturating_add(estimated);
+
+        estimated
+    }
+}
+
+/// Base fee observed since the given Gear block.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct BaseFeeSample {
+    pub block_number: u32,
+    pub base_fee: u128,
+}
+
+/// Merkle root as seen in the stored block history.
+#[derive(Debug, Clone, Copy)]
+pub struct ReplayedRoot {
+    pub block_number: u32,
+    pub nonces: usize,
+    /// Whether priority bridging was paid for the root.
+    pub priority: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    pub submissions: usize,
+    /// Submissions forced by `max_message_age`.
+    pub forced_submissions: usize,
+    /// Estimated amount of wei spent.
+    pub spent: u128,
+    /// Number of checks at which a ready batch was deferred.
+    pub deferrals: usize,
+    pub max_delay: Duration,
+}
+
+/// Replays `roots` through `policy` as if the relayer checked the batch every
+/// `check_interval`. Time is derived from Gear block numbers and the base fee follows
+/// `base_fees`, which must be sorted by block number.
+pub fn replay(
+    mut policy: SubmissionPolicy,
+    roots: &[ReplayedRoot],
+    base_fees: &[BaseFeeSample],
+    check_interval: Duration,
+) -> ReplayReport {
+    let mut report = ReplayReport::default();
+    let (Some(first), Some(last)) = (roots.first(), roots.last()) else {
+        return report;
+    };
+
+    let time_of =
+        |block_number: u32| GEAR_BLOCK_TIME * block_number.saturating_sub(first.block_number);
+    let base_fee_at = |block_number: u32| {
+        base_fees
+            .iter()
+            .take_while(|sample| sample.block_number <= block_number)
+            .last()
+            .or(base_fees.first())
+            .map(|sample| sample.base_fee)
+    };
+
+    // Let the last batch run into every timeout.
+    let end = time_of(last.block_number)
+        + policy.spike.timeout.max(
+            policy
+                .cost
+                .map_or(Duration::ZERO, |cost| cost.max_message_age),
+        )
+        + check_interval;
+
+    let mut next = 0;
+    let mut batch: Vec<ReplayedRoot> = Vec::new();
+    let mut now = Duration::ZERO;
+    while now <= end {
+        while let Some(root) = roots
+            .get(next)
+            .filter(|root| time_of(root.block_number) <= now)
+        {
+            batch.push(*root);
+            next += 1;
+        }
+
+        let block_number = first.block_number + (now.as_secs() / GEAR_BLOCK_TIME.as_secs()) as u32;
+        let base_fee = base_fee_at(block_number);
+        let state = BatchState {
+            roots: batch.len(),
+            nonces: batch.iter().map(|root| root.nonces).sum(),
+            priority: batch.iter().any(|root| root.priority),
+            pending_for: batch
+                .first()
+                .map_or(Duration::ZERO, |root| now - time_of(root.block_number)),
+        };
+
+        match policy.decide(&state, base_fee, now.as_secs()) {
+            Decision::Wait => {}
+            Decision::DeferBaseFee(_) | Decision::DeferBudget => report.deferrals += 1,
+            Decision::Submit(trigger) => {
+                report.submissions += 1;
+                if trigger == Trigger::MessageAge {
+                    report.forced_submissions += 1;
+                }
+                report.spent += policy.record_submission(base_fee, now.as_secs());
+                report.max_delay = report.max_delay.max(state.pending_for);
+                batch.clear();
+            }
+        }
+
+        now += check_interval;
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn spike() -> SpikeConfig {
+        SpikeConfig {
+            timeout: Duration::from_secs(30 * 60),
+            priority_timeout: Duration::from_secs(10 * 60),
+            window: Duration::from_secs(15 * 60),
+            threshold: 8,
+        }
+    }
+
+    fn cost() -> CostAwareConfig {
+        CostAwareConfig {
+            max_base_fee: 20 * GWEI,
+            priority_max_base_fee: 50 * GWEI,
+            daily_budget: 1_000_000 * 40 * GWEI,
+            submission_gas: 1_000_000,
+            max_message_age: Duration::from_secs(4 * 60 * 60),
+        }
+    }
+
+    fn batch(nonces: usize, pending_for: Duration) -> BatchState {
+        BatchState {
+            roots: 1,
+            nonces,
+            priority: false,
+            pending_for,
+        }
+    }
+
+    #[test]
+    fn spike_only_policy_ignores_base_fee() {
+        let policy = SubmissionPolicy::new(spike(), None);
+
+        assert_eq!(
+            policy.decide(&batch(8, Duration::ZERO), Some(1_000 * GWEI), 0),
+            Decision::Submit(Trigger::Spike)
+        );
+        assert_eq!(
+            policy.decide(&batch(1, Duration::from_secs(60)), Some(GWEI), 0),
+            Decision::Wait
+        );
+        assert_eq!(
+            policy.decide(&BatchState::default(), None, 0),
+            Decision::Wait
+        );
+    }
+
+    #[test]
+    fn defers_on_high_base_fee_until_message_age() {
+        let policy = SubmissionPolicy::new(spike(), Some(cost()));
+        let ready = batch(1, Duration::from_secs(60 * 60));
+
+        assert_eq!(
+            policy.decide(&ready, Some(30 * GWEI), 0),
+            Decision::DeferBaseFee(30 * GWEI)
+        );
+        assert_eq!(
+            policy.decide(
+                &BatchState {
+                    priority: true,
+                    ..ready
+                },
+                Some(30 * GWEI),
+                0
+            ),
+            Decision::Submit(Trigger::Timeout)
+        );
+        assert_eq!(
+            policy.decide(&ready, None, 0),
+            Decision::Submit(Trigger::Timeout)
+        );
+        assert_eq!(
+            policy.decide(
+                &batch(1, Duration::from_secs(4 * 60 * 60)),
+                Some(1_000 * GWEI),
+                0
+            ),
+            Decision::Submit(Trigger::MessageAge)
+        );
+    }
+
+    #[test]
+    fn daily_budget_resets_next_day() {
+        let mut policy = SubmissionPolicy::new(spike(), Some(cost()));
+        let ready = batch(8, Duration::ZERO);
+
+        assert_eq!(
+            policy.record_submission(Some(20 * GWEI), 10),
+            20 * 1_000_000 * GWEI
+        );
+        assert_eq!(
+            policy.decide(&ready, Some(20 * GWEI), 20),
+            Decision::Submit(Trigger::Spike)
+        );
+        policy.record_submission(Some(20 * GWEI), 20);
+        assert_eq!(policy.decide(&ready, Some(GWEI), 30), Decision::DeferBudget);
+
+        assert_eq!(policy.spent(SECONDS_PER_DAY), 0);
+        assert_eq!(
+            policy.decide(&ready, Some(GWEI), SECONDS_PER_DAY),
+            Decision::Submit(Trigger::Spike)
+        );
+    }
+
+    #[test]
+    fn unknown_base_fee_counts_against_budget() {
+        let mut policy = SubmissionPolicy::new(spike(), Some(cost()));
+        let ready = batch(8, Duration::ZERO);
+
+        assert_eq!(
+            policy.record_submission(None, 10),
+            cost().max_base_fee * 1_000_000
+        );
+        assert_eq!(
+            policy.decide(&ready, None, 20),
+            Decision::Submit(Trigger::Spike)
+        );
+        policy.record_submission(None, 20);
+        assert_eq!(policy.decide(&ready, None, 30), Decision::DeferBudget);
+
+        policy.restore_spending(DailySpending::default());
+        policy.record_submission(Some(100 * GWEI), 30);
+        assert_eq!(policy.record_submission(None, 40), 100 * GWEI * 1_000_000);
+    }
+
+    #[test]
+    fn spending_is_restored() {
+        let mut policy = SubmissionPolicy::new(spike(), Some(cost()));
+        policy.record_submission(Some(20 * GWEI), 10);
+
+        let mut restarted = SubmissionPolicy::new(spike(), Some(cost()));
+        restarted.restore_spending(policy.spending());
+        assert_eq!(restarted.spent(20), 20 * 1_000_000 * GWEI);
+        assert_eq!(
+            restarted.decide(&batch(8, Duration::ZERO), Some(20 * GWEI), 20),
+            Decision::Submit(Trigger::Spike)
+        );
+        restarted.record_submission(Some(20 * GWEI), 20);
+        assert_eq!(
+            restarted.decide(&batch(8, Duration::ZERO), Some(GWEI), 30),
+            Decision::DeferBudget
+        );
+    }
+
+    #[test]
+    fn replay_compares_policies() {
+        // One message every 10 minutes for 6 hours.
+        let roots: Vec<_> = (0..36)
+            .map(|index| ReplayedRoot {
+                block_number: 1_000 + index * 200,
+                nonces: 1,
+                priority: false,
+            })
+            .collect();
+        // Base fee is high for the first 3 hours.
+        let base_fees = [
+            BaseFeeSample {
+                block_number: 0,
+                base_fee: 40 * GWEI,
+            },
+            BaseFeeSample {
+                block_number: 1_000 + 3_600,
+                base_fee: 10 * GWEI,
+            },
+        ];
+        let check_interval = Duration::from_secs(30);
+
+        let spike_only = replay(
+            SubmissionPolicy::new(spike(), None),
+            &roots,
+            &base_fees,
+            check_interval,
+        );
+        let cost_aware = replay(
+            SubmissionPolicy::new(spike(), Some(cost())),
+            &roots,
+            &base_fees,
+            check_interval,
+        );
+
+        assert_eq!(spike_only.deferrals, 0);
+        assert!(cost_aware.deferrals > 0);
+        assert!(cost_aware.submissions < spike_only.submissions);
+        assert!(cost_aware.spent < spike_only.spent);
+        assert!(cost_aware.max_delay <= cost().max_message_age);
+    }
+    #[test]
+    fn replay_submits_priority_roots_above_max_base_fee() {
+        // Base fee is between `max_base_fee` and `priority_max_base_fee` all the time.
+        let base_fees = [BaseFeeSample {
+            block_number: 0,
+            base_fee: 40 * GWEI,
+        }];
+        let replay_root = |priority| {
+            replay(
+                SubmissionPolicy::new(spike(), Some(cost())),
+                &[ReplayedRoot {
+                    block_number: 1_000,
+                    nonces: 1,
+                    priority,
+                }],
+                &base_fees,
+                Duration::from_secs(30),
+            )
+        };
+
+        let priority = replay_root(true);
+        assert_eq!(priority.submissions, 1);
+        assert_eq!(priority.forced_submissions, 0);
+        assert_eq!(priority.deferrals, 0);
+        assert_eq!(priority.max_delay, spike().priority_timeout);
+
+        // Without paid priority the root is deferred until its age forces the submission.
+        let regular = replay_root(false);
+        assert_eq!(regular.submissions, 1);
+        assert_eq!(regular.forced_submissions, 1);
+        assert_eq!(regular.max_delay, cost().max_message_age);
+    }
+}
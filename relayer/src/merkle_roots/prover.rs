@@ -1,4 +1,5 @@
 use crate::{
+    merkle_roots::prover_pool::{ProofJob, ProverPool},
     prover_interface::{self, FinalProof},
     rpc,
 };
@@ -57,6 +58,10 @@ struct ProverContext {
     genesis_config: GenesisConfig,
     count_thread: Option<usize>,
     gnark_data_path: PathBuf,
+    /// When set, proofs are generated by the pool workers instead of this process.
+    prover_pool: Option<ProverPool>,
+    /// When set, fake proofs are returned after this delay instead of generating them.
+    mock_latency: Option<Duration>,
 }
 
 enum RequestSender {
@@ -241,6 +246,8 @@ impl FinalityProver {
         genesis_config: GenesisConfig,
         count_thread: Option<usize>,
         gnark_data_path: PathBuf,
+        prover_pool: Option<ProverPool>,
+        mock_latency: Option<Duration>,
     ) -> Self {
         Self {
             context: ProverContext {
@@ -248,6 +255,8 @@ impl FinalityProver {
                 genesis_config,
                 count_thread,
                 gnark_data_path,
+                prover_pool,
+                mock_latency,
             },
 
             metrics: Metrics::new(),
@@ -510,31 +519,35 @@ async fn generate_proof(
     log::info!("Proving merkle root({merkle_root}) presence in block #{block_number}");
 
     let start = Instant::now();
-    let genesis_config = context.genesis_config;
-    let count_thread = context.count_thread;
-    let gnark_data_path = context.gnark_data_path.clone();
-    let proof = rpc::retry_gear(
-        &mut context.api_provider,
-        "prover finality proof",
-        move |gear_api| {
-            let inner_proof = inner_proof.clone();
-            let block_inclusion_proof = block_inclusion_proof.clone();
-            let gnark_data_path = gnark_data_path.clone();
-            async move {
-                prover_interface::prove_final(
-                    &gear_api,
-                    inner_proof,
-                    genesis_config,
-                    block_hash,
-                    count_thread,
-                    gnark_data_path,
-                    Some(block_inclusion_proof),
-                )
-                .await
-            }
-        },
-    )
-    .await?;
+    let proof = match (&context.prover_pool, context.mock_latency) {
+        (_, Some(latency)) => {
+            tokio::time::sleep(latency).await;
+            FinalProof::mock(block_number, merkle_root.0)
+        }
+        (Some(pool), None) => {
+            pool.prove(ProofJob::new(
+                block_number,
+                block_hash,
+                merkle_root,
+                inner_proof,
+                context.genesis_config,
+                block_inclusion_proof,
+            ))
+            .await?
+        }
+        (None, None) => {
+            prove_final_locally(
+                &mut context.api_provider,
+                block_hash,
+                inner_proof,
+                context.genesis_config,
+                context.count_thread,
+                context.gnark_data_path.clone(),
+                block_inclusion_proof,
+            )
+            .await?
+        }
+    };
     let elapsed = start.elapsed().as_secs_f64();
     log::info!("Proof for {merkle_root} generated (block #{block_number}) in {elapsed:.3} seconds",);
 
@@ -549,6 +562,38 @@ async fn generate_proof(
     Ok(proof)
 }
 
+/// Generates final proof in the current process. Used both by the in-process prover
+/// and by prover pool workers.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn prove_final_locally(
+    api_provider: &mut ApiProviderConnection,
+    block_hash: H256,
+    inner_proof: ProofWithCircuitData,
+    genesis_config: GenesisConfig,
+    count_thread: Option<usize>,
+    gnark_data_path: PathBuf,
+    block_inclusion_proof: RawBlockInclusionProof,
+) -> anyhow::Result<FinalProof> {
+    rpc::retry_gear(api_provider, "prover finality proof", move |gear_api| {
+        let inner_proof = inner_proof.clone();
+        let block_inclusion_proof = block_inclusion_proof.clone();
+        let gnark_data_path = gnark_data_path.clone();
+        async move {
+            prover_interface::prove_final(
+                &gear_api,
+                inner_proof,
+                genesis_config,
+                block_hash,
+                count_thread,
+                gnark_data_path,
+                Some(block_inclusion_proof),
+            )
+            .await
+        }
+    })
+    .await
+}
+
 struct SharedRequest {
     relayer_id: String,
     priority: i64,
@@ -593,6 +638,7 @@ impl SharedFinalityProver {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn register(
         &self,
         relayer_id: String,
@@ -601,6 +647,8 @@ impl SharedFinalityProver {
         genesis_config: GenesisConfig,
         count_thread: Option<usize>,
         gnark_data_path: PathBuf,
+        prover_pool: Option<ProverPool>,
+        mock_latency: Option<Duration>,
     ) -> FinalityProverIo {
         let (response_tx, response_rx) = tokio::sync::mpsc::unbounded_channel();
         FinalityProverIo::new_shared(
@@ -611,6 +659,8 @@ impl SharedFinalityProver {
                 genesis_config,
                 count_thread,
                 gnark_data_path,
+                prover_pool,
+                mock_latency,
             },
             self.requests.clone(),
             response_rx,
@@ -0,0 +1,1094 @@
+//! Worker pool for final merkle-root proofs.
+//!
+//! Instead of running Plonky2 proving and the gnark wrap inside of the relayer process,
+//! [`ProverPool`] queues [`ProofJob`]s and hands them out to workers under a lease. Workers
+//! must heartbeat the lease while proving, otherwise the job is considered abandoned and is
+//! put back into the queue for another worker. Remote workers talk to the pool over an
+//! authenticated HTTP protocol (see [`create_server`] and [`HttpJobSource`]); a loopback
+//! worker uses the pool directly from the same process.
+
+use crate::{
+    hex_utils::hex_bytes, merkle_roots::prover::prove_final_locally, prover_interface::FinalProof,
+};
+use ::prover::proving::{CircuitData, GenesisConfig, Proof, ProofWithCircuitData};
+use actix_web::{guard, middleware, web, App, HttpRequest, HttpResponse, HttpServer};
+use async_trait::async_trait;
+use gear_common::api_provider::ApiProviderConnection;
+use gear_rpc_client::dto::RawBlockInclusionProof;
+use primitive_types::H256;
+use prometheus::{IntCounter, IntGauge};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::TcpListener,
+    path::PathBuf,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+use subtle::ConstantTimeEq;
+use tokio::sync::{oneshot, Notify};
+use utils_prometheus::{impl_metered_service, MeteredService};
+
+const HEADER_TOKEN: &str = "X-Token";
+/// Maximum time a lease request waits for a job before returning an empty response.
+const LEASE_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+/// Delay before a worker retries after failing to reach the pool.
+const WORKER_ERROR_DELAY: Duration = Duration::from_secs(5);
+
+pub type JobId = u64;
+
+/// Self-contained description of a single final proof, suitable for sending to a remote worker.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProofJob {
+    pub block_number: u32,
+    pub block_hash: H256,
+    pub merkle_root: H256,
+    #[serde(with = "hex_bytes")]
+    pub inner_proof: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub inner_circuit_data: Vec<u8>,
+    pub genesis_authority_set_id: u64,
+    pub genesis_authority_set_hash: H256,
+    pub block_inclusion_proof: RawBlockInclusionProof,
+}
+
+impl ProofJob {
+    pub fn new(
+        block_number: u32,
+        block_hash: H256,
+        merkle_root: H256,
+        inner_proof: ProofWithCircuitData,
+        genesis_config: GenesisConfig,
+        block_inclusion_proof: RawBlockInclusionProof,
+    ) -> Self {
+        Self {
+            block_number,
+            block_hash,
+            merkle_root,
+            inner_proof: inner_proof.proof.into_bytes(),
+            inner_circuit_data: inner_proof.circuit_data.into_bytes(),
+            genesis_authority_set_id: genesis_config.authority_set_id,
+            genesis_authority_set_hash: H256(genesis_config.authority_set_hash),
+            block_inclusion_proof,
+        }
+    }
+
+    pub fn inner_proof(&self) -> ProofWithCircuitData {
+        ProofWithCircuitData {
+            proof: Proof::from_bytes(self.inner_proof.clone()),
+            circuit_data: CircuitData::from_bytes(self.inner_circuit_data.clone()),
+        }
+    }
+
+    pub fn genesis_config(&self) -> GenesisConfig {
+        GenesisConfig {
+            authority_set_id: self.genesis_authority_set_id,
+            authority_set_hash: self.genesis_authority_set_hash.0,
+        }
+    }
+}
+
+/// Job handed out to a worker together with its lease parameters.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LeasedJob {
+    pub job_id: JobId,
+    pub attempt: u32,
+    /// Lease is revoked unless the worker heartbeats within this interval.
+    pub lease_timeout_ms: u64,
+    pub job: ProofJob,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobResult {
+    Proof(FinalProof),
+    Failed(String),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ProverPoolConfig {
+    pub lease_timeout: Duration,
+    /// How many times a job is handed out before the request is failed.
+    pub max_attempts: u32,
+}
+
+impl Default for ProverPoolConfig {
+    fn default() -> Self {
+        Self {
+            lease_timeout: Duration::from_secs(2 * 60),
+            max_attempts: 3,
+        }
+    }
+}
+
+impl_metered_service! {
+    struct Metrics {
+        queued_jobs: IntGauge = IntGauge::new(
+            "prover_pool_queued_jobs",
+            "Number of proof jobs waiting for a worker"
+        ),
+        leased_jobs: IntGauge = IntGauge::new(
+            "prover_pool_leased_jobs",
+            "Number of proof jobs currently leased by workers"
+        ),
+        expired_leases: IntCounter = IntCounter::new(
+            "prover_pool_expired_leases",
+            "Number of leases revoked because the worker stopped heartbeating"
+        ),
+        failed_jobs: IntCounter = IntCounter::new(
+            "prover_pool_failed_jobs",
+            "Number of proof jobs failed after exhausting all attempts"
+        ),
+    }
+}
+
+/// Job which is queued or leased by a worker.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolJob {
+    pub job_id: JobId,
+    pub block_number: u32,
+    pub merkle_root: H256,
+    pub attempts: u32,
+    /// Worker holding the lease, `None` while the job is queued.
+    pub worker_id: Option<String>,
+}
+
+struct QueuedJob {
+    id: JobId,
+    job: Arc<ProofJob>,
+    attempts: u32,
+}
+
+struct Lease {
+    worker_id: String,
+    deadline: Instant,
+    job: QueuedJob,
+}
+
+type Waiter = oneshot::Sender<Result<FinalProof, String>>;
+
+#[derive(Default)]
+struct PoolState {
+    next_id: JobId,
+    queue: VecDeque<QueuedJob>,
+    leases: HashMap<JobId, Lease>,
+    waiters: HashMap<JobId, Waiter>,
+}
+
+impl PoolState {
+    fn enqueue(&mut self, job: ProofJob, waiter: Waiter) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.waiters.insert(id, waiter);
+        self.queue.push_back(QueuedJob {
+            id,
+            job: Arc::new(job),
+            attempts: 0,
+        });
+
+        id
+    }
+
+    fn lease(
+        &mut self,
+        worker_id: &str,
+        now: Instant,
+        lease_timeout: Duration,
+    ) -> Option<LeasedJob> {
+        while let Some(mut queued) = self.queue.pop_front() {
+            // The requesting relayer is gone, there is no one to deliver the proof to.
+            if self
+                .waiters
+                .get(&queued.id)
+                .map(|waiter| waiter.is_closed())
+                .unwrap_or(true)
+            {
+                self.waiters.remove(&queued.id);
+                continue;
+            }
+
+            queued.attempts += 1;
+            let leased = LeasedJob {
+                job_id: queued.id,
+                attempt: queued.attempts,
+                lease_timeout_ms: lease_timeout.as_millis() as u64,
+                job: (*queued.job).clone(),
+            };
+            self.leases.insert(
+                queued.id,
+                Lease {
+                    worker_id: worker_id.to_string(),
+                    deadline: now + lease_timeout,
+                    job: queued,
+                },
+            );
+
+            return Some(leased);
+        }
+
+        None
+    }
+
+    fn heartbeat(
+        &mut self,
+        worker_id: &str,
+        job_id: JobId,
+        now: Instant,
+        lease_timeout: Duration,
+    ) -> bool {
+        match self.leases.get_mut(&job_id) {
+            Some(lease) if lease.worker_id == worker_id => {
+                lease.deadline = now + lease_timeout;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Applies a worker result. Returns `false` if the worker no longer holds the lease,
+    /// in which case the result is discarded. A proof of another block or merkle root than
+    /// the leased job counts as a failed attempt.
+    fn complete(
+        &mut self,
+        worker_id: &str,
+        job_id: JobId,
+        result: JobResult,
+        max_attempts: u32,
+    ) -> bool {
+        match self.leases.get(&job_id) {
+            Some(lease) if lease.worker_id == worker_id => {}
+            _ => return false,
+        }
+        let lease = self
+            .leases
+            .remove(&job_id)
+            .expect("lease presence is checked above");
+
+        let job = &lease.job.job;
+        let result = match result {
+            JobResult::Proof(proof)
+                if proof.block_number != job.block_number
+                    || proof.merkle_root != job.merkle_root.0 =>
+            {
+                JobResult::Failed(format!(
+                    "proof is for merkle root {:?} at block #{}, expected {:?} at block #{}",
+                    H256(proof.merkle_root),
+                    proof.block_number,
+                    job.merkle_root,
+                    job.block_number
+                ))
+            }
+            result => result,
+        };
+
+        match result {
+            JobResult::Proof(proof) => {
+                if let Some(waiter) = self.waiters.remove(&job_id) {
+                    let _ = waiter.send(Ok(proof));
+                }
+            }
+            JobResult::Failed(error) => {
+                log::warn!(
+                    "Prover pool: worker {worker_id} failed job #{job_id} (attempt {}): {error}",
+                    lease.job.attempts
+                );
+                self.retry_or_fail(lease.job, error, max_attempts);
+            }
+        }
+
+        true
+    }
+
+    fn jobs(&self) -> Vec<PoolJob> {
+        let job = |queued: &QueuedJob, worker_id: Option<&str>| PoolJob {
+            job_id: queued.id,
+            block_number: queued.job.block_number,
+            merkle_root: queued.job.merkle_root,
+            attempts: queued.attempts,
+            worker_id: worker_id.map(str::to_string),
+        };
+
+        let mut jobs: Vec<_> = self
+            .leases
+            .values()
+            .map(|lease| job(&lease.job, Some(&lease.worker_id)))
+            .chain(self.queue.iter().map(|queued| job(queued, None)))
+            .collect();
+        jobs.sort_by_key(|job| job.job_id);
+        jobs
+    }
+
+    /// Revokes leases which have not been renewed in time. Returns ids of expired jobs.
+    fn expire(&mut self, now: Instant, max_attempts: u32) -> Vec<JobId> {
+        let expired: Vec<JobId> = self
+            .leases
+            .iter()
+            .filter(|(_, lease)| lease.deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in &expired {
+            let lease = self.leases.remove(id).expect("id is taken from leases");
+            log::warn!(
+                "Prover pool: lease of job #{id} held by worker {} expired",
+                lease.worker_id
+            );
+            self.retry_or_fail(
+                lease.job,
+                format!("worker {} stopped responding", lease.worker_id),
+                max_attempts,
+            );
+        }
+
+        expired
+    }
+
+    fn retry_or_fail(&mut self, job: QueuedJob, error: String, max_attempts: u32) {
+        if job.attempts >= max_attempts {
+            if let Some(waiter) = self.waiters.remove(&job.id) {
+                let _ = waiter.send(Err(format!(
+                    "proof job failed after {} attempts: {error}",
+                    job.attempts
+                )));
+            }
+        } else {
+            // Re-queued jobs keep their place at the front to preserve the ordering.
+            self.queue.push_front(job);
+        }
+    }
+}
+
+struct PoolInner {
+    state: Mutex<PoolState>,
+    notify: Notify,
+    config: ProverPoolConfig,
+    metrics: Metrics,
+}
+
+impl PoolInner {
+    fn state(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        self.state.lock().expect("prover pool state lock poisoned")
+    }
+
+    fn record_metrics(&self, state: &PoolState) {
+        self.metrics.queued_jobs.set(state.queue.len() as i64);
+        self.metrics.leased_jobs.set(state.leases.len() as i64);
+    }
+}
+
+/// Queue of final proof jobs shared between the relayer and its workers.
+#[derive(Clone)]
+pub struct ProverPool {
+    inner: Arc<PoolInner>,
+}
+
+impl MeteredService for ProverPool {
+    fn get_sources(&self) -> impl IntoIterator<Item = Box<dyn prometheus::core::Collector>> {
+        self.inner.metrics.get_sources()
+    }
+}
+
+impl ProverPool {
+    /// Creates the pool and spawns the task revoking expired leases. Must be called
+    /// from within a tokio runtime.
+    pub fn new(config: ProverPoolConfig) -> Self {
+        let inner = Arc::new(PoolInner {
+            state: Mutex::new(PoolState::default()),
+            notify: Notify::new(),
+            config,
+            metrics: Metrics::new(),
+        });
+
+        tokio::spawn(expire_leases(
+            Arc::downgrade(&inner),
+            (config.lease_timeout / 4).max(Duration::from_secs(1)),
+        ));
+
+        Self { inner }
+    }
+
+    pub fn config(&self) -> ProverPoolConfig {
+        self.inner.config
+    }
+
+    /// Jobs which are queued or leased, ordered by id.
+    pub fn jobs(&self) -> Vec<PoolJob> {
+        self.inner.state().jobs()
+    }
+
+    /// Queues the job and waits until some worker delivers the proof.
+    pub async fn prove(&self, job: ProofJob) -> anyhow::Result<FinalProof> {
+        let block_number = job.block_number;
+        let (sender, receiver) = oneshot::channel();
+        let job_id = {
+            let mut state = self.inner.state();
+            let job_id = state.enqueue(job, sender);
+            self.inner.record_metrics(&state);
+            job_id
+        };
+        self.inner.notify.notify_one();
+
+        log::info!("Prover pool: queued job #{job_id} for block #{block_number}");
+
+        match receiver.await {
+            Ok(Ok(proof)) => Ok(proof),
+            Ok(Err(error)) => {
+                self.inner.metrics.failed_jobs.inc();
+                Err(anyhow::anyhow!(
+                    "Prover pool job #{job_id} for block #{block_number}: {error}"
+                ))
+            }
+            Err(_) => Err(anyhow::anyhow!(
+                "Prover pool job #{job_id} for block #{block_number} was dropped"
+            )),
+        }
+    }
+
+    /// Leases the next job to the worker, waiting up to `wait` for one to become available.
+    pub async fn lease(&self, worker_id: &str, wait: Duration) -> Option<LeasedJob> {
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            {
+                let mut state = self.inner.state();
+                let leased =
+                    state.lease(worker_id, Instant::now(), self.inner.config.lease_timeout);
+                self.inner.record_metrics(&state);
+                if let Some(leased) = leased {
+                    log::info!(
+                        "Prover pool: job #{} (attempt {}) leased to worker {worker_id}",
+                        leased.job_id,
+                        leased.attempt
+                    );
+                    return Some(leased);
+                }
+            }
+
+            if tokio::time::timeout_at(deadline, self.inner.notify.notified())
+                .await
+                .is_err()
+            {
+                return None;
+            }
+        }
+    }
+
+    pub fn heartbeat(&self, worker_id: &str, job_id: JobId) -> bool {
+        self.inner.state().heartbeat(
+            worker_id,
+            job_id,
+            Instant::now(),
+            self.inner.config.lease_timeout,
+        )
+    }
+
+    pub fn complete(&self, worker_id: &str, job_id: JobId, result: JobResult) -> bool {
+        let (accepted, requeued) = {
+            let mut state = self.inner.state();
+            let queued = state.queue.len();
+            let accepted =
+                state.complete(worker_id, job_id, result, self.inner.config.max_attempts);
+            self.inner.record_metrics(&state);
+            (accepted, state.queue.len() > queued)
+        };
+        if requeued {
+            self.inner.notify.notify_one();
+        }
+        accepted
+    }
+}
+
+async fn expire_leases(inner: Weak<PoolInner>, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+
+        let expired = {
+            let mut state = inner.state();
+            let expired = state.expire(Instant::now(), inner.config.max_attempts);
+            inner.record_metrics(&state);
+            expired
+        };
+        inner.metrics.expired_leases.inc_by(expired.len() as u64);
+        for _ in &expired {
+            inner.notify.notify_one();
+        }
+    }
+}
+
+/// Where a worker takes jobs from.
+#[async_trait]
+pub trait JobSource: Send + Sync {
+    async fn lease(&self, worker_id: &str) -> anyhow::Result<Option<LeasedJob>>;
+    async fn heartbeat(&self, worker_id: &str, job_id: JobId) -> anyhow::Result<bool>;
+    async fn complete(
+        &self,
+        worker_id: &str,
+        job_id: JobId,
+        result: JobResult,
+    ) -> anyhow::Result<bool>;
+}
+
+#[async_trait]
+impl JobSource for ProverPool {
+    async fn lease(&self, worker_id: &str) -> anyhow::Result<Option<LeasedJob>> {
+        Ok(ProverPool::lease(self, worker_id, LEASE_POLL_TIMEOUT).await)
+    }
+
+    async fn heartbeat(&self, worker_id: &str, job_id: JobId) -> anyhow::Result<bool> {
+        Ok(ProverPool::heartbeat(self, worker_id, job_id))
+    }
+
+    async fn complete(
+        &self,
+        worker_id: &str,
+        job_id: JobId,
+        result: JobResult,
+    ) -> anyhow::Result<bool> {
+        Ok(ProverPool::complete(self, worker_id, job_id, result))
+    }
+}
+
+/// Client side of the pool HTTP protocol, used by remote workers.
+pub struct HttpJobSource {
+    client: reqwest::Client,
+    url: String,
+    token: String,
+}
+
+impl HttpJobSource {
+    pub fn new(url: String, token: String) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(LEASE_POLL_TIMEOUT + Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    async fn post<Req: Serialize + ?Sized, Resp: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        body: &Req,
+    ) -> anyhow::Result<Resp> {
+        let response = self
+            .client
+            .post(format!("{}{path}", self.url))
+            .header(HEADER_TOKEN, &self.token)
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json().await?)
+    }
+}
+
+#[async_trait]
+impl JobSource for HttpJobSource {
+    async fn lease(&self, worker_id: &str) -> anyhow::Result<Option<LeasedJob>> {
+        let response: LeaseResponse = self
+            .post(
+                "/prover/lease",
+                &LeaseRequest {
+                    worker_id: worker_id.to_string(),
+                },
+            )
+            .await?;
+        Ok(response.job)
+    }
+
+    async fn heartbeat(&self, worker_id: &str, job_id: JobId) -> anyhow::Result<bool> {
+        let response: AcceptedResponse = self
+            .post(
+                "/prover/heartbeat",
+                &HeartbeatRequest {
+                    worker_id: worker_id.to_string(),
+                    job_id,
+                },
+            )
+            .await?;
+        Ok(response.accepted)
+    }
+
+    async fn complete(
+        &self,
+        worker_id: &str,
+        job_id: JobId,
+        result: JobResult,
+    ) -> anyhow::Result<bool> {
+        let response: AcceptedResponse = self
+            .post(
+                "/prover/complete",
+                &CompleteRequest {
+                    worker_id: worker_id.to_string(),
+                    job_id,
+                    result,
+                },
+            )
+            .await?;
+        Ok(response.accepted)
+    }
+}
+
+/// Worker which generates final proofs for jobs taken from a [`JobSource`].
+pub struct ProverWorker<S> {
+    worker_id: String,
+    source: Arc<S>,
+    api_provider: ApiProviderConnection,
+    count_thread: Option<usize>,
+    gnark_data_path: PathBuf,
+}
+
+impl<S: JobSource + 'static> ProverWorker<S> {
+    pub fn new(
+        worker_id: String,
+        source: S,
+        api_provider: ApiProviderConnection,
+        count_thread: Option<usize>,
+        gnark_data_path: PathBuf,
+    ) -> Self {
+        Self {
+            worker_id,
+            source: Arc::new(source),
+            api_provider,
+            count_thread,
+            gnark_data_path,
+        }
+    }
+
+    /// Runs the worker on a blocking thread of the current runtime, as proving occupies the
+    /// thread for minutes.
+    pub fn spawn(self) -> tokio::task::JoinHandle<()> {
+        let handle = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || handle.block_on(self.run()))
+    }
+
+    pub async fn run(mut self) {
+        log::info!("Prover worker {} started", self.worker_id);
+
+        loop {
+            let leased = match self.source.lease(&self.worker_id).await {
+                Ok(Some(leased)) => leased,
+                Ok(None) => continue,
+                Err(err) => {
+                    log::error!(
+                        "Prover worker {}: failed to lease job: {err}",
+                        self.worker_id
+                    );
+                    tokio::time::sleep(WORKER_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+
+            let job_id = leased.job_id;
+            let heartbeat = tokio::spawn(send_heartbeats(
+                self.source.clone(),
+                self.worker_id.clone(),
+                job_id,
+                Duration::from_millis(leased.lease_timeout_ms / 3).max(Duration::from_secs(1)),
+            ));
+
+            let result = match self.prove(&leased.job).await {
+                Ok(proof) => JobResult::Proof(proof),
+                Err(err) => {
+                    log::error!(
+                        "Prover worker {}: job #{job_id} for block #{} failed: {err}",
+                        self.worker_id,
+                        leased.job.block_number
+                    );
+                    JobResult::Failed(err.to_string())
+                }
+            };
+            heartbeat.abort();
+
+            loop {
+                match self
+                    .source
+                    .complete(&self.worker_id, job_id, result.clone())
+                    .await
+                {
+                    Ok(true) => break,
+                    Ok(false) => {
+                        log::warn!(
+                            "Prover worker {}: lease of job #{job_id} was revoked, result discarded",
+                            self.worker_id
+                        );
+                        break;
+                    }
+                    Err(err) => {
+                        log::error!(
+                            "Prover worker {}: failed to report job #{job_id}: {err}",
+                            self.worker_id
+                        );
+                        tokio::time::sleep(WORKER_ERROR_DELAY).await;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn prove(&mut self, job: &ProofJob) -> anyhow::Result<FinalProof> {
+        prove_final_locally(
+            &mut self.api_provider,
+            job.block_hash,
+            job.inner_proof(),
+            job.genesis_config(),
+            self.count_thread,
+            self.gnark_data_path.clone(),
+            job.block_inclusion_proof.clone(),
+        )
+        .await
+    }
+}
+
+async fn send_heartbeats<S: JobSource>(
+    source: Arc<S>,
+    worker_id: String,
+    job_id: JobId,
+    period: Duration,
+) {
+    let mut interval = tokio::time::interval(period);
+    // the first tick completes immediately
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match source.heartbeat(&worker_id, job_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                log::warn!("Prover worker {worker_id}: lease of job #{job_id} is lost");
+                return;
+            }
+            Err(err) => log::error!("Prover worker {worker_id}: heartbeat failed: {err}"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LeaseRequest {
+    worker_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LeaseResponse {
+    job: Option<LeasedJob>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HeartbeatRequest {
+    worker_id: String,
+    job_id: JobId,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CompleteRequest {
+    worker_id: String,
+    job_id: JobId,
+    result: JobResult,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AcceptedResponse {
+    accepted: bool,
+}
+
+struct Secret(String);
+
+fn is_authorized(request: &HttpRequest, secret: &Secret) -> bool {
+    request
+        .headers()
+        .get(HEADER_TOKEN)
+        // Compared in constant time so that the token can't be guessed from response times.
+        .map(|token| bool::from(token.as_bytes().ct_eq(secret.0.as_bytes())))
+        .unwrap_or(false)
+}
+
+async fn lease(
+    request: HttpRequest,
+    body: web::Json<LeaseRequest>,
+    secret: web::Data<Secret>,
+    pool: web::Data<ProverPool>,
+) -> HttpResponse {
+    if !is_authorized(&request, &secret) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let job = pool.lease(&body.worker_id, LEASE_POLL_TIMEOUT).await;
+    HttpResponse::Ok().json(LeaseResponse { job })
+}
+
+async fn heartbeat(
+    request: HttpRequest,
+    body: web::Json<HeartbeatRequest>,
+    secret: web::Data<Secret>,
+    pool: web::Data<ProverPool>,
+) -> HttpResponse {
+    if !is_authorized(&request, &secret) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let accepted = pool.heartbeat(&body.worker_id, body.job_id);
+    HttpResponse::Ok().json(AcceptedResponse { accepted })
+}
+
+async fn complete(
+    request: HttpRequest,
+    body: web::Json<CompleteRequest>,
+    secret: web::Data<Secret>,
+    pool: web::Data<ProverPool>,
+) -> HttpResponse {
+    if !is_authorized(&request, &secret) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let CompleteRequest {
+        worker_id,
+        job_id,
+        result,
+    } = body.into_inner();
+    let accepted = pool.complete(&worker_id, job_id, result);
+    HttpResponse::Ok().json(AcceptedResponse { accepted })
+}
+
+/// Creates HTTP server through which remote workers lease jobs from the pool.
+pub fn create_server(
+    tcp_listener: TcpListener,
+    secret: String,
+    pool: ProverPool,
+) -> std::io::Result<actix_web::dev::Server> {
+    let secret = web::Data::new(Secret(secret));
+    let pool = web::Data::new(pool);
+
+    let server = HttpServer::new(move || {
+        let unauthorized = || {
+            web::route()
+                .guard(guard::Any(guard::Get()).or(guard::Post()))
+                .to(HttpResponse::Unauthorized)
+        };
+
+        App::new()
+            .app_data(secret.clone())
+            .app_data(pool.clone())
+            .wrap(middleware::Logger::default())
+            .app_data(
+                // inner proof with its circuit data, 64 MiB
+                web::JsonConfig::default().limit(64 * 1024 * 1024),
+            )
+            .service(
+                web::resource("/prover/lease")
+                    .route(web::post().to(lease))
+                    .route(unauthorized()),
+            )
+            .service(
+                web::resource("/prover/heartbeat")
+                    .route(web::post().to(heartbeat))
+                    .route(unauthorized()),
+            )
+            .service(
+                web::resource("/prover/complete")
+                    .route(web::post().to(complete))
+                    .route(unauthorized()),
+            )
+    });
+
+    Ok(server.listen(tcp_listener)?.disable_signals().run())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(block_number: u32) -> ProofJob {
+        ProofJob {
+            block_number,
+            block_hash: H256::repeat_byte(1),
+            merkle_root: H256::repeat_byte(2),
+            inner_proof: vec![1, 2, 3],
+            inner_circuit_data: vec![4, 5, 6],
+            genesis_authority_set_id: 7,
+            genesis_authority_set_hash: H256::repeat_byte(3),
+            block_inclusion_proof: RawBlockInclusionProof {
+                justification_round: 0,
+                required_authority_set_id: 7,
+                validator_set: Vec::new(),
+                block_hash: H256::repeat_byte(1),
+                block_number,
+                pre_commits: Vec::new(),
+            },
+        }
+    }
+
+    fn proof(block_number: u32) -> FinalProof {
+        FinalProof {
+            proof: vec![0xaa],
+            block_number,
+            merkle_root: [2; 32],
+        }
+    }
+
+    const LEASE: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn lease_hands_out_jobs_in_order() {
+        let mut state = PoolState::default();
+        let now = Instant::now();
+        let (tx0, _rx0) = oneshot::channel();
+        let (tx1, _rx1) = oneshot::channel();
+        state.enqueue(job(10), tx0);
+        state.enqueue(job(11), tx1);
+
+        let first = state.lease("a", now, LEASE).unwrap();
+        let second = state.lease("b", now, LEASE).unwrap();
+        assert_eq!(first.job.block_number, 10);
+        assert_eq!(second.job.block_number, 11);
+        assert_eq!(first.attempt, 1);
+        assert!(state.lease("c", now, LEASE).is_none());
+    }
+
+    #[test]
+    fn jobs_report_leases_and_queue() {
+        let mut state = PoolState::default();
+        let now = Instant::now();
+        let (tx0, _rx0) = oneshot::channel();
+        let (tx1, _rx1) = oneshot::channel();
+        state.enqueue(job(10), tx0);
+        state.enqueue(job(11), tx1);
+        state.lease("a", now, LEASE).unwrap();
+
+        let jobs = state.jobs();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].block_number, 10);
+        assert_eq!(jobs[0].attempts, 1);
+        assert_eq!(jobs[0].worker_id.as_deref(), Some("a"));
+        assert_eq!(jobs[1].block_number, 11);
+        assert_eq!(jobs[1].worker_id, None);
+    }
+
+    #[test]
+    fn completion_delivers_proof_to_waiter() {
+        let mut state = PoolState::default();
+        let (tx, mut rx) = oneshot::channel();
+        let id = state.enqueue(job(10), tx);
+        state.lease("a", Instant::now(), LEASE).unwrap();
+
+        assert!(!state.complete("b", id, JobResult::Proof(proof(10)), 3));
+        assert!(state.complete("a", id, JobResult::Proof(proof(10)), 3));
+        assert_eq!(rx.try_recv().unwrap().unwrap().block_number, 10);
+        assert!(state.leases.is_empty());
+    }
+
+    #[test]
+    fn proof_of_another_job_is_rejected() {
+        let mut state = PoolState::default();
+        let now = Instant::now();
+        let (tx, mut rx) = oneshot::channel();
+        let id = state.enqueue(job(10), tx);
+
+        state.lease("a", now, LEASE).unwrap();
+        assert!(state.complete("a", id, JobResult::Proof(proof(11)), 3));
+        assert!(rx.try_recv().is_err());
+
+        let retried = state.lease("b", now, LEASE).unwrap();
+        assert_eq!(retried.job_id, id);
+        let wrong_root = FinalProof {
+            merkle_root: [9; 32],
+            ..proof(10)
+        };
+        assert!(state.complete("b", id, JobResult::Proof(wrong_root), 2));
+
+        assert!(state.queue.is_empty());
+        let err = rx.try_recv().unwrap().unwrap_err();
+        assert!(err.contains("expected"));
+    }
+
+    #[test]
+    fn expired_lease_is_requeued_and_stale_result_rejected() {
+        let mut state = PoolState::default();
+        let now = Instant::now();
+        let (tx, _rx) = oneshot::channel();
+        let id = state.enqueue(job(10), tx);
+        state.lease("a", now, LEASE).unwrap();
+
+        assert!(state.expire(now + LEASE / 2, 3).is_empty());
+        assert!(state.heartbeat("a", id, now + LEASE / 2, LEASE));
+        assert!(state.expire(now + LEASE, 3).is_empty());
+        assert_eq!(state.expire(now + LEASE * 2, 3), vec![id]);
+
+        let retried = state.lease("b", now + LEASE * 2, LEASE).unwrap();
+        assert_eq!(retried.job_id, id);
+        assert_eq!(retried.attempt, 2);
+
+        assert!(!state.heartbeat("a", id, now + LEASE * 2, LEASE));
+        assert!(!state.complete("a", id, JobResult::Proof(proof(10)), 3));
+    }
+
+    #[test]
+    fn job_fails_after_max_attempts() {
+        let mut state = PoolState::default();
+        let now = Instant::now();
+        let (tx, mut rx) = oneshot::channel();
+        let id = state.enqueue(job(10), tx);
+
+        state.lease("a", now, LEASE).unwrap();
+        assert!(state.complete("a", id, JobResult::Failed("rpc".into()), 2));
+        state.lease("b", now, LEASE).unwrap();
+        assert_eq!(state.expire(now + LEASE, 2), vec![id]);
+
+        assert!(state.queue.is_empty());
+        let err = rx.try_recv().unwrap().unwrap_err();
+        assert!(err.contains("after 2 attempts"));
+    }
+
+    #[test]
+    fn jobs_without_waiter_are_skipped() {
+        let mut state = PoolState::default();
+        let (tx, rx) = oneshot::channel();
+        state.enqueue(job(10), tx);
+        drop(rx);
+
+        assert!(state.lease("a", Instant::now(), LEASE).is_none());
+        assert!(state.waiters.is_empty());
+    }
+
+    #[test]
+    fn proof_job_roundtrips_through_json() {
+        let encoded = serde_json::to_string(&job(10)).unwrap();
+        let decoded: ProofJob = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(decoded.inner_proof, vec![1, 2, 3]);
+        assert_eq!(decoded.inner_circuit_data, vec![4, 5, 6]);
+        assert_eq!(decoded.genesis_config().authority_set_id, 7);
+    }
+
+    #[tokio::test]
+    async fn remote_worker_protocol_requires_token() {
+        let pool = ProverPool::new(ProverPoolConfig::default());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(create_server(listener, "secret".into(), pool.clone()).unwrap());
+
+        let url = format!("http://127.0.0.1:{port}");
+        let unauthorized = HttpJobSource::new(url.clone(), "wrong".into()).unwrap();
+        assert!(unauthorized.heartbeat("w", 0).await.is_err());
+
+        let pool_for_prove = pool.clone();
+        let proving = tokio::spawn(async move { pool_for_prove.prove(job(10)).await });
+
+        let source = HttpJobSource::new(url, "secret".into()).unwrap();
+        let leased = source.lease("w").await.unwrap().unwrap();
+        assert_eq!(leased.job.block_number, 10);
+        assert!(source.heartbeat("w", leased.job_id).await.unwrap());
+        assert!(source
+            .complete("w", leased.job_id, JobResult::Proof(proof(10)))
+            .await
+            .unwrap());
+
+        assert_eq!(proving.await.unwrap().unwrap().block_number, 10);
+    }
+}
@@ -0,0 +1,56 @@
+//! Read-only view of the merkle root relayer state served by the HTTP status endpoint.
+
+use super::{prover_pool::JobId, storage::SubmissionTransactions, MerkleRootStatus};
+use primitive_types::H256;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayerStatus {
+    pub relayer_id: String,
+    pub last_submitted_block: Option<u32>,
+    /// Merkle roots which are not finalized yet, ordered by block number.
+    pub merkle_roots: Vec<MerkleRootState>,
+    pub finalized_merkle_roots: usize,
+    /// Blocks of merkle roots which wait for the current batch to be proven.
+    pub batch: Vec<u32>,
+    pub authority_set_sync: AuthoritySetSyncStatus,
+    pub prover_jobs: Vec<ProverJob>,
+    /// Transactions of merkle roots which are being submitted to Ethereum.
+    pub submissions: Vec<SubmissionTransactions>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleRootState {
+    pub block_number: u32,
+    pub block_hash: H256,
+    pub merkle_root: H256,
+    pub queue_id: u64,
+    pub status: MerkleRootStatus,
+    pub submitted: bool,
+    /// Number of `/get_merkle_root_proof` requests waiting for the proof.
+    pub waiting_requests: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthoritySetSyncStatus {
+    pub latest_proven_authority_set_id: Option<u64>,
+    pub waiting: Vec<WaitingForAuthoritySet>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WaitingForAuthoritySet {
+    pub authority_set_id: u64,
+    pub blocks: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProverJob {
+    pub block_number: u32,
+    pub merkle_root: H256,
+    /// Prover pool job id, `None` when proofs are generated in-process.
+    pub job_id: Option<JobId>,
+    /// Number of times the job was leased to prover pool workers.
+    pub attempts: u32,
+    /// Prover pool worker holding the job, `None` while the job is queued.
+    pub worker_id: Option<String>,
+}
@@ -0,0 +1,836 @@
+use super::{
+    json::JsonSnapshotBackend, Block, PersistedState, StateBackend, StateSnapshot,
+    SubmissionTransactions,
+};
+use crate::merkle_roots::{policy::DailySpending, MerkleRoot, MerkleRootStatus};
+use anyhow::Context;
+use primitive_types::H256;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const BLOCK_PREFIX: u8 = b'b';
+const SUBMITTED_PREFIX: u8 = b's';
+const ROOT_PREFIX: u8 = b'r';
+const TRANSITION_PREFIX: u8 = b't';
+const TRANSACTIONS_PREFIX: u8 = b'x';
+const MIGRATED_KEY: &[u8] = b"meta/migrated";
+const SPENDING_KEY: &[u8] = b"meta/spending";
+
+/// How many status transitions are kept in the database. Older ones are dropped.
+const MAX_TRANSITIONS: u64 = 10_000;
+
+/// Change of [`MerkleRootStatus`] recorded by [`DatabaseBackend`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub block_number: u32,
+    pub merkle_root: H256,
+    /// `None` when the merkle root is seen for the first time.
+    pub from: Option<MerkleRootStatus>,
+    /// `None` when the merkle root was removed from the storage.
+    pub to: Option<MerkleRootStatus>,
+    /// Unix timestamp in seconds.
+    pub timestamp: u64,
+}
+
+#[derive(Clone)]
+struct RootState {
+    status: MerkleRootStatus,
+    fingerprint: u64,
+}
+
+struct Cache {
+    roots: HashMap<(u32, H256), RootState>,
+    next_transition: u64,
+}
+
+impl Cache {
+    /// Adds changed merkle roots and their status transitions to the `batch`.
+    fn write_roots(
+        &mut self,
+        batch: &mut sled::Batch,
+        roots: &HashMap<(u32, H256), MerkleRoot>,
+    ) -> anyhow::Result<()> {
+        let timestamp = timestamp();
+        let mut transitions = Vec::new();
+
+        for (&(block_number, merkle_root), root) in roots {
+            transitions.extend(self.write_root(
+                batch,
+                block_number,
+                merkle_root,
+                root,
+                timestamp,
+            )?);
+        }
+
+        self.roots.retain(|&(block_number, merkle_root), state| {
+            if roots.contains_key(&(block_number, merkle_root)) {
+                return true;
+            }
+
+            batch.remove(root_key(ROOT_PREFIX, block_number, merkle_root));
+            transitions.push(StatusTransition {
+                block_number,
+                merkle_root,
+                from: Some(state.status.clone()),
+                to: None,
+                timestamp,
+            });
+
+            false
+        });
+
+        self.write_transitions(batch, transitions)
+    }
+
+    /// Adds a merkle root to the `batch` if it has changed. Returns its status transition
+    /// if the status has changed as well.
+    fn write_root(
+        &mut self,
+        batch: &mut sled::Batch,
+        block_number: u32,
+        merkle_root: H256,
+        root: &MerkleRoot,
+        timestamp: u64,
+    ) -> anyhow::Result<Option<StatusTransition>> {
+        let value = serde_json::to_vec(root)?;
+        let fingerprint = fingerprint(&value);
+
+        let previous = self.roots.get(&(block_number, merkle_root));
+        if previous.is_some_and(|state| state.fingerprint == fingerprint) {
+            return Ok(None);
+        }
+
+        let from = previous.map(|state| state.status.clone());
+        let transition = (from.as_ref() != Some(&root.status)).then(|| StatusTransition {
+            block_number,
+            merkle_root,
+            from,
+            to: Some(root.status.clone()),
+            timestamp,
+        });
+
+        batch.insert(root_key(ROOT_PREFIX, block_number, merkle_root), value);
+        self.roots.insert(
+            (block_number, merkle_root),
+            RootState {
+                status: root.status.clone(),
+                fingerprint,
+            },
+        );
+
+        Ok(transition)
+    }
+
+    fn write_transitions(
+        &mut self,
+        batch: &mut sled::Batch,
+        transitions: impl IntoIterator<Item = StatusTransition>,
+    ) -> anyhow::Result<()> {
+        for transition in transitions {
+            batch.insert(
+                transition_key(self.next_transition).to_vec(),
+                serde_json::to_vec(&transition)?,
+            );
+            if self.next_transition >= MAX_TRANSITIONS {
+                batch.remove(transition_key(self.next_transition - MAX_TRANSITIONS).to_vec());
+            }
+            self.next_transition += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Backend which stores state in an embedded transactional database. Every block
+/// change, submission state change and merkle root write is applied atomically and
+/// flushed to disk before the call returns. A block whose merkle root is processed is
+/// written in the same batch as the merkle root status.
+pub struct DatabaseBackend {
+    db: sled::Db,
+    cache: Mutex<Cache>,
+}
+
+impl DatabaseBackend {
+    /// Opens database at `path`. On the first start state is migrated from the JSON
+    /// snapshot at `json_snapshot_path` if it exists.
+    pub async fn open(path: &Path, json_snapshot_path: PathBuf) -> anyhow::Result<Self> {
+        let db = sled::open(path)
+            .with_context(|| format!("Failed to open database at {}", path.display()))?;
+
+        Self::from_db(db, json_snapshot_path).await
+    }
+
+    async fn from_db(db: sled::Db, json_snapshot_path: PathBuf) -> anyhow::Result<Self> {
+        let next_transition = match db.scan_prefix([TRANSITION_PREFIX]).next_back() {
+            Some(entry) => transition_index(&entry?.0)? + 1,
+            None => 0,
+        };
+
+        let backend = Self {
+            db,
+            cache: Mutex::new(Cache {
+                roots: HashMap::new(),
+                next_transition,
+            }),
+        };
+
+        if backend.db.contains_key(MIGRATED_KEY)? {
+            let mut roots = HashMap::new();
+            for entry in backend.db.scan_prefix([ROOT_PREFIX]) {
+                let (key, value) = entry?;
+                let root: MerkleRoot = serde_json::from_slice(&value)?;
+                roots.insert(
+                    root_key_parts(&key)?,
+                    RootState {
+                        status: root.status,
+                        fingerprint: fingerprint(&value),
+                    },
+                );
+            }
+            backend.cache.lock().expect("cache lock poisoned").roots = roots;
+
+            return Ok(backend);
+        }
+
+        let json = JsonSnapshotBackend::new(json_snapshot_path);
+        let state = json.load().await?.unwrap_or_default();
+        if !state.roots.is_empty() || !state.blocks.is_empty() {
+            log::info!(
+                "Migrating merkle root storage from {} ({} blocks, {} merkle roots)",
+                json.path().display(),
+                state.blocks.len(),
+                state.roots.len()
+            );
+        }
+
+        let mut batch = sled::Batch::default();
+        for (block_number, block) in &state.blocks {
+            batch.insert(
+                block_key(*block_number).to_vec(),
+                serde_json::to_vec(block)?,
+            );
+        }
+        for (block_number, merkle_root) in &state.submitted_merkle_roots {
+            batch.insert(
+                root_key(SUBMITTED_PREFIX, *block_number, *merkle_root),
+                Vec::new(),
+            );
+        }
+        for ((block_number, merkle_root), transactions) in &state.transactions {
+            batch.insert(
+                root_key(TRANSACTIONS_PREFIX, *block_number, *merkle_root),
+                serde_json::to_vec(transactions)?,
+            );
+        }
+        batch.insert(SPENDING_KEY, serde_json::to_vec(&state.spending)?);
+        batch.insert(MIGRATED_KEY, Vec::new());
+        backend
+            .cache
+            .lock()
+            .expect("cache lock poisoned")
+            .write_roots(&mut batch, &state.roots)?;
+
+        backend.apply(batch).await?;
+
+        Ok(backend)
+    }
+
+    /// Returns recorded status transitions in the order they happened.
+    pub fn transitions(&self) -> anyhow::Result<Vec<StatusTransition>> {
+        self.db
+            .scan_prefix([TRANSITION_PREFIX])
+            .map(|entry| Ok(serde_json::from_slice(&entry?.1)?))
+            .collect()
+    }
+
+    /// Adds the merkle root and its status transition to `batch` and applies it. If the
+    /// batch fails to apply, the cached merkle root state is restored so that the merkle
+    /// root is written again next time.
+    async fn apply_with_root(
+        &self,
+        mut batch: sled::Batch,
+        block_number: u32,
+        merkle_root: H256,
+        root: &MerkleRoot,
+    ) -> anyhow::Result<()> {
+        let key = (block_number, merkle_root);
+        let previous = {
+            let mut cache = self.cache.lock().expect("cache lock poisoned");
+            let previous = cache.roots.get(&key).cloned();
+            let transition =
+                cache.write_root(&mut batch, block_number, merkle_root, root, timestamp())?;
+            cache.write_transitions(&mut batch, transition)?;
+
+            previous
+        };
+
+        let result = self.apply(batch).await;
+        if result.is_err() {
+            let mut cache = self.cache.lock().expect("cache lock poisoned");
+            match previous {
+                Some(state) => cache.roots.insert(key, state),
+                None => cache.roots.remove(&key),
+            };
+        }
+
+        result
+    }
+
+    async fn apply(&self, batch: sled::Batch) -> anyhow::Result<()> {
+        self.db.apply_batch(batch)?;
+        self.db.flush_async().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl StateBackend for DatabaseBackend {
+    async fn load(&self) -> anyhow::Result<Option<PersistedState>> {
+        let mut state = PersistedState::default();
+
+        for entry in self.db.scan_prefix([BLOCK_PREFIX]) {
+            let (key, value) = entry?;
+            state
+                .blocks
+                .insert(block_number(&key)?, serde_json::from_slice(&value)?);
+        }
+
+        for entry in self.db.scan_prefix([SUBMITTED_PREFIX]) {
+            state
+                .submitted_merkle_roots
+                .insert(root_key_parts(&entry?.0)?);
+        }
+
+        for entry in self.db.scan_prefix([ROOT_PREFIX]) {
+            let (key, value) = entry?;
+            state
+                .roots
+                .insert(root_key_parts(&key)?, serde_json::from_slice(&value)?);
+        }
+
+        for entry in self.db.scan_prefix([TRANSACTIONS_PREFIX]) {
+            let (key, value) = entry?;
+            state
+                .transactions
+                .insert(root_key_parts(&key)?, serde_json::from_slice(&value)?);
+        }
+
+        if let Some(spending) = self.db.get(SPENDING_KEY)? {
+            state.spending = serde_json::from_slice(&spending)?;
+        }
+
+        Ok(Some(state))
+    }
+
+    async fn write_block(&self, block_number: u32, block: Option<&Block>) -> anyhow::Result<()> {
+        let mut batch = sled::Batch::default();
+        block_to_batch(&mut batch, block_number, block)?;
+
+        self.apply(batch).await
+    }
+
+    async fn write_processed_block(
+        &self,
+        block_number: u32,
+        block: Option<&Block>,
+        merkle_root: H256,
+        root: &MerkleRoot,
+    ) -> anyhow::Result<()> {
+        let mut batch = sled::Batch::default();
+        block_to_batch(&mut batch, block_number, block)?;
+
+        self.apply_with_root(batch, block_number, merkle_root, root)
+            .await
+    }
+
+    async fn write_root(
+        &self,
+        block_number: u32,
+        merkle_root: H256,
+        root: &MerkleRoot,
+    ) -> anyhow::Result<()> {
+        self.apply_with_root(sled::Batch::default(), block_number, merkle_root, root)
+            .await
+    }
+
+    async fn remove_blocks_before(&self, block_number: u32) -> anyhow::Result<()> {
+        let mut batch = sled::Batch::default();
+        for entry in self.db.range(block_key(0)..block_key(block_number)) {
+            batch.remove(entry?.0);
+        }
+
+        self.apply(batch).await
+    }
+
+    async fn write_submitted(
+        &self,
+        block_number: u32,
+        merkle_root: H256,
+        submitted: bool,
+    ) -> anyhow::Result<()> {
+        let key = root_key(SUBMITTED_PREFIX, block_number, merkle_root);
+        let mut batch = sled::Batch::default();
+        if submitted {
+            batch.insert(key, Vec::new());
+        } else {
+            batch.remove(key);
+        }
+
+        self.apply(batch).await
+    }
+
+    async fn write_transactions(
+        &self,
+        block_number: u32,
+        merkle_root: H256,
+        transactions: Option<&SubmissionTransactions>,
+    ) -> anyhow::Result<()> {
+        let key = root_key(TRANSACTIONS_PREFIX, block_number, merkle_root);
+        let mut batch = sled::Batch::default();
+        match transactions {
+            Some(transactions) => batch.insert(key, serde_json::to_vec(transactions)?),
+            None => batch.remove(key),
+        }
+
+        self.apply(batch).await
+    }
+
+    async fn write_spending(&self, spending: DailySpending) -> anyhow::Result<()> {
+        let mut batch = sled::Batch::default();
+        batch.insert(SPENDING_KEY, serde_json::to_vec(&spending)?);
+
+        self.apply(batch).await
+    }
+
+    async fn save(&self, snapshot: StateSnapshot<'_>) -> anyhow::Result<()> {
+        // Blocks, submission state and spending are written through as they change, so only
+        // merkle roots which have changed since then need to be persisted here.
+        let mut batch = sled::Batch::default();
+        {
+            let mut cache = self.cache.lock().expect("cache lock poisoned");
+            cache.write_roots(&mut batch, snapshot.roots)?;
+        }
+
+        self.apply(batch).await
+    }
+}
+
+fn block_to_batch(
+    batch: &mut sled::Batch,
+    block_number: u32,
+    block: Option<&Block>,
+) -> anyhow::Result<()> {
+    match block {
+        Some(block) => batch.insert(block_key(block_number).to_vec(), serde_json::to_vec(block)?),
+        None => batch.remove(block_key(block_number).to_vec()),
+    }
+
+    Ok(())
+}
+
+/// Current unix timestamp in seconds.
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn fingerprint(value: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn block_key(block_number: u32) -> [u8; 5] {
+    let mut key = [BLOCK_PREFIX; 5];
+    key[1..].copy_from_slice(&block_number.to_be_bytes());
+    key
+}
+
+fn block_number(key: &[u8]) -> anyhow::Result<u32> {
+    let bytes = key
+        .get(1..5)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("Invalid block key: {}", hex::encode(key)))?;
+
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn root_key(prefix: u8, block_number: u32, merkle_root: H256) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + 4 + 32);
+    key.push(prefix);
+    key.extend_from_slice(&block_number.to_be_bytes());
+    key.extend_from_slice(merkle_root.as_bytes());
+    key
+}
+
+fn root_key_parts(key: &[u8]) -> anyhow::Result<(u32, H256)> {
+    if key.len() != 1 + 4 + 32 {
+        anyhow::bail!("Invalid merkle root key: {}", hex::encode(key));
+    }
+
+    Ok((block_number(key)?, H256::from_slice(&key[5..])))
+}
+
+fn transition_key(index: u64) -> [u8; 9] {
+    let mut key = [TRANSITION_PREFIX; 9];
+    key[1..].copy_from_slice(&index.to_be_bytes());
+    key
+}
+
+fn transition_index(key: &[u8]) -> anyhow::Result<u64> {
+    let bytes = key
+        .get(1..9)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("Invalid transition key: {}", hex::encode(key)))?;
+
+    Ok(u64::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gear_rpc_client::dto::RawBlockInclusionProof;
+    use std::collections::{BTreeMap, HashSet};
+
+    fn inclusion_proof(block_number: u32) -> RawBlockInclusionProof {
+        RawBlockInclusionProof {
+            justification_round: 0,
+            required_authority_set_id: 1,
+            validator_set: vec![],
+            block_hash: H256::repeat_byte(block_number as u8),
+            block_number,
+            pre_commits: vec![],
+        }
+    }
+
+    fn block(block_number: u32) -> Block {
+        Block {
+            block_hash: H256::repeat_byte(block_number as u8),
+            merkle_root_changed: Some((0, H256::repeat_byte(0xAA))),
+            authority_set_changed: false,
+            inclusion_proof: inclusion_proof(block_number),
+        }
+    }
+
+    fn merkle_root(block_number: u32, status: MerkleRootStatus) -> MerkleRoot {
+        MerkleRoot {
+            block_number,
+            block_hash: H256::repeat_byte(block_number as u8),
+            queue_id: 0,
+            message_nonces: vec![],
+            priority: false,
+            http_requests: vec![],
+            proof: None,
+            status,
+            block_inclusion_proof: inclusion_proof(block_number),
+        }
+    }
+
+    fn temporary_db() -> sled::Db {
+        sled::Config::new().temporary(true).open().unwrap()
+    }
+
+    /// Path of a JSON snapshot which is never created.
+    fn missing_snapshot() -> PathBuf {
+        std::env::temp_dir().join(format!("merkle-roots-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    fn transactions(block_number: u32, hashes: usize) -> SubmissionTransactions {
+        SubmissionTransactions {
+            block_number,
+            merkle_root: H256::repeat_byte(0xAA),
+            nonce: 7,
+            hashes: (0..hashes)
+                .map(|index| H256::repeat_byte(index as u8))
+                .collect(),
+            max_fee_per_gas: 2_000_000_000,
+            max_priority_fee_per_gas: 500_000_000,
+        }
+    }
+
+    fn statuses(
+        transitions: &[StatusTransition],
+    ) -> Vec<(Option<MerkleRootStatus>, Option<MerkleRootStatus>)> {
+        transitions
+            .iter()
+            .map(|transition| (transition.from.clone(), transition.to.clone()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn records_status_transitions() {
+        let backend = DatabaseBackend::from_db(temporary_db(), missing_snapshot())
+            .await
+            .unwrap();
+        let key = (10, H256::repeat_byte(0xAA));
+        let blocks = BTreeMap::new();
+        let submitted_merkle_roots = HashSet::new();
+        let mut roots = HashMap::new();
+
+        for status in [
+            MerkleRootStatus::GenerateProof,
+            MerkleRootStatus::GenerateProof,
+            MerkleRootStatus::SubmitProof,
+            MerkleRootStatus::Finalized,
+        ] {
+            roots.insert(key, merkle_root(key.0, status));
+            backend
+                .save(StateSnapshot {
+                    blocks: &blocks,
+                    submitted_merkle_roots: &submitted_merkle_roots,
+                    roots: &roots,
+                    transactions: &HashMap::new(),
+                    spending: DailySpending::default(),
+                })
+                .await
+                .unwrap();
+        }
+
+        roots.clear();
+        backend
+            .save(StateSnapshot {
+                blocks: &blocks,
+                submitted_merkle_roots: &submitted_merkle_roots,
+                roots: &roots,
+                transactions: &HashMap::new(),
+                spending: DailySpending::default(),
+            })
+            .await
+            .unwrap();
+
+        let transitions = backend.transitions().unwrap();
+        assert!(transitions
+            .iter()
+            .all(|transition| (transition.block_number, transition.merkle_root) == key));
+        assert_eq!(
+            statuses(&transitions),
+            vec![
+                (None, Some(MerkleRootStatus::GenerateProof)),
+                (
+                    Some(MerkleRootStatus::GenerateProof),
+                    Some(MerkleRootStatus::SubmitProof)
+                ),
+                (
+                    Some(MerkleRootStatus::SubmitProof),
+                    Some(MerkleRootStatus::Finalized)
+                ),
+                (Some(MerkleRootStatus::Finalized), None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_blocks_and_submissions_through() {
+        let db = temporary_db();
+        let backend = DatabaseBackend::from_db(db.clone(), missing_snapshot())
+            .await
+            .unwrap();
+        let root = H256::repeat_byte(0xAA);
+
+        for block_number in 1..=3 {
+            backend
+                .write_block(block_number, Some(&block(block_number)))
+                .await
+                .unwrap();
+        }
+        backend.write_block(2, None).await.unwrap();
+        backend.remove_blocks_before(2).await.unwrap();
+        backend.write_submitted(3, root, true).await.unwrap();
+        backend.write_submitted(4, root, true).await.unwrap();
+        backend.write_submitted(4, root, false).await.unwrap();
+        backend
+            .write_transactions(3, root, Some(&transactions(3, 1)))
+            .await
+            .unwrap();
+        backend
+            .write_transactions(3, root, Some(&transactions(3, 2)))
+            .await
+            .unwrap();
+        backend
+            .write_transactions(4, root, Some(&transactions(4, 1)))
+            .await
+            .unwrap();
+        backend.write_transactions(4, root, None).await.unwrap();
+        backend
+            .write_spending(DailySpending { day: 2, spent: 5 })
+            .await
+            .unwrap();
+        drop(backend);
+
+        let backend = DatabaseBackend::from_db(db, missing_snapshot())
+            .await
+            .unwrap();
+        let state = backend.load().await.unwrap().unwrap();
+        assert_eq!(state.blocks.keys().copied().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(
+            state.submitted_merkle_roots,
+            HashSet::from_iter([(3, root)])
+        );
+        assert_eq!(
+            state.transactions,
+            HashMap::from_iter([((3, root), transactions(3, 2))])
+        );
+        assert_eq!(state.spending, DailySpending { day: 2, spent: 5 });
+    }
+
+    #[tokio::test]
+    async fn writes_processed_block_with_merkle_root() {
+        let db = temporary_db();
+        let backend = DatabaseBackend::from_db(db.clone(), missing_snapshot())
+            .await
+            .unwrap();
+        let root = H256::repeat_byte(0xAA);
+
+        backend.write_block(7, Some(&block(7))).await.unwrap();
+        backend
+            .write_processed_block(
+                7,
+                None,
+                root,
+                &merkle_root(7, MerkleRootStatus::GenerateProof),
+            )
+            .await
+            .unwrap();
+        drop(backend);
+
+        let backend = DatabaseBackend::from_db(db, missing_snapshot())
+            .await
+            .unwrap();
+        let state = backend.load().await.unwrap().unwrap();
+        assert!(state.blocks.is_empty());
+        assert_eq!(
+            state.roots[&(7, root)].status,
+            MerkleRootStatus::GenerateProof
+        );
+        assert_eq!(
+            statuses(&backend.transitions().unwrap()),
+            vec![(None, Some(MerkleRootStatus::GenerateProof))]
+        );
+
+        // Unchanged merkle root isn't written again by the snapshot.
+        let roots =
+            HashMap::from_iter([((7, root), merkle_root(7, MerkleRootStatus::GenerateProof))]);
+        backend
+            .save(StateSnapshot {
+                blocks: &BTreeMap::new(),
+                submitted_merkle_roots: &HashSet::new(),
+                roots: &roots,
+                transactions: &HashMap::new(),
+                spending: DailySpending::default(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(backend.transitions().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn writes_root_status_transitions_through() {
+        let db = temporary_db();
+        let backend = DatabaseBackend::from_db(db.clone(), missing_snapshot())
+            .await
+            .unwrap();
+        let root = H256::repeat_byte(0xAA);
+
+        for status in [
+            MerkleRootStatus::GenerateProof,
+            MerkleRootStatus::SubmitProof,
+            MerkleRootStatus::Finalized,
+        ] {
+            backend
+                .write_root(9, root, &merkle_root(9, status))
+                .await
+                .unwrap();
+        }
+        drop(backend);
+
+        let backend = DatabaseBackend::from_db(db, missing_snapshot())
+            .await
+            .unwrap();
+        let state = backend.load().await.unwrap().unwrap();
+        assert_eq!(state.roots[&(9, root)].status, MerkleRootStatus::Finalized);
+        assert_eq!(
+            statuses(&backend.transitions().unwrap()),
+            vec![
+                (None, Some(MerkleRootStatus::GenerateProof)),
+                (
+                    Some(MerkleRootStatus::GenerateProof),
+                    Some(MerkleRootStatus::SubmitProof)
+                ),
+                (
+                    Some(MerkleRootStatus::SubmitProof),
+                    Some(MerkleRootStatus::Finalized)
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn migrates_json_snapshot_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot_path = dir.path().join("merkle-roots.json");
+        let json = JsonSnapshotBackend::new(snapshot_path.clone());
+        let blocks = BTreeMap::from_iter([(5, block(5))]);
+        let submitted_merkle_roots = HashSet::from_iter([(5, H256::repeat_byte(0xAA))]);
+        let roots = HashMap::from_iter([(
+            (5, H256::repeat_byte(0xAA)),
+            merkle_root(5, MerkleRootStatus::SubmitProof),
+        )]);
+        let submission_transactions =
+            HashMap::from_iter([((5, H256::repeat_byte(0xAA)), transactions(5, 3))]);
+        json.save(StateSnapshot {
+            blocks: &blocks,
+            submitted_merkle_roots: &submitted_merkle_roots,
+            roots: &roots,
+            transactions: &submission_transactions,
+            spending: DailySpending { day: 3, spent: 7 },
+        })
+        .await
+        .unwrap();
+
+        let db = temporary_db();
+        let backend = DatabaseBackend::from_db(db.clone(), snapshot_path.clone())
+            .await
+            .unwrap();
+        let state = backend.load().await.unwrap().unwrap();
+        assert_eq!(state.blocks.keys().copied().collect::<Vec<_>>(), vec![5]);
+        assert_eq!(state.submitted_merkle_roots, submitted_merkle_roots);
+        assert_eq!(state.transactions, submission_transactions);
+        assert_eq!(state.spending, DailySpending { day: 3, spent: 7 });
+        assert_eq!(
+            state.roots[&(5, H256::repeat_byte(0xAA))].status,
+            MerkleRootStatus::SubmitProof
+        );
+        assert_eq!(
+            statuses(&backend.transitions().unwrap()),
+            vec![(None, Some(MerkleRootStatus::SubmitProof))]
+        );
+        drop(backend);
+
+        // Snapshot is not imported again once the database is initialized.
+        json.save(StateSnapshot {
+            blocks: &BTreeMap::new(),
+            submitted_merkle_roots: &HashSet::new(),
+            roots: &HashMap::new(),
+            transactions: &HashMap::new(),
+            spending: DailySpending::default(),
+        })
+        .await
+        .unwrap();
+        let backend = DatabaseBackend::from_db(db, snapshot_path.clone())
+            .await
+            .unwrap();
+        let state = backend.load().await.unwrap().unwrap();
+        assert_eq!(state.roots.len(), 1);
+        assert_eq!(backend.transitions().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,232 @@
+use super::{Block, PersistedState, StateBackend, StateSnapshot, SubmissionTransactions};
+use crate::merkle_roots::{policy::DailySpending, MerkleRoot};
+use primitive_types::H256;
+use serde::{ser::SerializeStruct, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use tokio::io::AsyncWriteExt;
+
+/// Backend which keeps the whole state in a single JSON file. The file is rewritten
+/// on every [`StateBackend::save`] call so changes in between are not persisted.
+pub struct JsonSnapshotBackend {
+    path: PathBuf,
+}
+
+impl JsonSnapshotBackend {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[async_trait::async_trait]
+impl StateBackend for JsonSnapshotBackend {
+    async fn load(&self) -> anyhow::Result<Option<PersistedState>> {
+        let contents = match tokio::fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let DeserializedStorage {
+            blocks,
+            submitted_merkle_roots,
+            roots,
+            transactions,
+            spending,
+        } = serde_json::from_str(&contents)?;
+
+        Ok(Some(PersistedState {
+            blocks,
+            submitted_merkle_roots,
+            roots,
+            transactions,
+            spending,
+        }))
+    }
+
+    async fn write_block(&self, _block_number: u32, _block: Option<&Block>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn write_processed_block(
+        &self,
+        _block_number: u32,
+        _block: Option<&Block>,
+        _merkle_root: H256,
+        _root: &MerkleRoot,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn write_root(
+        &self,
+        _block_number: u32,
+        _merkle_root: H256,
+        _root: &MerkleRoot,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn remove_blocks_before(&self, _block_number: u32) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn write_submitted(
+        &self,
+        _block_number: u32,
+        _merkle_root: H256,
+        _submitted: bool,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn write_transactions(
+        &self,
+        _block_number: u32,
+        _merkle_root: H256,
+        _transactions: Option<&SubmissionTransactions>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn write_spending(&self, _spending: DailySpending) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn save(&self, snapshot: StateSnapshot<'_>) -> anyhow::Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)
+            .await?;
+
+        let storage = SerializedStorage {
+            blocks: snapshot.blocks,
+            submitted_merkle_roots: snapshot.submitted_merkle_roots,
+            roots: snapshot.roots,
+            transactions: snapshot.transactions,
+            spending: snapshot.spending,
+        };
+
+        let serialized = serde_json::to_string(&storage)?;
+
+        file.write_all(serialized.as_bytes()).await?;
+        file.flush().await?;
+        drop(file);
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+
+        Ok(())
+    }
+}
+
+struct SerializedStorage<'a> {
+    blocks: &'a BTreeMap<u32, Block>,
+    submitted_merkle_roots: &'a HashSet<(u32, H256)>,
+    roots: &'a HashMap<(u32, H256), MerkleRoot>,
+    transactions: &'a HashMap<(u32, H256), SubmissionTransactions>,
+    spending: DailySpending,
+}
+
+impl Serialize for SerializedStorage<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut state = serializer.serialize_struct("MerkleRootStorage", 5)?;
+        state.serialize_field("blocks", &self.blocks)?;
+        state.serialize_field("submitted_merkle_roots", &self.submitted_merkle_roots)?;
+
+        let roots_hex: HashMap<String, MerkleRoot> = self
+            .roots
+            .iter()
+            .map(|((block, hash), root)| {
+                let key = format!("{}-{}", block, hex::encode(hash.as_bytes()));
+                (key, root.clone())
+            })
+            .collect();
+        state.serialize_field("roots", &roots_hex)?;
+        state.serialize_field(
+            "submission_transactions",
+            &self.transactions.values().collect::<Vec<_>>(),
+        )?;
+        state.serialize_field("submission_spending", &self.spending)?;
+        state.end()
+    }
+}
+
+struct DeserializedStorage {
+    blocks: BTreeMap<u32, Block>,
+    submitted_merkle_roots: HashSet<(u32, H256)>,
+    roots: HashMap<(u32, H256), MerkleRoot>,
+    transactions: HashMap<(u32, H256), SubmissionTransactions>,
+    spending: DailySpending,
+}
+impl<'de> Deserialize<'de> for DeserializedStorage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Helper {
+            blocks: BTreeMap<u32, Block>,
+            submitted_merkle_roots: HashSet<(u32, H256)>,
+            roots: HashMap<String, MerkleRoot>,
+            #[serde(default)]
+            submission_transactions: Vec<SubmissionTransactions>,
+            #[serde(default)]
+            submission_spending: DailySpending,
+        }
+
+        let helper = Helper::deserialize(deserializer)?;
+
+        let roots = helper
+            .roots
+            .into_iter()
+            .map(|(key, root)| {
+                let parts: Vec<&str> = key.splitn(2, '-').collect();
+                if parts.len() != 2 {
+                    return Err(serde::de::Error::custom("Invalid root key format"));
+                }
+
+                let block: u32 = parts[0]
+                    .parse()
+                    .map_err(|_| serde::de::Error::custom("Invalid block number"))?;
+
+                let hash_bytes = hex::decode(parts[1])
+                    .map_err(|_| serde::de::Error::custom("Invalid hash format"))?;
+
+                if hash_bytes.len() != 32 {
+                    return Err(serde::de::Error::custom("Hash must be 32 bytes"));
+                }
+
+                let hash = H256::from_slice(&hash_bytes);
+                Ok(((block, hash), root))
+            })
+            .collect::<Result<HashMap<(u32, H256), MerkleRoot>, D::Error>>()?;
+
+        Ok(DeserializedStorage {
+            blocks: helper.blocks,
+            submitted_merkle_roots: helper.submitted_merkle_roots,
+            roots,
+            transactions: helper
+                .submission_transactions
+                .into_iter()
+                .map(|transactions| {
+                    (
+                        (transactions.block_number, transactions.merkle_root),
+                        transactions,
+                    )
+                })
+                .collect(),
+            spending: helper.submission_spending,
+        })
+    }
+}
@@ -0,0 +1,538 @@
+use crate::{
+    merkle_roots::{policy::DailySpending, MerkleRoot},
+    message_relayer::common::{
+        gear::block_storage::{UnprocessedBlocks, UnprocessedBlocksStorage},
+        GearBlock,
+    },
+    proof_storage::ProofStorage,
+};
+use anyhow::Context;
+use gear_rpc_client::dto::RawBlockInclusionProof;
+use primitive_types::{H256, U256};
+use sails_rs::events::EventIo;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{btree_map::Entry, BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+};
+use tokio::sync::RwLock;
+
+mod database;
+mod json;
+
+pub use database::{DatabaseBackend, StatusTransition};
+pub use json::JsonSnapshotBackend;
+
+/// State of the merkle root relayer. Changes are written through the [`StateBackend`] first
+/// and applied in memory only once they're persisted, so memory never gets ahead of the disk.
+pub struct MerkleRootStorage {
+    pub proofs: Arc<dyn ProofStorage>,
+    pub blocks: RwLock<BTreeMap<u32, Block>>,
+    pub submitted_roots: RwLock<HashSet<(u32, H256)>>,
+    transactions: RwLock<HashMap<(u32, H256), SubmissionTransactions>>,
+    spending: RwLock<DailySpending>,
+    pub path: PathBuf,
+    backend: Box<dyn StateBackend>,
+}
+
+/// State of [`MerkleRootStorage`] as it is persisted by a [`StateBackend`].
+#[derive(Default)]
+pub struct PersistedState {
+    pub blocks: BTreeMap<u32, Block>,
+    pub submitted_merkle_roots: HashSet<(u32, H256)>,
+    pub roots: HashMap<(u32, H256), MerkleRoot>,
+    pub transactions: HashMap<(u32, H256), SubmissionTransactions>,
+    pub spending: DailySpending,
+}
+
+/// Borrowed view of the whole storage state, passed to [`StateBackend::save`].
+pub struct StateSnapshot<'a> {
+    pub blocks: &'a BTreeMap<u32, Block>,
+    pub submitted_merkle_roots: &'a HashSet<(u32, H256)>,
+    pub roots: &'a HashMap<(u32, H256), MerkleRoot>,
+    pub transactions: &'a HashMap<(u32, H256), SubmissionTransactions>,
+    pub spending: DailySpending,
+}
+
+/// Persistence backend of [`MerkleRootStorage`].
+///
+/// Backends which are able to persist individual changes do so in `write_*` methods, snapshot
+/// based backends persist everything in [`StateBackend::save`].
+#[async_trait::async_trait]
+pub trait StateBackend: Send + Sync {
+    /// Loads persisted state. Returns `None` if nothing has been persisted yet.
+    async fn load(&self) -> anyhow::Result<Option<PersistedState>>;
+
+    /// Persists the block state. `None` means the block is removed.
+    async fn write_block(&self, block_number: u32, block: Option<&Block>) -> anyhow::Result<()>;
+
+    /// Persists the block state together with the merkle root found in it, so the block is
+    /// never removed without the merkle root status being persisted as well.
+    async fn write_processed_block(
+        &self,
+        block_number: u32,
+        block: Option<&Block>,
+        merkle_root: H256,
+        root: &MerkleRoot,
+    ) -> anyhow::Result<()>;
+
+    /// Persists the merkle root state. Called on every status transition of the merkle root.
+    async fn write_root(
+        &self,
+        block_number: u32,
+        merkle_root: H256,
+        root: &MerkleRoot,
+    ) -> anyhow::Result<()>;
+
+    /// Removes all blocks with a number lower than `block_number`.
+    async fn remove_blocks_before(&self, block_number: u32) -> anyhow::Result<()>;
+
+    async fn write_submitted(
+        &self,
+        block_number: u32,
+        merkle_root: H256,
+        submitted: bool,
+    ) -> anyhow::Result<()>;
+
+    /// Persists transactions sent for a merkle root. `None` means they are forgotten.
+    async fn write_transactions(
+        &self,
+        block_number: u32,
+        merkle_root: H256,
+        transactions: Option<&SubmissionTransactions>,
+    ) -> anyhow::Result<()>;
+
+    /// Persists spending of the submission policy.
+    async fn write_spending(&self, spending: DailySpending) -> anyhow::Result<()>;
+
+    async fn save(&self, snapshot: StateSnapshot<'_>) -> anyhow::Result<()>;
+}
+
+/// Transactions submitting a merkle root to Ethereum. All of them share the same nonce,
+/// so at most one of them gets mined.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmissionTransactions {
+    /// Gear block number the merkle root is proven for.
+    pub block_number: u32,
+    pub merkle_root: H256,
+    pub nonce: u64,
+    /// Hashes of the original transaction and all of its replacements, oldest first.
+    pub hashes: Vec<H256>,
+    /// Fee caps of the latest replacement, in wei.
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Block {
+    pub block_hash: H256,
+    pub merkle_root_changed: Option<(u64, H256)>,
+    pub authority_set_changed: bool,
+    pub inclusion_proof: RawBlockInclusionProof,
+}
+
+impl Block {
+    pub fn is_processed(&self) -> bool {
+        self.merkle_root_changed.is_none() && !self.authority_set_changed
+    }
+}
+
+pub(super) fn queue_merkle_root_changed(block: &GearBlock) -> Option<(u64, H256)> {
+    block.events().iter().find_map(|event| match event {
+        gsdk::Event::GearEthBridge(
+            gsdk::gear::gear_eth_bridge::Event::QueueMerkleRootChanged { queue_id, root },
+        ) => Some((*queue_id, *root)),
+        _ => None,
+    })
+}
+
+pub(super) fn message_queued_events_of(block: &GearBlock) -> impl Iterator<Item = U256> + use<'_> {
+    block.events().iter().filter_map(|event| match event {
+        gsdk::Event::GearEthBridge(gsdk::gear::gear_eth_bridge::Event::MessageQueued {
+            message,
+            ..
+        }) => Some(U256(message.nonce.0)),
+        _ => None,
+    })
+}
+
+pub(super) fn priority_bridging_paid<'a>(
+    block: &'a GearBlock,
+    bridging_payment_address: H256,
+) -> impl Iterator<Item = (H256, U256)> + 'a {
+    block
+        .user_message_sent_events(bridging_payment_address, H256::default())
+        .filter_map(|payload| {
+            match bridging_payment_client::bridging_payment::events::BridgingPaymentEvents::decode_event(payload) {
+                Ok(bridging_payment_client::bridging_payment::events::BridgingPaymentEvents::PriorityBridgingPaid {
+                    block,
+                    nonce,
+                }) => Some((H256::from(block.0), U256(nonce.0))),
+                _ => None,
+            }
+        })
+}
+
+pub(super) fn authority_set_changed(block: &GearBlock) -> bool {
+    block
+        .events()
+        .iter()
+        .find_map(|event| match event {
+            gsdk::Event::GearEthBridge(
+                gsdk::gear::gear_eth_bridge::Event::AuthoritySetHashChanged(_),
+            )
+            | gsdk::Event::Grandpa(gsdk::gear::grandpa::Event::NewAuthorities { .. }) => Some(()),
+            _ => None,
+        })
+        .is_some()
+}
+
+#[async_trait::async_trait]
+impl UnprocessedBlocksStorage for MerkleRootStorage {
+    async fn unprocessed_blocks(&self) -> UnprocessedBlocks {
+        let blocks = self.blocks.read().await;
+        let first_block = blocks.first_key_value().map(|(k, v)| (v.block_hash, *k));
+        UnprocessedBlocks {
+            blocks: blocks.iter().map(|(k, v)| (v.block_hash, *k)).collect(),
+            last_block: None,
+            first_block,
+        }
+    }
+
+    async fn add_block(
+        &self,
+        api: &gear_rpc_client::GearApi,
+        block: &GearBlock,
+    ) -> anyhow::Result<()> {
+        let merkle_root_changed = queue_merkle_root_changed(block);
+        let authority_set_changed = authority_set_changed(block);
+
+        let proof = api
+            .produce_finality_proof(&block.grandpa_justification)
+            .await?;
+
+        let block_hash = block.hash();
+        let block_number = block.number();
+
+        let mut blocks = self.blocks.write().await;
+        let Entry::Vacant(entry) = blocks.entry(block_number) else {
+            log::warn!("Block #{block_number} already exists in storage");
+            return Ok(());
+        };
+
+        let block = Block {
+            block_hash,
+            merkle_root_changed,
+            authority_set_changed,
+            inclusion_proof: proof,
+        };
+        self.backend
+            .write_block(block_number, Some(&block))
+            .await
+            .with_context(|| format!("Failed to persist state of block #{block_number}"))?;
+        entry.insert(block);
+
+        Ok(())
+    }
+}
+
+impl MerkleRootStorage {
+    /// Creates storage which keeps its state in JSON snapshot at `path`.
+    pub fn new(proofs: Arc<dyn ProofStorage>, path: PathBuf) -> Arc<Self> {
+        let backend = Box::new(JsonSnapshotBackend::new(path.clone()));
+        Self::with_backend(proofs, path, backend)
+    }
+
+    pub fn with_backend(
+        proofs: Arc<dyn ProofStorage>,
+        path: PathBuf,
+        backend: Box<dyn StateBackend>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            proofs,
+            blocks: RwLock::new(BTreeMap::new()),
+            submitted_roots: RwLock::new(HashSet::new()),
+            transactions: RwLock::new(HashMap::new()),
+            spending: RwLock::new(DailySpending::default()),
+            path,
+            backend,
+        })
+    }
+
+    pub async fn is_merkle_root_submitted(&self, block: u32, merkle_root: H256) -> bool {
+        self.submitted_roots
+            .read()
+            .await
+            .contains(&(block, merkle_root))
+    }
+
+    pub async fn submitted_merkle_root(&self, block: u32, merkle_root: H256) -> anyhow::Result<()> {
+        let mut submitted_roots = self.submitted_roots.write().await;
+        if !submitted_roots.contains(&(block, merkle_root)) {
+            self.write_submitted(block, merkle_root, true).await?;
+            submitted_roots.insert((block, merkle_root));
+        }
+
+        Ok(())
+    }
+
+    pub async fn submission_failed(&self, block: u32, merkle_root: H256) -> anyhow::Result<()> {
+        let mut submitted_roots = self.submitted_roots.write().await;
+        if submitted_roots.contains(&(block, merkle_root)) {
+            self.write_submitted(block, merkle_root, false).await?;
+            submitted_roots.remove(&(block, merkle_root));
+        }
+
+        Ok(())
+    }
+
+    async fn write_submitted(
+        &self,
+        block: u32,
+        merkle_root: H256,
+        submitted: bool,
+    ) -> anyhow::Result<()> {
+        self.backend
+            .write_submitted(block, merkle_root, submitted)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to persist submission state of merkle root {merkle_root} for block #{block}"
+                )
+            })
+    }
+
+    pub async fn submission_transactions(
+        &self,
+        block: u32,
+        merkle_root: H256,
+    ) -> Option<SubmissionTransactions> {
+        self.transactions
+            .read()
+            .await
+            .get(&(block, merkle_root))
+            .cloned()
+    }
+
+    /// Transactions of all merkle roots which are being submitted, ordered by block number.
+    pub async fn tracked_transactions(&self) -> Vec<SubmissionTransactions> {
+        let mut transactions: Vec<_> = self.transactions.read().await.values().cloned().collect();
+        transactions.sort_by_key(|transactions| transactions.block_number);
+        transactions
+    }
+
+    /// Records the latest transaction sent for a merkle root, replacing the previous record.
+    pub async fn track_transactions(
+        &self,
+        transactions: SubmissionTransactions,
+    ) -> anyhow::Result<()> {
+        let key = (transactions.block_number, transactions.merkle_root);
+        let mut tracked = self.transactions.write().await;
+        self.write_transactions(key.0, key.1, Some(&transactions))
+            .await?;
+        tracked.insert(key, transactions);
+
+        Ok(())
+    }
+
+    /// Forgets transactions of a merkle root once the submission is confirmed or abandoned.
+    pub async fn forget_transactions(&self, block: u32, merkle_root: H256) -> anyhow::Result<()> {
+        let mut tracked = self.transactions.write().await;
+        if tracked.contains_key(&(block, merkle_root)) {
+            self.write_transactions(block, merkle_root, None).await?;
+            tracked.remove(&(block, merkle_root));
+        }
+
+        Ok(())
+    }
+
+    async fn write_transactions(
+        &self,
+        block: u32,
+        merkle_root: H256,
+        transactions: Option<&SubmissionTransactions>,
+    ) -> anyhow::Result<()> {
+        self.backend
+            .write_transactions(block, merkle_root, transactions)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to persist transactions of merkle root {merkle_root} for block #{block}"
+                )
+            })
+    }
+
+    pub async fn spending(&self) -> DailySpending {
+        *self.spending.read().await
+    }
+
+    /// Records spending of the submission policy, so that a restart doesn't reset the
+    /// daily budget.
+    pub async fn record_spending(&self, spending: DailySpending) -> anyhow::Result<()> {
+        let mut current = self.spending.write().await;
+        self.backend
+            .write_spending(spending)
+            .await
+            .context("Failed to persist submission spending")?;
+        *current = spending;
+
+        Ok(())
+    }
+
+    /// Persists a merkle root whose status has changed, so the transition survives a crash.
+    pub async fn write_root(
+        &self,
+        block: u32,
+        merkle_root: H256,
+        root: &MerkleRoot,
+    ) -> anyhow::Result<()> {
+        self.backend
+            .write_root(block, merkle_root, root)
+            .await
+            .with_context(|| {
+                format!("Failed to persist merkle root {merkle_root} for block #{block}")
+            })
+    }
+
+    /// Marks the merkle root of a block as processed. The block state is persisted together
+    /// with `root`, the state the merkle root is left in, if it's tracked.
+    pub async fn merkle_root_processed(
+        &self,
+        block_number: u32,
+        merkle_root: H256,
+        root: Option<&MerkleRoot>,
+    ) -> anyhow::Result<()> {
+        let mut blocks = self.blocks.write().await;
+
+        let Some(block) = blocks.get(&block_number) else {
+            return Ok(());
+        };
+
+        let block = block.authority_set_changed.then(|| Block {
+            merkle_root_changed: None,
+            ..block.clone()
+        });
+
+        match root {
+            Some(root) => self
+                .backend
+                .write_processed_block(block_number, block.as_ref(), merkle_root, root)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to persist state of block #{block_number} with merkle root {merkle_root}"
+                    )
+                })?,
+            None => self.write_block(block_number, block.as_ref()).await?,
+        }
+
+        set_block(&mut blocks, block_number, block);
+
+        Ok(())
+    }
+
+    pub async fn authority_set_processed(&self, block_number: u32) -> anyhow::Result<()> {
+        let mut blocks = self.blocks.write().await;
+
+        let Some(block) = blocks.get(&block_number) else {
+            return Ok(());
+        };
+
+        let block = block.merkle_root_changed.is_some().then(|| Block {
+            authority_set_changed: false,
+            ..block.clone()
+        });
+        self.write_block(block_number, block.as_ref()).await?;
+        set_block(&mut blocks, block_number, block);
+
+        Ok(())
+    }
+
+    async fn write_block(&self, block_number: u32, block: Option<&Block>) -> anyhow::Result<()> {
+        self.backend
+            .write_block(block_number, block)
+            .await
+            .with_context(|| format!("Failed to persist state of block #{block_number}"))
+    }
+
+    /// Save unprocessed blocks and merkle roots to the storage backend.
+    pub async fn save(&self, roots: &HashMap<(u32, H256), MerkleRoot>) -> anyhow::Result<()> {
+        self.prune_blocks().await?;
+        let blocks = self.blocks.read().await;
+        let submitted_merkle_roots = self.submitted_roots.read().await;
+        let transactions = self.transactions.read().await;
+        let spending = *self.spending.read().await;
+
+        self.backend
+            .save(StateSnapshot {
+                blocks: &blocks,
+                submitted_merkle_roots: &submitted_merkle_roots,
+                roots,
+                transactions: &transactions,
+                spending,
+            })
+            .await
+    }
+
+    pub async fn load(&self) -> anyhow::Result<HashMap<(u32, H256), MerkleRoot>> {
+        let Some(PersistedState {
+            blocks,
+            submitted_merkle_roots,
+            roots,
+            transactions,
+            spending,
+        }) = self.backend.load().await?
+        else {
+            log::info!(
+                "No merkle root state found at {}, starting from scratch",
+                self.path.display()
+            );
+            return Ok(HashMap::new());
+        };
+
+        *self.blocks.write().await = blocks;
+        *self.submitted_roots.write().await = submitted_merkle_roots;
+        *self.transactions.write().await = transactions;
+        *self.spending.write().await = spending;
+        Ok(roots)
+    }
+
+    pub async fn prune_blocks(&self) -> anyhow::Result<()> {
+        let mut blocks = self.blocks.write().await;
+        let mut remove_until = None;
+        for (index, (number, block)) in blocks.iter().enumerate() {
+            if index + 100 > blocks.len() {
+                remove_until = Some(*number);
+                break;
+            }
+
+            if !block.is_processed() {
+                remove_until = Some(*number);
+                break;
+            }
+        }
+
+        if let Some(remove_until) = remove_until {
+            self.backend
+                .remove_blocks_before(remove_until)
+                .await
+                .with_context(|| format!("Failed to prune blocks before #{remove_until}"))?;
+            *blocks = blocks.split_off(&remove_until);
+        }
+
+        Ok(())
+    }
+}
+
+/// Applies a block state which has been persisted. `None` means the block is removed.
+fn set_block(blocks: &mut BTreeMap<u32, Block>, block_number: u32, block: Option<Block>) {
+    match block {
+        Some(block) => {
+            blocks.insert(block_number, block);
+        }
+        None => {
+            blocks.remove(&block_number);
+        }
+    }
+}
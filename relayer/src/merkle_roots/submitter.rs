@@ -1,26 +1,140 @@
-use alloy::{
-    network::Ethereum,
-    providers::{PendingTransactionBuilder, PendingTransactionError},
-    rpc::types::TransactionReceipt,
-};
-use ethereum_client::EthApi;
+use alloy::rpc::types::TransactionReceipt;
+use ethereum_client::{EthApi, FeeCaps, SignedTransaction, TxHash};
 use futures::{stream::FuturesUnordered, StreamExt};
 use primitive_types::H256;
 use prometheus::{
     core::{AtomicU64, GenericCounter, GenericGauge},
     Gauge, IntCounter, IntGauge,
 };
-use std::sync::Arc;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender, WeakUnboundedSender},
+        watch,
+    },
+    time::Instant,
+};
 use utils_prometheus::{impl_metered_service, MeteredService};
 
 use crate::{
-    common::{submit_merkle_root_to_ethereum, BASE_RETRY_DELAY, MAX_RETRIES},
+    common::{BASE_RETRY_DELAY, MAX_RETRIES},
     prover_interface::FinalProof,
     rpc,
 };
 
-use super::storage::MerkleRootStorage;
+use super::{
+    fee_bumping::FeeBumpConfig,
+    leader_election::LeaderStatus,
+    storage::{MerkleRootStorage, SubmissionTransactions},
+    LiveOptions,
+};
+
+/// How often a follower checks whether held merkle roots were submitted by the leader.
+const FOLLOWER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often receipts of submitted transactions are polled.
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(12);
+
+/// Ethereum calls made by the submitter. Implemented by [`EthApi`], other implementations
+/// run the submitter against a fake chain.
+#[async_trait::async_trait]
+pub trait SubmissionApi: Clone + Send + Sync + 'static {
+    async fn get_approx_balance(&self) -> Result<f64, ethereum_client::Error>;
+    async fn estimate_fee_caps(&self) -> Result<FeeCaps, ethereum_client::Error>;
+    async fn allocate_nonce(&self) -> Result<u64, ethereum_client::Error>;
+    async fn sign_merkle_root_with_fees(
+        &self,
+        block_number: u32,
+        merkle_root: [u8; 32],
+        proof: Vec<u8>,
+        nonce: u64,
+        fees: FeeCaps,
+    ) -> Result<SignedTransaction, ethereum_client::Error>;
+    async fn send_signed(&self, tx: &SignedTransaction) -> Result<TxHash, ethereum_client::Error>;
+    async fn nonce_sent(&self, nonce: u64);
+    async fn nonce_maybe_sent(&self, nonce: u64);
+    async fn release_nonce(&self, nonce: u64);
+    async fn get_transaction_receipt(
+        &self,
+        tx_hash: TxHash,
+    ) -> Result<Option<TransactionReceipt>, ethereum_client::Error>;
+    async fn block_number(&self) -> Result<u64, ethereum_client::Error>;
+    async fn latest_nonce(&self) -> Result<u64, ethereum_client::Error>;
+    async fn read_finalized_merkle_root(
+        &self,
+        block_number: u32,
+    ) -> Result<Option<[u8; 32]>, ethereum_client::Error>;
+    async fn reconnect(&self) -> Result<Self, ethereum_client::Error>;
+}
+
+#[async_trait::async_trait]
+impl SubmissionApi for EthApi {
+    async fn get_approx_balance(&self) -> Result<f64, ethereum_client::Error> {
+        EthApi::get_approx_balance(self).await
+    }
+
+    async fn estimate_fee_caps(&self) -> Result<FeeCaps, ethereum_client::Error> {
+        EthApi::estimate_fee_caps(self).await
+    }
+
+    async fn allocate_nonce(&self) -> Result<u64, ethereum_client::Error> {
+        EthApi::allocate_nonce(self).await
+    }
+
+    async fn sign_merkle_root_with_fees(
+        &self,
+        block_number: u32,
+        merkle_root: [u8; 32],
+        proof: Vec<u8>,
+        nonce: u64,
+        fees: FeeCaps,
+    ) -> Result<SignedTransaction, ethereum_client::Error> {
+        EthApi::sign_merkle_root_with_fees(self, block_number, merkle_root, proof, nonce, fees)
+            .await
+    }
+
+    async fn send_signed(&self, tx: &SignedTransaction) -> Result<TxHash, ethereum_client::Error> {
+        EthApi::send_signed(self, tx).await
+    }
+
+    async fn nonce_sent(&self, nonce: u64) {
+        EthApi::nonce_sent(self, nonce).await
+    }
+
+    async fn nonce_maybe_sent(&self, nonce: u64) {
+        EthApi::nonce_maybe_sent(self, nonce).await
+    }
+
+    async fn release_nonce(&self, nonce: u64) {
+        EthApi::release_nonce(self, nonce).await
+    }
+
+    async fn get_transaction_receipt(
+        &self,
+        tx_hash: TxHash,
+    ) -> Result<Option<TransactionReceipt>, ethereum_client::Error> {
+        EthApi::get_transaction_receipt(self, tx_hash).await
+    }
+
+    async fn block_number(&self) -> Result<u64, ethereum_client::Error> {
+        EthApi::block_number(self).await
+    }
+
+    async fn latest_nonce(&self) -> Result<u64, ethereum_client::Error> {
+        EthApi::latest_nonce(self).await
+    }
+
+    async fn read_finalized_merkle_root(
+        &self,
+        block_number: u32,
+    ) -> Result<Option<[u8; 32]>, ethereum_client::Error> {
+        EthApi::read_finalized_merkle_root(self, block_number).await
+    }
+
+    async fn reconnect(&self) -> Result<Self, ethereum_client::Error> {
+        EthApi::reconnect(self).await
+    }
+}
 
 pub struct Request {
     pub era: Option<u64>,
@@ -101,35 +215,264 @@ struct SubmissionError {
     merkle_root_block: u32,
     merkle_root: H256,
     proof: FinalProof,
-    error: PendingTransactionError,
+    error: anyhow::Error,
 }
 
-impl SubmittedMerkleRoot {
-    async fn new(
-        pending_tx: PendingTransactionBuilder<Ethereum>,
-        era: Option<u64>,
-        merkle_root_block: u32,
-        merkle_root: H256,
-        proof: FinalProof,
-        confirmations: u64,
-    ) -> Result<Self, SubmissionError> {
-        Ok(Self {
-            merkle_root_block,
-            merkle_root,
-            era,
-            proof: proof.clone(),
-            receipt: pending_tx
-                .with_required_confirmations(confirmations)
-                .get_receipt()
-                .await
-                .map_err(|error| SubmissionError {
-                    era,
-                    merkle_root_block,
-                    merkle_root,
-                    error,
-                    proof,
-                })?,
-        })
+enum Progress {
+    Pending,
+    Confirmed(TransactionReceipt),
+    /// The nonce is taken by a transaction we don't track.
+    NonceUsed,
+}
+
+struct WatchState {
+    last_sent: Instant,
+    nonce_used: bool,
+    ceiling_reached: bool,
+    leadership_lost: bool,
+}
+
+/// Waits until one of the transactions submitting a merkle root is confirmed, replacing
+/// the transaction with a higher-fee one whenever it's stuck.
+#[derive(Clone)]
+struct SubmissionWatcher<E> {
+    eth_api: E,
+    storage: Arc<MerkleRootStorage>,
+    confirmations: u64,
+    live_options: watch::Receiver<LiveOptions>,
+    relayer_id: String,
+    /// Stuck transactions are replaced only while this replica is the leader.
+    leader: Option<LeaderStatus>,
+    fee_bumps: IntCounter,
+}
+
+impl<E: SubmissionApi> SubmissionWatcher<E> {
+    /// Read on every poll so that a reloaded config applies to transactions in flight.
+    fn fee_bumping(&self) -> FeeBumpConfig {
+        self.live_options.borrow().fee_bumping
+    }
+
+    async fn watch(
+        mut self,
+        request: Request,
+        transactions: SubmissionTransactions,
+    ) -> Result<SubmittedMerkleRoot, SubmissionError> {
+        match self.wait_for_receipt(&request.proof, transactions).await {
+            Ok(receipt) => Ok(SubmittedMerkleRoot {
+                era: request.era,
+                merkle_root_block: request.merkle_root_block,
+                merkle_root: request.merkle_root,
+                proof: request.proof,
+                receipt,
+            }),
+
+            Err(error) => Err(SubmissionError {
+                era: request.era,
+                merkle_root_block: request.merkle_root_block,
+                merkle_root: request.merkle_root,
+                proof: request.proof,
+                error,
+            }),
+        }
+    }
+
+    async fn wait_for_receipt(
+        &mut self,
+        proof: &FinalProof,
+        mut transactions: SubmissionTransactions,
+    ) -> anyhow::Result<TransactionReceipt> {
+        let mut state = WatchState {
+            last_sent: Instant::now(),
+            nonce_used: false,
+            ceiling_reached: false,
+            leadership_lost: false,
+        };
+
+        loop {
+            match self.poll(proof, &mut transactions, &mut state).await {
+                Ok(Progress::Confirmed(receipt)) => return Ok(receipt),
+
+                Ok(Progress::Pending) => {}
+
+                Ok(Progress::NonceUsed) => anyhow::bail!(
+                    "Nonce {} is used by a transaction other than {:?}",
+                    transactions.nonce,
+                    transactions.hashes
+                ),
+
+                Err(err) if is_recoverable_eth_error(&err) => {
+                    log::warn!(
+                        "Merkle root relayer {}: recoverable error while waiting for merkle root {} transaction: {err}. Reconnecting",
+                        self.relayer_id,
+                        transactions.merkle_root
+                    );
+                    // The transaction is watched until RPC is back rather than given up on.
+                    match self.eth_api.reconnect().await {
+                        Ok(eth_api) => self.eth_api = eth_api,
+                        Err(err) => log::warn!(
+                            "Merkle root relayer {}: failed to reconnect to Ethereum API: {err}",
+                            self.relayer_id
+                        ),
+                    }
+                }
+
+                Err(err) => return Err(err.into()),
+            }
+
+            tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn poll(
+        &self,
+        proof: &FinalProof,
+        transactions: &mut SubmissionTransactions,
+        state: &mut WatchState,
+    ) -> Result<Progress, ethereum_client::Error> {
+        let relayer_id = self.relayer_id.as_str();
+
+        // Any of the replacements might be the one that got mined.
+        for hash in transactions.hashes.iter().rev() {
+            let Some(receipt) = self
+                .eth_api
+                .get_transaction_receipt(TxHash::from(hash.0))
+                .await?
+            else {
+                continue;
+            };
+
+            let included_at = receipt.block_number.unwrap_or_default();
+            let latest = self.eth_api.block_number().await?;
+            if included_at + self.confirmations.saturating_sub(1) <= latest {
+                return Ok(Progress::Confirmed(receipt));
+            }
+
+            return Ok(Progress::Pending);
+        }
+
+        if self.eth_api.latest_nonce().await? > transactions.nonce {
+            // A replacement might have been mined between the receipt and nonce queries,
+            // so receipts are checked once more before giving up.
+            if state.nonce_used {
+                return Ok(Progress::NonceUsed);
+            }
+
+            state.nonce_used = true;
+            return Ok(Progress::Pending);
+        }
+
+        let fee_bumping = self.fee_bumping();
+        if state.last_sent.elapsed() < fee_bumping.stuck_timeout {
+            return Ok(Progress::Pending);
+        }
+
+        let current = FeeCaps {
+            max_fee_per_gas: transactions.max_fee_per_gas,
+            max_priority_fee_per_gas: transactions.max_priority_fee_per_gas,
+        };
+        let estimate = self.eth_api.estimate_fee_caps().await?;
+        let Some(fees) = fee_bumping.bump(current, Some(estimate)) else {
+            if !state.ceiling_reached {
+                log::warn!(
+                    "Merkle root relayer {relayer_id}: transaction {:?} of merkle root {} is stuck but fees are at the ceiling: {current:?}",
+                    transactions.hashes.last(),
+                    transactions.merkle_root
+                );
+                state.ceiling_reached = true;
+            }
+
+            return Ok(Progress::Pending);
+        };
+
+        // Leadership might have been lost while the transaction was pending. The new leader
+        // submits the merkle root itself, so the transaction is left as is.
+        if !self.leader.as_ref().is_none_or(LeaderStatus::is_leader) {
+            if !state.leadership_lost {
+                log::warn!(
+                    "Merkle root relayer {relayer_id}: not a leader anymore, stuck transaction {:?} of merkle root {} isn't replaced",
+                    transactions.hashes.last(),
+                    transactions.merkle_root
+                );
+                state.leadership_lost = true;
+            }
+
+            return Ok(Progress::Pending);
+        }
+
+        let tx = match self
+            .eth_api
+            .sign_merkle_root_with_fees(
+                proof.block_number,
+                proof.merkle_root,
+                proof.proof.clone(),
+                transactions.nonce,
+                fees,
+            )
+            .await
+        {
+            Ok(tx) => tx,
+            Err(err) if is_recoverable_eth_error(&err) => return Err(err),
+            Err(err) => {
+                log::warn!(
+                    "Merkle root relayer {relayer_id}: failed to replace transaction of merkle root {}: {err}",
+                    transactions.merkle_root
+                );
+                state.last_sent = Instant::now();
+                return Ok(Progress::Pending);
+            }
+        };
+        let tx_hash = tx.hash();
+
+        // The replacement is tracked before it's broadcast, so that it's recognised once
+        // mined even if the relayer crashes in between.
+        let mut replaced = transactions.clone();
+        replaced.hashes.push(H256::from(tx_hash.0));
+        replaced.max_fee_per_gas = fees.max_fee_per_gas;
+        replaced.max_priority_fee_per_gas = fees.max_priority_fee_per_gas;
+        if let Err(err) = self.storage.track_transactions(replaced.clone()).await {
+            log::error!(
+                "Merkle root relayer {relayer_id}: replacement {tx_hash} of merkle root {} isn't sent as it can't be tracked: {err:?}",
+                transactions.merkle_root
+            );
+            state.last_sent = Instant::now();
+            return Ok(Progress::Pending);
+        }
+        let previous = transactions.hashes.last().copied();
+        *transactions = replaced;
+
+        match self.eth_api.send_signed(&tx).await {
+            Ok(_) => {}
+            Err(ethereum_client::Error::TransactionMaybeSent { error, .. }) => {
+                log::warn!(
+                    "Merkle root relayer {relayer_id}: replacement {tx_hash} of merkle root {} might not have been sent: {error}",
+                    transactions.merkle_root
+                );
+            }
+            Err(err) if is_recoverable_eth_error(&err) => return Err(err),
+            // The stuck transaction might have been mined meanwhile, which is
+            // figured out on the next poll. The replacement stays tracked, it just never
+            // gets a receipt if it hasn't reached any node.
+            Err(err) => {
+                log::warn!(
+                    "Merkle root relayer {relayer_id}: failed to replace transaction of merkle root {}: {err}",
+                    transactions.merkle_root
+                );
+                state.last_sent = Instant::now();
+                return Ok(Progress::Pending);
+            }
+        }
+
+        log::info!(
+            "Merkle root relayer {relayer_id}: replaced stuck transaction {previous:?} of merkle root {} with {tx_hash} (max fee per gas: {}, max priority fee per gas: {})",
+            transactions.merkle_root,
+            fees.max_fee_per_gas,
+            fees.max_priority_fee_per_gas
+        );
+
+        self.fee_bumps.inc();
+        state.last_sent = Instant::now();
+
+        Ok(Progress::Pending)
     }
 }
 
@@ -150,6 +493,11 @@ impl_metered_service!(
             "Total number of failed merkle root submissions",
         ),
 
+        fee_bumps: IntCounter = IntCounter::new(
+            "merkle_root_relayer_fee_bumps",
+            "Total number of stuck merkle root transactions replaced with higher fees",
+        ),
+
         pending_submissions: IntGauge = IntGauge::new(
             "merkle_root_relayer_pending_submissions",
             "Total number of pending merkle root submissions",
@@ -181,36 +529,152 @@ impl_metered_service!(
     }
 );
 
-pub struct MerkleRootSubmitter {
-    eth_api: EthApi,
+pub struct MerkleRootSubmitter<E = EthApi> {
+    eth_api: E,
     storage: Arc<MerkleRootStorage>,
     confirmations: u64,
+    live_options: watch::Receiver<LiveOptions>,
     relayer_id: String,
+    /// When set, merkle roots are submitted only while this replica is the leader.
+    leader: Option<LeaderStatus>,
+    /// Requests of the `leader_gate`, where requests are handed back once leadership is lost.
+    gate: Option<WeakUnboundedSender<Request>>,
     metrics: Metrics,
 }
 
-impl MeteredService for MerkleRootSubmitter {
+impl<E> MeteredService for MerkleRootSubmitter<E> {
     fn get_sources(&self) -> impl IntoIterator<Item = Box<dyn prometheus::core::Collector>> {
         self.metrics.get_sources()
     }
 }
 
-impl MerkleRootSubmitter {
+impl<E: SubmissionApi> MerkleRootSubmitter<E> {
     pub fn new(
-        eth_api: EthApi,
+        eth_api: E,
         storage: Arc<MerkleRootStorage>,
         confirmations: u64,
+        live_options: watch::Receiver<LiveOptions>,
         relayer_id: String,
+        leader: Option<LeaderStatus>,
     ) -> Self {
         Self {
             eth_api,
             storage,
             confirmations,
+            live_options,
             relayer_id,
+            leader,
+            gate: None,
             metrics: Metrics::new(),
         }
     }
 
+    fn watcher(&self) -> SubmissionWatcher<E> {
+        SubmissionWatcher {
+            eth_api: self.eth_api.clone(),
+            storage: self.storage.clone(),
+            confirmations: self.confirmations,
+            live_options: self.live_options.clone(),
+            relayer_id: self.relayer_id.clone(),
+            leader: self.leader.clone(),
+            fee_bumps: self.metrics.fee_bumps.clone(),
+        }
+    }
+
+    fn is_leader(&self) -> bool {
+        self.leader.as_ref().is_none_or(LeaderStatus::is_leader)
+    }
+
+    /// Sends a transaction with a nonce and fee caps which are tracked so that it can be
+    /// replaced later on. The transaction is tracked before it's broadcast, so that it's
+    /// recognised once mined even if the relayer crashes in between.
+    ///
+    /// The outer error means the transaction can't be tracked and so isn't sent.
+    async fn send_merkle_root(
+        &self,
+        proof: &FinalProof,
+    ) -> anyhow::Result<Result<SubmissionTransactions, ethereum_client::Error>> {
+        log::info!(
+            "Merkle root relayer {}: submitting merkle root {} at block #{} to Ethereum",
+            self.relayer_id,
+            H256::from(proof.merkle_root),
+            proof.block_number
+        );
+
+        let (tx, transactions) = match self.sign_merkle_root(proof).await {
+            Ok(signed) => signed,
+            Err(err) => return Ok(Err(err)),
+        };
+        let nonce = transactions.nonce;
+
+        if let Err(err) = self.storage.track_transactions(transactions.clone()).await {
+            self.eth_api.release_nonce(nonce).await;
+            return Err(err);
+        }
+
+        match self.eth_api.send_signed(&tx).await {
+            Ok(_) => self.eth_api.nonce_sent(nonce).await,
+            // The transaction is watched as if it's sent and the nonce stays reserved. If
+            // it never reached a node, it's replaced once considered stuck, which by default
+            // happens before the reserved nonce is filled as a gap.
+            Err(ethereum_client::Error::TransactionMaybeSent { tx_hash, error }) => {
+                log::warn!(
+                    "Merkle root relayer {}: transaction {tx_hash} of merkle root {} might not have been sent: {error}",
+                    self.relayer_id,
+                    H256::from(proof.merkle_root)
+                );
+                self.eth_api.nonce_maybe_sent(nonce).await;
+            }
+            Err(err) => {
+                self.eth_api.release_nonce(nonce).await;
+                self.storage
+                    .forget_transactions(transactions.block_number, transactions.merkle_root)
+                    .await?;
+                return Ok(Err(err));
+            }
+        }
+
+        Ok(Ok(transactions))
+    }
+
+    /// Signs a transaction submitting the merkle root with a newly allocated nonce.
+    async fn sign_merkle_root(
+        &self,
+        proof: &FinalProof,
+    ) -> Result<(SignedTransaction, SubmissionTransactions), ethereum_client::Error> {
+        let estimate = self.eth_api.estimate_fee_caps().await?;
+        let fees = self.live_options.borrow().fee_bumping.initial(estimate);
+        let nonce = self.eth_api.allocate_nonce().await?;
+        let tx = match self
+            .eth_api
+            .sign_merkle_root_with_fees(
+                proof.block_number,
+                proof.merkle_root,
+                proof.proof.clone(),
+                nonce,
+                fees,
+            )
+            .await
+        {
+            Ok(tx) => tx,
+            Err(err) => {
+                self.eth_api.release_nonce(nonce).await;
+                return Err(err);
+            }
+        };
+
+        let transactions = SubmissionTransactions {
+            block_number: proof.block_number,
+            merkle_root: H256::from(proof.merkle_root),
+            nonce,
+            hashes: vec![H256::from(tx.hash().0)],
+            max_fee_per_gas: fees.max_fee_per_gas,
+            max_priority_fee_per_gas: fees.max_priority_fee_per_gas,
+        };
+
+        Ok((tx, transactions))
+    }
+
     async fn process(
         &mut self,
         proofs: &mut UnboundedReceiver<Request>,
@@ -234,6 +698,19 @@ impl MerkleRootSubmitter {
                         return Ok(());
                     };
 
+                    // Transactions sent before the restart: whichever of them gets mined
+                    // confirms the submission.
+                    if let Some(transactions) = self.storage.submission_transactions(request.proof.block_number, H256::from(request.proof.merkle_root)).await {
+                        log::info!(
+                            "Merkle root relayer {relayer_id}: resuming {} tracked transaction(s) of merkle root {} for block #{}",
+                            transactions.hashes.len(),
+                            transactions.merkle_root,
+                            request.merkle_root_block
+                        );
+                        pending_transactions.push(self.watcher().watch(request, transactions));
+                        continue;
+                    }
+
                     if self.storage.is_merkle_root_submitted(request.merkle_root_block, H256::from(request.proof.merkle_root)).await {
                         log::info!(
                             "Merkle root relayer {relayer_id}: merkle root {} for block #{} is already submitted", H256::from(request.proof.merkle_root), request.merkle_root_block);
@@ -250,22 +727,32 @@ impl MerkleRootSubmitter {
                     }
 
                     loop {
-                    match submit_merkle_root_to_ethereum(&self.eth_api, request.proof.clone()).await {
-                        Ok(pending_tx) => {
+                    // Leadership is checked before every attempt, as it might have been
+                    // lost since the request passed the leader gate.
+                    if !self.is_leader() {
+                        log::info!(
+                            "Merkle root relayer {relayer_id}: not a leader anymore, handing merkle root {} for block #{} back to the leader gate",
+                            request.merkle_root,
+                            request.merkle_root_block
+                        );
+                        let Some(gate) = self.gate.as_ref().and_then(WeakUnboundedSender::upgrade) else {
+                            return Ok(());
+                        };
+                        if gate.send(request).is_err() {
+                            return Ok(());
+                        }
+                        break;
+                    }
+
+                    match self.send_merkle_root(&request.proof).await? {
+                        Ok(transactions) => {
                             log::info!(
-                                "Merkle root relayer {relayer_id}: submitted merkle root to Ethereum, tx hash: {}",
-                                pending_tx.tx_hash()
+                                "Merkle root relayer {relayer_id}: submitted merkle root to Ethereum, tx hash: {:?}",
+                                transactions.hashes[0]
                             );
-                            self.storage.submitted_merkle_root(request.merkle_root_block, H256::from(request.proof.merkle_root)).await;
+                            self.storage.submitted_merkle_root(request.merkle_root_block, H256::from(request.proof.merkle_root)).await?;
                             self.metrics.total_submissions.inc();
-                            pending_transactions.push(SubmittedMerkleRoot::new(
-                                pending_tx,
-                                request.era,
-                                request.merkle_root_block,
-                                request.merkle_root,
-                                request.proof,
-                                self.confirmations,
-                            ));
+                            pending_transactions.push(self.watcher().watch(request, transactions));
                             break;
                         }
                         // How do we get here?
@@ -292,7 +779,7 @@ impl MerkleRootSubmitter {
                             } else {
                                 log::error!("Merkle root relayer {relayer_id}: failed to submit merkle root {}: Error during contract execution: {err:?}", H256::from(request.proof.merkle_root));
                                 self.metrics.failed_submissions.inc();
-                                self.storage.submission_failed(request.proof.block_number, H256::from(request.proof.merkle_root)).await;
+                                self.storage.submission_failed(request.proof.block_number, H256::from(request.proof.merkle_root)).await?;
                                 if responses.send(Response {
                                     era: request.era,
                                     merkle_root_block: request.merkle_root_block,
@@ -321,7 +808,7 @@ impl MerkleRootSubmitter {
 
                             log::error!("Merkle root relayer {relayer_id}: failed to submit merkle root {}: {}", H256::from(request.proof.merkle_root), err);
                             self.metrics.failed_submissions.inc();
-                            self.storage.submission_failed(request.proof.block_number, H256::from(request.proof.merkle_root)).await;
+                            self.storage.submission_failed(request.proof.block_number, H256::from(request.proof.merkle_root)).await?;
                             if responses.send(Response {
                                 era: request.era,
                                 merkle_root_block: request.merkle_root_block,
@@ -338,6 +825,12 @@ impl MerkleRootSubmitter {
                 },
 
                 Some(result) = pending_transactions.next() => {
+                    let proof = match &result {
+                        Ok(submitted) => &submitted.proof,
+                        Err(err) => &err.proof,
+                    };
+                    self.storage.forget_transactions(proof.block_number, H256::from(proof.merkle_root)).await?;
+
                     match result {
                         Ok(submitted) => {
                             // update gas used metrics
@@ -425,7 +918,7 @@ impl MerkleRootSubmitter {
                             log::error!("Merkle root relayer {relayer_id}: failed to submit merkle root {}: {}", err.merkle_root, err.error);
                             self.metrics.pending_submissions.dec();
                             self.metrics.failed_submissions.inc();
-                            self.storage.submission_failed(err.merkle_root_block, H256::from(err.proof.merkle_root)).await;
+                            self.storage.submission_failed(err.merkle_root_block, H256::from(err.proof.merkle_root)).await?;
                             if responses.send(Response {
                                 era: err.era,
                                 merkle_root_block: err.merkle_root_block,
@@ -442,16 +935,132 @@ impl MerkleRootSubmitter {
         }
     }
 
-    pub fn run(self) -> SubmitterIo {
+    pub fn run(mut self) -> SubmitterIo {
         let (tx, rx) = unbounded_channel();
         let (response_tx, response_rx) = unbounded_channel();
 
+        let rx = match self.leader.clone() {
+            Some(leader) => {
+                // Weak so that the gate stops once `SubmitterIo` is dropped.
+                self.gate = Some(tx.downgrade());
+                let (gated_tx, gated_rx) = unbounded_channel();
+                tokio::task::spawn(leader_gate(
+                    leader,
+                    self.eth_api.clone(),
+                    self.relayer_id.clone(),
+                    rx,
+                    gated_tx,
+                    response_tx.clone(),
+                ));
+
+                gated_rx
+            }
+
+            None => rx,
+        };
+
         tokio::task::spawn(task(self, rx, response_tx));
 
         SubmitterIo::new(tx, response_rx)
     }
 }
 
+/// Forwards requests to the submitter while this replica is the leader. Followers hold
+/// requests back and drop those the leader has already submitted, so that the rest are
+/// submitted right away on takeover.
+async fn leader_gate<E: SubmissionApi>(
+    mut leader: LeaderStatus,
+    eth_api: E,
+    relayer_id: String,
+    mut requests: UnboundedReceiver<Request>,
+    submitter: UnboundedSender<Request>,
+    responses: UnboundedSender<Response>,
+) {
+    let mut held: Vec<Request> = Vec::new();
+    let mut check_interval = tokio::time::interval(FOLLOWER_CHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            request = requests.recv() => {
+                let Some(request) = request else {
+                    return;
+                };
+
+                if leader.is_leader() {
+                    if submitter.send(request).is_err() {
+                        return;
+                    }
+                } else {
+                    log::info!(
+                        "Merkle root relayer {relayer_id}: not a leader, holding merkle root {} for block #{}",
+                        request.merkle_root,
+                        request.merkle_root_block
+                    );
+                    held.push(request);
+                }
+            }
+
+            is_leader = leader.changed() => {
+                match is_leader {
+                    Some(true) => {
+                        log::info!(
+                            "Merkle root relayer {relayer_id}: became the leader, submitting {} held merkle root(s)",
+                            held.len()
+                        );
+                        for request in held.drain(..) {
+                            if submitter.send(request).is_err() {
+                                return;
+                            }
+                        }
+                    }
+
+                    Some(false) => {}
+
+                    None => {
+                        log::error!("Merkle root relayer {relayer_id}: leader election stopped, merkle roots won't be submitted");
+                        return;
+                    }
+                }
+            }
+
+            _ = check_interval.tick(), if !held.is_empty() => {
+                let mut pending = Vec::with_capacity(held.len());
+                for request in held.drain(..) {
+                    match eth_api.read_finalized_merkle_root(request.proof.block_number).await {
+                        Ok(Some(_)) => {
+                            log::info!(
+                                "Merkle root relayer {relayer_id}: merkle root {} for block #{} is submitted by the leader",
+                                request.merkle_root,
+                                request.merkle_root_block
+                            );
+                            if responses.send(Response {
+                                era: request.era,
+                                merkle_root_block: request.merkle_root_block,
+                                merkle_root: request.merkle_root,
+                                proof: request.proof,
+                                status: ResponseStatus::Submitted,
+                            }).is_err() {
+                                return;
+                            }
+                        }
+
+                        Ok(None) => pending.push(request),
+
+                        Err(err) => {
+                            log::warn!(
+                                "Merkle root relayer {relayer_id}: failed to check merkle root for block #{}: {err}",
+                                request.merkle_root_block
+                            );
+                            pending.push(request);
+                        }
+                    }
+                }
+                held = pending;
+            }
+        }
+    }
+}
+
 fn is_recoverable_eth_error(err: &ethereum_client::Error) -> bool {
     match err {
         ethereum_client::Error::ErrorInHTTPTransport(err) => {
@@ -461,8 +1070,8 @@ fn is_recoverable_eth_error(err: &ethereum_client::Error) -> bool {
     }
 }
 
-async fn task(
-    mut this: MerkleRootSubmitter,
+async fn task<E: SubmissionApi>(
+    mut this: MerkleRootSubmitter<E>,
     mut proofs: UnboundedReceiver<Request>,
     responses: UnboundedSender<Response>,
 ) {
@@ -501,3 +1110,324 @@ async fn task(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        merkle_roots::{CriticalThreshold, SpikeConfig},
+        proof_storage::InMemoryProofStorage,
+    };
+    use alloy::{
+        network::TxSignerSync,
+        primitives::{Address, Bytes, TxKind, U256},
+        signers::local::PrivateKeySigner,
+        transports::TransportErrorKind,
+    };
+    use alloy_consensus::{
+        Eip658Value, Receipt, ReceiptEnvelope, ReceiptWithBloom, SignableTransaction, TxEip1559,
+        TxEnvelope,
+    };
+    use ethereum_client::nonce::GAP_CHECK_INTERVAL;
+    use std::{
+        collections::HashMap,
+        sync::{Mutex, MutexGuard},
+    };
+
+    const KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+    const BLOCK_TIME: Duration = Duration::from_secs(12);
+    const GAS_USED: u64 = 300_000;
+    const ESTIMATE: FeeCaps = FeeCaps {
+        max_fee_per_gas: 20_000_000_000,
+        max_priority_fee_per_gas: 1_000_000_000,
+    };
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum NonceReport {
+        Sent(u64),
+        MaybeSent(u64),
+        Released(u64),
+    }
+
+    #[derive(Clone, Copy)]
+    struct Transaction {
+        nonce: u64,
+        merkle_root: [u8; 32],
+        fees: FeeCaps,
+    }
+
+    struct Included {
+        transaction: Transaction,
+        block: u64,
+    }
+
+    struct ChainState {
+        started_at: Instant,
+        /// Number of the next broadcasts which fail without reaching the node.
+        lost_broadcasts: usize,
+        signed: HashMap<TxHash, Transaction>,
+        transactions: HashMap<TxHash, Included>,
+        nonce_reports: Vec<NonceReport>,
+        /// When the gap filler takes the nonce reported as sent without its transaction
+        /// reaching the node.
+        gap_filled_at: Option<Instant>,
+    }
+
+    /// Ethereum with a single fee payer whose transactions are included into the next block
+    /// once broadcast. Like the [`ethereum_client::NonceManager`], a nonce reported as sent
+    /// while the node has no transaction for it is filled as a gap after two checks.
+    #[derive(Clone)]
+    struct FakeEthereum {
+        signer: Arc<PrivateKeySigner>,
+        state: Arc<Mutex<ChainState>>,
+    }
+
+    impl FakeEthereum {
+        fn new(lost_broadcasts: usize) -> Self {
+            Self {
+                signer: Arc::new(KEY.parse().unwrap()),
+                state: Arc::new(Mutex::new(ChainState {
+                    started_at: Instant::now(),
+                    lost_broadcasts,
+                    signed: HashMap::new(),
+                    transactions: HashMap::new(),
+                    nonce_reports: Vec::new(),
+                    gap_filled_at: None,
+                })),
+            }
+        }
+
+        fn state(&self) -> MutexGuard<'_, ChainState> {
+            self.state.lock().expect("Chain state is poisoned")
+        }
+    }
+
+    impl ChainState {
+        fn block(&self) -> u64 {
+            (self.started_at.elapsed().as_secs() / BLOCK_TIME.as_secs()) + 1
+        }
+
+        fn included(&self, tx_hash: &TxHash) -> Option<&Included> {
+            self.transactions
+                .get(tx_hash)
+                .filter(|included| included.block <= self.block())
+        }
+
+        fn is_gap_filled(&self) -> bool {
+            self.gap_filled_at.is_some_and(|at| at <= Instant::now())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SubmissionApi for FakeEthereum {
+        async fn get_approx_balance(&self) -> Result<f64, ethereum_client::Error> {
+            Ok(1.0)
+        }
+
+        async fn estimate_fee_caps(&self) -> Result<FeeCaps, ethereum_client::Error> {
+            Ok(ESTIMATE)
+        }
+
+        async fn allocate_nonce(&self) -> Result<u64, ethereum_client::Error> {
+            Ok(0)
+        }
+
+        async fn sign_merkle_root_with_fees(
+            &self,
+            _block_number: u32,
+            merkle_root: [u8; 32],
+            _proof: Vec<u8>,
+            nonce: u64,
+            fees: FeeCaps,
+        ) -> Result<SignedTransaction, ethereum_client::Error> {
+            let mut tx = TxEip1559 {
+                chain_id: 1,
+                nonce,
+                gas_limit: 500_000,
+                max_fee_per_gas: fees.max_fee_per_gas,
+                max_priority_fee_per_gas: fees.max_priority_fee_per_gas,
+                to: TxKind::Call(Address::ZERO),
+                value: U256::ZERO,
+                access_list: Default::default(),
+                input: Bytes::copy_from_slice(&merkle_root),
+            };
+            let signature = self.signer.sign_transaction_sync(&mut tx).unwrap();
+            let tx = SignedTransaction::from(TxEnvelope::from(tx.into_signed(signature)));
+            self.state().signed.insert(
+                tx.hash(),
+                Transaction {
+                    nonce,
+                    merkle_root,
+                    fees,
+                },
+            );
+
+            Ok(tx)
+        }
+
+        async fn send_signed(
+            &self,
+            tx: &SignedTransaction,
+        ) -> Result<TxHash, ethereum_client::Error> {
+            let mut state = self.state();
+            if state.lost_broadcasts > 0 {
+                state.lost_broadcasts -= 1;
+                return Err(ethereum_client::Error::TransactionMaybeSent {
+                    tx_hash: tx.hash(),
+                    error: TransportErrorKind::backend_gone(),
+                });
+            }
+
+            let transaction = state.signed[&tx.hash()];
+            let block = state.block() + 1;
+            if !state.is_gap_filled() {
+                state.gap_filled_at = None;
+            }
+            state
+                .transactions
+                .insert(tx.hash(), Included { transaction, block });
+
+            Ok(tx.hash())
+        }
+
+        async fn nonce_sent(&self, nonce: u64) {
+            let mut state = self.state();
+            state.nonce_reports.push(NonceReport::Sent(nonce));
+            if !state
+                .transactions
+                .values()
+                .any(|included| included.transaction.nonce == nonce)
+            {
+                state.gap_filled_at = Some(Instant::now() + 2 * GAP_CHECK_INTERVAL);
+            }
+        }
+
+        async fn nonce_maybe_sent(&self, nonce: u64) {
+            self.state()
+                .nonce_reports
+                .push(NonceReport::MaybeSent(nonce));
+        }
+
+        async fn release_nonce(&self, nonce: u64) {
+            self.state()
+                .nonce_reports
+                .push(NonceReport::Released(nonce));
+        }
+
+        async fn get_transaction_receipt(
+            &self,
+            tx_hash: TxHash,
+        ) -> Result<Option<TransactionReceipt>, ethereum_client::Error> {
+            Ok(self
+                .state()
+                .included(&tx_hash)
+                .map(|included| receipt(tx_hash, included.block)))
+        }
+
+        async fn block_number(&self) -> Result<u64, ethereum_client::Error> {
+            Ok(self.state().block())
+        }
+
+        async fn latest_nonce(&self) -> Result<u64, ethereum_client::Error> {
+            let state = self.state();
+            let included = state
+                .transactions
+                .keys()
+                .any(|tx_hash| state.included(tx_hash).is_some());
+
+            Ok((included || state.is_gap_filled()) as u64)
+        }
+
+        async fn read_finalized_merkle_root(
+            &self,
+            _block_number: u32,
+        ) -> Result<Option<[u8; 32]>, ethereum_client::Error> {
+            let state = self.state();
+            Ok(state
+                .transactions
+                .keys()
+                .find_map(|tx_hash| state.included(tx_hash))
+                .map(|included| included.transaction.merkle_root))
+        }
+
+        async fn reconnect(&self) -> Result<Self, ethereum_client::Error> {
+            Ok(self.clone())
+        }
+    }
+
+    fn receipt(tx_hash: TxHash, block: u64) -> TransactionReceipt {
+        TransactionReceipt {
+            inner: ReceiptEnvelope::Eip1559(ReceiptWithBloom {
+                receipt: Receipt {
+                    status: Eip658Value::Eip658(true),
+                    cumulative_gas_used: GAS_USED,
+                    logs: vec![],
+                },
+                logs_bloom: Default::default(),
+            }),
+            transaction_hash: tx_hash,
+            transaction_index: Some(0),
+            block_hash: Some(H256::from_low_u64_be(block).0.into()),
+            block_number: Some(block),
+            gas_used: GAS_USED,
+            effective_gas_price: ESTIMATE.max_fee_per_gas,
+            blob_gas_used: None,
+            blob_gas_price: None,
+            from: Address::ZERO,
+            to: Some(Address::ZERO),
+            contract_address: None,
+        }
+    }
+
+    fn live_options() -> LiveOptions {
+        LiveOptions {
+            spike_config: SpikeConfig::default(),
+            critical_threshold: CriticalThreshold::AuthoritySetChange,
+            fee_bumping: FeeBumpConfig::default(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replaces_maybe_sent_transaction_instead_of_cancelling_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = MerkleRootStorage::new(
+            Arc::new(InMemoryProofStorage::default()),
+            dir.path().join("storage.json"),
+        );
+        let ethereum = FakeEthereum::new(1);
+        let (_options, live_options) = watch::channel(live_options());
+        let mut submitter = MerkleRootSubmitter::new(
+            ethereum.clone(),
+            storage,
+            1,
+            live_options,
+            "test".to_string(),
+            None,
+        )
+        .run();
+
+        let proof = FinalProof::mock(42, [1; 32]);
+        assert!(submitter.submit_merkle_root(42, H256::from(proof.merkle_root), proof));
+        let response = submitter.recv().await.unwrap();
+        assert!(
+            matches!(response.status, ResponseStatus::Submitted),
+            "{:?}",
+            response.status
+        );
+
+        let state = ethereum.state();
+        assert_eq!(state.nonce_reports, vec![NonceReport::MaybeSent(0)]);
+        assert!(!state.is_gap_filled());
+
+        // Only the replacement reached the node.
+        let included: Vec<_> = state
+            .transactions
+            .values()
+            .map(|included| included.transaction)
+            .collect();
+        assert_eq!(included.len(), 1);
+        assert_eq!(included[0].nonce, 0);
+        assert_eq!(included[0].merkle_root, [1; 32]);
+        assert!(included[0].fees.max_fee_per_gas > ESTIMATE.max_fee_per_gas);
+    }
+}
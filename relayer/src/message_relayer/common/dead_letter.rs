@@ -0,0 +1,204 @@
+//! Dead-letter queue of transactions which failed to be relayed.
+//!
+//! A failed transaction is taken out of the queue of its transaction manager along with its
+//! state at the moment of failure. Failures classified as transient, by
+//! [`rpc::classify_anyhow`] where the error occurs, are retried automatically with a
+//! backoff, up to [`MAX_AUTO_RETRIES`] times. The rest stay until an operator retries the
+//! transaction from a chosen stage or discards it.
+
+use crate::rpc::{self, RetryDecision, RetryPolicy};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// How many times a transient failure is retried without an operator.
+pub const MAX_AUTO_RETRIES: u32 = 5;
+
+const AUTO_RETRY_BASE_DELAY: Duration = Duration::from_secs(60);
+const AUTO_RETRY_MAX_DELAY: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// Failure is expected to go away on its own, e.g. an RPC connection loss.
+    Transient,
+    Permanent,
+}
+
+impl FailureClass {
+    pub fn classify(err: &anyhow::Error) -> Self {
+        rpc::classify_anyhow(err).into()
+    }
+
+    /// Class of a failure known only by its reason, as stored before the dead-letter
+    /// queue. Only the text of the reason is there to classify.
+    pub fn classify_reason(reason: &str) -> Self {
+        rpc::is_recoverable_error_text(reason)
+            .then_some(Self::Transient)
+            .unwrap_or(Self::Permanent)
+    }
+}
+
+impl From<RetryDecision> for FailureClass {
+    fn from(decision: RetryDecision) -> Self {
+        match decision {
+            RetryDecision::Retry => Self::Transient,
+            RetryDecision::Fail => Self::Permanent,
+        }
+    }
+}
+
+/// Failed transaction with its state at the moment of failure.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeadLetter<T> {
+    pub transaction: T,
+    pub reason: String,
+    pub class: FailureClass,
+    /// Number of times relaying of the transaction failed.
+    pub attempts: u32,
+    /// Unix timestamp of the last failure.
+    pub failed_at: u64,
+    /// Unix timestamp the transaction is retried automatically at. Unset if it waits for
+    /// an operator.
+    pub retry_at: Option<u64>,
+}
+
+impl<T> DeadLetter<T> {
+    pub fn new(
+        transaction: T,
+        reason: String,
+        class: FailureClass,
+        attempts: u32,
+        now: u64,
+    ) -> Self {
+        let retry_at =
+            (class == FailureClass::Transient && attempts <= MAX_AUTO_RETRIES).then(|| {
+                let policy = RetryPolicy {
+                    base_delay: AUTO_RETRY_BASE_DELAY,
+                    max_delay: AUTO_RETRY_MAX_DELAY,
+                };
+
+                now + policy.delay(attempts.saturating_sub(1)).as_secs()
+            });
+
+        Self {
+            transaction,
+            reason,
+            class,
+            attempts,
+            failed_at: now,
+            retry_at,
+        }
+    }
+
+    pub fn is_due(&self, now: u64) -> bool {
+        self.retry_at.is_some_and(|retry_at| retry_at <= now)
+    }
+}
+
+/// Failed transaction as kept in storage. Storages written before the dead-letter queue
+/// keep only the failure reason.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StoredDeadLetter<T> {
+    DeadLetter(DeadLetter<T>),
+    Reason(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RetryError {
+    #[error("transaction {0} is not in the dead-letter queue")]
+    NotFound(Uuid),
+    #[error("unknown stage {0}")]
+    UnknownStage(String),
+    #[error("transaction can't be restarted at {stage} from {status} state")]
+    StageUnavailable { stage: String, status: String },
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::transports::{RpcError, TransportErrorKind};
+
+    #[test]
+    fn classifies_original_errors() {
+        let err = anyhow::Error::from(RpcError::<TransportErrorKind>::Transport(
+            TransportErrorKind::BackendGone,
+        ));
+        assert_eq!(FailureClass::classify(&err), FailureClass::Transient);
+        // The reason alone doesn't tell it's a transport failure.
+        assert_eq!(
+            FailureClass::classify_reason(&err.to_string()),
+            FailureClass::Permanent
+        );
+
+        let err = anyhow::anyhow!("Message stuck");
+        assert_eq!(FailureClass::classify(&err), FailureClass::Permanent);
+        assert_eq!(
+            FailureClass::classify_reason("connection reset by peer"),
+            FailureClass::Transient
+        );
+    }
+
+    #[test]
+    fn retries_only_transient_failures() {
+        let dead_letter = DeadLetter::new(
+            (),
+            "connection reset by peer".to_string(),
+            FailureClass::Transient,
+            1,
+            1_000,
+        );
+        assert_eq!(dead_letter.class, FailureClass::Transient);
+        let retry_at = dead_letter.retry_at.unwrap();
+        assert!((1_060..=1_061).contains(&retry_at));
+        assert!(!dead_letter.is_due(1_059));
+        assert!(dead_letter.is_due(retry_at));
+
+        let dead_letter = DeadLetter::new(
+            (),
+            "Message stuck".to_string(),
+            FailureClass::Permanent,
+            1,
+            1_000,
+        );
+        assert_eq!(dead_letter.class, FailureClass::Permanent);
+        assert_eq!(dead_letter.retry_at, None);
+
+        let dead_letter = DeadLetter::new(
+            (),
+            "request timed out".to_string(),
+            FailureClass::Transient,
+            MAX_AUTO_RETRIES + 1,
+            1_000,
+        );
+        assert_eq!(dead_letter.class, FailureClass::Transient);
+        assert_eq!(dead_letter.retry_at, None);
+    }
+
+    #[test]
+    fn reads_legacy_failures() {
+        let stored: StoredDeadLetter<u8> = serde_json::from_str("\"Message stuck\"").unwrap();
+        assert!(matches!(stored, StoredDeadLetter::Reason(reason) if reason == "Message stuck"));
+
+        let dead_letter =
+            DeadLetter::new(7u8, "timeout".to_string(), FailureClass::Transient, 2, 0);
+        let json = serde_json::to_string(&dead_letter).unwrap();
+        let stored: StoredDeadLetter<u8> = serde_json::from_str(&json).unwrap();
+        assert!(matches!(
+            stored,
+            StoredDeadLetter::DeadLetter(DeadLetter {
+                transaction: 7,
+                attempts: 2,
+                ..
+            })
+        ));
+    }
+}
@@ -1,11 +1,83 @@
 use crate::{common::BASE_RETRY_DELAY, message_relayer::common::RelayedMerkleRoot};
-use ethereum_client::{abi::IMessageQueue::IMessageQueueErrors, EthApi, TxHash};
+use ethereum_client::{
+    abi::IMessageQueue::IMessageQueueErrors, BatchDelivery, BatchOutcome, ContentMessage, EthApi,
+    TxHash,
+};
 use gear_rpc_client::dto::{MerkleProof, Message};
 use prometheus::{Gauge, IntCounter};
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use utils_prometheus::{impl_metered_service, MeteredService};
 use uuid::Uuid;
 
+/// Messages are delivered one per transaction by default.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 1;
+
+/// Ethereum calls made by the message sender. Implemented by [`EthApi`], other
+/// implementations run the sender against a fake chain.
+#[async_trait::async_trait]
+pub trait DeliveryApi: Clone + Send + Sync + 'static {
+    async fn get_approx_balance(&self) -> Result<f64, ethereum_client::Error>;
+    async fn provide_content_message(
+        &self,
+        block_number: u32,
+        message: ContentMessage,
+    ) -> Result<TxHash, ethereum_client::Error>;
+    async fn provide_content_messages(
+        &self,
+        block_number: u32,
+        messages: Vec<ContentMessage>,
+    ) -> Result<BatchDelivery, ethereum_client::Error>;
+    async fn reconnect(&self) -> Result<Self, ethereum_client::Error>;
+}
+
+#[async_trait::async_trait]
+impl DeliveryApi for EthApi {
+    async fn get_approx_balance(&self) -> Result<f64, ethereum_client::Error> {
+        EthApi::get_approx_balance(self).await
+    }
+
+    async fn provide_content_message(
+        &self,
+        block_number: u32,
+        message: ContentMessage,
+    ) -> Result<TxHash, ethereum_client::Error> {
+        let ContentMessage {
+            total_leaves,
+            leaf_index,
+            nonce,
+            sender,
+            receiver,
+            payload,
+            proof,
+        } = message;
+
+        EthApi::provide_content_message(
+            self,
+            block_number,
+            total_leaves,
+            leaf_index,
+            nonce,
+            sender,
+            receiver,
+            payload,
+            proof,
+        )
+        .await
+    }
+
+    async fn provide_content_messages(
+        &self,
+        block_number: u32,
+        messages: Vec<ContentMessage>,
+    ) -> Result<BatchDelivery, ethereum_client::Error> {
+        EthApi::provide_content_messages(self, block_number, messages).await
+    }
+
+    async fn reconnect(&self) -> Result<Self, ethereum_client::Error> {
+        EthApi::reconnect(self).await
+    }
+}
+
 pub struct Request {
     pub message: Message,
     pub relayed_root: RelayedMerkleRoot,
@@ -16,6 +88,9 @@ pub struct Request {
 pub enum Response {
     MessageAlreadyProcessed(Uuid),
     ProcessingStarted(TxHash, Uuid),
+    /// Message is included into the batch transaction. The transaction succeeding doesn't
+    /// mean the message is processed: it has to be checked separately.
+    BatchProcessingStarted(TxHash, Uuid),
 }
 
 pub struct MessageSenderIo {
@@ -53,13 +128,16 @@ impl MessageSenderIo {
     }
 }
 
-pub struct MessageSender {
-    eth_api: EthApi,
+pub struct MessageSender<E = EthApi> {
+    eth_api: E,
+    max_batch_size: usize,
+    /// Requests taken from the channel but not delivered yet.
+    pending: Vec<Request>,
 
     metrics: Metrics,
 }
 
-impl MeteredService for MessageSender {
+impl<E> MeteredService for MessageSender<E> {
     fn get_sources(&self) -> impl IntoIterator<Item = Box<dyn prometheus::core::Collector>> {
         self.metrics.get_sources()
     }
@@ -76,18 +154,31 @@ impl_metered_service! {
             "ethereum_message_sender_total_submissions",
             "Total number of merkle root submissions to Ethereum",
         ),
+        total_batched_messages: IntCounter = IntCounter::new(
+            "ethereum_message_sender_total_batched_messages",
+            "Total number of messages delivered to Ethereum in batches",
+        ),
     }
 }
 
-impl MessageSender {
-    pub fn new(eth_api: EthApi) -> Self {
+impl<E: DeliveryApi> MessageSender<E> {
+    pub fn new(eth_api: E) -> Self {
         Self {
             eth_api,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            pending: Vec::new(),
 
             metrics: Metrics::new(),
         }
     }
 
+    /// Delivers up to `max_batch_size` queued messages of the same merkle root in one
+    /// transaction.
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size.max(1);
+        self
+    }
+
     pub fn spawn(self) -> MessageSenderIo {
         let (requests_tx, requests_rx) = mpsc::unbounded_channel();
         let (responses_tx, responses_rx) = mpsc::unbounded_channel();
@@ -101,8 +192,8 @@ impl MessageSender {
     }
 }
 
-async fn task(
-    mut this: MessageSender,
+async fn task<E: DeliveryApi>(
+    mut this: MessageSender<E>,
     mut channel_message_data: UnboundedReceiver<Request>,
     channel_tx_data: UnboundedSender<Response>,
 ) {
@@ -134,72 +225,343 @@ async fn task(
     }
 }
 
-async fn task_inner(
-    this: &mut MessageSender,
+async fn task_inner<E: DeliveryApi>(
+    this: &mut MessageSender<E>,
     requests: &mut UnboundedReceiver<Request>,
     responses: &UnboundedSender<Response>,
 ) -> anyhow::Result<()> {
-    while let Some(request) = requests.recv().await {
-        let Request {
-            message,
-            relayed_root,
-            proof,
-            tx_uuid,
-        } = request;
-
-        let tx_hash = match this
-            .eth_api
-            .provide_content_message(
-                relayed_root.block.0,
-                proof.num_leaves as u32,
-                proof.leaf_index as u32,
-                message.nonce_be,
-                message.source,
-                message.destination,
-                message.payload.to_vec(),
-                proof.proof,
-            )
-            .await
-        {
-            Ok(tx_hash) => tx_hash,
-            Err(ethereum_client::Error::MessageQueue(
-                IMessageQueueErrors::MessageAlreadyProcessed(_),
-            )) => {
-                log::info!(
-                    "Message with nonce {} already processed, skipping: tx_uuid = {}",
-                    hex::encode(message.nonce_be),
-                    tx_uuid
-                );
-                if responses
-                    .send(Response::MessageAlreadyProcessed(tx_uuid))
-                    .is_err()
-                {
+    loop {
+        if this.pending.is_empty() {
+            let Some(request) = requests.recv().await else {
+                return Ok(());
+            };
+            this.pending.push(request);
+        }
+
+        while this.pending.len() < this.max_batch_size {
+            let Ok(request) = requests.try_recv() else {
+                break;
+            };
+            this.pending.push(request);
+        }
+
+        let mut batches = group_by_root(std::mem::take(&mut this.pending)).into_iter();
+        while let Some(batch) = batches.next() {
+            let delivered = match batch.len() {
+                1 => send_one_by_one(this, batch, responses).await,
+                _ => send_batch(this, batch, responses).await,
+            };
+
+            match delivered {
+                Ok(true) => {}
+                Ok(false) => {
                     log::info!("Response channel closed, exiting");
                     return Ok(());
                 }
+                Err(e) => {
+                    this.pending.extend(batches.flatten());
+                    return Err(e);
+                }
+            }
+        }
+
+        let fee_payer_balance = this.eth_api.get_approx_balance().await?;
+        this.metrics.fee_payer_balance.set(fee_payer_balance);
+    }
+}
+
+/// Splits `requests` into batches of the same merkle root, keeping their order.
+fn group_by_root(requests: Vec<Request>) -> Vec<Vec<Request>> {
+    let mut batches: Vec<Vec<Request>> = Vec::new();
+    for request in requests {
+        match batches
+            .iter_mut()
+            .find(|batch| batch[0].relayed_root == request.relayed_root)
+        {
+            Some(batch) => batch.push(request),
+            None => batches.push(vec![request]),
+        }
+    }
+
+    batches
+}
+
+/// Delivers `requests` one by one, each in its own transaction. On error the requests
+/// after the failed one are queued again. Returns `false` if the response channel is
+/// closed.
+async fn send_one_by_one<E: DeliveryApi>(
+    this: &mut MessageSender<E>,
+    requests: Vec<Request>,
+    responses: &UnboundedSender<Response>,
+) -> anyhow::Result<bool> {
+    let mut requests = requests.into_iter();
+    while let Some(request) = requests.next() {
+        match send_message(this, request, responses).await {
+            Ok(true) => {}
+            Ok(false) => return Ok(false),
+            Err(e) => {
+                this.pending.extend(requests);
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Delivers a message in its own transaction. Returns `false` if the response channel
+/// is closed.
+async fn send_message<E: DeliveryApi>(
+    this: &mut MessageSender<E>,
+    request: Request,
+    responses: &UnboundedSender<Response>,
+) -> anyhow::Result<bool> {
+    let message = content_message(&request);
+    let nonce = hex::encode(message.nonce);
+    let Request {
+        relayed_root,
+        tx_uuid,
+        ..
+    } = request;
+
+    let tx_hash = match this
+        .eth_api
+        .provide_content_message(relayed_root.block.0, message)
+        .await
+    {
+        Ok(tx_hash) => tx_hash,
+        Err(ethereum_client::Error::MessageQueue(
+            IMessageQueueErrors::MessageAlreadyProcessed(_),
+        )) => {
+            log::info!(
+                "Message with nonce {nonce} already processed, skipping: tx_uuid = {tx_uuid}"
+            );
+            return Ok(responses
+                .send(Response::MessageAlreadyProcessed(tx_uuid))
+                .is_ok());
+        }
+        Err(e) => return Err(anyhow::anyhow!("Failed to provide content message: {e}")),
+    };
+
+    log::info!("Message with nonce {nonce} relaying started: tx_hash = {tx_hash}");
+
+    this.metrics.total_submissions.inc();
+
+    Ok(responses
+        .send(Response::ProcessingStarted(tx_hash, tx_uuid))
+        .is_ok())
+}
+
+/// Delivers messages of the same merkle root in one transaction. Messages which can't be
+/// batched are delivered one by one. Returns `false` if the response channel is closed.
+async fn send_batch<E: DeliveryApi>(
+    this: &mut MessageSender<E>,
+    batch: Vec<Request>,
+    responses: &UnboundedSender<Response>,
+) -> anyhow::Result<bool> {
+    let block = batch[0].relayed_root.block;
+    let messages = batch.iter().map(content_message).collect();
+
+    let delivery = match this
+        .eth_api
+        .provide_content_messages(block.0, messages)
+        .await
+    {
+        Ok(delivery) => delivery,
+        // Sending the messages again one by one might deliver them twice. The batch is
+        // watched as if it's sent instead: if it never reached a node, its messages fail
+        // as those of any transaction that isn't found.
+        Err(ethereum_client::Error::TransactionMaybeSent { tx_hash, error }) => {
+            log::warn!(
+                "Batch {tx_hash} of {} messages of block #{block} might not have been sent: {error}",
+                batch.len()
+            );
+            this.metrics.total_submissions.inc();
+
+            return Ok(batch.into_iter().all(|request| {
+                responses
+                    .send(Response::BatchProcessingStarted(tx_hash, request.tx_uuid))
+                    .is_ok()
+            }));
+        }
+        Err(e) => {
+            log::warn!(
+                "Failed to deliver batch of {} messages of block #{block}, delivering them one by one: {e}",
+                batch.len()
+            );
+            return send_one_by_one(this, batch, responses).await;
+        }
+    };
+
+    if let Some(tx_hash) = delivery.tx_hash {
+        this.metrics.total_submissions.inc();
+        log::info!("Batch of messages of block #{block} relaying started: tx_hash = {tx_hash}");
+    }
+
+    let mut rejected = Vec::new();
+    for (request, outcome) in batch.into_iter().zip(delivery.outcomes) {
+        let nonce = hex::encode(request.message.nonce_be);
+        let response = match (outcome, delivery.tx_hash) {
+            (BatchOutcome::Included, Some(tx_hash)) => {
+                log::info!("Message with nonce {nonce} is included into batch {tx_hash}");
+                this.metrics.total_batched_messages.inc();
+                Response::BatchProcessingStarted(tx_hash, request.tx_uuid)
+            }
+
+            (BatchOutcome::AlreadyProcessed, _) => {
+                log::info!(
+                    "Message with nonce {nonce} already processed, skipping: tx_uuid = {}",
+                    request.tx_uuid
+                );
+                Response::MessageAlreadyProcessed(request.tx_uuid)
+            }
+
+            (outcome, _) => {
+                log::warn!(
+                    "Message with nonce {nonce} can't be batched ({outcome:?}), delivering it separately"
+                );
+                rejected.push(request);
                 continue;
             }
-            Err(e) => return Err(anyhow::anyhow!("Failed to provide content message: {e}")),
         };
 
-        log::info!(
-            "Message with nonce {} relaying started: tx_hash = {tx_hash}",
-            hex::encode(message.nonce_be)
-        );
+        if responses.send(response).is_err() {
+            return Ok(false);
+        }
+    }
 
-        this.metrics.total_submissions.inc();
+    send_one_by_one(this, rejected, responses).await
+}
 
-        if responses
-            .send(Response::ProcessingStarted(tx_hash, tx_uuid))
-            .is_err()
-        {
-            log::info!("Response channel closed, exiting");
-            return Ok(());
+fn content_message(request: &Request) -> ContentMessage {
+    ContentMessage {
+        total_leaves: request.proof.num_leaves as u32,
+        leaf_index: request.proof.leaf_index as u32,
+        nonce: request.message.nonce_be,
+        sender: request.message.source,
+        receiver: request.message.destination,
+        payload: request.message.payload.to_vec(),
+        proof: request.proof.proof.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_relayer::common::{AuthoritySetId, GearBlockNumber};
+    use alloy::transports::TransportErrorKind;
+    use primitive_types::H256;
+    use std::sync::{Arc, Mutex};
+
+    const BATCH_TX: TxHash = TxHash::repeat_byte(7);
+
+    /// Ethereum where it's never settled whether a batch transaction reached a node.
+    #[derive(Clone, Default)]
+    struct FakeEthereum {
+        /// Nonces of the messages delivered in their own transactions.
+        delivered: Arc<Mutex<Vec<[u8; 32]>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl DeliveryApi for FakeEthereum {
+        async fn get_approx_balance(&self) -> Result<f64, ethereum_client::Error> {
+            Ok(1.0)
         }
 
-        let fee_payer_balance = this.eth_api.get_approx_balance().await?;
-        this.metrics.fee_payer_balance.set(fee_payer_balance);
+        async fn provide_content_message(
+            &self,
+            _block_number: u32,
+            message: ContentMessage,
+        ) -> Result<TxHash, ethereum_client::Error> {
+            self.delivered.lock().unwrap().push(message.nonce);
+            Ok(TxHash::from(message.nonce))
+        }
+
+        async fn provide_content_messages(
+            &self,
+            _block_number: u32,
+            _messages: Vec<ContentMessage>,
+        ) -> Result<BatchDelivery, ethereum_client::Error> {
+            Err(ethereum_client::Error::TransactionMaybeSent {
+                tx_hash: BATCH_TX,
+                error: TransportErrorKind::backend_gone(),
+            })
+        }
+
+        async fn reconnect(&self) -> Result<Self, ethereum_client::Error> {
+            Ok(self.clone())
+        }
+    }
+
+    fn request(block: u32, nonce: u8) -> Request {
+        Request {
+            message: Message {
+                nonce_be: [nonce; 32],
+                ..Default::default()
+            },
+            relayed_root: RelayedMerkleRoot {
+                block: GearBlockNumber(block),
+                block_hash: H256::repeat_byte(block as u8),
+                timestamp: 0,
+                authority_set_id: AuthoritySetId(0),
+                merkle_root: H256::repeat_byte(block as u8),
+            },
+            proof: MerkleProof {
+                root: [block as u8; 32],
+                proof: vec![],
+                num_leaves: 3,
+                leaf_index: nonce as u64,
+            },
+            tx_uuid: Uuid::new_v4(),
+        }
+    }
+
+    #[test]
+    fn groups_requests_by_merkle_root() {
+        let requests = vec![request(1, 0), request(2, 1), request(1, 2), request(2, 3)];
+        let uuids: Vec<_> = requests.iter().map(|request| request.tx_uuid).collect();
+
+        let batches = group_by_root(requests);
+
+        let batches: Vec<Vec<_>> = batches
+            .iter()
+            .map(|batch| batch.iter().map(|request| request.tx_uuid).collect())
+            .collect();
+        assert_eq!(
+            batches,
+            vec![vec![uuids[0], uuids[2]], vec![uuids[1], uuids[3]]]
+        );
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn watches_maybe_sent_batch_instead_of_delivering_messages_again() {
+        let ethereum = FakeEthereum::default();
+        let mut sender = MessageSender::new(ethereum.clone())
+            .with_max_batch_size(2)
+            .spawn();
+
+        let requests = vec![request(1, 0), request(1, 1)];
+        let uuids: Vec<_> = requests.iter().map(|request| request.tx_uuid).collect();
+        for request in requests {
+            assert!(sender.send(
+                request.message,
+                request.relayed_root,
+                request.proof,
+                request.tx_uuid
+            ));
+        }
+
+        let mut watched = vec![];
+        for _ in &uuids {
+            match sender.recv().await.unwrap() {
+                Response::BatchProcessingStarted(tx_hash, tx_uuid) => {
+                    assert_eq!(tx_hash, BATCH_TX);
+                    watched.push(tx_uuid);
+                }
+                _ => panic!("Message of the batch isn't watched"),
+            }
+        }
+        assert_eq!(watched, uuids);
+        assert!(ethereum.delivered.lock().unwrap().is_empty());
+    }
 }
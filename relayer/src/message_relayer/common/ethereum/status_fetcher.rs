@@ -1,5 +1,6 @@
 use crate::{
     common::{self, BASE_RETRY_DELAY, MAX_RETRIES},
+    message_relayer::common::dead_letter::FailureClass,
     rpc,
 };
 use alloy::providers::{
@@ -25,15 +26,20 @@ pub struct StatusFetcher {
 pub struct Request {
     pub tx_uuid: Uuid,
     pub tx_hash: TxHash,
+    /// Nonce of the message when it's delivered in a batch transaction. Its logs are checked
+    /// to confirm the message is processed by it.
+    pub message_nonce: Option<[u8; 32]>,
 }
 
 pub enum Response {
     Success(Uuid, TxHash),
     Failed(Uuid, PendingTransactionError),
+    /// Batch transaction is confirmed but the message isn't processed by it.
+    NotProcessed(Uuid, TxHash),
 }
 
 type TxWatchResult =
-    Result<(Uuid, alloy::rpc::types::TransactionReceipt), (Uuid, TxHash, PendingTransactionError)>;
+    Result<(Request, alloy::rpc::types::TransactionReceipt), (Request, PendingTransactionError)>;
 type TxWatch = BoxFuture<'static, TxWatchResult>;
 
 pub struct StatusFetcherIo {
@@ -42,8 +48,34 @@ pub struct StatusFetcherIo {
 }
 
 impl StatusFetcherIo {
+    pub fn new(requests: UnboundedSender<Request>, responses: UnboundedReceiver<Response>) -> Self {
+        Self {
+            requests,
+            responses,
+        }
+    }
+
     pub fn send_request(&self, tx_uuid: Uuid, tx_hash: TxHash) -> bool {
-        let request = Request { tx_uuid, tx_hash };
+        let request = Request {
+            tx_uuid,
+            tx_hash,
+            message_nonce: None,
+        };
+        self.requests.send(request).is_ok()
+    }
+
+    /// Watches batch transaction `tx_hash` which includes the message with `message_nonce`.
+    pub fn send_batch_request(
+        &self,
+        tx_uuid: Uuid,
+        tx_hash: TxHash,
+        message_nonce: [u8; 32],
+    ) -> bool {
+        let request = Request {
+            tx_uuid,
+            tx_hash,
+            message_nonce: Some(message_nonce),
+        };
         self.requests.send(request).is_ok()
     }
 
@@ -103,10 +135,7 @@ impl StatusFetcher {
         let (responses_tx, responses_rx) = mpsc::unbounded_channel();
         tokio::task::spawn(task(self, requests_rx, responses_tx));
 
-        StatusFetcherIo {
-            requests: requests_tx,
-            responses: responses_rx,
-        }
+        StatusFetcherIo::new(requests_tx, responses_rx)
     }
 }
 
@@ -162,21 +191,19 @@ async fn task_inner(
                     return Ok(());
                 };
 
-                let Request { tx_uuid, tx_hash, .. } = request;
-
                 this.metrics.pending_tx_count.inc();
 
                 txs.push(watch_tx(
                     this.eth_api.raw_provider().root().clone(),
-                    tx_uuid,
-                    tx_hash,
+                    request,
                     this.confirmations,
                 ));
             }
 
             Some(tx) = txs.next(), if !txs.is_empty() => {
                 match tx {
-                    Ok((uuid, receipt)) => {
+                    Ok((request, receipt)) => {
+                        let uuid = request.tx_uuid;
                         let tx_hash = receipt.transaction_hash;
                         let gas_used = receipt.gas_used;
 
@@ -193,19 +220,27 @@ async fn task_inner(
 
 
                         this.metrics.pending_tx_count.dec();
-                        responses.send(Response::Success(uuid, tx_hash))?;
+
+                        let processed = request.message_nonce.is_none_or(|nonce| {
+                            this.eth_api.processed_messages(&receipt).contains(&nonce)
+                        });
+                        if processed {
+                            responses.send(Response::Success(uuid, tx_hash))?;
+                        } else {
+                            log::warn!("Message of transaction {uuid} isn't processed by batch {tx_hash}");
+                            responses.send(Response::NotProcessed(uuid, tx_hash))?;
+                        }
                     }
-                    Err((uuid, tx_hash, e)) if rpc::is_recoverable_error_text(&e) => {
-                        log::warn!("Recoverable error while polling transaction {tx_hash}: {e}. Reconnecting and continuing to watch");
+                    Err((request, e)) if rpc::is_recoverable_error_text(&e) => {
+                        log::warn!("Recoverable error while polling transaction {}: {e}. Reconnecting and continuing to watch", request.tx_hash);
                         this.eth_api = this.eth_api.reconnect().await?;
                         txs.push(watch_tx(
                             this.eth_api.raw_provider().root().clone(),
-                            uuid,
-                            tx_hash,
+                            request,
                             this.confirmations,
                         ));
                     }
-                    Err((uuid, _tx_hash, e)) => {
+                    Err((Request { tx_uuid: uuid, .. }, e)) => {
                         this.metrics.total_failed_txs.inc();
                         log::error!("Failed to get transaction {uuid} status: {e}");
                         responses.send(Response::Failed(uuid, e))?;
@@ -216,16 +251,27 @@ async fn task_inner(
     }
 }
 
-fn watch_tx(provider: RootProvider, tx_uuid: Uuid, tx_hash: TxHash, confirmations: u64) -> TxWatch {
+/// Class of a failure to watch a transaction: only a transport error may be permanent,
+/// losing the watcher or waiting too long is fixed by watching again.
+pub fn failure_class(err: &PendingTransactionError) -> FailureClass {
+    match err {
+        PendingTransactionError::TransportError(err) => rpc::classify_alloy_rpc(err).into(),
+        PendingTransactionError::FailedToRegister
+        | PendingTransactionError::Recv(_)
+        | PendingTransactionError::TxWatcher(_) => FailureClass::Transient,
+    }
+}
+
+fn watch_tx(provider: RootProvider, request: Request, confirmations: u64) -> TxWatch {
     Box::pin(async move {
-        let pending = PendingTransactionBuilder::new(provider, tx_hash);
-        Ok((
-            tx_uuid,
-            pending
-                .with_required_confirmations(confirmations)
-                .get_receipt()
-                .await
-                .map_err(|e| (tx_uuid, tx_hash, e))?,
-        ))
+        let pending = PendingTransactionBuilder::new(provider, request.tx_hash);
+        match pending
+            .with_required_confirmations(confirmations)
+            .get_receipt()
+            .await
+        {
+            Ok(receipt) => Ok((request, receipt)),
+            Err(e) => Err((request, e)),
+        }
     })
 }
@@ -0,0 +1,126 @@
+//! Evaluation of bridging fees against the cost of message delivery on Ethereum.
+
+use ethereum_client::EthApi;
+use std::sync::Arc;
+
+/// Gas a message delivery is assumed to take unless configured otherwise.
+pub const DEFAULT_GAS_PER_MESSAGE: u64 = 200_000;
+/// Margin over the delivery cost a fee has to cover unless configured otherwise.
+pub const DEFAULT_MARGIN_PERCENT: u32 = 20;
+
+/// ETH has 18 decimals and VARA has 12.
+const WEI_PER_VARA_UNIT: f64 = 1e6;
+
+/// Source of the exchange rate between ETH and VARA.
+#[async_trait::async_trait]
+pub trait PriceSource: Send + Sync {
+    /// Price of one ETH in VARA.
+    async fn eth_price_in_vara(&self) -> anyhow::Result<f64>;
+}
+
+/// Exchange rate set in the config.
+pub struct StaticPriceSource {
+    eth_price_in_vara: f64,
+}
+
+impl StaticPriceSource {
+    pub fn new(eth_price_in_vara: f64) -> Self {
+        Self { eth_price_in_vara }
+    }
+}
+
+#[async_trait::async_trait]
+impl PriceSource for StaticPriceSource {
+    async fn eth_price_in_vara(&self) -> anyhow::Result<f64> {
+        Ok(self.eth_price_in_vara)
+    }
+}
+
+/// Fee market with prices set in the config.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeeMarketConfig {
+    pub eth_price_in_vara: f64,
+    pub gas_per_message: u64,
+    pub margin_percent: u32,
+}
+
+impl FeeMarketConfig {
+    pub fn build(&self, eth_api: EthApi) -> FeeMarket {
+        FeeMarket::new(
+            eth_api,
+            Arc::new(StaticPriceSource::new(self.eth_price_in_vara)),
+            self.gas_per_message,
+            self.margin_percent,
+        )
+    }
+}
+
+/// Estimates the fee a message has to be paid with to be relayed without a loss.
+#[derive(Clone)]
+pub struct FeeMarket {
+    eth_api: EthApi,
+    prices: Arc<dyn PriceSource>,
+    gas_per_message: u64,
+    margin_percent: u32,
+}
+
+impl FeeMarket {
+    pub fn new(
+        eth_api: EthApi,
+        prices: Arc<dyn PriceSource>,
+        gas_per_message: u64,
+        margin_percent: u32,
+    ) -> Self {
+        Self {
+            eth_api,
+            prices,
+            gas_per_message,
+            margin_percent,
+        }
+    }
+
+    /// Minimal fee, in the smallest VARA units, covering delivery of a message at the
+    /// current gas price plus margin.
+    pub async fn required_fee(&self) -> anyhow::Result<u128> {
+        let fees = self.eth_api.estimate_fee_caps().await?;
+        let eth_price_in_vara = self.prices.eth_price_in_vara().await?;
+
+        Ok(required_fee(
+            self.gas_per_message,
+            fees.max_fee_per_gas,
+            eth_price_in_vara,
+            self.margin_percent,
+        ))
+    }
+}
+
+fn required_fee(
+    gas_per_message: u64,
+    max_fee_per_gas: u128,
+    eth_price_in_vara: f64,
+    margin_percent: u32,
+) -> u128 {
+    let cost_wei = gas_per_message as u128 * max_fee_per_gas;
+    let fee = cost_wei as f64 * eth_price_in_vara * (100 + margin_percent) as f64
+        / (100.0 * WEI_PER_VARA_UNIT);
+
+    fee.ceil() as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_fee_covers_cost_with_margin() {
+        // 200k gas at 10 Gwei is 0.002 ETH, that is 200 VARA at 100k VARA per ETH.
+        let fee = required_fee(200_000, 10_000_000_000, 100_000.0, 20);
+        assert_eq!(fee, 240 * 1_000_000_000_000);
+
+        assert_eq!(required_fee(200_000, 0, 100_000.0, 20), 0);
+        assert_eq!(
+            required_fee(200_000, 10_000_000_000, 100_000.0, 0),
+            200 * 1_000_000_000_000
+        );
+    }
+}
@@ -24,6 +24,13 @@ pub struct MerkleRootFetcherIo {
 }
 
 impl MerkleRootFetcherIo {
+    pub fn new(requests: UnboundedSender<Request>, responses: UnboundedReceiver<Response>) -> Self {
+        Self {
+            requests,
+            responses,
+        }
+    }
+
     pub fn send_request(
         &self,
         tx_uuid: Uuid,
@@ -61,10 +68,7 @@ impl MerkleProofFetcher {
         let (resp_tx, resp_rx) = mpsc::unbounded_channel();
         tokio::task::spawn(task(self, req_rx, resp_tx));
 
-        MerkleRootFetcherIo {
-            requests: req_tx,
-            responses: resp_rx,
-        }
+        MerkleRootFetcherIo::new(req_tx, resp_rx)
     }
 }
 
@@ -1,8 +1,11 @@
-use bridging_payment_client::bridging_payment::events::BridgingPaymentEvents;
+use bridging_payment_client::{
+    bridging_payment::events::BridgingPaymentEvents, traits::BridgingPayment as _, BridgingPayment,
+};
 
+use gear_common::api_provider::ApiProviderConnection;
 use primitive_types::H256;
 use prometheus::IntCounter;
-use sails_rs::events::EventIo;
+use sails_rs::{calls::Query, events::EventIo, gclient::calls::GClientRemoting, ActorId};
 use tokio::sync::{
     broadcast::{error::RecvError, Receiver},
     mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
@@ -13,6 +16,7 @@ use crate::message_relayer::common::{GearBlock, PaidMessage};
 
 pub struct MessagePaidEventExtractor {
     bridging_payment_address: H256,
+    api_provider: ApiProviderConnection,
 
     metrics: Metrics,
 }
@@ -33,9 +37,10 @@ impl_metered_service! {
 }
 
 impl MessagePaidEventExtractor {
-    pub fn new(bridging_payment_address: H256) -> Self {
+    pub fn new(bridging_payment_address: H256, api_provider: ApiProviderConnection) -> Self {
         Self {
             bridging_payment_address,
+            api_provider,
             metrics: Metrics::new(),
         }
     }
@@ -83,7 +88,7 @@ impl MessagePaidEventExtractor {
     ) -> anyhow::Result<()> {
         let block_hash = block.hash();
 
-        let messages = block
+        let messages: Vec<_> = block
             .user_message_sent_events(self.bridging_payment_address, H256::zero())
             .filter_map(|event| {
                 BridgingPaymentEvents::decode_event(event)
@@ -92,26 +97,62 @@ impl MessagePaidEventExtractor {
                         BridgingPaymentEvents::BridgingPaid { nonce } => Some(nonce),
                         _ => None,
                     })
-            });
+            })
+            .collect();
+        if messages.is_empty() {
+            return Ok(());
+        }
 
-        let mut total = 0;
+        let fee = self.fee_paid(&block).await?;
+        let total = messages.len();
         for nonce in messages {
             let mut nonce_be = [0; 32];
             nonce.to_big_endian(&mut nonce_be);
 
-            sender.send(PaidMessage { nonce: nonce_be })?;
-            total += 1;
+            sender.send(PaidMessage {
+                nonce: nonce_be,
+                fee,
+            })?;
         }
 
-        if total > 0 {
-            log::info!(
-                "Found {total} paid messages in block #{} ({block_hash}",
-                block.number()
-            );
-        }
+        log::info!(
+            "Found {total} paid messages with fee {fee} in block #{} ({block_hash}",
+            block.number()
+        );
 
         self.metrics.total_messages_found.inc_by(total as u64);
 
         Ok(())
     }
+
+    /// Fee paid for messages of `block`. The program accepts exactly its current fee, which
+    /// only an admin call in the same block may have changed. The event doesn't carry the
+    /// fee, so it's read before and after the block, and the smaller one is credited: a
+    /// fee change never credits a message more than was paid.
+    async fn fee_paid(&self, block: &GearBlock) -> anyhow::Result<u128> {
+        let before = self.fee_at(block.header.parent_hash.0.into()).await?;
+        let after = self.fee_at(block.hash()).await?;
+        if before != after {
+            log::warn!(
+                "Fee of bridging-payment changed from {before} to {after} in block #{}, crediting paid messages with {}",
+                block.number(),
+                before.min(after)
+            );
+        }
+
+        Ok(before.min(after))
+    }
+
+    /// Fee of bridging-payment at `block_hash`.
+    async fn fee_at(&self, block_hash: H256) -> anyhow::Result<u128> {
+        let remoting = GClientRemoting::new(self.api_provider.clone().gclient());
+        let state = BridgingPayment::new(remoting)
+            .get_state()
+            .at_block(block_hash.0.into())
+            .recv(ActorId::from(self.bridging_payment_address.0))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read bridging-payment state: {e:?}"))?;
+
+        Ok(state.fee)
+    }
 }
@@ -0,0 +1,234 @@
+//! Lifecycle events of bridged messages. Transaction managers emit them on every state
+//! transition and the web server streams them to subscribers.
+
+use alloy::sol_types::SolEvent;
+use alloy_rlp::Decodable;
+use eth_events_electra_client::EthToVaraEvent;
+use ethereum_client::abi::IERC20Manager;
+use ethereum_common::utils::ReceiptEnvelope;
+use primitive_types::{H160, H256, U256};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Number of events kept for subscribers which are lagging behind.
+const CAPACITY: usize = 1_024;
+
+/// Length of the vft-manager payload: sender, receiver, token and amount.
+const VFT_MANAGER_PAYLOAD_LEN: usize = 32 + 20 + 20 + 32;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifecycleEvent {
+    pub tx_uuid: Uuid,
+    /// Nonce of the bridged message, Gear to Ethereum messages only.
+    pub nonce: Option<U256>,
+    /// Account which requested the token transfer, if known.
+    pub sender: Option<String>,
+    /// Ethereum address of the transferred token, if known.
+    pub token: Option<H160>,
+    #[serde(flatten)]
+    pub kind: LifecycleEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LifecycleEventKind {
+    /// Message is queued on Gear and picked up by the relayer.
+    MessageQueued { block: u32 },
+    /// Merkle root covering the message is relayed to Ethereum.
+    MerkleRootIncluded {
+        merkle_root_block: u32,
+        merkle_root: H256,
+    },
+    /// Transaction delivering the message is sent to Ethereum.
+    MessageSubmitted { tx_hash: H256 },
+    /// Message is processed on Ethereum. Transaction hash is unknown when the message was
+    /// already processed by someone else.
+    MessageDelivered { tx_hash: Option<H256> },
+    /// Message failed to be relayed to Ethereum.
+    MessageFailed { reason: String },
+
+    /// Ethereum transaction is picked up by the relayer.
+    TransactionSeen { tx_hash: H256, slot: u64 },
+    /// Checkpoint light client reached the slot of the transaction.
+    CheckpointReached { tx_hash: H256, checkpoint: u64 },
+    /// Inclusion proof of the transaction is composed.
+    ProofComposed { tx_hash: H256 },
+    /// Result of `submit_receipt` on Gear.
+    ReceiptSubmitted {
+        tx_hash: H256,
+        error: Option<String>,
+    },
+}
+
+impl LifecycleEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MessageQueued { .. } => "message_queued",
+            Self::MerkleRootIncluded { .. } => "merkle_root_included",
+            Self::MessageSubmitted { .. } => "message_submitted",
+            Self::MessageDelivered { .. } => "message_delivered",
+            Self::MessageFailed { .. } => "message_failed",
+            Self::TransactionSeen { .. } => "transaction_seen",
+            Self::CheckpointReached { .. } => "checkpoint_reached",
+            Self::ProofComposed { .. } => "proof_composed",
+            Self::ReceiptSubmitted { .. } => "receipt_submitted",
+        }
+    }
+}
+
+/// Sender and token of a transfer, if they can be determined.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Parties {
+    pub sender: Option<String>,
+    pub token: Option<H160>,
+}
+
+impl Parties {
+    /// Decodes the payload vft-manager sends to `ERC20Manager`.
+    pub fn from_vft_manager_payload(payload: &[u8]) -> Self {
+        if payload.len() != VFT_MANAGER_PAYLOAD_LEN {
+            return Self::default();
+        }
+
+        Self {
+            sender: Some(format!("{:?}", H256::from_slice(&payload[..32]))),
+            token: Some(H160::from_slice(&payload[52..72])),
+        }
+    }
+
+    /// Looks for the `BridgingRequested` event of `ERC20Manager` in the proven receipt.
+    pub fn from_eth_to_vara_event(event: &EthToVaraEvent) -> Self {
+        let Ok(receipt) = ReceiptEnvelope::decode(&mut &event.receipt_rlp[..]) else {
+            return Self::default();
+        };
+
+        receipt
+            .logs()
+            .iter()
+            .find_map(|log| {
+                IERC20Manager::BridgingRequested::decode_raw_log_validate(
+                    log.topics(),
+                    &log.data.data,
+                )
+                .ok()
+            })
+            .map(|event| Self {
+                sender: Some(format!("{:?}", H160::from(event.from.0 .0))),
+                token: Some(H160::from(event.token.0 .0)),
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Broadcasts lifecycle events to subscribers. Events are dropped when nobody listens.
+#[derive(Clone)]
+pub struct LifecycleEvents {
+    sender: broadcast::Sender<LifecycleEvent>,
+}
+
+impl Default for LifecycleEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        Self { sender }
+    }
+}
+
+impl LifecycleEvents {
+    pub fn emit(
+        &self,
+        tx_uuid: Uuid,
+        nonce: Option<U256>,
+        parties: Parties,
+        kind: LifecycleEventKind,
+    ) {
+        let _ = self.sender.send(LifecycleEvent {
+            tx_uuid,
+            nonce,
+            sender: parties.sender,
+            token: parties.token,
+            kind,
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// Subscription filter. Events which don't carry a filtered field never match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    pub nonce: Option<U256>,
+    pub sender: Option<String>,
+    pub token: Option<H160>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &LifecycleEvent) -> bool {
+        let nonce = self.nonce.is_none_or(|nonce| event.nonce == Some(nonce));
+        let sender = self.sender.as_ref().is_none_or(|sender| {
+            event
+                .sender
+                .as_ref()
+                .is_some_and(|event_sender| event_sender.eq_ignore_ascii_case(sender))
+        });
+        let token = self.token.is_none_or(|token| event.token == Some(token));
+
+        nonce && sender && token
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_vft_manager_payload() {
+        let mut payload = vec![0x11; 32];
+        payload.extend([0x22; 20]);
+        payload.extend([0x33; 20]);
+        payload.extend([0; 32]);
+
+        let parties = Parties::from_vft_manager_payload(&payload);
+        assert_eq!(
+            parties.sender,
+            Some(format!("{:?}", H256::repeat_byte(0x11)))
+        );
+        assert_eq!(parties.token, Some(H160::repeat_byte(0x33)));
+
+        assert_eq!(
+            Parties::from_vft_manager_payload(&payload[1..]),
+            Parties::default()
+        );
+    }
+
+    #[test]
+    fn filter_requires_every_field() {
+        let event = LifecycleEvent {
+            tx_uuid: Uuid::nil(),
+            nonce: Some(U256::from(5)),
+            sender: Some(format!("{:?}", H160::repeat_byte(0xab))),
+            token: None,
+            kind: LifecycleEventKind::MessageQueued { block: 1 },
+        };
+
+        assert!(EventFilter::default().matches(&event));
+        assert!(EventFilter {
+            nonce: Some(U256::from(5)),
+            sender: Some(format!("{:?}", H160::repeat_byte(0xab)).to_uppercase()),
+            token: None,
+        }
+        .matches(&event));
+        assert!(!EventFilter {
+            nonce: Some(U256::from(6)),
+            ..Default::default()
+        }
+        .matches(&event));
+        assert!(!EventFilter {
+            token: Some(H160::repeat_byte(1)),
+            ..Default::default()
+        }
+        .matches(&event));
+    }
+}
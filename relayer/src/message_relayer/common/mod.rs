@@ -13,8 +13,11 @@ use subxt::{
     OnlineClient,
 };
 
+pub mod dead_letter;
 pub mod ethereum;
+pub mod fee_market;
 pub mod gear;
+pub mod lifecycle;
 pub mod paid_messages_filter;
 
 #[derive(
@@ -89,6 +92,8 @@ pub struct MessageInBlock {
 #[derive(Clone, Copy, Debug)]
 pub struct PaidMessage {
     pub nonce: [u8; 32],
+    /// Fee paid, in the smallest VARA units.
+    pub fee: u128,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -235,6 +240,7 @@ pub fn message_hash(message: &Message) -> [u8; 32] {
 
 pub mod web_request {
     use super::*;
+    use dead_letter::{DeadLetter, FailureClass};
     use tokio::sync::oneshot::Sender;
 
     #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -268,6 +274,9 @@ pub mod web_request {
             block_number: u32,
             response: Sender<MerkleRootsResponse>,
         },
+        GetStatus {
+            response: Sender<crate::merkle_roots::status::RelayerStatus>,
+        },
     }
 
     #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -288,4 +297,78 @@ pub mod web_request {
             message: String,
         },
     }
+
+    /// Key a transaction of a [`TransactionQueueStatus`] is looked up by.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum TransactionLookup {
+        Uuid(uuid::Uuid),
+        Nonce(U256),
+        TxHash(H256),
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+    pub struct TransactionState {
+        pub uuid: uuid::Uuid,
+        pub status: String,
+        /// Nonce of the bridged message, if the direction has one.
+        pub nonce: Option<U256>,
+        /// Ethereum transaction the message originates from or is delivered with.
+        pub tx_hash: Option<H256>,
+        /// Gear block number or Ethereum slot the message originates from.
+        pub block: Option<u64>,
+        pub completed: bool,
+        pub failure: Option<String>,
+    }
+
+    /// Transaction in the dead-letter queue. `transaction.status` is the stage it failed at.
+    #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+    pub struct FailedTransaction {
+        pub transaction: TransactionState,
+        pub class: FailureClass,
+        pub attempts: u32,
+        /// Unix timestamp of the last failure.
+        pub failed_at: u64,
+        /// Unix timestamp of the next automatic retry, if any.
+        pub retry_at: Option<u64>,
+    }
+
+    impl FailedTransaction {
+        pub fn new<T>(dead_letter: &DeadLetter<T>, transaction: TransactionState) -> Self {
+            Self {
+                transaction,
+                class: dead_letter.class,
+                attempts: dead_letter.attempts,
+                failed_at: dead_letter.failed_at,
+                retry_at: dead_letter.retry_at,
+            }
+        }
+    }
+
+    /// Query of `POST /failed_transactions/{uuid}/retry`.
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+    pub struct RetryRequest {
+        /// Stage to restart the transaction at. The stage it failed at if unset.
+        pub stage: Option<String>,
+    }
+
+    /// Paid message which isn't relayed as its fee doesn't cover the delivery cost.
+    #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+    pub struct UnderpaidMessage {
+        pub nonce: U256,
+        pub block: u32,
+        /// Fee paid, in the smallest VARA units.
+        pub fee_paid: u128,
+        /// Fee required at the last evaluation.
+        pub fee_required: u128,
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+    pub struct TransactionQueueStatus {
+        pub pending: Vec<TransactionState>,
+        pub completed: usize,
+        pub failed: usize,
+        /// Messages held back by the fee market.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub underpaid: Vec<UnderpaidMessage>,
+    }
 }
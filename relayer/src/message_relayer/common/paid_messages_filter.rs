@@ -1,19 +1,35 @@
-use super::{MessageInBlock, PaidMessage};
-use futures::{
-    future::{self, Either},
-    pin_mut,
-};
+use super::{fee_market::FeeMarket, web_request::UnderpaidMessage, MessageInBlock, PaidMessage};
 use gclient::ext::sp_runtime::AccountId32;
+use primitive_types::U256;
 use prometheus::IntGauge;
-use std::collections::{HashMap, HashSet};
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{
+    mpsc::{UnboundedReceiver, UnboundedSender},
+    RwLock,
+};
 use utils_prometheus::{impl_metered_service, MeteredService};
 
+/// How often fees of underpaid messages are evaluated again.
+const UNDERPAID_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Underpaid messages by nonce, shared with the transaction manager to be reported.
+pub type UnderpaidBacklog = Arc<RwLock<BTreeMap<[u8; 32], UnderpaidMessage>>>;
+
 pub struct PaidMessagesFilter {
     pending_messages: HashMap<[u8; 32], MessageInBlock>,
-    pending_nonces: Vec<[u8; 32]>,
+    /// Fees paid for messages which aren't discovered yet.
+    pending_fees: HashMap<[u8; 32], u128>,
+    /// Paid messages waiting for the fee to be evaluated, or held back as underpaid.
+    paid_messages: BTreeMap<[u8; 32], (MessageInBlock, u128)>,
     excluded_from_fees: HashSet<AccountId32>,
     sender: UnboundedSender<MessageInBlock>,
+    /// Relays messages regardless of the fee paid if not set.
+    fee_market: Option<FeeMarket>,
+    underpaid: UnderpaidBacklog,
 
     metrics: Metrics,
 }
@@ -29,7 +45,15 @@ impl_metered_service! {
         pending_messages_count: IntGauge = IntGauge::new(
             "paid_messages_filter_pending_messages_count",
             "Amount of discovered but not paid messages",
-        )
+        ),
+        underpaid_messages_count: IntGauge = IntGauge::new(
+            "paid_messages_filter_underpaid_messages_count",
+            "Amount of paid messages whose fee doesn't cover the delivery cost",
+        ),
+        required_fee: IntGauge = IntGauge::new(
+            "paid_messages_filter_required_fee",
+            "Fee required at the last evaluation, in the smallest VARA units",
+        ),
     }
 }
 
@@ -40,13 +64,24 @@ impl PaidMessagesFilter {
     ) -> Self {
         Self {
             pending_messages: HashMap::default(),
-            pending_nonces: vec![],
+            pending_fees: HashMap::default(),
+            paid_messages: BTreeMap::default(),
             excluded_from_fees,
             sender,
+            fee_market: None,
+            underpaid: UnderpaidBacklog::default(),
             metrics: Metrics::new(),
         }
     }
 
+    /// Relays only messages whose fee covers the cost estimated by `fee_market`. The rest
+    /// are held in `underpaid` and relayed once the cost drops enough.
+    pub fn with_fee_market(mut self, fee_market: FeeMarket, underpaid: UnderpaidBacklog) -> Self {
+        self.fee_market = Some(fee_market);
+        self.underpaid = underpaid;
+        self
+    }
+
     pub fn spawn(
         mut self,
         mut messages: UnboundedReceiver<MessageInBlock>,
@@ -59,6 +94,87 @@ impl PaidMessagesFilter {
             }
         });
     }
+
+    /// Relays paid messages whose fee is enough and updates the backlog of the rest.
+    async fn relay_paid(&mut self) -> anyhow::Result<()> {
+        if self.paid_messages.is_empty() {
+            return Ok(());
+        }
+
+        let required_fee = match &self.fee_market {
+            None => 0,
+            Some(fee_market) => match fee_market.required_fee().await {
+                Ok(required_fee) => required_fee,
+                Err(e) => {
+                    log::warn!("Failed to estimate required fee, holding paid messages: {e}");
+                    return Ok(());
+                }
+            },
+        };
+        self.metrics
+            .required_fee
+            .set(required_fee.try_into().unwrap_or(i64::MAX));
+
+        self.relay_with_fee(required_fee).await
+    }
+
+    /// Credits `fee` paid for the message with `nonce`. Every payment goes to the relayer,
+    /// so paying twice adds up. Returns whether the message is held as underpaid, so its
+    /// fee has to be evaluated again.
+    fn credit_fee(&mut self, nonce: [u8; 32], fee: u128) -> bool {
+        if let Some((_, paid_fee)) = self.paid_messages.get_mut(&nonce) {
+            *paid_fee = paid_fee.saturating_add(fee);
+            return true;
+        }
+
+        let paid_fee = self.pending_fees.entry(nonce).or_default();
+        *paid_fee = paid_fee.saturating_add(fee);
+        false
+    }
+
+    async fn relay_with_fee(&mut self, required_fee: u128) -> anyhow::Result<()> {
+        let (paid, underpaid): (BTreeMap<_, _>, BTreeMap<_, _>) =
+            std::mem::take(&mut self.paid_messages)
+                .into_iter()
+                .partition(|(_, (_, fee))| *fee >= required_fee);
+        self.paid_messages = underpaid;
+
+        for (message, fee) in paid.into_values() {
+            log::debug!(
+                "Message {} is paid with {fee}, relaying",
+                hex::encode(message.message.nonce_be)
+            );
+            self.sender.send(message)?;
+        }
+
+        let mut backlog = self.underpaid.write().await;
+        for (nonce, (_, fee)) in &self.paid_messages {
+            if !backlog.contains_key(nonce) {
+                log::warn!(
+                    "Message {} is underpaid: fee {fee} is less than required {required_fee}, holding it",
+                    hex::encode(nonce)
+                );
+            }
+        }
+        *backlog = self
+            .paid_messages
+            .iter()
+            .map(|(nonce, (message, fee))| {
+                let underpaid = UnderpaidMessage {
+                    nonce: U256::from_big_endian(nonce),
+                    block: message.block.0,
+                    fee_paid: *fee,
+                    fee_required: required_fee,
+                };
+                (*nonce, underpaid)
+            })
+            .collect();
+        self.metrics
+            .underpaid_messages_count
+            .set(backlog.len() as i64);
+
+        Ok(())
+    }
 }
 
 async fn run_inner(
@@ -66,25 +182,17 @@ async fn run_inner(
     messages: &mut UnboundedReceiver<MessageInBlock>,
     paid_messages: &mut UnboundedReceiver<PaidMessage>,
 ) -> anyhow::Result<()> {
-    loop {
-        let recv_messages = messages.recv();
-        pin_mut!(recv_messages);
-
-        let recv_paid_messages = paid_messages.recv();
-        pin_mut!(recv_paid_messages);
+    let mut recheck = tokio::time::interval(UNDERPAID_CHECK_INTERVAL);
 
-        match future::select(recv_messages, recv_paid_messages).await {
-            Either::Left((None, _)) => {
-                log::info!("Channel with messages closed. Exiting");
-                return Ok(());
-            }
-
-            Either::Right((None, _)) => {
-                log::info!("Channel with paid messages closed. Exiting");
-                return Ok(());
-            }
+    loop {
+        let mut recheck_due = false;
+        tokio::select! {
+            message = messages.recv() => {
+                let Some(message) = message else {
+                    log::info!("Channel with messages closed. Exiting");
+                    return Ok(());
+                };
 
-            Either::Left((Some(message), _)) => {
                 if self_
                     .excluded_from_fees
                     .contains(&AccountId32::from(message.message.source))
@@ -110,16 +218,37 @@ async fn run_inner(
                 }
             }
 
-            Either::Right((Some(PaidMessage { nonce }), _)) => self_.pending_nonces.push(nonce),
+            paid = paid_messages.recv() => {
+                let Some(PaidMessage { nonce, fee }) = paid else {
+                    log::info!("Channel with paid messages closed. Exiting");
+                    return Ok(());
+                };
+
+                recheck_due = self_.credit_fee(nonce, fee);
+            }
+
+            _ = recheck.tick() => recheck_due = true,
         }
 
-        for i in (0..self_.pending_nonces.len()).rev() {
-            if let Some(message) = self_.pending_messages.remove(&self_.pending_nonces[i]) {
-                self_.sender.send(message)?;
-                self_.pending_nonces.remove(i);
+        let nonces: Vec<_> = self_
+            .pending_fees
+            .keys()
+            .filter(|nonce| self_.pending_messages.contains_key(*nonce))
+            .copied()
+            .collect();
+        for nonce in &nonces {
+            if let (Some(message), Some(fee)) = (
+                self_.pending_messages.remove(nonce),
+                self_.pending_fees.remove(nonce),
+            ) {
+                self_.paid_messages.insert(*nonce, (message, fee));
             }
         }
 
+        if !nonces.is_empty() || recheck_due {
+            self_.relay_paid().await?;
+        }
+
         self_
             .metrics
             .pending_messages_count
@@ -187,7 +316,12 @@ mod tests {
             msg_receiver.is_empty(),
             "Message from account1 should not be sent"
         );
-        paid_sender.send(PaidMessage { nonce: [1u8; 32] }).unwrap();
+        paid_sender
+            .send(PaidMessage {
+                nonce: [1u8; 32],
+                fee: 0,
+            })
+            .unwrap();
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
         let res = msg_receiver.recv().await.unwrap();
@@ -199,4 +333,77 @@ mod tests {
         drop(msg_sender);
         drop(paid_sender);
     }
+
+    #[tokio::test]
+    async fn holds_underpaid_messages() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut filter = PaidMessagesFilter::new(HashSet::new(), sender);
+
+        for (nonce, fee) in [(1u8, 100), (2, 50)] {
+            let message = MessageInBlock {
+                message: Message {
+                    nonce_be: [nonce; 32],
+                    ..Default::default()
+                },
+                block: GearBlockNumber(7),
+                block_hash: H256::default(),
+                authority_set_id: AuthoritySetId(0),
+            };
+            filter.paid_messages.insert([nonce; 32], (message, fee));
+        }
+
+        filter.relay_with_fee(80).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().message.nonce_be, [1; 32]);
+        assert!(receiver.is_empty());
+        assert_eq!(
+            filter
+                .underpaid
+                .read()
+                .await
+                .values()
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![UnderpaidMessage {
+                nonce: U256::from_big_endian(&[2; 32]),
+                block: 7,
+                fee_paid: 50,
+                fee_required: 80,
+            }]
+        );
+
+        // Gas got cheaper.
+        filter.relay_with_fee(50).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().message.nonce_be, [2; 32]);
+        assert!(filter.underpaid.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn credits_top_ups_of_underpaid_messages() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut filter = PaidMessagesFilter::new(HashSet::new(), sender);
+
+        let message = MessageInBlock {
+            message: Message {
+                nonce_be: [3; 32],
+                ..Default::default()
+            },
+            block: GearBlockNumber(7),
+            block_hash: H256::default(),
+            authority_set_id: AuthoritySetId(0),
+        };
+        filter.paid_messages.insert([3; 32], (message, 50));
+        filter.relay_with_fee(80).await.unwrap();
+        assert!(receiver.is_empty());
+
+        assert!(filter.credit_fee([3; 32], 30));
+        assert!(filter.pending_fees.is_empty());
+        filter.relay_with_fee(80).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap().message.nonce_be, [3; 32]);
+        assert!(filter.underpaid.read().await.is_empty());
+
+        // Message isn't discovered yet.
+        assert!(!filter.credit_fee([4; 32], 30));
+        assert!(!filter.credit_fee([4; 32], 30));
+        assert_eq!(filter.pending_fees[&[4; 32]], 60);
+    }
 }
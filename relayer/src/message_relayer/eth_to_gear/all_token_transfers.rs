@@ -1,7 +1,5 @@
 use super::{
-    message_sender::MessageSender,
-    proof_composer::ProofComposer,
-    storage::{JSONStorage, Storage},
+    message_sender::MessageSender, proof_composer::ProofComposer, storage::Storage,
     tx_manager::TransactionManager,
 };
 use crate::message_relayer::common::{
@@ -38,7 +36,7 @@ pub struct Relayer {
 
     storage: Arc<dyn Storage>,
 
-    tx_manager: TransactionManager,
+    tx_manager: Arc<TransactionManager>,
 }
 
 impl MeteredService for Relayer {
@@ -62,7 +60,7 @@ impl Relayer {
         historical_proxy_address: H256,
         vft_manager_address: H256,
         mut api_provider: ApiProviderConnection,
-        storage_path: String,
+        storage: Arc<dyn Storage>,
         genesis_time: u64,
         eth_unprocessed_block_storage_path: Option<String>,
     ) -> anyhow::Result<Self> {
@@ -79,8 +77,6 @@ impl Relayer {
         let ethereum_block_listener =
             EthereumBlockListener::new(eth_api.clone(), from_eth_block, block_storage);
 
-        let storage = Arc::new(JSONStorage::new(storage_path));
-
         let deposit_event_extractor = DepositEventExtractor::new(
             eth_api.clone(),
             erc20_manager_address,
@@ -116,7 +112,7 @@ impl Relayer {
             suri,
         );
 
-        let tx_manager = TransactionManager::new(storage.clone());
+        let tx_manager = Arc::new(TransactionManager::new(storage.clone()));
 
         Ok(Self {
             gear_block_listener,
@@ -1,3 +1,4 @@
+use crate::message_relayer::common::dead_letter::FailureClass;
 use alloy_primitives::FixedBytes;
 use eth_events_electra_client::EthToVaraEvent;
 use futures::executor::block_on;
@@ -75,7 +76,8 @@ pub struct Response {
 #[derive(Clone, Debug)]
 pub enum MessageStatus {
     Success,
-    Failure(String),
+    /// Reason of the failure with its class, determined where the error occurs.
+    Failure(String, FailureClass),
 }
 
 impl_metered_service!(
@@ -229,21 +231,23 @@ impl MessageSender {
             .await
             .map_err(|e| {
                 let error = anyhow::anyhow!("Failed to send message: {e:?}");
+                let class = FailureClass::classify(&anyhow::Error::new(e));
                 responses
                     .send(Response {
                         tx_uuid,
-                        status: MessageStatus::Failure(error.to_string()),
+                        status: MessageStatus::Failure(error.to_string(), class),
                     })
                     .unwrap_or_default();
 
                 error
             })?
             .map_err(|e| {
+                // Historical proxy answered with an error.
                 let error = anyhow::anyhow!("Failed to receive message: {e:?}");
                 responses
                     .send(Response {
                         tx_uuid,
-                        status: MessageStatus::Failure(error.to_string()),
+                        status: MessageStatus::Failure(error.to_string(), FailureClass::Permanent),
                     })
                     .unwrap_or_default();
 
@@ -269,7 +273,7 @@ impl MessageSender {
             responses
                 .send(Response {
                     tx_uuid,
-                    status: MessageStatus::Failure(error.to_string()),
+                    status: MessageStatus::Failure(error.to_string(), FailureClass::Permanent),
                 })
                 .unwrap_or_default();
             error
@@ -307,7 +311,7 @@ impl MessageSender {
                 if responses
                     .send(Response {
                         tx_uuid,
-                        status: MessageStatus::Failure(message),
+                        status: MessageStatus::Failure(message, FailureClass::Permanent),
                     })
                     .is_err()
                 {
@@ -321,7 +325,7 @@ impl MessageSender {
                 if responses
                     .send(Response {
                         tx_uuid,
-                        status: MessageStatus::Failure(message),
+                        status: MessageStatus::Failure(message, FailureClass::Permanent),
                     })
                     .is_err()
                 {
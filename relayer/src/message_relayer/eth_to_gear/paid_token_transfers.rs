@@ -1,8 +1,4 @@
-use super::{
-    message_sender, proof_composer,
-    storage::{JSONStorage, Storage},
-    tx_manager,
-};
+use super::{message_sender, proof_composer, storage::Storage, tx_manager};
 use crate::message_relayer::common::{
     ethereum::{
         self, block_listener::BlockListener as EthereumBlockListener,
@@ -37,7 +33,7 @@ pub struct Relayer {
 
     message_sender: message_sender::MessageSender,
     proof_composer: proof_composer::ProofComposer,
-    tx_manager: TransactionManager,
+    tx_manager: Arc<TransactionManager>,
 
     transaction_data_extractor: Option<TransactionDataExtractor>,
     tx_events_sender: Option<UnboundedSender<TxHashWithSlot>>,
@@ -70,7 +66,7 @@ impl Relayer {
         historical_proxy_address: H256,
         vft_manager_address: H256,
         mut api_provider: ApiProviderConnection,
-        storage_path: String,
+        storage: Arc<dyn Storage>,
         genesis_time: u64,
         eth_unprocessed_block_storage_path: Option<String>,
         http_receiver: Option<UnboundedReceiver<EthTransaction>>,
@@ -92,9 +88,7 @@ impl Relayer {
             Arc::new(crate::message_relayer::common::gear::block_storage::NoStorage),
         );
 
-        let storage = Arc::new(JSONStorage::new(storage_path));
-
-        let tx_manager = TransactionManager::new(storage.clone());
+        let tx_manager = Arc::new(TransactionManager::new(storage.clone()));
 
         let message_paid_event_extractor = MessagePaidEventExtractor::new(
             eth_api.clone(),
@@ -169,6 +163,11 @@ impl Relayer {
         })
     }
 
+    /// Transaction queue of the relayer, served by the status endpoints of the web server.
+    pub fn tx_manager(&self) -> Arc<TransactionManager> {
+        self.tx_manager.clone()
+    }
+
     pub async fn run(self) -> anyhow::Result<()> {
         let [gear_blocks] = self.gear_block_listener.run().await;
         let ethereum_blocks = self.ethereum_block_listener.spawn();
@@ -1,4 +1,10 @@
-use std::ops::ControlFlow;
+use std::{
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use crate::message_relayer::common::{EthereumSlotNumber, TxHashWithSlot};
 use alloy::providers::Provider;
@@ -44,6 +50,7 @@ pub struct Response {
 pub struct ProofComposerIo {
     requests_channel: UnboundedSender<Request>,
     responses_channel: UnboundedReceiver<Response>,
+    last_checkpoint: Arc<AtomicU64>,
 }
 
 impl ProofComposerIo {
@@ -54,6 +61,15 @@ impl ProofComposerIo {
         Self {
             requests_channel,
             responses_channel,
+            last_checkpoint: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Last checkpoint the proof composer received.
+    pub fn last_checkpoint(&self) -> Option<EthereumSlotNumber> {
+        match self.last_checkpoint.load(Ordering::Relaxed) {
+            0 => None,
+            slot => Some(EthereumSlotNumber(slot)),
         }
     }
 
@@ -100,6 +116,7 @@ pub struct ProofComposer {
     pub suri: String,
     pub to_process: Vec<(Uuid, TxHashWithSlot)>,
 
+    shared_checkpoint: Arc<AtomicU64>,
     metrics: Metrics,
 }
 
@@ -127,6 +144,7 @@ impl ProofComposer {
             suri,
             to_process: Vec::with_capacity(100),
 
+            shared_checkpoint: Arc::new(AtomicU64::new(0)),
             metrics: Metrics::new(),
         }
     }
@@ -134,12 +152,16 @@ impl ProofComposer {
     pub fn run(self, checkpoints: UnboundedReceiver<EthereumSlotNumber>) -> ProofComposerIo {
         let (requests_tx, requests_rx) = unbounded_channel();
         let (response_tx, response_rx) = unbounded_channel();
+        let last_checkpoint = self.shared_checkpoint.clone();
 
         spawn_blocking(move || {
             block_on(task(self, checkpoints, requests_rx, response_tx));
         });
 
-        ProofComposerIo::new(requests_tx, response_rx)
+        ProofComposerIo {
+            last_checkpoint,
+            ..ProofComposerIo::new(requests_tx, response_rx)
+        }
     }
 
     async fn process(
@@ -240,6 +262,7 @@ async fn handle_requests(
                     this.last_checkpoint = Some(checkpoint);
 
                     this.metrics.last_checkpoint.set(checkpoint.0 as i64);
+                    this.shared_checkpoint.store(checkpoint.0, Ordering::Relaxed);
 
                     this.waiting_for_checkpoints.retain(|(tx_uuid, tx)| {
                         if tx.slot_number <= checkpoint {
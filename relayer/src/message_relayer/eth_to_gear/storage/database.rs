@@ -0,0 +1,548 @@
+use super::{BlockStorage, JSONStorage, Storage};
+use crate::message_relayer::{
+    common::{
+        dead_letter::{DeadLetter, StoredDeadLetter},
+        EthereumSlotNumber,
+    },
+    eth_to_gear::tx_manager::{Transaction, TransactionManager},
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+const BLOCK_PREFIX: u8 = b'b';
+const QUEUED_PREFIX: u8 = b'q';
+const COMPLETED_PREFIX: u8 = b'c';
+const FAILED_PREFIX: u8 = b'f';
+const MIGRATED_KEY: &[u8] = b"meta/migrated";
+
+const TRANSACTION_PREFIXES: [u8; 3] = [QUEUED_PREFIX, COMPLETED_PREFIX, FAILED_PREFIX];
+
+/// How many completed transactions are kept in the database. Older ones are dropped
+/// from it and aren't loaded after a restart.
+const COMPLETED_TO_KEEP: usize = 10_000;
+
+/// Entries stored in the database which are needed to write changes only.
+#[derive(Default)]
+struct Cache {
+    /// Stored blocks by key.
+    blocks: HashMap<Vec<u8>, Vec<u8>>,
+    /// Stored completed transactions, the oldest first.
+    completed: BTreeSet<Uuid>,
+}
+
+impl Cache {
+    /// Adds changed `blocks` to the `batch` and removes stored blocks which aren't among
+    /// them anymore.
+    fn write_blocks(&mut self, batch: &mut sled::Batch, blocks: Vec<(Vec<u8>, Vec<u8>)>) {
+        let mut keys = HashSet::with_capacity(blocks.len());
+        for (key, value) in blocks {
+            keys.insert(key.clone());
+            if self.blocks.get(&key) == Some(&value) {
+                continue;
+            }
+
+            batch.insert(key.clone(), value.clone());
+            self.blocks.insert(key, value);
+        }
+
+        self.blocks.retain(|key, _| {
+            if keys.contains(key) {
+                return true;
+            }
+
+            batch.remove(key.clone());
+            false
+        });
+    }
+
+    /// Adds `transactions` to the `batch` under the prefix they're stored with, removing
+    /// them from under the other prefixes. Transactions without an entry are removed. The
+    /// oldest completed transactions over `completed_to_keep` are removed as well.
+    fn write_transactions(
+        &mut self,
+        batch: &mut sled::Batch,
+        transactions: Vec<(Uuid, Option<(u8, Vec<u8>)>)>,
+        completed_to_keep: usize,
+    ) {
+        for (uuid, entry) in transactions {
+            let prefix = entry.as_ref().map(|(prefix, _)| *prefix);
+            for other in TRANSACTION_PREFIXES {
+                if Some(other) != prefix {
+                    batch.remove(tx_key(other, uuid));
+                }
+            }
+
+            if prefix == Some(COMPLETED_PREFIX) {
+                self.completed.insert(uuid);
+            } else {
+                self.completed.remove(&uuid);
+            }
+
+            if let Some((prefix, value)) = entry {
+                batch.insert(tx_key(prefix, uuid), value);
+            }
+        }
+
+        while self.completed.len() > completed_to_keep {
+            let uuid = self.completed.pop_first().expect("Completed aren't empty");
+            batch.remove(tx_key(COMPLETED_PREFIX, uuid));
+        }
+    }
+}
+
+/// Storage which keeps transactions and blocks in an embedded database. Only transactions
+/// changed since the previous save and changed blocks are written, processed blocks over
+/// the kept amount and the oldest completed transactions are removed from it.
+pub struct DatabaseStorage {
+    db: sled::Db,
+    json_storage_path: PathBuf,
+    block_storage: BlockStorage,
+    completed_to_keep: usize,
+    cache: Mutex<Cache>,
+}
+
+impl DatabaseStorage {
+    /// Opens database at `path`. On the first load state is migrated from the
+    /// [`JSONStorage`] at `json_storage_path` if it exists.
+    pub fn open(path: &Path, json_storage_path: PathBuf) -> anyhow::Result<Self> {
+        let db = sled::open(path)
+            .with_context(|| format!("Failed to open database at {}", path.display()))?;
+
+        Ok(Self::from_db(db, json_storage_path))
+    }
+
+    fn from_db(db: sled::Db, json_storage_path: PathBuf) -> Self {
+        Self {
+            db,
+            json_storage_path,
+            block_storage: BlockStorage::new(),
+            completed_to_keep: COMPLETED_TO_KEEP,
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    async fn migrate(&self, tx_manager: &TransactionManager) -> anyhow::Result<()> {
+        let json = JSONStorage::new(&self.json_storage_path);
+        json.load(tx_manager).await?;
+
+        let blocks = std::mem::take(&mut *json.block_storage.blocks.write().await);
+        let transactions = tx_manager.transactions.read().await.len()
+            + tx_manager.completed.read().await.len()
+            + tx_manager.failed.read().await.len();
+        if !blocks.is_empty() || transactions > 0 {
+            log::info!(
+                "Migrating transaction storage from {} ({} blocks, {} transactions)",
+                self.json_storage_path.display(),
+                blocks.len(),
+                transactions
+            );
+        }
+        *self.block_storage.blocks.write().await = blocks;
+
+        let mut batch = sled::Batch::default();
+        batch.insert(MIGRATED_KEY, Vec::new());
+        self.write(batch, Some(tx_manager)).await
+    }
+
+    /// Writes transactions of `tx_manager`, if set, changed since the previous write and
+    /// blocks along with the `batch`.
+    async fn write(
+        &self,
+        mut batch: sled::Batch,
+        tx_manager: Option<&TransactionManager>,
+    ) -> anyhow::Result<()> {
+        self.block_storage.prune().await;
+        let blocks = self
+            .block_storage
+            .blocks
+            .read()
+            .await
+            .iter()
+            .map(|(slot, block)| Ok((block_key(*slot).to_vec(), serde_json::to_vec(block)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        {
+            let mut cache = self.cache.lock().await;
+            // Changes are taken under the lock so that concurrent saves can't store an older
+            // state of a transaction after a newer one.
+            let changed = match tx_manager {
+                Some(tx_manager) => tx_manager.take_changed().await,
+                None => HashSet::new(),
+            };
+            let result = self
+                .write_changed(&mut cache, batch, tx_manager, &changed, blocks)
+                .await;
+            if let (Err(_), Some(tx_manager)) = (&result, tx_manager) {
+                tx_manager.mark_changed(changed).await;
+            }
+            result?;
+        }
+        self.db.flush_async().await?;
+
+        Ok(())
+    }
+
+    async fn write_changed(
+        &self,
+        cache: &mut Cache,
+        mut batch: sled::Batch,
+        tx_manager: Option<&TransactionManager>,
+        changed: &HashSet<Uuid>,
+        blocks: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> anyhow::Result<()> {
+        if let Some(tx_manager) = tx_manager {
+            let transactions = self.transaction_entries(tx_manager, changed).await?;
+            cache.write_transactions(&mut batch, transactions, self.completed_to_keep);
+        }
+        cache.write_blocks(&mut batch, blocks);
+
+        Ok(self.db.apply_batch(batch)?)
+    }
+
+    /// Entries of `changed` transactions along with the prefix they're stored under, `None`
+    /// for the ones which were removed.
+    async fn transaction_entries(
+        &self,
+        tx_manager: &TransactionManager,
+        changed: &HashSet<Uuid>,
+    ) -> anyhow::Result<Vec<(Uuid, Option<(u8, Vec<u8>)>)>> {
+        let transactions = tx_manager.transactions.read().await;
+        let completed = tx_manager.completed.read().await;
+        let failed = tx_manager.failed.read().await;
+
+        let mut entries = Vec::with_capacity(changed.len());
+        for uuid in changed {
+            let entry = if let Some(tx) = transactions.get(uuid) {
+                Some((QUEUED_PREFIX, serde_json::to_vec(tx)?))
+            } else if let Some(tx) = completed.get(uuid) {
+                Some((COMPLETED_PREFIX, serde_json::to_vec(tx)?))
+            } else if let Some(dead_letter) = failed.get(uuid) {
+                Some((FAILED_PREFIX, serde_json::to_vec(dead_letter)?))
+            } else {
+                None
+            };
+            entries.push((*uuid, entry));
+        }
+
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl Storage for DatabaseStorage {
+    fn block_storage(&self) -> &BlockStorage {
+        &self.block_storage
+    }
+
+    async fn save(&self, tx_manager: &TransactionManager) -> anyhow::Result<()> {
+        self.write(sled::Batch::default(), Some(tx_manager)).await
+    }
+
+    async fn load(&self, tx_manager: &TransactionManager) -> anyhow::Result<()> {
+        if !self.db.contains_key(MIGRATED_KEY)? {
+            return self.migrate(tx_manager).await;
+        }
+
+        let mut cache = self.cache.lock().await;
+
+        let mut blocks = BTreeMap::new();
+        for entry in self.db.scan_prefix([BLOCK_PREFIX]) {
+            let (key, value) = entry?;
+            blocks.insert(slot_number(&key)?, serde_json::from_slice(&value)?);
+            cache.blocks.insert(key.to_vec(), value.to_vec());
+        }
+        *self.block_storage.blocks.write().await = blocks;
+
+        for prefix in [QUEUED_PREFIX, COMPLETED_PREFIX] {
+            for entry in self.db.scan_prefix([prefix]) {
+                let (key, value) = entry?;
+                let tx: Transaction = serde_json::from_slice(&value)?;
+                if prefix == COMPLETED_PREFIX {
+                    cache.completed.insert(uuid(&key)?);
+                }
+                tx_manager.add_transaction(tx).await;
+            }
+        }
+
+        let mut failed = BTreeMap::new();
+        for entry in self.db.scan_prefix([FAILED_PREFIX]) {
+            let (key, value) = entry?;
+            let dead_letter: DeadLetter<Transaction> = serde_json::from_slice(&value)?;
+            failed.insert(uuid(&key)?, StoredDeadLetter::DeadLetter(dead_letter));
+        }
+        tx_manager.restore_failed(failed).await;
+        // Loaded transactions are stored as they are.
+        tx_manager.take_changed().await;
+
+        Ok(())
+    }
+
+    async fn save_blocks(&self) -> anyhow::Result<()> {
+        self.write(sled::Batch::default(), None).await
+    }
+}
+
+fn block_key(slot: EthereumSlotNumber) -> [u8; 9] {
+    let mut key = [BLOCK_PREFIX; 9];
+    key[1..].copy_from_slice(&slot.0.to_be_bytes());
+    key
+}
+
+fn slot_number(key: &[u8]) -> anyhow::Result<EthereumSlotNumber> {
+    let bytes = key
+        .get(1..9)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("Invalid block key: {}", hex::encode(key)))?;
+
+    Ok(EthereumSlotNumber(u64::from_be_bytes(bytes)))
+}
+
+fn tx_key(prefix: u8, uuid: Uuid) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + 16);
+    key.push(prefix);
+    key.extend_from_slice(uuid.as_bytes());
+    key
+}
+
+fn uuid(key: &[u8]) -> anyhow::Result<Uuid> {
+    key.get(1..)
+        .and_then(|bytes| Uuid::from_slice(bytes).ok())
+        .ok_or_else(|| anyhow::anyhow!("Invalid transaction key: {}", hex::encode(key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_relayer::{
+        common::{dead_letter::FailureClass, EthereumBlockNumber, TxHashWithSlot},
+        eth_to_gear::tx_manager::TxStatus,
+    };
+    use ethereum_client::TxHash;
+    use std::sync::Arc;
+
+    fn temporary_db() -> sled::Db {
+        sled::Config::new().temporary(true).open().unwrap()
+    }
+
+    /// Path of a JSON storage which is never created.
+    fn missing_json_storage() -> PathBuf {
+        std::env::temp_dir().join(format!("eth-gear-storage-{}", Uuid::new_v4()))
+    }
+
+    fn transaction(slot: u64, status: TxStatus) -> Transaction {
+        Transaction::new(
+            TxHashWithSlot {
+                slot_number: EthereumSlotNumber(slot),
+                tx_hash: TxHash::from([slot as u8; 32]),
+            },
+            status,
+        )
+    }
+
+    fn stored_keys(db: &sled::Db, prefix: u8) -> usize {
+        db.scan_prefix([prefix]).count()
+    }
+
+    async fn reload(db: sled::Db) -> (Arc<DatabaseStorage>, TransactionManager) {
+        let storage = Arc::new(DatabaseStorage::from_db(db, missing_json_storage()));
+        let tx_manager = TransactionManager::new(storage.clone());
+        storage.load(&tx_manager).await.unwrap();
+
+        (storage, tx_manager)
+    }
+
+    #[tokio::test]
+    async fn writes_changes_through() {
+        let db = temporary_db();
+        let (storage, tx_manager) = reload(db.clone()).await;
+
+        let queued = transaction(1, TxStatus::ComposeProof);
+        let completed = transaction(2, TxStatus::ComposeProof);
+        let failed = transaction(3, TxStatus::ComposeProof);
+        for tx in [&queued, &completed, &failed] {
+            tx_manager.add_transaction(tx.clone()).await;
+        }
+        storage
+            .block_storage()
+            .add_block(
+                EthereumSlotNumber(1),
+                EthereumBlockNumber(10),
+                [queued.tx.tx_hash].into_iter(),
+            )
+            .await;
+        storage.save(&tx_manager).await.unwrap();
+        assert_eq!(stored_keys(&db, QUEUED_PREFIX), 3);
+        assert_eq!(stored_keys(&db, BLOCK_PREFIX), 1);
+
+        let mut tx = tx_manager
+            .transactions
+            .write()
+            .await
+            .remove(&completed.uuid)
+            .unwrap();
+        tx.status = TxStatus::Completed;
+        tx_manager.add_transaction(tx).await;
+        tx_manager
+            .fail_transaction(
+                failed.uuid,
+                "Message stuck".to_string(),
+                FailureClass::Permanent,
+            )
+            .await;
+        storage.save(&tx_manager).await.unwrap();
+        drop((storage, tx_manager));
+
+        let (_storage, tx_manager) = reload(db.clone()).await;
+        let transactions = tx_manager.transactions.read().await;
+        assert_eq!(
+            transactions.keys().copied().collect::<Vec<_>>(),
+            vec![queued.uuid]
+        );
+        assert!(tx_manager
+            .completed
+            .read()
+            .await
+            .contains_key(&completed.uuid));
+        let failed_transactions = tx_manager.failed_transactions().await;
+        assert_eq!(failed_transactions.len(), 1);
+        assert_eq!(failed_transactions[0].transaction.uuid, failed.uuid);
+        assert_eq!(failed_transactions[0].attempts, 1);
+        assert_eq!(stored_keys(&db, QUEUED_PREFIX), 1);
+    }
+
+    #[tokio::test]
+    async fn writes_only_changed_transactions() {
+        let db = temporary_db();
+        let (storage, tx_manager) = reload(db.clone()).await;
+
+        let unchanged = transaction(1, TxStatus::ComposeProof);
+        let changed = transaction(2, TxStatus::ComposeProof);
+        tx_manager.add_transaction(unchanged.clone()).await;
+        tx_manager.add_transaction(changed.clone()).await;
+        storage.save(&tx_manager).await.unwrap();
+
+        // Entries removed behind the storage's back are only restored on a change.
+        db.remove(tx_key(QUEUED_PREFIX, unchanged.uuid)).unwrap();
+        db.remove(tx_key(QUEUED_PREFIX, changed.uuid)).unwrap();
+        tx_manager
+            .fail_transaction(
+                changed.uuid,
+                "Message stuck".to_string(),
+                FailureClass::Permanent,
+            )
+            .await;
+        storage.save(&tx_manager).await.unwrap();
+
+        assert_eq!(stored_keys(&db, QUEUED_PREFIX), 0);
+        assert_eq!(stored_keys(&db, FAILED_PREFIX), 1);
+        assert!(tx_manager.take_changed().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn compacts_blocks_and_completed_transactions() {
+        let db = temporary_db();
+        let mut storage = DatabaseStorage::from_db(db.clone(), missing_json_storage());
+        storage.completed_to_keep = 2;
+        let storage = Arc::new(storage);
+        let tx_manager = TransactionManager::new(storage.clone());
+        storage.load(&tx_manager).await.unwrap();
+
+        for slot in 0..150 {
+            storage
+                .block_storage()
+                .add_block(
+                    EthereumSlotNumber(slot),
+                    EthereumBlockNumber(slot),
+                    std::iter::empty(),
+                )
+                .await;
+        }
+        storage.save_blocks().await.unwrap();
+        assert_eq!(stored_keys(&db, BLOCK_PREFIX), 99);
+        let (first_block, _) = db.scan_prefix([BLOCK_PREFIX]).next().unwrap().unwrap();
+        assert_eq!(slot_number(&first_block).unwrap(), EthereumSlotNumber(51));
+
+        let completed: Vec<_> = (0..3)
+            .map(|slot| transaction(slot, TxStatus::Completed))
+            .collect();
+        for tx in &completed {
+            tx_manager.add_transaction(tx.clone()).await;
+        }
+        storage.save(&tx_manager).await.unwrap();
+        drop((storage, tx_manager));
+
+        let (_storage, tx_manager) = reload(db).await;
+        assert_eq!(
+            tx_manager
+                .completed
+                .read()
+                .await
+                .keys()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![completed[1].uuid, completed[2].uuid]
+        );
+    }
+
+    #[tokio::test]
+    async fn migrates_json_storage_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_path_buf();
+
+        let json = Arc::new(JSONStorage::new(&path));
+        let json_manager = TransactionManager::new(json.clone());
+        let queued = transaction(1, TxStatus::ComposeProof);
+        let failed = transaction(2, TxStatus::ComposeProof);
+        json_manager.add_transaction(queued.clone()).await;
+        json_manager.add_transaction(failed.clone()).await;
+        json_manager
+            .fail_transaction(
+                failed.uuid,
+                "Message stuck".to_string(),
+                FailureClass::Permanent,
+            )
+            .await;
+        json.block_storage()
+            .add_block(
+                EthereumSlotNumber(1),
+                EthereumBlockNumber(10),
+                [queued.tx.tx_hash].into_iter(),
+            )
+            .await;
+        json.save(&json_manager).await.unwrap();
+
+        let db = temporary_db();
+        let storage = Arc::new(DatabaseStorage::from_db(db.clone(), path.clone()));
+        let tx_manager = TransactionManager::new(storage.clone());
+        storage.load(&tx_manager).await.unwrap();
+        assert!(tx_manager
+            .transactions
+            .read()
+            .await
+            .contains_key(&queued.uuid));
+        assert_eq!(tx_manager.failed_transactions().await.len(), 1);
+        assert!(
+            storage
+                .block_storage()
+                .is_transaction_pending(EthereumSlotNumber(1), queued.tx.tx_hash)
+                .await
+        );
+        drop((storage, tx_manager));
+
+        // JSON storage is not imported again once the database is initialized.
+        json.save(&TransactionManager::new(json.clone()))
+            .await
+            .unwrap();
+        let storage = Arc::new(DatabaseStorage::from_db(db, path.clone()));
+        let tx_manager = TransactionManager::new(storage.clone());
+        storage.load(&tx_manager).await.unwrap();
+        assert_eq!(tx_manager.transactions.read().await.len(), 1);
+        assert_eq!(tx_manager.failed.read().await.len(), 1);
+    }
+}
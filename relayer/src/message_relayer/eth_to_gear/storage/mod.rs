@@ -1,5 +1,7 @@
 use super::tx_manager::{Transaction, TransactionManager};
-use crate::message_relayer::common::{EthereumBlockNumber, EthereumSlotNumber, TxHashWithSlot};
+use crate::message_relayer::common::{
+    dead_letter::StoredDeadLetter, EthereumBlockNumber, EthereumSlotNumber, TxHashWithSlot,
+};
 use anyhow::Context;
 use async_trait::async_trait;
 use ethereum_client::TxHash;
@@ -16,6 +18,10 @@ use tokio::{
 };
 use uuid::Uuid;
 
+mod database;
+
+pub use database::DatabaseStorage;
+
 /// Storage type implementing
 /// storage for Ethereum blocks.
 pub struct BlockStorage {
@@ -230,8 +236,9 @@ impl Storage for NoStorage {
     fn block_storage(&self) -> &BlockStorage {
         &self.0
     }
-    async fn save(&self, _tx_manager: &TransactionManager) -> anyhow::Result<()> {
-        /* no-op */
+    async fn save(&self, tx_manager: &TransactionManager) -> anyhow::Result<()> {
+        // Nothing is written, changes are dropped so they don't pile up.
+        tx_manager.take_changed().await;
         Ok(())
     }
 
@@ -257,7 +264,7 @@ pub struct JSONStorage {
 struct StoredState {
     transactions: BTreeMap<Uuid, Transaction>,
     completed: BTreeMap<Uuid, Transaction>,
-    failed: BTreeMap<Uuid, String>,
+    failed: BTreeMap<Uuid, StoredDeadLetter<Transaction>>,
 }
 
 const STATE_FILE: &str = "state.json";
@@ -364,13 +371,14 @@ impl JSONStorage {
         for tx in state.completed.into_values() {
             tx_manager.add_transaction(tx).await;
         }
-        tx_manager.failed.write().await.extend(state.failed);
+        tx_manager.restore_failed(state.failed).await;
 
         Ok(true)
     }
 
     async fn load_legacy(&self, tx_manager: &TransactionManager) -> anyhow::Result<()> {
         let mut dir = tokio::fs::read_dir(&self.path).await?;
+        let mut failed = BTreeMap::new();
 
         while let Some(entry) = dir.next_entry().await? {
             if entry
@@ -389,8 +397,7 @@ impl JSONStorage {
                                     self.path.display()
                                 )
                             })?;
-                    let map: BTreeMap<Uuid, String> = serde_json::from_str(&contents)?;
-                    *tx_manager.failed.write().await = map;
+                    failed = serde_json::from_str(&contents)?;
                 } else if entry.file_name().to_str() == Some("blocks.json") {
                     let contents =
                         tokio::fs::read_to_string(entry.path())
@@ -411,6 +418,9 @@ impl JSONStorage {
             }
         }
 
+        // failed transactions are taken out of the queue once it's loaded
+        tx_manager.restore_failed(failed).await;
+
         Ok(())
     }
 
@@ -454,9 +464,17 @@ impl Storage for JSONStorage {
             tokio::fs::create_dir_all(&self.path).await?;
         }
 
+        // The whole state is written, so which transactions changed doesn't matter.
+        tx_manager.take_changed().await;
         let transactions = tx_manager.transactions.read().await.clone();
         let completed = tx_manager.completed.read().await.clone();
-        let failed = tx_manager.failed.read().await.clone();
+        let failed = tx_manager
+            .failed
+            .read()
+            .await
+            .iter()
+            .map(|(uuid, dead_letter)| (*uuid, StoredDeadLetter::DeadLetter(dead_letter.clone())))
+            .collect();
 
         self.write_state(&StoredState {
             transactions,
@@ -542,4 +560,44 @@ mod tests {
         assert!(!storage.is_transaction_pending(slot, tx1).await);
         assert!(storage.is_transaction_pending(slot, tx2).await);
     }
+
+    #[tokio::test]
+    async fn moves_legacy_failures_to_dead_letters() {
+        use crate::message_relayer::{
+            common::dead_letter::FailureClass, eth_to_gear::tx_manager::TxStatus,
+        };
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_path_buf();
+
+        let tx = Transaction::new(tx_in_slot(7, tx_hash(1)), TxStatus::ComposeProof);
+        let uuid = tx.uuid;
+        let legacy = serde_json::json!({
+            "transactions": { uuid.to_string(): tx },
+            "completed": {},
+            "failed": { uuid.to_string(): "connection reset by peer" },
+        });
+        tokio::fs::write(path.join(STATE_FILE), legacy.to_string())
+            .await
+            .unwrap();
+
+        let storage = Arc::new(JSONStorage::new(&path));
+        let tx_manager = TransactionManager::new(storage.clone());
+        storage.load(&tx_manager).await.unwrap();
+
+        assert!(tx_manager.transactions.read().await.is_empty());
+        let failed = tx_manager.failed_transactions().await;
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].transaction.uuid, uuid);
+        assert_eq!(failed[0].class, FailureClass::Transient);
+        assert_eq!(failed[0].attempts, 1);
+        assert!(failed[0].retry_at.is_some());
+
+        // Dead letters survive a restart as they are.
+        storage.save(&tx_manager).await.unwrap();
+        let restarted = TransactionManager::new(storage.clone());
+        storage.load(&restarted).await.unwrap();
+        assert_eq!(restarted.failed_transactions().await, failed);
+    }
 }
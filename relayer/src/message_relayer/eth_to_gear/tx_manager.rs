@@ -3,22 +3,36 @@ use super::{
     proof_composer::{self, ProofComposerIo},
     storage::Storage,
 };
-use crate::message_relayer::{common::TxHashWithSlot, eth_to_gear::message_sender::MessageStatus};
+use crate::message_relayer::{
+    common::{
+        dead_letter::{self, DeadLetter, FailureClass, RetryError, StoredDeadLetter},
+        lifecycle::{LifecycleEvent, LifecycleEventKind, LifecycleEvents, Parties},
+        web_request::{
+            FailedTransaction, TransactionLookup, TransactionQueueStatus, TransactionState,
+        },
+        EthereumSlotNumber, TxHashWithSlot,
+    },
+    eth_to_gear::message_sender::MessageStatus,
+};
 use eth_events_electra_client::EthToVaraEvent;
-use prometheus::IntCounter;
+use primitive_types::H256;
+use prometheus::{IntCounter, IntGauge};
 use sails_rs::{Decode, Encode};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BTreeMap, HashMap},
-    sync::Arc,
-    time::Instant,
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 use tokio::{
     sync::{
+        broadcast,
         mpsc::{error::TryRecvError, UnboundedReceiver},
-        RwLock,
+        Mutex, RwLock,
     },
-    time::{self, Duration},
+    time::{self, Duration, Instant},
 };
 use utils_prometheus::{impl_metered_service, MeteredService};
 use uuid::Uuid;
@@ -30,6 +44,9 @@ pub struct Transaction {
     pub uuid: Uuid,
     pub status: TxStatus,
     pub tx: TxHashWithSlot,
+    /// Number of times relaying of the transaction failed.
+    #[serde(default)]
+    pub attempts: u32,
 }
 
 impl Transaction {
@@ -38,6 +55,42 @@ impl Transaction {
             uuid: Uuid::now_v7(),
             status,
             tx,
+            attempts: 0,
+        }
+    }
+
+    /// Ethereum to Gear transactions carry no message nonce, so they are never found by one.
+    fn matches(&self, lookup: &TransactionLookup) -> bool {
+        match lookup {
+            TransactionLookup::Uuid(uuid) => self.uuid == *uuid,
+            TransactionLookup::Nonce(_) => false,
+            TransactionLookup::TxHash(hash) => H256::from(self.tx.tx_hash.0) == *hash,
+        }
+    }
+
+    fn state(&self, failure: Option<String>) -> TransactionState {
+        TransactionState {
+            uuid: self.uuid,
+            status: self.status.name().to_string(),
+            nonce: None,
+            tx_hash: Some(H256::from(self.tx.tx_hash.0)),
+            block: Some(self.tx.slot_number.0),
+            completed: matches!(self.status, TxStatus::Completed),
+            failure,
+        }
+    }
+
+    fn tx_hash(&self) -> H256 {
+        H256::from(self.tx.tx_hash.0)
+    }
+
+    /// Sender and token are known only once the proof is composed.
+    fn parties(&self) -> Parties {
+        match &self.status {
+            TxStatus::SubmitMessage { payload } => EthToVaraEvent::decode(&mut payload.as_slice())
+                .map(|event| Parties::from_eth_to_vara_event(&event))
+                .unwrap_or_default(),
+            _ => Parties::default(),
         }
     }
 }
@@ -50,6 +103,30 @@ pub enum TxStatus {
     Completed,
 }
 
+impl TxStatus {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::ComposeProof => "compose_proof",
+            Self::SubmitMessage { .. } => "submit_message",
+            Self::Completed => "completed",
+        }
+    }
+
+    /// Status to restart a transaction failed in this status at `stage`. Message can be
+    /// submitted again only if its proof is already composed.
+    fn restart_at(&self, stage: &str) -> Result<Self, RetryError> {
+        match (stage, self) {
+            ("compose_proof", _) => Ok(Self::ComposeProof),
+            ("submit_message", Self::SubmitMessage { .. }) => Ok(self.clone()),
+            ("submit_message", _) => Err(RetryError::StageUnavailable {
+                stage: stage.to_string(),
+                status: self.name().to_string(),
+            }),
+            _ => Err(RetryError::UnknownStage(stage.to_string())),
+        }
+    }
+}
+
 impl_metered_service!(
     struct Metrics {
         total_transactions: IntCounter = IntCounter::new(
@@ -64,6 +141,10 @@ impl_metered_service!(
             "eth_geartransaction_manager_failed_transactions",
             "Total number of failed transactions",
         ),
+        dead_letter_transactions: IntGauge = IntGauge::new(
+            "eth_gear_transaction_manager_dead_letter_transactions",
+            "Number of failed transactions waiting for a retry",
+        ),
     }
 );
 
@@ -74,9 +155,16 @@ pub struct TransactionManager {
     pub transactions_timestamp: RwLock<HashMap<Uuid, Instant>>,
 
     pub completed: RwLock<BTreeMap<Uuid, Transaction>>,
-    pub failed: RwLock<BTreeMap<Uuid, String>>,
+    /// Dead-letter queue, see [`dead_letter`].
+    pub failed: RwLock<BTreeMap<Uuid, DeadLetter<Transaction>>>,
     pub storage: Arc<dyn Storage>,
+    /// Transactions added, changed or removed since storage took them with
+    /// [`Self::take_changed`].
+    changed: Mutex<HashSet<Uuid>>,
 
+    events: LifecycleEvents,
+    /// Last checkpoint `CheckpointReached` events were emitted for.
+    announced_checkpoint: AtomicU64,
     metrics: Metrics,
 }
 
@@ -94,18 +182,221 @@ impl TransactionManager {
             completed: RwLock::new(BTreeMap::new()),
             failed: RwLock::new(BTreeMap::new()),
             storage,
+            changed: Mutex::new(HashSet::new()),
 
+            events: LifecycleEvents::default(),
+            announced_checkpoint: AtomicU64::new(0),
             metrics: Metrics::new(),
         }
     }
 
-    pub async fn fail_transaction(&self, tx_uuid: Uuid, reason: String) {
-        self.failed.write().await.insert(tx_uuid, reason);
+    /// Takes the transactions changed since the previous call, so that storage writes only
+    /// them.
+    pub async fn take_changed(&self) -> HashSet<Uuid> {
+        std::mem::take(&mut *self.changed.lock().await)
+    }
+
+    /// Marks transactions as changed, e.g. when storage failed to write the ones it took.
+    pub async fn mark_changed(&self, tx_uuids: impl IntoIterator<Item = Uuid>) {
+        self.changed.lock().await.extend(tx_uuids);
+    }
+
+    /// Moves the transaction from the queue to the dead-letter queue.
+    pub async fn fail_transaction(&self, tx_uuid: Uuid, reason: String, class: FailureClass) {
+        let Some(tx) = self.transactions.write().await.remove(&tx_uuid) else {
+            log::warn!("Failed transaction {tx_uuid} is unknown: {reason}");
+            return;
+        };
+
+        self.dead_letter(tx, reason, class).await;
+    }
+
+    async fn dead_letter(&self, mut tx: Transaction, reason: String, class: FailureClass) {
+        self.transactions_timestamp.write().await.remove(&tx.uuid);
+
+        self.mark_changed([tx.uuid]).await;
+        tx.attempts += 1;
+        let attempts = tx.attempts;
+        let dead_letter = DeadLetter::new(tx, reason, class, attempts, dead_letter::now());
+        match dead_letter.retry_at {
+            Some(retry_at) => log::warn!(
+                "Transaction {} failed, attempt #{}, retrying at {retry_at}: {}",
+                dead_letter.transaction.uuid,
+                dead_letter.attempts,
+                dead_letter.reason
+            ),
+            None => log::error!(
+                "Transaction {} failed, attempt #{}, moved to dead-letter queue: {}",
+                dead_letter.transaction.uuid,
+                dead_letter.attempts,
+                dead_letter.reason
+            ),
+        }
+
+        let mut failed = self.failed.write().await;
+        failed.insert(dead_letter.transaction.uuid, dead_letter);
         self.metrics.failed_transactions.inc();
+        self.metrics
+            .dead_letter_transactions
+            .set(failed.len() as i64);
+    }
+
+    /// Puts failed transactions loaded from storage into the dead-letter queue. Failures
+    /// stored with only a reason keep their transaction in the queue, it's moved out here.
+    pub async fn restore_failed(&self, stored: BTreeMap<Uuid, StoredDeadLetter<Transaction>>) {
+        let now = dead_letter::now();
+        self.mark_changed(stored.keys().copied()).await;
+        let mut transactions = self.transactions.write().await;
+        let mut failed = self.failed.write().await;
+        for (uuid, stored) in stored {
+            let dead_letter = match stored {
+                StoredDeadLetter::DeadLetter(dead_letter) => dead_letter,
+                StoredDeadLetter::Reason(reason) => {
+                    let Some(mut tx) = transactions.remove(&uuid) else {
+                        log::warn!("Dropping failed transaction {uuid} without state: {reason}");
+                        continue;
+                    };
+
+                    tx.attempts = tx.attempts.max(1);
+                    let attempts = tx.attempts;
+                    let class = FailureClass::classify_reason(&reason);
+                    DeadLetter::new(tx, reason, class, attempts, now)
+                }
+            };
+
+            transactions.remove(&uuid);
+            failed.insert(uuid, dead_letter);
+        }
+
+        self.metrics
+            .dead_letter_transactions
+            .set(failed.len() as i64);
+    }
+
+    /// Transactions in the dead-letter queue.
+    pub async fn failed_transactions(&self) -> Vec<FailedTransaction> {
+        self.failed
+            .read()
+            .await
+            .values()
+            .map(|dead_letter| {
+                let state = dead_letter
+                    .transaction
+                    .state(Some(dead_letter.reason.clone()));
+                FailedTransaction::new(dead_letter, state)
+            })
+            .collect()
+    }
+
+    /// Puts the failed transaction back into the queue at `stage`, or at the stage it
+    /// failed at if not set.
+    pub async fn retry_transaction(
+        &self,
+        tx_uuid: Uuid,
+        stage: Option<&str>,
+    ) -> Result<TransactionState, RetryError> {
+        let mut failed = self.failed.write().await;
+        let dead_letter = failed.get(&tx_uuid).ok_or(RetryError::NotFound(tx_uuid))?;
+        let status = match stage {
+            Some(stage) => dead_letter.transaction.status.restart_at(stage)?,
+            None => dead_letter.transaction.status.clone(),
+        };
+
+        let mut tx = failed
+            .remove(&tx_uuid)
+            .expect("Dead letter is checked above")
+            .transaction;
+        self.metrics
+            .dead_letter_transactions
+            .set(failed.len() as i64);
+        drop(failed);
+
+        tx.status = status;
+        log::info!("Transaction {tx_uuid} is restarted at {}", tx.status.name());
+        let state = tx.state(None);
+        self.transactions_timestamp.write().await.remove(&tx_uuid);
+        self.transactions.write().await.insert(tx_uuid, tx);
+        self.mark_changed([tx_uuid]).await;
+
+        Ok(state)
+    }
+
+    /// Drops the failed transaction for good. Returns whether it was in the dead-letter queue.
+    pub async fn discard_transaction(&self, tx_uuid: Uuid) -> bool {
+        let mut failed = self.failed.write().await;
+        let discarded = failed.remove(&tx_uuid).is_some();
+        if discarded {
+            log::warn!("Failed transaction {tx_uuid} is discarded");
+            self.mark_changed([tx_uuid]).await;
+            self.metrics
+                .dead_letter_transactions
+                .set(failed.len() as i64);
+        }
+
+        discarded
+    }
+
+    /// Restarts transactions whose automatic retry is due.
+    async fn retry_due(&self) {
+        let now = dead_letter::now();
+        let due: Vec<_> = self
+            .failed
+            .read()
+            .await
+            .values()
+            .filter(|dead_letter| dead_letter.is_due(now))
+            .map(|dead_letter| dead_letter.transaction.uuid)
+            .collect();
+
+        for tx_uuid in due {
+            if let Err(err) = self.retry_transaction(tx_uuid, None).await {
+                log::warn!("Failed to retry transaction {tx_uuid}: {err}");
+            }
+        }
+    }
+
+    /// Subscribes to lifecycle events of the relayed transactions.
+    pub fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, tx: &Transaction, kind: LifecycleEventKind) {
+        self.events.emit(tx.uuid, None, tx.parties(), kind);
+    }
+
+    /// Emits `CheckpointReached` for transactions which wait for a checkpoint between the
+    /// previously announced one and `checkpoint`.
+    async fn announce_checkpoint(&self, checkpoint: Option<EthereumSlotNumber>) {
+        let Some(EthereumSlotNumber(checkpoint)) = checkpoint else {
+            return;
+        };
+
+        let previous = self
+            .announced_checkpoint
+            .fetch_max(checkpoint, Ordering::Relaxed);
+        if checkpoint <= previous {
+            return;
+        }
+
+        let transactions = self.transactions.read().await;
+        for tx in transactions.values() {
+            let slot = tx.tx.slot_number.0;
+            if matches!(tx.status, TxStatus::ComposeProof) && previous < slot && slot <= checkpoint
+            {
+                self.emit(
+                    tx,
+                    LifecycleEventKind::CheckpointReached {
+                        tx_hash: tx.tx_hash(),
+                        checkpoint,
+                    },
+                );
+            }
+        }
     }
 
     pub async fn add_transaction(&self, tx: Transaction) {
         self.metrics.total_transactions.inc();
+        self.mark_changed([tx.uuid]).await;
         match tx.status {
             TxStatus::Completed => {
                 self.completed.write().await.insert(tx.uuid, tx);
@@ -118,6 +409,52 @@ impl TransactionManager {
         }
     }
 
+    /// Transactions which are still being relayed, along with the number of completed
+    /// and failed ones.
+    pub async fn queue_status(&self) -> TransactionQueueStatus {
+        let pending = self
+            .transactions
+            .read()
+            .await
+            .values()
+            .map(|tx| tx.state(None))
+            .collect();
+
+        TransactionQueueStatus {
+            pending,
+            completed: self.completed.read().await.len(),
+            failed: self.failed.read().await.len(),
+            ..Default::default()
+        }
+    }
+
+    pub async fn find_transaction(&self, lookup: &TransactionLookup) -> Option<TransactionState> {
+        let tx = {
+            let transactions = self.transactions.read().await;
+            transactions.values().find(|tx| tx.matches(lookup)).cloned()
+        };
+        let tx = match tx {
+            Some(tx) => Some(tx),
+            None => {
+                let completed = self.completed.read().await;
+                completed.values().find(|tx| tx.matches(lookup)).cloned()
+            }
+        };
+        if let Some(tx) = tx {
+            return Some(tx.state(None));
+        }
+
+        let failed = self.failed.read().await;
+        failed
+            .values()
+            .find(|dead_letter| dead_letter.transaction.matches(lookup))
+            .map(|dead_letter| {
+                dead_letter
+                    .transaction
+                    .state(Some(dead_letter.reason.clone()))
+            })
+    }
+
     async fn update_storage(&self) {
         if let Err(err) = self.storage.save(self).await {
             log::error!("Failed to save transactions to storage: {err:?}");
@@ -125,7 +462,7 @@ impl TransactionManager {
     }
 
     pub async fn run(
-        self,
+        &self,
         mut message_paid_events: UnboundedReceiver<TxHashWithSlot>,
         mut proof_composer: ProofComposerIo,
         mut message_sender: MessageSenderIo,
@@ -193,6 +530,10 @@ impl TransactionManager {
                 },
         }
 
+        self.announce_checkpoint(proof_composer.last_checkpoint())
+            .await;
+        self.retry_due().await;
+
         self.resume(message_sender, proof_composer).await
     }
 
@@ -254,7 +595,26 @@ impl TransactionManager {
 
         log::info!("Received paid event {tx_hash:?}, transaction UUID: {tx_uuid}");
 
+        self.emit(
+            &tx,
+            LifecycleEventKind::TransactionSeen {
+                tx_hash: tx.tx_hash(),
+                slot: tx.tx.slot_number.0,
+            },
+        );
+        let checkpoint = self.announced_checkpoint.load(Ordering::Relaxed);
+        if tx.tx.slot_number.0 <= checkpoint {
+            self.emit(
+                &tx,
+                LifecycleEventKind::CheckpointReached {
+                    tx_hash: tx.tx_hash(),
+                    checkpoint,
+                },
+            );
+        }
+
         self.transactions.write().await.insert(tx_uuid, tx);
+        self.mark_changed([tx_uuid]).await;
 
         // now that we've seen the transaction it will be saved
         // in regular storage, not in block storage. We can remove
@@ -279,6 +639,13 @@ impl TransactionManager {
             tx.status = TxStatus::SubmitMessage {
                 payload: payload.encode(),
             };
+            self.mark_changed([tx_uuid]).await;
+            self.emit(
+                tx,
+                LifecycleEventKind::ProofComposed {
+                    tx_hash: tx.tx_hash(),
+                },
+            );
 
             drop(transactions);
         } else {
@@ -302,20 +669,29 @@ impl TransactionManager {
 
         log::info!("Received response for transaction {tx_uuid}: {status:?}");
 
+        let error = match &status {
+            MessageStatus::Success => None,
+            MessageStatus::Failure(message, _) => Some(message.clone()),
+        };
+        self.emit(
+            &tx,
+            LifecycleEventKind::ReceiptSubmitted {
+                tx_hash: tx.tx_hash(),
+                error,
+            },
+        );
+
         match status {
             MessageStatus::Success => {
                 tx.status = TxStatus::Completed;
-                // transaction may have been failed and restarted. Remove
-                // it from failed set if it succeeded.
-                self.failed.write().await.remove(&tx.uuid);
+                self.mark_changed([tx.uuid]).await;
                 self.completed.write().await.insert(tx.uuid, tx);
                 self.metrics.completed_transactions.inc();
             }
 
-            MessageStatus::Failure(message) => {
-                self.fail_transaction(tx_uuid, message).await;
-                // add transaction back to tx queue. It can be restarted later.
-                transactions.insert(tx_uuid, tx);
+            MessageStatus::Failure(message, class) => {
+                drop(transactions);
+                self.dead_letter(tx, message, class).await;
             }
         }
     }
@@ -34,7 +34,7 @@ pub struct Relayer {
 
     message_queued_receiver: UnboundedReceiver<MessageInBlock>,
 
-    tx_manager: TransactionManager,
+    tx_manager: Arc<TransactionManager>,
 }
 
 impl MeteredService for Relayer {
@@ -56,6 +56,7 @@ impl Relayer {
         confirmations_merkle_root: u64,
 
         confirmations_status: u64,
+        max_batch_size: usize,
 
         storage_path: impl AsRef<Path>,
 
@@ -63,7 +64,7 @@ impl Relayer {
         governance_pauser: ActorId,
     ) -> anyhow::Result<Self> {
         let storage = Arc::new(JSONStorage::new(storage_path));
-        let tx_manager = TransactionManager::new(storage.clone());
+        let tx_manager = Arc::new(TransactionManager::new(storage.clone()));
         if let Err(e) = tx_manager.load_from_storage().await {
             log::warn!("Failed to load transaction manager state: {e}");
         }
@@ -93,7 +94,8 @@ impl Relayer {
             eth_api.clone(),
         );
 
-        let message_sender = MessageSender::new(eth_api.clone());
+        let message_sender =
+            MessageSender::new(eth_api.clone()).with_max_batch_size(max_batch_size);
 
         let proof_fetcher = MerkleProofFetcher::new(api_provider);
         let status_fetcher = StatusFetcher::new(eth_api, confirmations_status);
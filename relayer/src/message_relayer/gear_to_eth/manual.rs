@@ -136,7 +136,14 @@ pub async fn relay(
                 } else if !tx_manager.failed.read().await.is_empty() {
                     log::error!(
                         "Failed to relay transaction nonce={message_nonce}, block={gear_block}: {}",
-                        tx_manager.failed.read().await.first_key_value().unwrap().1
+                        tx_manager
+                            .failed
+                            .read()
+                            .await
+                            .first_key_value()
+                            .unwrap()
+                            .1
+                            .reason
                     );
 
                     return;
@@ -6,6 +6,7 @@ use crate::{
                 accumulator::Accumulator, merkle_root_extractor::MerkleRootExtractor,
                 message_sender::MessageSender, status_fetcher::StatusFetcher,
             },
+            fee_market::FeeMarket,
             gear::{
                 block_listener::BlockListener as GearBlockListener,
                 merkle_proof_fetcher::MerkleProofFetcher,
@@ -52,7 +53,7 @@ pub struct Relayer {
     message_queued_receiver: UnboundedReceiver<MessageInBlock>,
     message_receiver: UnboundedReceiver<MessageInBlock>,
 
-    tx_manager: TransactionManager,
+    tx_manager: Arc<TransactionManager>,
 }
 
 impl MeteredService for Relayer {
@@ -76,14 +77,16 @@ impl Relayer {
         api_provider: ApiProviderConnection,
         confirmations_merkle_root: u64,
         confirmations_status: u64,
+        max_batch_size: usize,
         excluded_from_fees: HashSet<AccountId32>,
+        fee_market: Option<FeeMarket>,
         receiver: UnboundedReceiver<Message>,
         storage_path: impl AsRef<Path>,
         governance_admin: ActorId,
         governance_pauser: ActorId,
     ) -> AnyResult<Self> {
         let storage = Arc::new(JSONStorage::new(storage_path));
-        let tx_manager = TransactionManager::new(storage.clone());
+        let tx_manager = Arc::new(TransactionManager::new(storage.clone()));
         if let Err(e) = tx_manager.load_from_storage().await {
             log::warn!("Failed to load transaction manager state: {e}");
         }
@@ -94,7 +97,8 @@ impl Relayer {
         let listener_message_queued =
             MessageQueuedEventExtractor::new(api_provider.clone(), message_queued_sender, storage);
 
-        let message_paid_listener = MessagePaidEventExtractor::new(bridging_payment_address);
+        let message_paid_listener =
+            MessagePaidEventExtractor::new(bridging_payment_address, api_provider.clone());
 
         let (roots_sender, roots_receiver) = mpsc::unbounded_channel();
         let merkle_root_extractor = MerkleRootExtractor::new(
@@ -104,7 +108,8 @@ impl Relayer {
             roots_sender.clone(),
         );
 
-        let message_sender = MessageSender::new(eth_api.clone());
+        let message_sender =
+            MessageSender::new(eth_api.clone()).with_max_batch_size(max_batch_size);
 
         let proof_fetcher = MerkleProofFetcher::new(api_provider.clone());
         let status_fetcher = StatusFetcher::new(eth_api.clone(), confirmations_status);
@@ -112,7 +117,11 @@ impl Relayer {
         let (messages_sender, messages_receiver) = mpsc::unbounded_channel();
         let message_data_extractor =
             MessageDataExtractor::new(api_provider.clone(), messages_sender.downgrade(), receiver);
-        let paid_messages_filter = PaidMessagesFilter::new(excluded_from_fees, messages_sender);
+        let mut paid_messages_filter = PaidMessagesFilter::new(excluded_from_fees, messages_sender);
+        if let Some(fee_market) = fee_market {
+            paid_messages_filter =
+                paid_messages_filter.with_fee_market(fee_market, tx_manager.underpaid.clone());
+        }
 
         let accumulator = Accumulator::new(
             roots_receiver,
@@ -150,6 +159,11 @@ impl Relayer {
         })
     }
 
+    /// Transaction queue of the relayer, served by the status endpoints of the web server.
+    pub fn tx_manager(&self) -> Arc<TransactionManager> {
+        self.tx_manager.clone()
+    }
+
     pub async fn run(self) -> AnyResult<()> {
         let [gear_blocks_0, gear_blocks_1] = self.gear_block_listener.run().await;
 
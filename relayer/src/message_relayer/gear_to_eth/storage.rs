@@ -16,6 +16,7 @@ use uuid::Uuid;
 
 use crate::message_relayer::{
     common::{
+        dead_letter::StoredDeadLetter,
         ethereum::accumulator::utils::MerkleRoots,
         gear::block_storage::{UnprocessedBlocks, UnprocessedBlocksStorage},
         GearBlock, GearBlockNumber, MessageInBlock,
@@ -325,6 +326,32 @@ impl JSONStorage {
 
         Ok(tx)
     }
+
+    /// Removes files of transactions which are neither queued nor completed, that is
+    /// failed or discarded ones.
+    async fn remove_stale_tx_files(&self, tx_manager: &TransactionManager) -> anyhow::Result<()> {
+        let transactions = tx_manager.transactions.read().await;
+        let completed = tx_manager.completed.read().await;
+
+        let mut dir = tokio::fs::read_dir(&self.path).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let Some(uuid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| Uuid::from_str(name).ok())
+            else {
+                continue;
+            };
+
+            if !transactions.contains_key(&uuid) && !completed.contains_key(&uuid) {
+                tokio::fs::remove_file(entry.path())
+                    .await
+                    .with_context(|| format!("Failed to remove transaction file: {uuid}"))?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
@@ -345,20 +372,22 @@ impl Storage for JSONStorage {
                 })?;
         }
 
-        let mut failed = BTreeMap::new();
-        let failed_map = tx_manager.failed.read().await;
-
         for (tx_uuid, tx) in tx_manager.transactions.read().await.iter() {
             self.write_tx(tx_uuid, tx).await?;
-            if let Some(reason) = failed_map.get(tx_uuid) {
-                failed.insert(*tx_uuid, reason.clone());
-            }
         }
 
         for (tx_uuid, tx) in tx_manager.completed.read().await.iter() {
             self.write_tx(tx_uuid, tx).await?;
         }
 
+        let failed: BTreeMap<_, _> = tx_manager
+            .failed
+            .read()
+            .await
+            .iter()
+            .map(|(uuid, dead_letter)| (*uuid, StoredDeadLetter::DeadLetter(dead_letter.clone())))
+            .collect();
+
         let mut failed_file = tokio::fs::OpenOptions::new()
             .write(true)
             .create(true)
@@ -376,6 +405,8 @@ impl Storage for JSONStorage {
 
         failed_file.flush().await?;
 
+        self.remove_stale_tx_files(tx_manager).await?;
+
         let merkle = tx_manager.merkle_roots.read().await;
         let mut merkle_file = tokio::fs::OpenOptions::new()
             .write(true)
@@ -403,6 +434,7 @@ impl Storage for JSONStorage {
         }
 
         let mut dir = tokio::fs::read_dir(&self.path).await?;
+        let mut failed = BTreeMap::new();
 
         while let Some(entry) = dir.next_entry().await? {
             if entry
@@ -415,9 +447,8 @@ impl Storage for JSONStorage {
                     let contents = tokio::fs::read_to_string(entry.path())
                         .await
                         .context("Failed to read 'failed' transactions file")?;
-                    let map: BTreeMap<Uuid, String> = serde_json::from_str(&contents)
+                    failed = serde_json::from_str(&contents)
                         .context("Failed to parse 'failed' transactions")?;
-                    tx_manager.failed.write().await.extend(map);
                 } else if entry.file_name().to_str() == Some("merkle_roots") {
                     let contents = tokio::fs::read_to_string(entry.path())
                         .await
@@ -448,6 +479,9 @@ impl Storage for JSONStorage {
             }
         }
 
+        // failed transactions are taken out of the queue once it's loaded
+        tx_manager.restore_failed(failed).await;
+
         Ok(())
     }
 
@@ -1,32 +1,51 @@
 use ethereum_client::TxHash;
 use gear_rpc_client::dto::MerkleProof;
-use prometheus::IntCounter;
+use primitive_types::{H256, U256};
+use prometheus::{IntCounter, IntGauge};
 use sails_rs::ActorId;
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, sync::Arc};
-use tokio::sync::{mpsc::UnboundedReceiver, RwLock};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{broadcast, mpsc::UnboundedReceiver, Notify, RwLock};
 use utils_prometheus::{impl_metered_service, MeteredService};
 use uuid::Uuid;
 
 use crate::message_relayer::{
     common::{
+        dead_letter::{self, DeadLetter, FailureClass, RetryError, StoredDeadLetter},
         ethereum::{
             accumulator::{self, utils::MerkleRoots, AccumulatorIo},
             message_sender::{self, MessageSenderIo},
             status_fetcher::{self, StatusFetcherIo},
         },
         gear::merkle_proof_fetcher::MerkleRootFetcherIo,
-        message_hash, MessageInBlock, RelayedMerkleRoot,
+        lifecycle::{LifecycleEvent, LifecycleEventKind, LifecycleEvents, Parties},
+        message_hash,
+        paid_messages_filter::UnderpaidBacklog,
+        web_request::{
+            FailedTransaction, TransactionLookup, TransactionQueueStatus, TransactionState,
+        },
+        MessageInBlock, RelayedMerkleRoot,
     },
     gear_to_eth::storage::Storage,
 };
 
+/// How often failed transactions are checked for a due automatic retry when the queue
+/// is idle.
+const RETRY_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Transaction {
     pub uuid: Uuid,
     pub message: MessageInBlock,
     pub message_hash: [u8; 32],
     pub status: TxStatus,
+    /// Number of times relaying of the message failed.
+    #[serde(default)]
+    pub attempts: u32,
 }
 
 impl Transaction {
@@ -37,8 +56,40 @@ impl Transaction {
             status,
             message_hash: message_hash(&message.message),
             message,
+            attempts: 0,
         }
     }
+
+    fn matches(&self, lookup: &TransactionLookup) -> bool {
+        match lookup {
+            TransactionLookup::Uuid(uuid) => self.uuid == *uuid,
+            TransactionLookup::Nonce(nonce) => {
+                U256::from_big_endian(&self.message.message.nonce_be) == *nonce
+            }
+            TransactionLookup::TxHash(hash) => self
+                .status
+                .tx_hash()
+                .is_some_and(|tx_hash| H256::from(tx_hash.0) == *hash),
+        }
+    }
+
+    fn state(&self, failure: Option<String>) -> TransactionState {
+        let tx_hash = self.status.tx_hash().map(|tx_hash| H256::from(tx_hash.0));
+
+        TransactionState {
+            uuid: self.uuid,
+            status: self.status.name().to_string(),
+            nonce: Some(U256::from_big_endian(&self.message.message.nonce_be)),
+            tx_hash,
+            block: Some(self.message.block.0 as u64),
+            completed: matches!(self.status, TxStatus::Completed),
+            failure,
+        }
+    }
+
+    fn nonce(&self) -> U256 {
+        U256::from_big_endian(&self.message.message.nonce_be)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -47,9 +98,71 @@ pub enum TxStatus {
     FetchMerkleRoot(RelayedMerkleRoot),
     SendMessage(RelayedMerkleRoot, MerkleProof),
     WaitConfirmations(TxHash),
+    /// Message is delivered in a batch transaction. Merkle root and proof are kept to
+    /// deliver it again if the batch doesn't process it.
+    WaitBatchConfirmations(TxHash, RelayedMerkleRoot, MerkleProof),
     Completed,
 }
 
+impl TxStatus {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::WaitForMerkleRoot => "wait_for_merkle_root",
+            Self::FetchMerkleRoot(_) => "fetch_merkle_root",
+            Self::SendMessage(..) => "send_message",
+            Self::WaitConfirmations(_) => "wait_confirmations",
+            Self::WaitBatchConfirmations(..) => "wait_batch_confirmations",
+            Self::Completed => "completed",
+        }
+    }
+
+    fn tx_hash(&self) -> Option<TxHash> {
+        match self {
+            Self::WaitConfirmations(tx_hash) | Self::WaitBatchConfirmations(tx_hash, ..) => {
+                Some(*tx_hash)
+            }
+            _ => None,
+        }
+    }
+
+    /// Status to restart a message failed in this status at `stage`. Later stages need
+    /// the merkle root, proof or transaction the message got before the failure.
+    fn restart_at(&self, stage: &str) -> Result<Self, RetryError> {
+        let status = match (stage, self) {
+            ("wait_for_merkle_root", _) => Some(Self::WaitForMerkleRoot),
+
+            ("fetch_merkle_root", Self::FetchMerkleRoot(merkle_root))
+            | ("fetch_merkle_root", Self::SendMessage(merkle_root, _))
+            | ("fetch_merkle_root", Self::WaitBatchConfirmations(_, merkle_root, _)) => {
+                Some(Self::FetchMerkleRoot(*merkle_root))
+            }
+
+            ("send_message", Self::SendMessage(merkle_root, proof))
+            | ("send_message", Self::WaitBatchConfirmations(_, merkle_root, proof)) => {
+                Some(Self::SendMessage(*merkle_root, proof.clone()))
+            }
+
+            ("wait_confirmations", Self::WaitConfirmations(_))
+            | ("wait_batch_confirmations", Self::WaitBatchConfirmations(..)) => Some(self.clone()),
+
+            (
+                "fetch_merkle_root"
+                | "send_message"
+                | "wait_confirmations"
+                | "wait_batch_confirmations",
+                _,
+            ) => None,
+
+            _ => return Err(RetryError::UnknownStage(stage.to_string())),
+        };
+
+        status.ok_or_else(|| RetryError::StageUnavailable {
+            stage: stage.to_string(),
+            status: self.name().to_string(),
+        })
+    }
+}
+
 impl_metered_service!(
     struct Metrics {
         total_transactions: IntCounter = IntCounter::new(
@@ -64,6 +177,10 @@ impl_metered_service!(
             "eth_geartransaction_manager_failed_transactions",
             "Total number of failed transactions",
         ),
+        dead_letter_transactions: IntGauge = IntGauge::new(
+            "gear_eth_transaction_manager_dead_letter_transactions",
+            "Number of failed transactions waiting for a retry",
+        ),
     }
 );
 
@@ -71,10 +188,17 @@ pub struct TransactionManager {
     pub merkle_roots: Arc<RwLock<MerkleRoots>>,
 
     pub transactions: RwLock<BTreeMap<Uuid, Transaction>>,
-    pub failed: RwLock<BTreeMap<Uuid, String>>,
+    /// Dead-letter queue, see [`dead_letter`].
+    pub failed: RwLock<BTreeMap<Uuid, DeadLetter<Transaction>>>,
     pub completed: RwLock<BTreeMap<Uuid, Transaction>>,
+    /// Paid messages not relayed yet as their fee is too low.
+    pub underpaid: UnderpaidBacklog,
     pub storage: Arc<dyn Storage>,
 
+    /// Transactions put back into the queue which are not passed to the services yet.
+    retried: RwLock<BTreeSet<Uuid>>,
+    retry_notify: Notify,
+    events: LifecycleEvents,
     metrics: Metrics,
 }
 
@@ -91,15 +215,191 @@ impl TransactionManager {
             transactions: RwLock::new(BTreeMap::new()),
             failed: RwLock::new(BTreeMap::new()),
             completed: RwLock::new(BTreeMap::new()),
+            underpaid: UnderpaidBacklog::default(),
             storage,
 
+            retried: RwLock::new(BTreeSet::new()),
+            retry_notify: Notify::new(),
+            events: LifecycleEvents::default(),
             metrics: Metrics::new(),
         }
     }
 
-    pub async fn fail_transaction(&self, tx_uuid: Uuid, reason: String) {
-        self.failed.write().await.insert(tx_uuid, reason);
+    /// Moves the transaction from the queue to the dead-letter queue.
+    pub async fn fail_transaction(&self, tx_uuid: Uuid, reason: String, class: FailureClass) {
+        let tx = self.transactions.write().await.remove(&tx_uuid);
+        self.emit_for(
+            tx_uuid,
+            tx.as_ref(),
+            LifecycleEventKind::MessageFailed {
+                reason: reason.clone(),
+            },
+        );
+
+        let Some(mut tx) = tx else {
+            log::warn!("Failed transaction {tx_uuid} is unknown: {reason}");
+            return;
+        };
+
+        tx.attempts += 1;
+        let attempts = tx.attempts;
+        let dead_letter = DeadLetter::new(tx, reason, class, attempts, dead_letter::now());
+        let nonce = hex::encode(dead_letter.transaction.message.message.nonce_be);
+        match dead_letter.retry_at {
+            Some(retry_at) => log::warn!(
+                "Transaction {tx_uuid}, nonce={nonce} failed, attempt #{attempts}, retrying at {retry_at}: {}",
+                dead_letter.reason
+            ),
+            None => log::error!(
+                "Transaction {tx_uuid}, nonce={nonce} failed, attempt #{attempts}, moved to dead-letter queue: {}",
+                dead_letter.reason
+            ),
+        }
+
+        let mut failed = self.failed.write().await;
+        failed.insert(tx_uuid, dead_letter);
         self.metrics.failed_transactions.inc();
+        self.metrics
+            .dead_letter_transactions
+            .set(failed.len() as i64);
+    }
+
+    /// Puts failed transactions loaded from storage into the dead-letter queue. Failures
+    /// stored with only a reason keep their transaction in the queue, it's moved out here.
+    pub async fn restore_failed(&self, stored: BTreeMap<Uuid, StoredDeadLetter<Transaction>>) {
+        let now = dead_letter::now();
+        let mut transactions = self.transactions.write().await;
+        let mut failed = self.failed.write().await;
+        for (uuid, stored) in stored {
+            let dead_letter = match stored {
+                StoredDeadLetter::DeadLetter(dead_letter) => dead_letter,
+                StoredDeadLetter::Reason(reason) => {
+                    let Some(mut tx) = transactions.remove(&uuid) else {
+                        log::warn!("Dropping failed transaction {uuid} without state: {reason}");
+                        continue;
+                    };
+
+                    tx.attempts = tx.attempts.max(1);
+                    let attempts = tx.attempts;
+                    let class = FailureClass::classify_reason(&reason);
+                    DeadLetter::new(tx, reason, class, attempts, now)
+                }
+            };
+
+            transactions.remove(&uuid);
+            failed.insert(uuid, dead_letter);
+        }
+
+        self.metrics
+            .dead_letter_transactions
+            .set(failed.len() as i64);
+    }
+
+    /// Transactions in the dead-letter queue.
+    pub async fn failed_transactions(&self) -> Vec<FailedTransaction> {
+        self.failed
+            .read()
+            .await
+            .values()
+            .map(|dead_letter| {
+                let state = dead_letter
+                    .transaction
+                    .state(Some(dead_letter.reason.clone()));
+                FailedTransaction::new(dead_letter, state)
+            })
+            .collect()
+    }
+
+    /// Puts the failed transaction back into the queue at `stage`, or at the stage it
+    /// failed at if not set.
+    pub async fn retry_transaction(
+        &self,
+        tx_uuid: Uuid,
+        stage: Option<&str>,
+    ) -> Result<TransactionState, RetryError> {
+        let mut failed = self.failed.write().await;
+        let dead_letter = failed.get(&tx_uuid).ok_or(RetryError::NotFound(tx_uuid))?;
+        let status = match stage {
+            Some(stage) => dead_letter.transaction.status.restart_at(stage)?,
+            None => dead_letter.transaction.status.clone(),
+        };
+
+        let mut tx = failed
+            .remove(&tx_uuid)
+            .expect("Dead letter is checked above")
+            .transaction;
+        self.metrics
+            .dead_letter_transactions
+            .set(failed.len() as i64);
+        drop(failed);
+
+        tx.status = status;
+        log::info!(
+            "Transaction {tx_uuid}, nonce={} is restarted at {}",
+            hex::encode(tx.message.message.nonce_be),
+            tx.status.name()
+        );
+        let state = tx.state(None);
+        self.transactions.write().await.insert(tx_uuid, tx);
+        self.retried.write().await.insert(tx_uuid);
+        self.retry_notify.notify_one();
+
+        Ok(state)
+    }
+
+    /// Drops the failed transaction for good. Returns whether it was in the dead-letter queue.
+    pub async fn discard_transaction(&self, tx_uuid: Uuid) -> bool {
+        let mut failed = self.failed.write().await;
+        let discarded = failed.remove(&tx_uuid).is_some();
+        if discarded {
+            log::warn!("Failed transaction {tx_uuid} is discarded");
+            self.metrics
+                .dead_letter_transactions
+                .set(failed.len() as i64);
+        }
+
+        discarded
+    }
+
+    /// Restarts transactions whose automatic retry is due.
+    async fn retry_due(&self) {
+        let now = dead_letter::now();
+        let due: Vec<_> = self
+            .failed
+            .read()
+            .await
+            .values()
+            .filter(|dead_letter| dead_letter.is_due(now))
+            .map(|dead_letter| dead_letter.transaction.uuid)
+            .collect();
+
+        for tx_uuid in due {
+            if let Err(err) = self.retry_transaction(tx_uuid, None).await {
+                log::warn!("Failed to retry transaction {tx_uuid}: {err}");
+            }
+        }
+    }
+
+    /// Subscribes to lifecycle events of the relayed messages.
+    pub fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, tx: &Transaction, kind: LifecycleEventKind) {
+        self.emit_for(tx.uuid, Some(tx), kind);
+    }
+
+    fn emit_for(&self, tx_uuid: Uuid, tx: Option<&Transaction>, kind: LifecycleEventKind) {
+        let (nonce, parties) = tx
+            .map(|tx| {
+                (
+                    Some(tx.nonce()),
+                    Parties::from_vft_manager_payload(&tx.message.message.payload),
+                )
+            })
+            .unwrap_or_default();
+
+        self.events.emit(tx_uuid, nonce, parties, kind);
     }
 
     pub async fn add_transaction(&self, tx: Transaction) {
@@ -127,6 +427,54 @@ impl TransactionManager {
         self.storage.load(self).await
     }
 
+    /// Transactions which are still being relayed, along with the number of completed
+    /// and failed ones and the messages held back as underpaid.
+    pub async fn queue_status(&self) -> TransactionQueueStatus {
+        let pending = self
+            .transactions
+            .read()
+            .await
+            .values()
+            .map(|tx| tx.state(None))
+            .collect();
+
+        TransactionQueueStatus {
+            pending,
+            completed: self.completed.read().await.len(),
+            failed: self.failed.read().await.len(),
+            underpaid: self.underpaid.read().await.values().cloned().collect(),
+        }
+    }
+
+    /// Looks a transaction up among pending, completed and failed ones. Transactions are
+    /// looked up by hash only while waiting for confirmations.
+    pub async fn find_transaction(&self, lookup: &TransactionLookup) -> Option<TransactionState> {
+        let tx = {
+            let transactions = self.transactions.read().await;
+            transactions.values().find(|tx| tx.matches(lookup)).cloned()
+        };
+        let tx = match tx {
+            Some(tx) => Some(tx),
+            None => {
+                let completed = self.completed.read().await;
+                completed.values().find(|tx| tx.matches(lookup)).cloned()
+            }
+        };
+        if let Some(tx) = tx {
+            return Some(tx.state(None));
+        }
+
+        let failed = self.failed.read().await;
+        failed
+            .values()
+            .find(|dead_letter| dead_letter.transaction.matches(lookup))
+            .map(|dead_letter| {
+                dead_letter
+                    .transaction
+                    .state(Some(dead_letter.reason.clone()))
+            })
+    }
+
     async fn resume(
         &self,
         accumulator: &mut AccumulatorIo,
@@ -137,91 +485,162 @@ impl TransactionManager {
         let transactions = self.transactions.write().await;
 
         for (_, tx) in transactions.iter() {
-            match tx.status {
-                TxStatus::WaitForMerkleRoot => {
-                    self.storage
-                        .block_storage()
-                        .complete_transaction(&tx.message)
-                        .await;
-                    log::info!(
-                        "Transaction {}, nonce={} is waiting for merkle root",
-                        tx.uuid,
-                        hex::encode(tx.message.message.nonce_be)
-                    );
-                    if !accumulator.send_message(
-                        tx.uuid,
-                        tx.message.authority_set_id,
-                        tx.message.block,
-                        tx.message.block_hash,
-                        ActorId::from(tx.message.message.source),
-                    ) {
-                        log::warn!("Accumulator stopped accepting messages, exiting");
-                        return Ok(false);
-                    }
+            if !self
+                .dispatch(
+                    tx,
+                    accumulator,
+                    proof_fetcher,
+                    message_sender,
+                    status_fetcher,
+                )
+                .await
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Passes retried transactions to the services. Returns `false` if one of them stopped.
+    async fn resume_retried(
+        &self,
+        accumulator: &mut AccumulatorIo,
+        proof_fetcher: &mut MerkleRootFetcherIo,
+        message_sender: &mut MessageSenderIo,
+        status_fetcher: &mut StatusFetcherIo,
+    ) -> bool {
+        let retried = std::mem::take(&mut *self.retried.write().await);
+        for tx_uuid in retried {
+            let Some(tx) = self.transactions.read().await.get(&tx_uuid).cloned() else {
+                continue;
+            };
+
+            if !self
+                .dispatch(
+                    &tx,
+                    accumulator,
+                    proof_fetcher,
+                    message_sender,
+                    status_fetcher,
+                )
+                .await
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Passes the transaction to the service handling its status. Returns `false` if the
+    /// service stopped.
+    async fn dispatch(
+        &self,
+        tx: &Transaction,
+        accumulator: &mut AccumulatorIo,
+        proof_fetcher: &mut MerkleRootFetcherIo,
+        message_sender: &mut MessageSenderIo,
+        status_fetcher: &mut StatusFetcherIo,
+    ) -> bool {
+        match tx.status {
+            TxStatus::WaitForMerkleRoot => {
+                self.storage
+                    .block_storage()
+                    .complete_transaction(&tx.message)
+                    .await;
+                log::info!(
+                    "Transaction {}, nonce={} is waiting for merkle root",
+                    tx.uuid,
+                    hex::encode(tx.message.message.nonce_be)
+                );
+                if !accumulator.send_message(
+                    tx.uuid,
+                    tx.message.authority_set_id,
+                    tx.message.block,
+                    tx.message.block_hash,
+                    ActorId::from(tx.message.message.source),
+                ) {
+                    log::warn!("Accumulator stopped accepting messages, exiting");
+                    return false;
                 }
+            }
 
-                TxStatus::FetchMerkleRoot(ref merkle_root) => {
-                    log::info!(
-                        "Transaction {}, nonce={} is fetching merkle root for block #{}",
-                        tx.uuid,
-                        hex::encode(tx.message.message.nonce_be),
-                        merkle_root.block
-                    );
-                    if !proof_fetcher.send_request(
-                        tx.uuid,
-                        tx.message.block.0,
-                        tx.message_hash,
-                        tx.message.message.nonce_be,
-                        *merkle_root,
-                    ) {
-                        log::warn!("Merkle root fetcher stopped accepting requests, exiting");
-                        return Ok(false);
-                    }
+            TxStatus::FetchMerkleRoot(ref merkle_root) => {
+                log::info!(
+                    "Transaction {}, nonce={} is fetching merkle root for block #{}",
+                    tx.uuid,
+                    hex::encode(tx.message.message.nonce_be),
+                    merkle_root.block
+                );
+                if !proof_fetcher.send_request(
+                    tx.uuid,
+                    tx.message.block.0,
+                    tx.message_hash,
+                    tx.message.message.nonce_be,
+                    *merkle_root,
+                ) {
+                    log::warn!("Merkle root fetcher stopped accepting requests, exiting");
+                    return false;
                 }
+            }
 
-                TxStatus::SendMessage(ref relayed_merkle_root, ref proof) => {
-                    log::info!(
-                        "Transaction {}, nonce={} is being relayed with merkle root for block #{}",
-                        tx.uuid,
-                        hex::encode(tx.message.message.nonce_be),
-                        relayed_merkle_root.block
-                    );
-                    if !message_sender.send(
-                        tx.message.message.clone(),
-                        *relayed_merkle_root,
-                        proof.clone(),
-                        tx.uuid,
-                    ) {
-                        log::warn!("Message sender stopped accepting messages, exiting");
-                        return Ok(false);
-                    }
+            TxStatus::SendMessage(ref relayed_merkle_root, ref proof) => {
+                log::info!(
+                    "Transaction {}, nonce={} is being relayed with merkle root for block #{}",
+                    tx.uuid,
+                    hex::encode(tx.message.message.nonce_be),
+                    relayed_merkle_root.block
+                );
+                if !message_sender.send(
+                    tx.message.message.clone(),
+                    *relayed_merkle_root,
+                    proof.clone(),
+                    tx.uuid,
+                ) {
+                    log::warn!("Message sender stopped accepting messages, exiting");
+                    return false;
                 }
+            }
 
-                TxStatus::WaitConfirmations(tx_hash) => {
-                    log::info!(
-                        "Transaction {}, nonce={} is waiting for confirmations, tx_hash={}",
-                        tx.uuid,
-                        hex::encode(tx.message.message.nonce_be),
-                        tx_hash
-                    );
-                    if !status_fetcher.send_request(tx.uuid, tx_hash) {
-                        log::warn!("Status fetcher stopped accepting requests, exiting");
-                        return Ok(false);
-                    }
+            TxStatus::WaitConfirmations(tx_hash) => {
+                log::info!(
+                    "Transaction {}, nonce={} is waiting for confirmations, tx_hash={}",
+                    tx.uuid,
+                    hex::encode(tx.message.message.nonce_be),
+                    tx_hash
+                );
+                if !status_fetcher.send_request(tx.uuid, tx_hash) {
+                    log::warn!("Status fetcher stopped accepting requests, exiting");
+                    return false;
                 }
+            }
 
-                TxStatus::Completed => {
-                    // Completed transactions do not need to be resumed
-                    // no-op
+            TxStatus::WaitBatchConfirmations(tx_hash, ..) => {
+                log::info!(
+                    "Transaction {}, nonce={} is waiting for confirmations of batch, tx_hash={}",
+                    tx.uuid,
+                    hex::encode(tx.message.message.nonce_be),
+                    tx_hash
+                );
+                if !status_fetcher.send_batch_request(tx.uuid, tx_hash, tx.message.message.nonce_be)
+                {
+                    log::warn!("Status fetcher stopped accepting requests, exiting");
+                    return false;
                 }
             }
+
+            TxStatus::Completed => {
+                // Completed transactions do not need to be resumed
+                // no-op
+            }
         }
 
-        Ok(true)
+        true
     }
 
     pub async fn run(
-        self,
+        &self,
         mut accumulator: AccumulatorIo,
         mut queued_messages: UnboundedReceiver<MessageInBlock>,
         mut proof_fetcher: MerkleRootFetcherIo,
@@ -284,6 +703,10 @@ impl TransactionManager {
                 return Ok(false);
             }
 
+            _ = self.retry_notify.notified() => {}
+
+            _ = tokio::time::sleep(RETRY_CHECK_INTERVAL) => {}
+
             message = queued_messages.recv() => {
                 let Some(message) = message else {
                     log::info!("No more messages to process, exiting");
@@ -316,6 +739,7 @@ impl TransactionManager {
                         self.storage.block_storage().complete_transaction(&message).await;
                         let tx = Transaction::new(message, TxStatus::WaitForMerkleRoot);
                         self.add_transaction(tx.clone()).await;
+                        self.emit(&tx, LifecycleEventKind::MessageQueued { block: tx.message.block.0 });
 
                         (tx, true)
                     }
@@ -349,6 +773,10 @@ impl TransactionManager {
                     accumulator::Response::Success { tx_uuid, merkle_root, ..} => {
                         if let Some(tx) = self.transactions.write().await.get_mut(&tx_uuid) {
                             tx.status = TxStatus::FetchMerkleRoot(merkle_root);
+                            self.emit(tx, LifecycleEventKind::MerkleRootIncluded {
+                                merkle_root_block: merkle_root.block.0,
+                                merkle_root: merkle_root.merkle_root,
+                            });
                             log::info!(
                                 "Transaction {} at block #{}({}), hash={}, nonce={} got merkle root {} for block #{}",
                                 tx_uuid,
@@ -375,12 +803,22 @@ impl TransactionManager {
                     }
 
                     accumulator::Response::Overflowed(message) => {
-                        self.fail_transaction(message.tx_uuid, "Message overflowed".to_string()).await;
+                        self.fail_transaction(
+                            message.tx_uuid,
+                            "Message overflowed".to_string(),
+                            FailureClass::Permanent,
+                        )
+                        .await;
                     }
 
                     accumulator::Response::Stuck { tx_uuid, .. } => {
 
-                        self.fail_transaction(tx_uuid, "Message stuck".to_string()).await;
+                        self.fail_transaction(
+                            tx_uuid,
+                            "Message stuck".to_string(),
+                            FailureClass::Permanent,
+                        )
+                        .await;
                     }
                 }
             }
@@ -424,6 +862,7 @@ impl TransactionManager {
                         );
                         if let Some(tx) = self.transactions.write().await.get_mut(&tx_uuid) {
                             tx.status = TxStatus::Completed;
+                            self.emit(tx, LifecycleEventKind::MessageDelivered { tx_hash: None });
                         } else {
                             log::warn!("Received message for unknown transaction: {tx_uuid}");
                         }
@@ -433,6 +872,9 @@ impl TransactionManager {
                     message_sender::Response::ProcessingStarted(tx_hash, tx_uuid) => {
                         if let Some(tx) = self.transactions.write().await.get_mut(&tx_uuid) {
                             tx.status = TxStatus::WaitConfirmations(tx_hash);
+                            self.emit(tx, LifecycleEventKind::MessageSubmitted {
+                                tx_hash: H256::from(tx_hash.0),
+                            });
                         } else {
                             log::warn!("Received message for unknown transaction: {tx_uuid}");
                         }
@@ -442,6 +884,33 @@ impl TransactionManager {
                             return Ok(false);
                         }
                     }
+
+                    message_sender::Response::BatchProcessingStarted(tx_hash, tx_uuid) => {
+                        let mut transactions = self.transactions.write().await;
+                        let Some(tx) = transactions.get_mut(&tx_uuid) else {
+                            log::warn!("Received message for unknown transaction: {tx_uuid}");
+                            return Ok(true);
+                        };
+
+                        let TxStatus::SendMessage(merkle_root, proof) = tx.status.clone() else {
+                            log::warn!("Transaction {tx_uuid} is included into batch {tx_hash} while in {} state", tx.status.name());
+                            return Ok(true);
+                        };
+
+                        tx.status = TxStatus::WaitBatchConfirmations(tx_hash, merkle_root, proof);
+                        self.emit(tx, LifecycleEventKind::MessageSubmitted {
+                            tx_hash: H256::from(tx_hash.0),
+                        });
+
+                        if !status_fetcher.send_batch_request(
+                            tx_uuid,
+                            tx_hash,
+                            tx.message.message.nonce_be,
+                        ) {
+                            log::warn!("Status fetcher stopped accepting requests, exiting");
+                            return Ok(false);
+                        }
+                    }
                 }
             }
 
@@ -459,7 +928,11 @@ impl TransactionManager {
                                 message: tx.message,
                                 message_hash: tx.message_hash,
                                 status: TxStatus::Completed,
+                                attempts: tx.attempts,
                             };
+                            self.emit(&completed_tx, LifecycleEventKind::MessageDelivered {
+                                tx_hash: Some(H256::from(tx_hash.0)),
+                            });
                             self.completed.write().await.insert(uuid, completed_tx);
                             self.metrics.completed_transactions.inc();
 
@@ -471,19 +944,47 @@ impl TransactionManager {
                         }
                     }
 
-                    status_fetcher::Response::Failed(uuid, e) => {
-                        if let Some(tx) = self.transactions.write().await.remove(&uuid) {
-                            self.fail_transaction(uuid, e.to_string()).await;
-                            let nonce = hex::encode(tx.message.message.nonce_be);
-                            log::error!("Transaction {uuid}, nonce={nonce} failed: {e}", );
-                        } else {
-                            log::warn!("Received failure response for unknown transaction: {uuid}");
+                    status_fetcher::Response::NotProcessed(uuid, tx_hash) => {
+                        let mut transactions = self.transactions.write().await;
+                        let Some(tx) = transactions.get_mut(&uuid) else {
+                            log::warn!("Received not processed response for unknown transaction: {uuid}");
+                            return Ok(true);
+                        };
+
+                        let TxStatus::WaitBatchConfirmations(_, merkle_root, proof) = tx.status.clone()
+                        else {
+                            log::warn!("Transaction {uuid} isn't processed by batch {tx_hash} while in {} state", tx.status.name());
+                            return Ok(true);
+                        };
+
+                        log::warn!(
+                            "Transaction {uuid}, nonce={} isn't processed by batch {tx_hash}, relaying it again",
+                            hex::encode(tx.message.message.nonce_be)
+                        );
+                        tx.status = TxStatus::SendMessage(merkle_root, proof.clone());
+                        if !message_sender.send(
+                            tx.message.message.clone(),
+                            merkle_root,
+                            proof,
+                            uuid,
+                        ) {
+                            log::warn!("Message sender stopped accepting messages, exiting");
+                            return Ok(false);
                         }
                     }
+
+                    status_fetcher::Response::Failed(uuid, e) => {
+                        let class = status_fetcher::failure_class(&e);
+                        self.fail_transaction(uuid, e.to_string(), class).await;
+                    }
                 }
             }
         }
 
-        Ok(true)
+        self.retry_due().await;
+
+        Ok(self
+            .resume_retried(accumulator, proof_fetcher, message_sender, status_fetcher)
+            .await)
     }
 }
@@ -0,0 +1,386 @@
+//! Portable archive of a proof chain, used to move proofs between [`ProofStorage`] backends.
+
+use super::{AuthoritySetId, ProofStorage, ProofStorageError};
+use crate::hex_utils::hex_bytes;
+use anyhow::{anyhow, Context};
+use primitive_types::H256;
+use prover::proving::{
+    CircuitData, GenesisConfig, Proof, ProofWithCircuitData, ValidatorSetChangePublicInputs,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProofArchive {
+    pub version: u32,
+    pub genesis_authority_set_id: u64,
+    pub genesis_authority_set_hash: H256,
+    #[serde(with = "hex_bytes")]
+    pub circuit_data: Vec<u8>,
+    /// Proofs ordered by authority set id, starting from `genesis_authority_set_id + 1`.
+    pub proofs: Vec<ArchivedProof>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArchivedProof {
+    pub authority_set_id: AuthoritySetId,
+    #[serde(with = "hex_bytes")]
+    pub proof: Vec<u8>,
+}
+
+impl ProofArchive {
+    /// Reads the whole proof chain from `storage`.
+    pub async fn export(
+        storage: &dyn ProofStorage,
+        genesis_config: GenesisConfig,
+    ) -> Result<Self, ProofStorageError> {
+        let latest = storage
+            .get_latest_authority_set_id()
+            .await
+            .ok_or(ProofStorageError::NotInitialized)?;
+        let circuit_data = storage.get_circuit_data().await?;
+
+        let mut proofs = Vec::new();
+        for authority_set_id in genesis_config.authority_set_id + 1..=latest {
+            let proof = storage
+                .get_proof_for_authority_set_id(authority_set_id)
+                .await?;
+            proofs.push(ArchivedProof {
+                authority_set_id,
+                proof: proof.proof.into_bytes(),
+            });
+        }
+
+        Ok(Self {
+            version: ARCHIVE_VERSION,
+            genesis_authority_set_id: genesis_config.authority_set_id,
+            genesis_authority_set_hash: H256(genesis_config.authority_set_hash),
+            circuit_data: circuit_data.into_bytes(),
+            proofs,
+        })
+    }
+
+    pub fn latest_authority_set_id(&self) -> Option<AuthoritySetId> {
+        self.proofs.last().map(|proof| proof.authority_set_id)
+    }
+
+    pub fn check_genesis_config(&self, genesis_config: &GenesisConfig) -> anyhow::Result<()> {
+        if self.genesis_authority_set_id != genesis_config.authority_set_id
+            || self.genesis_authority_set_hash != H256(genesis_config.authority_set_hash)
+        {
+            return Err(anyhow!(
+                "Archive genesis config (authority set id #{}, hash {}) doesn't match the expected one (authority set id #{}, hash {})",
+                self.genesis_authority_set_id,
+                self.genesis_authority_set_hash,
+                genesis_config.authority_set_id,
+                H256(genesis_config.authority_set_hash),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks archive structure: version and that proofs form a chain without gaps.
+    fn check_chain(&self) -> anyhow::Result<()> {
+        if self.version != ARCHIVE_VERSION {
+            return Err(anyhow!(
+                "Unsupported archive version {}, expected {ARCHIVE_VERSION}",
+                self.version
+            ));
+        }
+
+        if self.proofs.is_empty() {
+            return Err(anyhow!("Archive contains no proofs"));
+        }
+
+        for (index, proof) in self.proofs.iter().enumerate() {
+            let expected = self.genesis_authority_set_id + 1 + index as u64;
+            if proof.authority_set_id != expected {
+                return Err(anyhow!(
+                    "Proof chain is broken: expected authority set id #{expected}, found #{}",
+                    proof.authority_set_id
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that proofs form a chain and every one of them verifies against the circuit data
+    /// and proves the changes from the archive genesis to the authority set it's stored for.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        self.check_chain()?;
+
+        let proofs: Vec<_> = self
+            .proofs
+            .par_iter()
+            .map(|proof| {
+                let proof_with_circuit_data = ProofWithCircuitData {
+                    proof: Proof::from_bytes(proof.proof.clone()),
+                    circuit_data: CircuitData::from_bytes(self.circuit_data.clone()),
+                };
+                let public_inputs = proof_with_circuit_data
+                    .verify()
+                    .then(|| proof_with_circuit_data.validator_set_change_public_inputs())
+                    .flatten();
+
+                (proof.authority_set_id, public_inputs)
+            })
+            .collect();
+
+        let invalid: Vec<AuthoritySetId> = proofs
+            .iter()
+            .filter(|(_, public_inputs)| public_inputs.is_none())
+            .map(|(authority_set_id, _)| *authority_set_id)
+            .collect();
+        if !invalid.is_empty() {
+            return Err(anyhow!(
+                "Proofs for authority set ids {invalid:?} don't verify against the circuit data"
+            ));
+        }
+
+        for (authority_set_id, public_inputs) in proofs {
+            let public_inputs = public_inputs.expect("Invalid proofs are reported above");
+            self.check_public_inputs(authority_set_id, &public_inputs)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the proof stored for `authority_set_id` proves exactly that id starting
+    /// from the archive genesis.
+    fn check_public_inputs(
+        &self,
+        authority_set_id: AuthoritySetId,
+        public_inputs: &ValidatorSetChangePublicInputs,
+    ) -> anyhow::Result<()> {
+        self.check_genesis_config(&public_inputs.genesis_config)
+            .with_context(|| {
+                format!("Proof for authority set id #{authority_set_id} has another genesis")
+            })?;
+
+        if public_inputs.authority_set_id != authority_set_id {
+            return Err(anyhow!(
+                "Proof stored for authority set id #{authority_set_id} proves authority set id #{}",
+                public_inputs.authority_set_id
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Writes the proof chain into `storage`. The archive must be checked with
+    /// [`Self::verify`] first, which is CPU-bound and so is left to the caller to run off
+    /// the async runtime.
+    ///
+    /// Storage must be either empty or contain a prefix of the same chain, in which case
+    /// the stored proofs are compared with the archived ones and only the missing proofs
+    /// are written.
+    pub async fn import_verified(&self, storage: &dyn ProofStorage) -> anyhow::Result<usize> {
+        let circuit_data = CircuitData::from_bytes(self.circuit_data.clone());
+
+        let next = match storage.get_latest_authority_set_id().await {
+            None => {
+                let genesis = &self.proofs[0];
+                storage
+                    .init(
+                        ProofWithCircuitData {
+                            proof: Proof::from_bytes(genesis.proof.clone()),
+                            circuit_data,
+                        },
+                        self.genesis_authority_set_id,
+                    )
+                    .await
+                    .context("Failed to initialize proof storage")?;
+                1
+            }
+            Some(latest) => {
+                if storage.get_circuit_data().await?.into_bytes() != self.circuit_data {
+                    return Err(anyhow!(
+                        "Proof storage contains circuit data different from the archive one"
+                    ));
+                }
+                if latest < self.genesis_authority_set_id + 1
+                    || latest > self.latest_authority_set_id().unwrap_or_default()
+                {
+                    return Err(anyhow!(
+                        "Proof storage latest authority set id #{latest} is not a part of the archived chain"
+                    ));
+                }
+
+                let next = (latest - self.genesis_authority_set_id) as usize;
+                for proof in &self.proofs[..next] {
+                    let stored = storage
+                        .get_proof_for_authority_set_id(proof.authority_set_id)
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "Failed to read stored proof for authority set id #{}",
+                                proof.authority_set_id
+                            )
+                        })?;
+                    if stored.proof.into_bytes() != proof.proof {
+                        return Err(anyhow!(
+                            "Proof storage contains a proof for authority set id #{} different from the archive one",
+                            proof.authority_set_id
+                        ));
+                    }
+                }
+
+                next
+            }
+        };
+
+        for proof in &self.proofs[next..] {
+            storage
+                .update(
+                    Proof::from_bytes(proof.proof.clone()),
+                    proof.authority_set_id,
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to store proof for authority set id #{}",
+                        proof.authority_set_id
+                    )
+                })?;
+        }
+
+        Ok(self.proofs.len() - next)
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open archive {}", path.display()))?;
+
+        serde_json::from_reader(std::io::BufReader::new(file))
+            .with_context(|| format!("Failed to decode archive {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        let file = std::fs::File::create(&tmp_path)
+            .with_context(|| format!("Failed to create archive {}", tmp_path.display()))?;
+        serde_json::to_writer(std::io::BufWriter::new(file), self)?;
+        std::fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::InMemoryProofStorage, *};
+
+    fn genesis_config() -> GenesisConfig {
+        GenesisConfig {
+            authority_set_id: 10,
+            authority_set_hash: [7; 32],
+        }
+    }
+
+    fn archive(ids: impl IntoIterator<Item = u64>) -> ProofArchive {
+        ProofArchive {
+            version: ARCHIVE_VERSION,
+            genesis_authority_set_id: 10,
+            genesis_authority_set_hash: H256([7; 32]),
+            circuit_data: vec![1; 8],
+            proofs: ids
+                .into_iter()
+                .map(|authority_set_id| ArchivedProof {
+                    authority_set_id,
+                    proof: vec![authority_set_id as u8; 8],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rejects_broken_chain() {
+        assert!(archive(11..=13).check_chain().is_ok());
+        assert!(archive([]).check_chain().is_err());
+        assert!(archive([11, 12, 14]).check_chain().is_err());
+        assert!(archive([12, 13]).check_chain().is_err());
+    }
+
+    #[test]
+    fn checks_genesis_config() {
+        let archive = archive(11..=12);
+        assert!(archive.check_genesis_config(&genesis_config()).is_ok());
+
+        let mut other = genesis_config();
+        other.authority_set_hash = [8; 32];
+        assert!(archive.check_genesis_config(&other).is_err());
+    }
+
+    #[test]
+    fn checks_proof_public_inputs() {
+        let archive = archive(11..=12);
+        let public_inputs = |genesis_config, authority_set_id| ValidatorSetChangePublicInputs {
+            genesis_config,
+            authority_set_id,
+        };
+        assert!(archive
+            .check_public_inputs(12, &public_inputs(genesis_config(), 12))
+            .is_ok());
+
+        let err = archive
+            .check_public_inputs(12, &public_inputs(genesis_config(), 11))
+            .unwrap_err();
+        assert!(err.to_string().contains("proves authority set id #11"));
+
+        let mut other = genesis_config();
+        other.authority_set_id = 9;
+        assert!(archive
+            .check_public_inputs(12, &public_inputs(other, 12))
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn roundtrips_through_storage() {
+        let archive = archive(11..=13);
+        let storage = InMemoryProofStorage::default();
+        assert_eq!(archive.import_verified(&storage).await.unwrap(), 3);
+
+        let exported = ProofArchive::export(&storage, genesis_config())
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::to_string(&exported).unwrap(),
+            serde_json::to_string(&archive).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn resumes_partial_import() {
+        let storage = InMemoryProofStorage::default();
+        archive(11..=12).import_verified(&storage).await.unwrap();
+
+        assert_eq!(archive(11..=14).import_verified(&storage).await.unwrap(), 2);
+        assert_eq!(storage.get_latest_authority_set_id().await, Some(14));
+
+        let err = archive(11..=13)
+            .import_verified(&storage)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("#14"));
+    }
+
+    #[tokio::test]
+    async fn rejects_diverging_prefix() {
+        let storage = InMemoryProofStorage::default();
+        let mut diverging = archive(11..=12);
+        diverging.proofs[1].proof = vec![0xff; 8];
+        diverging.import_verified(&storage).await.unwrap();
+
+        let err = archive(11..=14)
+            .import_verified(&storage)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("authority set id #12 different"));
+        assert_eq!(storage.get_latest_authority_set_id().await, Some(12));
+    }
+}
@@ -1,12 +1,19 @@
 use prover::proving::{CircuitData, Proof, ProofWithCircuitData};
 
+mod archive;
 mod file_system;
 mod gear;
 mod in_memory;
+mod object_store;
 
+pub use archive::{ArchivedProof, ProofArchive};
 pub use file_system::FileSystemProofStorage;
 pub use gear::GearProofStorage;
-use in_memory::InMemoryProofStorage;
+pub(crate) use in_memory::InMemoryProofStorage;
+pub use object_store::{
+    FileSystemObjectStore, Object, ObjectStore, ObjectStoreProofStorage, PutCondition, PutOutcome,
+    S3Config, S3ObjectStore,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ProofStorageError {
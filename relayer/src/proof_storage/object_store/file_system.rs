@@ -0,0 +1,82 @@
+use super::{digest, Object, ObjectStore, PutCondition, PutOutcome};
+use anyhow::Context;
+use std::{io::ErrorKind, path::PathBuf};
+use tokio::{fs, sync::Mutex};
+
+/// Object store emulated on top of a local directory. Keys are mapped to file paths
+/// and ETags are digests of the file contents.
+///
+/// Conditional writes are serialized within the process only, so it is meant for
+/// tests and single-host setups rather than as a replacement for a real object store.
+pub struct FileSystemObjectStore {
+    root: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl FileSystemObjectStore {
+    pub async fn new(root: PathBuf) -> anyhow::Result<Self> {
+        fs::create_dir_all(&root).await.with_context(|| {
+            format!("Failed to create object store directory {}", root.display())
+        })?;
+
+        Ok(Self {
+            root,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        key.split('/')
+            .filter(|segment| !segment.is_empty() && *segment != "." && *segment != "..")
+            .fold(self.root.clone(), |path, segment| path.join(segment))
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectStore for FileSystemObjectStore {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Object>> {
+        match fs::read(self.path(key)).await {
+            Ok(bytes) => Ok(Some(Object {
+                etag: digest(&bytes),
+                bytes,
+            })),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).with_context(|| format!("Failed to read object {key}")),
+        }
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        condition: PutCondition,
+    ) -> anyhow::Result<PutOutcome> {
+        let _guard = self.write_lock.lock().await;
+
+        let path = self.path(key);
+        let current = match fs::read(&path).await {
+            Ok(bytes) => Some(digest(&bytes)),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err).with_context(|| format!("Failed to read object {key}")),
+        };
+
+        let matches = match condition {
+            PutCondition::IfAbsent => current.is_none(),
+            PutCondition::IfMatch(etag) => current == Some(etag),
+        };
+        if !matches {
+            return Ok(PutOutcome::PreconditionFailed);
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let tmp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        fs::write(&tmp_path, &bytes).await?;
+        fs::rename(&tmp_path, &path)
+            .await
+            .with_context(|| format!("Failed to write object {key}"))?;
+
+        Ok(PutOutcome::Written(digest(&bytes)))
+    }
+}
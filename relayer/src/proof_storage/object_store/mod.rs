@@ -0,0 +1,550 @@
+//! Proof storage backed by an object store, allowing several relayer replicas to
+//! share authority set proofs.
+//!
+//! Objects are content-addressed: circuit data lives under `circuit_data/<digest>`
+//! and proofs under `proofs/<authority set id>/<digest>`. The `manifest.json` object
+//! maps authority set ids to proof digests and is updated with a conditional write,
+//! so concurrent updates from different replicas never overwrite each other.
+
+use super::{AuthoritySetId, ProofStorage, ProofStorageError};
+use anyhow::{anyhow, Context};
+use prover::proving::{CircuitData, Proof, ProofWithCircuitData};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+use tokio::sync::RwLock;
+
+mod file_system;
+mod s3;
+
+pub use file_system::FileSystemObjectStore;
+pub use s3::{S3Config, S3ObjectStore};
+
+const MANIFEST_KEY: &str = "manifest.json";
+const MAX_UPDATE_ATTEMPTS: usize = 8;
+
+/// Opaque version identifier of a stored object.
+pub type ETag = String;
+
+pub struct Object {
+    pub bytes: Vec<u8>,
+    pub etag: ETag,
+}
+
+pub enum PutCondition {
+    /// Write only if there's no object under the key.
+    IfAbsent,
+    /// Write only if the current version of the object matches.
+    IfMatch(ETag),
+}
+
+pub enum PutOutcome {
+    Written(ETag),
+    PreconditionFailed,
+}
+
+/// Minimal interface of an object store with conditional writes.
+#[async_trait::async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Returns `None` if there's no object under the key.
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Object>>;
+
+    async fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        condition: PutCondition,
+    ) -> anyhow::Result<PutOutcome>;
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Manifest {
+    circuit_data: String,
+    proofs: BTreeMap<AuthoritySetId, String>,
+}
+
+#[derive(Default)]
+struct Cache {
+    manifest: Option<(Manifest, ETag)>,
+    circuit_data: Option<CircuitData>,
+    proofs: BTreeMap<AuthoritySetId, Proof>,
+}
+
+pub struct ObjectStoreProofStorage {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+    /// Directory where downloaded objects are kept between restarts.
+    cache_dir: Option<PathBuf>,
+    cache: RwLock<Cache>,
+}
+
+#[async_trait::async_trait]
+impl ProofStorage for ObjectStoreProofStorage {
+    async fn init(
+        &self,
+        proof_with_circuit_data: ProofWithCircuitData,
+        genesis_validator_set_id: u64,
+    ) -> Result<(), ProofStorageError> {
+        let mut cache = self.cache.write().await;
+        self.refresh(&mut cache).await?;
+        if cache.manifest.is_some() {
+            return Err(ProofStorageError::AlreadyInitialized);
+        }
+
+        let authority_set_id = genesis_validator_set_id + 1;
+        let ProofWithCircuitData {
+            proof,
+            circuit_data,
+        } = proof_with_circuit_data;
+
+        let circuit_data_digest = self
+            .put_content("circuit_data", circuit_data.clone().into_bytes())
+            .await?;
+        let proof_digest = self
+            .put_content(
+                &format!("proofs/{authority_set_id}"),
+                proof.clone().into_bytes(),
+            )
+            .await?;
+
+        let manifest = Manifest {
+            circuit_data: circuit_data_digest,
+            proofs: BTreeMap::from([(authority_set_id, proof_digest)]),
+        };
+        let bytes = serde_json::to_vec(&manifest).map_err(anyhow::Error::from)?;
+        match self
+            .store
+            .put(&self.key(MANIFEST_KEY), bytes, PutCondition::IfAbsent)
+            .await?
+        {
+            PutOutcome::Written(etag) => {
+                cache.manifest = Some((manifest, etag));
+                cache.circuit_data = Some(circuit_data);
+                cache.proofs.insert(authority_set_id, proof);
+
+                Ok(())
+            }
+            PutOutcome::PreconditionFailed => {
+                self.refresh(&mut cache).await?;
+
+                Err(ProofStorageError::AlreadyInitialized)
+            }
+        }
+    }
+
+    async fn get_circuit_data(&self) -> Result<CircuitData, ProofStorageError> {
+        if let Some(circuit_data) = &self.cache.read().await.circuit_data {
+            return Ok(circuit_data.clone());
+        }
+
+        let mut cache = self.cache.write().await;
+        if cache.manifest.is_none() {
+            self.refresh(&mut cache).await?;
+        }
+        let digest = match &cache.manifest {
+            Some((manifest, _)) => manifest.circuit_data.clone(),
+            None => return Err(ProofStorageError::NotInitialized),
+        };
+
+        let bytes = self.get_content("circuit_data", &digest).await?;
+        let circuit_data = CircuitData::from_bytes(bytes);
+        cache.circuit_data = Some(circuit_data.clone());
+
+        Ok(circuit_data)
+    }
+
+    async fn get_latest_authority_set_id(&self) -> Option<AuthoritySetId> {
+        let mut cache = self.cache.write().await;
+        if let Err(err) = self.refresh(&mut cache).await {
+            log::warn!("Failed to refresh proof storage manifest, using cached one: {err}");
+        }
+
+        cache
+            .manifest
+            .as_ref()
+            .and_then(|(manifest, _)| manifest.proofs.last_key_value())
+            .map(|(authority_set_id, _)| *authority_set_id)
+    }
+
+    async fn get_proof_for_authority_set_id(
+        &self,
+        authority_set_id: AuthoritySetId,
+    ) -> Result<ProofWithCircuitData, ProofStorageError> {
+        let circuit_data = self.get_circuit_data().await?;
+
+        if let Some(proof) = self.cache.read().await.proofs.get(&authority_set_id) {
+            return Ok(ProofWithCircuitData {
+                proof: proof.clone(),
+                circuit_data,
+            });
+        }
+
+        let mut cache = self.cache.write().await;
+        let mut digest = cache
+            .manifest
+            .as_ref()
+            .and_then(|(manifest, _)| manifest.proofs.get(&authority_set_id).cloned());
+        if digest.is_none() {
+            self.refresh(&mut cache).await?;
+            digest = cache
+                .manifest
+                .as_ref()
+                .and_then(|(manifest, _)| manifest.proofs.get(&authority_set_id).cloned());
+        }
+        let digest = digest.ok_or(ProofStorageError::NotFound(authority_set_id))?;
+
+        let bytes = self
+            .get_content(&format!("proofs/{authority_set_id}"), &digest)
+            .await?;
+        let proof = Proof::from_bytes(bytes);
+        cache.proofs.insert(authority_set_id, proof.clone());
+
+        Ok(ProofWithCircuitData {
+            proof,
+            circuit_data,
+        })
+    }
+
+    async fn update(
+        &self,
+        proof: Proof,
+        new_authority_set_id: AuthoritySetId,
+    ) -> Result<(), ProofStorageError> {
+        let mut cache = self.cache.write().await;
+        let proof_digest = self
+            .put_content(
+                &format!("proofs/{new_authority_set_id}"),
+                proof.clone().into_bytes(),
+            )
+            .await?;
+
+        for _ in 0..MAX_UPDATE_ATTEMPTS {
+            self.refresh(&mut cache).await?;
+            let Some((manifest, etag)) = &cache.manifest else {
+                return Err(ProofStorageError::NotInitialized);
+            };
+
+            if manifest.proofs.contains_key(&new_authority_set_id) {
+                log::info!(
+                    "Proof for authority set id #{new_authority_set_id} is already stored by another relayer"
+                );
+                return Ok(());
+            }
+
+            let latest = manifest
+                .proofs
+                .last_key_value()
+                .map(|(authority_set_id, _)| *authority_set_id)
+                .ok_or(ProofStorageError::NotInitialized)?;
+            if new_authority_set_id != latest + 1 {
+                return Err(ProofStorageError::AuthoritySetIdMismatch);
+            }
+
+            let mut manifest = manifest.clone();
+            manifest
+                .proofs
+                .insert(new_authority_set_id, proof_digest.clone());
+            let bytes = serde_json::to_vec(&manifest).map_err(anyhow::Error::from)?;
+
+            match self
+                .store
+                .put(
+                    &self.key(MANIFEST_KEY),
+                    bytes,
+                    PutCondition::IfMatch(etag.clone()),
+                )
+                .await?
+            {
+                PutOutcome::Written(etag) => {
+                    cache.manifest = Some((manifest, etag));
+                    cache.proofs.insert(new_authority_set_id, proof);
+
+                    return Ok(());
+                }
+                PutOutcome::PreconditionFailed => {
+                    log::debug!(
+                        "Proof storage manifest changed concurrently while storing authority set id #{new_authority_set_id}, retrying"
+                    );
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "Failed to store proof for authority set id #{new_authority_set_id}: too many concurrent manifest updates"
+        )
+        .into())
+    }
+}
+
+impl ObjectStoreProofStorage {
+    pub async fn new(
+        store: Arc<dyn ObjectStore>,
+        prefix: String,
+        cache_dir: Option<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        if let Some(cache_dir) = &cache_dir {
+            tokio::fs::create_dir_all(cache_dir)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to create proof storage cache directory {}",
+                        cache_dir.display()
+                    )
+                })?;
+        }
+
+        let storage = Self {
+            store,
+            prefix: prefix.trim_matches('/').to_string(),
+            cache_dir,
+            cache: RwLock::new(Cache::default()),
+        };
+
+        let mut cache = storage.cache.write().await;
+        storage.refresh(&mut cache).await?;
+        match &cache.manifest {
+            Some((manifest, _)) => log::info!(
+                "Proof storage manifest loaded, latest authority set id is #{}",
+                manifest.proofs.keys().last().copied().unwrap_or_default()
+            ),
+            None => log::info!("Proof storage manifest not found. Waiting for initialization"),
+        }
+        drop(cache);
+
+        Ok(storage)
+    }
+
+    fn key(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{name}", self.prefix)
+        }
+    }
+
+    /// Reloads manifest from the store if it has changed.
+    async fn refresh(&self, cache: &mut Cache) -> Result<(), ProofStorageError> {
+        let Some(object) = self.store.get(&self.key(MANIFEST_KEY)).await? else {
+            return Ok(());
+        };
+
+        if matches!(&cache.manifest, Some((_, etag)) if *etag == object.etag) {
+            return Ok(());
+        }
+
+        let manifest: Manifest = serde_json::from_slice(&object.bytes)
+            .context("Failed to decode proof storage manifest")?;
+
+        if let Some((cached, _)) = &cache.manifest {
+            if cached.circuit_data != manifest.circuit_data {
+                cache.circuit_data = None;
+            }
+        }
+        cache
+            .proofs
+            .retain(|authority_set_id, _| manifest.proofs.contains_key(authority_set_id));
+        cache.manifest = Some((manifest, object.etag));
+
+        Ok(())
+    }
+
+    /// Stores content-addressed object under `namespace` and returns its digest.
+    async fn put_content(&self, namespace: &str, bytes: Vec<u8>) -> anyhow::Result<String> {
+        let digest = digest(&bytes);
+        let key = self.key(&format!("{namespace}/{digest}"));
+
+        // Content-addressed object might already be uploaded by another relayer.
+        self.store
+            .put(&key, bytes.clone(), PutCondition::IfAbsent)
+            .await?;
+        self.write_cache_file(&digest, &bytes).await;
+
+        Ok(digest)
+    }
+
+    async fn get_content(&self, namespace: &str, digest: &str) -> anyhow::Result<Vec<u8>> {
+        if let Some(bytes) = self.read_cache_file(digest).await {
+            return Ok(bytes);
+        }
+
+        let key = self.key(&format!("{namespace}/{digest}"));
+        let object = self
+            .store
+            .get(&key)
+            .await?
+            .ok_or_else(|| anyhow!("Object {key} referenced by manifest not found"))?;
+        if self::digest(&object.bytes) != digest {
+            return Err(anyhow!("Object {key} doesn't match its digest"));
+        }
+
+        self.write_cache_file(digest, &object.bytes).await;
+
+        Ok(object.bytes)
+    }
+
+    async fn read_cache_file(&self, digest: &str) -> Option<Vec<u8>> {
+        let path = self.cache_dir.as_ref()?.join(digest);
+        let bytes = tokio::fs::read(&path).await.ok()?;
+        if self::digest(&bytes) != digest {
+            log::warn!("Corrupted proof storage cache file {}", path.display());
+            return None;
+        }
+
+        Some(bytes)
+    }
+
+    async fn write_cache_file(&self, digest: &str, bytes: &[u8]) {
+        let Some(cache_dir) = &self.cache_dir else {
+            return;
+        };
+
+        let path = cache_dir.join(digest);
+        let tmp_path = cache_dir.join(format!("{digest}.tmp"));
+        let result = async {
+            tokio::fs::write(&tmp_path, bytes).await?;
+            tokio::fs::rename(&tmp_path, &path).await
+        }
+        .await;
+        if let Err(err) = result {
+            log::warn!(
+                "Failed to write proof storage cache file {}: {err}",
+                path.display()
+            );
+        }
+    }
+}
+
+fn digest(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    async fn storage(root: &Path, cache_dir: Option<PathBuf>) -> ObjectStoreProofStorage {
+        let store = Arc::new(
+            FileSystemObjectStore::new(root.to_path_buf())
+                .await
+                .unwrap(),
+        );
+        ObjectStoreProofStorage::new(store, "mainnet".to_string(), cache_dir)
+            .await
+            .unwrap()
+    }
+
+    fn genesis() -> ProofWithCircuitData {
+        ProofWithCircuitData {
+            proof: Proof::from_bytes(vec![1; 16]),
+            circuit_data: CircuitData::from_bytes(vec![2; 16]),
+        }
+    }
+
+    #[tokio::test]
+    async fn replicas_share_proofs() {
+        let root = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let first = storage(root.path(), None).await;
+        let second = storage(root.path(), Some(cache_dir.path().to_path_buf())).await;
+
+        assert_eq!(second.get_latest_authority_set_id().await, None);
+        first.init(genesis(), 10).await.unwrap();
+        assert!(matches!(
+            second.init(genesis(), 10).await,
+            Err(ProofStorageError::AlreadyInitialized)
+        ));
+
+        first
+            .update(Proof::from_bytes(vec![3; 16]), 12)
+            .await
+            .unwrap();
+        assert_eq!(second.get_latest_authority_set_id().await, Some(12));
+
+        let proof = second.get_proof_for_authority_set_id(12).await.unwrap();
+        assert_eq!(proof.proof.into_bytes(), vec![3; 16]);
+        assert_eq!(proof.circuit_data.into_bytes(), vec![2; 16]);
+        assert!(matches!(
+            second.get_proof_for_authority_set_id(13).await,
+            Err(ProofStorageError::NotFound(13))
+        ));
+    }
+
+    #[tokio::test]
+    async fn concurrent_update_is_accepted_once() {
+        let root = tempfile::tempdir().unwrap();
+        let first = storage(root.path(), None).await;
+        let second = storage(root.path(), None).await;
+        first.init(genesis(), 10).await.unwrap();
+
+        first
+            .update(Proof::from_bytes(vec![3; 16]), 12)
+            .await
+            .unwrap();
+        // The second replica computed the same authority set concurrently.
+        second
+            .update(Proof::from_bytes(vec![4; 16]), 12)
+            .await
+            .unwrap();
+        assert!(matches!(
+            second.update(Proof::from_bytes(vec![5; 16]), 14).await,
+            Err(ProofStorageError::AuthoritySetIdMismatch)
+        ));
+
+        let proof = second.get_proof_for_authority_set_id(12).await.unwrap();
+        assert_eq!(proof.proof.into_bytes(), vec![3; 16]);
+    }
+
+    #[tokio::test]
+    async fn file_system_store_conditional_writes() {
+        let root = tempfile::tempdir().unwrap();
+        let store = FileSystemObjectStore::new(root.path().to_path_buf())
+            .await
+            .unwrap();
+
+        let PutOutcome::Written(etag) = store
+            .put("key", vec![1], PutCondition::IfAbsent)
+            .await
+            .unwrap()
+        else {
+            panic!("object should be written");
+        };
+        assert!(matches!(
+            store
+                .put("key", vec![2], PutCondition::IfAbsent)
+                .await
+                .unwrap(),
+            PutOutcome::PreconditionFailed
+        ));
+        assert!(matches!(
+            store
+                .put("key", vec![2], PutCondition::IfMatch(etag.clone()))
+                .await
+                .unwrap(),
+            PutOutcome::Written(_)
+        ));
+        assert!(matches!(
+            store
+                .put("key", vec![3], PutCondition::IfMatch(etag))
+                .await
+                .unwrap(),
+            PutOutcome::PreconditionFailed
+        ));
+        assert_eq!(store.get("key").await.unwrap().unwrap().bytes, vec![2]);
+        assert!(store.get("missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn serves_objects_from_cache_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let first = storage(root.path(), Some(cache_dir.path().to_path_buf())).await;
+        first.init(genesis(), 10).await.unwrap();
+
+        // Objects are served from the cache once they are removed from the store.
+        let second = storage(root.path(), Some(cache_dir.path().to_path_buf())).await;
+        std::fs::remove_dir_all(root.path().join("mainnet/proofs")).unwrap();
+        let proof = second.get_proof_for_authority_set_id(11).await.unwrap();
+        assert_eq!(proof.proof.into_bytes(), vec![1; 16]);
+    }
+}
@@ -0,0 +1,299 @@
+//! Benchmark of the gear-eth-core prover on recorded data.
+//!
+//! [`ProverFixture`] holds everything fetched from a Gear node to prove a merkle root: the
+//! first authority set change after genesis and the finality of the block with the root. A
+//! benchmark proves every circuit with it once per thread count and reports circuit sizes,
+//! proving time and peak memory as JSON, so prover machines can be sized and regressions
+//! tracked between releases without a node.
+
+use crate::prover_interface::{
+    self, gnark, parse_rpc_block_finality_proof, parse_rpc_inclusion_proof,
+};
+use anyhow::{anyhow, Context};
+use gear_rpc_client::{
+    dto::{BlockFinalityProof, RawBlockInclusionProof, StorageInclusionProof},
+    GearApi,
+};
+use parity_scale_codec::{Decode, Encode};
+use primitive_types::H256;
+use prover::{
+    bench::{self, CircuitStats, Fixture},
+    proving::GenesisConfig,
+    GearHeader,
+};
+use rayon::ThreadPoolBuilder;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const FIXTURE_VERSION: u32 = 1;
+const REPORT_VERSION: u32 = 1;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProverFixture {
+    pub version: u32,
+    pub genesis_authority_set_id: u64,
+    pub genesis_authority_set_hash: H256,
+    /// Finality of a block in the last epoch of the genesis authority set.
+    pub current_epoch_block_finality: BlockFinalityProof,
+    pub next_validator_set_inclusion_proof: StorageInclusionProof,
+    /// Block with the merkle root.
+    pub block_number: u32,
+    pub block_finality: RawBlockInclusionProof,
+    /// Hex-encoded SCALE headers from the block with the merkle root to the finalized one.
+    pub headers: Vec<String>,
+    pub message_inclusion_proof: StorageInclusionProof,
+}
+
+impl ProverFixture {
+    /// Fetches the data `prove_genesis` and `prove_final` use to prove the merkle root of
+    /// `block`. Genesis is the authority set preceding the one of `block`.
+    pub async fn record(gear_api: &GearApi, block: H256) -> anyhow::Result<Self> {
+        let authority_set_id = gear_api.authority_set_id(block).await?;
+        let era_first_block = gear_api
+            .find_era_first_block(authority_set_id)
+            .await
+            .context("Unable to find the first block of an era")?;
+        let genesis_block = gear_api
+            .block_hash_to_number(era_first_block)
+            .await?
+            .checked_sub(1)
+            .ok_or_else(|| {
+                anyhow!("Block {block} is in the first authority set, there is no change to prove")
+            })?;
+        let genesis = gear_api
+            .authority_set_state(Some(gear_api.block_number_to_hash(genesis_block).await?))
+            .await?;
+
+        let (epoch_block, current_epoch_block_finality) = gear_api
+            .fetch_finality_proof_for_session(genesis.authority_set_id + 1)
+            .await?;
+        let next_validator_set_inclusion_proof = gear_api
+            .fetch_next_session_keys_inclusion_proof(epoch_block)
+            .await?;
+
+        let (justification, headers) =
+            prover_interface::get_justification_and_headers(gear_api, block).await?;
+        let block_finality = gear_api.produce_finality_proof(&justification).await?;
+        if block_finality.required_authority_set_id != genesis.authority_set_id + 1 {
+            return Err(anyhow!(
+                "Block {block} is finalized by authority set #{}, expected #{}",
+                block_finality.required_authority_set_id,
+                genesis.authority_set_id + 1
+            ));
+        }
+
+        let Some(header_first) = headers.first() else {
+            return Err(anyhow!("No headers for block {block}"));
+        };
+        let message_inclusion_proof = gear_api
+            .fetch_sent_message_inclusion_proof(header_first.hash().0.into())
+            .await?;
+
+        Ok(Self {
+            version: FIXTURE_VERSION,
+            genesis_authority_set_id: genesis.authority_set_id,
+            genesis_authority_set_hash: H256(genesis.authority_set_hash),
+            current_epoch_block_finality,
+            next_validator_set_inclusion_proof,
+            block_number: header_first.number,
+            block_finality,
+            headers: headers
+                .iter()
+                .map(|header| hex::encode(header.encode()))
+                .collect(),
+            message_inclusion_proof,
+        })
+    }
+
+    /// Converts the fixture into prover inputs. Validator signs are proven by
+    /// `count_thread` threads.
+    pub fn prover_fixture(&self, count_thread: usize) -> anyhow::Result<Fixture> {
+        if self.version != FIXTURE_VERSION {
+            return Err(anyhow!(
+                "Unsupported fixture version {}, expected {FIXTURE_VERSION}",
+                self.version
+            ));
+        }
+
+        let headers = self
+            .headers
+            .iter()
+            .map(|header| {
+                let header = hex::decode(header)?;
+                Ok(GearHeader::decode(&mut &header[..])?)
+            })
+            .collect::<anyhow::Result<_>>()
+            .context("Fixture contains invalid header")?;
+
+        Ok(Fixture {
+            genesis_config: GenesisConfig {
+                authority_set_id: self.genesis_authority_set_id,
+                authority_set_hash: self.genesis_authority_set_hash.0,
+            },
+            current_epoch_block_finality: parse_rpc_block_finality_proof(
+                self.current_epoch_block_finality.clone(),
+                Some(count_thread),
+            ),
+            next_validator_set_inclusion_proof: parse_rpc_inclusion_proof(
+                self.next_validator_set_inclusion_proof.clone(),
+            ),
+            next_validator_set_data: self.next_validator_set_inclusion_proof.stored_data.clone(),
+            block_finality: parse_rpc_block_finality_proof(
+                self.block_finality.clone().into(),
+                Some(count_thread),
+            ),
+            headers,
+            message_inclusion_proof: parse_rpc_inclusion_proof(
+                self.message_inclusion_proof.clone(),
+            ),
+            message_contents: self.message_inclusion_proof.stored_data.clone(),
+        })
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Failed to open fixture {}", path.display()))?;
+
+        serde_json::from_reader(std::io::BufReader::new(file))
+            .with_context(|| format!("Failed to decode fixture {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to create fixture {}", path.display()))?;
+        serde_json::to_writer(std::io::BufWriter::new(file), self)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+pub struct BenchReport {
+    pub version: u32,
+    pub relayer_version: &'static str,
+    pub genesis_authority_set_id: u64,
+    pub block_number: u32,
+    pub runs: Vec<BenchRun>,
+}
+
+#[derive(Serialize)]
+pub struct BenchRun {
+    pub thread_count: usize,
+    pub circuits: Vec<CircuitStats>,
+}
+
+/// Proves the fixture once per thread count. The gnark wrap is skipped when
+/// `gnark_data_path` isn't set.
+pub fn run(
+    fixture: &ProverFixture,
+    thread_counts: &[usize],
+    gnark_data_path: Option<&Path>,
+) -> anyhow::Result<BenchReport> {
+    let mut runs = Vec::with_capacity(thread_counts.len());
+    for &thread_count in thread_counts {
+        if thread_count == 0 {
+            return Err(anyhow!("Thread count must be positive"));
+        }
+
+        log::info!("Proving fixture with {thread_count} threads");
+
+        let inputs = fixture.prover_fixture(thread_count)?;
+        let pool = ThreadPoolBuilder::new().num_threads(thread_count).build()?;
+        let (mut circuits, wrapped) = pool.install(|| bench::run(inputs));
+
+        if let Some(gnark_data_path) = gnark_data_path {
+            let (_, usage) = bench::measure(|| gnark::prove_circuit(&wrapped, gnark_data_path));
+            circuits.push(CircuitStats {
+                circuit: "gnark_wrap",
+                shape: None,
+                usage,
+            });
+        }
+
+        runs.push(BenchRun {
+            thread_count,
+            circuits,
+        });
+    }
+
+    Ok(BenchReport {
+        version: REPORT_VERSION,
+        relayer_version: env!("CARGO_PKG_VERSION"),
+        genesis_authority_set_id: fixture.genesis_authority_set_id,
+        block_number: fixture.block_number,
+        runs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gear_rpc_client::dto::PreCommit;
+
+    fn fixture() -> ProverFixture {
+        let inclusion_proof = StorageInclusionProof {
+            address: vec![0x12],
+            block_header: vec![],
+            branch_nodes_data: vec![],
+            leaf_node_data: vec![],
+            stored_data: vec![1; 32],
+        };
+        let header = GearHeader::new(
+            42,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+
+        ProverFixture {
+            version: FIXTURE_VERSION,
+            genesis_authority_set_id: 7,
+            genesis_authority_set_hash: H256::repeat_byte(0xaa),
+            current_epoch_block_finality: BlockFinalityProof {
+                validator_set: vec![[1; 32]],
+                pre_commits: vec![PreCommit {
+                    public_key: [1; 32],
+                    signature: [2; 64],
+                }],
+                message: vec![0; 53],
+            },
+            next_validator_set_inclusion_proof: inclusion_proof.clone(),
+            block_number: 42,
+            block_finality: RawBlockInclusionProof {
+                justification_round: 1,
+                required_authority_set_id: 8,
+                validator_set: vec![[1; 32]],
+                block_hash: header.hash().0.into(),
+                block_number: 42,
+                pre_commits: vec![],
+            },
+            headers: vec![hex::encode(header.encode())],
+            message_inclusion_proof: inclusion_proof,
+        }
+    }
+
+    #[test]
+    fn converts_fixture_into_prover_inputs() {
+        let fixture: ProverFixture =
+            serde_json::from_str(&serde_json::to_string(&fixture()).unwrap()).unwrap();
+
+        let inputs = fixture.prover_fixture(4).unwrap();
+        assert_eq!(inputs.genesis_config.authority_set_id, 7);
+        assert_eq!(inputs.genesis_config.authority_set_hash, [0xaa; 32]);
+        assert_eq!(inputs.current_epoch_block_finality.count_thread, Some(4));
+        assert_eq!(inputs.block_finality.count_thread, Some(4));
+        assert_eq!(inputs.headers.len(), 1);
+        assert_eq!(inputs.headers[0].number, 42);
+        assert_eq!(inputs.message_contents, vec![1; 32]);
+    }
+
+    #[test]
+    fn rejects_unsupported_fixture_version() {
+        let mut fixture = fixture();
+        fixture.version = FIXTURE_VERSION + 1;
+
+        let err = fixture.prover_fixture(4).err().unwrap().to_string();
+        assert!(err.contains("Unsupported fixture version"));
+    }
+}
@@ -140,7 +140,31 @@ pub struct FinalProof {
     pub merkle_root: [u8; 32],
 }
 
+/// Size of a proof serialized by gnark for the on-chain PLONK verifier.
+const PROOF_SIZE: usize = 0x340 + 0x60;
+
 impl FinalProof {
+    /// Fake proof of `merkle_root` at `block_number`. Only verifiers deployed as
+    /// `VerifierMock.sol` accept it.
+    pub fn mock(block_number: u32, merkle_root: [u8; 32]) -> Self {
+        let public_inputs = [
+            BigUint::from_bytes_be(&merkle_root[..24]),
+            BigUint::from_bytes_be(
+                &[
+                    &merkle_root[24..],
+                    &block_number.to_be_bytes()[..],
+                    &[0; 12],
+                ]
+                .concat(),
+            ),
+        ];
+
+        Self::from_proof_and_public_inputs(
+            format!("0x{}", hex::encode([0; PROOF_SIZE])),
+            public_inputs,
+        )
+    }
+
     pub fn from_proof_and_public_inputs(proof: String, public_inputs: [BigUint; 2]) -> Self {
         // data layout:
         // root[0] root[1] root[2] root[3] root[4] root[5]
@@ -350,7 +374,7 @@ pub async fn prove_final_with_block_finality(
     ))
 }
 
-fn parse_rpc_inclusion_proof(proof: dto::StorageInclusionProof) -> StorageInclusion {
+pub(crate) fn parse_rpc_inclusion_proof(proof: dto::StorageInclusionProof) -> StorageInclusion {
     let address_nibbles = proof
         .address
         .into_iter()
@@ -373,7 +397,7 @@ fn parse_rpc_inclusion_proof(proof: dto::StorageInclusionProof) -> StorageInclus
     }
 }
 
-fn parse_rpc_block_finality_proof(
+pub(crate) fn parse_rpc_block_finality_proof(
     proof: dto::BlockFinalityProof,
     count_thread: Option<usize>,
 ) -> BlockFinality {
@@ -438,3 +462,22 @@ pub mod gnark {
         serde_json::from_str(&result).expect("Got wrong output from gnark prover")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_proof_keeps_block_and_root() {
+        let mut merkle_root = [0; 32];
+        merkle_root[0] = 0xaa;
+        merkle_root[23] = 0xbb;
+        merkle_root[31] = 0xcc;
+
+        let proof = FinalProof::mock(0x01020304, merkle_root);
+
+        assert_eq!(proof.block_number, 0x01020304);
+        assert_eq!(proof.merkle_root, merkle_root);
+        assert_eq!(proof.proof.len(), PROOF_SIZE);
+    }
+}
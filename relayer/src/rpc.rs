@@ -44,7 +44,7 @@ impl Default for RetryPolicy {
 }
 
 impl RetryPolicy {
-    fn delay(&self, attempt: u32) -> Duration {
+    pub fn delay(&self, attempt: u32) -> Duration {
         let multiplier = 1u32.checked_shl(attempt.min(6)).unwrap_or(64);
         let delay = self
             .base_delay
@@ -1,33 +1,150 @@
-use crate::message_relayer::common::web_request::{
-    EthTransaction, EthTransactions, MerkleRootBlocks, MerkleRootsRequest, Message, Messages,
+use crate::{
+    api_tokens::{ApiTokens, Caller, Denied, Scope},
+    hex_utils,
+    message_relayer::{
+        common::{
+            dead_letter::RetryError,
+            lifecycle::{EventFilter, LifecycleEvent},
+            web_request::{
+                EthTransaction, EthTransactions, FailedTransaction, MerkleRootBlocks,
+                MerkleRootsRequest, Message, Messages, RetryRequest, TransactionLookup,
+                TransactionQueueStatus, TransactionState,
+            },
+        },
+        eth_to_gear, gear_to_eth,
+    },
 };
 use actix_web::{guard, middleware, web, App, HttpRequest, HttpResponse, HttpServer};
-use futures::{stream::FuturesUnordered, StreamExt};
-use std::{collections::HashSet, net::TcpListener};
-use tokio::sync::mpsc::UnboundedSender;
+use futures::{
+    stream::{self, FuturesUnordered},
+    StreamExt,
+};
+use primitive_types::U256;
+use serde::Deserialize;
+use std::{collections::HashSet, net::TcpListener, sync::Arc, time::Duration};
+use tokio::sync::{broadcast, mpsc::UnboundedSender};
+use uuid::Uuid;
 
 const HEADER_TOKEN: &str = "X-Token";
 
-struct Secret(String);
+/// Idle lifecycle event streams get a comment line this often so proxies keep them open.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
 
 struct LogContext(String);
 
+/// Transaction queue of a token transfer relayer exposed by the status endpoints.
+#[derive(Clone)]
+pub enum TransactionQueue {
+    EthToGear(Arc<eth_to_gear::tx_manager::TransactionManager>),
+    GearToEth(Arc<gear_to_eth::tx_manager::TransactionManager>),
+}
+
+impl TransactionQueue {
+    async fn status(&self) -> TransactionQueueStatus {
+        match self {
+            Self::EthToGear(tx_manager) => tx_manager.queue_status().await,
+            Self::GearToEth(tx_manager) => tx_manager.queue_status().await,
+        }
+    }
+
+    async fn find(&self, lookup: &TransactionLookup) -> Option<TransactionState> {
+        match self {
+            Self::EthToGear(tx_manager) => tx_manager.find_transaction(lookup).await,
+            Self::GearToEth(tx_manager) => tx_manager.find_transaction(lookup).await,
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<LifecycleEvent> {
+        match self {
+            Self::EthToGear(tx_manager) => tx_manager.subscribe(),
+            Self::GearToEth(tx_manager) => tx_manager.subscribe(),
+        }
+    }
+
+    async fn failed(&self) -> Vec<FailedTransaction> {
+        match self {
+            Self::EthToGear(tx_manager) => tx_manager.failed_transactions().await,
+            Self::GearToEth(tx_manager) => tx_manager.failed_transactions().await,
+        }
+    }
+
+    async fn retry(
+        &self,
+        tx_uuid: Uuid,
+        stage: Option<&str>,
+    ) -> Result<TransactionState, RetryError> {
+        match self {
+            Self::EthToGear(tx_manager) => tx_manager.retry_transaction(tx_uuid, stage).await,
+            Self::GearToEth(tx_manager) => tx_manager.retry_transaction(tx_uuid, stage).await,
+        }
+    }
+
+    async fn discard(&self, tx_uuid: Uuid) -> bool {
+        match self {
+            Self::EthToGear(tx_manager) => tx_manager.discard_transaction(tx_uuid).await,
+            Self::GearToEth(tx_manager) => tx_manager.discard_transaction(tx_uuid).await,
+        }
+    }
+}
+
+/// Checks the token of the request against `scope`. Denied requests are audited here, the
+/// caller audits the authorized ones once they're handled.
+fn authorize(
+    request: &HttpRequest,
+    tokens: &ApiTokens,
+    log_context: &LogContext,
+    scope: Scope,
+) -> Result<Caller, HttpResponse> {
+    let token = request
+        .headers()
+        .get(HEADER_TOKEN)
+        .and_then(|h| h.to_str().ok());
+    let route = request.path();
+
+    let denied = match tokens.authorize(token, scope) {
+        Ok(caller) => return Ok(caller),
+        Err(denied) => denied,
+    };
+
+    let (name, response) = match denied {
+        Denied::Unauthorized => (None, HttpResponse::Unauthorized().finish()),
+        Denied::Forbidden { name } => {
+            log::warn!(
+                "[{}] Token {name} is not allowed to call {route}",
+                log_context.0.as_str()
+            );
+            (Some(name), HttpResponse::Forbidden().finish())
+        }
+        Denied::QuotaExceeded { name, retry_after } => {
+            log::warn!(
+                "[{}] Token {name} exceeded its quota calling {route}",
+                log_context.0.as_str()
+            );
+            let retry_after = retry_after.as_secs().max(1).to_string();
+            (
+                Some(name),
+                HttpResponse::TooManyRequests()
+                    .insert_header(("Retry-After", retry_after))
+                    .finish(),
+            )
+        }
+    };
+    tokens.audit(name.as_deref(), route, response.status().as_u16(), None);
+
+    Err(response)
+}
+
 async fn relay_messages(
     request: HttpRequest,
     messages: web::Json<Messages>,
-    secret: web::Data<Secret>,
+    tokens: web::Data<ApiTokens>,
     log_context: web::Data<LogContext>,
     channel: web::Data<UnboundedSender<Message>>,
 ) -> HttpResponse {
-    if !request
-        .headers()
-        .get(HEADER_TOKEN)
-        .and_then(|h| h.to_str().ok())
-        .map(|t| t == secret.0.as_str())
-        .unwrap_or(false)
-    {
-        return HttpResponse::Unauthorized().finish();
-    }
+    let caller = match authorize(&request, &tokens, &log_context, Scope::RelayMessages) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
 
     let messages = messages.into_inner().messages;
     let len = messages.len();
@@ -54,31 +171,34 @@ async fn relay_messages(
         }
     }
 
-    if to_process == 0 {
+    let response = if to_process == 0 {
         HttpResponse::Ok().finish()
     } else if to_process == len {
         HttpResponse::InternalServerError().finish()
     } else {
         HttpResponse::Accepted().finish()
-    }
+    };
+    tokens.audit(
+        Some(&caller.name),
+        request.path(),
+        response.status().as_u16(),
+        None,
+    );
+
+    response
 }
 
 async fn relay_transactions(
     request: HttpRequest,
     transactions: web::Json<EthTransactions>,
-    secret: web::Data<Secret>,
+    tokens: web::Data<ApiTokens>,
     log_context: web::Data<LogContext>,
     channel: web::Data<UnboundedSender<EthTransaction>>,
 ) -> HttpResponse {
-    if !request
-        .headers()
-        .get(HEADER_TOKEN)
-        .and_then(|h| h.to_str().ok())
-        .map(|t| t == secret.0.as_str())
-        .unwrap_or(false)
-    {
-        return HttpResponse::Unauthorized().finish();
-    }
+    let caller = match authorize(&request, &tokens, &log_context, Scope::RelayTransactions) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
 
     let transactions = transactions.into_inner().transactions;
     let len = transactions.len();
@@ -103,34 +223,38 @@ async fn relay_transactions(
         }
     }
 
-    if to_process == 0 {
+    let response = if to_process == 0 {
         HttpResponse::Ok().finish()
     } else if to_process == len {
         HttpResponse::InternalServerError().finish()
     } else {
         HttpResponse::Accepted().finish()
-    }
+    };
+    tokens.audit(
+        Some(&caller.name),
+        request.path(),
+        response.status().as_u16(),
+        None,
+    );
+
+    response
 }
 
 async fn get_merkle_root_proof(
     request: HttpRequest,
     blocks: web::Json<MerkleRootBlocks>,
-    secret: web::Data<Secret>,
+    tokens: web::Data<ApiTokens>,
     log_context: web::Data<LogContext>,
     channel: web::Data<UnboundedSender<MerkleRootsRequest>>,
 ) -> HttpResponse {
-    if !request
-        .headers()
-        .get(HEADER_TOKEN)
-        .and_then(|h| h.to_str().ok())
-        .map(|t| t == secret.0.as_str())
-        .unwrap_or(false)
-    {
-        return HttpResponse::Unauthorized().finish();
-    }
+    let caller = match authorize(&request, &tokens, &log_context, Scope::MerkleRootProof) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
 
     let blocks = blocks.into_inner().blocks;
     let len = blocks.len();
+    let requested = blocks.clone();
 
     let mut futures = FuturesUnordered::new();
     let mut failed_sends = 0;
@@ -172,33 +296,320 @@ async fn get_merkle_root_proof(
         }
     }
 
-    if to_process == 0 {
+    let response = if to_process == 0 {
         HttpResponse::Ok().json(merkle_roots)
     } else if to_process == len {
         HttpResponse::InternalServerError().finish()
     } else {
         HttpResponse::Accepted().json(merkle_roots)
+    };
+    tokens.audit(
+        Some(&caller.name),
+        request.path(),
+        response.status().as_u16(),
+        Some(&requested),
+    );
+
+    response
+}
+
+async fn merkle_roots_status(
+    request: HttpRequest,
+    tokens: web::Data<ApiTokens>,
+    log_context: web::Data<LogContext>,
+    channel: web::Data<UnboundedSender<MerkleRootsRequest>>,
+) -> HttpResponse {
+    if let Err(response) = authorize(&request, &tokens, &log_context, Scope::Status) {
+        return response;
+    }
+
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    if channel
+        .send(MerkleRootsRequest::GetStatus { response: sender })
+        .is_err()
+    {
+        log::error!(
+            "[{}] Unable to send merkle roots status request",
+            log_context.0.as_str()
+        );
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    match receiver.await {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => {
+            log::error!(
+                "[{}] Unable to receive merkle roots status: {e:?}",
+                log_context.0.as_str()
+            );
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn transactions_status(
+    request: HttpRequest,
+    tokens: web::Data<ApiTokens>,
+    log_context: web::Data<LogContext>,
+    queue: web::Data<TransactionQueue>,
+) -> HttpResponse {
+    if let Err(response) = authorize(&request, &tokens, &log_context, Scope::Status) {
+        return response;
+    }
+
+    HttpResponse::Ok().json(queue.status().await)
+}
+
+async fn transaction_status(
+    request: HttpRequest,
+    path: web::Path<(String, String)>,
+    tokens: web::Data<ApiTokens>,
+    log_context: web::Data<LogContext>,
+    queue: web::Data<TransactionQueue>,
+) -> HttpResponse {
+    if let Err(response) = authorize(&request, &tokens, &log_context, Scope::Status) {
+        return response;
+    }
+
+    let (key, value) = path.into_inner();
+    let Some(lookup) = parse_lookup(&key, &value) else {
+        return HttpResponse::BadRequest().finish();
+    };
+
+    match queue.find(&lookup).await {
+        Some(transaction) => HttpResponse::Ok().json(transaction),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+async fn failed_transactions(
+    request: HttpRequest,
+    tokens: web::Data<ApiTokens>,
+    log_context: web::Data<LogContext>,
+    queue: web::Data<TransactionQueue>,
+) -> HttpResponse {
+    if let Err(response) = authorize(&request, &tokens, &log_context, Scope::FailedTransactions) {
+        return response;
+    }
+
+    HttpResponse::Ok().json(queue.failed().await)
+}
+
+/// Puts a failed transaction back into the queue at the `stage` query parameter, or at
+/// the stage it failed at.
+async fn retry_failed_transaction(
+    request: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<RetryRequest>,
+    tokens: web::Data<ApiTokens>,
+    log_context: web::Data<LogContext>,
+    queue: web::Data<TransactionQueue>,
+) -> HttpResponse {
+    let caller = match authorize(&request, &tokens, &log_context, Scope::FailedTransactions) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
+
+    let response = match Uuid::parse_str(&path.into_inner()) {
+        Err(_) => HttpResponse::BadRequest().finish(),
+        Ok(tx_uuid) => match queue.retry(tx_uuid, query.stage.as_deref()).await {
+            Ok(transaction) => {
+                log::info!(
+                    "[{}] Token {} restarted failed transaction {tx_uuid} at {}",
+                    log_context.0.as_str(),
+                    caller.name,
+                    transaction.status
+                );
+                HttpResponse::Ok().json(transaction)
+            }
+            Err(err @ RetryError::NotFound(_)) => HttpResponse::NotFound().body(err.to_string()),
+            Err(err @ RetryError::UnknownStage(_)) => {
+                HttpResponse::BadRequest().body(err.to_string())
+            }
+            Err(err @ RetryError::StageUnavailable { .. }) => {
+                HttpResponse::Conflict().body(err.to_string())
+            }
+        },
+    };
+    tokens.audit(
+        Some(&caller.name),
+        request.path(),
+        response.status().as_u16(),
+        None,
+    );
+
+    response
+}
+
+/// Drops a failed transaction for good.
+async fn discard_failed_transaction(
+    request: HttpRequest,
+    path: web::Path<String>,
+    tokens: web::Data<ApiTokens>,
+    log_context: web::Data<LogContext>,
+    queue: web::Data<TransactionQueue>,
+) -> HttpResponse {
+    let caller = match authorize(&request, &tokens, &log_context, Scope::FailedTransactions) {
+        Ok(caller) => caller,
+        Err(response) => return response,
+    };
+
+    let response = match Uuid::parse_str(&path.into_inner()) {
+        Err(_) => HttpResponse::BadRequest().finish(),
+        Ok(tx_uuid) if queue.discard(tx_uuid).await => {
+            log::warn!(
+                "[{}] Token {} discarded failed transaction {tx_uuid}",
+                log_context.0.as_str(),
+                caller.name
+            );
+            HttpResponse::Ok().finish()
+        }
+        Ok(_) => HttpResponse::NotFound().finish(),
+    };
+    tokens.audit(
+        Some(&caller.name),
+        request.path(),
+        response.status().as_u16(),
+        None,
+    );
+
+    response
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    nonce: Option<String>,
+    sender: Option<String>,
+    token: Option<String>,
+}
+
+impl EventsQuery {
+    fn into_filter(self) -> Option<EventFilter> {
+        Some(EventFilter {
+            nonce: match self.nonce {
+                Some(nonce) => Some(parse_nonce(&nonce)?),
+                None => None,
+            },
+            sender: self.sender,
+            token: match self.token {
+                Some(token) => Some(hex_utils::decode_h160(&token).ok()?),
+                None => None,
+            },
+        })
+    }
+}
+
+/// Streams lifecycle events of the transaction queue as server-sent events.
+async fn lifecycle_events(
+    request: HttpRequest,
+    query: web::Query<EventsQuery>,
+    tokens: web::Data<ApiTokens>,
+    log_context: web::Data<LogContext>,
+    queue: web::Data<TransactionQueue>,
+) -> HttpResponse {
+    if let Err(response) = authorize(&request, &tokens, &log_context, Scope::Events) {
+        return response;
+    }
+
+    let Some(filter) = query.into_inner().into_filter() else {
+        return HttpResponse::BadRequest().finish();
+    };
+
+    let log_context = log_context.0.clone();
+    let events = stream::unfold(
+        (queue.subscribe(), filter),
+        move |(mut receiver, filter)| {
+            let log_context = log_context.clone();
+            async move {
+                loop {
+                    let chunk = match tokio::time::timeout(KEEP_ALIVE_INTERVAL, receiver.recv())
+                        .await
+                    {
+                        Err(_) => ": keep-alive\n\n".to_string(),
+                        Ok(Ok(event)) if filter.matches(&event) => {
+                            let data = match serde_json::to_string(&event) {
+                                Ok(data) => data,
+                                Err(e) => {
+                                    log::error!(
+                                        "[{log_context}] Unable to serialize lifecycle event: {e:?}"
+                                    );
+                                    continue;
+                                }
+                            };
+
+                            format!("event: {}\ndata: {data}\n\n", event.kind.name())
+                        }
+                        Ok(Ok(_)) => continue,
+                        Ok(Err(broadcast::error::RecvError::Lagged(skipped))) => {
+                            log::warn!(
+                                "[{log_context}] Lifecycle event subscriber lagged behind by {skipped} events"
+                            );
+                            format!("event: lagged\ndata: {{\"skipped\":{skipped}}}\n\n")
+                        }
+                        Ok(Err(broadcast::error::RecvError::Closed)) => return None,
+                    };
+
+                    return Some((
+                        Ok::<_, actix_web::Error>(web::Bytes::from(chunk)),
+                        (receiver, filter),
+                    ));
+                }
+            }
+        },
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
+/// Parses a nonce which is either decimal or 0x-prefixed hex.
+fn parse_nonce(value: &str) -> Option<U256> {
+    match value.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).ok(),
+        None => U256::from_dec_str(value).ok(),
+    }
+}
+
+/// Parses `/status/transactions/{key}/{value}`.
+fn parse_lookup(key: &str, value: &str) -> Option<TransactionLookup> {
+    match key {
+        "uuid" => Uuid::parse_str(value).ok().map(TransactionLookup::Uuid),
+        "nonce" => parse_nonce(value).map(TransactionLookup::Nonce),
+        "hash" => hex_utils::decode_h256(value)
+            .ok()
+            .map(TransactionLookup::TxHash),
+        _ => None,
     }
 }
 
+/// Creates the relayer web server. Besides the routes relaying requests through the given
+/// channels, it serves read-only `GET /status/...` routes: merkle roots relayer state when
+/// `merkle_roots_channel` is set and the transaction queue when `transaction_queue` is set.
+/// The latter also enables the `GET /events` stream of message lifecycle events and the
+/// `/failed_transactions` routes managing the dead-letter queue. Every route checks the
+/// `X-Token` header against `tokens` and the scope the route requires.
 pub fn create(
     tcp_listener: TcpListener,
-    secret: String,
+    tokens: Arc<ApiTokens>,
     log_context: String,
     messages_channel: Option<UnboundedSender<Message>>,
     merkle_roots_channel: Option<UnboundedSender<MerkleRootsRequest>>,
     transactions_channel: Option<UnboundedSender<EthTransaction>>,
+    transaction_queue: Option<TransactionQueue>,
 ) -> std::io::Result<actix_web::dev::Server> {
     let messages_channel = messages_channel.map(web::Data::new);
     let merkle_roots_channel = merkle_roots_channel.map(web::Data::new);
     let transactions_channel = transactions_channel.map(web::Data::new);
+    let transaction_queue = transaction_queue.map(web::Data::new);
 
-    let secret = web::Data::new(Secret(secret));
+    let tokens = web::Data::from(tokens);
     let log_context = web::Data::new(LogContext(log_context));
 
     let server = HttpServer::new(move || {
         let mut app = App::new()
-            .app_data(secret.clone())
+            .app_data(tokens.clone())
             .app_data(log_context.clone())
             // enable logger
             .wrap(middleware::Logger::default())
@@ -221,15 +632,19 @@ pub fn create(
         };
 
         app = if let Some(channel) = merkle_roots_channel.as_ref() {
-            app.app_data(channel.clone()).service(
-                web::resource("/get_merkle_root_proof")
-                    .route(web::post().to(get_merkle_root_proof))
-                    .route(
-                        web::route()
-                            .guard(guard::Any(guard::Get()).or(guard::Post()))
-                            .to(HttpResponse::Unauthorized),
-                    ),
-            )
+            app.app_data(channel.clone())
+                .service(
+                    web::resource("/get_merkle_root_proof")
+                        .route(web::post().to(get_merkle_root_proof))
+                        .route(
+                            web::route()
+                                .guard(guard::Any(guard::Get()).or(guard::Post()))
+                                .to(HttpResponse::Unauthorized),
+                        ),
+                )
+                .service(
+                    web::resource("/status/merkle_roots").route(web::get().to(merkle_roots_status)),
+                )
         } else {
             app
         };
@@ -248,6 +663,31 @@ pub fn create(
             app
         };
 
+        app = if let Some(queue) = transaction_queue.as_ref() {
+            app.app_data(queue.clone())
+                .service(
+                    web::resource("/status/transactions").route(web::get().to(transactions_status)),
+                )
+                .service(
+                    web::resource("/status/transactions/{key}/{value}")
+                        .route(web::get().to(transaction_status)),
+                )
+                .service(web::resource("/events").route(web::get().to(lifecycle_events)))
+                .service(
+                    web::resource("/failed_transactions").route(web::get().to(failed_transactions)),
+                )
+                .service(
+                    web::resource("/failed_transactions/{uuid}/retry")
+                        .route(web::post().to(retry_failed_transaction)),
+                )
+                .service(
+                    web::resource("/failed_transactions/{uuid}/discard")
+                        .route(web::post().to(discard_failed_transaction)),
+                )
+        } else {
+            app
+        };
+
         app
     });
 
@@ -259,7 +699,13 @@ pub fn create(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::message_relayer::common::web_request::MerkleRootsResponse;
+    use crate::{
+        merkle_roots::status::{AuthoritySetSyncStatus, RelayerStatus},
+        message_relayer::common::{
+            web_request::MerkleRootsResponse, EthereumSlotNumber, TxHashWithSlot,
+        },
+    };
+    use ethereum_client::TxHash;
     use ethereum_common::U256;
     use reqwest::{
         header::{HeaderValue, CONTENT_TYPE},
@@ -285,11 +731,12 @@ mod tests {
 
         let server = super::create(
             listener,
-            SECRET.to_string(),
+            Arc::new(ApiTokens::new(SECRET.to_string())),
             "test".to_string(),
             Some(channel),
             None,
             None,
+            None,
         )
         .unwrap();
 
@@ -396,11 +843,12 @@ mod tests {
 
         let server = super::create(
             listener,
-            SECRET.to_string(),
+            Arc::new(ApiTokens::new(SECRET.to_string())),
             "test".to_string(),
             None,
             Some(channel),
             None,
+            None,
         )
         .unwrap();
 
@@ -440,6 +888,7 @@ mod tests {
                     .send(MerkleRootsResponse::NoMerkleRootOnBlock { block_number })
                     .unwrap();
             }
+            MerkleRootsRequest::GetStatus { .. } => panic!("unexpected status request"),
         }
 
         let response = request.await.unwrap();
@@ -489,6 +938,7 @@ mod tests {
                     .send(MerkleRootsResponse::NoMerkleRootOnBlock { block_number })
                     .unwrap();
             }
+            MerkleRootsRequest::GetStatus { .. } => panic!("unexpected status request"),
         }
         assert!(receiver_b.try_recv().is_err());
         assert_eq!(request_a.await.unwrap().status(), reqwest::StatusCode::OK);
@@ -518,6 +968,7 @@ mod tests {
                     .send(MerkleRootsResponse::NoMerkleRootOnBlock { block_number })
                     .unwrap();
             }
+            MerkleRootsRequest::GetStatus { .. } => panic!("unexpected status request"),
         }
         assert!(receiver_a.try_recv().is_err());
         assert_eq!(request_b.await.unwrap().status(), reqwest::StatusCode::OK);
@@ -534,11 +985,12 @@ mod tests {
 
         let server = super::create(
             listener,
-            SECRET.to_string(),
+            Arc::new(ApiTokens::new(SECRET.to_string())),
             "test".to_string(),
             None,
             Some(channel),
             None,
+            None,
         )
         .unwrap();
 
@@ -574,11 +1026,12 @@ mod tests {
 
         let server = super::create(
             listener,
-            SECRET.to_string(),
+            Arc::new(ApiTokens::new(SECRET.to_string())),
             "test".to_string(),
             None,
             Some(channel),
             None,
+            None,
         )
         .unwrap();
 
@@ -611,6 +1064,7 @@ mod tests {
                 assert_eq!(block_number, 123);
                 drop(response);
             }
+            MerkleRootsRequest::GetStatus { .. } => panic!("unexpected status request"),
         }
 
         let response = request.await.unwrap();
@@ -620,6 +1074,462 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_merkle_roots_status() {
+        const SECRET: &str = "SECRET123";
+        let (url, mut receiver) = spawn_merkle_root_server(SECRET, "test");
+        let url = url.replace("/get_merkle_root_proof", "/status/merkle_roots");
+
+        let client = ClientBuilder::new()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .unwrap();
+
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let request = task::spawn(async move {
+            client
+                .get(&url)
+                .header(HEADER_TOKEN, SECRET)
+                .send()
+                .await
+                .unwrap()
+        });
+
+        let status = RelayerStatus {
+            relayer_id: "test".to_string(),
+            last_submitted_block: Some(100),
+            merkle_roots: Vec::new(),
+            finalized_merkle_roots: 3,
+            batch: vec![101],
+            authority_set_sync: AuthoritySetSyncStatus {
+                latest_proven_authority_set_id: Some(7),
+                waiting: Vec::new(),
+            },
+            prover_jobs: Vec::new(),
+            submissions: Vec::new(),
+        };
+        match receiver.recv().await.unwrap() {
+            MerkleRootsRequest::GetStatus { response } => response.send(status.clone()).unwrap(),
+            MerkleRootsRequest::GetMerkleRootProof { .. } => panic!("unexpected proof request"),
+        }
+
+        let response = request.await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(response.json::<RelayerStatus>().await.unwrap(), status);
+    }
+
+    #[tokio::test]
+    async fn test_transactions_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let tx_manager = Arc::new(eth_to_gear::tx_manager::TransactionManager::new(Arc::new(
+            eth_to_gear::storage::NoStorage::new(),
+        )));
+        let transaction = eth_to_gear::tx_manager::Transaction::new(
+            TxHashWithSlot {
+                slot_number: EthereumSlotNumber(42),
+                tx_hash: TxHash::repeat_byte(1),
+            },
+            eth_to_gear::tx_manager::TxStatus::ComposeProof,
+        );
+        let uuid = transaction.uuid;
+        tx_manager.add_transaction(transaction).await;
+
+        const SECRET: &str = "SECRET123";
+        let server = super::create(
+            listener,
+            Arc::new(ApiTokens::new(SECRET.to_string())),
+            "test".to_string(),
+            None,
+            None,
+            None,
+            Some(TransactionQueue::EthToGear(tx_manager)),
+        )
+        .unwrap();
+        task::spawn(server);
+
+        let client = ClientBuilder::new()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        let base = format!("http://127.0.0.1:{port}/status/transactions");
+        let get = |path: String| {
+            client
+                .get(format!("{base}{path}"))
+                .header(HEADER_TOKEN, SECRET)
+                .send()
+        };
+
+        let response = client.get(&base).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = get(String::new()).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let status = response.json::<TransactionQueueStatus>().await.unwrap();
+        assert_eq!(status.pending.len(), 1);
+        assert_eq!(status.pending[0].uuid, uuid);
+        assert_eq!(status.pending[0].status, "compose_proof");
+        assert_eq!(status.pending[0].block, Some(42));
+
+        let hash = format!("0x{}", "01".repeat(32));
+        for path in [format!("/uuid/{uuid}"), format!("/hash/{hash}")] {
+            let response = get(path).await.unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+            let transaction = response.json::<TransactionState>().await.unwrap();
+            assert_eq!(transaction.uuid, uuid);
+        }
+
+        let response = get("/nonce/0x01".to_string()).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let response = get("/uuid/not-a-uuid".to_string()).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_events() {
+        use crate::message_relayer::common::{
+            dead_letter::FailureClass, AuthoritySetId, GearBlockNumber, MessageInBlock,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let tx_manager = Arc::new(gear_to_eth::tx_manager::TransactionManager::new(Arc::new(
+            gear_to_eth::storage::NoStorage::new(),
+        )));
+        let transactions = [6u8, 7].map(|nonce| {
+            let mut nonce_be = [0; 32];
+            nonce_be[31] = nonce;
+            gear_to_eth::tx_manager::Transaction::new(
+                MessageInBlock {
+                    message: gear_rpc_client::dto::Message {
+                        nonce_be,
+                        source: [1; 32],
+                        destination: [2; 20],
+                        payload: vec![],
+                    },
+                    block: GearBlockNumber(10),
+                    block_hash: Default::default(),
+                    authority_set_id: AuthoritySetId(1),
+                },
+                gear_to_eth::tx_manager::TxStatus::WaitForMerkleRoot,
+            )
+        });
+        for transaction in transactions.iter().cloned() {
+            tx_manager.add_transaction(transaction).await;
+        }
+
+        const SECRET: &str = "SECRET123";
+        let server = super::create(
+            listener,
+            Arc::new(ApiTokens::new(SECRET.to_string())),
+            "test".to_string(),
+            None,
+            None,
+            None,
+            Some(TransactionQueue::GearToEth(tx_manager.clone())),
+        )
+        .unwrap();
+        task::spawn(server);
+
+        let client = ClientBuilder::new()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        let base = format!("http://127.0.0.1:{port}/events");
+
+        let response = client.get(&base).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .get(format!("{base}?token=0x01"))
+            .header(HEADER_TOKEN, SECRET)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let mut response = client
+            .get(format!("{base}?nonce=7"))
+            .header(HEADER_TOKEN, SECRET)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+
+        for transaction in &transactions {
+            tx_manager
+                .fail_transaction(
+                    transaction.uuid,
+                    "Message stuck".to_string(),
+                    FailureClass::Permanent,
+                )
+                .await;
+        }
+
+        let mut body = String::new();
+        while !body.ends_with("\n\n") {
+            let chunk = response.chunk().await.unwrap().unwrap();
+            body.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+
+        let data = body
+            .strip_prefix("event: message_failed\ndata: ")
+            .and_then(|data| data.strip_suffix("\n\n"))
+            .unwrap();
+        let event = serde_json::from_str::<LifecycleEvent>(data).unwrap();
+        assert_eq!(event.tx_uuid, transactions[1].uuid);
+        assert_eq!(event.nonce, Some(U256::from(7)));
+    }
+
+    #[tokio::test]
+    async fn test_failed_transactions() {
+        use crate::message_relayer::common::{
+            dead_letter::FailureClass, AuthoritySetId, GearBlockNumber, MessageInBlock,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let tx_manager = Arc::new(gear_to_eth::tx_manager::TransactionManager::new(Arc::new(
+            gear_to_eth::storage::NoStorage::new(),
+        )));
+        let transaction = gear_to_eth::tx_manager::Transaction::new(
+            MessageInBlock {
+                message: gear_rpc_client::dto::Message {
+                    nonce_be: [3; 32],
+                    source: [1; 32],
+                    destination: [2; 20],
+                    payload: vec![],
+                },
+                block: GearBlockNumber(10),
+                block_hash: Default::default(),
+                authority_set_id: AuthoritySetId(1),
+            },
+            gear_to_eth::tx_manager::TxStatus::WaitForMerkleRoot,
+        );
+        let uuid = transaction.uuid;
+        tx_manager.add_transaction(transaction).await;
+        tx_manager
+            .fail_transaction(uuid, "Message stuck".to_string(), FailureClass::Permanent)
+            .await;
+
+        const SECRET: &str = "SECRET123";
+        let server = super::create(
+            listener,
+            Arc::new(ApiTokens::new(SECRET.to_string())),
+            "test".to_string(),
+            None,
+            None,
+            None,
+            Some(TransactionQueue::GearToEth(tx_manager.clone())),
+        )
+        .unwrap();
+        task::spawn(server);
+
+        let client = ClientBuilder::new()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        let base = format!("http://127.0.0.1:{port}/failed_transactions");
+        let post = |path: String| {
+            client
+                .post(format!("{base}{path}"))
+                .header(HEADER_TOKEN, SECRET)
+                .send()
+        };
+
+        let response = client.get(&base).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let response = client
+            .get(&base)
+            .header(HEADER_TOKEN, SECRET)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let failed = response.json::<Vec<FailedTransaction>>().await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].transaction.uuid, uuid);
+        assert_eq!(failed[0].transaction.status, "wait_for_merkle_root");
+        assert_eq!(
+            failed[0].transaction.failure.as_deref(),
+            Some("Message stuck")
+        );
+        assert_eq!(failed[0].class, FailureClass::Permanent);
+        assert_eq!(failed[0].attempts, 1);
+        assert_eq!(failed[0].retry_at, None);
+        assert!(tx_manager.queue_status().await.pending.is_empty());
+
+        let response = post(format!("/{uuid}/retry?stage=send_message"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+        let response = post(format!("/{uuid}/retry?stage=unknown")).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        let response = post(format!("/{}/retry", Uuid::new_v4())).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let response = post(format!("/{uuid}/retry")).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let transaction = response.json::<TransactionState>().await.unwrap();
+        assert_eq!(transaction.status, "wait_for_merkle_root");
+        let status = tx_manager.queue_status().await;
+        assert_eq!(status.pending.len(), 1);
+        assert_eq!(status.failed, 0);
+
+        tx_manager
+            .fail_transaction(uuid, "Message stuck".to_string(), FailureClass::Permanent)
+            .await;
+        assert_eq!(tx_manager.failed_transactions().await[0].attempts, 2);
+
+        let response = post(format!("/{uuid}/discard")).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let response = post(format!("/{uuid}/discard")).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+        assert_eq!(tx_manager.queue_status().await.failed, 0);
+    }
+
+    #[test]
+    fn test_parse_lookup() {
+        assert_eq!(
+            parse_lookup("nonce", "0x10"),
+            Some(TransactionLookup::Nonce(U256::from(16)))
+        );
+        assert_eq!(
+            parse_lookup("nonce", "16"),
+            Some(TransactionLookup::Nonce(U256::from(16)))
+        );
+        assert_eq!(parse_lookup("hash", "0x01"), None);
+        assert_eq!(parse_lookup("block", "1"), None);
+    }
+
+    #[tokio::test]
+    async fn test_scoped_tokens() {
+        use sha2::{Digest, Sha256};
+
+        let dir = tempfile::tempdir().unwrap();
+        let tokens_path = dir.path().join("tokens.toml");
+        std::fs::write(
+            &tokens_path,
+            format!(
+                "[[tokens]]\nname = \"partner\"\ntoken_sha256 = \"{}\"\nscopes = [\"merkle_root_proof\"]\nrequests = 1\nperiod = \"1h\"\n",
+                hex::encode(Sha256::digest(b"PARTNER"))
+            ),
+        )
+        .unwrap();
+        let audit_path = dir.path().join("audit.log");
+        let tokens = ApiTokens::load(
+            "SECRET123".to_string(),
+            Some(tokens_path),
+            Some(audit_path.clone()),
+        )
+        .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (messages_channel, _messages) = mpsc::unbounded_channel();
+        let (merkle_roots_channel, mut merkle_roots) = mpsc::unbounded_channel();
+        let server = super::create(
+            listener,
+            Arc::new(tokens),
+            "test".to_string(),
+            Some(messages_channel),
+            Some(merkle_roots_channel),
+            None,
+            None,
+        )
+        .unwrap();
+        task::spawn(server);
+
+        task::spawn(async move {
+            while let Some(request) = merkle_roots.recv().await {
+                match request {
+                    MerkleRootsRequest::GetMerkleRootProof {
+                        block_number,
+                        response,
+                    } => {
+                        let _ = response
+                            .send(MerkleRootsResponse::NoMerkleRootOnBlock { block_number });
+                    }
+                    MerkleRootsRequest::GetStatus { .. } => panic!("unexpected status request"),
+                }
+            }
+        });
+
+        let client = ClientBuilder::new()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        let post = |route: &str, token: &str, body: serde_json::Value| {
+            client
+                .post(format!("http://127.0.0.1:{port}{route}"))
+                .header(HEADER_TOKEN, token)
+                .json(&body)
+                .send()
+        };
+
+        let response = post(
+            "/relay_messages",
+            "PARTNER",
+            serde_json::json!({ "messages": [] }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let proof_request = serde_json::json!({ "blocks": [42] });
+        let response = post("/get_merkle_root_proof", "PARTNER", proof_request.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = post("/get_merkle_root_proof", "PARTNER", proof_request.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key("Retry-After"));
+
+        let response = post("/get_merkle_root_proof", "SECRET123", proof_request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let audit = std::fs::read_to_string(&audit_path).unwrap();
+        let records: Vec<serde_json::Value> = audit
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let summary: Vec<_> = records
+            .iter()
+            .map(|record| {
+                (
+                    record["token"].as_str().unwrap(),
+                    record["status"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("partner", 403),
+                ("partner", 200),
+                ("partner", 429),
+                (crate::api_tokens::SHARED_TOKEN_NAME, 200)
+            ]
+        );
+        assert_eq!(records[1]["blocks"], serde_json::json!([42]));
+    }
+
     fn spawn_merkle_root_server(
         secret: &str,
         log_context: &str,
@@ -630,11 +1540,12 @@ mod tests {
 
         let server = super::create(
             listener,
-            secret.to_string(),
+            Arc::new(ApiTokens::new(secret.to_string())),
             log_context.to_string(),
             None,
             Some(channel),
             None,
+            None,
         )
         .unwrap();
         task::spawn(server);
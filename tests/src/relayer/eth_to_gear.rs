@@ -13,7 +13,7 @@ use ethereum_common::{
 };
 use gear_common::api_provider::ApiProvider;
 use relayer::message_relayer::{
-    common::{EthereumSlotNumber, TxHashWithSlot},
+    common::{dead_letter::FailureClass, EthereumSlotNumber, TxHashWithSlot},
     eth_to_gear::{
         message_sender::{self, MessageSender, MessageSenderIo},
         proof_composer::{self, ProofComposerIo},
@@ -205,6 +205,7 @@ impl MockMessageSender {
                             tx_uuid: req.tx_uuid,
                             status: message_sender::MessageStatus::Failure(
                                 "Mock failure for testing".to_string(),
+                                FailureClass::Permanent,
                             ),
                         })
                         .unwrap();
@@ -1,2 +1,3 @@
 pub mod eth_to_gear;
+pub mod simulation;
 pub mod upload;
@@ -0,0 +1,275 @@
+use super::Chain;
+use alloy::primitives::FixedBytes;
+use ethereum_client::FeeCaps;
+use gear_rpc_client::dto::Message;
+use primitive_types::H256;
+use relayer::message_relayer::common::{
+    AuthoritySetId, EthereumSlotNumber, GearBlockNumber, MessageInBlock, RelayedMerkleRoot,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+use tokio::time::{self, Instant};
+
+pub const GEAR_BLOCK_TIME: Duration = Duration::from_secs(3);
+pub const ETHEREUM_BLOCK_TIME: Duration = Duration::from_secs(12);
+/// Number of slots the finalized checkpoint lags behind the head of the beacon chain.
+pub const CHECKPOINT_LAG: u64 = 64;
+
+/// Both chains of the simulation. Heights follow the virtual clock, so they're the same
+/// on every run of a test.
+pub struct Network {
+    started_at: Instant,
+    gear: Mutex<Gear>,
+    ethereum: Mutex<Ethereum>,
+}
+
+#[derive(Default)]
+pub struct Gear {
+    pub authority_set_id: u64,
+    pub rpc_down: bool,
+    /// Last blocks of the previous authority sets which have no merkle root relayed yet.
+    pub unrelayed_sets: Vec<(AuthoritySetId, GearBlockNumber)>,
+    /// Ethereum transactions whose receipts are submitted to Gear.
+    pub receipts: BTreeSet<FixedBytes<32>>,
+}
+
+#[derive(Default)]
+pub struct Ethereum {
+    pub rpc_down: bool,
+    pub merkle_roots: Vec<RelayedMerkleRoot>,
+    /// Sent transactions with the nonce of the message they deliver and the block they're
+    /// included at.
+    pub transactions: BTreeMap<FixedBytes<32>, ([u8; 32], u64)>,
+    /// Sent merkle root transactions of the submitter with the block they're included at.
+    pub submissions: BTreeMap<FixedBytes<32>, (Submission, u64)>,
+    sent: u64,
+}
+
+/// Transaction submitting a merkle root, sent by the single fee payer of the submitter.
+#[derive(Clone, Copy, Debug)]
+pub struct Submission {
+    pub nonce: u64,
+    pub block_number: u32,
+    pub merkle_root: [u8; 32],
+    pub fees: FeeCaps,
+}
+
+impl Network {
+    pub fn new(authority_set_id: u64) -> Self {
+        Self {
+            started_at: Instant::now(),
+            gear: Mutex::new(Gear {
+                authority_set_id,
+                ..Default::default()
+            }),
+            ethereum: Mutex::new(Ethereum::default()),
+        }
+    }
+
+    pub fn gear(&self) -> MutexGuard<'_, Gear> {
+        self.gear.lock().expect("Gear state is poisoned")
+    }
+
+    pub fn ethereum(&self) -> MutexGuard<'_, Ethereum> {
+        self.ethereum.lock().expect("Ethereum state is poisoned")
+    }
+
+    pub fn gear_block(&self) -> GearBlockNumber {
+        GearBlockNumber((self.started_at.elapsed().as_secs() / GEAR_BLOCK_TIME.as_secs()) as u32)
+    }
+
+    pub fn ethereum_block(&self) -> u64 {
+        self.started_at.elapsed().as_secs() / ETHEREUM_BLOCK_TIME.as_secs()
+    }
+
+    /// Last finalized slot a checkpoint is available for.
+    pub fn checkpoint(&self) -> EthereumSlotNumber {
+        EthereumSlotNumber(self.ethereum_block().saturating_sub(CHECKPOINT_LAG))
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.started_at.elapsed().as_secs()
+    }
+
+    pub fn is_rpc_down(&self, chain: Chain) -> bool {
+        match chain {
+            Chain::Gear => self.gear().rpc_down,
+            Chain::Ethereum => self.ethereum().rpc_down,
+        }
+    }
+
+    pub fn set_rpc_down(&self, chain: Chain, down: bool) {
+        match chain {
+            Chain::Gear => self.gear().rpc_down = down,
+            Chain::Ethereum => self.ethereum().rpc_down = down,
+        }
+    }
+
+    /// Waits until RPC of the chain is reachable, polling it once per its block.
+    pub async fn wait_rpc(&self, chain: Chain) {
+        let block_time = match chain {
+            Chain::Gear => GEAR_BLOCK_TIME,
+            Chain::Ethereum => ETHEREUM_BLOCK_TIME,
+        };
+
+        while self.is_rpc_down(chain) {
+            time::sleep(block_time).await;
+        }
+    }
+
+    /// Queues a message on Gear in the current block.
+    pub fn queue_message(&self, nonce: u64) -> MessageInBlock {
+        let block = self.gear_block();
+        let mut nonce_be = [0; 32];
+        nonce_be[24..].copy_from_slice(&nonce.to_be_bytes());
+
+        MessageInBlock {
+            message: Message {
+                nonce_be,
+                source: [1; 32],
+                destination: [2; 20],
+                payload: nonce.to_be_bytes().to_vec(),
+            },
+            block,
+            block_hash: block_hash(block),
+            authority_set_id: AuthoritySetId(self.gear().authority_set_id),
+        }
+    }
+
+    /// Switches Gear to the next authority set. The last block of the previous set is
+    /// remembered so the merkle root for it is relayed as well.
+    pub fn change_authority_set(&self) {
+        let block = self.gear_block();
+        let mut gear = self.gear();
+        let authority_set_id = AuthoritySetId(gear.authority_set_id);
+        gear.unrelayed_sets.push((authority_set_id, block));
+        gear.authority_set_id += 1;
+    }
+
+    /// Merkle roots the merkle root relayer has to submit now: for the last blocks of
+    /// the finished authority sets and for the current block.
+    pub fn take_merkle_roots(&self) -> Vec<RelayedMerkleRoot> {
+        let block = self.gear_block();
+        let timestamp = self.timestamp();
+        let mut gear = self.gear();
+        let current = (AuthoritySetId(gear.authority_set_id), block);
+
+        gear.unrelayed_sets
+            .drain(..)
+            .chain([current])
+            .map(|(authority_set_id, block)| RelayedMerkleRoot {
+                block,
+                block_hash: block_hash(block),
+                timestamp,
+                authority_set_id,
+                merkle_root: merkle_root(block),
+            })
+            .collect()
+    }
+
+    pub fn submit_merkle_root(&self, merkle_root: RelayedMerkleRoot) {
+        self.ethereum().merkle_roots.push(merkle_root);
+    }
+
+    /// Sends the transaction delivering the message with `nonce`. It's included into the
+    /// next block.
+    pub fn send_transaction(&self, nonce: [u8; 32]) -> FixedBytes<32> {
+        let block = self.ethereum_block() + 1;
+        let mut ethereum = self.ethereum();
+        ethereum.sent += 1;
+
+        let tx_hash = FixedBytes::from(H256::from_low_u64_be(ethereum.sent).0);
+        ethereum.transactions.insert(tx_hash, (nonce, block));
+
+        tx_hash
+    }
+
+    /// Block the transaction is included at, if it wasn't dropped by a reorg.
+    pub fn transaction_block(&self, tx_hash: &FixedBytes<32>) -> Option<u64> {
+        self.ethereum()
+            .transactions
+            .get(tx_hash)
+            .map(|(_, block)| *block)
+    }
+
+    /// Sends the merkle root transaction. It's included into the next block unless the
+    /// nonce is taken by another transaction.
+    pub fn send_submission(&self, tx_hash: FixedBytes<32>, submission: Submission) -> bool {
+        let block = self.ethereum_block() + 1;
+        let mut ethereum = self.ethereum();
+        if ethereum
+            .submissions
+            .values()
+            .any(|(sent, _)| sent.nonce == submission.nonce)
+        {
+            return false;
+        }
+
+        ethereum.submissions.insert(tx_hash, (submission, block));
+        true
+    }
+
+    /// Block the merkle root transaction is included at, if it's included by now and
+    /// wasn't dropped by a reorg.
+    pub fn submission_block(&self, tx_hash: &FixedBytes<32>) -> Option<u64> {
+        let head = self.ethereum_block();
+        self.ethereum()
+            .submissions
+            .get(tx_hash)
+            .map(|(_, block)| *block)
+            .filter(|block| *block <= head)
+    }
+
+    /// Merkle root transactions included by now.
+    pub fn included_submissions(&self) -> Vec<Submission> {
+        let head = self.ethereum_block();
+        self.ethereum()
+            .submissions
+            .values()
+            .filter(|(_, block)| *block <= head)
+            .map(|(submission, _)| *submission)
+            .collect()
+    }
+
+    /// Nonce of the next transaction of the submitter fee payer.
+    pub fn fee_payer_nonce(&self) -> u64 {
+        self.included_submissions()
+            .iter()
+            .map(|submission| submission.nonce + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn is_processed(&self, nonce: &[u8; 32]) -> bool {
+        let block = self.ethereum_block();
+        self.ethereum()
+            .transactions
+            .values()
+            .any(|(processed, included_at)| processed == nonce && *included_at <= block)
+    }
+
+    /// Replaces the last `depth` Ethereum blocks with empty ones. Transactions included
+    /// into them are dropped.
+    pub fn reorg(&self, depth: u64) {
+        let head = self.ethereum_block();
+        let kept = head.saturating_sub(depth);
+        let mut ethereum = self.ethereum();
+        ethereum
+            .transactions
+            .retain(|_, (_, block)| *block <= kept || *block > head);
+        ethereum
+            .submissions
+            .retain(|_, (_, block)| *block <= kept || *block > head);
+    }
+}
+
+pub fn block_hash(block: GearBlockNumber) -> H256 {
+    H256::from_low_u64_be(block.0 as u64 + 1)
+}
+
+pub fn merkle_root(block: GearBlockNumber) -> H256 {
+    H256::from_low_u64_le(block.0 as u64 + 1)
+}
@@ -0,0 +1,261 @@
+use super::{
+    chains::{Network, ETHEREUM_BLOCK_TIME},
+    run_until, Chain, Fault, MockProver, Script,
+};
+use crate::relayer::eth_to_gear::TRANSACTIONS;
+use alloy::primitives::FixedBytes;
+use eth_events_electra_client::EthToVaraEvent;
+use primitive_types::H256;
+use relayer::message_relayer::{
+    common::{
+        dead_letter::FailureClass,
+        web_request::{TransactionLookup, TransactionState},
+        EthereumSlotNumber, TxHashWithSlot,
+    },
+    eth_to_gear::{
+        message_sender::{self, MessageSenderIo, MessageStatus},
+        proof_composer::{self, ProofComposerIo},
+        storage::NoStorage,
+        tx_manager::TransactionManager,
+    },
+};
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    time,
+};
+
+/// Proof the mock prover composes for every transaction.
+static EVENT: LazyLock<EthToVaraEvent> = LazyLock::new(|| {
+    TRANSACTIONS
+        .values()
+        .next()
+        .expect("Test transactions are loaded")
+        .event()
+});
+
+/// Ethereum to Gear transaction manager running against the fake chains.
+struct EthToGear {
+    network: Arc<Network>,
+    tx_manager: Arc<TransactionManager>,
+    paid_events: UnboundedSender<TxHashWithSlot>,
+}
+
+impl EthToGear {
+    fn spawn(network: Arc<Network>, prover: MockProver) -> Self {
+        let tx_manager = Arc::new(TransactionManager::new(Arc::new(NoStorage::new())));
+
+        let (requests_tx, requests_rx) = unbounded_channel();
+        let (responses_tx, responses_rx) = unbounded_channel();
+        tokio::spawn(compose_proofs(
+            network.clone(),
+            prover,
+            requests_rx,
+            responses_tx,
+        ));
+        let proof_composer = ProofComposerIo::new(requests_tx, responses_rx);
+
+        let (requests_tx, requests_rx) = unbounded_channel();
+        let (responses_tx, responses_rx) = unbounded_channel();
+        tokio::spawn(submit_receipts(network.clone(), requests_rx, responses_tx));
+        let message_sender = MessageSenderIo::new(requests_tx, responses_rx);
+
+        let (paid_events, paid_events_rx) = unbounded_channel();
+        let runner = tx_manager.clone();
+        tokio::spawn(async move {
+            runner
+                .run(paid_events_rx, proof_composer, message_sender)
+                .await
+        });
+
+        Self {
+            network,
+            tx_manager,
+            paid_events,
+        }
+    }
+
+    /// Pays for the bridging request sent in transaction `id` at the current slot.
+    fn pay(&self, id: u64) {
+        let tx = TxHashWithSlot {
+            slot_number: EthereumSlotNumber(self.network.ethereum_block()),
+            tx_hash: tx_hash(id),
+        };
+        self.paid_events
+            .send(tx)
+            .expect("Transaction manager is running");
+    }
+
+    async fn state(&self, id: u64) -> TransactionState {
+        self.tx_manager
+            .find_transaction(&TransactionLookup::TxHash(H256::from(tx_hash(id).0)))
+            .await
+            .expect("Transaction is paid")
+    }
+
+    async fn is_completed(&self, id: u64) -> bool {
+        self.state(id).await.completed
+    }
+}
+
+fn tx_hash(id: u64) -> FixedBytes<32> {
+    FixedBytes::from(H256::from_low_u64_be(id).0)
+}
+
+/// Composes proofs once the checkpoint for the slot of the transaction is available, as
+/// the proof composer does. Requests which arrive while the beacon node is unreachable are
+/// lost.
+async fn compose_proofs(
+    network: Arc<Network>,
+    prover: MockProver,
+    mut requests: UnboundedReceiver<proof_composer::Request>,
+    responses: UnboundedSender<proof_composer::Response>,
+) {
+    let mut waiting: Vec<proof_composer::Request> = vec![];
+    let mut interval = time::interval(ETHEREUM_BLOCK_TIME);
+    loop {
+        tokio::select! {
+            request = requests.recv() => {
+                let Some(request) = request else {
+                    return;
+                };
+
+                if network.is_rpc_down(Chain::Ethereum) {
+                    log::warn!("Dropping proof request for transaction {}", request.tx_uuid);
+                    continue;
+                }
+
+                waiting.retain(|waiting| waiting.tx_uuid != request.tx_uuid);
+                waiting.push(request);
+            }
+
+            _ = interval.tick() => {
+                let checkpoint = network.checkpoint();
+                let (ready, rest) = waiting
+                    .into_iter()
+                    .partition(|request| request.tx.slot_number <= checkpoint);
+                waiting = rest;
+
+                for request in ready {
+                    prover.prove().await;
+
+                    let response = proof_composer::Response {
+                        payload: EVENT.clone(),
+                        tx_uuid: request.tx_uuid,
+                    };
+                    if responses.send(response).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn submit_receipts(
+    network: Arc<Network>,
+    mut requests: UnboundedReceiver<message_sender::Request>,
+    responses: UnboundedSender<message_sender::Response>,
+) {
+    while let Some(request) = requests.recv().await {
+        assert_eq!(request.payload, *EVENT);
+
+        let status = if network.is_rpc_down(Chain::Gear) {
+            MessageStatus::Failure(
+                "connection reset by peer".to_string(),
+                FailureClass::Transient,
+            )
+        } else {
+            assert!(
+                network.gear().receipts.insert(request.tx_hash),
+                "Receipt of {} is submitted twice",
+                request.tx_hash
+            );
+            MessageStatus::Success
+        };
+
+        let response = message_sender::Response {
+            tx_uuid: request.tx_uuid,
+            status,
+        };
+        if responses.send(response).is_err() {
+            return;
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn relays_transactions_after_checkpoint() {
+    let network = Arc::new(Network::new(1));
+    let relayer = EthToGear::spawn(network.clone(), MockProver::default());
+
+    relayer.pay(1);
+    time::sleep(Duration::from_secs(60)).await;
+    relayer.pay(2);
+
+    // Checkpoint for the slot of the first transaction is available at 12:48.
+    assert!(!run_until(Duration::from_secs(12 * 60), || relayer.is_completed(1)).await);
+    assert_eq!(relayer.state(1).await.status, "compose_proof");
+
+    assert!(
+        run_until(Duration::from_secs(3 * 60), || async {
+            relayer.is_completed(1).await && relayer.is_completed(2).await
+        })
+        .await
+    );
+    assert_eq!(network.gear().receipts.len(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn resends_lost_proof_requests() {
+    let network = Arc::new(Network::new(1));
+    Script::new()
+        .at(Duration::ZERO, Fault::RpcDrop(Chain::Ethereum))
+        .at(Duration::from_secs(60), Fault::RpcRestore(Chain::Ethereum))
+        .spawn(network.clone());
+    let relayer = EthToGear::spawn(network.clone(), MockProver::default());
+
+    time::sleep(Duration::from_secs(1)).await;
+    relayer.pay(1);
+
+    // Without the drop it completes by 13:30. The lost request is sent again only once
+    // 15 minutes pass.
+    assert!(!run_until(Duration::from_secs(14 * 60), || relayer.is_completed(1)).await);
+    assert!(run_until(Duration::from_secs(3 * 60), || relayer.is_completed(1)).await);
+    assert_eq!(network.gear().receipts.len(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn retries_receipts_failed_while_gear_rpc_is_down() {
+    let network = Arc::new(Network::new(1));
+    Script::new()
+        .at(Duration::from_secs(10 * 60), Fault::RpcDrop(Chain::Gear))
+        .at(Duration::from_secs(15 * 60), Fault::RpcRestore(Chain::Gear))
+        .spawn(network.clone());
+    let relayer = EthToGear::spawn(network.clone(), MockProver::default());
+    relayer.pay(1);
+
+    assert!(
+        run_until(Duration::from_secs(14 * 60), || async {
+            relayer.state(1).await.failure.is_some()
+        })
+        .await
+    );
+    let state = relayer.state(1).await;
+    assert_eq!(state.status, "submit_message");
+    assert_eq!(state.failure.as_deref(), Some("connection reset by peer"));
+
+    time::sleep(Duration::from_secs(2 * 60)).await;
+    relayer
+        .tx_manager
+        .retry_transaction(state.uuid, Some("submit_message"))
+        .await
+        .expect("Transaction is in the dead-letter queue");
+
+    assert!(run_until(Duration::from_secs(60), || relayer.is_completed(1)).await);
+    assert_eq!(network.gear().receipts.len(), 1);
+    assert!(relayer.tx_manager.failed.read().await.is_empty());
+}
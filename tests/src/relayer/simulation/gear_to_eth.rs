@@ -0,0 +1,455 @@
+use super::{
+    chains::{Network, ETHEREUM_BLOCK_TIME},
+    run_until, Chain, Fault, MockProver, Script,
+};
+use alloy::providers::{PendingTransactionError, WatchTxError};
+use gear_rpc_client::dto::MerkleProof;
+use primitive_types::U256;
+use relayer::message_relayer::{
+    common::{
+        ethereum::{
+            accumulator::{
+                self,
+                utils::{Added, MerkleRoots, Messages},
+                AccumulatorIo,
+            },
+            message_sender::{self, MessageSenderIo},
+            status_fetcher::{self, StatusFetcherIo},
+        },
+        gear::merkle_proof_fetcher::{self, MerkleRootFetcherIo},
+        lifecycle::LifecycleEventKind,
+        web_request::{TransactionLookup, TransactionState},
+        MessageInBlock, RelayedMerkleRoot,
+    },
+    gear_to_eth::{storage::NoStorage, tx_manager::TransactionManager},
+};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        RwLock,
+    },
+    time,
+};
+
+/// How often the merkle root relayer submits a merkle root for the current Gear block.
+const MERKLE_ROOT_PERIOD: Duration = Duration::from_secs(60);
+const CONFIRMATIONS: u64 = 3;
+
+/// Gear to Ethereum transaction manager running against the fake chains.
+struct GearToEth {
+    network: Arc<Network>,
+    tx_manager: Arc<TransactionManager>,
+    messages: UnboundedSender<MessageInBlock>,
+}
+
+impl GearToEth {
+    fn spawn(network: Arc<Network>, prover: MockProver) -> Self {
+        let tx_manager = Arc::new(TransactionManager::new(Arc::new(NoStorage::new())));
+
+        let (roots_tx, roots_rx) = unbounded_channel();
+        tokio::spawn(relay_merkle_roots(network.clone(), prover, roots_tx));
+
+        let (requests_tx, requests_rx) = unbounded_channel();
+        let (responses_tx, responses_rx) = unbounded_channel();
+        tokio::spawn(accumulate(
+            tx_manager.merkle_roots.clone(),
+            roots_rx,
+            requests_rx,
+            responses_tx,
+        ));
+        let accumulator = AccumulatorIo::new(responses_rx, requests_tx);
+
+        let (requests_tx, requests_rx) = unbounded_channel();
+        let (responses_tx, responses_rx) = unbounded_channel();
+        tokio::spawn(fetch_proofs(network.clone(), requests_rx, responses_tx));
+        let proof_fetcher = MerkleRootFetcherIo::new(requests_tx, responses_rx);
+
+        let (requests_tx, requests_rx) = unbounded_channel();
+        let (responses_tx, responses_rx) = unbounded_channel();
+        tokio::spawn(send_messages(network.clone(), requests_rx, responses_tx));
+        let message_sender = MessageSenderIo::new(requests_tx, responses_rx);
+
+        let (requests_tx, requests_rx) = unbounded_channel();
+        let (responses_tx, responses_rx) = unbounded_channel();
+        tokio::spawn(fetch_statuses(network.clone(), requests_rx, responses_tx));
+        let status_fetcher = StatusFetcherIo::new(requests_tx, responses_rx);
+
+        let (messages, queued_messages) = unbounded_channel();
+        let runner = tx_manager.clone();
+        tokio::spawn(async move {
+            runner
+                .run(
+                    accumulator,
+                    queued_messages,
+                    proof_fetcher,
+                    message_sender,
+                    status_fetcher,
+                )
+                .await
+        });
+
+        Self {
+            network,
+            tx_manager,
+            messages,
+        }
+    }
+
+    fn queue_message(&self, nonce: u64) -> MessageInBlock {
+        let message = self.network.queue_message(nonce);
+        self.messages
+            .send(message.clone())
+            .expect("Transaction manager is running");
+
+        message
+    }
+
+    async fn state(&self, nonce: u64) -> TransactionState {
+        self.tx_manager
+            .find_transaction(&TransactionLookup::Nonce(U256::from(nonce)))
+            .await
+            .expect("Message is queued")
+    }
+
+    async fn is_completed(&self, nonce: u64) -> bool {
+        self.state(nonce).await.completed
+    }
+
+    /// Number of Ethereum transactions delivering the message.
+    fn deliveries(&self, message: &MessageInBlock) -> usize {
+        self.network
+            .ethereum()
+            .transactions
+            .values()
+            .filter(|(nonce, _)| *nonce == message.message.nonce_be)
+            .count()
+    }
+}
+
+/// Stands in for the merkle root relayer: periodically proves the current Gear block and
+/// the last blocks of finished authority sets and submits their merkle roots to Ethereum.
+pub async fn relay_merkle_roots(
+    network: Arc<Network>,
+    prover: MockProver,
+    roots: UnboundedSender<RelayedMerkleRoot>,
+) {
+    let mut interval = time::interval(MERKLE_ROOT_PERIOD);
+    loop {
+        interval.tick().await;
+        if network.is_rpc_down(Chain::Gear) || network.is_rpc_down(Chain::Ethereum) {
+            continue;
+        }
+
+        let merkle_roots = network.take_merkle_roots();
+        prover.prove().await;
+
+        for merkle_root in merkle_roots {
+            network.submit_merkle_root(merkle_root);
+            if roots.send(merkle_root).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Accumulator matching messages with relayed merkle roots without the governance delays.
+async fn accumulate(
+    merkle_roots: Arc<RwLock<MerkleRoots>>,
+    mut relayed: UnboundedReceiver<RelayedMerkleRoot>,
+    mut requests: UnboundedReceiver<accumulator::Request>,
+    responses: UnboundedSender<accumulator::Response>,
+) {
+    let success = |request: accumulator::Request, merkle_root| accumulator::Response::Success {
+        authority_set_id: request.authority_set_id,
+        block: request.block,
+        tx_uuid: request.tx_uuid,
+        merkle_root,
+    };
+
+    let mut messages = Messages::new(1_000);
+    loop {
+        let sent = tokio::select! {
+            request = requests.recv() => {
+                let Some(request) = request else {
+                    return;
+                };
+
+                let merkle_root = merkle_roots
+                    .read()
+                    .await
+                    .find(request.authority_set_id, request.block, u64::MAX, 0)
+                    .copied();
+                match merkle_root {
+                    Some(merkle_root) => responses.send(success(request, merkle_root)).is_ok(),
+                    None if messages.add(request.clone()).is_none() => {
+                        responses.send(accumulator::Response::Overflowed(request)).is_ok()
+                    }
+                    None => true,
+                }
+            }
+
+            merkle_root = relayed.recv() => {
+                let Some(merkle_root) = merkle_root else {
+                    return;
+                };
+
+                let stuck = match merkle_roots.write().await.add(merkle_root) {
+                    Ok(Added::Removed(removed)) => messages
+                        .drain_all(&removed)
+                        .map(|request| accumulator::Response::Stuck {
+                            authority_set_id: request.authority_set_id,
+                            block: request.block,
+                            tx_uuid: request.tx_uuid,
+                            merkle_root: removed,
+                        })
+                        .collect(),
+                    Ok(_) => vec![],
+                    Err(_) => continue,
+                };
+
+                stuck
+                    .into_iter()
+                    .chain(
+                        messages
+                            .drain(&merkle_root, u64::MAX, |_| 0)
+                            .map(|request| success(request, merkle_root)),
+                    )
+                    .all(|response| responses.send(response).is_ok())
+            }
+        };
+
+        if !sent {
+            return;
+        }
+    }
+}
+
+async fn fetch_proofs(
+    network: Arc<Network>,
+    mut requests: UnboundedReceiver<merkle_proof_fetcher::Request>,
+    responses: UnboundedSender<merkle_proof_fetcher::Response>,
+) {
+    while let Some(request) = requests.recv().await {
+        network.wait_rpc(Chain::Gear).await;
+
+        let proof = MerkleProof {
+            root: request.merkle_root.merkle_root.0,
+            proof: vec![request.message_hash],
+            num_leaves: 1,
+            leaf_index: 0,
+        };
+        let response = merkle_proof_fetcher::Response {
+            proof,
+            merkle_root: request.merkle_root,
+            tx_uuid: request.tx_uuid,
+        };
+        if responses.send(response).is_err() {
+            return;
+        }
+    }
+}
+
+async fn send_messages(
+    network: Arc<Network>,
+    mut requests: UnboundedReceiver<message_sender::Request>,
+    responses: UnboundedSender<message_sender::Response>,
+) {
+    while let Some(request) = requests.recv().await {
+        network.wait_rpc(Chain::Ethereum).await;
+
+        assert!(
+            network
+                .ethereum()
+                .merkle_roots
+                .contains(&request.relayed_root),
+            "Message is relayed with merkle root {:?} unknown to Ethereum",
+            request.relayed_root
+        );
+        assert_eq!(request.proof.root, request.relayed_root.merkle_root.0);
+
+        let nonce = request.message.nonce_be;
+        let response = if network.is_processed(&nonce) {
+            message_sender::Response::MessageAlreadyProcessed(request.tx_uuid)
+        } else {
+            let tx_hash = network.send_transaction(nonce);
+            message_sender::Response::ProcessingStarted(tx_hash, request.tx_uuid)
+        };
+
+        if responses.send(response).is_err() {
+            return;
+        }
+    }
+}
+
+/// Watches every transaction in a separate task, as the status fetcher does. While RPC is
+/// down the transaction keeps being watched.
+async fn fetch_statuses(
+    network: Arc<Network>,
+    mut requests: UnboundedReceiver<status_fetcher::Request>,
+    responses: UnboundedSender<status_fetcher::Response>,
+) {
+    while let Some(request) = requests.recv().await {
+        let network = network.clone();
+        let responses = responses.clone();
+
+        tokio::spawn(async move {
+            loop {
+                time::sleep(ETHEREUM_BLOCK_TIME).await;
+                if network.is_rpc_down(Chain::Ethereum) {
+                    continue;
+                }
+
+                let response = match network.transaction_block(&request.tx_hash) {
+                    None => status_fetcher::Response::Failed(
+                        request.tx_uuid,
+                        PendingTransactionError::TxWatcher(WatchTxError::Timeout),
+                    ),
+                    Some(block) if block + CONFIRMATIONS <= network.ethereum_block() => {
+                        status_fetcher::Response::Success(request.tx_uuid, request.tx_hash)
+                    }
+                    Some(_) => continue,
+                };
+
+                let _ = responses.send(response);
+                return;
+            }
+        });
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn relays_messages() {
+    let network = Arc::new(Network::new(1));
+    let relayer = GearToEth::spawn(network, MockProver::default());
+
+    let mut messages = vec![];
+    for nonce in 1..=3 {
+        messages.push(relayer.queue_message(nonce));
+        time::sleep(Duration::from_secs(45)).await;
+    }
+
+    assert!(
+        run_until(Duration::from_secs(10 * 60), || async {
+            for nonce in 1..=3 {
+                if !relayer.is_completed(nonce).await {
+                    return false;
+                }
+            }
+
+            true
+        })
+        .await
+    );
+
+    for message in &messages {
+        assert_eq!(relayer.deliveries(message), 1);
+    }
+    assert!(relayer.tx_manager.failed.read().await.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn relays_messages_across_authority_set_change() {
+    let network = Arc::new(Network::new(1));
+    Script::new()
+        .at(Duration::from_secs(30), Fault::AuthoritySetChange)
+        .spawn(network.clone());
+    let relayer = GearToEth::spawn(network, MockProver::default());
+    let mut events = relayer.tx_manager.subscribe();
+
+    // Queued after the first merkle root is proven, so it's covered only by the merkle
+    // root for the last block of the first authority set.
+    time::sleep(Duration::from_secs(15)).await;
+    let old = relayer.queue_message(1);
+    time::sleep(Duration::from_secs(30)).await;
+    let new = relayer.queue_message(2);
+    assert_eq!(old.authority_set_id.0, 1);
+    assert_eq!(new.authority_set_id.0, 2);
+
+    assert!(
+        run_until(Duration::from_secs(10 * 60), || async {
+            relayer.is_completed(1).await && relayer.is_completed(2).await
+        })
+        .await
+    );
+
+    let mut merkle_root_blocks = vec![];
+    while let Ok(event) = events.try_recv() {
+        if let LifecycleEventKind::MerkleRootIncluded {
+            merkle_root_block, ..
+        } = event.kind
+        {
+            merkle_root_blocks.push((event.nonce, merkle_root_block));
+        }
+    }
+
+    // Authority set changes at block #10, the second merkle root is proven at block #20.
+    assert_eq!(
+        merkle_root_blocks,
+        vec![(Some(U256::from(1)), 10), (Some(U256::from(2)), 20)]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn retries_message_dropped_by_reorg() {
+    let network = Arc::new(Network::new(1));
+    // The message is sent at 0:30 and included into block #3.
+    Script::new()
+        .at(Duration::from_secs(50), Fault::Reorg { depth: 2 })
+        .spawn(network.clone());
+    let relayer = GearToEth::spawn(network, MockProver::default());
+    let message = relayer.queue_message(1);
+
+    assert!(
+        run_until(Duration::from_secs(5 * 60), || async {
+            relayer.state(1).await.failure.is_some()
+        })
+        .await
+    );
+    let state = relayer.state(1).await;
+    assert_eq!(state.status, "wait_confirmations");
+    assert_eq!(relayer.deliveries(&message), 0);
+
+    // The proof isn't kept once the message is sent, so it's relayed from the start.
+    relayer
+        .tx_manager
+        .retry_transaction(state.uuid, Some("send_message"))
+        .await
+        .expect_err("Proof of the sent message is dropped");
+    relayer
+        .tx_manager
+        .retry_transaction(state.uuid, Some("wait_for_merkle_root"))
+        .await
+        .expect("Message is in the dead-letter queue");
+
+    assert!(run_until(Duration::from_secs(5 * 60), || relayer.is_completed(1)).await);
+    assert_eq!(relayer.deliveries(&message), 1);
+    assert!(relayer.tx_manager.failed.read().await.is_empty());
+}
+
+async fn waits_for_rpc(chain: Chain) {
+    let network = Arc::new(Network::new(1));
+    Script::new()
+        .at(Duration::from_secs(10), Fault::RpcDrop(chain))
+        .at(Duration::from_secs(5 * 60), Fault::RpcRestore(chain))
+        .spawn(network.clone());
+    let relayer = GearToEth::spawn(network, MockProver::default());
+    let message = relayer.queue_message(1);
+
+    assert!(!run_until(Duration::from_secs(4 * 60), || relayer.is_completed(1)).await);
+    assert_eq!(relayer.deliveries(&message), 0);
+
+    assert!(run_until(Duration::from_secs(3 * 60), || relayer.is_completed(1)).await);
+    assert_eq!(relayer.deliveries(&message), 1);
+    assert!(relayer.tx_manager.failed.read().await.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_for_gear_rpc() {
+    waits_for_rpc(Chain::Gear).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_for_ethereum_rpc() {
+    waits_for_rpc(Chain::Ethereum).await;
+}
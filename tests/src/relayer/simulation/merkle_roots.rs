@@ -0,0 +1,319 @@
+use super::{
+    chains::{Network, Submission},
+    Chain, Fault, MockProver, Script,
+};
+use alloy::{
+    network::TxSignerSync,
+    primitives::{Address, Bytes, TxKind, U256},
+    rpc::types::TransactionReceipt,
+    signers::local::PrivateKeySigner,
+    transports::TransportErrorKind,
+};
+use alloy_consensus::{
+    Eip658Value, Receipt, ReceiptEnvelope, ReceiptWithBloom, SignableTransaction, TxEip1559,
+    TxEnvelope,
+};
+use ethereum_client::{FeeCaps, SignedTransaction, TxHash};
+use primitive_types::H256;
+use relayer::{
+    merkle_roots::{
+        fee_bumping::FeeBumpConfig,
+        storage::MerkleRootStorage,
+        submitter::{MerkleRootSubmitter, ResponseStatus, SubmissionApi, SubmitterIo},
+        CriticalThreshold, LiveOptions, SpikeConfig,
+    },
+    proof_storage::FileSystemProofStorage,
+    prover_interface::FinalProof,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tempfile::TempDir;
+use tokio::{sync::watch, time};
+
+const KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const CONFIRMATIONS: u64 = 3;
+const GAS_USED: u64 = 300_000;
+const ESTIMATE: FeeCaps = FeeCaps {
+    max_fee_per_gas: 20_000_000_000,
+    max_priority_fee_per_gas: 1_000_000_000,
+};
+
+/// `EthApi` of the merkle root submitter backed by the fake Ethereum. Every call fails
+/// while Ethereum RPC is down, reconnecting included.
+#[derive(Clone)]
+struct SimulatedEthApi {
+    network: Arc<Network>,
+    signer: Arc<PrivateKeySigner>,
+    /// Signed transactions which might be broadcast.
+    signed: Arc<Mutex<HashMap<TxHash, Submission>>>,
+    next_nonce: Arc<AtomicU64>,
+}
+
+impl SimulatedEthApi {
+    fn new(network: Arc<Network>) -> Self {
+        Self {
+            network,
+            signer: Arc::new(KEY.parse().expect("Key is valid")),
+            signed: Default::default(),
+            next_nonce: Default::default(),
+        }
+    }
+
+    fn check_rpc(&self) -> Result<(), ethereum_client::Error> {
+        if self.network.is_rpc_down(Chain::Ethereum) {
+            return Err(ethereum_client::Error::ErrorInHTTPTransport(
+                TransportErrorKind::backend_gone(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl SubmissionApi for SimulatedEthApi {
+    async fn get_approx_balance(&self) -> Result<f64, ethereum_client::Error> {
+        self.check_rpc()?;
+        Ok(1.0)
+    }
+
+    async fn estimate_fee_caps(&self) -> Result<FeeCaps, ethereum_client::Error> {
+        self.check_rpc()?;
+        Ok(ESTIMATE)
+    }
+
+    async fn allocate_nonce(&self) -> Result<u64, ethereum_client::Error> {
+        self.check_rpc()?;
+        Ok(self.next_nonce.fetch_add(1, Ordering::SeqCst))
+    }
+
+    async fn sign_merkle_root_with_fees(
+        &self,
+        block_number: u32,
+        merkle_root: [u8; 32],
+        _proof: Vec<u8>,
+        nonce: u64,
+        fees: FeeCaps,
+    ) -> Result<SignedTransaction, ethereum_client::Error> {
+        let mut tx = TxEip1559 {
+            chain_id: 1,
+            nonce,
+            gas_limit: 500_000,
+            max_fee_per_gas: fees.max_fee_per_gas,
+            max_priority_fee_per_gas: fees.max_priority_fee_per_gas,
+            to: TxKind::Call(Address::ZERO),
+            value: U256::ZERO,
+            access_list: Default::default(),
+            input: Bytes::copy_from_slice(&merkle_root),
+        };
+        let signature = self
+            .signer
+            .sign_transaction_sync(&mut tx)
+            .map_err(|err| ethereum_client::Error::SigningTransaction(err.to_string()))?;
+        let tx = SignedTransaction::from(TxEnvelope::from(tx.into_signed(signature)));
+        self.signed
+            .lock()
+            .expect("Signed transactions are poisoned")
+            .insert(
+                tx.hash(),
+                Submission {
+                    nonce,
+                    block_number,
+                    merkle_root,
+                    fees,
+                },
+            );
+
+        Ok(tx)
+    }
+
+    async fn send_signed(&self, tx: &SignedTransaction) -> Result<TxHash, ethereum_client::Error> {
+        self.check_rpc()?;
+        let submission = self
+            .signed
+            .lock()
+            .expect("Signed transactions are poisoned")[&tx.hash()];
+        if !self.network.send_submission(tx.hash(), submission) {
+            return Err(ethereum_client::Error::ErrorInHTTPTransport(
+                TransportErrorKind::custom_str("nonce too low"),
+            ));
+        }
+
+        Ok(tx.hash())
+    }
+
+    async fn nonce_sent(&self, _nonce: u64) {}
+
+    async fn nonce_maybe_sent(&self, _nonce: u64) {}
+
+    async fn release_nonce(&self, _nonce: u64) {}
+
+    async fn get_transaction_receipt(
+        &self,
+        tx_hash: TxHash,
+    ) -> Result<Option<TransactionReceipt>, ethereum_client::Error> {
+        self.check_rpc()?;
+        Ok(self
+            .network
+            .submission_block(&tx_hash)
+            .map(|block| receipt(tx_hash, block)))
+    }
+
+    async fn block_number(&self) -> Result<u64, ethereum_client::Error> {
+        self.check_rpc()?;
+        Ok(self.network.ethereum_block())
+    }
+
+    async fn latest_nonce(&self) -> Result<u64, ethereum_client::Error> {
+        self.check_rpc()?;
+        Ok(self.network.fee_payer_nonce())
+    }
+
+    async fn read_finalized_merkle_root(
+        &self,
+        block_number: u32,
+    ) -> Result<Option<[u8; 32]>, ethereum_client::Error> {
+        self.check_rpc()?;
+        Ok(self
+            .network
+            .included_submissions()
+            .into_iter()
+            .find(|submission| submission.block_number == block_number)
+            .map(|submission| submission.merkle_root))
+    }
+
+    async fn reconnect(&self) -> Result<Self, ethereum_client::Error> {
+        self.check_rpc()?;
+        Ok(self.clone())
+    }
+}
+
+fn receipt(tx_hash: TxHash, block: u64) -> TransactionReceipt {
+    TransactionReceipt {
+        inner: ReceiptEnvelope::Eip1559(ReceiptWithBloom {
+            receipt: Receipt {
+                status: Eip658Value::Eip658(true),
+                cumulative_gas_used: GAS_USED,
+                logs: vec![],
+            },
+            logs_bloom: Default::default(),
+        }),
+        transaction_hash: tx_hash,
+        transaction_index: Some(0),
+        block_hash: Some(H256::from_low_u64_be(block).0.into()),
+        block_number: Some(block),
+        gas_used: GAS_USED,
+        effective_gas_price: ESTIMATE.max_fee_per_gas,
+        blob_gas_used: None,
+        blob_gas_price: None,
+        from: Address::ZERO,
+        to: Some(Address::ZERO),
+        contract_address: None,
+    }
+}
+
+/// Merkle root submitter running against the fake Ethereum.
+struct Submitter {
+    io: SubmitterIo,
+    // Storage of the submitter is removed with it.
+    _dir: TempDir,
+}
+
+impl Submitter {
+    async fn spawn(network: Arc<Network>) -> Self {
+        let dir = tempfile::tempdir().expect("Temporary directory is created");
+        let proofs = FileSystemProofStorage::new(dir.path().join("proofs")).await;
+        let storage = MerkleRootStorage::new(Arc::new(proofs), dir.path().join("storage.json"));
+
+        let (_, live_options) = watch::channel(LiveOptions {
+            spike_config: SpikeConfig::default(),
+            critical_threshold: CriticalThreshold::AuthoritySetChange,
+            fee_bumping: FeeBumpConfig::default(),
+        });
+        let io = MerkleRootSubmitter::new(
+            SimulatedEthApi::new(network),
+            storage,
+            CONFIRMATIONS,
+            live_options,
+            "simulation".to_string(),
+            None,
+        )
+        .run();
+
+        Self { io, _dir: dir }
+    }
+
+    /// Proves the merkle root for `block` and submits it.
+    async fn submit(&self, prover: MockProver, block: u32) -> [u8; 32] {
+        prover.prove().await;
+
+        let merkle_root = H256::from_low_u64_le(block as u64 + 1);
+        let proof = FinalProof::mock(block, merkle_root.0);
+        assert!(self.io.submit_merkle_root(block, merkle_root, proof));
+
+        merkle_root.0
+    }
+
+    /// Waits until the submission is confirmed. Returns `false` if it isn't within
+    /// `timeout`.
+    async fn is_submitted(&mut self, timeout: Duration) -> bool {
+        let Ok(response) = time::timeout(timeout, self.io.recv()).await else {
+            return false;
+        };
+        let response = response.expect("Submitter is running");
+
+        match response.status {
+            ResponseStatus::Submitted => true,
+            ResponseStatus::Failed(err) => panic!("Merkle root submission failed: {err}"),
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn resubmits_merkle_root_dropped_by_reorg() {
+    let network = Arc::new(Network::new(1));
+    // The merkle root is submitted at 0:30 and included into block #3.
+    Script::new()
+        .at(Duration::from_secs(50), Fault::Reorg { depth: 2 })
+        .spawn(network.clone());
+    let mut submitter = Submitter::spawn(network.clone()).await;
+    let merkle_root = submitter.submit(MockProver::default(), 10).await;
+
+    // The transaction is replaced once it's stuck for 3 minutes.
+    assert!(!submitter.is_submitted(Duration::from_secs(3 * 60)).await);
+    assert!(network.included_submissions().is_empty());
+
+    assert!(submitter.is_submitted(Duration::from_secs(2 * 60)).await);
+    let included = network.included_submissions();
+    assert_eq!(included.len(), 1);
+    assert_eq!(included[0].nonce, 0);
+    assert_eq!(included[0].merkle_root, merkle_root);
+    assert!(included[0].fees.max_fee_per_gas > ESTIMATE.max_fee_per_gas);
+}
+
+#[tokio::test(start_paused = true)]
+async fn waits_for_ethereum_rpc_while_submission_is_pending() {
+    let network = Arc::new(Network::new(1));
+    // The merkle root is submitted at 0:30, RPC drops before it's confirmed.
+    Script::new()
+        .at(Duration::from_secs(40), Fault::RpcDrop(Chain::Ethereum))
+        .at(Duration::from_secs(160), Fault::RpcRestore(Chain::Ethereum))
+        .spawn(network.clone());
+    let mut submitter = Submitter::spawn(network.clone()).await;
+    let merkle_root = submitter.submit(MockProver::default(), 10).await;
+
+    assert!(!submitter.is_submitted(Duration::from_secs(2 * 60)).await);
+    assert!(submitter.is_submitted(Duration::from_secs(30)).await);
+
+    // The transaction isn't replaced as it's confirmed before it's considered stuck.
+    let included = network.included_submissions();
+    assert_eq!(included.len(), 1);
+    assert_eq!(included[0].merkle_root, merkle_root);
+    assert_eq!(included[0].fees, ESTIMATE);
+}
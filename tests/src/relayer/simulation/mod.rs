@@ -0,0 +1,114 @@
+//! Deterministic simulation of the token relayer transaction managers and the merkle root
+//! submitter.
+//!
+//! The `TransactionManager`s and the `MerkleRootSubmitter` are real. Gear and Ethereum are
+//! replaced by the in-memory [`Network`]: the services transaction managers talk to over
+//! channels are replaced by fakes backed by it and the submitter calls it through its
+//! `SubmissionApi`. Tests run on the paused tokio clock: the only source of time is the
+//! virtual clock, so a run depends only on the test and its [`Script`] of faults.
+//!
+//! Merkle root relayer, authority set sync and the prover aren't run: they call `GearApi`
+//! and the prover directly. Merkle roots are put on the fake Ethereum by
+//! [`gear_to_eth::relay_merkle_roots`] instead, with proving modelled by [`MockProver`] as
+//! a fixed delay.
+
+mod chains;
+mod eth_to_gear;
+mod gear_to_eth;
+mod merkle_roots;
+
+use chains::Network;
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::time;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chain {
+    Gear,
+    Ethereum,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    /// RPC of the chain stops responding.
+    RpcDrop(Chain),
+    RpcRestore(Chain),
+    /// Last `depth` Ethereum blocks are replaced with empty ones.
+    Reorg {
+        depth: u64,
+    },
+    /// Gear switches to the next authority set.
+    AuthoritySetChange,
+}
+
+/// Faults injected into the network at the given moments of virtual time since the start.
+#[derive(Default)]
+pub struct Script(Vec<(Duration, Fault)>);
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn at(mut self, at: Duration, fault: Fault) -> Self {
+        self.0.push((at, fault));
+        self
+    }
+
+    pub fn spawn(mut self, network: Arc<Network>) {
+        self.0.sort_by_key(|(at, _)| *at);
+        let started_at = time::Instant::now();
+
+        tokio::spawn(async move {
+            for (at, fault) in self.0 {
+                time::sleep_until(started_at + at).await;
+                log::info!("Injecting fault at {at:?}: {fault:?}");
+
+                match fault {
+                    Fault::RpcDrop(chain) => network.set_rpc_down(chain, true),
+                    Fault::RpcRestore(chain) => network.set_rpc_down(chain, false),
+                    Fault::Reorg { depth } => network.reorg(depth),
+                    Fault::AuthoritySetChange => network.change_authority_set(),
+                }
+            }
+        });
+    }
+}
+
+/// Prover which answers after a fixed delay instead of building proofs.
+#[derive(Clone, Copy)]
+pub struct MockProver {
+    pub latency: Duration,
+}
+
+impl Default for MockProver {
+    fn default() -> Self {
+        Self {
+            latency: Duration::from_secs(30),
+        }
+    }
+}
+
+impl MockProver {
+    pub async fn prove(&self) {
+        time::sleep(self.latency).await;
+    }
+}
+
+/// Advances the virtual clock a second at a time until `done` holds. Returns `false` if it
+/// doesn't hold within `timeout`.
+pub async fn run_until<F, Fut>(timeout: Duration, mut done: F) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let deadline = time::Instant::now() + timeout;
+    while time::Instant::now() < deadline {
+        if done().await {
+            return true;
+        }
+
+        time::sleep(Duration::from_secs(1)).await;
+    }
+
+    done().await
+}
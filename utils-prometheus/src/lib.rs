@@ -1,6 +1,9 @@
 use axum::{routing::get, Router};
 use prometheus::{core::Collector, Encoder, Registry, TextEncoder};
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, RwLock},
+};
 use tokio::net::TcpListener;
 
 pub struct MetricsBuilder {
@@ -11,6 +14,14 @@ pub struct Metrics {
     registries: Vec<Registry>,
 }
 
+/// Metrics served from one endpoint by several services. Unlike [`Metrics`], the set of
+/// registries can change while the endpoint is up, so a restarted service replaces the
+/// collectors of its previous run.
+#[derive(Clone, Default)]
+pub struct SharedMetrics {
+    registries: Arc<RwLock<BTreeMap<String, Vec<Registry>>>>,
+}
+
 pub trait MeteredService {
     fn get_sources(&self) -> impl IntoIterator<Item = Box<dyn Collector>>;
 }
@@ -207,6 +218,53 @@ impl Metrics {
     }
 }
 
+impl SharedMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves collectors of `metrics` under `key`, replacing the ones set before.
+    pub fn set(&self, key: impl Into<String>, metrics: MetricsBuilder) {
+        self.registries
+            .write()
+            .expect("metrics lock is poisoned")
+            .insert(key.into(), metrics.registries);
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.registries
+            .write()
+            .expect("metrics lock is poisoned")
+            .remove(key);
+    }
+
+    pub async fn run(&self, endpoint: String) {
+        let shared = self.clone();
+
+        let app = Router::new().route(
+            "/metrics",
+            get(move || Metrics::gather_metrics(shared.registries())),
+        );
+        let listener = TcpListener::bind(&endpoint)
+            .await
+            .expect("Failed to create TcpListener");
+
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+    }
+
+    fn registries(&self) -> Vec<Registry> {
+        self.registries
+            .read()
+            .expect("metrics lock is poisoned")
+            .values()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -256,4 +314,41 @@ mod tests {
         assert_eq!(values.get("a"), Some(&1));
         assert_eq!(values.get("b"), Some(&2));
     }
+
+    #[test]
+    fn shared_metrics_replace_registries_by_key() {
+        let first = TestMetrics::new();
+        first.value.set(1);
+        let second = TestMetrics::new();
+        second.value.set(2);
+        let restarted = TestMetrics::new();
+        restarted.value.set(3);
+
+        let metrics = SharedMetrics::new();
+        metrics.set(
+            "a",
+            MetricsBuilder::new().register_labeled_service(&first, [("service", "a")]),
+        );
+        metrics.set(
+            "b",
+            MetricsBuilder::new().register_labeled_service(&second, [("service", "b")]),
+        );
+        metrics.set(
+            "a",
+            MetricsBuilder::new().register_labeled_service(&restarted, [("service", "a")]),
+        );
+
+        let values = |metrics: &SharedMetrics| {
+            Metrics::gather_metric_families(&metrics.registries())
+                .iter()
+                .flat_map(|family| family.get_metric())
+                .map(|metric| metric.get_gauge().get_value() as i64)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(values(&metrics), vec![3, 2]);
+
+        metrics.remove("a");
+        assert_eq!(values(&metrics), vec![2]);
+    }
 }